@@ -1,6 +1,6 @@
 use crate::{
     router::Route,
-    state::{get_stored_server, logout, AppState},
+    state::{base_path, get_stored_server, logout, AppState},
 };
 use dioxus::prelude::*;
 use gloo_storage::{LocalStorage, Storage};
@@ -110,14 +110,19 @@ pub fn DashboardLayout() -> Element {
         Route::SettingsBrandingRoute => "Branding",
         Route::SettingsIntroRoute => "Intro",
         Route::SettingsRemuxdbRoute => "Remuxdb",
+        Route::SettingsDlnaRoute => "DLNA",
         Route::AccessUsersRoute => "Users",
         Route::AccessApiKeysRoute => "API Keys",
+        Route::AccessSsoRoute => "Single Sign-On",
+        Route::AccessLdapRoute => "LDAP",
         Route::TasksRoute => "Tasks",
         Route::DevicesRoute => "Devices",
         Route::ActivityRoute => "Activity",
         Route::NotFound { .. } => "",
     };
 
+    let base = base_path();
+
     rsx! {
         div { class: "layout",
             if *sidebar_open.read() {
@@ -198,6 +203,7 @@ pub fn DashboardLayout() -> Element {
                             | Route::SettingsBrandingRoute
                             | Route::SettingsIntroRoute
                             | Route::SettingsRemuxdbRoute
+                            | Route::SettingsDlnaRoute
                         ),
                         NavSubItem {
                             label: "General",
@@ -229,6 +235,11 @@ pub fn DashboardLayout() -> Element {
                             active: route == Route::SettingsRemuxdbRoute,
                             on_click: move |_| { navigator().push(Route::SettingsRemuxdbRoute); sidebar_open.set(false); },
                         }
+                        NavSubItem {
+                            label: "DLNA",
+                            active: route == Route::SettingsDlnaRoute,
+                            on_click: move |_| { navigator().push(Route::SettingsDlnaRoute); sidebar_open.set(false); },
+                        }
                         NavSubItem {
                             label: "Branding",
                             active: route == Route::SettingsBrandingRoute,
@@ -238,7 +249,7 @@ pub fn DashboardLayout() -> Element {
 
                     SidebarGroup {
                         label: "Access",
-                        active: matches!(route, Route::AccessUsersRoute | Route::AccessApiKeysRoute),
+                        active: matches!(route, Route::AccessUsersRoute | Route::AccessApiKeysRoute | Route::AccessSsoRoute | Route::AccessLdapRoute),
                         NavSubItem {
                             label: "Users",
                             active: route == Route::AccessUsersRoute,
@@ -249,6 +260,16 @@ pub fn DashboardLayout() -> Element {
                             active: route == Route::AccessApiKeysRoute,
                             on_click: move |_| { navigator().push(Route::AccessApiKeysRoute); sidebar_open.set(false); },
                         }
+                        NavSubItem {
+                            label: "Single Sign-On",
+                            active: route == Route::AccessSsoRoute,
+                            on_click: move |_| { navigator().push(Route::AccessSsoRoute); sidebar_open.set(false); },
+                        }
+                        NavSubItem {
+                            label: "LDAP",
+                            active: route == Route::AccessLdapRoute,
+                            on_click: move |_| { navigator().push(Route::AccessLdapRoute); sidebar_open.set(false); },
+                        }
                     }
 
                     div { class: "nav-divider" }
@@ -273,7 +294,7 @@ pub fn DashboardLayout() -> Element {
                     a {
                         class: "btn btn-ghost",
                         style: "width:100%;margin-bottom:8px",
-                        href: "/",
+                        href: "{base}/",
                         "Jellyfin Web"
                     }
                     button {
@@ -1,9 +1,12 @@
-use dioxus::prelude::*;
+use std::rc::Rc;
+
+use dioxus::{prelude::*, web::WebHistory};
 use gloo_storage::{LocalStorage, Storage};
 use remux_sdks::{
     remux::{
-        AuthenticateUserByName, CountryInfo, GetCountries, GetCurrentUser,
-        GetStartupConfiguration, JellyfinAuth, PostStartupComplete,
+        AuthenticateUserByName, AuthenticateWithOidc, AuthenticationResult,
+        CountryInfo, GetCountries, GetCurrentUser, GetOidcInfo,
+        GetStartupConfiguration, JellyfinAuth, OidcPublicInfo, PostStartupComplete,
         PostStartupConfiguration, PostStartupUser, PublicSystemInfo,
         StartupConfiguration, StartupUser, Username,
     },
@@ -11,8 +14,9 @@ use remux_sdks::{
 };
 
 use crate::state::{
-    browser_metadata_country_code, get_or_create_device_id, get_origin,
-    get_stored_server, store_credentials, StoredServer, TAILWIND_CSS, THEME_CSS,
+    base_path, browser_metadata_country_code, get_or_create_device_id, get_origin,
+    get_stored_server, store_credentials, take_sso_login_code, StoredServer,
+    TAILWIND_CSS, THEME_CSS,
 };
 
 mod components;
@@ -24,7 +28,11 @@ mod state;
 use router::Route;
 
 fn main() {
-    dioxus::launch(App);
+    // Routes live under the base URL the server is hosted at, not only `/admin`.
+    let history = WebHistory::new(Some(format!("{}/admin", base_path())), true);
+    dioxus::LaunchBuilder::web()
+        .with_cfg(dioxus::web::Config::new().history(Rc::new(history)))
+        .launch(App);
 }
 
 #[derive(Clone, PartialEq)]
@@ -105,15 +113,17 @@ fn App() -> Element {
         });
     });
 
+    let base = base_path();
+
     rsx! {
-        document::Link { rel: "stylesheet", href: TAILWIND_CSS }
-        document::Link { rel: "stylesheet", href: THEME_CSS }
+        document::Link { rel: "stylesheet", href: "{base}{TAILWIND_CSS}" }
+        document::Link { rel: "stylesheet", href: "{base}{THEME_CSS}" }
         {match *wizard_needed.read() {
             None => rsx! {
                 div { class: "login-page",
                     div { class: "login-card",
                         div { class: "login-header",
-                            a { href: "/", class: "login-brand-label", "Remux" }
+                            a { href: "{base}/", class: "login-brand-label", "Remux" }
                             p { class: "connecting", "Starting up…" }
                         }
                     }
@@ -132,7 +142,7 @@ fn App() -> Element {
                         div { class: "login-page",
                             div { class: "login-card",
                                 div { class: "login-header",
-                                    a { href: "/", class: "login-brand-label", "Remux" }
+                                    a { href: "{base}/", class: "login-brand-label", "Remux" }
                                     p { class: "connecting", "Starting up…" }
                                 }
                             }
@@ -143,7 +153,7 @@ fn App() -> Element {
                         div { class: "login-page",
                             div { class: "login-card",
                                 div { class: "login-header",
-                                    a { href: "/", class: "login-brand-label", "Remux" }
+                                    a { href: "{base}/", class: "login-brand-label", "Remux" }
                                     h1 { class: "login-title", "Admin Dashboard" }
                                 }
                                 div { class: "login-body",
@@ -163,6 +173,33 @@ fn App() -> Element {
     }
 }
 
+/// Credentials to store for a successful sign-in, or the message to show when
+/// the user isn't an admin.
+fn admin_credentials(
+    result: AuthenticationResult,
+    url: String,
+) -> Result<StoredServer, String> {
+    let (Some(token), Some(user)) = (result.access_token, result.user) else {
+        return Err("Login failed: no token in response".into());
+    };
+    if !user
+        .policy
+        .is_administrator
+    {
+        return Err("Admin access required.".into());
+    }
+    Ok(StoredServer {
+        id: result.server_id,
+        name: "Remux".to_string(),
+        manual_address: url,
+        access_token: token,
+        user_id: user
+            .id
+            .to_string(),
+        date_last_accessed: 0.0,
+    })
+}
+
 #[component]
 fn Login(on_login: EventHandler) -> Element {
     let mut server_url: Signal<Option<String>> = use_signal(|| None);
@@ -171,6 +208,7 @@ fn Login(on_login: EventHandler) -> Element {
     let mut password = use_signal(String::new);
     let mut error = use_signal(|| Option::<String>::None);
     let mut loading = use_signal(|| false);
+    let mut oidc: Signal<Option<OidcPublicInfo>> = use_signal(|| None);
 
     use_effect(move || {
         spawn(async move {
@@ -182,7 +220,45 @@ fn Login(on_login: EventHandler) -> Element {
                     .is_ok(),
                 Err(_) => false,
             };
-            server_url.set(Some(if reachable { origin } else { String::new() }));
+            server_url.set(Some(if reachable {
+                origin.clone()
+            } else {
+                String::new()
+            }));
+            if !reachable {
+                return;
+            }
+
+            if let Some(code) = take_sso_login_code() {
+                loading.set(true);
+                let device_id = get_or_create_device_id();
+                let result = match remux_sdks::remux::client(&origin) {
+                    Ok(c) => c
+                        .with_auth(JellyfinAuth::new(&device_id))
+                        .execute(AuthenticateWithOidc { code })
+                        .await
+                        .map_err(|e| format!("Single sign-on failed: {e}")),
+                    Err(e) => Err(format!("Bad server URL: {e}")),
+                };
+                match result.and_then(|r| admin_credentials(r, origin.clone())) {
+                    Ok(server) => {
+                        store_credentials(server);
+                        on_login.call(());
+                        return;
+                    }
+                    Err(e) => error.set(Some(e)),
+                }
+                loading.set(false);
+            }
+
+            if let Ok(c) = remux_sdks::remux::client(&origin) {
+                if let Ok(info) = c
+                    .execute(GetOidcInfo)
+                    .await
+                {
+                    oidc.set(Some(info).filter(|i| i.enabled));
+                }
+            }
         });
     });
 
@@ -235,33 +311,13 @@ fn Login(on_login: EventHandler) -> Element {
                 })
                 .await
             {
-                Ok(result) => {
-                    if let (Some(token), Some(user)) =
-                        (result.access_token, result.user)
-                    {
-                        if !user
-                            .policy
-                            .is_administrator
-                        {
-                            error.set(Some("Admin access required.".into()));
-                            loading.set(false);
-                            return;
-                        }
-                        store_credentials(StoredServer {
-                            id: result.server_id,
-                            name: "Remux".to_string(),
-                            manual_address: url,
-                            access_token: token,
-                            user_id: user
-                                .id
-                                .to_string(),
-                            date_last_accessed: 0.0,
-                        });
+                Ok(result) => match admin_credentials(result, url) {
+                    Ok(server) => {
+                        store_credentials(server);
                         on_login.call(());
-                    } else {
-                        error.set(Some("Login failed: no token in response".into()));
                     }
-                }
+                    Err(e) => error.set(Some(e)),
+                },
                 Err(ClientError::Unauthorized) => {
                     error.set(Some("Invalid username or password".into()));
                 }
@@ -274,6 +330,12 @@ fn Login(on_login: EventHandler) -> Element {
         });
     };
 
+    let base = base_path();
+    let sso_start = format!(
+        "{base}/sso/oidc/start?returnTo={}",
+        urlencoding::encode(&format!("{base}/admin"))
+    );
+
     rsx! {
         div { class: "login-page",
             div { class: "login-card",
@@ -339,6 +401,14 @@ fn Login(on_login: EventHandler) -> Element {
                                 if *loading.read() { "Signing in…" } else { "Sign In" }
                             }
                         }
+
+                        if let Some(info) = oidc.read().as_ref() {
+                            a {
+                                class: "btn btn-secondary login-btn",
+                                href: "{sso_start}",
+                                "{info.button_label}"
+                            }
+                        }
                     }
                 }
             }
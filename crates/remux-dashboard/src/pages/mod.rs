@@ -17,8 +17,9 @@ pub use dashboard::DashboardPage;
 pub use devices::DevicesPage;
 pub use iptv::IptvPage;
 pub use settings::{
-    IntroSettingsCard, JellyfinImportCard, P2pSettingsCard, PlaybackSettingsCard,
-    ProbeSettingsCard, RemuxdbSettingsCard, SearchSettingsCard, ServerSettingsCard,
+    DlnaSettingsCard, IntroSettingsCard, JellyfinImportCard, LdapSettingsCard,
+    OidcSettingsCard, P2pSettingsCard, PlaybackSettingsCard, ProbeSettingsCard,
+    RemuxdbSettingsCard, SearchSettingsCard, ServerSettingsCard,
 };
 pub use streams::StreamGroupsCard;
 pub use users::UsersPage;
@@ -4,11 +4,14 @@ use crate::{
 };
 use dioxus::prelude::*;
 use remux_sdks::remux::{
-    CountryInfo, CultureDto, EmbeddedSubtitleHandling, EncodingOptions, GetCountries,
-    GetCultures, GetEncodingConfiguration, GetIntroConfiguration,
-    GetSystemConfiguration, HardwareAccelerationType, IntroOptions, IntroOrder,
-    IntroTriggers, ServerConfiguration, StartTask, UpdateEncodingConfiguration,
-    UpdateIntroConfiguration, UpdateSystemConfiguration,
+    CountryInfo, CultureDto, DlnaOptions, EmbeddedSubtitleHandling, EncodingOptions,
+    GetCountries, GetCultures, GetDlnaConfiguration, GetEncodingConfiguration,
+    GetIntroConfiguration, GetLdapConfiguration, GetOidcConfiguration,
+    GetSystemConfiguration, GetUsers, HardwareAccelerationType, IntroOptions,
+    IntroOrder, IntroTriggers, LdapOptions, OidcOptions, ServerConfiguration,
+    StartTask, UpdateDlnaConfiguration, UpdateEncodingConfiguration,
+    UpdateIntroConfiguration, UpdateLdapConfiguration, UpdateOidcConfiguration,
+    UpdateSystemConfiguration, UserDto,
 };
 
 #[component]
@@ -346,10 +349,14 @@ pub fn PlaybackSettingsCard(app_state: AppState) -> Element {
     let mut h264_crf = use_signal(|| 23_u32);
     let mut h265_crf = use_signal(|| 28_u32);
     let mut normalize_audio_loudness = use_signal(|| false);
+    let mut enable_adaptive_bitrate_streaming = use_signal(|| false);
+    let mut apply_replay_gain = use_signal(|| false);
     let mut enable_video_transcoding = use_signal(|| true);
     let mut enable_audio_transcoding = use_signal(|| true);
     let mut enable_remuxing = use_signal(|| true);
     let mut subtitle_mode = use_signal(|| "Burn".to_string());
+    let mut trickplay_interval_secs = use_signal(|| 10_u32);
+    let mut trickplay_width = use_signal(|| 320_u32);
     let mut base_cfg: Signal<Option<ServerConfiguration>> = use_signal(|| None);
     let mut min_resume_pct = use_signal(|| 5_i64);
     let mut max_resume_pct = use_signal(|| 90_i64);
@@ -449,6 +456,14 @@ pub fn PlaybackSettingsCard(app_state: AppState) -> Element {
                         opts.normalize_audio_loudness
                             .unwrap_or(true),
                     );
+                    enable_adaptive_bitrate_streaming.set(
+                        opts.enable_adaptive_bitrate_streaming
+                            .unwrap_or(false),
+                    );
+                    apply_replay_gain.set(
+                        opts.apply_replay_gain
+                            .unwrap_or(false),
+                    );
                     enable_video_transcoding.set(
                         opts.enable_video_transcoding
                             .unwrap_or(true),
@@ -466,6 +481,15 @@ pub fn PlaybackSettingsCard(app_state: AppState) -> Element {
                             .unwrap_or(EmbeddedSubtitleHandling::Burn)
                             .to_string(),
                     );
+                    trickplay_interval_secs.set(
+                        opts.trickplay_interval_ms
+                            .unwrap_or(10_000)
+                            / 1000,
+                    );
+                    trickplay_width.set(
+                        opts.trickplay_width
+                            .unwrap_or(320),
+                    );
                 }
                 Err(e) => error.set(Some(format!("Failed to load settings: {e}"))),
             }
@@ -515,10 +539,16 @@ pub fn PlaybackSettingsCard(app_state: AppState) -> Element {
             enable_audio_transcoding: Some(*enable_audio_transcoding.peek()),
             enable_remuxing: Some(*enable_remuxing.peek()),
             normalize_audio_loudness: Some(*normalize_audio_loudness.peek()),
+            enable_adaptive_bitrate_streaming: Some(
+                *enable_adaptive_bitrate_streaming.peek(),
+            ),
+            apply_replay_gain: Some(*apply_replay_gain.peek()),
             subtitle_mode: subtitle_mode
                 .peek()
                 .parse::<EmbeddedSubtitleHandling>()
                 .ok(),
+            trickplay_interval_ms: Some(*trickplay_interval_secs.peek() * 1000),
+            trickplay_width: Some(*trickplay_width.peek()),
         };
         let min_pct = *min_resume_pct.peek();
         let max_pct = *max_resume_pct.peek();
@@ -732,6 +762,47 @@ pub fn PlaybackSettingsCard(app_state: AppState) -> Element {
                             }
                         }
 
+                        div { class: "field",
+                            label { class: "field-label", "Trickplay Thumbnails" }
+                            div { class: "field-hint", "Seek preview images generated by the Generate Trickplay Images task, and on first playback for remote streams. Changes apply to newly generated thumbnails." }
+                            div { style: "display:flex;gap:16px;flex-wrap:wrap",
+                                div { style: "display:flex;flex-direction:column;gap:4px",
+                                    label { r#for: "trickplay-interval", style: "font-size:0.85em", "Interval in seconds (default 10)" }
+                                    input {
+                                        id: "trickplay-interval",
+                                        r#type: "number",
+                                        class: "text-input",
+                                        style: "width:80px",
+                                        min: "1",
+                                        max: "600",
+                                        value: "{trickplay_interval_secs}",
+                                        onchange: move |e| {
+                                            if let Ok(v) = e.value().parse::<u32>() {
+                                                trickplay_interval_secs.set(v.clamp(1, 600));
+                                            }
+                                        },
+                                    }
+                                }
+                                div { style: "display:flex;flex-direction:column;gap:4px",
+                                    label { r#for: "trickplay-width", style: "font-size:0.85em", "Width in pixels (default 320)" }
+                                    input {
+                                        id: "trickplay-width",
+                                        r#type: "number",
+                                        class: "text-input",
+                                        style: "width:80px",
+                                        min: "80",
+                                        max: "1280",
+                                        value: "{trickplay_width}",
+                                        onchange: move |e| {
+                                            if let Ok(v) = e.value().parse::<u32>() {
+                                                trickplay_width.set(v.clamp(80, 1280));
+                                            }
+                                        },
+                                    }
+                                }
+                            }
+                        }
+
                         div { class: "field",
                             label { class: "field-label", "Audio Loudness Normalization" }
                             div { class: "field-hint", "Normalize transcoded audio to a consistent volume level. May increase time to first segment. Has no effect when audio is stream-copied." }
@@ -745,6 +816,32 @@ pub fn PlaybackSettingsCard(app_state: AppState) -> Element {
                             }
                         }
 
+                        div { class: "field",
+                            label { class: "field-label", "Adaptive Bitrate Streaming" }
+                            div { class: "field-hint", "Offer lower-resolution renditions alongside the full one when transcoding video, so players on slow connections can step down. Each rendition the player switches to runs its own encode." }
+                            label { style: "display:flex;align-items:center;gap:8px",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: *enable_adaptive_bitrate_streaming.read(),
+                                    onchange: move |e| enable_adaptive_bitrate_streaming.set(e.checked()),
+                                }
+                                "Offer 1080p/720p/480p/360p renditions"
+                            }
+                        }
+
+                        div { class: "field",
+                            label { class: "field-label", "ReplayGain" }
+                            div { class: "field-hint", "Apply each track's ReplayGain track gain when transcoding music. Leave off for clients that already apply the normalization gain themselves." }
+                            label { style: "display:flex;align-items:center;gap:8px",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: *apply_replay_gain.read(),
+                                    onchange: move |e| apply_replay_gain.set(e.checked()),
+                                }
+                                "Apply ReplayGain to transcoded music"
+                            }
+                        }
+
                         div { class: "field",
                             label { class: "field-label", "HDR Tone Mapping" }
                             div { class: "field-hint", "Convert HDR content to SDR using tone mapping. Without tone mapping, colour metadata is rewritten so clients treat the stream as SDR (may look washed out on some content)." }
@@ -1723,6 +1820,673 @@ pub fn IntroSettingsCard(app_state: AppState) -> Element {
     }
 }
 
+fn split_groups(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[component]
+pub fn OidcSettingsCard(app_state: AppState) -> Element {
+    let mut enabled = use_signal(|| false);
+    let mut issuer_url = use_signal(String::new);
+    let mut client_id = use_signal(String::new);
+    let mut client_secret = use_signal(String::new);
+    let mut scopes = use_signal(String::new);
+    let mut username_claim = use_signal(String::new);
+    let mut groups_claim = use_signal(String::new);
+    let mut allowed_groups = use_signal(String::new);
+    let mut admin_groups = use_signal(String::new);
+    let mut auto_provision = use_signal(|| true);
+    let mut redirect_uri = use_signal(String::new);
+    let mut button_label = use_signal(String::new);
+    let mut loading = use_signal(|| true);
+    let mut saving = use_signal(|| false);
+    let mut error = use_signal(|| Option::<String>::None);
+    let mut saved = use_signal(|| false);
+
+    let app_state_load = app_state.clone();
+    use_effect(move || {
+        let client = app_state_load.clone();
+        spawn(async move {
+            match client
+                .execute(GetOidcConfiguration)
+                .await
+            {
+                Ok(opts) => {
+                    enabled.set(opts.enabled);
+                    issuer_url.set(opts.issuer_url);
+                    client_id.set(opts.client_id);
+                    client_secret.set(
+                        opts.client_secret
+                            .unwrap_or_default(),
+                    );
+                    scopes.set(opts.scopes);
+                    username_claim.set(opts.username_claim);
+                    groups_claim.set(opts.groups_claim);
+                    allowed_groups.set(
+                        opts.allowed_groups
+                            .join(", "),
+                    );
+                    admin_groups.set(
+                        opts.admin_groups
+                            .join(", "),
+                    );
+                    auto_provision.set(opts.auto_provision);
+                    redirect_uri.set(
+                        opts.redirect_uri
+                            .unwrap_or_default(),
+                    );
+                    button_label.set(opts.button_label);
+                }
+                Err(e) => error.set(Some(format!("Failed to load SSO settings: {e}"))),
+            }
+            loading.set(false);
+        });
+    });
+
+    let on_submit = move |e: Event<FormData>| {
+        e.prevent_default();
+        let client = app_state.clone();
+        let optional = |v: String| {
+            let v = v
+                .trim()
+                .to_string();
+            (!v.is_empty()).then_some(v)
+        };
+        let opts = OidcOptions {
+            enabled: *enabled.peek(),
+            issuer_url: issuer_url
+                .peek()
+                .trim()
+                .to_string(),
+            client_id: client_id
+                .peek()
+                .trim()
+                .to_string(),
+            client_secret: optional(
+                client_secret
+                    .peek()
+                    .clone(),
+            ),
+            scopes: scopes
+                .peek()
+                .trim()
+                .to_string(),
+            username_claim: username_claim
+                .peek()
+                .trim()
+                .to_string(),
+            groups_claim: groups_claim
+                .peek()
+                .trim()
+                .to_string(),
+            allowed_groups: split_groups(&allowed_groups.peek()),
+            admin_groups: split_groups(&admin_groups.peek()),
+            auto_provision: *auto_provision.peek(),
+            redirect_uri: optional(
+                redirect_uri
+                    .peek()
+                    .clone(),
+            ),
+            button_label: button_label
+                .peek()
+                .trim()
+                .to_string(),
+        };
+        saved.set(false);
+        error.set(None);
+        saving.set(true);
+        spawn(async move {
+            match client
+                .execute(UpdateOidcConfiguration { config: opts })
+                .await
+            {
+                Ok(_) => saved.set(true),
+                Err(e) => error.set(Some(e.user_message())),
+            }
+            saving.set(false);
+        });
+    };
+
+    rsx! {
+        Card { title: "OpenID Connect",
+            if *loading.read() {
+                LoadingText {}
+            } else {
+                form { onsubmit: on_submit, style: "display:flex;flex-direction:column;gap:14px",
+                    ToggleRow {
+                        label: "Enable single sign-on",
+                        checked: *enabled.read(),
+                        on_change: move |v| enabled.set(v),
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-issuer", "Issuer URL" }
+                        div { class: "field-hint",
+                            "The provider's issuer, e.g. https://auth.example.com/application/o/remux/. Its discovery document is read from /.well-known/openid-configuration."
+                        }
+                        input {
+                            id: "oidc-issuer",
+                            r#type: "url",
+                            class: "text-input",
+                            value: "{issuer_url}",
+                            oninput: move |e| issuer_url.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-client-id", "Client ID" }
+                        input {
+                            id: "oidc-client-id",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{client_id}",
+                            oninput: move |e| client_id.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-client-secret", "Client Secret" }
+                        div { class: "field-hint", "Leave blank for a public client." }
+                        input {
+                            id: "oidc-client-secret",
+                            r#type: "password",
+                            class: "text-input",
+                            value: "{client_secret}",
+                            oninput: move |e| client_secret.set(e.value()),
+                            autocomplete: "off",
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-redirect", "Redirect URI" }
+                        div { class: "field-hint",
+                            "Register this with the provider. Leave blank to use {{server address}}/sso/oidc/callback."
+                        }
+                        input {
+                            id: "oidc-redirect",
+                            r#type: "url",
+                            class: "text-input",
+                            placeholder: "https://remux.example.com/sso/oidc/callback",
+                            value: "{redirect_uri}",
+                            oninput: move |e| redirect_uri.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-scopes", "Scopes" }
+                        div { class: "field-hint", "Requested in addition to openid, space separated." }
+                        input {
+                            id: "oidc-scopes",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{scopes}",
+                            oninput: move |e| scopes.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-username-claim", "Username Claim" }
+                        input {
+                            id: "oidc-username-claim",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{username_claim}",
+                            oninput: move |e| username_claim.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-groups-claim", "Groups Claim" }
+                        input {
+                            id: "oidc-groups-claim",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{groups_claim}",
+                            oninput: move |e| groups_claim.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-allowed-groups", "Allowed Groups" }
+                        div { class: "field-hint", "Comma separated. Leave blank to let every account of the provider sign in." }
+                        input {
+                            id: "oidc-allowed-groups",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{allowed_groups}",
+                            oninput: move |e| allowed_groups.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-admin-groups", "Admin Groups" }
+                        div { class: "field-hint",
+                            "Comma separated. Members become administrators and everyone else loses admin on sign-in. Leave blank to manage admins here instead."
+                        }
+                        input {
+                            id: "oidc-admin-groups",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{admin_groups}",
+                            oninput: move |e| admin_groups.set(e.value()),
+                        }
+                    }
+
+                    ToggleRow {
+                        label: "Create users on first sign-in",
+                        checked: *auto_provision.read(),
+                        on_change: move |v| auto_provision.set(v),
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "oidc-button-label", "Button Label" }
+                        input {
+                            id: "oidc-button-label",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{button_label}",
+                            oninput: move |e| button_label.set(e.value()),
+                        }
+                    }
+
+                    if let Some(err) = error.read().as_ref() {
+                        ErrorAlert { message: err.clone() }
+                    }
+                    if *saved.read() {
+                        SuccessAlert { message: "Single sign-on settings saved.".to_string() }
+                    }
+
+                    FormActions {
+                        button {
+                            r#type: "submit",
+                            class: "btn btn-primary",
+                            disabled: *saving.read(),
+                            if *saving.read() { "Saving…" } else { "Save Settings" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn LdapSettingsCard(app_state: AppState) -> Element {
+    let mut enabled = use_signal(|| false);
+    let mut server_url = use_signal(String::new);
+    let mut start_tls = use_signal(|| false);
+    let mut bind_dn = use_signal(String::new);
+    let mut bind_password = use_signal(String::new);
+    let mut base_dn = use_signal(String::new);
+    let mut user_filter = use_signal(String::new);
+    let mut username_attribute = use_signal(String::new);
+    let mut group_attribute = use_signal(String::new);
+    let mut admin_group = use_signal(String::new);
+    let mut auto_provision = use_signal(|| true);
+    let mut loading = use_signal(|| true);
+    let mut saving = use_signal(|| false);
+    let mut error = use_signal(|| Option::<String>::None);
+    let mut saved = use_signal(|| false);
+
+    let app_state_load = app_state.clone();
+    use_effect(move || {
+        let client = app_state_load.clone();
+        spawn(async move {
+            match client
+                .execute(GetLdapConfiguration)
+                .await
+            {
+                Ok(opts) => {
+                    enabled.set(opts.enabled);
+                    server_url.set(opts.server_url);
+                    start_tls.set(opts.start_tls);
+                    bind_dn.set(
+                        opts.bind_dn
+                            .unwrap_or_default(),
+                    );
+                    bind_password.set(
+                        opts.bind_password
+                            .unwrap_or_default(),
+                    );
+                    base_dn.set(opts.base_dn);
+                    user_filter.set(opts.user_filter);
+                    username_attribute.set(opts.username_attribute);
+                    group_attribute.set(opts.group_attribute);
+                    admin_group.set(
+                        opts.admin_group
+                            .unwrap_or_default(),
+                    );
+                    auto_provision.set(opts.auto_provision);
+                }
+                Err(e) => error.set(Some(format!("Failed to load LDAP settings: {e}"))),
+            }
+            loading.set(false);
+        });
+    });
+
+    let on_submit = move |e: Event<FormData>| {
+        e.prevent_default();
+        let client = app_state.clone();
+        let optional = |v: String| {
+            let v = v
+                .trim()
+                .to_string();
+            (!v.is_empty()).then_some(v)
+        };
+        let opts = LdapOptions {
+            enabled: *enabled.peek(),
+            server_url: server_url
+                .peek()
+                .trim()
+                .to_string(),
+            start_tls: *start_tls.peek(),
+            bind_dn: optional(
+                bind_dn
+                    .peek()
+                    .clone(),
+            ),
+            bind_password: optional(
+                bind_password
+                    .peek()
+                    .clone(),
+            ),
+            base_dn: base_dn
+                .peek()
+                .trim()
+                .to_string(),
+            user_filter: user_filter
+                .peek()
+                .trim()
+                .to_string(),
+            username_attribute: username_attribute
+                .peek()
+                .trim()
+                .to_string(),
+            group_attribute: group_attribute
+                .peek()
+                .trim()
+                .to_string(),
+            admin_group: optional(
+                admin_group
+                    .peek()
+                    .clone(),
+            ),
+            auto_provision: *auto_provision.peek(),
+        };
+        saved.set(false);
+        error.set(None);
+        saving.set(true);
+        spawn(async move {
+            match client
+                .execute(UpdateLdapConfiguration { config: opts })
+                .await
+            {
+                Ok(_) => saved.set(true),
+                Err(e) => error.set(Some(e.user_message())),
+            }
+            saving.set(false);
+        });
+    };
+
+    rsx! {
+        Card { title: "LDAP",
+            if *loading.read() {
+                LoadingText {}
+            } else {
+                form { onsubmit: on_submit, style: "display:flex;flex-direction:column;gap:14px",
+                    ToggleRow {
+                        label: "Enable LDAP sign-in",
+                        checked: *enabled.read(),
+                        on_change: move |v| enabled.set(v),
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "ldap-url", "Server URL" }
+                        input {
+                            id: "ldap-url",
+                            r#type: "text",
+                            class: "text-input",
+                            placeholder: "ldaps://ldap.example.com",
+                            value: "{server_url}",
+                            oninput: move |e| server_url.set(e.value()),
+                        }
+                    }
+
+                    ToggleRow {
+                        label: "Use StartTLS",
+                        checked: *start_tls.read(),
+                        on_change: move |v| start_tls.set(v),
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "ldap-bind-dn", "Bind DN" }
+                        div { class: "field-hint", "Account used to search for users. Leave blank to search anonymously." }
+                        input {
+                            id: "ldap-bind-dn",
+                            r#type: "text",
+                            class: "text-input",
+                            placeholder: "cn=remux,dc=example,dc=com",
+                            value: "{bind_dn}",
+                            oninput: move |e| bind_dn.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "ldap-bind-password", "Bind Password" }
+                        input {
+                            id: "ldap-bind-password",
+                            r#type: "password",
+                            class: "text-input",
+                            value: "{bind_password}",
+                            oninput: move |e| bind_password.set(e.value()),
+                            autocomplete: "off",
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "ldap-base-dn", "Base DN" }
+                        input {
+                            id: "ldap-base-dn",
+                            r#type: "text",
+                            class: "text-input",
+                            placeholder: "ou=people,dc=example,dc=com",
+                            value: "{base_dn}",
+                            oninput: move |e| base_dn.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "ldap-filter", "User Filter" }
+                        div { class: "field-hint", "{{username}} is replaced with the name typed at the login page." }
+                        input {
+                            id: "ldap-filter",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{user_filter}",
+                            oninput: move |e| user_filter.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "ldap-username-attr", "Username Attribute" }
+                        input {
+                            id: "ldap-username-attr",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{username_attribute}",
+                            oninput: move |e| username_attribute.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "ldap-group-attr", "Group Attribute" }
+                        input {
+                            id: "ldap-group-attr",
+                            r#type: "text",
+                            class: "text-input",
+                            value: "{group_attribute}",
+                            oninput: move |e| group_attribute.set(e.value()),
+                        }
+                    }
+
+                    div { class: "field",
+                        label { class: "field-label", r#for: "ldap-admin-group", "Admin Group" }
+                        div { class: "field-hint",
+                            "DN of the group whose members are administrators. Everyone else loses admin on sign-in. Leave blank to manage admins here instead."
+                        }
+                        input {
+                            id: "ldap-admin-group",
+                            r#type: "text",
+                            class: "text-input",
+                            placeholder: "cn=admins,ou=groups,dc=example,dc=com",
+                            value: "{admin_group}",
+                            oninput: move |e| admin_group.set(e.value()),
+                        }
+                    }
+
+                    ToggleRow {
+                        label: "Create users on first sign-in",
+                        checked: *auto_provision.read(),
+                        on_change: move |v| auto_provision.set(v),
+                    }
+
+                    if let Some(err) = error.read().as_ref() {
+                        ErrorAlert { message: err.clone() }
+                    }
+                    if *saved.read() {
+                        SuccessAlert { message: "LDAP settings saved.".to_string() }
+                    }
+
+                    FormActions {
+                        button {
+                            r#type: "submit",
+                            class: "btn btn-primary",
+                            disabled: *saving.read(),
+                            if *saving.read() { "Saving…" } else { "Save Settings" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn DlnaSettingsCard(app_state: AppState) -> Element {
+    let mut default_user_id = use_signal(String::new);
+    let mut users: Signal<Vec<UserDto>> = use_signal(Vec::new);
+    let mut loading = use_signal(|| true);
+    let mut saving = use_signal(|| false);
+    let mut error = use_signal(|| Option::<String>::None);
+    let mut saved = use_signal(|| false);
+
+    let app_state_load = app_state.clone();
+    use_effect(move || {
+        let client = app_state_load.clone();
+        spawn(async move {
+            match client
+                .execute(GetUsers)
+                .await
+            {
+                Ok(list) => users.set(list),
+                Err(e) => error.set(Some(format!("Failed to load users: {e}"))),
+            }
+            match client
+                .execute(GetDlnaConfiguration)
+                .await
+            {
+                Ok(opts) => default_user_id.set(
+                    opts.default_user_id
+                        .map(|id| id.to_string())
+                        .unwrap_or_default(),
+                ),
+                Err(e) => error.set(Some(format!("Failed to load DLNA settings: {e}"))),
+            }
+            loading.set(false);
+        });
+    });
+
+    let on_submit = move |e: Event<FormData>| {
+        e.prevent_default();
+        let client = app_state.clone();
+        let opts = DlnaOptions {
+            default_user_id: default_user_id
+                .peek()
+                .parse()
+                .ok(),
+        };
+        saved.set(false);
+        error.set(None);
+        saving.set(true);
+        spawn(async move {
+            match client
+                .execute(UpdateDlnaConfiguration { config: opts })
+                .await
+            {
+                Ok(_) => saved.set(true),
+                Err(e) => error.set(Some(e.user_message())),
+            }
+            saving.set(false);
+        });
+    };
+
+    rsx! {
+        Card { title: "DLNA",
+            if *loading.read() {
+                LoadingText {}
+            } else {
+                form { onsubmit: on_submit, style: "display:flex;flex-direction:column;gap:14px",
+                    div { class: "field",
+                        label { class: "field-label", r#for: "dlna-user", "Browse As" }
+                        div { class: "field-hint",
+                            "TVs and receivers on the local network see this user's libraries, within its parental limits. Renderers are refused until a user is picked."
+                        }
+                        select {
+                            id: "dlna-user",
+                            class: "select-input",
+                            value: "{default_user_id}",
+                            onchange: move |e| default_user_id.set(e.value()),
+                            option {
+                                value: "",
+                                selected: default_user_id.read().is_empty(),
+                                "None"
+                            }
+                            for user in users.read().iter() {
+                                option {
+                                    value: "{user.id}",
+                                    selected: *default_user_id.read() == user.id.to_string(),
+                                    "{user.name}"
+                                }
+                            }
+                        }
+                    }
+
+                    if let Some(err) = error.read().as_ref() {
+                        ErrorAlert { message: err.clone() }
+                    }
+                    if *saved.read() {
+                        SuccessAlert { message: "DLNA settings saved.".to_string() }
+                    }
+
+                    FormActions {
+                        button {
+                            r#type: "submit",
+                            class: "btn btn-primary",
+                            disabled: *saving.read(),
+                            if *saving.read() { "Saving…" } else { "Save Settings" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[component]
 pub fn RemuxdbSettingsCard(app_state: AppState) -> Element {
     let mut base_cfg: Signal<Option<ServerConfiguration>> = use_signal(|| None);
@@ -259,6 +259,15 @@ pub fn UserForm(
             })
             .unwrap_or(0)
     });
+    let mut daily_watch_limit: Signal<i64> = use_signal(|| {
+        existing
+            .as_ref()
+            .map(|u| {
+                u.policy
+                    .daily_watch_time_limit_minutes
+            })
+            .unwrap_or(0)
+    });
     let mut enable_video_transcoding = use_signal(|| {
         existing
             .as_ref()
@@ -417,6 +426,7 @@ pub fn UserForm(
             .clone();
         let remote_search_snapshot = *enable_remote_search.peek();
         let max_sessions_snapshot = *max_active_sessions.peek();
+        let daily_watch_limit_snapshot = *daily_watch_limit.peek();
         let video_transcoding_snapshot = *enable_video_transcoding.peek();
         let audio_transcoding_snapshot = *enable_audio_transcoding.peek();
         let remuxing_snapshot = *enable_remuxing.peek();
@@ -484,6 +494,7 @@ pub fn UserForm(
                     policy.stream_filter = stream_filter.clone();
                     policy.enable_remote_search = remote_search_snapshot;
                     policy.max_active_sessions = max_sessions_snapshot;
+                    policy.daily_watch_time_limit_minutes = daily_watch_limit_snapshot;
                     policy.enable_video_playback_transcoding =
                         video_transcoding_snapshot;
                     policy.enable_audio_playback_transcoding =
@@ -548,6 +559,7 @@ pub fn UserForm(
                         || stream_filter.is_some()
                         || !remote_search_snapshot
                         || max_sessions_snapshot > 0
+                        || daily_watch_limit_snapshot > 0
                         || !video_transcoding_snapshot
                         || !audio_transcoding_snapshot
                         || !remuxing_snapshot
@@ -561,6 +573,8 @@ pub fn UserForm(
                         policy.stream_filter = stream_filter.clone();
                         policy.enable_remote_search = remote_search_snapshot;
                         policy.max_active_sessions = max_sessions_snapshot;
+                        policy.daily_watch_time_limit_minutes =
+                            daily_watch_limit_snapshot;
                         policy.enable_video_playback_transcoding =
                             video_transcoding_snapshot;
                         policy.enable_audio_playback_transcoding =
@@ -715,6 +729,25 @@ pub fn UserForm(
                 span { class: "field-hint", "Leave blank for unlimited" }
             }
 
+            div { class: "field",
+                label { class: "field-label", r#for: "u-daily-watch", "Daily Watch Time (minutes)" }
+                input {
+                    id: "u-daily-watch",
+                    r#type: "number",
+                    class: "field-input",
+                    min: "1",
+                    placeholder: "Unlimited",
+                    value: if *daily_watch_limit.read() > 0 { daily_watch_limit.read().to_string() } else { String::new() },
+                    oninput: move |e| {
+                        let v = e.value();
+                        daily_watch_limit.set(
+                            v.parse::<i64>().map(|n| n.max(1)).unwrap_or(0)
+                        );
+                    },
+                }
+                span { class: "field-hint", "New playback is refused once today's total is reached. Leave blank for unlimited" }
+            }
+
             if is_edit && !all_addons.read().is_empty() {
                 div { class: "field",
                     div { class: "field-row",
@@ -37,10 +37,16 @@ pub enum Route {
     SettingsRemuxdbRoute,
     #[route("/settings/branding")]
     SettingsBrandingRoute,
+    #[route("/settings/dlna")]
+    SettingsDlnaRoute,
     #[route("/access/users")]
     AccessUsersRoute,
     #[route("/access/apikeys")]
     AccessApiKeysRoute,
+    #[route("/access/sso")]
+    AccessSsoRoute,
+    #[route("/access/ldap")]
+    AccessLdapRoute,
     #[route("/tasks")]
     TasksRoute,
     #[route("/devices")]
@@ -136,6 +142,12 @@ pub(crate) fn SettingsBrandingRoute() -> Element {
     rsx! { BrandingPage { app_state } }
 }
 
+#[component]
+pub(crate) fn SettingsDlnaRoute() -> Element {
+    let app_state = use_context::<AppState>();
+    rsx! { DlnaSettingsCard { app_state } }
+}
+
 #[component]
 pub(crate) fn AccessUsersRoute() -> Element {
     let app_state = use_context::<AppState>();
@@ -148,6 +160,18 @@ pub(crate) fn AccessApiKeysRoute() -> Element {
     rsx! { ApiKeysPage { app_state } }
 }
 
+#[component]
+pub(crate) fn AccessSsoRoute() -> Element {
+    let app_state = use_context::<AppState>();
+    rsx! { OidcSettingsCard { app_state } }
+}
+
+#[component]
+pub(crate) fn AccessLdapRoute() -> Element {
+    let app_state = use_context::<AppState>();
+    rsx! { LdapSettingsCard { app_state } }
+}
+
 #[component]
 pub(crate) fn TasksRoute() -> Element {
     let app_state = use_context::<AppState>();
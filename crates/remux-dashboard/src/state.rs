@@ -145,14 +145,90 @@ pub fn fmt_datetime(dt: impl std::fmt::Display) -> String {
     }
 }
 
+/// The base URL the server is hosted under: the path ahead of `/admin`,
+/// empty at the root.
+pub fn base_path() -> String {
+    let path = web_sys::window()
+        .and_then(|w| {
+            w.location()
+                .pathname()
+                .ok()
+        })
+        .unwrap_or_default();
+    path.match_indices("/admin")
+        .map(|(i, _)| i)
+        .find(|&i| {
+            matches!(
+                path[i + "/admin".len()..]
+                    .chars()
+                    .next(),
+                None | Some('/')
+            )
+        })
+        .map(|i| path[..i].to_string())
+        .unwrap_or_default()
+}
+
+/// The server address, base URL included.
 pub fn get_origin() -> String {
-    web_sys::window()
+    let origin = web_sys::window()
         .and_then(|w| {
             w.location()
                 .origin()
                 .ok()
         })
-        .unwrap_or_default()
+        .unwrap_or_default();
+    format!("{origin}{}", base_path())
+}
+
+/// Query parameter `/sso/oidc/callback` uses to hand the one-time login code back.
+pub const SSO_LOGIN_CODE_PARAM: &str = "remux_sso";
+
+/// Takes the single sign-on login code out of the current URL, if present.
+/// The address bar is rewritten without it so a reload doesn't replay it.
+pub fn take_sso_login_code() -> Option<String> {
+    let window = web_sys::window()?;
+    let location = window.location();
+    let search = location
+        .search()
+        .ok()?;
+    let mut code = None;
+    let mut rest = Vec::new();
+    for pair in search
+        .trim_start_matches('?')
+        .split('&')
+        .filter(|p| !p.is_empty())
+    {
+        match pair.split_once('=') {
+            Some((SSO_LOGIN_CODE_PARAM, value)) => {
+                code = urlencoding::decode(value)
+                    .ok()
+                    .map(|v| v.into_owned());
+            }
+            _ => rest.push(pair),
+        }
+    }
+    let code = code.filter(|c| !c.is_empty())?;
+
+    let path = location
+        .pathname()
+        .unwrap_or_default();
+    let hash = location
+        .hash()
+        .unwrap_or_default();
+    let query = if rest.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", rest.join("&"))
+    };
+    if let Ok(history) = window.history() {
+        let _ = history.replace_state_with_url(
+            &web_sys::wasm_bindgen::JsValue::NULL,
+            "",
+            Some(&format!("{path}{query}{hash}")),
+        );
+    }
+    Some(code)
 }
 
 pub fn browser_metadata_country_code() -> String {
@@ -0,0 +1,243 @@
+use crate::{Auth, Body, Endpoint, RestClient};
+use http::Method;
+use serde::{Deserialize, Serialize};
+
+/// Where a user authorises an implicit-grant client and is shown a token to
+/// paste back.
+pub fn authorize_url(client_id: &str) -> String {
+    format!(
+        "https://anilist.co/api/v2/oauth/authorize?client_id={client_id}&response_type=token"
+    )
+}
+
+#[derive(Clone, Debug)]
+pub struct AniListAuth {
+    pub access_token: String,
+}
+
+impl Auth for AniListAuth {
+    fn apply(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        req.bearer_auth(&self.access_token)
+            .header("Accept", "application/json")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphQlError {
+    pub message: String,
+    pub status: Option<u16>,
+}
+
+/// AniList answers GraphQL errors with a 200 as often as not, so callers
+/// check `errors` before trusting `data`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphQlResponse<T> {
+    pub data: Option<T>,
+    #[serde(default)]
+    pub errors: Vec<GraphQlError>,
+}
+
+fn graphql(query: &str, variables: serde_json::Value) -> Body {
+    Body::Json(serde_json::json!({
+        "query": query,
+        "variables": variables,
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MediaListStatus {
+    Current,
+    Planning,
+    Completed,
+    Dropped,
+    Paused,
+    Repeating,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Viewer {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewerData {
+    #[serde(rename = "Viewer")]
+    pub viewer: Viewer,
+}
+
+/// The user the token belongs to.
+#[derive(Debug, Clone)]
+pub struct ViewerEndpoint;
+
+impl Endpoint for ViewerEndpoint {
+    type Output = GraphQlResponse<ViewerData>;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        graphql("query { Viewer { id name } }", serde_json::json!({}))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaListEntry {
+    pub id: i64,
+    pub progress: Option<i64>,
+    pub status: Option<MediaListStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Media {
+    pub id: i64,
+    /// `None` while airing with no announced length.
+    pub episodes: Option<i64>,
+    /// The viewer's own entry, when they have one.
+    pub media_list_entry: Option<MediaListEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaData {
+    #[serde(rename = "Media")]
+    pub media: Media,
+}
+
+#[derive(Debug, Clone)]
+pub struct MediaEndpoint {
+    pub anilist_id: i64,
+}
+
+impl Endpoint for MediaEndpoint {
+    type Output = GraphQlResponse<MediaData>;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        graphql(
+            "query ($id: Int) { Media(id: $id, type: ANIME) { id episodes mediaListEntry { id progress status } } }",
+            serde_json::json!({ "id": self.anilist_id }),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveData {
+    #[serde(rename = "SaveMediaListEntry")]
+    pub entry: MediaListEntry,
+}
+
+/// Creates the viewer's entry if missing, otherwise updates it.
+#[derive(Debug, Clone)]
+pub struct SaveMediaListEntryEndpoint {
+    pub anilist_id: i64,
+    pub progress: i64,
+    pub status: MediaListStatus,
+}
+
+impl Endpoint for SaveMediaListEntryEndpoint {
+    type Output = GraphQlResponse<SaveData>;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        graphql(
+            "mutation ($mediaId: Int, $progress: Int, $status: MediaListStatus) { SaveMediaListEntry(mediaId: $mediaId, progress: $progress, status: $status) { id progress status } }",
+            serde_json::json!({
+                "mediaId": self.anilist_id,
+                "progress": self.progress,
+                "status": self.status,
+            }),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaRef {
+    pub id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListEntry {
+    pub progress: Option<i64>,
+    pub status: Option<MediaListStatus>,
+    /// Unix seconds.
+    pub updated_at: Option<i64>,
+    pub media: MediaRef,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaList {
+    #[serde(default)]
+    pub entries: Vec<ListEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaListCollection {
+    #[serde(default)]
+    pub lists: Vec<MediaList>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionData {
+    #[serde(rename = "MediaListCollection")]
+    pub collection: MediaListCollection,
+}
+
+/// Every anime entry a user has in the given statuses.
+#[derive(Debug, Clone)]
+pub struct MediaListCollectionEndpoint {
+    pub user_id: i64,
+    pub statuses: Vec<MediaListStatus>,
+}
+
+impl Endpoint for MediaListCollectionEndpoint {
+    type Output = GraphQlResponse<CollectionData>;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        graphql(
+            "query ($userId: Int, $statuses: [MediaListStatus]) { MediaListCollection(userId: $userId, type: ANIME, status_in: $statuses) { lists { entries { progress status updatedAt media { id } } } } }",
+            serde_json::json!({
+                "userId": self.user_id,
+                "statuses": self.statuses,
+            }),
+        )
+    }
+}
+
+pub fn anilist_client(
+    access_token: &str,
+    base_url: &str,
+) -> Result<RestClient<AniListAuth>, url::ParseError> {
+    Ok(RestClient::new(base_url)?.with_auth(AniListAuth {
+        access_token: access_token.to_string(),
+    }))
+}
@@ -1,5 +1,22 @@
-use crate::{Endpoint, NoAuth, RestClient};
+use crate::{Auth, Body, Endpoint, NoAuth, RestClient};
+use chrono::{DateTime, Utc};
+use http::{HeaderMap, HeaderValue, Method, header};
 use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+pub const KITSU_API: &str = "https://kitsu.io/api";
+
+#[derive(Clone, Debug)]
+pub struct KitsuAuth {
+    pub access_token: String,
+}
+
+impl Auth for KitsuAuth {
+    fn apply(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        req.bearer_auth(&self.access_token)
+            .header("Accept", "application/vnd.api+json")
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MappingsEndpoint {
@@ -11,7 +28,7 @@ impl Endpoint for MappingsEndpoint {
     type Output = MappingsResponse;
 
     fn path(&self) -> String {
-        format!("anime/{}/mappings", self.kitsu_id)
+        format!("edge/anime/{}/mappings", self.kitsu_id)
     }
 }
 
@@ -51,8 +68,407 @@ impl MappingsResponse {
                     .ok()
             })
     }
+
+    pub fn anilist_id(&self) -> Option<i64> {
+        self.data
+            .iter()
+            .find(|e| {
+                e.attributes
+                    .external_site
+                    == "anilist/anime"
+            })
+            .and_then(|e| {
+                e.attributes
+                    .external_id
+                    .parse()
+                    .ok()
+            })
+    }
+}
+
+/// A JSON:API resource. Relationships are kept raw: only the id of a to-one
+/// relation is ever read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Resource<A> {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub attributes: A,
+    #[serde(default)]
+    pub relationships: serde_json::Value,
+}
+
+impl<A> Resource<A> {
+    /// The id behind a to-one relationship, when the response included it.
+    pub fn related_id(&self, name: &str) -> Option<i64> {
+        self.relationships
+            .get(name)?
+            .get("data")?
+            .get("id")?
+            .as_str()?
+            .parse()
+            .ok()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document<T> {
+    pub data: T,
+    #[serde(default)]
+    pub included: Vec<Resource<serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KitsuToken {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Seconds from `created_at`.
+    pub expires_in: i64,
+    /// Unix seconds.
+    pub created_at: i64,
+}
+
+impl KitsuToken {
+    /// Unix seconds.
+    pub fn expires_at(&self) -> i64 {
+        self.created_at + self.expires_in
+    }
+}
+
+/// Kitsu's password grant. The web client's own flow, so no app registration
+/// is needed.
+#[derive(Debug, Clone)]
+pub struct PasswordTokenEndpoint {
+    pub username: String,
+    pub password: String,
+}
+
+impl Endpoint for PasswordTokenEndpoint {
+    type Output = KitsuToken;
+
+    fn path(&self) -> String {
+        "oauth/token".to_string()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        Body::Form(vec![
+            ("grant_type".into(), "password".into()),
+            (
+                "username".into(),
+                self.username
+                    .clone(),
+            ),
+            (
+                "password".into(),
+                self.password
+                    .clone(),
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RefreshTokenEndpoint {
+    pub refresh_token: String,
+}
+
+impl Endpoint for RefreshTokenEndpoint {
+    type Output = KitsuToken;
+
+    fn path(&self) -> String {
+        "oauth/token".to_string()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        Body::Form(vec![
+            ("grant_type".into(), "refresh_token".into()),
+            (
+                "refresh_token".into(),
+                self.refresh_token
+                    .clone(),
+            ),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserAttributes {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SelfUserParams {
+    #[serde(rename = "filter[self]")]
+    filter_self: bool,
+}
+
+/// The user the access token belongs to.
+#[derive(Debug, Clone)]
+pub struct SelfUserEndpoint;
+
+impl Endpoint for SelfUserEndpoint {
+    type Output = Document<Vec<Resource<UserAttributes>>>;
+
+    fn path(&self) -> String {
+        "edge/users".to_string()
+    }
+
+    fn query_params(&self) -> impl serde::Serialize + '_ {
+        SelfUserParams { filter_self: true }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnimeAttributes {
+    pub canonical_title: Option<String>,
+    /// `None` while airing with no announced length.
+    pub episode_count: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimeEndpoint {
+    pub kitsu_id: i64,
+}
+
+impl Endpoint for AnimeEndpoint {
+    type Output = Document<Resource<AnimeAttributes>>;
+
+    fn path(&self) -> String {
+        format!("edge/anime/{}", self.kitsu_id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ReverseMappingParams<'a> {
+    #[serde(rename = "filter[externalSite]")]
+    external_site: &'a str,
+    #[serde(rename = "filter[externalId]")]
+    external_id: &'a str,
+    include: &'static str,
+}
+
+/// Finds the Kitsu anime another site's id maps to, e.g. `thetvdb/series`
+/// or `anilist/anime`.
+#[derive(Debug, Clone)]
+pub struct ReverseMappingEndpoint {
+    pub external_site: String,
+    pub external_id: String,
+}
+
+impl Endpoint for ReverseMappingEndpoint {
+    type Output = Document<Vec<Resource<MappingAttributes>>>;
+
+    fn path(&self) -> String {
+        "edge/mappings".to_string()
+    }
+
+    fn query_params(&self) -> impl serde::Serialize + '_ {
+        ReverseMappingParams {
+            external_site: &self.external_site,
+            external_id: &self.external_id,
+            include: "item",
+        }
+    }
+}
+
+impl Document<Vec<Resource<MappingAttributes>>> {
+    /// The first mapped item that is an anime.
+    pub fn anime_id(&self) -> Option<i64> {
+        self.included
+            .iter()
+            .find(|r| r.kind == "anime")
+            .and_then(|r| {
+                r.id.parse()
+                    .ok()
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LibraryStatus {
+    Current,
+    Planned,
+    Completed,
+    OnHold,
+    Dropped,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryEntryAttributes {
+    pub status: Option<LibraryStatus>,
+    pub progress: Option<i64>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize)]
+struct LibraryEntriesParams {
+    #[serde(rename = "filter[userId]")]
+    user_id: i64,
+    #[serde(rename = "filter[animeId]")]
+    anime_id: Option<i64>,
+    #[serde(rename = "filter[status]")]
+    status: Option<LibraryStatus>,
+    #[serde(rename = "filter[kind]")]
+    kind: &'static str,
+    include: &'static str,
+    #[serde(rename = "page[limit]")]
+    limit: u32,
+    #[serde(rename = "page[offset]")]
+    offset: u32,
+}
+
+/// A user's anime library, optionally narrowed to one title or one status.
+/// Entries carry their anime's id as the `anime` relationship.
+#[derive(Debug, Clone)]
+pub struct LibraryEntriesEndpoint {
+    pub user_id: i64,
+    pub anime_id: Option<i64>,
+    pub status: Option<LibraryStatus>,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+impl Endpoint for LibraryEntriesEndpoint {
+    type Output = Document<Vec<Resource<LibraryEntryAttributes>>>;
+
+    fn path(&self) -> String {
+        "edge/library-entries".to_string()
+    }
+
+    fn query_params(&self) -> impl serde::Serialize + '_ {
+        LibraryEntriesParams {
+            user_id: self.user_id,
+            anime_id: self.anime_id,
+            status: self.status,
+            kind: "anime",
+            include: "anime",
+            limit: self.limit,
+            offset: self.offset,
+        }
+    }
+}
+
+/// Kitsu only accepts writes typed as JSON:API, which `Body::Json` is not.
+fn json_api_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/vnd.api+json"),
+    );
+    headers
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateLibraryEntryEndpoint {
+    pub user_id: i64,
+    pub anime_id: i64,
+    pub status: LibraryStatus,
+    pub progress: i64,
+}
+
+impl Endpoint for CreateLibraryEntryEndpoint {
+    type Output = Document<Resource<LibraryEntryAttributes>>;
+
+    fn path(&self) -> String {
+        "edge/library-entries".to_string()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn headers(&self) -> HeaderMap {
+        json_api_headers()
+    }
+
+    fn body(&self) -> Body {
+        Body::Text(
+            serde_json::json!({
+                "data": {
+                    "type": "libraryEntries",
+                    "attributes": {
+                        "status": self.status,
+                        "progress": self.progress,
+                    },
+                    "relationships": {
+                        "user": { "data": { "type": "users", "id": self.user_id.to_string() } },
+                        "anime": { "data": { "type": "anime", "id": self.anime_id.to_string() } },
+                    },
+                }
+            })
+            .to_string(),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateLibraryEntryEndpoint {
+    pub entry_id: String,
+    pub status: LibraryStatus,
+    pub progress: i64,
+}
+
+impl Endpoint for UpdateLibraryEntryEndpoint {
+    type Output = Document<Resource<LibraryEntryAttributes>>;
+
+    fn path(&self) -> String {
+        format!("edge/library-entries/{}", self.entry_id)
+    }
+
+    fn method(&self) -> Method {
+        Method::PATCH
+    }
+
+    fn headers(&self) -> HeaderMap {
+        json_api_headers()
+    }
+
+    fn body(&self) -> Body {
+        Body::Text(
+            serde_json::json!({
+                "data": {
+                    "type": "libraryEntries",
+                    "id": self.entry_id,
+                    "attributes": {
+                        "status": self.status,
+                        "progress": self.progress,
+                    },
+                }
+            })
+            .to_string(),
+        )
+    }
 }
 
 pub fn client() -> RestClient<NoAuth> {
-    RestClient::new("https://kitsu.io/api/edge/").expect("Kitsu base URL is valid")
+    kitsu_client(KITSU_API).expect("Kitsu base URL is valid")
+}
+
+/// Paths are relative to the API root, so one client reaches both `edge/`
+/// and `oauth/`.
+pub fn kitsu_client(base_url: &str) -> Result<RestClient<NoAuth>, url::ParseError> {
+    RestClient::new(base_url)
+}
+
+/// A client acting as the user who granted `access_token`.
+pub fn kitsu_user_client(
+    access_token: &str,
+    base_url: &str,
+) -> Result<RestClient<KitsuAuth>, url::ParseError> {
+    Ok(RestClient::new(base_url)?.with_auth(KitsuAuth {
+        access_token: access_token.to_string(),
+    }))
 }
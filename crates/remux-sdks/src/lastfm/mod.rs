@@ -0,0 +1,493 @@
+use crate::{Body, ClientError, Endpoint, RestClient};
+use http::Method;
+use serde::{Deserialize, Serialize};
+use serde_aux::prelude::deserialize_number_from_string;
+
+/// Where a user approves a desktop auth token.
+pub fn auth_url(api_key: &str, token: &str) -> String {
+    format!("https://www.last.fm/api/auth/?api_key={api_key}&token={token}")
+}
+
+/// An API account's key and shared secret. Every write and every auth call
+/// is signed with the secret, so endpoints carry it rather than the client.
+#[derive(Clone, Debug)]
+pub struct LastFmApp {
+    pub api_key: String,
+    pub secret: String,
+}
+
+impl LastFmApp {
+    /// `api_sig`: the md5 of every parameter, sorted by name and concatenated
+    /// as name then value, followed by the secret. `format` is not signed.
+    pub fn sign(&self, params: &[(String, String)]) -> String {
+        let mut sorted: Vec<&(String, String)> = params
+            .iter()
+            .filter(|(k, _)| k != "format" && k != "callback")
+            .collect();
+        sorted.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+        });
+        let mut raw = String::new();
+        for (k, v) in sorted {
+            raw.push_str(k);
+            raw.push_str(v);
+        }
+        raw.push_str(&self.secret);
+        format!("{:x}", md5::compute(raw.as_bytes()))
+    }
+
+    fn signed_form(
+        &self,
+        method: &str,
+        session_key: Option<&str>,
+        params: Vec<(&str, String)>,
+    ) -> Body {
+        let mut form: Vec<(String, String)> = params
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        form.push(("method".into(), method.into()));
+        form.push((
+            "api_key".into(),
+            self.api_key
+                .clone(),
+        ));
+        if let Some(sk) = session_key {
+            form.push(("sk".into(), sk.into()));
+        }
+        let sig = self.sign(&form);
+        form.push(("api_sig".into(), sig));
+        form.push(("format".into(), "json".into()));
+        Body::Form(form)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct LastFmErrorBody {
+    error: i64,
+    message: Option<String>,
+}
+
+/// Last.fm reports failures as `{"error": code, "message": ..}`, and the code
+/// is what tells a bad session from a busy server.
+fn map_error(status: u16, endpoint: &str, body: &str) -> ClientError {
+    let message = serde_json::from_str::<LastFmErrorBody>(body)
+        .ok()
+        .map(|e| {
+            format!(
+                "{} (error {})",
+                e.message
+                    .unwrap_or_default(),
+                e.error
+            )
+        })
+        .unwrap_or_else(|| "http error".to_string());
+    ClientError::Http {
+        status,
+        message,
+        endpoint: Some(endpoint.to_string()),
+        body: Some(body.to_string()),
+    }
+}
+
+/// The Last.fm error code behind a failed call, if it reported one.
+pub fn error_code(err: &ClientError) -> Option<i64> {
+    match err {
+        ClientError::Http {
+            body: Some(body), ..
+        } => serde_json::from_str::<LastFmErrorBody>(body)
+            .ok()
+            .map(|e| e.error),
+        _ => None,
+    }
+}
+
+/// Codes worth telling apart. The rest are permanent.
+pub mod codes {
+    pub const AUTHENTICATION_FAILED: i64 = 4;
+    pub const INVALID_SESSION_KEY: i64 = 9;
+    pub const INVALID_API_KEY: i64 = 10;
+    pub const SERVICE_OFFLINE: i64 = 11;
+    pub const TOKEN_NOT_AUTHORIZED: i64 = 14;
+    pub const TOKEN_EXPIRED: i64 = 15;
+    pub const TEMPORARILY_UNAVAILABLE: i64 = 16;
+    pub const SUSPENDED_API_KEY: i64 = 26;
+    pub const RATE_LIMIT_EXCEEDED: i64 = 29;
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TokenResponse {
+    pub token: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GetTokenEndpoint {
+    pub app: LastFmApp,
+}
+
+impl Endpoint for GetTokenEndpoint {
+    type Output = TokenResponse;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        self.app
+            .signed_form("auth.getToken", None, vec![])
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Session {
+    pub name: String,
+    /// Does not expire; valid until the user revokes the application.
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SessionResponse {
+    pub session: Session,
+}
+
+/// Trades an approved token for a session key.
+#[derive(Debug, Clone)]
+pub struct GetSessionEndpoint {
+    pub app: LastFmApp,
+    pub token: String,
+}
+
+impl Endpoint for GetSessionEndpoint {
+    type Output = SessionResponse;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        self.app
+            .signed_form(
+                "auth.getSession",
+                None,
+                vec![(
+                    "token",
+                    self.token
+                        .clone(),
+                )],
+            )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UserInfo {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UserInfoResponse {
+    pub user: UserInfo,
+}
+
+/// The user a session belongs to. Signed, so it fails on a revoked session.
+#[derive(Debug, Clone)]
+pub struct SessionUserEndpoint {
+    pub app: LastFmApp,
+    pub session_key: String,
+}
+
+impl Endpoint for SessionUserEndpoint {
+    type Output = UserInfoResponse;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        self.app
+            .signed_form("user.getInfo", Some(&self.session_key), vec![])
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LastFmTrack {
+    pub artist: String,
+    pub track: String,
+    pub album: Option<String>,
+    /// Seconds.
+    pub duration: Option<i64>,
+}
+
+impl LastFmTrack {
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            (
+                "artist",
+                self.artist
+                    .clone(),
+            ),
+            (
+                "track",
+                self.track
+                    .clone(),
+            ),
+        ];
+        if let Some(album) = &self.album {
+            params.push(("album", album.clone()));
+        }
+        if let Some(duration) = self.duration {
+            params.push(("duration", duration.to_string()));
+        }
+        params
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateNowPlayingEndpoint {
+    pub app: LastFmApp,
+    pub session_key: String,
+    pub track: LastFmTrack,
+}
+
+impl Endpoint for UpdateNowPlayingEndpoint {
+    type Output = serde_json::Value;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        self.app
+            .signed_form(
+                "track.updateNowPlaying",
+                Some(&self.session_key),
+                self.track
+                    .params(),
+            )
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ScrobbleCounts {
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub accepted: u32,
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub ignored: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Scrobbles {
+    #[serde(rename = "@attr", default)]
+    pub counts: ScrobbleCounts,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScrobbleResponse {
+    pub scrobbles: Scrobbles,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScrobbleEndpoint {
+    pub app: LastFmApp,
+    pub session_key: String,
+    pub track: LastFmTrack,
+    /// Unix seconds the track started playing.
+    pub timestamp: i64,
+}
+
+impl Endpoint for ScrobbleEndpoint {
+    type Output = ScrobbleResponse;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        let mut params = self
+            .track
+            .params();
+        params.push((
+            "timestamp",
+            self.timestamp
+                .to_string(),
+        ));
+        self.app
+            .signed_form("track.scrobble", Some(&self.session_key), params)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoveEndpoint {
+    pub app: LastFmApp,
+    pub session_key: String,
+    pub artist: String,
+    pub track: String,
+    /// `false` unloves.
+    pub love: bool,
+}
+
+impl Endpoint for LoveEndpoint {
+    type Output = serde_json::Value;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        let method = if self.love {
+            "track.love"
+        } else {
+            "track.unlove"
+        };
+        self.app
+            .signed_form(
+                method,
+                Some(&self.session_key),
+                vec![
+                    (
+                        "artist",
+                        self.artist
+                            .clone(),
+                    ),
+                    (
+                        "track",
+                        self.track
+                            .clone(),
+                    ),
+                ],
+            )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NamedRef {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LovedDate {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub uts: i64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LovedTrack {
+    pub name: String,
+    pub artist: NamedRef,
+    pub date: Option<LovedDate>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PageAttr {
+    #[serde(default, deserialize_with = "deserialize_number_from_string")]
+    pub page: u32,
+    #[serde(
+        rename = "totalPages",
+        default,
+        deserialize_with = "deserialize_number_from_string"
+    )]
+    pub total_pages: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LovedTracks {
+    #[serde(default)]
+    pub track: Vec<LovedTrack>,
+    #[serde(rename = "@attr", default)]
+    pub attr: PageAttr,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LovedTracksResponse {
+    pub lovedtracks: LovedTracks,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LovedTracksParams<'a> {
+    pub method: &'static str,
+    pub user: &'a str,
+    pub api_key: &'a str,
+    pub page: u32,
+    pub limit: u32,
+    pub format: &'static str,
+}
+
+/// Public, so unsigned.
+#[derive(Debug, Clone)]
+pub struct LovedTracksEndpoint {
+    pub api_key: String,
+    pub user: String,
+    pub page: u32,
+    pub limit: u32,
+}
+
+impl Endpoint for LovedTracksEndpoint {
+    type Output = LovedTracksResponse;
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn query_params(&self) -> impl serde::Serialize + '_ {
+        LovedTracksParams {
+            method: "user.getLovedTracks",
+            user: &self.user,
+            api_key: &self.api_key,
+            page: self.page,
+            limit: self.limit,
+            format: "json",
+        }
+    }
+}
+
+pub fn lastfm_client(base_url: &str) -> Result<RestClient, url::ParseError> {
+    Ok(RestClient::new(base_url)?.with_error_mapper(map_error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The worked example from the Last.fm authentication spec.
+    #[test]
+    fn signature_covers_sorted_params_and_secret_but_not_format() {
+        let app = LastFmApp {
+            api_key: "xxxxxxxxxx".into(),
+            secret: "mysecret".into(),
+        };
+        let params = vec![
+            ("method".to_string(), "auth.getSession".to_string()),
+            ("token".to_string(), "yyyyyy".to_string()),
+            ("api_key".to_string(), "xxxxxxxxxx".to_string()),
+            ("format".to_string(), "json".to_string()),
+        ];
+        let expected = format!(
+            "{:x}",
+            md5::compute("api_keyxxxxxxxxxxmethodauth.getSessiontokenyyyyyymysecret")
+        );
+        assert_eq!(app.sign(&params), expected);
+    }
+}
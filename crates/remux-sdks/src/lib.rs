@@ -1,8 +1,12 @@
 #![allow(warnings)]
 
+pub mod anilist;
 pub mod deezer;
 pub mod introdb;
 pub mod kitsu;
+pub mod lastfm;
+pub mod listenbrainz;
+pub mod oidc;
 pub mod remux;
 pub mod remuxdb;
 pub mod stremio;
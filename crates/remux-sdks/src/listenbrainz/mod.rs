@@ -0,0 +1,163 @@
+use crate::{Auth, Body, Endpoint, RestClient};
+use http::Method;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+#[derive(Clone, Debug)]
+pub struct ListenBrainzAuth {
+    /// The user token from listenbrainz.org/settings.
+    pub token: String,
+}
+
+impl Auth for ListenBrainzAuth {
+    fn apply(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        req.header("Authorization", format!("Token {}", self.token))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TokenValidation {
+    #[serde(default)]
+    pub valid: bool,
+    pub user_name: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidateTokenEndpoint;
+
+impl Endpoint for ValidateTokenEndpoint {
+    type Output = TokenValidation;
+
+    fn path(&self) -> String {
+        "1/validate-token".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenType {
+    /// Replaces the user's now-playing; never stored.
+    PlayingNow,
+    /// One finished listen.
+    Single,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct AdditionalInfo {
+    pub duration_ms: Option<i64>,
+    pub submission_client: Option<String>,
+    pub media_player: Option<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TrackMetadata {
+    pub artist_name: String,
+    pub track_name: String,
+    pub release_name: Option<String>,
+    pub additional_info: Option<AdditionalInfo>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Listen {
+    /// Unix seconds the listen started. Omitted for `PlayingNow`.
+    pub listened_at: Option<i64>,
+    pub track_metadata: TrackMetadata,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SubmitResponse {
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubmitListensEndpoint {
+    pub listen_type: ListenType,
+    pub listens: Vec<Listen>,
+}
+
+impl Endpoint for SubmitListensEndpoint {
+    type Output = SubmitResponse;
+
+    fn path(&self) -> String {
+        "1/submit-listens".to_string()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        Body::Json(serde_json::json!({
+            "listen_type": self.listen_type,
+            "payload": self.listens,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Feedback {
+    /// Unix seconds.
+    pub created: Option<i64>,
+    pub recording_mbid: Option<String>,
+    pub recording_msid: Option<String>,
+    pub score: i32,
+    pub track_metadata: Option<TrackMetadata>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FeedbackPage {
+    #[serde(default)]
+    pub feedback: Vec<Feedback>,
+    #[serde(default)]
+    pub total_count: u32,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedbackParams {
+    pub score: i32,
+    pub count: u32,
+    pub offset: u32,
+    /// Without this the rows carry only MusicBrainz ids, not names.
+    pub metadata: bool,
+}
+
+/// A user's loved (`score = 1`) or hated (`-1`) recordings.
+#[derive(Debug, Clone)]
+pub struct UserFeedbackEndpoint {
+    pub user_name: String,
+    pub score: i32,
+    pub count: u32,
+    pub offset: u32,
+}
+
+impl Endpoint for UserFeedbackEndpoint {
+    type Output = FeedbackPage;
+
+    fn path(&self) -> String {
+        format!("1/feedback/user/{}/get-feedback", self.user_name)
+    }
+
+    fn query_params(&self) -> impl serde::Serialize + '_ {
+        FeedbackParams {
+            score: self.score,
+            count: self.count,
+            offset: self.offset,
+            metadata: true,
+        }
+    }
+}
+
+pub fn listenbrainz_client(
+    token: &str,
+    base_url: &str,
+) -> Result<RestClient<ListenBrainzAuth>, url::ParseError> {
+    Ok(RestClient::new(base_url)?.with_auth(ListenBrainzAuth {
+        token: token.to_string(),
+    }))
+}
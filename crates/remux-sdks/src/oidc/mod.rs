@@ -0,0 +1,160 @@
+use crate::{Body, Endpoint};
+use http::Method;
+use serde::{Deserialize, Serialize};
+
+/// The parts of `/.well-known/openid-configuration` a relying party needs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    #[serde(default)]
+    pub userinfo_endpoint: Option<String>,
+    #[serde(default)]
+    pub end_session_endpoint: Option<String>,
+    /// Where the keys that sign ID tokens are published.
+    #[serde(default)]
+    pub jwks_uri: Option<String>,
+}
+
+/// Discovery document, relative to a client built on the issuer URL.
+#[derive(Debug, Clone)]
+pub struct DiscoveryEndpoint;
+
+impl Endpoint for DiscoveryEndpoint {
+    type Output = ProviderMetadata;
+
+    fn path(&self) -> String {
+        ".well-known/openid-configuration".to_string()
+    }
+}
+
+/// A JSON Web Key (RFC 7517). Only the members used to verify signatures
+/// are kept.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default)]
+    pub kid: Option<String>,
+    #[serde(default)]
+    pub alg: Option<String>,
+    #[serde(default, rename = "use")]
+    pub use_: Option<String>,
+    /// RSA modulus and exponent.
+    #[serde(default)]
+    pub n: Option<String>,
+    #[serde(default)]
+    pub e: Option<String>,
+    /// Elliptic curve name and coordinates; OKP keys only use `crv` and `x`.
+    #[serde(default)]
+    pub crv: Option<String>,
+    #[serde(default)]
+    pub x: Option<String>,
+    #[serde(default)]
+    pub y: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+/// The provider's signing keys.
+#[derive(Debug, Clone)]
+pub struct JwksEndpoint {
+    /// Absolute URL from the discovery document.
+    pub jwks_uri: String,
+}
+
+impl Endpoint for JwksEndpoint {
+    type Output = JwkSet;
+
+    fn path(&self) -> String {
+        self.jwks_uri
+            .clone()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub token_type: Option<String>,
+    #[serde(default)]
+    pub id_token: Option<String>,
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+}
+
+/// Authorization-code grant with a PKCE verifier. The client authenticates
+/// with `client_secret_post` when it has a secret, and as a public client
+/// otherwise.
+#[derive(Debug, Clone)]
+pub struct AuthorizationCodeEndpoint {
+    /// Absolute URL from the discovery document.
+    pub token_endpoint: String,
+    pub code: String,
+    pub redirect_uri: String,
+    pub code_verifier: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+}
+
+impl Endpoint for AuthorizationCodeEndpoint {
+    type Output = TokenResponse;
+
+    fn path(&self) -> String {
+        self.token_endpoint
+            .clone()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        let mut form = vec![
+            ("grant_type".into(), "authorization_code".into()),
+            (
+                "code".into(),
+                self.code
+                    .clone(),
+            ),
+            (
+                "redirect_uri".into(),
+                self.redirect_uri
+                    .clone(),
+            ),
+            (
+                "code_verifier".into(),
+                self.code_verifier
+                    .clone(),
+            ),
+            (
+                "client_id".into(),
+                self.client_id
+                    .clone(),
+            ),
+        ];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret".into(), secret.clone()));
+        }
+        Body::Form(form)
+    }
+}
+
+/// Userinfo claims. Send with a `BearerAuth` holding the access token.
+#[derive(Debug, Clone)]
+pub struct UserInfoEndpoint {
+    /// Absolute URL from the discovery document.
+    pub userinfo_endpoint: String,
+}
+
+impl Endpoint for UserInfoEndpoint {
+    type Output = serde_json::Map<String, serde_json::Value>;
+
+    fn path(&self) -> String {
+        self.userinfo_endpoint
+            .clone()
+    }
+}
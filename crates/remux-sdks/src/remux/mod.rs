@@ -529,6 +529,10 @@ pub struct ServerConfiguration {
     /// Items shorter than this are never shown in continue-watching. Default: 90.
     #[default(Some(90_i64))]
     pub min_resume_duration_seconds: Option<i64>,
+    /// Bitrate cap (bits/s) for clients outside the local network, unless the
+    /// user's policy sets its own. 0 = unlimited.
+    #[default(Some(0_i64))]
+    pub remote_client_bitrate_limit: Option<i64>,
 }
 
 #[derive(
@@ -658,11 +662,28 @@ pub struct EncodingOptions {
     /// Defaults to false.
     #[default(Some(false))]
     pub normalize_audio_loudness: Option<bool>,
+    /// Offer several renditions (1080p/720p/480p/360p, capped at the source)
+    /// in the HLS master playlist when video is transcoded, so players can
+    /// step down on slow connections. Each rendition is encoded only once a
+    /// client requests its segments.
+    #[default(Some(false))]
+    pub enable_adaptive_bitrate_streaming: Option<bool>,
+    /// Apply a track's ReplayGain track gain when transcoding music. Off by
+    /// default, as most clients already apply `NormalizationGain` themselves.
+    #[default(Some(false))]
+    pub apply_replay_gain: Option<bool>,
     /// Controls how embedded subtitle streams unsupported by the client are handled.
     /// Burn: encode into video (default). Extract: serve via Stream.js/VTT endpoint.
     /// Strip: remove from media source so the client never sees them.
     #[default(Some(EmbeddedSubtitleHandling::Burn))]
     pub subtitle_mode: Option<EmbeddedSubtitleHandling>,
+    /// Milliseconds between trickplay (seek preview) thumbnails.
+    #[default(Some(10_000u32))]
+    pub trickplay_interval_ms: Option<u32>,
+    /// Width in pixels of each trickplay thumbnail; height follows the video
+    /// aspect ratio.
+    #[default(Some(320u32))]
+    pub trickplay_width: Option<u32>,
 }
 
 // --- Embedded subtitle handling ---
@@ -749,6 +770,91 @@ pub struct IntroOptions {
     pub skip_resume: bool,
 }
 
+/// OpenID Connect sign-in settings.
+#[dto]
+pub struct OidcOptions {
+    pub enabled: bool,
+    /// Issuer URL. The discovery document is read from
+    /// `{issuer_url}/.well-known/openid-configuration`.
+    pub issuer_url: String,
+    pub client_id: String,
+    /// Leave empty for a public client.
+    pub client_secret: Option<String>,
+    /// Scopes requested besides `openid`, space separated.
+    #[default("profile email groups".to_string())]
+    pub scopes: String,
+    /// Claim used as the remux username.
+    #[default("preferred_username".to_string())]
+    pub username_claim: String,
+    /// Claim holding the user's groups, as a list or a single string.
+    #[default("groups".to_string())]
+    pub groups_claim: String,
+    /// When non-empty, only members of one of these groups may sign in.
+    pub allowed_groups: Vec<String>,
+    /// Members of one of these groups are admins and everyone else is not.
+    /// When empty, the admin flag is left to the dashboard.
+    pub admin_groups: Vec<String>,
+    /// Create a remux user on a first sign-in that matches no existing one.
+    #[default(true)]
+    pub auto_provision: bool,
+    /// Callback URL registered with the provider. Derived from the request
+    /// (`{origin}/sso/oidc/callback`) when unset.
+    pub redirect_uri: Option<String>,
+    #[default("Sign in with SSO".to_string())]
+    pub button_label: String,
+}
+
+/// What the login pages need to offer OpenID Connect sign-in.
+#[dto]
+pub struct OidcPublicInfo {
+    pub enabled: bool,
+    pub button_label: String,
+}
+
+#[dto]
+pub struct AuthenticateWithOidc {
+    /// One-time code handed to the browser by `/sso/oidc/callback`.
+    pub code: String,
+}
+
+/// LDAP sign-in settings.
+#[dto]
+pub struct LdapOptions {
+    pub enabled: bool,
+    /// `ldap://` or `ldaps://` URL of the directory server.
+    pub server_url: String,
+    /// Upgrade an `ldap://` connection with StartTLS.
+    pub start_tls: bool,
+    /// Account used to search for users. Searches anonymously when unset.
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    /// Where user entries are searched, e.g. `ou=people,dc=example,dc=com`.
+    pub base_dn: String,
+    /// Search filter. `{username}` is replaced with the escaped login name.
+    #[default("(uid={username})".to_string())]
+    pub user_filter: String,
+    /// Attribute used as the remux username.
+    #[default("uid".to_string())]
+    pub username_attribute: String,
+    /// Attribute listing the groups an entry belongs to.
+    #[default("memberOf".to_string())]
+    pub group_attribute: String,
+    /// Members of this group DN are admins and everyone else is not.
+    /// When unset, the admin flag is left to the dashboard.
+    pub admin_group: Option<String>,
+    /// Create a remux user on a first sign-in that matches no existing one.
+    #[default(true)]
+    pub auto_provision: bool,
+}
+
+/// DLNA media server settings.
+#[dto]
+pub struct DlnaOptions {
+    /// The user renderers browse as, with that user's libraries and parental
+    /// limits. Renderers see nothing while it is unset.
+    pub default_user_id: Option<Uuid>,
+}
+
 // --- Jellyfin import models (used to consume a remote Jellyfin server) ---
 #[dto]
 pub struct JellyfinUserPolicy {
@@ -2776,12 +2882,328 @@ impl Default for UserDto {
 #[serde(rename_all = "PascalCase")]
 #[strum(serialize_all = "PascalCase")]
 pub enum SyncPlayUserAccessType {
+    #[default]
     CreateAndJoinGroups,
     JoinGroups,
-    #[default]
     None,
 }
 
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum GroupStateType {
+    #[default]
+    Idle,
+    Waiting,
+    Paused,
+    Playing,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum GroupUpdateType {
+    UserJoined,
+    UserLeft,
+    GroupJoined,
+    GroupLeft,
+    #[default]
+    StateUpdate,
+    PlayQueue,
+    NotInGroup,
+    GroupDoesNotExist,
+    LibraryAccessDenied,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum SendCommandType {
+    Unpause,
+    #[default]
+    Pause,
+    Stop,
+    Seek,
+}
+
+/// The request that moved a SyncPlay group into its current state.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum PlaybackRequestType {
+    #[default]
+    Play,
+    SetPlaylistItem,
+    RemoveFromPlaylist,
+    MovePlaylistItem,
+    Queue,
+    Unpause,
+    Pause,
+    Stop,
+    Seek,
+    Buffer,
+    Ready,
+    NextItem,
+    PreviousItem,
+    SetRepeatMode,
+    SetShuffleMode,
+    Ping,
+    IgnoreWait,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum PlayQueueUpdateReason {
+    #[default]
+    NewPlaylist,
+    SetCurrentItem,
+    RemoveItems,
+    MoveItem,
+    Queue,
+    QueueNext,
+    NextItem,
+    PreviousItem,
+    RepeatMode,
+    ShuffleMode,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum GroupRepeatMode {
+    RepeatOne,
+    RepeatAll,
+    #[default]
+    RepeatNone,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum GroupShuffleMode {
+    #[default]
+    Sorted,
+    Shuffle,
+}
+
+#[dto]
+pub struct GroupInfoDto {
+    pub group_id: Uuid,
+    pub group_name: String,
+    pub state: GroupStateType,
+    /// Member user names.
+    pub participants: Vec<String>,
+    pub last_updated_at: DateTime<Utc>,
+}
+
+#[dto]
+pub struct SyncPlayQueueItem {
+    pub item_id: Uuid,
+    /// Tells two entries of the same item apart.
+    pub playlist_item_id: Uuid,
+}
+
+#[dto]
+pub struct PlayQueueUpdate {
+    pub reason: PlayQueueUpdateReason,
+    pub last_update: DateTime<Utc>,
+    pub playlist: Vec<SyncPlayQueueItem>,
+    pub playing_item_index: i32,
+    pub start_position_ticks: i64,
+    pub is_playing: bool,
+    pub shuffle_mode: GroupShuffleMode,
+    pub repeat_mode: GroupRepeatMode,
+}
+
+#[dto]
+pub struct GroupStateUpdate {
+    pub state: GroupStateType,
+    pub reason: PlaybackRequestType,
+}
+
+/// Sent as `SyncPlayCommand`: every member carries out `command` at `when`.
+#[dto]
+pub struct SendCommand {
+    pub group_id: Uuid,
+    pub playlist_item_id: Uuid,
+    pub when: DateTime<Utc>,
+    pub position_ticks: Option<i64>,
+    pub command: SendCommandType,
+    pub emitted_at: DateTime<Utc>,
+}
+
+/// Sent as `SyncPlayGroupUpdate`. `data` depends on `kind`: a user name for
+/// joins and leaves, a `GroupInfoDto`, `GroupStateUpdate` or `PlayQueueUpdate`
+/// for the rest.
+#[dto]
+pub struct GroupUpdate {
+    pub group_id: Uuid,
+    #[serde(rename = "Type")]
+    pub kind: GroupUpdateType,
+    pub data: serde_json::Value,
+}
+
+#[dto]
+pub struct NewGroupRequestDto {
+    pub group_name: String,
+}
+
+#[dto]
+pub struct JoinGroupRequestDto {
+    pub group_id: Uuid,
+}
+
+#[dto]
+pub struct SeekRequestDto {
+    pub position_ticks: i64,
+}
+
+/// Body of both `Buffering` and `Ready`.
+#[dto]
+pub struct BufferRequestDto {
+    pub when: Option<DateTime<Utc>>,
+    pub position_ticks: i64,
+    pub is_playing: bool,
+    pub playlist_item_id: Option<Uuid>,
+}
+
+#[dto]
+pub struct PlayRequestDto {
+    pub playing_queue: Vec<Uuid>,
+    pub playing_item_position: i32,
+    pub start_position_ticks: i64,
+}
+
+#[dto]
+pub struct PingRequestDto {
+    /// Round trip in milliseconds.
+    pub ping: i64,
+}
+
+// --- Live TV DVR ---
+
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::Display,
+)]
+#[serde(rename_all = "PascalCase")]
+#[strum(serialize_all = "PascalCase")]
+pub enum RecordingStatus {
+    #[default]
+    New,
+    InProgress,
+    Completed,
+    Cancelled,
+    ConflictedOk,
+    ConflictedNotOk,
+    Error,
+}
+
+/// A scheduled recording of one program.
+#[dto]
+pub struct TimerInfoDto {
+    pub id: Option<String>,
+    #[serde(rename = "Type")]
+    #[default(Some("Timer".to_string()))]
+    pub kind: Option<String>,
+    #[default(Some("remux".to_string()))]
+    pub server_id: Option<String>,
+    pub channel_id: Option<Uuid>,
+    pub channel_name: Option<String>,
+    pub program_id: Option<String>,
+    pub series_timer_id: Option<String>,
+    pub name: Option<String>,
+    pub overview: Option<String>,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    #[default(Some("remux".to_string()))]
+    pub service_name: Option<String>,
+    pub priority: i32,
+    pub pre_padding_seconds: i32,
+    pub post_padding_seconds: i32,
+    pub is_pre_padding_required: bool,
+    pub is_post_padding_required: bool,
+    pub status: RecordingStatus,
+    pub run_time_ticks: Option<i64>,
+    pub program_info: Option<Box<BaseItemDto>>,
+}
+
+/// A rule that schedules a timer for every airing of a series.
+#[dto]
+pub struct SeriesTimerInfoDto {
+    pub id: Option<String>,
+    #[serde(rename = "Type")]
+    #[default(Some("SeriesTimer".to_string()))]
+    pub kind: Option<String>,
+    #[default(Some("remux".to_string()))]
+    pub server_id: Option<String>,
+    pub channel_id: Option<Uuid>,
+    pub channel_name: Option<String>,
+    /// The program the rule was created from.
+    pub program_id: Option<String>,
+    /// Series title; airings are matched on it.
+    pub name: Option<String>,
+    pub overview: Option<String>,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    #[default(Some("remux".to_string()))]
+    pub service_name: Option<String>,
+    pub priority: i32,
+    pub pre_padding_seconds: i32,
+    pub post_padding_seconds: i32,
+    pub is_pre_padding_required: bool,
+    pub is_post_padding_required: bool,
+    #[default(true)]
+    pub record_any_time: bool,
+    pub record_any_channel: bool,
+    pub record_new_only: bool,
+    pub skip_episodes_in_library: bool,
+    pub keep_up_to: i32,
+    #[serde(default)]
+    pub days: Vec<String>,
+}
+
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::Display,
+)]
+#[serde(rename_all = "PascalCase")]
+#[strum(serialize_all = "PascalCase")]
+pub enum DynamicDayOfWeek {
+    #[default]
+    Sunday,
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Everyday,
+    Weekday,
+    Weekend,
+}
+
+/// A window in which a user may sign in and start playback. Hours are in
+/// server local time; `end_hour` is exclusive.
+#[dto]
+pub struct AccessSchedule {
+    pub id: i32,
+    pub user_id: Uuid,
+    pub day_of_week: DynamicDayOfWeek,
+    pub start_hour: f64,
+    pub end_hour: f64,
+}
+
 #[dto]
 pub struct UserPolicy {
     pub is_administrator: bool,
@@ -2812,7 +3234,10 @@ pub struct UserPolicy {
     #[default(true)]
     pub enable_user_preference_access: bool,
     #[serde(default)]
-    pub access_schedules: Vec<String>,
+    pub access_schedules: Vec<AccessSchedule>,
+    /// Minutes of playback allowed per day, in server local time. 0 = unlimited.
+    #[serde(default)]
+    pub daily_watch_time_limit_minutes: i64,
     #[serde(default)]
     pub block_unrated_items: Vec<String>,
     pub enable_remote_control_of_other_users: bool,
@@ -2873,7 +3298,7 @@ pub struct UserPolicy {
     #[serde(default = "default_password_reset_provider_id")]
     pub password_reset_provider_id: String,
     #[serde(default, deserialize_with = "deserialize_optional_with_default")]
-    #[default(SyncPlayUserAccessType::None)]
+    #[default(SyncPlayUserAccessType::CreateAndJoinGroups)]
     pub sync_play_access: SyncPlayUserAccessType,
 }
 
@@ -3037,6 +3462,18 @@ pub struct ProviderIds {
     pub imdb: Option<String>,
     pub tmdb: Option<String>,
     pub tvdb: Option<String>,
+    #[serde(rename = "MusicBrainzRecording")]
+    pub musicbrainz_recording: Option<String>,
+    #[serde(rename = "MusicBrainzTrack")]
+    pub musicbrainz_track: Option<String>,
+    #[serde(rename = "MusicBrainzAlbum")]
+    pub musicbrainz_album: Option<String>,
+    #[serde(rename = "MusicBrainzReleaseGroup")]
+    pub musicbrainz_release_group: Option<String>,
+    #[serde(rename = "MusicBrainzArtist")]
+    pub musicbrainz_artist: Option<String>,
+    #[serde(rename = "MusicBrainzAlbumArtist")]
+    pub musicbrainz_album_artist: Option<String>,
 }
 
 #[dto]
@@ -3358,6 +3795,24 @@ pub struct RemuxInfo {
     pub collection_default_sort_order: Option<Vec<SortOrder>>,
 }
 
+/// One resolution of trickplay tile sheets for a media source.
+#[dto]
+pub struct TrickplayInfo {
+    /// Thumbnail width in pixels.
+    pub width: u32,
+    /// Thumbnail height in pixels.
+    pub height: u32,
+    /// Thumbnails per row of a tile sheet.
+    pub tile_width: u32,
+    /// Thumbnails per column of a tile sheet.
+    pub tile_height: u32,
+    pub thumbnail_count: u32,
+    /// Milliseconds between thumbnails.
+    pub interval: u32,
+    /// Peak bits per second of the tile sheets, for the HLS playlist.
+    pub bandwidth: u32,
+}
+
 #[dto]
 pub struct BaseItemDto {
     pub id: Uuid,
@@ -3466,6 +3921,8 @@ pub struct BaseItemDto {
     pub album_artist: Option<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub album_artists: Vec<NameIdPair>,
+    /// ReplayGain track gain in dB.
+    pub normalization_gain: Option<f32>,
     pub season_name: Option<String>,
     pub media_streams: Option<Vec<MediaStream>>,
     pub video_type: Option<VideoType>,
@@ -3475,6 +3932,8 @@ pub struct BaseItemDto {
     #[serde(default)]
     pub backdrop_image_tags: Vec<String>,
     pub image_blur_hashes: Option<ImageBlurHashes>,
+    /// Trickplay sheets keyed by media source id, then thumbnail width.
+    pub trickplay: Option<HashMap<String, HashMap<u32, TrickplayInfo>>>,
     pub screenshot_image_tags: Option<Vec<String>>,
     pub parent_thumb_item_id: Option<Uuid>,
     pub parent_thumb_image_tag: Option<String>,
@@ -4228,6 +4687,13 @@ pub struct HlsVideoQuery {
     /// Length of this segment in ticks.
     #[serde(alias = "actualSegmentLengthTicks")]
     pub actual_segment_length_ticks: Option<i64>,
+    /// `false` opts a client out of the server's ABR ladder.
+    #[serde(alias = "enableAdaptiveBitrateStreaming")]
+    pub enable_adaptive_bitrate_streaming: Option<bool>,
+    #[serde(alias = "maxAudioChannels")]
+    pub max_audio_channels: Option<i32>,
+    #[serde(alias = "audioSampleRate")]
+    pub audio_sample_rate: Option<i32>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -5389,6 +5855,113 @@ impl Endpoint for UpdateIntroConfiguration {
     }
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct GetOidcConfiguration;
+
+impl Endpoint for GetOidcConfiguration {
+    type Output = OidcOptions;
+    fn path(&self) -> String {
+        "/system/configuration/oidc".into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateOidcConfiguration {
+    pub config: OidcOptions,
+}
+
+impl Endpoint for UpdateOidcConfiguration {
+    type Output = ();
+    fn path(&self) -> String {
+        "/system/configuration/oidc".into()
+    }
+    fn method(&self) -> Method {
+        Method::POST
+    }
+    fn body(&self) -> Body {
+        Body::Json(serde_json::to_value(&self.config).unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GetLdapConfiguration;
+
+impl Endpoint for GetLdapConfiguration {
+    type Output = LdapOptions;
+    fn path(&self) -> String {
+        "/system/configuration/ldap".into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateLdapConfiguration {
+    pub config: LdapOptions,
+}
+
+impl Endpoint for UpdateLdapConfiguration {
+    type Output = ();
+    fn path(&self) -> String {
+        "/system/configuration/ldap".into()
+    }
+    fn method(&self) -> Method {
+        Method::POST
+    }
+    fn body(&self) -> Body {
+        Body::Json(serde_json::to_value(&self.config).unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GetDlnaConfiguration;
+
+impl Endpoint for GetDlnaConfiguration {
+    type Output = DlnaOptions;
+    fn path(&self) -> String {
+        "/system/configuration/dlna".into()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateDlnaConfiguration {
+    pub config: DlnaOptions,
+}
+
+impl Endpoint for UpdateDlnaConfiguration {
+    type Output = ();
+    fn path(&self) -> String {
+        "/system/configuration/dlna".into()
+    }
+    fn method(&self) -> Method {
+        Method::POST
+    }
+    fn body(&self) -> Body {
+        Body::Json(serde_json::to_value(&self.config).unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GetOidcInfo;
+
+impl Endpoint for GetOidcInfo {
+    type Output = OidcPublicInfo;
+    fn path(&self) -> String {
+        "/sso/oidc/info".into()
+    }
+}
+
+impl Endpoint for AuthenticateWithOidc {
+    type Output = AuthenticationResult;
+    fn path(&self) -> String {
+        "/sso/oidc/authenticate".into()
+    }
+    fn method(&self) -> Method {
+        Method::POST
+    }
+    fn body(&self) -> Body {
+        Body::Json(serde_json::to_value(self).unwrap_or_default())
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct GetStartupConfiguration;
 
@@ -6436,13 +7009,16 @@ mod tests {
     use super::*;
 
     #[test]
-    fn user_policy_does_not_advertise_unimplemented_sync_play() {
+    fn user_policy_lets_users_create_sync_play_groups() {
         assert_eq!(
             UserPolicy::default().sync_play_access,
-            SyncPlayUserAccessType::None
+            SyncPlayUserAccessType::CreateAndJoinGroups
         );
         let policy: UserPolicy = serde_json::from_str("{}").unwrap();
-        assert_eq!(policy.sync_play_access, SyncPlayUserAccessType::None);
+        assert_eq!(
+            policy.sync_play_access,
+            SyncPlayUserAccessType::CreateAndJoinGroups
+        );
     }
 
     #[test]
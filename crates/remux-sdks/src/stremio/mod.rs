@@ -464,6 +464,40 @@ pub struct Meta {
 }
 
 impl Meta {
+    /// An otherwise empty meta, for addons that publish their own.
+    pub fn new(id: String, media_type: MediaType) -> Self {
+        Self {
+            imdb_id: None,
+            country: None,
+            director: None,
+            cast: None,
+            writer: None,
+            description: None,
+            genre: None,
+            imdb_rating: None,
+            name: None,
+            title: None,
+            status: None,
+            released: None,
+            slug: None,
+            media_type,
+            certification: None,
+            moviedb_id: None,
+            trailers: None,
+            background: None,
+            logo: None,
+            poster: None,
+            thumbnail: None,
+            awards: None,
+            popularity: None,
+            id,
+            genres: None,
+            runtime: None,
+            videos: None,
+            app_extras: None,
+        }
+    }
+
     /// Fetch the full meta from AIO and replace `self` with it.
     /// Catalog responses are often partial (missing `imdb_id` etc.); calling
     /// this upgrades the item to complete metadata before DB conversion.
@@ -728,7 +762,7 @@ impl Meta {
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Episode {
     pub id: String,
@@ -794,7 +828,7 @@ pub struct StreamsResponse {
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Stream {
     pub info_hash: Option<String>,
@@ -1,24 +1,38 @@
-use crate::{Auth, Endpoint, RestClient};
+use crate::{Auth, Body, Endpoint, RestClient};
+use chrono::{DateTime, Utc};
+use http::Method;
 use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
 
 #[derive(Clone, Debug)]
 pub struct TraktAuth {
     pub client_id: String,
+    /// A user's OAuth token. Public endpoints work without one.
+    pub access_token: Option<String>,
 }
 
 impl Auth for TraktAuth {
     fn apply(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        req.header("trakt-api-key", &self.client_id)
+        let req = req
+            .header("trakt-api-key", &self.client_id)
             .header("trakt-api-version", "2")
             .header("Content-Type", "application/json")
             .header("Accept", "application/json")
-            .header("User-Agent", "Mozilla/5.0 (compatible; remux/1.0)")
+            .header("User-Agent", "Mozilla/5.0 (compatible; remux/1.0)");
+        match &self.access_token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct TraktItemIds {
+    pub trakt: Option<i64>,
     pub imdb: Option<String>,
+    pub tmdb: Option<i64>,
+    pub tvdb: Option<i64>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -114,11 +128,545 @@ impl Endpoint for ShowStatsEndpoint {
     }
 }
 
+// --- OAuth (device code) ---
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeviceCode {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_url: String,
+    /// Seconds.
+    pub expires_in: u64,
+    /// Seconds between polls.
+    pub interval: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceCodeEndpoint {
+    pub client_id: String,
+}
+
+impl Endpoint for DeviceCodeEndpoint {
+    type Output = DeviceCode;
+
+    fn path(&self) -> String {
+        "oauth/device/code".to_string()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        Body::Json(serde_json::json!({ "client_id": self.client_id }))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TraktToken {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Seconds from `created_at`.
+    pub expires_in: i64,
+    /// Unix seconds.
+    pub created_at: i64,
+}
+
+impl TraktToken {
+    /// Unix seconds after which `access_token` is rejected.
+    pub fn expires_at(&self) -> i64 {
+        self.created_at + self.expires_in
+    }
+}
+
+/// Polled until the user approves. Trakt answers 400 while the code is still
+/// pending, 429 when polled too fast, and 404/409/410/418 once the code is
+/// unusable.
+#[derive(Debug, Clone)]
+pub struct DeviceTokenEndpoint {
+    pub device_code: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl Endpoint for DeviceTokenEndpoint {
+    type Output = TraktToken;
+
+    fn path(&self) -> String {
+        "oauth/device/token".to_string()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        Body::Json(serde_json::json!({
+            "code": self.device_code,
+            "client_id": self.client_id,
+            "client_secret": self.client_secret,
+        }))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RefreshTokenEndpoint {
+    pub refresh_token: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl Endpoint for RefreshTokenEndpoint {
+    type Output = TraktToken;
+
+    fn path(&self) -> String {
+        "oauth/token".to_string()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        Body::Json(serde_json::json!({
+            "refresh_token": self.refresh_token,
+            "client_id": self.client_id,
+            "client_secret": self.client_secret,
+            "redirect_uri": "urn:ietf:wg:oauth:2.0:oob",
+            "grant_type": "refresh_token",
+        }))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RevokeTokenEndpoint {
+    pub token: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl Endpoint for RevokeTokenEndpoint {
+    type Output = ();
+
+    fn path(&self) -> String {
+        "oauth/revoke".to_string()
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        Body::Json(serde_json::json!({
+            "token": self.token,
+            "client_id": self.client_id,
+            "client_secret": self.client_secret,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TraktUser {
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct UserSettings {
+    pub user: TraktUser,
+}
+
+#[derive(Debug, Clone)]
+pub struct UserSettingsEndpoint;
+
+impl Endpoint for UserSettingsEndpoint {
+    type Output = UserSettings;
+
+    fn path(&self) -> String {
+        "users/settings".to_string()
+    }
+}
+
+// --- Media objects ---
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TraktMovie {
+    pub title: Option<String>,
+    pub year: Option<i32>,
+    pub ids: TraktItemIds,
+    /// Minutes. Only present with `extended=full`.
+    pub runtime: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TraktShow {
+    pub title: Option<String>,
+    pub year: Option<i32>,
+    pub ids: TraktItemIds,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TraktEpisode {
+    pub season: i64,
+    pub number: i64,
+    pub ids: TraktItemIds,
+    /// Minutes. Only present with `extended=full`.
+    pub runtime: Option<i64>,
+}
+
+// --- Scrobble ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum ScrobbleAction {
+    Start,
+    Pause,
+    Stop,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScrobbleEpisode {
+    pub season: Option<i64>,
+    pub number: Option<i64>,
+    pub ids: Option<TraktItemIds>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrobbleRef {
+    pub ids: TraktItemIds,
+}
+
+/// Either `movie`, or `episode` with its `show` (or episode ids of its own).
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScrobbleBody {
+    pub movie: Option<ScrobbleRef>,
+    pub show: Option<ScrobbleRef>,
+    pub episode: Option<ScrobbleEpisode>,
+    /// Percent watched, 0-100.
+    pub progress: f64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScrobbleResponse {
+    pub action: Option<String>,
+    pub progress: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScrobbleEndpoint {
+    pub action: ScrobbleAction,
+    pub body: ScrobbleBody,
+}
+
+impl Endpoint for ScrobbleEndpoint {
+    type Output = ScrobbleResponse;
+
+    fn path(&self) -> String {
+        format!("scrobble/{}", self.action)
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        Body::Json(serde_json::to_value(&self.body).unwrap_or_default())
+    }
+}
+
+// --- Sync (writes) ---
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncEntry {
+    pub ids: TraktItemIds,
+    pub watched_at: Option<DateTime<Utc>>,
+    pub rating: Option<i64>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncEpisodeNumber {
+    pub number: i64,
+    pub watched_at: Option<DateTime<Utc>>,
+    pub rating: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncSeason {
+    pub number: i64,
+    pub episodes: Vec<SyncEpisodeNumber>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncShow {
+    pub ids: TraktItemIds,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub seasons: Vec<SyncSeason>,
+    pub watched_at: Option<DateTime<Utc>>,
+    pub rating: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncItems {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub movies: Vec<SyncEntry>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shows: Vec<SyncShow>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub episodes: Vec<SyncEntry>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum SyncList {
+    History,
+    Ratings,
+    Watchlist,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SyncNotFound {
+    #[serde(default)]
+    pub movies: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub shows: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub seasons: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub episodes: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SyncResponse {
+    #[serde(default)]
+    pub not_found: SyncNotFound,
+}
+
+impl SyncResponse {
+    /// Whether Trakt could not match something in the request. It still
+    /// answers 201 in that case.
+    pub fn missed_any(&self) -> bool {
+        !(self
+            .not_found
+            .movies
+            .is_empty()
+            && self
+                .not_found
+                .shows
+                .is_empty()
+            && self
+                .not_found
+                .seasons
+                .is_empty()
+            && self
+                .not_found
+                .episodes
+                .is_empty())
+    }
+}
+
+/// Adds `items` to `list`, or removes them when `remove` is set.
+#[derive(Debug, Clone)]
+pub struct SyncEndpoint {
+    pub list: SyncList,
+    pub remove: bool,
+    pub items: SyncItems,
+}
+
+impl Endpoint for SyncEndpoint {
+    type Output = SyncResponse;
+
+    fn path(&self) -> String {
+        if self.remove {
+            format!("sync/{}/remove", self.list)
+        } else {
+            format!("sync/{}", self.list)
+        }
+    }
+
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn body(&self) -> Body {
+        Body::Json(serde_json::to_value(&self.items).unwrap_or_default())
+    }
+}
+
+// --- Sync (reads) ---
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WatchedMovie {
+    pub last_watched_at: Option<DateTime<Utc>>,
+    pub movie: TraktMovie,
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchedMoviesEndpoint;
+
+impl Endpoint for WatchedMoviesEndpoint {
+    type Output = Vec<WatchedMovie>;
+
+    fn path(&self) -> String {
+        "sync/watched/movies".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WatchedEpisode {
+    pub number: i64,
+    pub last_watched_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WatchedSeason {
+    pub number: i64,
+    #[serde(default)]
+    pub episodes: Vec<WatchedEpisode>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WatchedShow {
+    pub show: TraktShow,
+    #[serde(default)]
+    pub seasons: Vec<WatchedSeason>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchedShowsEndpoint;
+
+impl Endpoint for WatchedShowsEndpoint {
+    type Output = Vec<WatchedShow>;
+
+    fn path(&self) -> String {
+        "sync/watched/shows".to_string()
+    }
+}
+
+/// One entry from history, playback progress, ratings or the watchlist. Which
+/// of `movie`/`show`/`episode` are set follows `type`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SyncedItem {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub movie: Option<TraktMovie>,
+    pub show: Option<TraktShow>,
+    pub episode: Option<TraktEpisode>,
+    /// History only.
+    pub watched_at: Option<DateTime<Utc>>,
+    /// Playback only: percent watched.
+    pub progress: Option<f64>,
+    /// Playback only.
+    pub paused_at: Option<DateTime<Utc>>,
+    /// Ratings only: 1-10.
+    pub rating: Option<i64>,
+    /// Ratings only.
+    pub rated_at: Option<DateTime<Utc>>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HistoryParams {
+    pub start_at: Option<DateTime<Utc>>,
+    pub page: u32,
+    pub limit: u32,
+}
+
+/// Watch events, newest first. Paginated.
+#[derive(Debug, Clone)]
+pub struct HistoryEndpoint {
+    pub start_at: Option<DateTime<Utc>>,
+    pub page: u32,
+    pub limit: u32,
+}
+
+impl Endpoint for HistoryEndpoint {
+    type Output = Vec<SyncedItem>;
+
+    fn path(&self) -> String {
+        "sync/history".to_string()
+    }
+
+    fn query_params(&self) -> impl serde::Serialize + '_ {
+        HistoryParams {
+            start_at: self.start_at,
+            page: self.page,
+            limit: self.limit,
+        }
+    }
+}
+
+/// Paused playbacks. `extended=full` so each item carries its runtime,
+/// without which a percentage cannot become a position.
+#[derive(Debug, Clone)]
+pub struct PlaybackEndpoint {
+    pub start_at: Option<DateTime<Utc>>,
+}
+
+impl Endpoint for PlaybackEndpoint {
+    type Output = Vec<SyncedItem>;
+
+    fn path(&self) -> String {
+        "sync/playback".to_string()
+    }
+
+    fn query_params(&self) -> impl serde::Serialize + '_ {
+        #[skip_serializing_none]
+        #[derive(Serialize)]
+        struct Q {
+            start_at: Option<DateTime<Utc>>,
+            extended: &'static str,
+        }
+        Q {
+            start_at: self.start_at,
+            extended: "full",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RatingsEndpoint;
+
+impl Endpoint for RatingsEndpoint {
+    type Output = Vec<SyncedItem>;
+
+    fn path(&self) -> String {
+        "sync/ratings".to_string()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchlistEndpoint;
+
+impl Endpoint for WatchlistEndpoint {
+    type Output = Vec<SyncedItem>;
+
+    fn path(&self) -> String {
+        "sync/watchlist".to_string()
+    }
+}
+
 pub fn trakt_client(
     client_id: &str,
     base_url: &str,
 ) -> Result<RestClient<TraktAuth>, url::ParseError> {
     Ok(RestClient::new(base_url)?.with_auth(TraktAuth {
         client_id: client_id.to_string(),
+        access_token: None,
+    }))
+}
+
+/// A client acting as the user who granted `access_token`.
+pub fn trakt_user_client(
+    client_id: &str,
+    access_token: &str,
+    base_url: &str,
+) -> Result<RestClient<TraktAuth>, url::ParseError> {
+    Ok(RestClient::new(base_url)?.with_auth(TraktAuth {
+        client_id: client_id.to_string(),
+        access_token: Some(access_token.to_string()),
     }))
 }
@@ -0,0 +1,50 @@
+-- DVR rules: a series timer schedules a timer for every matching airing.
+--
+-- Matching is by program title, since XMLTV programs carry no series id.
+CREATE TABLE series_timers (
+    id                   BLOB PRIMARY KEY NOT NULL,
+    name                 TEXT NOT NULL,
+    -- The channel the rule was created on. Ignored when record_any_channel.
+    channel_id           BLOB NOT NULL,
+    -- The program the rule was created from, for display only.
+    program_id           BLOB,
+    record_any_channel   INTEGER NOT NULL DEFAULT 0,
+    pre_padding_seconds  INTEGER NOT NULL DEFAULT 0,
+    post_padding_seconds INTEGER NOT NULL DEFAULT 0,
+    -- Completed recordings to keep; 0 keeps all.
+    keep_up_to           INTEGER NOT NULL DEFAULT 0,
+    created_at           DATETIME NOT NULL
+);
+
+-- One scheduled or finished recording of one airing.
+--
+-- Program details are copied in rather than referenced: the guide refresh
+-- prunes programs once they have ended or left the guide, and a timer has to
+-- outlive that.
+CREATE TABLE timers (
+    id                   BLOB PRIMARY KEY NOT NULL,
+    series_timer_id      BLOB REFERENCES series_timers(id) ON DELETE SET NULL,
+    channel_id           BLOB NOT NULL,
+    program_id           BLOB,
+    name                 TEXT NOT NULL,
+    overview             TEXT,
+    start_date           DATETIME NOT NULL,
+    end_date             DATETIME NOT NULL,
+    pre_padding_seconds  INTEGER NOT NULL DEFAULT 0,
+    post_padding_seconds INTEGER NOT NULL DEFAULT 0,
+    -- new | in_progress | completed | cancelled | error
+    status               TEXT NOT NULL DEFAULT 'new',
+    -- The `recording` media row holding the file, once recording started.
+    recording_id         BLOB,
+    last_error           TEXT,
+    created_at           DATETIME NOT NULL,
+    updated_at           DATETIME NOT NULL
+);
+
+-- A program is recorded at most once, however many rules match it.
+CREATE UNIQUE INDEX idx_timers_program_id ON timers(program_id);
+
+-- The scheduler's due query.
+CREATE INDEX idx_timers_status_start ON timers(status, start_date);
+
+CREATE INDEX idx_timers_series_timer_id ON timers(series_timer_id);
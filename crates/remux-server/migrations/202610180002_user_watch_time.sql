@@ -0,0 +1,8 @@
+-- Playback time per user per day, for the daily watch-time limit in the
+-- user policy. `day` is the server's local date, matching access schedules.
+CREATE TABLE user_watch_time (
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    day     DATE NOT NULL,
+    seconds INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (user_id, day)
+);
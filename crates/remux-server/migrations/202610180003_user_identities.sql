@@ -0,0 +1,13 @@
+-- External identities (OpenID Connect subjects) linked to remux users.
+--
+-- `provider` is the issuer URL, so switching identity providers never
+-- matches a subject minted by the previous one.
+CREATE TABLE user_identities (
+    provider   TEXT NOT NULL,
+    subject    TEXT NOT NULL,
+    user_id    BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (provider, subject)
+);
+
+CREATE INDEX idx_user_identities_user_id ON user_identities(user_id);
@@ -0,0 +1,21 @@
+-- Metadata read from NFO sidecars and local artwork during opendal scans.
+--
+-- `media_key` is the seed of the item's stable id (`movie:{imdb}`,
+-- `series:{imdb}`, `season:{imdb}:{s}`, `episode:{imdb}:{s}:{e}`). Image
+-- columns hold a local absolute path or an `opendal://{addon_id}/{path}`
+-- reference served through the addon.
+CREATE TABLE opendal_metadata (
+    addon_id       TEXT    NOT NULL REFERENCES addons(id) ON DELETE CASCADE,
+    media_key      TEXT    NOT NULL,
+    title          TEXT,
+    plot           TEXT,
+    rating         REAL,
+    tmdb_id        INTEGER,
+    tvdb_id        INTEGER,
+    primary_image  TEXT,
+    backdrop_image TEXT,
+    logo_image     TEXT,
+    thumb_image    TEXT,
+    scanned_at     TEXT    NOT NULL,
+    PRIMARY KEY (addon_id, media_key)
+);
@@ -0,0 +1,14 @@
+-- Embedded tags of local tracks. `album_artist` and `album` are only set when
+-- both are known; such tracks are served as artist → album → track trees,
+-- the rest stay flat. `audio_tags` holds the full tag set as JSON; remote
+-- files whose size and `modified_at` are unchanged are not read again.
+ALTER TABLE opendal_files ADD COLUMN modified_at TEXT;
+ALTER TABLE opendal_files ADD COLUMN album_artist TEXT;
+ALTER TABLE opendal_files ADD COLUMN album TEXT;
+ALTER TABLE opendal_files ADD COLUMN audio_tags TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_opendal_files_album
+    ON opendal_files(addon_id, album_artist, album);
+
+-- ReplayGain track gain in dB, reported to clients as `NormalizationGain`.
+ALTER TABLE media ADD COLUMN normalization_gain REAL;
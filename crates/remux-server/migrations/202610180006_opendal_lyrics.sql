@@ -0,0 +1,12 @@
+-- `.lrc`/`.txt` sidecar of a track, stored like `path`: absolute for local
+-- addons, relative to the operator root otherwise.
+ALTER TABLE opendal_files ADD COLUMN lyrics_path TEXT;
+
+-- Music libraries serve their own lyrics from now on.
+UPDATE addons
+SET resources = json_insert(resources, '$[#]', 'lyrics')
+WHERE json_extract(preset, '$.kind') IN ('opendal-local', 'opendal-webdav')
+  AND json_extract(preset, '$.config.media_kind') = 'track'
+  AND NOT EXISTS (
+    SELECT 1 FROM json_each(resources) WHERE value = 'lyrics'
+  );
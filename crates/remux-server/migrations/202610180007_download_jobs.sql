@@ -0,0 +1,32 @@
+-- Offline downloads: one row per item a user asked to have transcoded to a
+-- file. The ProcessDownloads task works through queued rows oldest first and
+-- deletes finished files once they expire.
+CREATE TABLE download_jobs (
+    id           BLOB PRIMARY KEY NOT NULL,
+    user_id      TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    media_id     TEXT NOT NULL REFERENCES media(id) ON DELETE CASCADE,
+    -- The target DownloadProfile as JSON.
+    profile      TEXT NOT NULL,
+    -- queued | running | completed | failed
+    status       TEXT NOT NULL DEFAULT 'queued',
+    -- 0-100 while running.
+    progress     REAL NOT NULL DEFAULT 0,
+    -- Suggested name for the finished file, with the profile's extension.
+    file_name    TEXT NOT NULL,
+    size         INTEGER,
+    error        TEXT,
+    created_at   DATETIME NOT NULL,
+    completed_at DATETIME,
+    expires_at   DATETIME
+);
+
+-- The worker's claim query and the expiry sweep.
+CREATE INDEX idx_download_jobs_status ON download_jobs(status, created_at);
+
+-- The per-user list and the download button's lookup by item.
+CREATE INDEX idx_download_jobs_user_media ON download_jobs(user_id, media_id);
+
+-- Picks up anything left queued across a restart and removes expired files.
+INSERT OR IGNORE INTO task_triggers (id, task_id, kind, time_limit_hours, cron)
+VALUES ('default-processdownloads-interval', 'ProcessDownloads',
+        'IntervalTrigger', NULL, '0 0 * * * *');
@@ -0,0 +1,603 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::DateTime;
+use std::{sync::Arc, time::Duration};
+use uuid::Uuid;
+
+use super::{
+    AddonCapabilities, AddonKind, AddonMetadata, AddonOption, AddonOptionType,
+    AddonPreset, AddonPresetRegistration, MediaKind, ResourceType,
+    kitsu::{self, kitsu_entry, next_progress, watched_episodes},
+    media_tracker::{
+        AuthFlow, MediaTrackerAddon, MediaTrackerCapabilities, MediaTrackerCredentials,
+        MediaTrackerCtx, MediaTrackerError, MediaTrackerEvent, MediaTrackerEventKind,
+        MediaTrackerResult, MediaTrackerTarget, RemoteWatch, SyncDirection,
+    },
+};
+use crate::{
+    db,
+    sdks::{
+        self,
+        anilist::{AniListAuth, GraphQlResponse, MediaListStatus},
+    },
+};
+
+pub struct AniListPreset;
+
+impl AddonPreset for AniListPreset {
+    fn id(&self) -> &'static str {
+        "anilist"
+    }
+
+    fn metadata(&self) -> AddonMetadata {
+        AddonMetadata {
+            id: "anilist".to_string(),
+            display_name: "AniList".to_string(),
+            description: "Keeps users' AniList anime progress in step with what they \
+                          watch, and imports what they are currently watching."
+                .to_string(),
+            icon: None,
+            supported_resources: vec![AddonMetadata::simple_resource(
+                ResourceType::Tracking,
+            )],
+            supported_types: vec![MediaKind::Movie, MediaKind::Series],
+            supported_resources_user: vec![],
+            supported_types_user: vec![],
+            options: vec![AddonOption {
+                id: "client_id".to_string(),
+                name: "AniList client ID".to_string(),
+                description: Some(
+                    "Create an API client at anilist.co/settings/developer with \
+                     https://anilist.co/api/v2/oauth/pin as its redirect URL."
+                        .to_string(),
+                ),
+                required: true,
+                default: None,
+                kind: AddonOptionType::String,
+            }],
+        }
+    }
+
+    fn from_cfg(
+        &self,
+        _addon_id: Uuid,
+        cfg: &serde_json::Value,
+        _config: &crate::Config,
+    ) -> Result<AddonCapabilities> {
+        let client_id = cfg
+            .get("client_id")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+        // Users fetch their token from the client's authorize page, so
+        // without one there is nowhere to send them.
+        let media_tracker: Option<Arc<dyn MediaTrackerAddon>> =
+            client_id.map(|client_id| Arc::new(AniListAddon { client_id }) as _);
+        Ok(AddonCapabilities {
+            media_tracker,
+            ..Default::default()
+        })
+    }
+}
+
+inventory::submit! {
+    AddonPresetRegistration(|| Box::new(AniListPreset))
+}
+
+pub struct AniListAddon {
+    client_id: String,
+}
+
+#[async_trait]
+impl AddonKind for AniListAddon {
+    fn id(&self) -> &'static str {
+        "anilist"
+    }
+}
+
+/// Sorts an AniList failure into what the dispatcher should do with it.
+fn tracker_error(err: sdks::ClientError) -> MediaTrackerError {
+    match err {
+        sdks::ClientError::Unauthorized => {
+            MediaTrackerError::reauth("AniList rejected the access token")
+        }
+        sdks::ClientError::RateLimited { retry_after_secs } => {
+            MediaTrackerError::retry_after(
+                "AniList rate limit",
+                Duration::from_secs(retry_after_secs),
+            )
+        }
+        sdks::ClientError::Http { status, .. } if status >= 500 => {
+            MediaTrackerError::retryable(format!("AniList returned {status}"))
+        }
+        sdks::ClientError::Http { status, .. } => {
+            MediaTrackerError::permanent(format!("AniList returned {status}"))
+        }
+        sdks::ClientError::Transport(e) => {
+            MediaTrackerError::retryable(format!("reaching AniList: {e}"))
+        }
+        other => MediaTrackerError::permanent(other.to_string()),
+    }
+}
+
+/// Unwraps a GraphQL answer. AniList reports most failures, an expired
+/// token included, in `errors` rather than the HTTP status.
+fn graphql_data<T>(response: GraphQlResponse<T>) -> MediaTrackerResult<T> {
+    if let Some(err) = response
+        .errors
+        .first()
+    {
+        let message = format!("AniList: {}", err.message);
+        return Err(match err.status {
+            Some(401) => MediaTrackerError::reauth(message),
+            Some(400) if err.message == "Invalid token" => {
+                MediaTrackerError::reauth(message)
+            }
+            Some(429) => {
+                MediaTrackerError::retry_after(message, Duration::from_secs(60))
+            }
+            Some(status) if status >= 500 => MediaTrackerError::retryable(message),
+            _ => MediaTrackerError::permanent(message),
+        });
+    }
+    response
+        .data
+        .ok_or_else(|| MediaTrackerError::permanent("AniList returned no data"))
+}
+
+async fn query<E, T>(
+    client: &sdks::RestClient<AniListAuth>,
+    endpoint: E,
+) -> MediaTrackerResult<T>
+where
+    E: sdks::Endpoint<Output = GraphQlResponse<T>> + Clone + Send,
+{
+    graphql_data(
+        client
+            .execute(endpoint)
+            .await
+            .map_err(tracker_error)?,
+    )
+}
+
+fn client(
+    token: &str,
+    ctx: &MediaTrackerCtx,
+) -> MediaTrackerResult<sdks::RestClient<AniListAuth>> {
+    sdks::anilist::anilist_client(
+        token,
+        &ctx.config
+            .anilist_base_url,
+    )
+    .map_err(|e| MediaTrackerError::permanent(e.to_string()))
+}
+
+fn read_creds(creds: &MediaTrackerCredentials) -> MediaTrackerResult<(String, i64)> {
+    let token = creds
+        .get_str("access_token")
+        .ok_or_else(|| MediaTrackerError::reauth("no AniList token stored"))?;
+    let user_id = creds
+        .expose()
+        .get("user_id")
+        .and_then(|v| v.as_i64())
+        .ok_or_else(|| MediaTrackerError::reauth("no AniList user stored"))?;
+    Ok((token.to_string(), user_id))
+}
+
+#[async_trait]
+impl MediaTrackerAddon for AniListAddon {
+    fn capabilities(&self) -> MediaTrackerCapabilities {
+        MediaTrackerCapabilities {
+            auth_flow: AuthFlow::Token,
+            connect_fields: vec![AddonOption {
+                id: "token".to_string(),
+                name: "AniList token".to_string(),
+                description: Some(format!(
+                    "Authorise at {} and paste the token it shows.",
+                    sdks::anilist::authorize_url(&self.client_id)
+                )),
+                required: true,
+                default: None,
+                kind: AddonOptionType::Password,
+            }],
+            supported_events: vec![
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::MarkPlayed,
+            ],
+            media_kinds: vec![db::MediaKind::Movie, db::MediaKind::Episode],
+            default_event_filter: vec![
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::MarkPlayed,
+            ],
+            history_import: true,
+            watch_state_sync: SyncDirection::Push,
+            ..Default::default()
+        }
+    }
+
+    async fn connect_with_token(
+        &self,
+        fields: &serde_json::Value,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<MediaTrackerCredentials> {
+        let token = fields
+            .get("token")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| MediaTrackerError::permanent("token is required"))?;
+        let viewer = query(&client(token, ctx)?, sdks::anilist::ViewerEndpoint)
+            .await?
+            .viewer;
+        Ok(MediaTrackerCredentials::new(serde_json::json!({
+            "access_token": token,
+            "user_id": viewer.id,
+            "user_name": viewer.name,
+        })))
+    }
+
+    async fn verify(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        let (token, _) = read_creds(creds)?;
+        query(&client(&token, ctx)?, sdks::anilist::ViewerEndpoint).await?;
+        Ok(())
+    }
+
+    async fn on_event(
+        &self,
+        event: &MediaTrackerEvent,
+        target: &MediaTrackerTarget,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        match event {
+            MediaTrackerEvent::PlaybackStop { played: true, .. }
+            | MediaTrackerEvent::MarkPlayed => {}
+            // AniList tracks episodes finished, nothing finer.
+            MediaTrackerEvent::PlaybackStop { played: false, .. } => return Ok(()),
+            other => {
+                return Err(MediaTrackerError::unsupported(
+                    &other
+                        .kind()
+                        .to_string(),
+                ));
+            }
+        }
+        // Library items carry Kitsu or TVDB ids, never AniList's, so the
+        // entry is found on Kitsu and followed across its mappings.
+        let Some((kitsu_id, episode)) = kitsu_entry(target, ctx).await? else {
+            return Ok(());
+        };
+        let anilist_id =
+            kitsu::cached_mapping(format!("kitsu/anilist:{kitsu_id}"), async {
+                Ok(kitsu::app_client(ctx)?
+                    .execute(sdks::kitsu::MappingsEndpoint { kitsu_id })
+                    .await
+                    .map_err(kitsu::tracker_error)?
+                    .anilist_id())
+            })
+            .await?;
+        let Some(anilist_id) = anilist_id else {
+            return Ok(());
+        };
+        let (token, _) = read_creds(creds)?;
+
+        let client = client(&token, ctx)?;
+        let media = query(&client, sdks::anilist::MediaEndpoint { anilist_id })
+            .await?
+            .media;
+        let current = media
+            .media_list_entry
+            .and_then(|e| e.progress);
+        let Some((progress, finished)) =
+            next_progress(current, episode, media.episodes)
+        else {
+            return Ok(());
+        };
+        query(
+            &client,
+            sdks::anilist::SaveMediaListEntryEndpoint {
+                anilist_id,
+                progress,
+                status: if finished {
+                    MediaListStatus::Completed
+                } else {
+                    MediaListStatus::Current
+                },
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// The user's currently-watching list, as the episodes it says they have
+    /// seen. Entries Kitsu has no mapping for are left out.
+    async fn import_history(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<Vec<RemoteWatch>> {
+        let (token, user_id) = read_creds(creds)?;
+        let collection = query(
+            &client(&token, ctx)?,
+            sdks::anilist::MediaListCollectionEndpoint {
+                user_id,
+                statuses: vec![MediaListStatus::Current],
+            },
+        )
+        .await?
+        .collection;
+
+        let kitsu = kitsu::app_client(ctx)?;
+        let mut out = Vec::new();
+        for entry in collection
+            .lists
+            .into_iter()
+            .flat_map(|l| l.entries)
+        {
+            let Some(progress) = entry
+                .progress
+                .filter(|p| *p > 0)
+            else {
+                continue;
+            };
+            let Some(kitsu_id) = kitsu
+                .execute(sdks::kitsu::ReverseMappingEndpoint {
+                    external_site: "anilist/anime".to_string(),
+                    external_id: entry
+                        .media
+                        .id
+                        .to_string(),
+                })
+                .await
+                .map_err(kitsu::tracker_error)?
+                .anime_id()
+            else {
+                continue;
+            };
+            let updated_at = entry
+                .updated_at
+                .and_then(|t| DateTime::from_timestamp(t, 0))
+                .map(|t| t.naive_utc());
+            out.extend(watched_episodes(kitsu_id, progress, updated_at));
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::{
+        Method::{GET, POST},
+        MockServer,
+    };
+
+    fn ctx(anilist: &MockServer, kitsu: &MockServer) -> MediaTrackerCtx {
+        MediaTrackerCtx {
+            config: Arc::new(crate::Config {
+                anilist_base_url: anilist.base_url(),
+                kitsu_base_url: kitsu.base_url(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn addon() -> AniListAddon {
+        AniListAddon {
+            client_id: "123".into(),
+        }
+    }
+
+    fn creds() -> MediaTrackerCredentials {
+        MediaTrackerCredentials::new(serde_json::json!({
+            "access_token": "tok",
+            "user_id": 7,
+        }))
+    }
+
+    fn movie() -> MediaTrackerTarget {
+        MediaTrackerTarget {
+            kind: db::MediaKind::Movie,
+            title: "Perfect Blue".into(),
+            year: Some(1997),
+            ids: db::ExternalIds {
+                kitsu: Some(1),
+                ..Default::default()
+            },
+            series: None,
+            season: None,
+            episode: None,
+            runtime: None,
+            artist: None,
+            album: None,
+        }
+    }
+
+    fn played() -> MediaTrackerEvent {
+        MediaTrackerEvent::PlaybackStop {
+            position_ticks: 0,
+            played: true,
+        }
+    }
+
+    fn mapped_to_anilist(kitsu: &MockServer) {
+        kitsu.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/anime/1/mappings");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": [{
+                        "attributes": { "externalSite": "anilist/anime", "externalId": "437" },
+                    }],
+                }));
+        });
+    }
+
+    #[tokio::test]
+    async fn a_watched_movie_completes_its_entry_through_the_kitsu_mapping() {
+        let anilist = MockServer::start();
+        let kitsu = MockServer::start();
+        mapped_to_anilist(&kitsu);
+        anilist.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .body_contains("Media(id")
+                .json_body_partial(r#"{ "variables": { "id": 437 } }"#);
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": { "Media": { "id": 437, "episodes": 1, "mediaListEntry": null } }
+                }));
+        });
+        let save = anilist.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .header("authorization", "Bearer tok")
+                .json_body_partial(
+                    r#"{ "variables": { "mediaId": 437, "progress": 1, "status": "COMPLETED" } }"#,
+                );
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": { "SaveMediaListEntry": { "id": 9, "progress": 1, "status": "COMPLETED" } }
+                }));
+        });
+
+        addon()
+            .on_event(&played(), &movie(), &creds(), &ctx(&anilist, &kitsu))
+            .await
+            .unwrap();
+        save.assert();
+    }
+
+    #[tokio::test]
+    async fn an_entry_anilist_does_not_list_is_skipped() {
+        let anilist = MockServer::start();
+        let kitsu = MockServer::start();
+        kitsu.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/anime/2/mappings");
+            then.status(200)
+                .json_body(serde_json::json!({ "data": [] }));
+        });
+        let writes = anilist.mock(|when, then| {
+            when.method(POST);
+            then.status(200);
+        });
+
+        let mut target = movie();
+        target
+            .ids
+            .kitsu = Some(2);
+        addon()
+            .on_event(&played(), &target, &creds(), &ctx(&anilist, &kitsu))
+            .await
+            .unwrap();
+        assert_eq!(writes.hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn an_expired_token_in_graphql_errors_asks_for_reauth() {
+        let anilist = MockServer::start();
+        let kitsu = MockServer::start();
+        mapped_to_anilist(&kitsu);
+        anilist.mock(|when, then| {
+            when.method(POST)
+                .path("/");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": null,
+                    "errors": [{ "message": "Invalid token", "status": 400 }],
+                }));
+        });
+
+        let err = addon()
+            .on_event(&played(), &movie(), &creds(), &ctx(&anilist, &kitsu))
+            .await
+            .unwrap_err();
+        assert!(err.requires_reauth());
+    }
+
+    #[tokio::test]
+    async fn connect_records_the_viewer() {
+        let anilist = MockServer::start();
+        let kitsu = MockServer::start();
+        anilist.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .header("authorization", "Bearer tok")
+                .body_contains("Viewer");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": { "Viewer": { "id": 7, "name": "faye" } }
+                }));
+        });
+
+        let creds = addon()
+            .connect_with_token(
+                &serde_json::json!({ "token": " tok " }),
+                &ctx(&anilist, &kitsu),
+            )
+            .await
+            .unwrap();
+        assert_eq!(creds.get_str("access_token"), Some("tok"));
+        assert_eq!(creds.expose()["user_id"], 7);
+    }
+
+    #[tokio::test]
+    async fn the_watching_list_imports_under_kitsu_ids() {
+        let anilist = MockServer::start();
+        let kitsu = MockServer::start();
+        anilist.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .json_body_partial(r#"{ "variables": { "userId": 7, "statuses": ["CURRENT"] } }"#);
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": { "MediaListCollection": { "lists": [{ "entries": [
+                        { "progress": 2, "status": "CURRENT", "updatedAt": 1_700_000_000, "media": { "id": 1 } },
+                        { "progress": 5, "status": "CURRENT", "updatedAt": 1_700_000_000, "media": { "id": 999 } },
+                    ] }] } }
+                }));
+        });
+        kitsu.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/mappings")
+                .query_param("filter[externalId]", "1");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": [],
+                    "included": [{ "id": "1", "type": "anime", "attributes": {} }],
+                }));
+        });
+        kitsu.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/mappings")
+                .query_param("filter[externalId]", "999");
+            then.status(200)
+                .json_body(serde_json::json!({ "data": [] }));
+        });
+
+        let watched = addon()
+            .import_history(&creds(), &ctx(&anilist, &kitsu))
+            .await
+            .unwrap();
+        assert_eq!(watched.len(), 2, "the unmapped entry is skipped");
+        assert!(
+            watched
+                .iter()
+                .all(|w| w
+                    .ids
+                    .kitsu
+                    == Some(1))
+        );
+        assert!(
+            watched[1]
+                .watched_at
+                .is_some()
+        );
+    }
+}
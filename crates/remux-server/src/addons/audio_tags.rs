@@ -0,0 +1,341 @@
+//! Embedded tags of local audio files.
+//!
+//! lofty maps ID3v2, Vorbis comments, MP4 atoms and APE onto the same item
+//! keys, so `TPE2`, `ALBUMARTIST` and `aART` all end up in `album_artist`.
+
+use std::{io::Cursor, path::PathBuf};
+
+use anyhow::Result;
+use lofty::{
+    config::ParseOptions,
+    file::TaggedFile,
+    picture::{Picture, PictureType},
+    prelude::*,
+    probe::Probe,
+    tag::{ItemValue, Tag},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track_number: Option<i64>,
+    pub disc_number: Option<i64>,
+    pub year: Option<i64>,
+    pub genres: Vec<String>,
+    pub musicbrainz_recording_id: Option<String>,
+    pub musicbrainz_track_id: Option<String>,
+    pub musicbrainz_album_id: Option<String>,
+    pub musicbrainz_release_group_id: Option<String>,
+    pub musicbrainz_artist_id: Option<String>,
+    pub musicbrainz_album_artist_id: Option<String>,
+    /// ReplayGain adjustments in dB.
+    pub track_gain: Option<f64>,
+    pub album_gain: Option<f64>,
+    /// In seconds; unknown when only the head of the file was read.
+    pub duration: Option<i64>,
+    /// ID3v2 `SYLT` as LRC, else the unsynchronised `USLT`/`LYRICS`/`©lyr`
+    /// text, which taggers often fill with LRC as well.
+    pub lyrics: Option<String>,
+    /// Front cover, or the first picture when none is marked as such.
+    #[serde(skip)]
+    pub cover: Option<Cover>,
+    /// Where the scan saved `cover`, kept so rescans need not read it again.
+    pub cover_image: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cover {
+    pub data: Vec<u8>,
+    pub mime: Option<String>,
+}
+
+impl Cover {
+    pub fn extension(&self) -> &'static str {
+        match self
+            .mime
+            .as_deref()
+        {
+            Some("image/png") => "png",
+            Some("image/webp") => "webp",
+            Some("image/gif") => "gif",
+            _ => "jpg",
+        }
+    }
+}
+
+impl AudioTags {
+    pub async fn read_path(path: PathBuf) -> Result<Self> {
+        tokio::task::spawn_blocking(move || {
+            let file = lofty::read_from_path(&path)?;
+            Ok(Self::from_file(&file))
+        })
+        .await?
+    }
+
+    /// Tags from the first bytes of a file. ID3v2, FLAC and Ogg keep them up
+    /// front; MP4 files with a trailing `moov` and ID3v1-only files come back
+    /// empty.
+    pub fn read_head(bytes: Vec<u8>) -> Result<Self> {
+        let file = Probe::new(Cursor::new(bytes))
+            .options(ParseOptions::new().read_properties(false))
+            .guess_file_type()?
+            .read()?;
+        Ok(Self::from_file(&file))
+    }
+
+    fn from_file(file: &TaggedFile) -> Self {
+        let mut tags = file
+            .primary_tag()
+            .or_else(|| file.first_tag())
+            .map(Self::from_tag)
+            .unwrap_or_default();
+        let duration = file
+            .properties()
+            .duration()
+            .as_secs();
+        tags.duration = (duration > 0).then_some(duration as i64);
+        tags
+    }
+
+    fn from_tag(tag: &Tag) -> Self {
+        let text = |key: ItemKey| {
+            tag.get_string(&key)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        AudioTags {
+            title: text(ItemKey::TrackTitle),
+            artist: text(ItemKey::TrackArtist),
+            album_artist: text(ItemKey::AlbumArtist),
+            album: text(ItemKey::AlbumTitle),
+            track_number: tag
+                .track()
+                .map(i64::from),
+            disc_number: tag
+                .disk()
+                .map(i64::from),
+            year: text(ItemKey::Year)
+                .or_else(|| text(ItemKey::RecordingDate))
+                .as_deref()
+                .and_then(parse_year),
+            genres: split_genres(tag.get_strings(&ItemKey::Genre)),
+            musicbrainz_recording_id: text(ItemKey::MusicBrainzRecordingId),
+            musicbrainz_track_id: text(ItemKey::MusicBrainzTrackId),
+            musicbrainz_album_id: text(ItemKey::MusicBrainzReleaseId),
+            musicbrainz_release_group_id: text(ItemKey::MusicBrainzReleaseGroupId),
+            musicbrainz_artist_id: text(ItemKey::MusicBrainzArtistId),
+            musicbrainz_album_artist_id: text(ItemKey::MusicBrainzReleaseArtistId),
+            track_gain: text(ItemKey::ReplayGainTrackGain)
+                .as_deref()
+                .and_then(parse_gain),
+            album_gain: text(ItemKey::ReplayGainAlbumGain)
+                .as_deref()
+                .and_then(parse_gain),
+            duration: None,
+            lyrics: synced_lyrics(tag).or_else(|| text(ItemKey::Lyrics)),
+            cover: front_cover(tag.pictures()),
+            cover_image: None,
+        }
+    }
+
+    /// `(album artist, album)` the track is filed under. Falls back to the
+    /// track artist, as most taggers leave the album artist empty when the
+    /// two are the same.
+    pub fn album_key(&self) -> Option<(&str, &str)> {
+        let artist = self
+            .album_artist
+            .as_deref()
+            .or(self
+                .artist
+                .as_deref())?;
+        Some((
+            artist,
+            self.album
+                .as_deref()?,
+        ))
+    }
+}
+
+/// `2004`, `2004-10-05` and `2004-10-05T00:00:00` all give 2004.
+fn parse_year(value: &str) -> Option<i64> {
+    value
+        .trim()
+        .get(..4)?
+        .parse()
+        .ok()
+        .filter(|year| *year > 0)
+}
+
+/// `-6.54 dB` → -6.54.
+fn parse_gain(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic() || c.is_whitespace())
+        .parse()
+        .ok()
+}
+
+/// Multi-value genre fields, plus `;`- and NUL-joined single values.
+fn split_genres<'a>(values: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut genres: Vec<String> = Vec::new();
+    for genre in values.flat_map(|v| v.split([';', '\0'])) {
+        let genre = genre.trim();
+        if !genre.is_empty()
+            && !genres
+                .iter()
+                .any(|g| g.eq_ignore_ascii_case(genre))
+        {
+            genres.push(genre.to_string());
+        }
+    }
+    genres
+}
+
+/// The generic tag keeps ID3v2 `SYLT` frames as raw bytes. Only millisecond
+/// timestamps are read; MPEG frame counts need the audio to resolve.
+fn synced_lyrics(tag: &Tag) -> Option<String> {
+    use lofty::id3::v2::{FrameFlags, SynchronizedTextFrame, TimestampFormat};
+
+    let ItemValue::Binary(data) = tag
+        .get(&ItemKey::Unknown("SYLT".to_string()))?
+        .value()
+    else {
+        return None;
+    };
+    let frame = SynchronizedTextFrame::parse(data, FrameFlags::default()).ok()?;
+    if !matches!(frame.timestamp_format, TimestampFormat::MS) {
+        return None;
+    }
+    let lines: Vec<String> = frame
+        .content
+        .iter()
+        .map(|(ms, text)| lrc_line(*ms, text))
+        .collect();
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// `[mm:ss.xx]text`
+fn lrc_line(ms: u32, text: &str) -> String {
+    format!(
+        "[{:02}:{:02}.{:02}]{}",
+        ms / 60_000,
+        ms / 1000 % 60,
+        ms % 1000 / 10,
+        text.trim()
+    )
+}
+
+fn front_cover(pictures: &[Picture]) -> Option<Cover> {
+    pictures
+        .iter()
+        .find(|p| p.pic_type() == PictureType::CoverFront)
+        .or_else(|| pictures.first())
+        .map(|p| Cover {
+            data: p
+                .data()
+                .to_vec(),
+            mime: p
+                .mime_type()
+                .map(|m| {
+                    m.as_str()
+                        .to_string()
+                }),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use lofty::{picture::MimeType, tag::TagType};
+
+    use super::*;
+
+    #[test]
+    fn gain_year_and_genre_values() {
+        assert_eq!(parse_gain("-6.54 dB"), Some(-6.54));
+        assert_eq!(parse_gain("+1.20 dB"), Some(1.2));
+        assert_eq!(parse_gain("0.5"), Some(0.5));
+        assert_eq!(parse_gain("loud"), None);
+
+        assert_eq!(parse_year("1997"), Some(1997));
+        assert_eq!(parse_year("1997-05-21T00:00:00"), Some(1997));
+        assert_eq!(parse_year("97"), None);
+
+        assert_eq!(lrc_line(83_456, " Hello "), "[01:23.45]Hello");
+
+        assert_eq!(
+            split_genres(
+                ["Rock; Alternative", "rock", "Trip-Hop\0Electronic"].into_iter()
+            ),
+            vec!["Rock", "Alternative", "Trip-Hop", "Electronic"]
+        );
+    }
+
+    #[test]
+    fn reads_vorbis_comments() {
+        let mut tag = Tag::new(TagType::VorbisComments);
+        for (key, value) in [
+            (ItemKey::TrackTitle, "Paranoid Android"),
+            (ItemKey::TrackArtist, "Radiohead"),
+            (ItemKey::AlbumTitle, "OK Computer"),
+            (ItemKey::RecordingDate, "1997-05-21"),
+            (ItemKey::Genre, "Alternative Rock"),
+            (
+                ItemKey::MusicBrainzReleaseId,
+                "0b6b4ba0-d36f-47bd-b4ea-6a5b91842d29",
+            ),
+            (ItemKey::ReplayGainTrackGain, "-8.10 dB"),
+            (ItemKey::Lyrics, "[00:01.00]Please could you stop the noise"),
+        ] {
+            tag.insert_text(key, value.to_string());
+        }
+        tag.set_track(2);
+        tag.set_disk(1);
+        tag.push_picture(Picture::new_unchecked(
+            PictureType::Other,
+            Some(MimeType::Jpeg),
+            None,
+            vec![1],
+        ));
+        tag.push_picture(Picture::new_unchecked(
+            PictureType::CoverFront,
+            Some(MimeType::Png),
+            None,
+            vec![2],
+        ));
+
+        let tags = AudioTags::from_tag(&tag);
+        assert_eq!(
+            tags.title
+                .as_deref(),
+            Some("Paranoid Android")
+        );
+        assert_eq!((tags.track_number, tags.disc_number), (Some(2), Some(1)));
+        assert_eq!(tags.year, Some(1997));
+        assert_eq!(tags.genres, vec!["Alternative Rock"]);
+        assert_eq!(
+            tags.musicbrainz_album_id
+                .as_deref(),
+            Some("0b6b4ba0-d36f-47bd-b4ea-6a5b91842d29")
+        );
+        assert_eq!(tags.track_gain, Some(-8.1));
+        assert_eq!(
+            tags.lyrics
+                .as_deref(),
+            Some("[00:01.00]Please could you stop the noise")
+        );
+        // No album artist: the album is filed under the track artist.
+        assert_eq!(tags.album_key(), Some(("Radiohead", "OK Computer")));
+
+        let cover = tags
+            .cover
+            .unwrap();
+        assert_eq!(cover.data, vec![2]);
+        assert_eq!(cover.extension(), "png");
+    }
+}
@@ -0,0 +1,925 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+use uuid::Uuid;
+
+use super::{
+    AddonCapabilities, AddonKind, AddonMetadata, AddonOption, AddonOptionType,
+    AddonPreset, AddonPresetRegistration, MediaKind, ResourceType,
+    media_tracker::{
+        AuthFlow, MediaTrackerAddon, MediaTrackerCapabilities, MediaTrackerCredentials,
+        MediaTrackerCtx, MediaTrackerError, MediaTrackerEvent, MediaTrackerEventKind,
+        MediaTrackerResult, MediaTrackerTarget, RemoteWatch, SyncDirection,
+    },
+};
+use crate::{
+    db,
+    sdks::{self, kitsu::LibraryStatus},
+};
+
+/// Kitsu tokens last a month; renewing a day early keeps a delivery from
+/// racing the expiry.
+const REFRESH_MARGIN_SECS: i64 = 24 * 60 * 60;
+
+/// Library page size.
+const LIBRARY_PAGE: u32 = 100;
+/// Stops a runaway walk; the rest arrives with the next import.
+const MAX_LIBRARY_PAGES: u32 = 20;
+
+pub struct KitsuPreset;
+
+impl AddonPreset for KitsuPreset {
+    fn id(&self) -> &'static str {
+        "kitsu"
+    }
+
+    fn metadata(&self) -> AddonMetadata {
+        AddonMetadata {
+            id: "kitsu".to_string(),
+            display_name: "Kitsu".to_string(),
+            description: "Keeps users' Kitsu anime progress in step with what they \
+                          watch, and imports what they are currently watching."
+                .to_string(),
+            icon: None,
+            supported_resources: vec![AddonMetadata::simple_resource(
+                ResourceType::Tracking,
+            )],
+            supported_types: vec![MediaKind::Movie, MediaKind::Series],
+            supported_resources_user: vec![],
+            supported_types_user: vec![],
+            options: vec![],
+        }
+    }
+
+    fn from_cfg(
+        &self,
+        _addon_id: Uuid,
+        _cfg: &serde_json::Value,
+        _config: &crate::Config,
+    ) -> Result<AddonCapabilities> {
+        let addon = Arc::new(KitsuAddon);
+        Ok(AddonCapabilities {
+            kind: Some(addon.clone()),
+            media_tracker: Some(addon),
+            ..Default::default()
+        })
+    }
+}
+
+inventory::submit! {
+    AddonPresetRegistration(|| Box::new(KitsuPreset))
+}
+
+pub struct KitsuAddon;
+
+#[async_trait]
+impl AddonKind for KitsuAddon {
+    fn id(&self) -> &'static str {
+        "kitsu"
+    }
+}
+
+/// Sorts a Kitsu failure into what the dispatcher should do with it.
+pub(super) fn tracker_error(err: sdks::ClientError) -> MediaTrackerError {
+    match err {
+        sdks::ClientError::Unauthorized => {
+            MediaTrackerError::reauth("Kitsu rejected the access token")
+        }
+        sdks::ClientError::RateLimited { retry_after_secs } => {
+            MediaTrackerError::retry_after(
+                "Kitsu rate limit",
+                Duration::from_secs(retry_after_secs),
+            )
+        }
+        sdks::ClientError::Http { status, .. } if status >= 500 => {
+            MediaTrackerError::retryable(format!("Kitsu returned {status}"))
+        }
+        sdks::ClientError::Http { status, .. } => {
+            MediaTrackerError::permanent(format!("Kitsu returned {status}"))
+        }
+        sdks::ClientError::Transport(e) => {
+            MediaTrackerError::retryable(format!("reaching Kitsu: {e}"))
+        }
+        other => MediaTrackerError::permanent(other.to_string()),
+    }
+}
+
+pub(super) fn app_client(
+    ctx: &MediaTrackerCtx,
+) -> MediaTrackerResult<sdks::RestClient<sdks::NoAuth>> {
+    sdks::kitsu::kitsu_client(
+        &ctx.config
+            .kitsu_base_url,
+    )
+    .map_err(|e| MediaTrackerError::permanent(e.to_string()))
+}
+
+/// Kitsu's id mappings, keyed `site:id`. They barely change, and without
+/// this every season-one episode of a show would look its series up again.
+static MAPPINGS: LazyLock<moka::sync::Cache<String, Option<i64>>> =
+    LazyLock::new(|| {
+        moka::sync::Cache::builder()
+            .max_capacity(10_000)
+            .time_to_live(Duration::from_secs(24 * 60 * 60))
+            .build()
+    });
+
+/// `lookup`'s answer for `key`, remembered whether or not it found an entry:
+/// a show that is not anime stays that way.
+pub(super) async fn cached_mapping(
+    key: String,
+    lookup: impl Future<Output = MediaTrackerResult<Option<i64>>>,
+) -> MediaTrackerResult<Option<i64>> {
+    if let Some(found) = MAPPINGS.get(&key) {
+        return Ok(found);
+    }
+    let found = lookup.await?;
+    MAPPINGS.insert(key, found);
+    Ok(found)
+}
+
+/// Which Kitsu anime a played item belongs to, and the episode number that
+/// playing it brings the entry's progress up to. `None` for anything that is
+/// not an anime Kitsu knows: most of a library, and nothing to report.
+///
+/// Kitsu splits a show into one entry per season, so an episode is only
+/// placed when its series carries a Kitsu id, or when a TVDB id maps over and
+/// the episode is in the first season; anything later would be a guess.
+pub(super) async fn kitsu_entry(
+    target: &MediaTrackerTarget,
+    ctx: &MediaTrackerCtx,
+) -> MediaTrackerResult<Option<(i64, i64)>> {
+    match target.kind {
+        db::MediaKind::Movie => Ok(target
+            .ids
+            .kitsu
+            .map(|kitsu_id| (kitsu_id, 1))),
+        db::MediaKind::Episode => {
+            let (Some(episode), Some(series)) = (
+                target.episode,
+                target
+                    .series
+                    .as_deref(),
+            ) else {
+                return Ok(None);
+            };
+            if let Some(kitsu_id) = series
+                .ids
+                .kitsu
+            {
+                return Ok(Some((kitsu_id, episode)));
+            }
+            let Some(tvdb) = series
+                .ids
+                .tvdb
+                .filter(|_| {
+                    target
+                        .season
+                        .unwrap_or(1)
+                        == 1
+                })
+            else {
+                return Ok(None);
+            };
+            let kitsu_id = cached_mapping(format!("thetvdb/series:{tvdb}"), async {
+                Ok(app_client(ctx)?
+                    .execute(sdks::kitsu::ReverseMappingEndpoint {
+                        external_site: "thetvdb/series".to_string(),
+                        external_id: tvdb.to_string(),
+                    })
+                    .await
+                    .map_err(tracker_error)?
+                    .anime_id())
+            })
+            .await?;
+            Ok(kitsu_id.map(|kitsu_id| (kitsu_id, episode)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The progress to record after watching `episode`, and whether that
+/// finishes the show. `None` when the entry is already at or past it:
+/// rewatching an early episode must not wind a user's progress back.
+pub(super) fn next_progress(
+    current: Option<i64>,
+    episode: i64,
+    episode_count: Option<i64>,
+) -> Option<(i64, bool)> {
+    if current.is_some_and(|c| c >= episode) {
+        return None;
+    }
+    Some((episode, episode_count.is_some_and(|n| episode >= n)))
+}
+
+/// One `RemoteWatch` per episode up to `progress`, keyed the way a target
+/// names a Kitsu-backed episode: the anime's id, its only season, and the
+/// episode number.
+pub(super) fn watched_episodes(
+    kitsu_id: i64,
+    progress: i64,
+    updated_at: Option<chrono::NaiveDateTime>,
+) -> impl Iterator<Item = RemoteWatch> {
+    (1..=progress).map(move |episode| RemoteWatch {
+        ids: db::ExternalIds {
+            kitsu: Some(kitsu_id),
+            ..Default::default()
+        },
+        title: None,
+        season: Some(1),
+        episode: Some(episode),
+        watched: true,
+        position_ticks: None,
+        // Only the latest episode's time is known.
+        watched_at: (episode == progress)
+            .then_some(updated_at)
+            .flatten(),
+        favorite: None,
+        rating: None,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KitsuCredentials {
+    access_token: String,
+    refresh_token: String,
+    /// Unix seconds.
+    expires_at: i64,
+    user_id: i64,
+}
+
+impl KitsuCredentials {
+    fn read(creds: &MediaTrackerCredentials) -> MediaTrackerResult<Self> {
+        serde_json::from_value(
+            creds
+                .expose()
+                .clone(),
+        )
+        .map_err(|_| {
+            MediaTrackerError::reauth("stored Kitsu credentials are unreadable")
+        })
+    }
+
+    fn into_credentials(self) -> MediaTrackerCredentials {
+        MediaTrackerCredentials::new(serde_json::json!({
+            "access_token": self.access_token,
+            "refresh_token": self.refresh_token,
+            "expires_at": self.expires_at,
+            "user_id": self.user_id,
+        }))
+    }
+
+    fn client(
+        &self,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<sdks::RestClient<sdks::kitsu::KitsuAuth>> {
+        sdks::kitsu::kitsu_user_client(
+            &self.access_token,
+            &ctx.config
+                .kitsu_base_url,
+        )
+        .map_err(|e| MediaTrackerError::permanent(e.to_string()))
+    }
+}
+
+/// The Kitsu user id behind a token.
+async fn self_user_id(
+    client: &sdks::RestClient<sdks::kitsu::KitsuAuth>,
+) -> MediaTrackerResult<i64> {
+    client
+        .execute(sdks::kitsu::SelfUserEndpoint)
+        .await
+        .map_err(tracker_error)?
+        .data
+        .first()
+        .and_then(|u| {
+            u.id.parse()
+                .ok()
+        })
+        .ok_or_else(|| {
+            MediaTrackerError::reauth("Kitsu did not say whose token this is")
+        })
+}
+
+#[async_trait]
+impl MediaTrackerAddon for KitsuAddon {
+    fn capabilities(&self) -> MediaTrackerCapabilities {
+        MediaTrackerCapabilities {
+            auth_flow: AuthFlow::Token,
+            connect_fields: vec![
+                AddonOption {
+                    id: "username".to_string(),
+                    name: "Kitsu email".to_string(),
+                    description: None,
+                    required: true,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "password".to_string(),
+                    name: "Kitsu password".to_string(),
+                    description: Some(
+                        "Traded for a token on connect; the password itself is not kept."
+                            .to_string(),
+                    ),
+                    required: true,
+                    default: None,
+                    kind: AddonOptionType::Password,
+                },
+            ],
+            supported_events: vec![
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::MarkPlayed,
+            ],
+            media_kinds: vec![db::MediaKind::Movie, db::MediaKind::Episode],
+            default_event_filter: vec![
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::MarkPlayed,
+            ],
+            history_import: true,
+            watch_state_sync: SyncDirection::Push,
+            ..Default::default()
+        }
+    }
+
+    async fn connect_with_token(
+        &self,
+        fields: &serde_json::Value,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<MediaTrackerCredentials> {
+        let field = |key: &str| {
+            fields
+                .get(key)
+                .and_then(|v| v.as_str())
+                .filter(|s| {
+                    !s.trim()
+                        .is_empty()
+                })
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    MediaTrackerError::permanent(format!("{key} is required"))
+                })
+        };
+        let token = app_client(ctx)?
+            .execute(sdks::kitsu::PasswordTokenEndpoint {
+                username: field("username")?
+                    .trim()
+                    .to_string(),
+                password: field("password")?,
+            })
+            .await
+            .map_err(|e| match e {
+                sdks::ClientError::Unauthorized
+                | sdks::ClientError::Http { status: 400, .. } => {
+                    MediaTrackerError::permanent("Kitsu rejected the email or password")
+                }
+                other => tracker_error(other),
+            })?;
+        let mut creds = KitsuCredentials {
+            expires_at: token.expires_at(),
+            access_token: token.access_token,
+            refresh_token: token.refresh_token,
+            user_id: 0,
+        };
+        creds.user_id = self_user_id(&creds.client(ctx)?).await?;
+        Ok(creds.into_credentials())
+    }
+
+    async fn refresh(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<MediaTrackerCredentials> {
+        let current = KitsuCredentials::read(creds)?;
+        if current.expires_at - Utc::now().timestamp() > REFRESH_MARGIN_SECS {
+            return Ok(creds.clone());
+        }
+        let result = app_client(ctx)?
+            .execute(sdks::kitsu::RefreshTokenEndpoint {
+                refresh_token: current.refresh_token,
+            })
+            .await;
+        match result {
+            Ok(token) => Ok(KitsuCredentials {
+                expires_at: token.expires_at(),
+                access_token: token.access_token,
+                refresh_token: token.refresh_token,
+                user_id: current.user_id,
+            }
+            .into_credentials()),
+            Err(sdks::ClientError::Http { status: 400, .. }) => Err(
+                MediaTrackerError::reauth("Kitsu refused to renew the access token"),
+            ),
+            Err(e) => Err(tracker_error(e)),
+        }
+    }
+
+    async fn verify(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        let creds = KitsuCredentials::read(creds)?;
+        self_user_id(&creds.client(ctx)?).await?;
+        Ok(())
+    }
+
+    async fn on_event(
+        &self,
+        event: &MediaTrackerEvent,
+        target: &MediaTrackerTarget,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        match event {
+            MediaTrackerEvent::PlaybackStop { played: true, .. }
+            | MediaTrackerEvent::MarkPlayed => {}
+            // Kitsu tracks episodes finished, nothing finer.
+            MediaTrackerEvent::PlaybackStop { played: false, .. } => return Ok(()),
+            other => {
+                return Err(MediaTrackerError::unsupported(
+                    &other
+                        .kind()
+                        .to_string(),
+                ));
+            }
+        }
+        let Some((kitsu_id, episode)) = kitsu_entry(target, ctx).await? else {
+            return Ok(());
+        };
+        let creds = KitsuCredentials::read(creds)?;
+        let client = creds.client(ctx)?;
+
+        let existing = client
+            .execute(sdks::kitsu::LibraryEntriesEndpoint {
+                user_id: creds.user_id,
+                anime_id: Some(kitsu_id),
+                status: None,
+                limit: 1,
+                offset: 0,
+            })
+            .await
+            .map_err(tracker_error)?
+            .data
+            .into_iter()
+            .next();
+        let current = existing
+            .as_ref()
+            .and_then(|e| {
+                e.attributes
+                    .progress
+            });
+        if current.is_some_and(|c| c >= episode) {
+            return Ok(());
+        }
+        let episode_count = client
+            .execute(sdks::kitsu::AnimeEndpoint { kitsu_id })
+            .await
+            .map_err(tracker_error)?
+            .data
+            .attributes
+            .episode_count;
+        let Some((progress, finished)) = next_progress(current, episode, episode_count)
+        else {
+            return Ok(());
+        };
+        let status = if finished {
+            LibraryStatus::Completed
+        } else {
+            LibraryStatus::Current
+        };
+
+        match existing {
+            Some(entry) => client
+                .execute(sdks::kitsu::UpdateLibraryEntryEndpoint {
+                    entry_id: entry.id,
+                    status,
+                    progress,
+                })
+                .await
+                .map(|_| ()),
+            None => client
+                .execute(sdks::kitsu::CreateLibraryEntryEndpoint {
+                    user_id: creds.user_id,
+                    anime_id: kitsu_id,
+                    status,
+                    progress,
+                })
+                .await
+                .map(|_| ()),
+        }
+        .map_err(tracker_error)
+    }
+
+    /// The user's currently-watching list, as the episodes it says they have
+    /// seen.
+    async fn import_history(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<Vec<RemoteWatch>> {
+        let creds = KitsuCredentials::read(creds)?;
+        let client = creds.client(ctx)?;
+        let mut out = Vec::new();
+        for page in 0..MAX_LIBRARY_PAGES {
+            let entries = client
+                .execute(sdks::kitsu::LibraryEntriesEndpoint {
+                    user_id: creds.user_id,
+                    anime_id: None,
+                    status: Some(LibraryStatus::Current),
+                    limit: LIBRARY_PAGE,
+                    offset: page * LIBRARY_PAGE,
+                })
+                .await
+                .map_err(tracker_error)?
+                .data;
+            let fetched = entries.len();
+            for entry in entries {
+                let (Some(kitsu_id), Some(progress)) = (
+                    entry.related_id("anime"),
+                    entry
+                        .attributes
+                        .progress,
+                ) else {
+                    continue;
+                };
+                let updated_at = entry
+                    .attributes
+                    .updated_at
+                    .map(|t| t.naive_utc());
+                out.extend(watched_episodes(kitsu_id, progress, updated_at));
+            }
+            if fetched < LIBRARY_PAGE as usize {
+                break;
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::{
+        Method::{GET, PATCH, POST},
+        MockServer,
+    };
+
+    fn ctx(server: &MockServer) -> MediaTrackerCtx {
+        MediaTrackerCtx {
+            config: Arc::new(crate::Config {
+                kitsu_base_url: server.base_url(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn creds() -> MediaTrackerCredentials {
+        KitsuCredentials {
+            access_token: "tok".into(),
+            refresh_token: "ref".into(),
+            expires_at: Utc::now().timestamp() + 30 * 86_400,
+            user_id: 42,
+        }
+        .into_credentials()
+    }
+
+    fn episode(
+        series_ids: db::ExternalIds,
+        season: i64,
+        number: i64,
+    ) -> MediaTrackerTarget {
+        MediaTrackerTarget {
+            kind: db::MediaKind::Episode,
+            title: "Asteroid Blues".into(),
+            year: None,
+            ids: db::ExternalIds::default(),
+            series: Some(Box::new(MediaTrackerTarget {
+                kind: db::MediaKind::Series,
+                title: "Cowboy Bebop".into(),
+                year: Some(1998),
+                ids: series_ids,
+                series: None,
+                season: None,
+                episode: None,
+                runtime: None,
+                artist: None,
+                album: None,
+            })),
+            season: Some(season),
+            episode: Some(number),
+            runtime: None,
+            artist: None,
+            album: None,
+        }
+    }
+
+    fn kitsu_ids(id: i64) -> db::ExternalIds {
+        db::ExternalIds {
+            kitsu: Some(id),
+            ..Default::default()
+        }
+    }
+
+    fn played() -> MediaTrackerEvent {
+        MediaTrackerEvent::PlaybackStop {
+            position_ticks: 0,
+            played: true,
+        }
+    }
+
+    fn library(entries: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({ "data": entries })
+    }
+
+    #[test]
+    fn progress_only_moves_forward_and_finishes_on_the_last_episode() {
+        assert_eq!(next_progress(None, 1, Some(26)), Some((1, false)));
+        assert_eq!(next_progress(Some(3), 4, Some(26)), Some((4, false)));
+        assert_eq!(next_progress(Some(25), 26, Some(26)), Some((26, true)));
+        assert_eq!(next_progress(Some(5), 2, Some(26)), None);
+        assert_eq!(next_progress(Some(5), 5, Some(26)), None);
+        assert_eq!(
+            next_progress(Some(5), 6, None),
+            Some((6, false)),
+            "an open-ended show is never finished"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_watched_episode_advances_an_existing_entry() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/library-entries")
+                .query_param("filter[userId]", "42")
+                .query_param("filter[animeId]", "1");
+            then.status(200)
+                .json_body(library(serde_json::json!([{
+                    "id": "900",
+                    "type": "libraryEntries",
+                    "attributes": { "status": "current", "progress": 4 },
+                }])));
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/anime/1");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": {
+                        "id": "1",
+                        "type": "anime",
+                        "attributes": { "canonicalTitle": "Cowboy Bebop", "episodeCount": 26 },
+                    }
+                }));
+        });
+        let update = server.mock(|when, then| {
+            when.method(PATCH)
+                .path("/edge/library-entries/900")
+                .header("content-type", "application/vnd.api+json")
+                .body_contains(r#""progress":5"#)
+                .body_contains(r#""status":"current""#);
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": {
+                        "id": "900",
+                        "type": "libraryEntries",
+                        "attributes": { "status": "current", "progress": 5 },
+                    }
+                }));
+        });
+
+        KitsuAddon
+            .on_event(
+                &played(),
+                &episode(kitsu_ids(1), 1, 5),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        update.assert();
+    }
+
+    #[tokio::test]
+    async fn a_rewatched_episode_leaves_progress_alone() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/library-entries");
+            then.status(200)
+                .json_body(library(serde_json::json!([{
+                    "id": "900",
+                    "type": "libraryEntries",
+                    "attributes": { "status": "completed", "progress": 26 },
+                }])));
+        });
+        let writes = server.mock(|when, then| {
+            when.method(PATCH);
+            then.status(200);
+        });
+
+        KitsuAddon
+            .on_event(
+                &played(),
+                &episode(kitsu_ids(1), 1, 3),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        assert_eq!(writes.hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn a_first_episode_creates_the_entry_through_a_tvdb_mapping() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/mappings")
+                .query_param("filter[externalSite]", "thetvdb/series")
+                .query_param("filter[externalId]", "76885");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": [{
+                        "id": "5",
+                        "type": "mappings",
+                        "attributes": { "externalSite": "thetvdb/series", "externalId": "76885" },
+                    }],
+                    "included": [{ "id": "1", "type": "anime", "attributes": {} }],
+                }));
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/library-entries");
+            then.status(200)
+                .json_body(library(serde_json::json!([])));
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/anime/1");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "data": { "id": "1", "type": "anime", "attributes": { "episodeCount": 26 } }
+                }));
+        });
+        let create = server.mock(|when, then| {
+            when.method(POST)
+                .path("/edge/library-entries")
+                .body_contains(r#""progress":1"#)
+                .body_contains(r#""id":"42""#);
+            then.status(201)
+                .json_body(serde_json::json!({
+                    "data": {
+                        "id": "901",
+                        "type": "libraryEntries",
+                        "attributes": { "status": "current", "progress": 1 },
+                    }
+                }));
+        });
+
+        let tvdb = db::ExternalIds {
+            tvdb: Some(76885),
+            ..Default::default()
+        };
+        KitsuAddon
+            .on_event(&played(), &episode(tvdb, 1, 1), &creds(), &ctx(&server))
+            .await
+            .unwrap();
+        create.assert();
+    }
+
+    #[tokio::test]
+    async fn a_later_season_without_a_kitsu_id_is_not_guessed() {
+        let server = MockServer::start();
+        let requests = server.mock(|_, then| {
+            then.status(500);
+        });
+        let tvdb = db::ExternalIds {
+            tvdb: Some(76885),
+            ..Default::default()
+        };
+
+        KitsuAddon
+            .on_event(&played(), &episode(tvdb, 2, 1), &creds(), &ctx(&server))
+            .await
+            .unwrap();
+        assert_eq!(requests.hits(), 0);
+    }
+
+    /// Most of a library is not anime. Its episodes are let through without
+    /// failing the delivery, and the show is looked up once, not per episode.
+    #[tokio::test]
+    async fn a_show_that_is_not_anime_is_skipped_and_remembered() {
+        let server = MockServer::start();
+        let lookup = server.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/mappings")
+                .query_param("filter[externalId]", "81189");
+            then.status(200)
+                .json_body(serde_json::json!({ "data": [] }));
+        });
+        let tvdb = db::ExternalIds {
+            tvdb: Some(81189),
+            ..Default::default()
+        };
+
+        for number in 1..=2 {
+            KitsuAddon
+                .on_event(
+                    &played(),
+                    &episode(tvdb.clone(), 1, number),
+                    &creds(),
+                    &ctx(&server),
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(lookup.hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn the_watching_list_imports_as_watched_episodes() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/library-entries")
+                .query_param("filter[status]", "current")
+                .query_param("include", "anime");
+            then.status(200)
+                .json_body(library(serde_json::json!([{
+                    "id": "900",
+                    "type": "libraryEntries",
+                    "attributes": { "status": "current", "progress": 3 },
+                    "relationships": { "anime": { "data": { "type": "anime", "id": "1" } } },
+                }])));
+        });
+
+        let watched = KitsuAddon
+            .import_history(&creds(), &ctx(&server))
+            .await
+            .unwrap();
+        mock.assert();
+        assert_eq!(watched.len(), 3);
+        assert!(
+            watched
+                .iter()
+                .all(|w| w.watched
+                    && w.ids
+                        .kitsu
+                        == Some(1))
+        );
+        assert_eq!(watched[2].episode, Some(3));
+    }
+
+    #[tokio::test]
+    async fn connect_trades_the_password_for_a_token() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/oauth/token")
+                .x_www_form_urlencoded_tuple("grant_type", "password")
+                .x_www_form_urlencoded_tuple("username", "spike@example.com");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "access_token": "tok",
+                    "refresh_token": "ref",
+                    "expires_in": 2_592_000,
+                    "created_at": 1_700_000_000,
+                    "token_type": "bearer",
+                }));
+        });
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/edge/users")
+                .header("authorization", "Bearer tok");
+            then.status(200)
+                .json_body(library(serde_json::json!([{
+                    "id": "42",
+                    "type": "users",
+                    "attributes": { "name": "spike" },
+                }])));
+        });
+
+        let creds = KitsuAddon
+            .connect_with_token(
+                &serde_json::json!({ "username": "spike@example.com", "password": "pw" }),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        assert_eq!(creds.get_str("access_token"), Some("tok"));
+        assert_eq!(creds.expose()["user_id"], 42);
+        assert!(
+            creds
+                .expose()
+                .get("password")
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,724 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{sync::Arc, time::Duration};
+use uuid::Uuid;
+
+use super::{
+    AddonCapabilities, AddonKind, AddonMetadata, AddonOption, AddonOptionType,
+    AddonPreset, AddonPresetRegistration, MediaKind, ResourceType,
+    media_tracker::{
+        AuthFlow, DeviceAuthPoll, DeviceAuthStart, MediaTrackerAddon,
+        MediaTrackerCapabilities, MediaTrackerCredentials, MediaTrackerCtx,
+        MediaTrackerError, MediaTrackerEvent, MediaTrackerEventKind,
+        MediaTrackerResult, MediaTrackerTarget, RemoteWatch, SyncDirection,
+    },
+};
+use crate::{
+    db,
+    sdks::{
+        self,
+        lastfm::{LastFmApp, LastFmTrack, codes},
+    },
+};
+
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+/// Last.fm auth tokens are good for an hour.
+const AUTH_TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+const AUTH_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Loved tracks page size. Last.fm allows up to 1000 but slows down well
+/// before that.
+const LOVED_PAGE: u32 = 200;
+/// Stops a runaway walk; the rest arrives with the next sweep.
+const MAX_LOVED_PAGES: u32 = 50;
+
+pub struct LastFmPreset;
+
+impl AddonPreset for LastFmPreset {
+    fn id(&self) -> &'static str {
+        "lastfm"
+    }
+
+    fn metadata(&self) -> AddonMetadata {
+        AddonMetadata {
+            id: "lastfm".to_string(),
+            display_name: "Last.fm".to_string(),
+            description: "Scrobbles what users listen to to Last.fm, and syncs their \
+                          loved tracks both ways."
+                .to_string(),
+            icon: None,
+            supported_resources: vec![AddonMetadata::simple_resource(
+                ResourceType::Tracking,
+            )],
+            supported_types: vec![MediaKind::Track],
+            supported_resources_user: vec![],
+            supported_types_user: vec![],
+            options: vec![
+                AddonOption {
+                    id: "api_key".to_string(),
+                    name: "Last.fm API key".to_string(),
+                    description: Some(
+                        "Create an API account at last.fm/api/account/create."
+                            .to_string(),
+                    ),
+                    required: true,
+                    default: None,
+                    kind: AddonOptionType::Password,
+                },
+                AddonOption {
+                    id: "shared_secret".to_string(),
+                    name: "Last.fm shared secret".to_string(),
+                    description: Some(
+                        "The same API account's shared secret.".to_string(),
+                    ),
+                    required: true,
+                    default: None,
+                    kind: AddonOptionType::Password,
+                },
+            ],
+        }
+    }
+
+    fn from_cfg(
+        &self,
+        _addon_id: Uuid,
+        cfg: &serde_json::Value,
+        _config: &crate::Config,
+    ) -> Result<AddonCapabilities> {
+        let field = |key: &str| {
+            cfg.get(key)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        // Every call is signed with the app's secret, so without both halves
+        // there is nothing to offer.
+        let addon = match (field("api_key"), field("shared_secret")) {
+            (Some(api_key), Some(secret)) => Arc::new(LastFmAddon {
+                app: LastFmApp { api_key, secret },
+            }),
+            _ => return Ok(AddonCapabilities::default()),
+        };
+        Ok(AddonCapabilities {
+            kind: Some(addon.clone()),
+            media_tracker: Some(addon),
+            ..Default::default()
+        })
+    }
+}
+
+inventory::submit! {
+    AddonPresetRegistration(|| Box::new(LastFmPreset))
+}
+
+pub struct LastFmAddon {
+    app: LastFmApp,
+}
+
+#[async_trait]
+impl AddonKind for LastFmAddon {
+    fn id(&self) -> &'static str {
+        "lastfm"
+    }
+}
+
+/// Sorts a Last.fm failure into what the dispatcher should do with it. The
+/// API's own error code says more than the HTTP status it rides on.
+fn tracker_error(err: sdks::ClientError) -> MediaTrackerError {
+    match sdks::lastfm::error_code(&err) {
+        Some(codes::INVALID_SESSION_KEY | codes::AUTHENTICATION_FAILED) => {
+            return MediaTrackerError::reauth("Last.fm rejected the session");
+        }
+        Some(codes::INVALID_API_KEY | codes::SUSPENDED_API_KEY) => {
+            return MediaTrackerError::permanent(
+                "Last.fm rejected the server's API key",
+            );
+        }
+        Some(codes::SERVICE_OFFLINE | codes::TEMPORARILY_UNAVAILABLE) => {
+            return MediaTrackerError::retryable("Last.fm is unavailable");
+        }
+        Some(codes::RATE_LIMIT_EXCEEDED) => {
+            return MediaTrackerError::retryable("Last.fm rate limit");
+        }
+        _ => {}
+    }
+    match err {
+        sdks::ClientError::Unauthorized => {
+            MediaTrackerError::reauth("Last.fm rejected the session")
+        }
+        sdks::ClientError::RateLimited { retry_after_secs } => {
+            MediaTrackerError::retry_after(
+                "Last.fm rate limit",
+                Duration::from_secs(retry_after_secs),
+            )
+        }
+        sdks::ClientError::Http { status, .. } if status >= 500 => {
+            MediaTrackerError::retryable(format!("Last.fm returned {status}"))
+        }
+        sdks::ClientError::Http { message, .. } => {
+            MediaTrackerError::permanent(format!("Last.fm: {message}"))
+        }
+        sdks::ClientError::Transport(e) => {
+            MediaTrackerError::retryable(format!("reaching Last.fm: {e}"))
+        }
+        other => MediaTrackerError::permanent(other.to_string()),
+    }
+}
+
+fn client(ctx: &MediaTrackerCtx) -> MediaTrackerResult<sdks::RestClient> {
+    sdks::lastfm::lastfm_client(
+        &ctx.config
+            .lastfm_base_url,
+    )
+    .map_err(|e| MediaTrackerError::permanent(e.to_string()))
+}
+
+fn session_key(creds: &MediaTrackerCredentials) -> MediaTrackerResult<String> {
+    creds
+        .get_str("session_key")
+        .map(str::to_string)
+        .ok_or_else(|| MediaTrackerError::reauth("no Last.fm session stored"))
+}
+
+/// Last.fm keys a scrobble on names, so a track without an artist cannot be
+/// sent at all.
+fn lastfm_track(target: &MediaTrackerTarget) -> MediaTrackerResult<LastFmTrack> {
+    Ok(LastFmTrack {
+        artist: target
+            .artist
+            .clone()
+            .ok_or_else(|| MediaTrackerError::permanent("track has no artist"))?,
+        track: target
+            .title
+            .clone(),
+        album: target
+            .album
+            .clone(),
+        duration: target.runtime,
+    })
+}
+
+#[async_trait]
+impl MediaTrackerAddon for LastFmAddon {
+    fn capabilities(&self) -> MediaTrackerCapabilities {
+        MediaTrackerCapabilities {
+            auth_flow: AuthFlow::OAuthDeviceCode,
+            supported_events: vec![
+                MediaTrackerEventKind::PlaybackStart,
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::Favorite,
+            ],
+            media_kinds: vec![db::MediaKind::Track],
+            default_event_filter: vec![
+                MediaTrackerEventKind::PlaybackStart,
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::Favorite,
+            ],
+            favorites: SyncDirection::Both,
+            ..Default::default()
+        }
+    }
+
+    /// Last.fm's desktop flow: the user approves a token on last.fm, then
+    /// the token is traded for a session. The approval link carries the
+    /// token, so there is nothing separate to type.
+    async fn begin_device_auth(
+        &self,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<DeviceAuthStart> {
+        let token = client(ctx)?
+            .execute(sdks::lastfm::GetTokenEndpoint {
+                app: self
+                    .app
+                    .clone(),
+            })
+            .await
+            .map_err(tracker_error)?
+            .token;
+        Ok(DeviceAuthStart {
+            verification_url: sdks::lastfm::auth_url(
+                &self
+                    .app
+                    .api_key,
+                &token,
+            ),
+            user_code: token.clone(),
+            poll_token: token,
+            interval: AUTH_POLL_INTERVAL,
+            expires_in: AUTH_TOKEN_TTL,
+        })
+    }
+
+    async fn poll_device_auth(
+        &self,
+        poll_token: &str,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<DeviceAuthPoll> {
+        let resp = client(ctx)?
+            .execute(sdks::lastfm::GetSessionEndpoint {
+                app: self
+                    .app
+                    .clone(),
+                token: poll_token.to_string(),
+            })
+            .await;
+        match resp {
+            Ok(resp) => Ok(DeviceAuthPoll::Approved(MediaTrackerCredentials::new(
+                serde_json::json!({
+                    "session_key": resp.session.key,
+                    "user_name": resp.session.name,
+                }),
+            ))),
+            Err(e) => match sdks::lastfm::error_code(&e) {
+                Some(codes::TOKEN_NOT_AUTHORIZED) => Ok(DeviceAuthPoll::Pending),
+                Some(codes::TOKEN_EXPIRED | codes::AUTHENTICATION_FAILED) => {
+                    Ok(DeviceAuthPoll::Denied)
+                }
+                _ => Err(tracker_error(e)),
+            },
+        }
+    }
+
+    async fn verify(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        client(ctx)?
+            .execute(sdks::lastfm::SessionUserEndpoint {
+                app: self
+                    .app
+                    .clone(),
+                session_key: session_key(creds)?,
+            })
+            .await
+            .map_err(tracker_error)?;
+        Ok(())
+    }
+
+    async fn on_event(
+        &self,
+        event: &MediaTrackerEvent,
+        target: &MediaTrackerTarget,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        let app = self
+            .app
+            .clone();
+        let session_key = session_key(creds)?;
+        let client = client(ctx)?;
+        match event {
+            MediaTrackerEvent::PlaybackStart { .. } => {
+                client
+                    .execute(sdks::lastfm::UpdateNowPlayingEndpoint {
+                        app,
+                        session_key,
+                        track: lastfm_track(target)?,
+                    })
+                    .await
+                    .map_err(tracker_error)?;
+            }
+            MediaTrackerEvent::PlaybackStop {
+                position_ticks,
+                played,
+            } => {
+                if !target.counts_as_listen(*position_ticks, *played) {
+                    return Ok(());
+                }
+                let resp = client
+                    .execute(sdks::lastfm::ScrobbleEndpoint {
+                        app,
+                        session_key,
+                        track: lastfm_track(target)?,
+                        // A scrobble is stamped with when the track began.
+                        timestamp: Utc::now().timestamp()
+                            - position_ticks / TICKS_PER_SECOND,
+                    })
+                    .await
+                    .map_err(tracker_error)?;
+                // Last.fm answers 200 even when its filters drop a scrobble;
+                // sending it again would be dropped the same way.
+                if resp
+                    .scrobbles
+                    .counts
+                    .accepted
+                    == 0
+                {
+                    return Err(MediaTrackerError::permanent(
+                        "Last.fm ignored the scrobble",
+                    ));
+                }
+            }
+            MediaTrackerEvent::Favorite { is_favorite } => {
+                let track = lastfm_track(target)?;
+                client
+                    .execute(sdks::lastfm::LoveEndpoint {
+                        app,
+                        session_key,
+                        artist: track.artist,
+                        track: track.track,
+                        love: *is_favorite,
+                    })
+                    .await
+                    .map_err(tracker_error)?;
+            }
+            other => {
+                return Err(MediaTrackerError::unsupported(
+                    &other
+                        .kind()
+                        .to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Loved tracks, newest first, so a `since` stops the walk early.
+    async fn pull_changes(
+        &self,
+        since: Option<chrono::NaiveDateTime>,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<Vec<RemoteWatch>> {
+        let user = creds
+            .get_str("user_name")
+            .ok_or_else(|| MediaTrackerError::reauth("no Last.fm user stored"))?
+            .to_string();
+        let client = client(ctx)?;
+        let mut out = Vec::new();
+        for page in 1..=MAX_LOVED_PAGES {
+            let resp = client
+                .execute(sdks::lastfm::LovedTracksEndpoint {
+                    api_key: self
+                        .app
+                        .api_key
+                        .clone(),
+                    user: user.clone(),
+                    page,
+                    limit: LOVED_PAGE,
+                })
+                .await
+                .map_err(tracker_error)?
+                .lovedtracks;
+            let mut reached_since = false;
+            for loved in resp.track {
+                let loved_at = loved
+                    .date
+                    .and_then(|d| DateTime::from_timestamp(d.uts, 0))
+                    .map(|t| t.naive_utc());
+                if let (Some(since), Some(at)) = (since, loved_at) {
+                    if at < since {
+                        reached_since = true;
+                        break;
+                    }
+                }
+                out.push(RemoteWatch {
+                    ids: db::ExternalIds {
+                        artist_name: Some(
+                            loved
+                                .artist
+                                .name,
+                        ),
+                        ..Default::default()
+                    },
+                    title: Some(loved.name),
+                    season: None,
+                    episode: None,
+                    watched: false,
+                    position_ticks: None,
+                    watched_at: None,
+                    favorite: Some(true),
+                    rating: None,
+                });
+            }
+            if reached_since
+                || page
+                    >= resp
+                        .attr
+                        .total_pages
+            {
+                break;
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::{
+        Method::{GET, POST},
+        MockServer,
+    };
+
+    fn addon() -> LastFmAddon {
+        LastFmAddon {
+            app: LastFmApp {
+                api_key: "key".into(),
+                secret: "secret".into(),
+            },
+        }
+    }
+
+    #[test]
+    fn a_configured_preset_registers_its_kind() {
+        let config = crate::Config::default();
+        let caps = LastFmPreset
+            .from_cfg(
+                Uuid::nil(),
+                &serde_json::json!({ "api_key": "key", "shared_secret": "secret" }),
+                &config,
+            )
+            .unwrap();
+        assert!(
+            caps.kind
+                .is_some()
+        );
+        assert!(
+            caps.media_tracker
+                .is_some()
+        );
+
+        let caps = LastFmPreset
+            .from_cfg(
+                Uuid::nil(),
+                &serde_json::json!({ "api_key": "key" }),
+                &config,
+            )
+            .unwrap();
+        assert!(
+            caps.media_tracker
+                .is_none()
+        );
+    }
+
+    fn ctx(server: &MockServer) -> MediaTrackerCtx {
+        MediaTrackerCtx {
+            config: Arc::new(crate::Config {
+                lastfm_base_url: server.base_url(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn creds() -> MediaTrackerCredentials {
+        MediaTrackerCredentials::new(serde_json::json!({
+            "session_key": "sk",
+            "user_name": "rob",
+        }))
+    }
+
+    fn track(runtime: Option<i64>) -> MediaTrackerTarget {
+        MediaTrackerTarget {
+            kind: db::MediaKind::Track,
+            title: "Teardrop".into(),
+            year: Some(1998),
+            ids: db::ExternalIds::default(),
+            series: None,
+            season: None,
+            episode: None,
+            runtime,
+            artist: Some("Massive Attack".into()),
+            album: Some("Mezzanine".into()),
+        }
+    }
+
+    fn stop(seconds: i64) -> MediaTrackerEvent {
+        MediaTrackerEvent::PlaybackStop {
+            position_ticks: seconds * TICKS_PER_SECOND,
+            played: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn four_minutes_is_a_scrobble_even_on_a_long_track() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .x_www_form_urlencoded_tuple("method", "track.scrobble")
+                .x_www_form_urlencoded_tuple("artist", "Massive Attack")
+                .x_www_form_urlencoded_tuple("album", "Mezzanine")
+                .x_www_form_urlencoded_tuple("sk", "sk")
+                .x_www_form_urlencoded_key_exists("api_sig");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "scrobbles": { "@attr": { "accepted": 1, "ignored": 0 } }
+                }));
+        });
+
+        addon()
+            .on_event(&stop(240), &track(Some(1200)), &creds(), &ctx(&server))
+            .await
+            .unwrap();
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn nothing_is_scrobbled_for_a_track_under_thirty_seconds() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/");
+            then.status(200);
+        });
+
+        addon()
+            .on_event(&stop(25), &track(Some(25)), &creds(), &ctx(&server))
+            .await
+            .unwrap();
+        assert_eq!(mock.hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn an_ignored_scrobble_is_not_retried() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "scrobbles": { "@attr": { "accepted": "0", "ignored": "1" } }
+                }));
+        });
+
+        let err = addon()
+            .on_event(&stop(300), &track(Some(330)), &creds(), &ctx(&server))
+            .await
+            .unwrap_err();
+        assert!(!err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn an_invalid_session_asks_the_user_to_reconnect() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/");
+            then.status(403)
+                .json_body(serde_json::json!({
+                    "error": 9,
+                    "message": "Invalid session key - Please re-authenticate",
+                }));
+        });
+
+        let err = addon()
+            .on_event(
+                &MediaTrackerEvent::PlaybackStart { position_ticks: 0 },
+                &track(None),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.requires_reauth());
+    }
+
+    #[tokio::test]
+    async fn a_favourite_loves_and_unloves() {
+        let server = MockServer::start();
+        let unlove = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .x_www_form_urlencoded_tuple("method", "track.unlove")
+                .x_www_form_urlencoded_tuple("track", "Teardrop");
+            then.status(200)
+                .json_body(serde_json::json!({}));
+        });
+
+        addon()
+            .on_event(
+                &MediaTrackerEvent::Favorite { is_favorite: false },
+                &track(None),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        unlove.assert();
+    }
+
+    #[tokio::test]
+    async fn polling_waits_for_approval_then_stores_the_session() {
+        let server = MockServer::start();
+        let mut pending = server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .x_www_form_urlencoded_tuple("method", "auth.getSession")
+                .x_www_form_urlencoded_tuple("token", "t1");
+            then.status(403)
+                .json_body(serde_json::json!({
+                    "error": 14,
+                    "message": "Unauthorized Token - This token has not been issued",
+                }));
+        });
+        assert!(matches!(
+            addon()
+                .poll_device_auth("t1", &ctx(&server))
+                .await
+                .unwrap(),
+            DeviceAuthPoll::Pending
+        ));
+        pending.delete();
+
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/")
+                .x_www_form_urlencoded_tuple("method", "auth.getSession");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "session": { "name": "rob", "key": "sk", "subscriber": 0 }
+                }));
+        });
+        let DeviceAuthPoll::Approved(creds) = addon()
+            .poll_device_auth("t1", &ctx(&server))
+            .await
+            .unwrap()
+        else {
+            panic!("expected approval");
+        };
+        assert_eq!(creds.get_str("session_key"), Some("sk"));
+        assert_eq!(creds.get_str("user_name"), Some("rob"));
+    }
+
+    #[tokio::test]
+    async fn loved_tracks_come_back_as_favourites_by_name() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/")
+                .query_param("method", "user.getLovedTracks")
+                .query_param("user", "rob");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "lovedtracks": {
+                        "track": [{
+                            "name": "Teardrop",
+                            "artist": { "name": "Massive Attack" },
+                            "date": { "uts": "1700000000" },
+                        }],
+                        "@attr": { "page": "1", "totalPages": "1" },
+                    }
+                }));
+        });
+
+        let loved = addon()
+            .pull_changes(None, &creds(), &ctx(&server))
+            .await
+            .unwrap();
+        mock.assert();
+        assert_eq!(loved.len(), 1);
+        assert_eq!(
+            loved[0]
+                .title
+                .as_deref(),
+            Some("Teardrop")
+        );
+        assert_eq!(loved[0].favorite, Some(true));
+    }
+}
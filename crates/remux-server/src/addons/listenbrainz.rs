@@ -0,0 +1,554 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{sync::Arc, time::Duration};
+use uuid::Uuid;
+
+use super::{
+    AddonCapabilities, AddonKind, AddonMetadata, AddonOption, AddonOptionType,
+    AddonPreset, AddonPresetRegistration, MediaKind, ResourceType,
+    media_tracker::{
+        AuthFlow, MediaTrackerAddon, MediaTrackerCapabilities, MediaTrackerCredentials,
+        MediaTrackerCtx, MediaTrackerError, MediaTrackerEvent, MediaTrackerEventKind,
+        MediaTrackerResult, MediaTrackerTarget, RemoteWatch, SyncDirection,
+    },
+};
+use crate::{
+    db,
+    sdks::{
+        self,
+        listenbrainz::{Listen, ListenType, TrackMetadata},
+    },
+};
+
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+/// Feedback page size; ListenBrainz caps it at 100.
+const FEEDBACK_PAGE: u32 = 100;
+/// Stops a runaway walk; the rest arrives with the next sweep.
+const MAX_FEEDBACK_PAGES: u32 = 50;
+
+pub struct ListenBrainzPreset;
+
+impl AddonPreset for ListenBrainzPreset {
+    fn id(&self) -> &'static str {
+        "listenbrainz"
+    }
+
+    fn metadata(&self) -> AddonMetadata {
+        AddonMetadata {
+            id: "listenbrainz".to_string(),
+            display_name: "ListenBrainz".to_string(),
+            description: "Submits what users listen to to ListenBrainz, and imports \
+                          their loved tracks as favourites."
+                .to_string(),
+            icon: None,
+            supported_resources: vec![AddonMetadata::simple_resource(
+                ResourceType::Tracking,
+            )],
+            supported_types: vec![MediaKind::Track],
+            supported_resources_user: vec![],
+            supported_types_user: vec![],
+            options: vec![],
+        }
+    }
+
+    fn from_cfg(
+        &self,
+        _addon_id: Uuid,
+        _cfg: &serde_json::Value,
+        _config: &crate::Config,
+    ) -> Result<AddonCapabilities> {
+        let addon = Arc::new(ListenBrainzAddon);
+        Ok(AddonCapabilities {
+            kind: Some(addon.clone()),
+            media_tracker: Some(addon),
+            ..Default::default()
+        })
+    }
+}
+
+inventory::submit! {
+    AddonPresetRegistration(|| Box::new(ListenBrainzPreset))
+}
+
+pub struct ListenBrainzAddon;
+
+#[async_trait]
+impl AddonKind for ListenBrainzAddon {
+    fn id(&self) -> &'static str {
+        "listenbrainz"
+    }
+}
+
+/// Sorts a ListenBrainz failure into what the dispatcher should do with it.
+fn tracker_error(err: sdks::ClientError) -> MediaTrackerError {
+    match err {
+        sdks::ClientError::Unauthorized => {
+            MediaTrackerError::reauth("ListenBrainz rejected the user token")
+        }
+        sdks::ClientError::RateLimited { retry_after_secs } => {
+            MediaTrackerError::retry_after(
+                "ListenBrainz rate limit",
+                Duration::from_secs(retry_after_secs),
+            )
+        }
+        sdks::ClientError::Http { status, .. } if status >= 500 => {
+            MediaTrackerError::retryable(format!("ListenBrainz returned {status}"))
+        }
+        sdks::ClientError::Http { status, .. } => {
+            MediaTrackerError::permanent(format!("ListenBrainz returned {status}"))
+        }
+        sdks::ClientError::Transport(e) => {
+            MediaTrackerError::retryable(format!("reaching ListenBrainz: {e}"))
+        }
+        other => MediaTrackerError::permanent(other.to_string()),
+    }
+}
+
+fn client(
+    token: &str,
+    ctx: &MediaTrackerCtx,
+) -> MediaTrackerResult<sdks::RestClient<sdks::listenbrainz::ListenBrainzAuth>> {
+    sdks::listenbrainz::listenbrainz_client(
+        token,
+        &ctx.config
+            .listenbrainz_base_url,
+    )
+    .map_err(|e| MediaTrackerError::permanent(e.to_string()))
+}
+
+fn token(creds: &MediaTrackerCredentials) -> MediaTrackerResult<&str> {
+    creds
+        .get_str("token")
+        .ok_or_else(|| MediaTrackerError::reauth("no ListenBrainz token stored"))
+}
+
+/// ListenBrainz keys a listen on names, so a track without an artist cannot
+/// be submitted at all.
+fn track_metadata(target: &MediaTrackerTarget) -> MediaTrackerResult<TrackMetadata> {
+    let artist_name = target
+        .artist
+        .clone()
+        .ok_or_else(|| MediaTrackerError::permanent("track has no artist"))?;
+    Ok(TrackMetadata {
+        artist_name,
+        track_name: target
+            .title
+            .clone(),
+        release_name: target
+            .album
+            .clone(),
+        additional_info: Some(sdks::listenbrainz::AdditionalInfo {
+            duration_ms: target
+                .runtime
+                .map(|s| s * 1000),
+            submission_client: Some("remux".to_string()),
+            media_player: None,
+        }),
+    })
+}
+
+#[async_trait]
+impl MediaTrackerAddon for ListenBrainzAddon {
+    fn capabilities(&self) -> MediaTrackerCapabilities {
+        MediaTrackerCapabilities {
+            auth_flow: AuthFlow::Token,
+            connect_fields: vec![AddonOption {
+                id: "token".to_string(),
+                name: "User token".to_string(),
+                description: Some("Found at listenbrainz.org/settings.".to_string()),
+                required: true,
+                default: None,
+                kind: AddonOptionType::Password,
+            }],
+            supported_events: vec![
+                MediaTrackerEventKind::PlaybackStart,
+                MediaTrackerEventKind::PlaybackStop,
+            ],
+            media_kinds: vec![db::MediaKind::Track],
+            default_event_filter: vec![
+                MediaTrackerEventKind::PlaybackStart,
+                MediaTrackerEventKind::PlaybackStop,
+            ],
+            // Loving a recording takes its MusicBrainz id, which a local
+            // track does not have, so favourites only come in.
+            favorites: SyncDirection::Pull,
+            ..Default::default()
+        }
+    }
+
+    async fn connect_with_token(
+        &self,
+        fields: &serde_json::Value,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<MediaTrackerCredentials> {
+        let token = fields
+            .get("token")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| MediaTrackerError::permanent("a user token is required"))?;
+        let validation = client(token, ctx)?
+            .execute(sdks::listenbrainz::ValidateTokenEndpoint)
+            .await
+            .map_err(tracker_error)?;
+        let user_name = validation
+            .user_name
+            .filter(|_| validation.valid)
+            .ok_or_else(|| {
+                MediaTrackerError::permanent("ListenBrainz rejected the user token")
+            })?;
+        Ok(MediaTrackerCredentials::new(serde_json::json!({
+            "token": token,
+            "user_name": user_name,
+        })))
+    }
+
+    async fn verify(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        let validation = client(token(creds)?, ctx)?
+            .execute(sdks::listenbrainz::ValidateTokenEndpoint)
+            .await
+            .map_err(tracker_error)?;
+        if validation.valid {
+            Ok(())
+        } else {
+            Err(MediaTrackerError::reauth(
+                "ListenBrainz rejected the user token",
+            ))
+        }
+    }
+
+    async fn on_event(
+        &self,
+        event: &MediaTrackerEvent,
+        target: &MediaTrackerTarget,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        let (listen_type, listened_at) = match event {
+            MediaTrackerEvent::PlaybackStart { .. } => (ListenType::PlayingNow, None),
+            MediaTrackerEvent::PlaybackStop {
+                position_ticks,
+                played,
+            } => {
+                if !target.counts_as_listen(*position_ticks, *played) {
+                    return Ok(());
+                }
+                // A listen is stamped with when it began.
+                let started =
+                    Utc::now().timestamp() - position_ticks / TICKS_PER_SECOND;
+                (ListenType::Single, Some(started))
+            }
+            other => {
+                return Err(MediaTrackerError::unsupported(
+                    &other
+                        .kind()
+                        .to_string(),
+                ));
+            }
+        };
+        client(token(creds)?, ctx)?
+            .execute(sdks::listenbrainz::SubmitListensEndpoint {
+                listen_type,
+                listens: vec![Listen {
+                    listened_at,
+                    track_metadata: track_metadata(target)?,
+                }],
+            })
+            .await
+            .map_err(tracker_error)?;
+        Ok(())
+    }
+
+    /// Loved tracks only; ListenBrainz listens are not user data to bring
+    /// back. Newest first, so a `since` stops the walk early.
+    async fn pull_changes(
+        &self,
+        since: Option<chrono::NaiveDateTime>,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<Vec<RemoteWatch>> {
+        let user_name = creds
+            .get_str("user_name")
+            .ok_or_else(|| MediaTrackerError::reauth("no ListenBrainz user stored"))?
+            .to_string();
+        let client = client(token(creds)?, ctx)?;
+        let mut out = Vec::new();
+        for page in 0..MAX_FEEDBACK_PAGES {
+            let resp = client
+                .execute(sdks::listenbrainz::UserFeedbackEndpoint {
+                    user_name: user_name.clone(),
+                    score: 1,
+                    count: FEEDBACK_PAGE,
+                    offset: page * FEEDBACK_PAGE,
+                })
+                .await
+                .map_err(tracker_error)?;
+            let fetched = resp
+                .feedback
+                .len();
+            let mut reached_since = false;
+            for feedback in resp.feedback {
+                let loved_at = feedback
+                    .created
+                    .and_then(|t| DateTime::from_timestamp(t, 0))
+                    .map(|t| t.naive_utc());
+                if let (Some(since), Some(at)) = (since, loved_at) {
+                    if at < since {
+                        reached_since = true;
+                        break;
+                    }
+                }
+                let Some(meta) = feedback.track_metadata else {
+                    continue;
+                };
+                out.push(RemoteWatch {
+                    ids: db::ExternalIds {
+                        artist_name: Some(meta.artist_name),
+                        album_title: meta.release_name,
+                        ..Default::default()
+                    },
+                    title: Some(meta.track_name),
+                    season: None,
+                    episode: None,
+                    watched: false,
+                    position_ticks: None,
+                    watched_at: None,
+                    favorite: Some(true),
+                    rating: None,
+                });
+            }
+            if reached_since || fetched < FEEDBACK_PAGE as usize {
+                break;
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::{
+        Method::{GET, POST},
+        MockServer,
+    };
+
+    fn ctx(server: &MockServer) -> MediaTrackerCtx {
+        MediaTrackerCtx {
+            config: Arc::new(crate::Config {
+                listenbrainz_base_url: server.base_url(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn creds() -> MediaTrackerCredentials {
+        MediaTrackerCredentials::new(serde_json::json!({
+            "token": "tok",
+            "user_name": "rob",
+        }))
+    }
+
+    fn track(runtime: Option<i64>) -> MediaTrackerTarget {
+        MediaTrackerTarget {
+            kind: db::MediaKind::Track,
+            title: "Teardrop".into(),
+            year: Some(1998),
+            ids: db::ExternalIds::default(),
+            series: None,
+            season: None,
+            episode: None,
+            runtime,
+            artist: Some("Massive Attack".into()),
+            album: Some("Mezzanine".into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn playback_start_submits_now_playing_without_a_timestamp() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/1/submit-listens")
+                .header("authorization", "Token tok")
+                .json_body_partial(
+                    r#"{"listen_type":"playing_now","payload":[{"track_metadata":{"artist_name":"Massive Attack","track_name":"Teardrop","release_name":"Mezzanine"}}]}"#,
+                );
+            then.status(200)
+                .json_body(serde_json::json!({ "status": "ok" }));
+        });
+
+        ListenBrainzAddon
+            .on_event(
+                &MediaTrackerEvent::PlaybackStart { position_ticks: 0 },
+                &track(Some(330)),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn a_stop_past_half_the_track_is_a_listen() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/1/submit-listens")
+                .json_body_partial(r#"{"listen_type":"single"}"#);
+            then.status(200)
+                .json_body(serde_json::json!({ "status": "ok" }));
+        });
+
+        ListenBrainzAddon
+            .on_event(
+                &MediaTrackerEvent::PlaybackStop {
+                    position_ticks: 170 * TICKS_PER_SECOND,
+                    played: false,
+                },
+                &track(Some(330)),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn a_stop_short_of_the_threshold_submits_nothing() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/1/submit-listens");
+            then.status(200);
+        });
+
+        ListenBrainzAddon
+            .on_event(
+                &MediaTrackerEvent::PlaybackStop {
+                    position_ticks: 60 * TICKS_PER_SECOND,
+                    played: false,
+                },
+                &track(Some(330)),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        assert_eq!(mock.hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn a_rejected_token_asks_the_user_to_reconnect() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/1/submit-listens");
+            then.status(401);
+        });
+
+        let err = ListenBrainzAddon
+            .on_event(
+                &MediaTrackerEvent::PlaybackStart { position_ticks: 0 },
+                &track(None),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.requires_reauth());
+    }
+
+    #[tokio::test]
+    async fn connect_stores_the_user_the_token_belongs_to() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/1/validate-token")
+                .header("authorization", "Token tok");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "code": 200,
+                    "message": "Token valid.",
+                    "valid": true,
+                    "user_name": "rob",
+                }));
+        });
+
+        let creds = ListenBrainzAddon
+            .connect_with_token(&serde_json::json!({ "token": " tok " }), &ctx(&server))
+            .await
+            .unwrap();
+        assert_eq!(creds.get_str("token"), Some("tok"));
+        assert_eq!(creds.get_str("user_name"), Some("rob"));
+    }
+
+    #[tokio::test]
+    async fn loved_tracks_come_back_as_favourites_by_name() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/1/feedback/user/rob/get-feedback")
+                .query_param("score", "1")
+                .query_param("metadata", "true");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "feedback": [
+                        {
+                            "created": 1_700_000_000,
+                            "recording_mbid": "m1",
+                            "score": 1,
+                            "track_metadata": {
+                                "artist_name": "Massive Attack",
+                                "track_name": "Teardrop",
+                                "release_name": "Mezzanine",
+                            },
+                        },
+                        {
+                            "created": 1_600_000_000,
+                            "recording_mbid": "m2",
+                            "score": 1,
+                            "track_metadata": {
+                                "artist_name": "Portishead",
+                                "track_name": "Roads",
+                            },
+                        },
+                    ],
+                    "total_count": 2,
+                    "offset": 0,
+                }));
+        });
+
+        let since = DateTime::from_timestamp(1_650_000_000, 0)
+            .unwrap()
+            .naive_utc();
+        let loved = ListenBrainzAddon
+            .pull_changes(Some(since), &creds(), &ctx(&server))
+            .await
+            .unwrap();
+        mock.assert();
+        assert_eq!(loved.len(), 1, "the older love predates the last sweep");
+        assert_eq!(
+            loved[0]
+                .title
+                .as_deref(),
+            Some("Teardrop")
+        );
+        assert_eq!(
+            loved[0]
+                .ids
+                .artist_name
+                .as_deref(),
+            Some("Massive Attack")
+        );
+        assert_eq!(loved[0].favorite, Some(true));
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::Deserialize;
+use sqlx::SqlitePool;
 use std::sync::Arc;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
@@ -78,7 +79,7 @@ struct LrcLibTrack {
     synced_lyrics: Option<String>,
 }
 
-fn parse_lrc(lrc: &str) -> Vec<LyricLine> {
+pub(super) fn parse_lrc(lrc: &str) -> Vec<LyricLine> {
     lrc.lines()
         .filter_map(|line| {
             let rest = line.strip_prefix('[')?;
@@ -105,7 +106,7 @@ fn parse_lrc(lrc: &str) -> Vec<LyricLine> {
         .collect()
 }
 
-fn plain_to_lines(plain: &str) -> Vec<LyricLine> {
+pub(super) fn plain_to_lines(plain: &str) -> Vec<LyricLine> {
     plain
         .lines()
         .map(|l| LyricLine {
@@ -199,7 +200,11 @@ impl LyricAddon for LrcLibAddon {
         "lrclib".to_string()
     }
 
-    async fn lyric_fetch(&self, req: &LyricSearchRequest) -> Result<Option<LyricDto>> {
+    async fn lyric_fetch(
+        &self,
+        req: &LyricSearchRequest,
+        db: &SqlitePool,
+    ) -> Result<Option<LyricDto>> {
         debug!(
             title = %req.title,
             artist = ?req.artist,
@@ -218,7 +223,7 @@ impl LyricAddon for LrcLibAddon {
 
         debug!(title = %req.title, "lrclib: exact match missed, trying search fallback");
         let results = self
-            .lyric_search(req)
+            .lyric_search(req, db)
             .await?;
         let first = results
             .into_iter()
@@ -235,6 +240,7 @@ impl LyricAddon for LrcLibAddon {
     async fn lyric_search(
         &self,
         req: &LyricSearchRequest,
+        _db: &SqlitePool,
     ) -> Result<Vec<RemoteLyricInfoDto>> {
         let mut url = reqwest::Url::parse(&format!("{}/search", BASE))?;
         {
@@ -280,7 +286,11 @@ impl LyricAddon for LrcLibAddon {
             .collect())
     }
 
-    async fn lyric_get_by_id(&self, id: &str) -> Result<Option<LyricDto>> {
+    async fn lyric_get_by_id(
+        &self,
+        id: &str,
+        _db: &SqlitePool,
+    ) -> Result<Option<LyricDto>> {
         let url = format!("{}/get/{}", BASE, id);
         debug!(id, "lrclib: get by id");
         let resp = self
@@ -193,8 +193,18 @@ pub struct MediaTrackerTarget {
     pub series: Option<Box<MediaTrackerTarget>>,
     pub season: Option<i64>,
     pub episode: Option<i64>,
+    /// Seconds, when known. What a provider needs to turn a position into
+    /// the percentage it scrobbles.
+    pub runtime: Option<i64>,
+    /// Set for tracks. Music services match a listen on names, not ids.
+    pub artist: Option<String>,
+    pub album: Option<String>,
 }
 
+const TICKS_PER_SECOND: i64 = 10_000_000;
+const MIN_LISTEN_RUNTIME_SECS: i64 = 30;
+const LISTEN_THRESHOLD_SECS: i64 = 4 * 60;
+
 /// Whether any id here is one a media tracker could key on. `ExternalIds`
 /// also carries Deezer, IPTV and Stremio ids, which identify nothing to them.
 fn has_media_tracker_ids(ids: &db::ExternalIds) -> bool {
@@ -221,6 +231,24 @@ impl MediaTrackerTarget {
                 .series
                 .as_ref()
                 .is_some_and(|s| has_media_tracker_ids(&s.ids))
+            || (self.kind == db::MediaKind::Track
+                && self
+                    .artist
+                    .is_some())
+    }
+
+    /// The scrobbling rule music services share: a track over 30 seconds
+    /// counts once half of it, or four minutes, has been heard. Without a
+    /// runtime only the four minutes, or a finish, can be judged.
+    pub fn counts_as_listen(&self, position_ticks: i64, played: bool) -> bool {
+        let heard = position_ticks / TICKS_PER_SECOND;
+        match self.runtime {
+            Some(runtime) => {
+                runtime > MIN_LISTEN_RUNTIME_SECS
+                    && (played || heard >= (runtime / 2).min(LISTEN_THRESHOLD_SECS))
+            }
+            None => played || heard >= LISTEN_THRESHOLD_SECS,
+        }
     }
 }
 
@@ -289,6 +317,9 @@ pub struct MediaTrackerCapabilities {
     /// renders it with the same generic form code as addon settings.
     pub connect_fields: Vec<remux_sdks::remux::AddonOption>,
     pub supported_events: Vec<MediaTrackerEventKind>,
+    /// Item kinds the provider can identify. A video tracker has nothing to
+    /// match a track against, and a scrobbler nothing for a movie.
+    pub media_kinds: Vec<db::MediaKind>,
     /// Must be a subset of `supported_events`.
     pub default_event_filter: Vec<MediaTrackerEventKind>,
     pub history_import: bool,
@@ -311,6 +342,7 @@ impl Default for MediaTrackerCapabilities {
             auth_flow: AuthFlow::Token,
             connect_fields: Vec::new(),
             supported_events: Vec::new(),
+            media_kinds: Vec::new(),
             default_event_filter: Vec::new(),
             history_import: false,
             progress_import: false,
@@ -327,6 +359,11 @@ impl MediaTrackerCapabilities {
         self.supported_events
             .contains(&kind)
     }
+
+    pub fn supports_kind(&self, kind: &db::MediaKind) -> bool {
+        self.media_kinds
+            .contains(kind)
+    }
 }
 
 /// Per-call context. No DB handle, matching `MetricsCtx`.
@@ -454,6 +491,9 @@ pub trait MediaTrackerAddon: AddonKind + Send + Sync {
 #[derive(Debug, Clone)]
 pub struct RemoteWatch {
     pub ids: db::ExternalIds,
+    /// For providers that name items instead of identifying them, as music
+    /// services do; the artist and album ride on `ids`.
+    pub title: Option<String>,
     pub season: Option<i64>,
     pub episode: Option<i64>,
     pub watched: bool,
@@ -620,6 +660,9 @@ mod tests {
             series: None,
             season: None,
             episode: None,
+            runtime: None,
+            artist: None,
+            album: None,
         }
     }
 
@@ -667,6 +710,45 @@ mod tests {
         assert!(episode.is_matchable());
     }
 
+    /// Music services key on names, so a track is matchable on its artist
+    /// alone, and only a track is.
+    #[test]
+    fn a_track_is_matchable_on_its_artist() {
+        let mut track = target(db::MediaKind::Track, db::ExternalIds::default());
+        assert!(!track.is_matchable());
+        track.artist = Some("Massive Attack".into());
+        assert!(track.is_matchable());
+
+        let mut movie = target(db::MediaKind::Movie, db::ExternalIds::default());
+        movie.artist = Some("Massive Attack".into());
+        assert!(!movie.is_matchable());
+    }
+
+    #[test]
+    fn a_listen_needs_half_the_track_or_four_minutes() {
+        let secs = |s: i64| s * TICKS_PER_SECOND;
+        let mut track = target(db::MediaKind::Track, db::ExternalIds::default());
+
+        track.runtime = Some(200);
+        assert!(!track.counts_as_listen(secs(99), false));
+        assert!(track.counts_as_listen(secs(100), false));
+
+        track.runtime = Some(1200);
+        assert!(!track.counts_as_listen(secs(239), false));
+        assert!(track.counts_as_listen(secs(240), false));
+
+        track.runtime = Some(30);
+        assert!(
+            !track.counts_as_listen(secs(30), true),
+            "too short to scrobble however much of it was heard"
+        );
+
+        track.runtime = None;
+        assert!(!track.counts_as_listen(secs(200), false));
+        assert!(track.counts_as_listen(secs(200), true));
+        assert!(track.counts_as_listen(secs(240), false));
+    }
+
     /// A music id identifies nothing to a media tracker, so it must not
     /// stand in for one.
     #[test]
@@ -717,6 +799,7 @@ mod tests {
                 tmdb: Some(603),
                 ..Default::default()
             },
+            title: None,
             season: None,
             episode: None,
             watched: false,
@@ -738,6 +821,7 @@ mod tests {
                 tmdb: Some(603),
                 ..Default::default()
             },
+            title: None,
             season: None,
             episode: None,
             watched: false,
@@ -2,19 +2,27 @@
 //! media types it serves; user-added instances are rows in the `addons` table.
 
 pub mod addon;
+pub mod anilist;
+pub mod audio_tags;
 pub mod deezer;
 pub mod eclipse;
 pub mod introdb;
 pub mod iptv;
+pub mod kitsu;
+pub mod lastfm;
+pub mod listenbrainz;
 pub mod lrclib;
 pub mod media_tracker;
+pub mod nfo;
 pub mod opendal;
+pub mod opendal_watch;
 pub mod probe;
 pub mod squid;
 pub mod stremio;
 pub mod tmdb;
 pub mod torznab;
 pub mod trakt;
+pub mod webhook;
 pub mod ytdlp;
 
 use anyhow::{Result, anyhow};
@@ -110,6 +118,8 @@ pub trait RemoteMediaStream: Send + Sync {
 
 #[derive(Debug)]
 pub struct LyricSearchRequest {
+    /// The track being looked up, for providers that hold its file.
+    pub media_id: Uuid,
     pub title: String,
     pub artist: Option<String>,
     pub album: Option<String>,
@@ -834,12 +844,26 @@ pub trait SegmentAddon: Send + Sync {
 #[async_trait]
 pub trait LyricAddon: Send + Sync {
     fn provider_id(&self) -> String;
-    async fn lyric_fetch(&self, req: &LyricSearchRequest) -> Result<Option<LyricDto>>;
+    /// Local providers (sidecar files, embedded tags) are asked before remote
+    /// ones, whatever the addon priority.
+    fn is_local(&self) -> bool {
+        false
+    }
+    async fn lyric_fetch(
+        &self,
+        req: &LyricSearchRequest,
+        db: &SqlitePool,
+    ) -> Result<Option<LyricDto>>;
     async fn lyric_search(
         &self,
         req: &LyricSearchRequest,
+        db: &SqlitePool,
     ) -> Result<Vec<RemoteLyricInfoDto>>;
-    async fn lyric_get_by_id(&self, id: &str) -> Result<Option<LyricDto>>;
+    async fn lyric_get_by_id(
+        &self,
+        id: &str,
+        db: &SqlitePool,
+    ) -> Result<Option<LyricDto>>;
 }
 
 /// A single popularity snapshot emitted by a `MetricsAddon`.
@@ -2198,20 +2222,23 @@ impl AddonService {
             .child_uuid_map(&ctx.db, actual_root_id)
             .await;
 
-        // Load ALL grandchild UUIDs in one query keyed by (parent_id, kind, idx).
-        // Avoids one query per season (O(n_seasons) → O(1) queries).
-        let existing_l2: std::collections::HashMap<(Uuid, String, i64), Uuid> =
-            sqlx::query_as::<_, (Uuid, String, Option<i64>, Uuid)>(
-                "SELECT parent_id, CAST(kind AS TEXT), idx, id
+        // Load ALL grandchild UUIDs in one query keyed by (parent_id, kind, parent_idx, idx).
+        // Avoids one query per season (O(n_seasons) → O(1) queries). parent_idx keeps
+        // track 1 of disc 1 and disc 2 of an album apart.
+        let existing_l2: std::collections::HashMap<
+            (Uuid, String, Option<i64>, i64),
+            Uuid,
+        > = sqlx::query_as::<_, (Uuid, String, Option<i64>, Option<i64>, Uuid)>(
+            "SELECT parent_id, CAST(kind AS TEXT), parent_idx, idx, id
                  FROM media WHERE grandparent_id = ? AND idx IS NOT NULL",
-            )
-            .bind(actual_root_id)
-            .fetch_all(&ctx.db)
-            .await
-            .unwrap_or_default()
-            .into_iter()
-            .filter_map(|(pid, k, idx, id)| idx.map(|i| ((pid, k, i), id)))
-            .collect();
+        )
+        .bind(actual_root_id)
+        .fetch_all(&ctx.db)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(pid, k, pidx, idx, id)| idx.map(|i| ((pid, k, pidx, i), id)))
+        .collect();
 
         let mut level1: Vec<db::Media> = Vec::with_capacity(raw_level1.len());
         for mut child in raw_level1 {
@@ -2302,6 +2329,7 @@ impl AddonService {
                         actual_child_id,
                         gc.kind
                             .to_string(),
+                        gc.parent_idx,
                         idx,
                     );
                     if let Some(&existing_id) = existing_l2.get(&key) {
@@ -3039,8 +3067,9 @@ impl AddonService {
     pub async fn lyric_fetch(
         &self,
         req: &LyricSearchRequest,
+        db: &SqlitePool,
     ) -> Result<Option<LyricDto>> {
-        let addons: Vec<(String, Arc<dyn LyricAddon>)> = self
+        let mut addons: Vec<(String, Arc<dyn LyricAddon>)> = self
             .inner
             .load()
             .iter()
@@ -3062,10 +3091,11 @@ impl AddonService {
                     })
             })
             .collect();
+        addons.sort_by_key(|(_, addon)| !addon.is_local());
 
         for (name, addon) in addons {
             match addon
-                .lyric_fetch(req)
+                .lyric_fetch(req, db)
                 .await
             {
                 Ok(Some(l)) => return Ok(Some(l)),
@@ -3081,8 +3111,9 @@ impl AddonService {
     pub async fn lyric_search(
         &self,
         req: &LyricSearchRequest,
+        db: &SqlitePool,
     ) -> Result<Vec<RemoteLyricInfoDto>> {
-        let addons: Vec<(String, Arc<dyn LyricAddon>)> = self
+        let mut addons: Vec<(String, Arc<dyn LyricAddon>)> = self
             .inner
             .load()
             .iter()
@@ -3104,11 +3135,12 @@ impl AddonService {
                     })
             })
             .collect();
+        addons.sort_by_key(|(_, addon)| !addon.is_local());
 
         let mut out = Vec::new();
         for (name, addon) in addons {
             match addon
-                .lyric_search(req)
+                .lyric_search(req, db)
                 .await
             {
                 Ok(items) => out.extend(items),
@@ -3123,6 +3155,7 @@ impl AddonService {
     pub async fn lyric_get_by_composite_id(
         &self,
         composite_id: &str,
+        db: &SqlitePool,
     ) -> Result<Option<LyricDto>> {
         let addons: Vec<Arc<dyn LyricAddon>> = self
             .inner
@@ -3138,7 +3171,7 @@ impl AddonService {
             let prefix = format!("{}_", addon.provider_id());
             if let Some(inner) = composite_id.strip_prefix(&prefix) {
                 return addon
-                    .lyric_get_by_id(inner)
+                    .lyric_get_by_id(inner, db)
                     .await;
             }
         }
@@ -0,0 +1,369 @@
+//! Kodi/Jellyfin `.nfo` sidecars.
+//!
+//! Only the identity and summary fields are read: title, plot, year, rating,
+//! season/episode numbers and provider ids. Everything else (cast, genres,
+//! stream details) is left to the metadata addons.
+
+use anyhow::Result;
+use quick_xml::{events::Event, reader::Reader};
+
+use crate::db;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NfoKind {
+    Movie,
+    TvShow,
+    Episode,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Nfo {
+    pub kind: Option<NfoKind>,
+    pub title: Option<String>,
+    pub plot: Option<String>,
+    pub year: Option<i64>,
+    /// Community rating on a 0–10 scale.
+    pub rating: Option<f64>,
+    pub season: Option<i64>,
+    pub episode: Option<i64>,
+    pub ids: db::ExternalIds,
+}
+
+impl Nfo {
+    /// Parses the first `<movie>`, `<tvshow>` or `<episodedetails>` element.
+    /// Returns `None` for anything else, including URL-only NFOs. Multi-episode
+    /// files list several `<episodedetails>`; only the first is read.
+    pub fn parse(xml: &str) -> Result<Option<Self>> {
+        let mut reader = Reader::from_str(xml);
+        reader
+            .config_mut()
+            .trim_text(true);
+
+        let mut parser = Parser::default();
+        let mut stack: Vec<Vec<u8>> = Vec::new();
+
+        loop {
+            let text = match reader.read_event()? {
+                Event::Start(e) => {
+                    let name = e
+                        .name()
+                        .as_ref()
+                        .to_ascii_lowercase();
+                    if stack.is_empty() {
+                        parser
+                            .nfo
+                            .kind = match name.as_slice() {
+                            b"movie" => Some(NfoKind::Movie),
+                            b"tvshow" => Some(NfoKind::TvShow),
+                            b"episodedetails" => Some(NfoKind::Episode),
+                            _ => return Ok(None),
+                        };
+                    }
+                    match name.as_slice() {
+                        b"uniqueid" => parser.uniqueid_type = attribute(&e, b"type"),
+                        b"rating" => {
+                            let is_default = attribute(&e, b"default")
+                                .is_some_and(|v| v.eq_ignore_ascii_case("true"));
+                            let max = attribute(&e, b"max")
+                                .and_then(|v| {
+                                    v.parse::<f64>()
+                                        .ok()
+                                })
+                                .filter(|m| *m > 0.0)
+                                .unwrap_or(10.0);
+                            parser.rating_attrs = (is_default, max);
+                        }
+                        _ => {}
+                    }
+                    stack.push(name);
+                    continue;
+                }
+                Event::End(_) => {
+                    stack.pop();
+                    if stack.is_empty() {
+                        break;
+                    }
+                    continue;
+                }
+                Event::Text(e) => e
+                    .unescape()
+                    .unwrap_or_default()
+                    .into_owned(),
+                Event::CData(e) => {
+                    String::from_utf8_lossy(&e.into_inner()).into_owned()
+                }
+                Event::Eof => break,
+                _ => continue,
+            };
+            parser.read_field(&stack, text.trim());
+        }
+
+        Ok(parser.finish())
+    }
+
+    fn set_imdb(&mut self, id: &str) {
+        if self
+            .ids
+            .imdb
+            .is_none()
+            && id.starts_with("tt")
+        {
+            self.ids
+                .imdb = db::NonEmptyString::try_new(id.to_string()).ok();
+        }
+    }
+}
+
+/// Parse state for values that are only settled once the element is read.
+#[derive(Default)]
+struct Parser {
+    nfo: Nfo,
+    outline: Option<String>,
+    premiered: Option<i64>,
+    legacy_rating: Option<f64>,
+    /// `(is_default, value)` of each `<ratings><rating>`, scaled to 0–10.
+    ratings: Vec<(bool, f64)>,
+    /// `(is_default, max)` of the `<rating>` being read.
+    rating_attrs: (bool, f64),
+    uniqueid_type: Option<String>,
+}
+
+impl Parser {
+    fn read_field(&mut self, stack: &[Vec<u8>], text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let parse_int = |s: &str| {
+            s.trim()
+                .parse::<i64>()
+                .ok()
+        };
+        let nfo = &mut self.nfo;
+        match stack {
+            [_, field] => match field.as_slice() {
+                b"title" => nfo.title = Some(text.to_string()),
+                b"plot" => nfo.plot = Some(text.to_string()),
+                b"outline" => self.outline = Some(text.to_string()),
+                b"year" => nfo.year = parse_int(text),
+                b"premiered" | b"aired" => {
+                    self.premiered = text
+                        .get(..4)
+                        .and_then(parse_int)
+                }
+                b"rating" => {
+                    self.legacy_rating = text
+                        .parse::<f64>()
+                        .ok()
+                }
+                b"season" => nfo.season = parse_int(text),
+                b"episode" => nfo.episode = parse_int(text),
+                b"imdbid" | b"imdb_id" => nfo.set_imdb(text),
+                b"tmdbid" => {
+                    nfo.ids
+                        .tmdb = parse_int(text)
+                }
+                b"tvdbid" => {
+                    nfo.ids
+                        .tvdb = parse_int(text)
+                }
+                b"uniqueid" => match self
+                    .uniqueid_type
+                    .as_deref()
+                    .map(str::to_ascii_lowercase)
+                    .as_deref()
+                {
+                    Some("imdb") => nfo.set_imdb(text),
+                    Some("tmdb") => {
+                        nfo.ids
+                            .tmdb = parse_int(text)
+                    }
+                    Some("tvdb") => {
+                        nfo.ids
+                            .tvdb = parse_int(text)
+                    }
+                    _ => {}
+                },
+                // Kodi's legacy `<id>`: an IMDB id, or a TVDB id for shows.
+                b"id" => {
+                    if text.starts_with("tt") {
+                        nfo.set_imdb(text);
+                    } else if nfo.kind == Some(NfoKind::TvShow)
+                        && nfo
+                            .ids
+                            .tvdb
+                            .is_none()
+                    {
+                        nfo.ids
+                            .tvdb = parse_int(text);
+                    }
+                }
+                _ => {}
+            },
+            [_, ratings, rating, value]
+                if ratings == b"ratings"
+                    && rating == b"rating"
+                    && value == b"value" =>
+            {
+                if let Ok(v) = text.parse::<f64>() {
+                    let (is_default, max) = self.rating_attrs;
+                    self.ratings
+                        .push((is_default, v * 10.0 / max));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> Option<Nfo> {
+        let mut nfo = self.nfo;
+        nfo.kind?;
+        if nfo
+            .plot
+            .is_none()
+        {
+            nfo.plot = self.outline;
+        }
+        if nfo
+            .year
+            .is_none()
+        {
+            nfo.year = self.premiered;
+        }
+        nfo.rating = self
+            .ratings
+            .iter()
+            .find(|(is_default, _)| *is_default)
+            .or(self
+                .ratings
+                .first())
+            .map(|(_, value)| *value)
+            .or(self.legacy_rating);
+        Some(nfo)
+    }
+}
+
+fn attribute(e: &quick_xml::events::BytesStart<'_>, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| {
+            a.key
+                .as_ref()
+                .eq_ignore_ascii_case(key)
+        })
+        .map(|a| String::from_utf8_lossy(&a.value).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn movie_nfo_with_unique_ids_and_ratings() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<movie>
+  <title>Heat</title>
+  <originaltitle>Heat</originaltitle>
+  <ratings>
+    <rating name="themoviedb" max="10">
+      <value>7.9</value>
+      <votes>6000</votes>
+    </rating>
+    <rating name="imdb" max="10" default="true">
+      <value>8.3</value>
+    </rating>
+  </ratings>
+  <outline>Short.</outline>
+  <plot><![CDATA[A group of professional bank robbers.]]></plot>
+  <premiered>1995-12-15</premiered>
+  <uniqueid type="imdb" default="true">tt0113277</uniqueid>
+  <uniqueid type="tmdb">949</uniqueid>
+  <actor><name>Al Pacino</name><role>Vincent Hanna</role></actor>
+  <set><name>Not a title</name></set>
+</movie>
+https://www.imdb.com/title/tt0113277/"#;
+        let nfo = Nfo::parse(xml)
+            .unwrap()
+            .unwrap();
+        assert_eq!(nfo.kind, Some(NfoKind::Movie));
+        assert_eq!(
+            nfo.title
+                .as_deref(),
+            Some("Heat")
+        );
+        assert_eq!(
+            nfo.plot
+                .as_deref(),
+            Some("A group of professional bank robbers.")
+        );
+        assert_eq!(nfo.year, Some(1995));
+        assert_eq!(nfo.rating, Some(8.3));
+        assert_eq!(
+            nfo.ids
+                .imdb
+                .as_deref()
+                .map(|s| s.as_str()),
+            Some("tt0113277")
+        );
+        assert_eq!(
+            nfo.ids
+                .tmdb,
+            Some(949)
+        );
+    }
+
+    #[test]
+    fn legacy_tvshow_and_episode_fields() {
+        let show = Nfo::parse(
+            "<tvshow><title>Bleach</title><rating>8.1</rating><id>74796</id>\
+             <outline>Ichigo gains the powers of a Soul Reaper.</outline></tvshow>",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(show.kind, Some(NfoKind::TvShow));
+        assert_eq!(
+            show.ids
+                .tvdb,
+            Some(74796)
+        );
+        assert_eq!(show.rating, Some(8.1));
+        assert_eq!(
+            show.plot
+                .as_deref(),
+            Some("Ichigo gains the powers of a Soul Reaper.")
+        );
+
+        let episode = Nfo::parse(
+            "<episodedetails><title>The Day I Became a Shinigami</title>\
+             <season>1</season><episode>1</episode><aired>2004-10-05</aired>\
+             <ratings><rating name=\"tvdb\" max=\"100\"><value>75</value></rating></ratings>\
+             </episodedetails>\
+             <episodedetails><title>Second</title><episode>2</episode></episodedetails>",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(episode.kind, Some(NfoKind::Episode));
+        assert_eq!(
+            episode
+                .title
+                .as_deref(),
+            Some("The Day I Became a Shinigami")
+        );
+        assert_eq!((episode.season, episode.episode), (Some(1), Some(1)));
+        assert_eq!(episode.year, Some(2004));
+        assert_eq!(episode.rating, Some(7.5));
+    }
+
+    #[test]
+    fn url_only_and_unknown_roots_are_ignored() {
+        assert!(
+            Nfo::parse("https://www.imdb.com/title/tt0113277/")
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            Nfo::parse("<musicvideo><title>x</title></musicvideo>")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
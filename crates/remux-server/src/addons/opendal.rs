@@ -14,10 +14,19 @@ use tracing::{debug, info, warn};
 use super::{
     AddonCapabilities, AddonKind, AddonMetadata, AddonOption, AddonOptionType,
     AddonPreset, AddonPresetRegistration, AddonSelectOption, CatalogAddon, CatalogInfo,
-    IndexAddon, MediaKind, ProgressReporter, ResourceType, StreamAddon, SubtitleAddon,
-    SubtitleInfo, TreeAddon,
+    IndexAddon, LyricAddon, LyricSearchRequest, MediaKind, ProgressReporter,
+    ResourceType, StreamAddon, SubtitleAddon, SubtitleInfo, TreeAddon,
+    audio_tags::{self, AudioTags},
+    lrclib, nfo,
 };
-use crate::{AppContext, addons::Addon, common, db, sdks, sdks::CachedEndpoint};
+use crate::{
+    AppContext,
+    addons::Addon,
+    common::{self, TickUnit, ToRunTimeTicks},
+    db, sdks,
+    sdks::CachedEndpoint,
+};
+use remux_sdks::remux::{LyricDto, LyricMetadata, RemoteLyricInfoDto};
 
 // ---------------------------------------------------------------------------
 // Shared option helper
@@ -49,7 +58,7 @@ fn media_kind_option() -> AddonOption {
     }
 }
 
-fn cfg_paths_local(cfg: &serde_json::Value) -> Result<Vec<String>> {
+pub(crate) fn cfg_paths_local(cfg: &serde_json::Value) -> Result<Vec<String>> {
     if let Some(arr) = cfg["paths"].as_array() {
         let v: Vec<String> = arr
             .iter()
@@ -72,7 +81,14 @@ fn cfg_paths_local(cfg: &serde_json::Value) -> Result<Vec<String>> {
     anyhow::bail!("opendal-local: at least one path is required")
 }
 
-fn cfg_paths_webdav(cfg: &serde_json::Value) -> Vec<String> {
+/// Whether the filesystem watcher follows a local addon; on unless turned off.
+pub(crate) fn cfg_watch(cfg: &serde_json::Value) -> bool {
+    cfg["watch"]
+        .as_bool()
+        .unwrap_or(true)
+}
+
+fn cfg_paths_remote(cfg: &serde_json::Value) -> Vec<String> {
     if let Some(arr) = cfg["paths"].as_array() {
         let v: Vec<String> = arr
             .iter()
@@ -101,33 +117,11 @@ impl AddonPreset for OpendalLocalPreset {
     }
 
     fn metadata(&self) -> AddonMetadata {
-        AddonMetadata {
-            id: "opendal-local".to_string(),
-            display_name: "Local".to_string(),
-            description: "Index and stream video or audio files from a local path."
-                .to_string(),
-            icon: None,
-            supported_resources: vec![
-                AddonMetadata::simple_resource(ResourceType::Stream),
-                AddonMetadata::simple_resource(ResourceType::Catalog),
-                AddonMetadata::simple_resource(ResourceType::Subtitles),
-            ],
-            supported_types: vec![
-                MediaKind::Movie,
-                MediaKind::Episode,
-                MediaKind::Track,
-            ],
-            supported_resources_user: vec![
-                ResourceType::Stream,
-                ResourceType::Subtitles,
-            ],
-            supported_types_user: vec![
-                MediaKind::Movie,
-                MediaKind::Episode,
-                MediaKind::Track,
-            ],
-            options: vec![
-                media_kind_option(),
+        storage_metadata(
+            "opendal-local",
+            "Local",
+            "Index and stream video or audio files from a local path.",
+            vec![
                 AddonOption {
                     id: "paths".to_string(),
                     name: "Paths".to_string(),
@@ -136,8 +130,20 @@ impl AddonPreset for OpendalLocalPreset {
                     default: None,
                     kind: AddonOptionType::StringList,
                 },
+                AddonOption {
+                    id: "watch".to_string(),
+                    name: "Watch for changes".to_string(),
+                    description: Some(
+                        "Index added, renamed and deleted files as they happen. \
+                         Network mounts may not report changes."
+                            .to_string(),
+                    ),
+                    required: false,
+                    default: Some(serde_json::Value::Bool(true)),
+                    kind: AddonOptionType::Boolean,
+                },
             ],
-        }
+        )
     }
 
     fn from_cfg(
@@ -146,10 +152,6 @@ impl AddonPreset for OpendalLocalPreset {
         cfg: &serde_json::Value,
         _config: &crate::Config,
     ) -> Result<AddonCapabilities> {
-        let media_kind = cfg["media_kind"]
-            .as_str()
-            .unwrap_or("movie")
-            .to_string();
         let paths = cfg_paths_local(cfg)?;
         let first = paths
             .first()
@@ -158,23 +160,13 @@ impl AddonPreset for OpendalLocalPreset {
         let operator =
             opendal::Operator::new(opendal::services::Fs::default().root(&first))?
                 .finish();
-
-        let addon = Arc::new(OpendalAddon {
+        Ok(storage_caps(OpendalAddon {
             addon_id,
             operator: Arc::new(operator),
             root: first,
             backend: "local".to_string(),
-            media_kind,
-        });
-        Ok(AddonCapabilities {
-            kind: Some(addon.clone()),
-            catalog: Some(addon.clone()),
-            stream: Some(addon.clone()),
-            tree: Some(addon.clone()),
-            index: Some(addon.clone()),
-            subtitle: Some(addon),
-            ..Default::default()
-        })
+            media_kind: cfg_media_kind(cfg),
+        }))
     }
 }
 
@@ -194,33 +186,11 @@ impl AddonPreset for OpendalWebdavPreset {
     }
 
     fn metadata(&self) -> AddonMetadata {
-        AddonMetadata {
-            id: "opendal-webdav".to_string(),
-            display_name: "WebDAV".to_string(),
-            description: "Index and stream video or audio files from a WebDAV server."
-                .to_string(),
-            icon: None,
-            supported_resources: vec![
-                AddonMetadata::simple_resource(ResourceType::Stream),
-                AddonMetadata::simple_resource(ResourceType::Catalog),
-                AddonMetadata::simple_resource(ResourceType::Subtitles),
-            ],
-            supported_types: vec![
-                MediaKind::Movie,
-                MediaKind::Episode,
-                MediaKind::Track,
-            ],
-            supported_resources_user: vec![
-                ResourceType::Stream,
-                ResourceType::Subtitles,
-            ],
-            supported_types_user: vec![
-                MediaKind::Movie,
-                MediaKind::Episode,
-                MediaKind::Track,
-            ],
-            options: vec![
-                media_kind_option(),
+        storage_metadata(
+            "opendal-webdav",
+            "WebDAV",
+            "Index and stream video or audio files from a WebDAV server.",
+            vec![
                 AddonOption {
                     id: "endpoint".to_string(),
                     name: "WebDAV URL".to_string(),
@@ -245,16 +215,90 @@ impl AddonPreset for OpendalWebdavPreset {
                     default: None,
                     kind: AddonOptionType::Password,
                 },
+                remote_paths_option(),
+            ],
+        )
+    }
+
+    fn from_cfg(
+        &self,
+        addon_id: Uuid,
+        cfg: &serde_json::Value,
+        _config: &crate::Config,
+    ) -> Result<AddonCapabilities> {
+        remote_caps(addon_id, self.id(), cfg)
+    }
+}
+
+inventory::submit! {
+    AddonPresetRegistration(|| Box::new(OpendalWebdavPreset))
+}
+
+// ---------------------------------------------------------------------------
+// OpendalS3Preset
+// ---------------------------------------------------------------------------
+
+pub struct OpendalS3Preset;
+
+impl AddonPreset for OpendalS3Preset {
+    fn id(&self) -> &'static str {
+        "opendal-s3"
+    }
+
+    fn metadata(&self) -> AddonMetadata {
+        storage_metadata(
+            "opendal-s3",
+            "S3",
+            "Index and stream video or audio files from an S3-compatible bucket \
+             (AWS, MinIO, Garage, R2…).",
+            vec![
                 AddonOption {
-                    id: "paths".to_string(),
-                    name: "Paths".to_string(),
-                    description: Some("Sub-paths to scan (default: /).".to_string()),
+                    id: "endpoint".to_string(),
+                    name: "Endpoint".to_string(),
+                    description: Some(
+                        "e.g. http://minio:9000. Leave empty for AWS.".to_string(),
+                    ),
                     required: false,
                     default: None,
-                    kind: AddonOptionType::StringList,
+                    kind: AddonOptionType::Url,
+                },
+                AddonOption {
+                    id: "bucket".to_string(),
+                    name: "Bucket".to_string(),
+                    description: None,
+                    required: true,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "region".to_string(),
+                    name: "Region".to_string(),
+                    description: Some(format!("Default: {S3_DEFAULT_REGION}.")),
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "access_key_id".to_string(),
+                    name: "Access Key ID".to_string(),
+                    description: Some(
+                        "Leave both keys empty for a public bucket.".to_string(),
+                    ),
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "secret_access_key".to_string(),
+                    name: "Secret Access Key".to_string(),
+                    description: None,
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::Password,
                 },
+                remote_paths_option(),
             ],
-        }
+        )
     }
 
     fn from_cfg(
@@ -263,51 +307,362 @@ impl AddonPreset for OpendalWebdavPreset {
         cfg: &serde_json::Value,
         _config: &crate::Config,
     ) -> Result<AddonCapabilities> {
-        let media_kind = cfg["media_kind"]
-            .as_str()
-            .unwrap_or("movie")
-            .to_string();
-        let endpoint = cfg["endpoint"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .ok_or_else(|| anyhow::anyhow!("opendal-webdav: endpoint is required"))?;
+        remote_caps(addon_id, self.id(), cfg)
+    }
+}
 
-        let mut builder = opendal::services::Webdav::default().endpoint(endpoint);
-        if let Some(u) = cfg["username"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-        {
-            builder = builder.username(u);
-        }
-        if let Some(p) = cfg["password"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-        {
-            builder = builder.password(p);
-        }
-        let operator = opendal::Operator::new(builder)?.finish();
+inventory::submit! {
+    AddonPresetRegistration(|| Box::new(OpendalS3Preset))
+}
 
-        let addon = Arc::new(OpendalAddon {
-            addon_id,
-            operator: Arc::new(operator),
-            root: endpoint.to_string(),
-            backend: "webdav".to_string(),
-            media_kind,
-        });
-        Ok(AddonCapabilities {
-            kind: Some(addon.clone()),
-            catalog: Some(addon.clone()),
-            stream: Some(addon.clone()),
-            tree: Some(addon.clone()),
-            index: Some(addon.clone()),
-            subtitle: Some(addon),
-            ..Default::default()
-        })
+// ---------------------------------------------------------------------------
+// OpendalSftpPreset
+// ---------------------------------------------------------------------------
+
+pub struct OpendalSftpPreset;
+
+impl AddonPreset for OpendalSftpPreset {
+    fn id(&self) -> &'static str {
+        "opendal-sftp"
+    }
+
+    fn metadata(&self) -> AddonMetadata {
+        storage_metadata(
+            "opendal-sftp",
+            "SFTP",
+            "Index and stream video or audio files from an SFTP server.",
+            vec![
+                AddonOption {
+                    id: "endpoint".to_string(),
+                    name: "Host".to_string(),
+                    description: Some("host or host:port".to_string()),
+                    required: true,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "username".to_string(),
+                    name: "Username".to_string(),
+                    description: None,
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "key".to_string(),
+                    name: "Private Key Path".to_string(),
+                    description: Some(
+                        "Path to an SSH private key on this server. Password login \
+                         is not supported; without a key the SSH agent is used."
+                            .to_string(),
+                    ),
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "known_hosts".to_string(),
+                    name: "Host Key Check".to_string(),
+                    description: None,
+                    required: false,
+                    default: Some(serde_json::Value::String("strict".to_string())),
+                    kind: AddonOptionType::Select {
+                        options: vec![
+                            AddonSelectOption {
+                                label: "Strict (host must be in known_hosts)"
+                                    .to_string(),
+                                value: "strict".to_string(),
+                            },
+                            AddonSelectOption {
+                                label: "Add new hosts to known_hosts".to_string(),
+                                value: "add".to_string(),
+                            },
+                            AddonSelectOption {
+                                label: "Accept any host key".to_string(),
+                                value: "accept".to_string(),
+                            },
+                        ],
+                    },
+                },
+                remote_paths_option(),
+            ],
+        )
+    }
+
+    fn from_cfg(
+        &self,
+        addon_id: Uuid,
+        cfg: &serde_json::Value,
+        _config: &crate::Config,
+    ) -> Result<AddonCapabilities> {
+        remote_caps(addon_id, self.id(), cfg)
     }
 }
 
 inventory::submit! {
-    AddonPresetRegistration(|| Box::new(OpendalWebdavPreset))
+    AddonPresetRegistration(|| Box::new(OpendalSftpPreset))
+}
+
+// ---------------------------------------------------------------------------
+// OpendalSmbPreset
+// ---------------------------------------------------------------------------
+
+pub struct OpendalSmbPreset;
+
+impl AddonPreset for OpendalSmbPreset {
+    fn id(&self) -> &'static str {
+        "opendal-smb"
+    }
+
+    fn metadata(&self) -> AddonMetadata {
+        storage_metadata(
+            "opendal-smb",
+            "SMB",
+            "Index and stream video or audio files from an SMB share (Windows, \
+             Samba, most NAS devices).",
+            vec![
+                AddonOption {
+                    id: "endpoint".to_string(),
+                    name: "Host".to_string(),
+                    description: Some("host or host:port".to_string()),
+                    required: true,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "share".to_string(),
+                    name: "Share".to_string(),
+                    description: None,
+                    required: true,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "username".to_string(),
+                    name: "Username".to_string(),
+                    description: Some("Leave empty for guest access.".to_string()),
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "password".to_string(),
+                    name: "Password".to_string(),
+                    description: None,
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::Password,
+                },
+                AddonOption {
+                    id: "domain".to_string(),
+                    name: "Domain".to_string(),
+                    description: Some("Usually empty, or WORKGROUP.".to_string()),
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                remote_paths_option(),
+            ],
+        )
+    }
+
+    fn from_cfg(
+        &self,
+        addon_id: Uuid,
+        cfg: &serde_json::Value,
+        _config: &crate::Config,
+    ) -> Result<AddonCapabilities> {
+        remote_caps(addon_id, self.id(), cfg)
+    }
+}
+
+inventory::submit! {
+    AddonPresetRegistration(|| Box::new(OpendalSmbPreset))
+}
+
+// ---------------------------------------------------------------------------
+// Shared preset helpers
+// ---------------------------------------------------------------------------
+
+const S3_DEFAULT_REGION: &str = "us-east-1";
+
+/// Every storage preset serves the same resources; only the connection
+/// options differ.
+fn storage_metadata(
+    id: &str,
+    display_name: &str,
+    description: &str,
+    options: Vec<AddonOption>,
+) -> AddonMetadata {
+    AddonMetadata {
+        id: id.to_string(),
+        display_name: display_name.to_string(),
+        description: description.to_string(),
+        icon: None,
+        supported_resources: vec![
+            AddonMetadata::simple_resource(ResourceType::Stream),
+            AddonMetadata::simple_resource(ResourceType::Catalog),
+            AddonMetadata::simple_resource(ResourceType::Subtitles),
+            AddonMetadata::simple_resource(ResourceType::Lyrics),
+        ],
+        supported_types: vec![MediaKind::Movie, MediaKind::Episode, MediaKind::Track],
+        supported_resources_user: vec![
+            ResourceType::Stream,
+            ResourceType::Subtitles,
+            ResourceType::Lyrics,
+        ],
+        supported_types_user: vec![
+            MediaKind::Movie,
+            MediaKind::Episode,
+            MediaKind::Track,
+        ],
+        options: std::iter::once(media_kind_option())
+            .chain(options)
+            .collect(),
+    }
+}
+
+fn remote_paths_option() -> AddonOption {
+    AddonOption {
+        id: "paths".to_string(),
+        name: "Paths".to_string(),
+        description: Some("Sub-paths to scan (default: /).".to_string()),
+        required: false,
+        default: None,
+        kind: AddonOptionType::StringList,
+    }
+}
+
+fn cfg_media_kind(cfg: &serde_json::Value) -> String {
+    cfg["media_kind"]
+        .as_str()
+        .unwrap_or("movie")
+        .to_string()
+}
+
+fn storage_caps(addon: OpendalAddon) -> AddonCapabilities {
+    let addon = Arc::new(addon);
+    AddonCapabilities {
+        kind: Some(addon.clone()),
+        catalog: Some(addon.clone()),
+        stream: Some(addon.clone()),
+        tree: Some(addon.clone()),
+        index: Some(addon.clone()),
+        lyric: Some(addon.clone()),
+        subtitle: Some(addon),
+        ..Default::default()
+    }
+}
+
+fn remote_caps(
+    addon_id: Uuid,
+    preset_kind: &str,
+    cfg: &serde_json::Value,
+) -> Result<AddonCapabilities> {
+    let (operator, root) = build_remote_operator(preset_kind, cfg)?;
+    Ok(storage_caps(OpendalAddon {
+        addon_id,
+        operator: Arc::new(operator),
+        root,
+        backend: preset_kind
+            .trim_start_matches("opendal-")
+            .to_string(),
+        media_kind: cfg_media_kind(cfg),
+    }))
+}
+
+fn cfg_str<'a>(cfg: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+    cfg[key]
+        .as_str()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
+
+/// Operator of a remote preset, and the root it is shown under.
+fn build_remote_operator(
+    preset_kind: &str,
+    cfg: &serde_json::Value,
+) -> Result<(opendal::Operator, String)> {
+    let required = |key: &str| {
+        cfg_str(cfg, key)
+            .ok_or_else(|| anyhow::anyhow!("{preset_kind}: {key} is required"))
+    };
+    match preset_kind {
+        "opendal-webdav" => {
+            let endpoint = required("endpoint")?;
+            let mut builder = opendal::services::Webdav::default().endpoint(endpoint);
+            if let Some(u) = cfg_str(cfg, "username") {
+                builder = builder.username(u);
+            }
+            if let Some(p) = cfg_str(cfg, "password") {
+                builder = builder.password(p);
+            }
+            Ok((
+                opendal::Operator::new(builder)?.finish(),
+                endpoint.to_string(),
+            ))
+        }
+        "opendal-s3" => {
+            let bucket = required("bucket")?;
+            // Credentials come from the addon only, never from the host's
+            // AWS profile or instance metadata.
+            let mut builder = opendal::services::S3::default()
+                .bucket(bucket)
+                .region(cfg_str(cfg, "region").unwrap_or(S3_DEFAULT_REGION))
+                .disable_config_load()
+                .disable_ec2_metadata();
+            if let Some(endpoint) = cfg_str(cfg, "endpoint") {
+                builder = builder.endpoint(endpoint);
+            }
+            match (
+                cfg_str(cfg, "access_key_id"),
+                cfg_str(cfg, "secret_access_key"),
+            ) {
+                (Some(key), Some(secret)) => {
+                    builder = builder
+                        .access_key_id(key)
+                        .secret_access_key(secret);
+                }
+                (None, None) => builder = builder.allow_anonymous(),
+                _ => bail!(
+                    "{preset_kind}: access_key_id and secret_access_key must be set together"
+                ),
+            }
+            Ok((
+                opendal::Operator::new(builder)?.finish(),
+                format!("s3://{bucket}"),
+            ))
+        }
+        "opendal-sftp" => {
+            let endpoint = required("endpoint")?;
+            let mut builder = opendal::services::Sftp::default()
+                .endpoint(endpoint)
+                .known_hosts_strategy(cfg_str(cfg, "known_hosts").unwrap_or("strict"));
+            if let Some(user) = cfg_str(cfg, "username") {
+                builder = builder.user(user);
+            }
+            if let Some(key) = cfg_str(cfg, "key") {
+                builder = builder.key(key);
+            }
+            Ok((
+                opendal::Operator::new(builder)?.finish(),
+                format!("sftp://{endpoint}"),
+            ))
+        }
+        "opendal-smb" => {
+            let endpoint = required("endpoint")?;
+            let share = required("share")?;
+            let operator = crate::smb::operator(crate::smb::SmbConfig {
+                endpoint: endpoint.to_string(),
+                share: share.to_string(),
+                username: cfg_str(cfg, "username").map(str::to_string),
+                password: cfg_str(cfg, "password").map(str::to_string),
+                domain: cfg_str(cfg, "domain").map(str::to_string),
+            });
+            Ok((operator, format!("smb://{endpoint}/{share}")))
+        }
+        other => bail!("opendal: unknown storage preset {other}"),
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -324,6 +679,8 @@ pub struct OpendalAddon {
 
 #[derive(sqlx::FromRow)]
 pub struct OpendalFile {
+    #[sqlx(default)]
+    pub id: Option<Uuid>,
     pub path: String,
     pub name: String,
     pub title: Option<String>,
@@ -333,6 +690,11 @@ pub struct OpendalFile {
     pub track_number: Option<i64>,
     pub year: Option<i64>,
     pub size: Option<i64>,
+    #[sqlx(default)]
+    #[sqlx(json(nullable))]
+    pub audio_tags: Option<AudioTags>,
+    #[sqlx(default)]
+    pub lyrics_path: Option<String>,
 }
 
 #[async_trait]
@@ -358,13 +720,14 @@ impl AddonKind for OpendalAddon {
             types: vec![],
             id_prefixes: None,
         };
-        Ok(Some((
-            vec![
-                make_ref(ResourceType::Stream),
-                make_ref(ResourceType::Catalog),
-            ],
-            vec![media_type],
-        )))
+        let mut resources = vec![
+            make_ref(ResourceType::Stream),
+            make_ref(ResourceType::Catalog),
+        ];
+        if self.media_kind == "track" {
+            resources.push(make_ref(ResourceType::Lyrics));
+        }
+        Ok(Some((resources, vec![media_type])))
     }
 }
 
@@ -403,7 +766,7 @@ impl CatalogAddon for OpendalAddon {
             return Ok(None);
         }
 
-        let items: Vec<db::Media> = match self.media_kind.as_str() {
+        let mut items: Vec<db::Media> = match self.media_kind.as_str() {
             "episode" => {
                 sqlx::query_as::<_, (String, Option<String>)>(
                     "SELECT DISTINCT imdb_id, title FROM opendal_files \
@@ -426,16 +789,51 @@ impl CatalogAddon for OpendalAddon {
                 .collect()
             }
             "track" => {
-                sqlx::query_as::<_, (Option<String>,)>(
-                    "SELECT title FROM opendal_files \
-                     WHERE addon_id = ? AND media_kind = 'track'",
+                // Tagged tracks are reached through their artist; the rest
+                // stay flat.
+                let mut metadata =
+                    LocalMetadata::load(ctx, self.addon_id, "artist:").await?;
+                let mut items: Vec<db::Media> = sqlx::query_as::<_, (String, Option<String>)>(
+                    "SELECT MIN(album_artist), \
+                            MAX(CASE WHEN json_extract(audio_tags, '$.album_artist') IS NULL \
+                                THEN json_extract(audio_tags, '$.musicbrainz_artist_id') \
+                                ELSE json_extract(audio_tags, '$.musicbrainz_album_artist_id') END) \
+                     FROM opendal_files \
+                     WHERE addon_id = ? AND media_kind = 'track' AND album_artist IS NOT NULL \
+                     GROUP BY LOWER(album_artist)",
                 )
                 .bind(self.addon_id)
                 .fetch_all(&ctx.db)
                 .await?
                 .into_iter()
-                .filter_map(|(title,)| title)
-                .map(|title| db::Media {
+                .map(|(name, musicbrainz_artist)| {
+                    let key = artist_key(&name);
+                    let mut artist = db::Media {
+                        id: music_id(self.addon_id, &key),
+                        title: name,
+                        kind: db::MediaKind::Artist,
+                        external_ids: db::ExternalIds {
+                            musicbrainz_artist,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    };
+                    if let Some(meta) = metadata.remove(&key) {
+                        meta.apply(&mut artist);
+                    }
+                    artist
+                })
+                .collect();
+                let tracks = sqlx::query_as::<_, (Option<String>,)>(
+                    "SELECT title FROM opendal_files \
+                     WHERE addon_id = ? AND media_kind = 'track' AND album IS NULL",
+                )
+                .bind(self.addon_id)
+                .fetch_all(&ctx.db)
+                .await?
+                .into_iter()
+                .filter_map(|(title,)| title)
+                .map(|title| db::Media {
                     id: common::get_stable_uuid(format!(
                         "{}:track:{}",
                         self.addon_id, title
@@ -443,8 +841,9 @@ impl CatalogAddon for OpendalAddon {
                     title: title.clone(),
                     kind: db::MediaKind::Track,
                     ..Default::default()
-                })
-                .collect()
+                });
+                items.extend(tracks);
+                items
             }
             _ => {
                 sqlx::query_as::<_, (String, Option<String>)>(
@@ -469,6 +868,28 @@ impl CatalogAddon for OpendalAddon {
             }
         };
 
+        if self.media_kind != "track" {
+            let prefix = if self.media_kind == "episode" {
+                "series:"
+            } else {
+                "movie:"
+            };
+            let mut metadata = LocalMetadata::load(ctx, self.addon_id, prefix).await?;
+            for item in &mut items {
+                let key = format!(
+                    "{prefix}{}",
+                    item.external_ids
+                        .imdb
+                        .as_deref()
+                        .map(String::as_str)
+                        .unwrap_or_default()
+                );
+                if let Some(meta) = metadata.remove(&key) {
+                    meta.apply(item);
+                }
+            }
+        }
+
         Ok(Some(Box::pin(futures::stream::iter(items))))
     }
 }
@@ -487,7 +908,7 @@ impl IndexAddon for OpendalAddon {
                 .tmdb_base_url,
         )
         .await;
-        scan_addon(ctx, &tmdb, addon).await?;
+        scan_addon(ctx, &tmdb, addon, None).await?;
         progress.set(100.0);
         Ok(())
     }
@@ -497,6 +918,16 @@ impl IndexAddon for OpendalAddon {
             .bind(addon.id)
             .execute(&ctx.db)
             .await?;
+        sqlx::query("DELETE FROM opendal_metadata WHERE addon_id = ?")
+            .bind(addon.id)
+            .execute(&ctx.db)
+            .await?;
+        let _ = tokio::fs::remove_dir_all(covers_dir(
+            &ctx.config
+                .data_dir,
+            addon.id,
+        ))
+        .await;
         Ok(())
     }
 }
@@ -643,15 +1074,28 @@ impl StreamAddon for OpendalAddon {
         _id_prefixes: Option<&[String]>,
     ) -> Result<Vec<crate::stream::StreamInfo>> {
         let files: Vec<OpendalFile> = if self.media_kind == "track" {
-            sqlx::query_as(
-                "SELECT path, name, title, imdb_id, season, episode, track_number, year, size \
-                 FROM opendal_files \
-                 WHERE addon_id = ? AND media_kind = 'track' AND LOWER(title) = LOWER(?)",
-            )
-            .bind(self.addon_id)
-            .bind(&media.title)
-            .fetch_all(&ctx.db)
-            .await?
+            let artist = media.artist_name_from(
+                media
+                    .grandparent
+                    .as_deref()
+                    .map(|artist| {
+                        artist
+                            .title
+                            .as_str()
+                    }),
+            );
+            let album = media.album_name_from(
+                media
+                    .parent
+                    .as_deref()
+                    .map(|album| {
+                        album
+                            .title
+                            .as_str()
+                    }),
+            );
+            self.track_files(media.id, &media.title, artist, album, &ctx.db)
+                .await?
         } else {
             let imdb_id = if self.media_kind == "episode" {
                 media
@@ -818,8 +1262,18 @@ impl StreamAddon for OpendalAddon {
 #[async_trait]
 impl TreeAddon for OpendalAddon {
     fn supports(&self, root: &db::Media) -> bool {
-        self.media_kind == "episode"
-            && matches!(root.kind, db::MediaKind::Series | db::MediaKind::Season)
+        match self
+            .media_kind
+            .as_str()
+        {
+            "episode" => {
+                matches!(root.kind, db::MediaKind::Series | db::MediaKind::Season)
+            }
+            "track" => {
+                matches!(root.kind, db::MediaKind::Artist | db::MediaKind::Album)
+            }
+            _ => false,
+        }
     }
 
     async fn get_children(
@@ -827,6 +1281,19 @@ impl TreeAddon for OpendalAddon {
         root: &db::Media,
         ctx: &AppContext,
     ) -> Result<Option<Vec<db::Media>>> {
+        if self.media_kind == "track" {
+            return match root.kind {
+                db::MediaKind::Artist => {
+                    self.artist_albums(root, ctx)
+                        .await
+                }
+                db::MediaKind::Album => {
+                    self.album_tracks(root, ctx)
+                        .await
+                }
+                _ => Ok(None),
+            };
+        }
         if self.media_kind != "episode" {
             return Ok(None);
         }
@@ -855,22 +1322,32 @@ impl TreeAddon for OpendalAddon {
                     return Ok(None);
                 }
 
+                let mut metadata = LocalMetadata::load(
+                    ctx,
+                    self.addon_id,
+                    &format!("season:{imdb_id}:"),
+                )
+                .await?;
                 let gp_box = Some(Box::new(root.clone()));
                 let seasons = season_nums
                     .into_iter()
-                    .map(|s| db::Media {
-                        id: common::get_stable_uuid(format!(
-                            "season:{}:{}",
-                            imdb_id, s
-                        )),
-                        title: format!("Season {}", s),
-                        kind: db::MediaKind::Season,
-                        parent_id: Some(root.id),
-                        grandparent_id: Some(root.id),
-                        idx: Some(s),
-                        parent_idx: Some(s),
-                        grandparent: gp_box.clone(),
-                        ..Default::default()
+                    .map(|s| {
+                        let key = format!("season:{}:{}", imdb_id, s);
+                        let mut season = db::Media {
+                            id: common::get_stable_uuid(key.clone()),
+                            title: format!("Season {}", s),
+                            kind: db::MediaKind::Season,
+                            parent_id: Some(root.id),
+                            grandparent_id: Some(root.id),
+                            idx: Some(s),
+                            parent_idx: Some(s),
+                            grandparent: gp_box.clone(),
+                            ..Default::default()
+                        };
+                        if let Some(meta) = metadata.remove(&key) {
+                            meta.apply(&mut season);
+                        }
+                        season
                     })
                     .collect();
 
@@ -930,12 +1407,19 @@ impl TreeAddon for OpendalAddon {
                     return Ok(None);
                 }
 
+                let mut metadata = LocalMetadata::load(
+                    ctx,
+                    self.addon_id,
+                    &format!("episode:{series_imdb}:{season_num}:"),
+                )
+                .await?;
                 let episodes: Vec<db::Media> = files
                     .into_iter()
                     .filter_map(|f| {
                         let ep_num = f.episode?;
                         // Leave title empty so the TMDB meta addon can fill in the proper
-                        // episode name via refresh_meta (which apply_title_format then wraps).
+                        // episode name via refresh_meta (which apply_title_format then wraps),
+                        // unless an episode NFO names it.
                         let title = String::new();
                         let descriptor = if self.backend == "local" {
                             crate::stream::StreamDescriptor::Local(
@@ -949,11 +1433,12 @@ impl TreeAddon for OpendalAddon {
                                     .clone(),
                             }
                         };
-                        Some(db::Media {
-                            id: common::get_stable_uuid(format!(
-                                "episode:{}:{}:{}",
-                                series_imdb, season_num, ep_num
-                            )),
+                        let key = format!(
+                            "episode:{}:{}:{}",
+                            series_imdb, season_num, ep_num
+                        );
+                        let mut episode = db::Media {
+                            id: common::get_stable_uuid(key.clone()),
                             title,
                             kind: db::MediaKind::Episode,
                             parent_id: Some(root.id),
@@ -970,7 +1455,11 @@ impl TreeAddon for OpendalAddon {
                                 ..Default::default()
                             }),
                             ..Default::default()
-                        })
+                        };
+                        if let Some(meta) = metadata.remove(&key) {
+                            meta.apply(&mut episode);
+                        }
+                        Some(episode)
                     })
                     .collect();
 
@@ -983,93 +1472,1233 @@ impl TreeAddon for OpendalAddon {
 }
 
 // ---------------------------------------------------------------------------
-// Opendal file index scanning (backing refresh_index)
+// Local music trees (artist → album → track, from embedded tags)
 // ---------------------------------------------------------------------------
 
-const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "vtt", "sub", "sup"];
+/// Bytes read from the head of a remote file to find its tags.
+const TAG_PROBE_BYTES: u64 = 4 << 20;
 
-/// Extract the file stem (filename without the last extension).
-fn stem_without_ext(name: &str) -> String {
-    match name.rsplit_once('.') {
-        Some((stem, _)) => stem.to_string(),
-        None => name.to_string(),
-    }
+/// `opendal_metadata` key, and seed of the stable id, of a tagged artist.
+/// ASCII-only lowercasing matches SQLite's `LOWER()`, which the tree
+/// queries group by.
+fn artist_key(artist: &str) -> String {
+    format!("artist:{}", artist.to_ascii_lowercase())
 }
 
-/// Split a subtitle stem (filename without its subtitle extension) into its base and subtitle metadata.
-///
-/// For `Breaking.Bad.S01E01.en.forced` returns `("Breaking.Bad.S01E01", Some("en"), true, false)`.
-/// Scans right-to-left: known flags first, then a 2–3-letter lang code, remainder is the base.
-fn split_subtitle_stem(stem: &str) -> (String, Option<String>, bool, bool) {
-    let parts: Vec<&str> = stem
-        .split('.')
-        .collect();
-    if parts.is_empty() {
-        return (stem.to_string(), None, false, false);
-    }
-    let mut suffix_start = parts.len();
-    let mut is_forced = false;
-    let mut is_hi = false;
+fn album_key(artist: &str, album: &str) -> String {
+    format!(
+        "album:{}:{}",
+        artist.to_ascii_lowercase(),
+        album.to_ascii_lowercase()
+    )
+}
 
-    while suffix_start > 0 {
-        match parts[suffix_start - 1]
-            .to_ascii_lowercase()
-            .as_str()
-        {
-            "forced" => {
-                is_forced = true;
-                suffix_start -= 1;
+fn music_id(addon_id: Uuid, key: &str) -> Uuid {
+    common::get_stable_uuid(format!("{addon_id}:{key}"))
+}
+
+fn year_date(year: i64) -> Option<chrono::NaiveDateTime> {
+    chrono::NaiveDate::from_ymd_opt(year as i32, 1, 1)?.and_hms_opt(0, 0, 0)
+}
+
+fn genre_relations(
+    media_id: Uuid,
+    genres: &[String],
+) -> Vec<(db::MediaRelation, db::Media)> {
+    genres
+        .iter()
+        .map(|name| {
+            let genre_id =
+                common::stable_media_uuid(&db::MediaKind::Genre, &name.to_lowercase());
+            (
+                db::MediaRelation {
+                    left_media_id: media_id,
+                    right_media_id: genre_id,
+                    ..Default::default()
+                },
+                db::Media {
+                    id: genre_id,
+                    title: name.clone(),
+                    kind: db::MediaKind::MusicGenre,
+                    ..Default::default()
+                },
+            )
+        })
+        .collect()
+}
+
+impl OpendalAddon {
+    async fn artist_albums(
+        &self,
+        root: &db::Media,
+        ctx: &AppContext,
+    ) -> Result<Option<Vec<db::Media>>> {
+        // Another addon's artist can have the same name.
+        if music_id(self.addon_id, &artist_key(&root.title)) != root.id {
+            return Ok(None);
+        }
+        let rows: Vec<(String, Option<i64>, Option<sqlx::types::Json<AudioTags>>)> =
+            sqlx::query_as(
+                "SELECT album, year, audio_tags FROM opendal_files \
+                 WHERE addon_id = ? AND media_kind = 'track' AND album IS NOT NULL \
+                   AND LOWER(album_artist) = LOWER(?)",
+            )
+            .bind(self.addon_id)
+            .bind(&root.title)
+            .fetch_all(&ctx.db)
+            .await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        // album key → (album, genres of its tracks)
+        let mut albums: std::collections::HashMap<String, (db::Media, Vec<String>)> =
+            Default::default();
+        for (title, year, tags) in rows {
+            let tags = tags
+                .map(|t| t.0)
+                .unwrap_or_default();
+            let key = album_key(&root.title, &title);
+            let (album, genres) = albums
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    let album = db::Media {
+                        id: music_id(self.addon_id, &key),
+                        title,
+                        kind: db::MediaKind::Album,
+                        parent_id: Some(root.id),
+                        grandparent_id: Some(root.id),
+                        external_ids: db::ExternalIds {
+                            artist_name: Some(
+                                root.title
+                                    .clone(),
+                            ),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    };
+                    (album, Vec::new())
+                });
+            if let Some(released_at) = year.and_then(year_date)
+                && album
+                    .released_at
+                    .is_none_or(|r| released_at < r)
+            {
+                album.released_at = Some(released_at);
             }
-            "hi" | "sdh" | "cc" => {
-                is_hi = true;
-                suffix_start -= 1;
+            if let Some(duration) = tags.duration {
+                album.runtime = Some(
+                    album
+                        .runtime
+                        .unwrap_or(0)
+                        + duration,
+                );
             }
-            "default" => {
-                suffix_start -= 1;
+            let ids = &mut album.external_ids;
+            ids.musicbrainz_album = ids
+                .musicbrainz_album
+                .take()
+                .or(tags.musicbrainz_album_id);
+            ids.musicbrainz_release_group = ids
+                .musicbrainz_release_group
+                .take()
+                .or(tags.musicbrainz_release_group_id);
+            ids.musicbrainz_album_artist = ids
+                .musicbrainz_album_artist
+                .take()
+                .or(tags.musicbrainz_album_artist_id);
+            for genre in tags.genres {
+                if !genres
+                    .iter()
+                    .any(|g| g.eq_ignore_ascii_case(&genre))
+                {
+                    genres.push(genre);
+                }
             }
-            _ => break,
         }
+
+        let mut metadata = LocalMetadata::load(
+            ctx,
+            self.addon_id,
+            &format!(
+                "album:{}:",
+                root.title
+                    .to_ascii_lowercase()
+            ),
+        )
+        .await?;
+        let mut albums: Vec<(String, db::Media)> = albums
+            .into_iter()
+            .map(|(key, (mut album, genres))| {
+                if !genres.is_empty() {
+                    album.relations = Some(genre_relations(album.id, &genres));
+                }
+                album.grandparent = Some(db::Media::stub(
+                    root.id,
+                    root.title
+                        .clone(),
+                ));
+                (key, album)
+            })
+            .collect();
+        albums.sort_by(|(a_key, a), (b_key, b)| {
+            (a.released_at, a_key).cmp(&(b.released_at, b_key))
+        });
+        Ok(Some(
+            albums
+                .into_iter()
+                .map(|(key, mut album)| {
+                    if let Some(meta) = metadata.remove(&key) {
+                        meta.apply(&mut album);
+                    }
+                    album
+                })
+                .collect(),
+        ))
     }
 
-    let lang = if suffix_start > 0 {
-        let part = parts[suffix_start - 1];
-        if part.len() >= 2
-            && part.len() <= 3
-            && part
-                .chars()
-                .all(|c| c.is_ascii_alphabetic())
-        {
-            suffix_start -= 1;
-            Some(part.to_string())
-        } else {
-            None
+    async fn album_tracks(
+        &self,
+        root: &db::Media,
+        ctx: &AppContext,
+    ) -> Result<Option<Vec<db::Media>>> {
+        let Some(artist) = root
+            .external_ids
+            .artist_name
+            .as_deref()
+        else {
+            return Ok(None);
+        };
+        if music_id(self.addon_id, &album_key(artist, &root.title)) != root.id {
+            return Ok(None);
+        }
+        let files: Vec<OpendalFile> = sqlx::query_as(
+            "SELECT id, path, name, title, imdb_id, season, episode, track_number, year, size, \
+                    audio_tags \
+             FROM opendal_files \
+             WHERE addon_id = ? AND media_kind = 'track' \
+               AND LOWER(album_artist) = LOWER(?) AND LOWER(album) = LOWER(?)",
+        )
+        .bind(self.addon_id)
+        .bind(artist)
+        .bind(&root.title)
+        .fetch_all(&ctx.db)
+        .await?;
+        if files.is_empty() {
+            return Ok(None);
         }
-    } else {
-        None
-    };
 
-    (parts[..suffix_start].join("."), lang, is_forced, is_hi)
+        let artist_id = root
+            .grandparent_id
+            .or(root.parent_id);
+        let mut tracks: Vec<db::Media> = files
+            .into_iter()
+            .map(|f| {
+                let tags = f
+                    .audio_tags
+                    .unwrap_or_default();
+                let descriptor = if self.backend == "local" {
+                    crate::stream::StreamDescriptor::Local(std::path::PathBuf::from(
+                        &f.path,
+                    ))
+                } else {
+                    crate::stream::StreamDescriptor::Opendal {
+                        addon_id: self.addon_id,
+                        path: f
+                            .path
+                            .clone(),
+                    }
+                };
+                let mut track = db::Media {
+                    id: f
+                        .id
+                        .unwrap_or_else(|| {
+                            common::get_stable_uuid(format!(
+                                "{}:{}",
+                                self.addon_id, f.path
+                            ))
+                        }),
+                    title: f
+                        .title
+                        .unwrap_or_default(),
+                    kind: db::MediaKind::Track,
+                    runtime: tags.duration,
+                    released_at: f
+                        .year
+                        .and_then(year_date),
+                    description: tags
+                        .artist
+                        .as_ref()
+                        .map(|a| format!("by {a}")),
+                    idx: f.track_number,
+                    parent_idx: tags.disc_number,
+                    parent_id: Some(root.id),
+                    grandparent_id: artist_id,
+                    normalization_gain: tags
+                        .track_gain
+                        .or(tags.album_gain),
+                    external_ids: db::ExternalIds {
+                        musicbrainz_recording: tags.musicbrainz_recording_id,
+                        musicbrainz_track: tags.musicbrainz_track_id,
+                        musicbrainz_album: tags.musicbrainz_album_id,
+                        musicbrainz_release_group: tags.musicbrainz_release_group_id,
+                        musicbrainz_artist: tags.musicbrainz_artist_id,
+                        musicbrainz_album_artist: tags.musicbrainz_album_artist_id,
+                        album_title: Some(
+                            root.title
+                                .clone(),
+                        ),
+                        artist_name: Some(
+                            tags.artist
+                                .unwrap_or_else(|| artist.to_string()),
+                        ),
+                        ..Default::default()
+                    },
+                    stream_info: Some(crate::stream::StreamInfo {
+                        descriptor,
+                        name: Some(f.name),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                };
+                if !tags
+                    .genres
+                    .is_empty()
+                {
+                    track.relations = Some(genre_relations(track.id, &tags.genres));
+                }
+                track.parent = Some(db::Media::stub(
+                    root.id,
+                    root.title
+                        .clone(),
+                ));
+                if let Some(artist_id) = artist_id {
+                    track.grandparent = Some(db::Media::stub(artist_id, artist));
+                }
+                track
+            })
+            .collect();
+        tracks.sort_by(|a, b| {
+            (a.parent_idx, a.idx, &a.title).cmp(&(b.parent_idx, b.idx, &b.title))
+        });
+        Ok(Some(tracks))
+    }
 }
 
-async fn scan_addon(
+/// Tags stored by an earlier scan of the same, unchanged file.
+async fn cached_audio_tags(
     ctx: &AppContext,
-    tmdb: &Option<sdks::RestClient<sdks::BearerAuth>>,
-    addon: &Addon,
-) -> Result<()> {
-    let cfg = addon
-        .preset
-        .config
-        .expose();
-    let media_kind = cfg["media_kind"]
-        .as_str()
-        .unwrap_or("movie")
-        .to_string();
-    let is_local = addon
-        .preset
-        .kind
-        == "opendal-local";
+    row_id: Uuid,
+    size: i64,
+    modified_at: &str,
+) -> Result<Option<AudioTags>> {
+    let tags: Option<Option<sqlx::types::Json<AudioTags>>> = sqlx::query_scalar(
+        "SELECT audio_tags FROM opendal_files WHERE id = ? AND size = ? AND modified_at = ?",
+    )
+    .bind(row_id)
+    .bind(size)
+    .bind(modified_at)
+    .fetch_optional(&ctx.db)
+    .await?;
+    Ok(tags
+        .flatten()
+        .map(|t| t.0))
+}
 
-    info!(addon = %addon.name, kind = %addon.preset.kind, media_kind, "opendal: scanning");
+/// Local files are read in place; remote ones only up to `TAG_PROBE_BYTES`.
+async fn read_audio_tags(
+    operator: &opendal::Operator,
+    rel: &str,
+    local_path: Option<&str>,
+) -> Option<AudioTags> {
+    let tags = match local_path {
+        Some(path) => AudioTags::read_path(std::path::PathBuf::from(path)).await,
+        None => match operator
+            .read_with(rel)
+            .range(0..TAG_PROBE_BYTES)
+            .await
+        {
+            Ok(buf) => AudioTags::read_head(buf.to_vec()),
+            Err(e) => Err(e.into()),
+        },
+    };
+    tags.inspect_err(
+        |e| debug!(path = rel, error = %e, "opendal: no readable audio tags"),
+    )
+    .ok()
+}
+
+fn covers_dir(data_dir: &std::path::Path, addon_id: Uuid) -> std::path::PathBuf {
+    data_dir
+        .join("meta")
+        .join("opendal")
+        .join(addon_id.to_string())
+}
+
+/// Writes an embedded cover to the data dir, named after its content so the
+/// tracks of an album share one file.
+async fn save_cover(
+    ctx: &AppContext,
+    addon_id: Uuid,
+    cover: &audio_tags::Cover,
+) -> Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let dir = covers_dir(
+        &ctx.config
+            .data_dir,
+        addon_id,
+    );
+    let path = dir.join(format!(
+        "{:x}.{}",
+        Sha256::digest(&cover.data),
+        cover.extension()
+    ));
+    if !tokio::fs::try_exists(&path)
+        .await
+        .unwrap_or(false)
+    {
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(&path, &cover.data).await?;
+    }
+    Ok(path
+        .to_string_lossy()
+        .into_owned())
+}
+
+// ---------------------------------------------------------------------------
+// Local lyrics (sidecar .lrc/.txt, embedded tags)
+// ---------------------------------------------------------------------------
+
+const TRACK_FILE_COLS: &str = "id, path, name, title, imdb_id, season, episode, track_number, \
+                               year, size, audio_tags, lyrics_path";
+
+impl OpendalAddon {
+    /// Tracks of a tagged album carry their file's id. Any other track only
+    /// matches a file with the same title on the same album by the same
+    /// artist: a title alone is shared by too many songs.
+    async fn track_files(
+        &self,
+        media_id: Uuid,
+        title: &str,
+        artist: Option<&str>,
+        album: Option<&str>,
+        db: &sqlx::SqlitePool,
+    ) -> Result<Vec<OpendalFile>> {
+        let by_id: Vec<OpendalFile> = sqlx::query_as(&format!(
+            "SELECT {TRACK_FILE_COLS} FROM opendal_files \
+             WHERE addon_id = ? AND media_kind = 'track' AND id = ?"
+        ))
+        .bind(self.addon_id)
+        .bind(media_id)
+        .fetch_all(db)
+        .await?;
+        if !by_id.is_empty() {
+            return Ok(by_id);
+        }
+        let (Some(artist), Some(album)) = (artist, album) else {
+            return Ok(vec![]);
+        };
+        Ok(sqlx::query_as(&format!(
+            "SELECT {TRACK_FILE_COLS} FROM opendal_files \
+             WHERE addon_id = ? AND media_kind = 'track' AND LOWER(title) = LOWER(?) \
+               AND LOWER(album) = LOWER(?) \
+               AND (LOWER(album_artist) = LOWER(?) \
+                    OR LOWER(json_extract(audio_tags, '$.artist')) = LOWER(?))"
+        ))
+        .bind(self.addon_id)
+        .bind(title)
+        .bind(album)
+        .bind(artist)
+        .bind(artist)
+        .fetch_all(db)
+        .await?)
+    }
+
+    /// The sidecar found by the last scan, else the embedded lyrics.
+    async fn file_lyrics(&self, file: OpendalFile) -> Option<LyricDto> {
+        let sidecar = match &file.lyrics_path {
+            Some(path) => {
+                let bytes = if self.backend == "local" {
+                    tokio::fs::read(path)
+                        .await
+                        .map_err(anyhow::Error::from)
+                } else {
+                    self.operator
+                        .read(path)
+                        .await
+                        .map(|buf| buf.to_vec())
+                        .map_err(anyhow::Error::from)
+                };
+                bytes
+                    .inspect_err(
+                        |e| warn!(path, error = %e, "opendal: failed to read lyrics"),
+                    )
+                    .ok()
+                    .map(|b| String::from_utf8_lossy(&b).into_owned())
+            }
+            None => None,
+        };
+        let tags = file
+            .audio_tags
+            .unwrap_or_default();
+        let text = sidecar.or(tags
+            .lyrics
+            .clone())?;
+        let text = text.trim_start_matches('\u{feff}');
+        if text
+            .trim()
+            .is_empty()
+        {
+            return None;
+        }
+
+        let synced = lrclib::parse_lrc(text);
+        let is_synced = !synced.is_empty();
+        Some(LyricDto {
+            metadata: LyricMetadata {
+                title: file.title,
+                artist: tags.artist,
+                album: tags.album,
+                length: tags
+                    .duration
+                    .and_then(|d| d.to_ticks(TickUnit::Seconds)),
+                is_synced: Some(is_synced),
+            },
+            lyrics: if is_synced {
+                synced
+            } else {
+                lrclib::plain_to_lines(text)
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl LyricAddon for OpendalAddon {
+    fn provider_id(&self) -> String {
+        format!("opendal-{}", self.addon_id)
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+
+    async fn lyric_fetch(
+        &self,
+        req: &LyricSearchRequest,
+        db: &sqlx::SqlitePool,
+    ) -> Result<Option<LyricDto>> {
+        if self.media_kind != "track" {
+            return Ok(None);
+        }
+        for file in self
+            .track_files(
+                req.media_id,
+                &req.title,
+                req.artist
+                    .as_deref(),
+                req.album
+                    .as_deref(),
+                db,
+            )
+            .await?
+        {
+            if let Some(lyrics) = self
+                .file_lyrics(file)
+                .await
+            {
+                return Ok(Some(lyrics));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn lyric_search(
+        &self,
+        req: &LyricSearchRequest,
+        db: &sqlx::SqlitePool,
+    ) -> Result<Vec<RemoteLyricInfoDto>> {
+        if self.media_kind != "track" {
+            return Ok(vec![]);
+        }
+        let mut out = Vec::new();
+        for file in self
+            .track_files(
+                req.media_id,
+                &req.title,
+                req.artist
+                    .as_deref(),
+                req.album
+                    .as_deref(),
+                db,
+            )
+            .await?
+        {
+            let Some(id) = file.id else { continue };
+            if let Some(lyrics) = self
+                .file_lyrics(file)
+                .await
+            {
+                out.push(RemoteLyricInfoDto {
+                    id: format!("{}_{id}", self.provider_id()),
+                    provider_name: "local".into(),
+                    lyrics,
+                });
+            }
+        }
+        Ok(out)
+    }
+
+    async fn lyric_get_by_id(
+        &self,
+        id: &str,
+        db: &sqlx::SqlitePool,
+    ) -> Result<Option<LyricDto>> {
+        let Ok(id) = id.parse::<Uuid>() else {
+            return Ok(None);
+        };
+        let file: Option<OpendalFile> = sqlx::query_as(&format!(
+            "SELECT {TRACK_FILE_COLS} FROM opendal_files \
+             WHERE addon_id = ? AND media_kind = 'track' AND id = ?"
+        ))
+        .bind(self.addon_id)
+        .bind(id)
+        .fetch_optional(db)
+        .await?;
+        Ok(match file {
+            Some(file) => {
+                self.file_lyrics(file)
+                    .await
+            }
+            None => None,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Opendal file index scanning (backing refresh_index)
+// ---------------------------------------------------------------------------
+
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "vtt", "sub", "sup"];
+/// Lyrics sidecars of a track, in order of preference.
+const LYRICS_EXTENSIONS: &[&str] = &["lrc", "txt"];
+
+/// Extract the file stem (filename without the last extension).
+fn stem_without_ext(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, _)) => stem.to_string(),
+        None => name.to_string(),
+    }
+}
+
+/// Split a subtitle stem (filename without its subtitle extension) into its base and subtitle metadata.
+///
+/// For `Breaking.Bad.S01E01.en.forced` returns `("Breaking.Bad.S01E01", Some("en"), true, false)`.
+/// Scans right-to-left: known flags first, then a 2–3-letter lang code, remainder is the base.
+fn split_subtitle_stem(stem: &str) -> (String, Option<String>, bool, bool) {
+    let parts: Vec<&str> = stem
+        .split('.')
+        .collect();
+    if parts.is_empty() {
+        return (stem.to_string(), None, false, false);
+    }
+    let mut suffix_start = parts.len();
+    let mut is_forced = false;
+    let mut is_hi = false;
+
+    while suffix_start > 0 {
+        match parts[suffix_start - 1]
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "forced" => {
+                is_forced = true;
+                suffix_start -= 1;
+            }
+            "hi" | "sdh" | "cc" => {
+                is_hi = true;
+                suffix_start -= 1;
+            }
+            "default" => {
+                suffix_start -= 1;
+            }
+            _ => break,
+        }
+    }
+
+    let lang = if suffix_start > 0 {
+        let part = parts[suffix_start - 1];
+        if part.len() >= 2
+            && part.len() <= 3
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphabetic())
+        {
+            suffix_start -= 1;
+            Some(part.to_string())
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    (parts[..suffix_start].join("."), lang, is_forced, is_hi)
+}
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "tbn"];
+
+/// Artwork names per image kind (Jellyfin/Kodi conventions). `{stem}-{name}`
+/// belongs to one video; a bare `{name}` to the whole folder.
+const ARTWORK_NAMES: &[(db::ImageKind, &[&str])] = &[
+    (db::ImageKind::Primary, &["poster", "folder", "cover"]),
+    (
+        db::ImageKind::Backdrop,
+        &["fanart", "backdrop", "background"],
+    ),
+    (db::ImageKind::Logo, &["clearlogo", "logo"]),
+    (db::ImageKind::Thumb, &["landscape", "thumb"]),
+];
+
+fn parent_dir(rel: &str) -> &str {
+    rel.rsplit_once('/')
+        .map(|(dir, _)| dir)
+        .unwrap_or("")
+}
+
+/// NFO, lyrics and artwork files of one scan root, indexed by directory.
+struct Sidecars {
+    operator: opendal::Operator,
+    /// dir → lowercased file name → entry path
+    files: std::collections::HashMap<String, std::collections::HashMap<String, String>>,
+    /// dir → number of media files directly inside it
+    media: std::collections::HashMap<String, usize>,
+    /// Parsed NFOs by entry path; `None` when unreadable or not an NFO root.
+    nfos: std::collections::HashMap<String, Option<nfo::Nfo>>,
+}
+
+impl Sidecars {
+    fn index(
+        operator: &opendal::Operator,
+        entries: &[opendal::Entry],
+        is_media_ext: fn(&str) -> bool,
+    ) -> Self {
+        let mut sidecars = Sidecars {
+            operator: operator.clone(),
+            files: Default::default(),
+            media: Default::default(),
+            nfos: Default::default(),
+        };
+        for entry in entries {
+            let rel = entry.path();
+            let dir = parent_dir(rel).to_string();
+            let name = entry
+                .name()
+                .to_lowercase();
+            let ext = name
+                .rsplit_once('.')
+                .map(|(_, ext)| ext)
+                .unwrap_or("");
+            if ext == "nfo"
+                || IMAGE_EXTENSIONS.contains(&ext)
+                || LYRICS_EXTENSIONS.contains(&ext)
+            {
+                sidecars
+                    .files
+                    .entry(dir)
+                    .or_default()
+                    .insert(name, rel.to_string());
+            } else if is_media_ext(ext) {
+                *sidecars
+                    .media
+                    .entry(dir)
+                    .or_default() += 1;
+            }
+        }
+        sidecars
+    }
+
+    /// Entry path of the first `{stem}.{ext}` present in `dir`, ignoring case.
+    fn find(&self, dir: &str, stems: &[String], exts: &[&str]) -> Option<String> {
+        let files = self
+            .files
+            .get(dir)?;
+        stems
+            .iter()
+            .flat_map(|stem| {
+                exts.iter()
+                    .map(move |ext| format!("{}.{ext}", stem.to_lowercase()))
+            })
+            .find_map(|name| {
+                files
+                    .get(&name)
+                    .cloned()
+            })
+    }
+
+    /// A folder holding a single video is that video's folder, so its
+    /// `movie.nfo` and bare artwork names belong to it.
+    fn owns_folder(&self, dir: &str) -> bool {
+        self.media
+            .get(dir)
+            == Some(&1)
+    }
+
+    async fn nfo(&mut self, rel: &str) -> Option<nfo::Nfo> {
+        if let Some(cached) = self
+            .nfos
+            .get(rel)
+        {
+            return cached.clone();
+        }
+        let parsed = match self
+            .operator
+            .read(rel)
+            .await
+        {
+            Ok(buf) => {
+                let text = String::from_utf8_lossy(&buf.to_bytes()).into_owned();
+                nfo::Nfo::parse(&text).unwrap_or_else(|e| {
+                    warn!(path = rel, error = %e, "opendal: unreadable nfo");
+                    None
+                })
+            }
+            Err(e) => {
+                warn!(path = rel, error = %e, "opendal: failed to read nfo");
+                None
+            }
+        };
+        self.nfos
+            .insert(rel.to_string(), parsed.clone());
+        parsed
+    }
+
+    /// `{stem}.nfo` of a video, falling back to `movie.nfo` in its own folder.
+    async fn movie_nfo(&mut self, dir: &str, stem: &str) -> Option<nfo::Nfo> {
+        let mut stems = vec![stem.to_string()];
+        if self.owns_folder(dir) {
+            stems.push("movie".to_string());
+        }
+        let rel = self.find(dir, &stems, &["nfo"])?;
+        self.nfo(&rel)
+            .await
+            .filter(|n| n.kind == Some(nfo::NfoKind::Movie))
+    }
+
+    async fn episode_nfo(&mut self, dir: &str, stem: &str) -> Option<nfo::Nfo> {
+        let rel = self.find(dir, &[stem.to_string()], &["nfo"])?;
+        self.nfo(&rel)
+            .await
+            .filter(|n| n.kind == Some(nfo::NfoKind::Episode))
+    }
+
+    /// The series folder of an episode in `dir`: the nearest ancestor with a
+    /// `tvshow.nfo`, else the top-level folder.
+    fn series_dir(&self, dir: &str) -> Option<String> {
+        let mut current = dir;
+        loop {
+            if self
+                .find(current, &["tvshow".to_string()], &["nfo"])
+                .is_some()
+            {
+                return Some(current.to_string());
+            }
+            if current.is_empty() {
+                break;
+            }
+            current = parent_dir(current);
+        }
+        dir.split('/')
+            .find(|c| !c.is_empty())
+            .map(str::to_string)
+    }
+
+    async fn series_nfo(&mut self, series_dir: &str) -> Option<nfo::Nfo> {
+        let rel = self.find(series_dir, &["tvshow".to_string()], &["nfo"])?;
+        self.nfo(&rel)
+            .await
+            .filter(|n| n.kind == Some(nfo::NfoKind::TvShow))
+    }
+
+    /// Artwork of a video (`stem`) and/or a whole folder.
+    fn artwork(
+        &self,
+        dir: &str,
+        stem: Option<&str>,
+        folder: bool,
+    ) -> Vec<(db::ImageKind, String)> {
+        ARTWORK_NAMES
+            .iter()
+            .filter_map(|(kind, names)| {
+                let mut stems: Vec<String> = stem
+                    .into_iter()
+                    .flat_map(|s| {
+                        names
+                            .iter()
+                            .map(move |n| format!("{s}-{n}"))
+                    })
+                    .collect();
+                if folder {
+                    stems.extend(
+                        names
+                            .iter()
+                            .map(|n| n.to_string()),
+                    );
+                }
+                self.find(dir, &stems, IMAGE_EXTENSIONS)
+                    .map(|rel| (*kind, rel))
+            })
+            .collect()
+    }
+
+    /// `seasonNN-poster.jpg` in the series folder, or `poster.jpg` inside the
+    /// season's own folder.
+    fn season_artwork(
+        &self,
+        series_dir: &str,
+        season_dir: Option<&str>,
+        season: i64,
+    ) -> Vec<(db::ImageKind, String)> {
+        let mut prefixes =
+            vec![format!("season{season:02}"), format!("season{season}")];
+        if season == 0 {
+            prefixes.push("season-specials".to_string());
+        }
+        let mut found = Vec::new();
+        for (kind, suffix) in [
+            (db::ImageKind::Primary, "poster"),
+            (db::ImageKind::Backdrop, "fanart"),
+            (db::ImageKind::Thumb, "landscape"),
+        ] {
+            let stems: Vec<String> = prefixes
+                .iter()
+                .map(|p| format!("{p}-{suffix}"))
+                .collect();
+            if let Some(rel) = self.find(series_dir, &stems, IMAGE_EXTENSIONS) {
+                found.push((kind, rel));
+            }
+        }
+        if let Some(season_dir) = season_dir {
+            for (kind, rel) in self.artwork(season_dir, None, true) {
+                if !found
+                    .iter()
+                    .any(|(k, _)| *k == kind)
+                {
+                    found.push((kind, rel));
+                }
+            }
+        }
+        found
+    }
+}
+
+/// IMDB id named by an NFO, directly or through its TMDB/TVDB ids.
+async fn nfo_imdb(
+    tmdb: &Option<sdks::RestClient<sdks::BearerAuth>>,
+    nfo: Option<&nfo::Nfo>,
+    is_tv: bool,
+) -> Option<String> {
+    let ids = &nfo?.ids;
+    if let Some(imdb) = &ids.imdb {
+        return Some(imdb.to_string());
+    }
+    if ids
+        .tmdb
+        .is_none()
+        && ids
+            .tvdb
+            .is_none()
+    {
+        return None;
+    }
+    crate::addons::tmdb::resolve_imdb_from_ids(ids, is_tv, tmdb.as_ref()?)
+        .await
+        .map(Into::into)
+}
+
+/// NFO fields and artwork of one movie, series, season or episode, keyed by
+/// the seed of its stable id.
+#[derive(Debug, Default, sqlx::FromRow)]
+struct LocalMetadata {
+    media_key: String,
+    title: Option<String>,
+    plot: Option<String>,
+    rating: Option<f64>,
+    tmdb_id: Option<i64>,
+    tvdb_id: Option<i64>,
+    primary_image: Option<String>,
+    backdrop_image: Option<String>,
+    logo_image: Option<String>,
+    thumb_image: Option<String>,
+}
+
+impl LocalMetadata {
+    fn new(media_key: String, nfo: Option<&nfo::Nfo>) -> Self {
+        let mut meta = LocalMetadata {
+            media_key,
+            ..Default::default()
+        };
+        if let Some(nfo) = nfo {
+            meta.title = nfo
+                .title
+                .clone();
+            meta.plot = nfo
+                .plot
+                .clone();
+            meta.rating = nfo.rating;
+            meta.tmdb_id = nfo
+                .ids
+                .tmdb;
+            meta.tvdb_id = nfo
+                .ids
+                .tvdb;
+        }
+        meta
+    }
+
+    fn with_images(mut self, images: Vec<(db::ImageKind, String)>) -> Self {
+        for (kind, path) in images {
+            let slot = match kind {
+                db::ImageKind::Primary => &mut self.primary_image,
+                db::ImageKind::Backdrop => &mut self.backdrop_image,
+                db::ImageKind::Logo => &mut self.logo_image,
+                db::ImageKind::Thumb => &mut self.thumb_image,
+            };
+            slot.get_or_insert(path);
+        }
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.title
+            .is_none()
+            && self
+                .plot
+                .is_none()
+            && self
+                .rating
+                .is_none()
+            && self
+                .tmdb_id
+                .is_none()
+            && self
+                .tvdb_id
+                .is_none()
+            && self
+                .primary_image
+                .is_none()
+            && self
+                .backdrop_image
+                .is_none()
+            && self
+                .logo_image
+                .is_none()
+            && self
+                .thumb_image
+                .is_none()
+    }
+
+    async fn save(
+        &self,
+        ctx: &AppContext,
+        addon_id: Uuid,
+        scanned_at: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO opendal_metadata \
+             (addon_id, media_key, title, plot, rating, tmdb_id, tvdb_id, \
+              primary_image, backdrop_image, logo_image, thumb_image, scanned_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT(addon_id, media_key) DO UPDATE SET \
+               title = excluded.title, plot = excluded.plot, rating = excluded.rating, \
+               tmdb_id = excluded.tmdb_id, tvdb_id = excluded.tvdb_id, \
+               primary_image = excluded.primary_image, \
+               backdrop_image = excluded.backdrop_image, \
+               logo_image = excluded.logo_image, thumb_image = excluded.thumb_image, \
+               scanned_at = excluded.scanned_at",
+        )
+        .bind(addon_id)
+        .bind(&self.media_key)
+        .bind(&self.title)
+        .bind(&self.plot)
+        .bind(self.rating)
+        .bind(self.tmdb_id)
+        .bind(self.tvdb_id)
+        .bind(&self.primary_image)
+        .bind(&self.backdrop_image)
+        .bind(&self.logo_image)
+        .bind(&self.thumb_image)
+        .bind(scanned_at)
+        .execute(&ctx.db)
+        .await?;
+        Ok(())
+    }
+
+    /// Rows whose key starts with `prefix`, by key.
+    async fn load(
+        ctx: &AppContext,
+        addon_id: Uuid,
+        prefix: &str,
+    ) -> Result<std::collections::HashMap<String, LocalMetadata>> {
+        let rows: Vec<LocalMetadata> = sqlx::query_as(
+            "SELECT media_key, title, plot, rating, tmdb_id, tvdb_id, \
+                    primary_image, backdrop_image, logo_image, thumb_image \
+             FROM opendal_metadata WHERE addon_id = ? AND substr(media_key, 1, ?) = ?",
+        )
+        .bind(addon_id)
+        .bind(prefix.len() as i64)
+        .bind(prefix)
+        .fetch_all(&ctx.db)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.media_key
+                        .clone(),
+                    row,
+                )
+            })
+            .collect())
+    }
+
+    fn apply(self, media: &mut db::Media) {
+        if let Some(title) = self.title {
+            media.title = title;
+        }
+        if self
+            .plot
+            .is_some()
+        {
+            media.description = self.plot;
+        }
+        if self
+            .rating
+            .is_some()
+        {
+            media.rating_audience = self.rating;
+        }
+        let ids = &mut media.external_ids;
+        ids.tmdb = ids
+            .tmdb
+            .or(self.tmdb_id);
+        ids.tvdb = ids
+            .tvdb
+            .or(self.tvdb_id);
+        for (kind, path) in [
+            (db::ImageKind::Primary, self.primary_image),
+            (db::ImageKind::Backdrop, self.backdrop_image),
+            (db::ImageKind::Logo, self.logo_image),
+            (db::ImageKind::Thumb, self.thumb_image),
+        ] {
+            if let Some(path) = path {
+                media.set_image(kind, path);
+            }
+        }
+    }
+}
+
+/// Re-indexes the parts of a local addon's roots that hold `changed` paths,
+/// for the filesystem watcher.
+pub(crate) async fn refresh_paths(
+    ctx: &AppContext,
+    addon: &Addon,
+    changed: &[std::path::PathBuf],
+) -> Result<()> {
+    let tmdb = common::tmdb_client(
+        &ctx.db,
+        &ctx.config
+            .tmdb_base_url,
+    )
+    .await;
+    scan_addon(ctx, &tmdb, addon, Some(changed)).await
+}
+
+/// What a scan limited to `changed` lists under a local `root`, as
+/// `(list_from, recursive)`: the whole top-level folder of each path, so a
+/// series or artist folder is always seen with its NFOs and artwork, and the
+/// loose files of the root itself.
+fn scoped_listings(
+    root: &str,
+    changed: &[std::path::PathBuf],
+) -> std::collections::BTreeSet<(String, bool)> {
+    let mut out = std::collections::BTreeSet::new();
+    for path in changed {
+        let Ok(rel) = path.strip_prefix(root) else {
+            continue;
+        };
+        let mut components = rel.components();
+        let Some(top) = components.next() else {
+            // The root itself was replaced.
+            return [("/".to_string(), true)].into();
+        };
+        let top = top
+            .as_os_str()
+            .to_string_lossy();
+        if components
+            .next()
+            .is_some()
+            || path.is_dir()
+        {
+            out.insert((format!("{top}/"), true));
+        } else {
+            out.insert(("/".to_string(), false));
+            if !path.exists() {
+                // Gone: it may have been a folder.
+                out.insert((format!("{top}/"), true));
+            }
+        }
+    }
+    out
+}
+
+async fn list_files(
+    operator: &opendal::Operator,
+    from: &str,
+    recursive: bool,
+) -> opendal::Result<Vec<opendal::Entry>> {
+    operator
+        .lister_with(from)
+        .recursive(recursive)
+        .await?
+        .try_filter(|entry| {
+            futures::future::ready(
+                entry
+                    .metadata()
+                    .mode()
+                    == EntryMode::FILE,
+            )
+        })
+        .try_collect()
+        .await
+}
+
+/// `changed` limits a local scan to the folders holding those paths; rows
+/// outside them are left alone.
+async fn scan_addon(
+    ctx: &AppContext,
+    tmdb: &Option<sdks::RestClient<sdks::BearerAuth>>,
+    addon: &Addon,
+    changed: Option<&[std::path::PathBuf]>,
+) -> Result<()> {
+    let cfg = addon
+        .preset
+        .config
+        .expose();
+    let media_kind = cfg_media_kind(cfg);
+    let is_local = addon
+        .preset
+        .kind
+        == "opendal-local";
+
+    info!(
+        addon = %addon.name,
+        kind = %addon.preset.kind,
+        media_kind,
+        changed = changed.map(<[_]>::len),
+        "opendal: scanning"
+    );
 
     let is_media_ext: fn(&str) -> bool = if media_kind == "track" {
         |ext| {
@@ -1085,60 +2714,103 @@ async fn scan_addon(
 
     let track_num_re = Regex::new(r"^(\d{1,3})[.\s\-_\[\]]+").unwrap();
 
-    // Build (operator, list_from, path_prefix) for each configured path.
+    // Build (operator, list_from, path_prefix, recursive) for each configured path.
     // Local: one Fs operator per root, list from "/", prefix gives absolute stored path.
-    // WebDAV: one shared operator, list from each sub-path, no prefix needed.
-    let scan_roots: Vec<(opendal::Operator, String, String)> = if is_local {
-        cfg_paths_local(cfg)?
-            .into_iter()
-            .map(|p| {
-                let op =
-                    opendal::Operator::new(opendal::services::Fs::default().root(&p))?
-                        .finish();
-                Ok((op, "/".to_string(), p))
-            })
-            .collect::<Result<_>>()?
+    // Remote (WebDAV, S3, SFTP, SMB): one shared operator, list from each sub-path,
+    // no prefix needed.
+    // A scoped scan also records `(stored path prefix, recursive)` of what it
+    // lists, which is all it may prune.
+    let mut prune_scope: Vec<(String, bool)> = Vec::new();
+    let scan_roots: Vec<(opendal::Operator, String, String, bool)> = if is_local {
+        let mut roots = Vec::new();
+        for p in cfg_paths_local(cfg)? {
+            let op = opendal::Operator::new(opendal::services::Fs::default().root(&p))?
+                .finish();
+            let Some(changed) = changed else {
+                roots.push((op, "/".to_string(), p, true));
+                continue;
+            };
+            for (list_from, recursive) in scoped_listings(&p, changed) {
+                let root = p.trim_end_matches('/');
+                prune_scope.push((
+                    match list_from.trim_end_matches('/') {
+                        "" => root.to_string(),
+                        top => format!("{root}/{top}"),
+                    },
+                    recursive,
+                ));
+                roots.push((op.clone(), list_from, p.clone(), recursive));
+            }
+        }
+        if changed.is_some() && roots.is_empty() {
+            return Ok(());
+        }
+        roots
     } else {
-        let op = build_webdav_operator(cfg)?;
-        cfg_paths_webdav(cfg)
+        let (op, _) = build_remote_operator(
+            &addon
+                .preset
+                .kind,
+            cfg,
+        )?;
+        cfg_paths_remote(cfg)
             .into_iter()
-            .map(|p| (op.clone(), p, String::new()))
+            .map(|p| (op.clone(), p, String::new(), true))
             .collect()
     };
 
     let mut seen_ids: Vec<Uuid> = Vec::new();
     let mut upserted = 0usize;
-
-    for (operator, list_from, path_prefix) in scan_roots {
-        let mut lister = operator
-            .lister_with(&list_from)
-            .recursive(true)
-            .await?;
-
-        while let Some(entry) = lister
-            .try_next()
-            .await?
-        {
-            if entry
-                .metadata()
-                .mode()
-                != EntryMode::FILE
-            {
-                continue;
+    // Every metadata row written by this scan carries the same stamp, so the
+    // rest can be pruned afterwards.
+    let scanned_at = Utc::now()
+        .naive_utc()
+        .to_string();
+    let mut saved_keys: std::collections::HashSet<String> = Default::default();
+
+    for (operator, list_from, path_prefix, recursive) in scan_roots {
+        // Listed up front: NFOs and artwork may come after the video they describe.
+        let entries = match list_files(&operator, &list_from, recursive).await {
+            Ok(entries) => entries,
+            // A scoped scan may be told about a folder that is already gone.
+            Err(e) if changed.is_some() && e.kind() == opendal::ErrorKind::NotFound => {
+                Vec::new()
             }
-
-            let entry_rel = entry
-                .path()
-                .to_string();
-            let path = if path_prefix.is_empty() {
-                entry_rel.clone()
+            Err(e) => return Err(e.into()),
+        };
+        let mut sidecars = Sidecars::index(&operator, &entries, is_media_ext);
+        let full_path = |rel: &str| {
+            if path_prefix.is_empty() {
+                rel.to_string()
             } else {
                 format!(
                     "{}/{}",
                     path_prefix.trim_end_matches('/'),
-                    entry_rel.trim_start_matches('/')
+                    rel.trim_start_matches('/')
                 )
-            };
+            }
+        };
+        // Local artwork is served from disk; remote artwork through the addon.
+        let image_ref = |rel: String| {
+            if is_local {
+                full_path(&rel)
+            } else {
+                format!("opendal://{}/{}", addon.id, rel)
+            }
+        };
+        let images_ref = |images: Vec<(db::ImageKind, String)>| {
+            images
+                .into_iter()
+                .map(|(kind, rel)| (kind, image_ref(rel)))
+                .collect::<Vec<_>>()
+        };
+
+        for entry in &entries {
+            let entry_rel = entry
+                .path()
+                .to_string();
+            let path = full_path(&entry_rel);
+            let dir = parent_dir(&entry_rel).to_string();
             let name = entry
                 .name()
                 .to_string();
@@ -1152,79 +2824,97 @@ async fn scan_addon(
             if SUBTITLE_EXTENSIONS.contains(&ext.as_str()) {
                 let stem = stem_without_ext(&name);
                 let (base_stem, _, _, _) = split_subtitle_stem(&stem);
-                let jellyfin_ids = db::ExternalIds::from_path(&path);
                 let parsed = hunch::hunch(&base_stem);
 
                 let (imdb_id, season, episode, year, title_str) =
                     match media_kind.as_str() {
                         "episode" => {
-                            let season = parsed
-                                .season()
-                                .map(|s| s as i64);
-                            let episode = parsed
-                                .episode()
-                                .map(|e| e as i64);
+                            let episode_nfo = sidecars
+                                .episode_nfo(&dir, &base_stem)
+                                .await;
+                            let series_nfo = match sidecars.series_dir(&dir) {
+                                Some(series_dir) => {
+                                    sidecars
+                                        .series_nfo(&series_dir)
+                                        .await
+                                }
+                                None => None,
+                            };
+                            let season = episode_nfo
+                                .as_ref()
+                                .and_then(|n| n.season)
+                                .or(parsed
+                                    .season()
+                                    .map(|s| s as i64));
+                            let episode = episode_nfo
+                                .as_ref()
+                                .and_then(|n| n.episode)
+                                .or(parsed
+                                    .episode()
+                                    .map(|e| e as i64));
                             let year = parsed
                                 .year()
                                 .map(|y| y as i64);
-                            let clean_title = parsed
-                                .title()
-                                .unwrap_or(base_stem.as_str())
-                                .to_string();
+                            let clean_title = series_nfo
+                                .as_ref()
+                                .and_then(|n| {
+                                    n.title
+                                        .clone()
+                                })
+                                .unwrap_or_else(|| {
+                                    parsed
+                                        .title()
+                                        .unwrap_or(base_stem.as_str())
+                                        .to_string()
+                                });
                             let existing_imdb =
                                 fetch_existing_imdb(ctx, addon.id, &path).await?;
-                            let imdb_id = if let Some(id) = existing_imdb {
-                                Some(id)
-                            } else if !jellyfin_ids.is_empty() {
-                                if let Some(client) = tmdb {
-                                    crate::addons::tmdb::resolve_imdb_from_ids(
-                                        &jellyfin_ids,
-                                        true,
-                                        client,
-                                    )
-                                    .await
-                                    .map(Into::into)
-                                } else {
-                                    jellyfin_ids
-                                        .imdb
-                                        .clone()
-                                        .map(Into::into)
-                                }
-                            } else {
-                                resolve_imdb(tmdb, &clean_title, None, true).await
-                            };
+                            let imdb_id = file_imdb(
+                                tmdb,
+                                &path,
+                                existing_imdb,
+                                series_nfo.as_ref(),
+                                &clean_title,
+                                None,
+                                true,
+                            )
+                            .await;
                             (imdb_id, season, episode, year, clean_title)
                         }
                         _ => {
-                            let year = parsed
-                                .year()
-                                .map(|y| y as i64);
-                            let clean_title = parsed
-                                .title()
-                                .unwrap_or(base_stem.as_str())
-                                .to_string();
+                            let movie_nfo = sidecars
+                                .movie_nfo(&dir, &base_stem)
+                                .await;
+                            let year = movie_nfo
+                                .as_ref()
+                                .and_then(|n| n.year)
+                                .or(parsed
+                                    .year()
+                                    .map(|y| y as i64));
+                            let clean_title = movie_nfo
+                                .as_ref()
+                                .and_then(|n| {
+                                    n.title
+                                        .clone()
+                                })
+                                .unwrap_or_else(|| {
+                                    parsed
+                                        .title()
+                                        .unwrap_or(base_stem.as_str())
+                                        .to_string()
+                                });
                             let existing_imdb =
                                 fetch_existing_imdb(ctx, addon.id, &path).await?;
-                            let imdb_id = if let Some(id) = existing_imdb {
-                                Some(id)
-                            } else if !jellyfin_ids.is_empty() {
-                                if let Some(client) = tmdb {
-                                    crate::addons::tmdb::resolve_imdb_from_ids(
-                                        &jellyfin_ids,
-                                        false,
-                                        client,
-                                    )
-                                    .await
-                                    .map(Into::into)
-                                } else {
-                                    jellyfin_ids
-                                        .imdb
-                                        .clone()
-                                        .map(Into::into)
-                                }
-                            } else {
-                                resolve_imdb(tmdb, &clean_title, year, false).await
-                            };
+                            let imdb_id = file_imdb(
+                                tmdb,
+                                &path,
+                                existing_imdb,
+                                movie_nfo.as_ref(),
+                                &clean_title,
+                                year,
+                                false,
+                            )
+                            .await;
                             (imdb_id, None, None, year, clean_title)
                         }
                     };
@@ -1246,7 +2936,7 @@ async fn scan_addon(
                      ON CONFLICT(id) DO UPDATE SET \
                        path = excluded.path, name = excluded.name, \
                        title = excluded.title, \
-                       imdb_id = COALESCE(opendal_files.imdb_id, excluded.imdb_id), \
+                       imdb_id = COALESCE(excluded.imdb_id, opendal_files.imdb_id), \
                        season = excluded.season, episode = excluded.episode, \
                        year = excluded.year, scanned_at = excluded.scanned_at",
                 )
@@ -1383,172 +3073,376 @@ async fn scan_addon(
                 .unwrap_or(&name)
                 .to_string();
 
-            let jellyfin_ids = db::ExternalIds::from_path(&path);
-
-            let (title, season, episode, track_number, year, imdb_id) = match media_kind
-                .as_str()
-            {
-                "track" => {
-                    let track_number = track_num_re
-                        .captures(&stem)
-                        .and_then(|c| c.get(1))
-                        .and_then(|m| {
-                            m.as_str()
-                                .parse::<i64>()
-                                .ok()
-                        });
-                    let clean_stem = if track_number.is_some() {
-                        track_num_re
-                            .replace(&stem, "")
-                            .into_owned()
-                    } else {
-                        stem.clone()
-                    };
-                    let parsed = hunch::hunch(&clean_stem);
-                    let title = parsed
-                        .title()
-                        .unwrap_or(clean_stem.as_str())
-                        .to_string();
-                    (Some(title), None, None, track_number, None, None)
-                }
-                "episode" => {
-                    let parsed = hunch::hunch(&stem);
-                    let season = parsed
-                        .season()
-                        .map(|s| s as i64);
-                    let episode = parsed
-                        .episode()
-                        .map(|e| e as i64);
-
-                    // When the filename starts with the episode code (e.g. "S01E07 - Title"),
-                    // hunch finds no title before it and returns None. In that case the series
-                    // folder name is the authoritative source; using the stem would store the
-                    // episode title (or the whole filename) as the series name.
-                    let (clean_title, year) = match parsed
-                        .title()
-                        .filter(|t| !t.is_empty())
-                    {
-                        Some(t) => {
-                            let year = parsed
-                                .year()
-                                .map(|y| y as i64);
-                            (t.to_string(), year)
-                        }
-                        None if path_components.len() >= 2 => {
-                            let series_dir = path_components[0];
-                            let dir_parsed = hunch::hunch(series_dir);
-                            let title = dir_parsed
-                                .title()
-                                .filter(|t| !t.is_empty())
-                                .unwrap_or(series_dir)
-                                .to_string();
-                            let year = dir_parsed
-                                .year()
-                                .map(|y| y as i64);
-                            (title, year)
-                        }
-                        _ => {
-                            let year = parsed
-                                .year()
-                                .map(|y| y as i64);
-                            (stem.clone(), year)
-                        }
-                    };
+            let size = entry
+                .metadata()
+                .content_length() as i64;
+            let modified_at = entry
+                .metadata()
+                .last_modified()
+                .map(|t| t.to_rfc3339());
+            let mut audio: Option<AudioTags> = None;
+            let mut lyrics_path: Option<String> = None;
+
+            let (title, season, episode, track_number, year, imdb_id, metadata) =
+                match media_kind.as_str() {
+                    "track" => {
+                        let track_number = track_num_re
+                            .captures(&stem)
+                            .and_then(|c| c.get(1))
+                            .and_then(|m| {
+                                m.as_str()
+                                    .parse::<i64>()
+                                    .ok()
+                            });
+                        let clean_stem = if track_number.is_some() {
+                            track_num_re
+                                .replace(&stem, "")
+                                .into_owned()
+                        } else {
+                            stem.clone()
+                        };
+                        let parsed = hunch::hunch(&clean_stem);
+                        let title = parsed
+                            .title()
+                            .unwrap_or(clean_stem.as_str())
+                            .to_string();
 
-                    let existing_imdb =
-                        fetch_existing_imdb(ctx, addon.id, &path).await?;
-                    let imdb_id = if let Some(id) = existing_imdb {
-                        Some(id)
-                    } else if !jellyfin_ids.is_empty() {
-                        if let Some(client) = tmdb {
-                            crate::addons::tmdb::resolve_imdb_from_ids(
-                                &jellyfin_ids,
-                                true,
-                                client,
-                            )
-                            .await
-                            .map(Into::into)
+                        // Reading a remote file's tags means downloading its
+                        // head, so unchanged files keep the last scan's.
+                        let cached = match &modified_at {
+                            Some(modified_at) if !is_local => {
+                                cached_audio_tags(ctx, row_id, size, modified_at)
+                                    .await?
+                            }
+                            _ => None,
+                        };
+                        let tags = if ext == "strm" {
+                            None
+                        } else if cached.is_some() {
+                            cached
                         } else {
-                            jellyfin_ids
-                                .imdb
-                                .clone()
-                                .map(Into::into)
+                            let mut tags = read_audio_tags(
+                                &operator,
+                                &entry_rel,
+                                is_local.then_some(path.as_str()),
+                            )
+                            .await;
+                            if let Some(tags) = &mut tags
+                                && let Some(cover) = tags
+                                    .cover
+                                    .take()
+                            {
+                                match save_cover(ctx, addon.id, &cover).await {
+                                    Ok(image) => tags.cover_image = Some(image),
+                                    Err(e) => {
+                                        warn!(path, error = %e, "opendal: failed to save embedded cover")
+                                    }
+                                }
+                            }
+                            tags
+                        };
+
+                        // Folder artwork wins over embedded covers; an artist
+                        // folder is recognised by its name.
+                        let mut metadata = Vec::new();
+                        if let Some((artist, album)) = tags
+                            .as_ref()
+                            .and_then(AudioTags::album_key)
+                        {
+                            let key = album_key(artist, album);
+                            if !saved_keys.contains(&key) {
+                                let mut images =
+                                    images_ref(sidecars.artwork(&dir, None, true));
+                                if let Some(cover) = tags
+                                    .as_ref()
+                                    .and_then(|t| {
+                                        t.cover_image
+                                            .clone()
+                                    })
+                                {
+                                    images.push((db::ImageKind::Primary, cover));
+                                }
+                                metadata.push(
+                                    LocalMetadata::new(key, None).with_images(images),
+                                );
+                            }
+                            let key = artist_key(artist);
+                            let artist_dir = parent_dir(&dir);
+                            if !saved_keys.contains(&key)
+                                && artist_dir
+                                    .rsplit('/')
+                                    .next()
+                                    .is_some_and(|name| {
+                                        name.eq_ignore_ascii_case(artist)
+                                    })
+                            {
+                                metadata.push(
+                                    LocalMetadata::new(key, None).with_images(
+                                        images_ref(
+                                            sidecars.artwork(artist_dir, None, true),
+                                        ),
+                                    ),
+                                );
+                            }
                         }
-                    } else {
-                        resolve_imdb(tmdb, &clean_title, None, true).await
-                    };
 
-                    if imdb_id.is_none() {
-                        debug!(path, title = %clean_title, "opendal: no IMDB id, skipping");
-                        continue;
+                        let year = tags
+                            .as_ref()
+                            .and_then(|t| t.year);
+                        let track_number = tags
+                            .as_ref()
+                            .and_then(|t| t.track_number)
+                            .or(track_number);
+                        let title = tags
+                            .as_ref()
+                            .and_then(|t| {
+                                t.title
+                                    .clone()
+                            })
+                            .unwrap_or(title);
+                        audio = tags;
+                        lyrics_path = sidecars
+                            .find(&dir, &[stem.clone()], LYRICS_EXTENSIONS)
+                            .map(|rel| full_path(&rel));
+                        (Some(title), None, None, track_number, year, None, metadata)
                     }
+                    "episode" => {
+                        let parsed = hunch::hunch(&stem);
+                        let episode_nfo = sidecars
+                            .episode_nfo(&dir, &stem)
+                            .await;
+                        let series_dir = sidecars.series_dir(&dir);
+                        let series_nfo = match &series_dir {
+                            Some(series_dir) => {
+                                sidecars
+                                    .series_nfo(series_dir)
+                                    .await
+                            }
+                            None => None,
+                        };
+                        let season = episode_nfo
+                            .as_ref()
+                            .and_then(|n| n.season)
+                            .or(parsed
+                                .season()
+                                .map(|s| s as i64));
+                        let episode = episode_nfo
+                            .as_ref()
+                            .and_then(|n| n.episode)
+                            .or(parsed
+                                .episode()
+                                .map(|e| e as i64));
+
+                        // When the filename starts with the episode code (e.g. "S01E07 - Title"),
+                        // hunch finds no title before it and returns None. In that case the series
+                        // folder name is the authoritative source; using the stem would store the
+                        // episode title (or the whole filename) as the series name.
+                        let (clean_title, year) = match parsed
+                            .title()
+                            .filter(|t| !t.is_empty())
+                        {
+                            Some(t) => {
+                                let year = parsed
+                                    .year()
+                                    .map(|y| y as i64);
+                                (t.to_string(), year)
+                            }
+                            None if path_components.len() >= 2 => {
+                                let series_dir = path_components[0];
+                                let dir_parsed = hunch::hunch(series_dir);
+                                let title = dir_parsed
+                                    .title()
+                                    .filter(|t| !t.is_empty())
+                                    .unwrap_or(series_dir)
+                                    .to_string();
+                                let year = dir_parsed
+                                    .year()
+                                    .map(|y| y as i64);
+                                (title, year)
+                            }
+                            _ => {
+                                let year = parsed
+                                    .year()
+                                    .map(|y| y as i64);
+                                (stem.clone(), year)
+                            }
+                        };
+                        // A curated tvshow.nfo beats anything parsed from names.
+                        let clean_title = series_nfo
+                            .as_ref()
+                            .and_then(|n| {
+                                n.title
+                                    .clone()
+                            })
+                            .unwrap_or(clean_title);
+                        let year = series_nfo
+                            .as_ref()
+                            .and_then(|n| n.year)
+                            .or(year);
+
+                        let existing_imdb =
+                            fetch_existing_imdb(ctx, addon.id, &path).await?;
+                        let imdb_id = file_imdb(
+                            tmdb,
+                            &path,
+                            existing_imdb,
+                            series_nfo.as_ref(),
+                            &clean_title,
+                            None,
+                            true,
+                        )
+                        .await;
 
-                    (Some(clean_title), season, episode, None, year, imdb_id)
-                }
-                _ => {
-                    // movie
-                    let parsed = hunch::hunch(&stem);
-                    let year = parsed
-                        .year()
-                        .map(|y| y as i64);
-                    let clean_title = parsed
-                        .title()
-                        .unwrap_or(stem.as_str())
-                        .to_string();
-
-                    let existing_imdb =
-                        fetch_existing_imdb(ctx, addon.id, &path).await?;
-                    let imdb_id = if let Some(id) = existing_imdb {
-                        Some(id)
-                    } else if !jellyfin_ids.is_empty() {
-                        if let Some(client) = tmdb {
-                            crate::addons::tmdb::resolve_imdb_from_ids(
-                                &jellyfin_ids,
-                                false,
-                                client,
+                        let Some(imdb) = imdb_id.as_deref() else {
+                            debug!(path, title = %clean_title, "opendal: no IMDB id, skipping");
+                            continue;
+                        };
+
+                        let series_art = series_dir
+                            .as_deref()
+                            .map(|d| sidecars.artwork(d, None, true))
+                            .unwrap_or_default();
+                        let mut metadata = vec![
+                            LocalMetadata::new(
+                                format!("series:{imdb}"),
+                                series_nfo.as_ref(),
                             )
-                            .await
-                            .map(Into::into)
-                        } else {
-                            jellyfin_ids
-                                .imdb
-                                .clone()
-                                .map(Into::into)
+                            .with_images(images_ref(series_art)),
+                        ];
+                        if let Some(s) = season {
+                            let season_dir = (series_dir.as_deref()
+                                != Some(dir.as_str()))
+                            .then_some(dir.as_str());
+                            let season_art = sidecars.season_artwork(
+                                series_dir
+                                    .as_deref()
+                                    .unwrap_or(""),
+                                season_dir,
+                                s,
+                            );
+                            metadata.push(
+                                LocalMetadata::new(format!("season:{imdb}:{s}"), None)
+                                    .with_images(images_ref(season_art)),
+                            );
+                            if let Some(e) = episode {
+                                let still = sidecars
+                                    .find(
+                                        &dir,
+                                        &[format!("{stem}-thumb"), stem.clone()],
+                                        IMAGE_EXTENSIONS,
+                                    )
+                                    .map(|rel| (db::ImageKind::Primary, rel));
+                                metadata.push(
+                                    LocalMetadata::new(
+                                        format!("episode:{imdb}:{s}:{e}"),
+                                        episode_nfo.as_ref(),
+                                    )
+                                    .with_images(
+                                        images_ref(
+                                            still
+                                                .into_iter()
+                                                .collect(),
+                                        ),
+                                    ),
+                                );
+                            }
                         }
-                    } else {
-                        resolve_imdb(tmdb, &clean_title, year, false).await
-                    };
 
-                    if imdb_id.is_none() {
-                        debug!(path, title = %clean_title, "opendal: no IMDB id, skipping");
-                        continue;
+                        (
+                            Some(clean_title),
+                            season,
+                            episode,
+                            None,
+                            year,
+                            imdb_id,
+                            metadata,
+                        )
                     }
+                    _ => {
+                        // movie
+                        let parsed = hunch::hunch(&stem);
+                        let movie_nfo = sidecars
+                            .movie_nfo(&dir, &stem)
+                            .await;
+                        let year = movie_nfo
+                            .as_ref()
+                            .and_then(|n| n.year)
+                            .or(parsed
+                                .year()
+                                .map(|y| y as i64));
+                        let clean_title = movie_nfo
+                            .as_ref()
+                            .and_then(|n| {
+                                n.title
+                                    .clone()
+                            })
+                            .unwrap_or_else(|| {
+                                parsed
+                                    .title()
+                                    .unwrap_or(stem.as_str())
+                                    .to_string()
+                            });
+
+                        let existing_imdb =
+                            fetch_existing_imdb(ctx, addon.id, &path).await?;
+                        let imdb_id = file_imdb(
+                            tmdb,
+                            &path,
+                            existing_imdb,
+                            movie_nfo.as_ref(),
+                            &clean_title,
+                            year,
+                            false,
+                        )
+                        .await;
 
-                    (Some(clean_title), None, None, None, year, imdb_id)
-                }
-            };
+                        let Some(imdb) = imdb_id.as_deref() else {
+                            debug!(path, title = %clean_title, "opendal: no IMDB id, skipping");
+                            continue;
+                        };
+
+                        let art = sidecars.artwork(
+                            &dir,
+                            Some(&stem),
+                            sidecars.owns_folder(&dir),
+                        );
+                        let metadata = vec![
+                            LocalMetadata::new(
+                                format!("movie:{imdb}"),
+                                movie_nfo.as_ref(),
+                            )
+                            .with_images(images_ref(art)),
+                        ];
+
+                        (Some(clean_title), None, None, None, year, imdb_id, metadata)
+                    }
+                };
 
-            let size = Some(
-                entry
-                    .metadata()
-                    .content_length() as i64,
-            );
             let now = Utc::now()
                 .naive_utc()
                 .to_string();
+            let (album_artist, album) = audio
+                .as_ref()
+                .and_then(AudioTags::album_key)
+                .map(|(artist, album)| (artist.to_string(), album.to_string()))
+                .unzip();
 
             sqlx::query(
                 "INSERT INTO opendal_files \
-                 (id, addon_id, media_kind, path, name, title, imdb_id, season, episode, track_number, year, size, scanned_at) \
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+                 (id, addon_id, media_kind, path, name, title, imdb_id, season, episode, track_number, year, size, \
+                  modified_at, album_artist, album, audio_tags, lyrics_path, scanned_at) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
                  ON CONFLICT(id) DO UPDATE SET \
                    path = excluded.path, \
                    name = excluded.name, media_kind = excluded.media_kind, \
                    title = excluded.title, \
-                   imdb_id = COALESCE(opendal_files.imdb_id, excluded.imdb_id), \
+                   imdb_id = COALESCE(excluded.imdb_id, opendal_files.imdb_id), \
                    season = excluded.season, episode = excluded.episode, \
                    track_number = excluded.track_number, \
-                   year = excluded.year, size = excluded.size, scanned_at = excluded.scanned_at",
+                   year = excluded.year, size = excluded.size, modified_at = excluded.modified_at, \
+                   album_artist = excluded.album_artist, album = excluded.album, \
+                   audio_tags = excluded.audio_tags, lyrics_path = excluded.lyrics_path, \
+                   scanned_at = excluded.scanned_at",
             )
             .bind(row_id)
             .bind(addon.id)
@@ -1562,15 +3456,50 @@ async fn scan_addon(
             .bind(track_number)
             .bind(year)
             .bind(size)
+            .bind(&modified_at)
+            .bind(album_artist)
+            .bind(album)
+            .bind(audio.map(sqlx::types::Json))
+            .bind(lyrics_path)
             .bind(&now)
             .execute(&ctx.db)
             .await?;
 
+            for meta in metadata {
+                if meta.is_empty()
+                    || !saved_keys.insert(
+                        meta.media_key
+                            .clone(),
+                    )
+                {
+                    continue;
+                }
+                meta.save(ctx, addon.id, &scanned_at)
+                    .await?;
+            }
+
             upserted += 1;
         }
     }
 
-    let deleted = prune_stale_paths(ctx, addon.id, &seen_ids).await?;
+    let deleted = prune_stale_paths(
+        ctx,
+        addon.id,
+        &seen_ids,
+        changed.map(|_| prune_scope.as_slice()),
+    )
+    .await?;
+    // NFO metadata is keyed by media rather than path; only a full scan knows
+    // what is left of it.
+    if changed.is_none() {
+        sqlx::query(
+            "DELETE FROM opendal_metadata WHERE addon_id = ? AND scanned_at <> ?",
+        )
+        .bind(addon.id)
+        .bind(&scanned_at)
+        .execute(&ctx.db)
+        .await?;
+    }
 
     info!(
         addon = %addon.name,
@@ -1582,27 +3511,6 @@ async fn scan_addon(
     Ok(())
 }
 
-fn build_webdav_operator(cfg: &serde_json::Value) -> Result<opendal::Operator> {
-    let endpoint = cfg["endpoint"]
-        .as_str()
-        .filter(|s| !s.is_empty())
-        .ok_or_else(|| anyhow::anyhow!("opendal-webdav: endpoint required"))?;
-    let mut builder = opendal::services::Webdav::default().endpoint(endpoint);
-    if let Some(u) = cfg["username"]
-        .as_str()
-        .filter(|s| !s.is_empty())
-    {
-        builder = builder.username(u);
-    }
-    if let Some(p) = cfg["password"]
-        .as_str()
-        .filter(|s| !s.is_empty())
-    {
-        builder = builder.password(p);
-    }
-    Ok(opendal::Operator::new(builder)?.finish())
-}
-
 async fn fetch_existing_imdb(
     ctx: &AppContext,
     addon_id: Uuid,
@@ -1618,6 +3526,40 @@ async fn fetch_existing_imdb(
     .flatten())
 }
 
+/// IMDB id of a scanned video or subtitle, from the strongest source that
+/// has one: its NFO, the id an earlier scan stored, ids tagged in the path,
+/// and last a TMDB search on the title.
+async fn file_imdb(
+    tmdb: &Option<sdks::RestClient<sdks::BearerAuth>>,
+    path: &str,
+    existing: Option<String>,
+    nfo: Option<&nfo::Nfo>,
+    title: &str,
+    year: Option<i64>,
+    is_tv: bool,
+) -> Option<String> {
+    if let Some(id) = nfo_imdb(tmdb, nfo, is_tv).await {
+        return Some(id);
+    }
+    if existing.is_some() {
+        return existing;
+    }
+    let path_ids = db::ExternalIds::from_path(path);
+    if path_ids.is_empty() {
+        return resolve_imdb(tmdb, title, year, is_tv).await;
+    }
+    match tmdb {
+        Some(client) => {
+            crate::addons::tmdb::resolve_imdb_from_ids(&path_ids, is_tv, client)
+                .await
+                .map(Into::into)
+        }
+        None => path_ids
+            .imdb
+            .map(Into::into),
+    }
+}
+
 async fn resolve_imdb(
     tmdb: &Option<sdks::RestClient<sdks::BearerAuth>>,
     title: &str,
@@ -1689,12 +3631,19 @@ async fn resolve_imdb(
     }
 }
 
+/// Deletes the addon's rows that were not `seen`. `scope` limits this to
+/// rows under the given `(path prefix, recursive)` folders of a scoped scan;
+/// `None` covers the whole addon.
 async fn prune_stale_paths(
     ctx: &AppContext,
     addon_id: Uuid,
     seen: &[Uuid],
+    scope: Option<&[(String, bool)]>,
 ) -> Result<usize> {
-    if seen.is_empty() {
+    if scope.is_some_and(<[_]>::is_empty) {
+        return Ok(0);
+    }
+    if seen.is_empty() && scope.is_none() {
         let result = sqlx::query("DELETE FROM opendal_files WHERE addon_id = ?")
             .bind(addon_id)
             .execute(&ctx.db)
@@ -1726,13 +3675,37 @@ async fn prune_stale_paths(
             .await?;
     }
 
-    let result = sqlx::query(
-        "DELETE FROM opendal_files \
-         WHERE addon_id = ? AND id NOT IN (SELECT id FROM _opendal_seen)",
-    )
-    .bind(addon_id)
-    .execute(&mut *tx)
-    .await?;
+    let mut qb = sqlx::QueryBuilder::new("DELETE FROM opendal_files WHERE addon_id = ");
+    qb.push_bind(addon_id);
+    qb.push(" AND id NOT IN (SELECT id FROM _opendal_seen)");
+    if let Some(scope) = scope {
+        qb.push(" AND (");
+        for (i, (prefix, recursive)) in scope
+            .iter()
+            .enumerate()
+        {
+            if i > 0 {
+                qb.push(" OR ");
+            }
+            // Compared in characters, as SQLite's substr/length count them.
+            let prefix = format!("{prefix}/");
+            qb.push("(substr(path, 1, length(");
+            qb.push_bind(prefix.clone());
+            qb.push(")) = ");
+            qb.push_bind(prefix.clone());
+            if !recursive {
+                qb.push(" AND instr(substr(path, length(");
+                qb.push_bind(prefix);
+                qb.push(") + 1), '/') = 0");
+            }
+            qb.push(")");
+        }
+        qb.push(")");
+    }
+    let result = qb
+        .build()
+        .execute(&mut *tx)
+        .await?;
 
     tx.commit()
         .await?;
@@ -1743,7 +3716,7 @@ async fn prune_stale_paths(
 mod tests {
     use std::sync::{Arc, atomic::AtomicU64};
 
-    use chrono::Utc;
+    use chrono::{Datelike, Utc};
     use futures::StreamExt;
     use regex::Regex;
     use remux_sdks::stremio::ResourceType;
@@ -3458,58 +5431,380 @@ mod tests {
 
         let (ctx, _guard) = test_server_with_tmdb(&tmdb).await;
 
-        let (addon, db_addon) = make_local_addon(&ctx, dir.path(), "episode").await;
-        addon
-            .refresh_index(&ctx, &db_addon, noop_progress())
+        let (addon, db_addon) = make_local_addon(&ctx, dir.path(), "episode").await;
+        addon
+            .refresh_index(&ctx, &db_addon, noop_progress())
+            .await
+            .unwrap();
+
+        for f in fixtures {
+            let count: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM opendal_files \
+                 WHERE addon_id = ? AND media_kind = 'episode' \
+                   AND imdb_id = ? AND season = ? AND episode = ?",
+            )
+            .bind(db_addon.id)
+            .bind(f.expected_imdb)
+            .bind(f.expected_season)
+            .bind(f.expected_episode)
+            .fetch_one(&ctx.db)
+            .await
+            .unwrap();
+            assert_eq!(
+                count, 1,
+                "{}: expected imdb={} s={} e={} after title-search resolution",
+                f.rel_path, f.expected_imdb, f.expected_season, f.expected_episode
+            );
+        }
+    }
+
+    // -----------------------------------------------------------------------
+    // E2E: movie resolve — tmdbid tag and no-label (title+year search).
+    // Also verifies catalog_stream surfaces the indexed movies.
+    // -----------------------------------------------------------------------
+
+    #[tokio::test]
+    async fn opendal_local_movie_resolve() {
+        // (rel_path, expected_imdb)
+        let fixtures: &[(&str, &str)] = &[
+            // [tmdbid-603] → The Matrix → tt0133093
+            (
+                "[tmdbid-603] The Matrix (1999)/The.Matrix.1999.mkv",
+                "tt0133093",
+            ),
+            // No label: title+year parsed from filename → TMDB search
+            ("Interstellar.2014.mkv", "tt0816692"),
+        ];
+
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &fixtures
+                .iter()
+                .map(|(p, _)| (*p, b"fake" as &[u8]))
+                .collect::<Vec<_>>(),
+        );
+
+        let (_, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+
+        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "movie").await;
+        addon
+            .refresh_index(ctx, &db_addon, noop_progress())
+            .await
+            .unwrap();
+
+        // Every fixture must have a DB row.
+        for (path, imdb) in fixtures {
+            let count: i64 = sqlx::query_scalar(
+                "SELECT COUNT(*) FROM opendal_files \
+                 WHERE addon_id = ? AND media_kind = 'movie' AND imdb_id = ?",
+            )
+            .bind(db_addon.id)
+            .bind(imdb)
+            .fetch_one(&ctx.db)
+            .await
+            .unwrap();
+            assert_eq!(count, 1, "{path}: expected imdb={imdb}");
+        }
+
+        // catalog_stream must return one Movie item per distinct IMDB.
+        let catalog: Vec<db::Media> = addon
+            .catalog_stream(ctx, "files")
+            .await
+            .unwrap()
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(
+            catalog.len(),
+            fixtures.len(),
+            "catalog should have one entry per movie"
+        );
+        assert!(
+            catalog
+                .iter()
+                .all(|m| m.kind == db::MediaKind::Movie)
+        );
+        for (_, imdb) in fixtures {
+            assert!(
+                catalog
+                    .iter()
+                    .any(|m| m
+                        .external_ids
+                        .imdb
+                        .as_deref()
+                        .map(|s| s.as_str())
+                        == Some(imdb)),
+                "catalog missing movie with imdb={imdb}"
+            );
+        }
+    }
+
+    // -----------------------------------------------------------------------
+    // E2E: track indexing — track_number extraction, catalog, and get_streams.
+    // -----------------------------------------------------------------------
+
+    #[tokio::test]
+    async fn opendal_local_track_index_and_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                ("01 - First Song.mp3", b"audio"),
+                ("02. Second Song.flac", b"audio"),
+                ("Track Without Number.ogg", b"audio"),
+            ],
+        );
+
+        let (_, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+
+        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "track").await;
+        addon
+            .refresh_index(ctx, &db_addon, noop_progress())
+            .await
+            .unwrap();
+
+        // Verify track_number and title are stored correctly.
+        let rows: Vec<(Option<String>, Option<i64>)> = sqlx::query_as(
+            "SELECT title, track_number FROM opendal_files \
+             WHERE addon_id = ? AND media_kind = 'track' ORDER BY COALESCE(track_number, 999)",
+        )
+        .bind(db_addon.id)
+        .fetch_all(&ctx.db)
+        .await
+        .unwrap();
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], (Some("First Song".to_string()), Some(1)));
+        assert_eq!(rows[1], (Some("Second Song".to_string()), Some(2)));
+        assert_eq!(
+            rows[2].1, None,
+            "unnumbered track must have track_number=NULL"
+        );
+
+        // catalog_stream must return one Track per file.
+        let catalog: Vec<db::Media> = addon
+            .catalog_stream(ctx, "files")
+            .await
+            .unwrap()
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(catalog.len(), 3);
+        assert!(
+            catalog
+                .iter()
+                .all(|m| m.kind == db::MediaKind::Track)
+        );
+
+        // get_streams must return a Local stream for each track (matched by title).
+        for item in &catalog {
+            let streams = addon
+                .get_streams(item, ctx, None)
+                .await
+                .unwrap();
+            assert!(
+                !streams.is_empty(),
+                "get_streams empty for track {:?}",
+                item.title
+            );
+            assert!(
+                streams
+                    .iter()
+                    .all(|s| matches!(s.descriptor, StreamDescriptor::Local(_))),
+                "expected Local descriptor for track {:?}",
+                item.title
+            );
+        }
+    }
+
+    // -----------------------------------------------------------------------
+    // E2E: tagged tracks are grouped into artist → album → track trees.
+    // -----------------------------------------------------------------------
+
+    /// A FLAC file with a three-minute STREAMINFO and the given Vorbis
+    /// comments, and no audio frames.
+    fn flac_with_tags(comments: &[(&str, &str)]) -> Vec<u8> {
+        let mut out = b"fLaC".to_vec();
+        out.extend([0, 0, 0, 34]);
+        out.extend(4096u16.to_be_bytes());
+        out.extend(4096u16.to_be_bytes());
+        out.extend([0; 6]);
+        let samples = 44100u64 * 180;
+        out.extend(((44100u64 << 44) | (1 << 41) | (15 << 36) | samples).to_be_bytes());
+        out.extend([0; 16]);
+
+        let mut block = Vec::new();
+        let vendor = b"remux";
+        block.extend((vendor.len() as u32).to_le_bytes());
+        block.extend(vendor);
+        block.extend((comments.len() as u32).to_le_bytes());
+        for (key, value) in comments {
+            let comment = format!("{key}={value}");
+            block.extend((comment.len() as u32).to_le_bytes());
+            block.extend(comment.as_bytes());
+        }
+        out.push(0x84);
+        out.extend(&(block.len() as u32).to_be_bytes()[1..]);
+        out.extend(block);
+        out
+    }
+
+    #[tokio::test]
+    async fn opendal_local_tagged_tracks_build_artist_album_tree() {
+        let album_tags = [
+            ("ARTIST", "Radiohead"),
+            ("ALBUM", "OK Computer"),
+            ("DATE", "1997-05-21"),
+            ("GENRE", "Alternative Rock"),
+            (
+                "MUSICBRAINZ_ALBUMID",
+                "0b6b4ba0-d36f-47bd-b4ea-6a5b91842d29",
+            ),
+        ];
+        let tagged = |title: &str, number: &str, disc: &str| {
+            let mut comments = album_tags.to_vec();
+            comments.extend([
+                ("TITLE", title),
+                ("TRACKNUMBER", number),
+                ("DISCNUMBER", disc),
+                ("REPLAYGAIN_TRACK_GAIN", "-8.10 dB"),
+            ]);
+            flac_with_tags(&comments)
+        };
+        let airbag = tagged("Airbag", "1", "1");
+        let paranoid = tagged("Paranoid Android", "2", "1");
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                ("Radiohead/OK Computer/02 - b.flac", paranoid.as_slice()),
+                ("Radiohead/OK Computer/01 - a.flac", airbag.as_slice()),
+                ("Untagged Song.mp3", b"audio"),
+            ],
+        );
+
+        let (_, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+
+        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "track").await;
+        addon
+            .refresh_index(ctx, &db_addon, noop_progress())
+            .await
+            .unwrap();
+
+        // One artist for the tagged files, the untagged one stays a flat track.
+        let catalog: Vec<db::Media> = addon
+            .catalog_stream(ctx, "files")
+            .await
+            .unwrap()
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(catalog.len(), 2);
+        let artist = catalog
+            .iter()
+            .find(|m| m.kind == db::MediaKind::Artist)
+            .expect("tagged files must yield an artist");
+        assert_eq!(artist.title, "Radiohead");
+        assert!(
+            catalog
+                .iter()
+                .any(|m| m.kind == db::MediaKind::Track && m.title == "Untagged Song")
+        );
+
+        let albums = addon
+            .get_children(artist, ctx)
             .await
+            .unwrap()
             .unwrap();
+        assert_eq!(albums.len(), 1);
+        let album = &albums[0];
+        assert_eq!(album.kind, db::MediaKind::Album);
+        assert_eq!(album.title, "OK Computer");
+        assert_eq!(album.parent_id, Some(artist.id));
+        assert_eq!(
+            album
+                .released_at
+                .map(|d| d.year()),
+            Some(1997)
+        );
+        assert_eq!(
+            album
+                .external_ids
+                .musicbrainz_album
+                .as_deref(),
+            Some("0b6b4ba0-d36f-47bd-b4ea-6a5b91842d29")
+        );
 
-        for f in fixtures {
-            let count: i64 = sqlx::query_scalar(
-                "SELECT COUNT(*) FROM opendal_files \
-                 WHERE addon_id = ? AND media_kind = 'episode' \
-                   AND imdb_id = ? AND season = ? AND episode = ?",
-            )
-            .bind(db_addon.id)
-            .bind(f.expected_imdb)
-            .bind(f.expected_season)
-            .bind(f.expected_episode)
-            .fetch_one(&ctx.db)
+        let tracks = addon
+            .get_children(album, ctx)
             .await
+            .unwrap()
             .unwrap();
-            assert_eq!(
-                count, 1,
-                "{}: expected imdb={} s={} e={} after title-search resolution",
-                f.rel_path, f.expected_imdb, f.expected_season, f.expected_episode
-            );
+        let titles: Vec<_> = tracks
+            .iter()
+            .map(|t| {
+                (
+                    t.parent_idx,
+                    t.idx,
+                    t.title
+                        .as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                (Some(1), Some(1), "Airbag"),
+                (Some(1), Some(2), "Paranoid Android"),
+            ]
+        );
+        for track in &tracks {
+            assert_eq!(track.parent_id, Some(album.id));
+            assert_eq!(track.grandparent_id, Some(artist.id));
+            assert_eq!(track.normalization_gain, Some(-8.1));
+            assert_eq!(track.runtime, Some(180));
+
+            let streams = addon
+                .get_streams(track, ctx, None)
+                .await
+                .unwrap();
+            assert_eq!(streams.len(), 1, "one stream for {:?}", track.title);
+            assert!(matches!(streams[0].descriptor, StreamDescriptor::Local(_)));
         }
     }
 
     // -----------------------------------------------------------------------
-    // E2E: movie resolve — tmdbid tag and no-label (title+year search).
-    // Also verifies catalog_stream surfaces the indexed movies.
+    // E2E: lyrics come from a sidecar .lrc, else from embedded tags.
     // -----------------------------------------------------------------------
 
     #[tokio::test]
-    async fn opendal_local_movie_resolve() {
-        // (rel_path, expected_imdb)
-        let fixtures: &[(&str, &str)] = &[
-            // [tmdbid-603] → The Matrix → tt0133093
-            (
-                "[tmdbid-603] The Matrix (1999)/The.Matrix.1999.mkv",
-                "tt0133093",
-            ),
-            // No label: title+year parsed from filename → TMDB search
-            ("Interstellar.2014.mkv", "tt0816692"),
-        ];
-
+    async fn opendal_local_track_lyrics_from_sidecar_and_tags() {
+        let embedded = flac_with_tags(&[
+            ("TITLE", "Plain"),
+            ("LYRICS", "First line\nSecond line"),
+        ]);
         let dir = tempfile::tempdir().unwrap();
         write_files(
             dir.path(),
-            &fixtures
-                .iter()
-                .map(|(p, _)| (*p, b"fake" as &[u8]))
-                .collect::<Vec<_>>(),
+            &[
+                ("01 - Synced.mp3", b"audio"),
+                (
+                    "01 - Synced.lrc",
+                    b"[ar:Someone]\n[00:01.00]Hello\n[00:02.50]World\n",
+                ),
+                ("Plain.flac", embedded.as_slice()),
+                ("Quiet Song.ogg", b"audio"),
+            ],
         );
 
         let (_, guard) = new_test_server()
@@ -3517,27 +5812,11 @@ mod tests {
             .unwrap();
         let ctx = &guard.0;
 
-        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "movie").await;
+        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "track").await;
         addon
             .refresh_index(ctx, &db_addon, noop_progress())
             .await
             .unwrap();
-
-        // Every fixture must have a DB row.
-        for (path, imdb) in fixtures {
-            let count: i64 = sqlx::query_scalar(
-                "SELECT COUNT(*) FROM opendal_files \
-                 WHERE addon_id = ? AND media_kind = 'movie' AND imdb_id = ?",
-            )
-            .bind(db_addon.id)
-            .bind(imdb)
-            .fetch_one(&ctx.db)
-            .await
-            .unwrap();
-            assert_eq!(count, 1, "{path}: expected imdb={imdb}");
-        }
-
-        // catalog_stream must return one Movie item per distinct IMDB.
         let catalog: Vec<db::Media> = addon
             .catalog_stream(ctx, "files")
             .await
@@ -3545,77 +5824,360 @@ mod tests {
             .unwrap()
             .collect()
             .await;
+        let request = |title: &str| {
+            let item = catalog
+                .iter()
+                .find(|m| m.title == title)
+                .unwrap_or_else(|| panic!("{title} not in catalog"));
+            LyricSearchRequest {
+                media_id: item.id,
+                title: item
+                    .title
+                    .clone(),
+                artist: None,
+                album: None,
+                duration_secs: None,
+            }
+        };
+
+        let synced = addon
+            .lyric_fetch(&request("Synced"), &ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
         assert_eq!(
-            catalog.len(),
-            fixtures.len(),
-            "catalog should have one entry per movie"
+            synced
+                .metadata
+                .is_synced,
+            Some(true)
+        );
+        let lines: Vec<_> = synced
+            .lyrics
+            .iter()
+            .map(|l| {
+                (
+                    l.text
+                        .as_str(),
+                    l.start,
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![("Hello", Some(10_000_000)), ("World", Some(25_000_000))]
+        );
+
+        let plain = addon
+            .lyric_fetch(&request("Plain"), &ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            plain
+                .metadata
+                .is_synced,
+            Some(false)
         );
+        let lines: Vec<_> = plain
+            .lyrics
+            .iter()
+            .map(|l| {
+                l.text
+                    .as_str()
+            })
+            .collect();
+        assert_eq!(lines, vec!["First line", "Second line"]);
+
         assert!(
-            catalog
-                .iter()
-                .all(|m| m.kind == db::MediaKind::Movie)
+            addon
+                .lyric_fetch(&request("Quiet Song"), &ctx.db)
+                .await
+                .unwrap()
+                .is_none()
         );
-        for (_, imdb) in fixtures {
+
+        // Search results can be fetched again by their id.
+        let found = addon
+            .lyric_search(&request("Synced"), &ctx.db)
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        let inner = found[0]
+            .id
+            .strip_prefix(&format!("{}_", addon.provider_id()))
+            .unwrap();
+        let again = addon
+            .lyric_get_by_id(inner, &ctx.db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            again
+                .lyrics
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn opendal_track_without_its_file_id_matches_on_title_album_and_artist() {
+        let tagged = flac_with_tags(&[
+            ("TITLE", "Intro"),
+            ("ARTIST", "The xx"),
+            ("ALBUM", "xx"),
+            ("LYRICS", "Instrumental"),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path(), &[("01 - Intro.flac", tagged.as_slice())]);
+
+        let (_, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "track").await;
+        addon
+            .refresh_index(ctx, &db_addon, noop_progress())
+            .await
+            .unwrap();
+
+        // A track from another service, so its id names no file here.
+        let request = |artist: Option<&str>, album: Option<&str>| LyricSearchRequest {
+            media_id: Uuid::new_v4(),
+            title: "Intro".into(),
+            artist: artist.map(Into::into),
+            album: album.map(Into::into),
+            duration_secs: None,
+        };
+        for (artist, album) in [
+            (None, None),
+            (Some("The xx"), None),
+            (Some("The xx"), Some("Coexist")),
+            (Some("M83"), Some("xx")),
+        ] {
             assert!(
-                catalog
-                    .iter()
-                    .any(|m| m
-                        .external_ids
-                        .imdb
-                        .as_deref()
-                        .map(|s| s.as_str())
-                        == Some(imdb)),
-                "catalog missing movie with imdb={imdb}"
+                addon
+                    .lyric_fetch(&request(artist, album), &ctx.db)
+                    .await
+                    .unwrap()
+                    .is_none(),
+                "{artist:?} / {album:?} matched"
             );
         }
+        assert!(
+            addon
+                .lyric_fetch(&request(Some("the xx"), Some("XX")), &ctx.db)
+                .await
+                .unwrap()
+                .is_some()
+        );
     }
 
     // -----------------------------------------------------------------------
-    // E2E: track indexing — track_number extraction, catalog, and get_streams.
+    // E2E: S3 — a MinIO-style bucket stood in by httpmock is listed, indexed
+    // and streamed with ranges.
     // -----------------------------------------------------------------------
 
     #[tokio::test]
-    async fn opendal_local_track_index_and_stream() {
-        let dir = tempfile::tempdir().unwrap();
-        write_files(
-            dir.path(),
-            &[
-                ("01 - First Song.mp3", b"audio"),
-                ("02. Second Song.flac", b"audio"),
-                ("Track Without Number.ogg", b"audio"),
-            ],
+    async fn opendal_s3_bucket_index_and_ranged_stream() {
+        let s3 = httpmock::MockServer::start();
+        let list = s3.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/music")
+                .query_param("list-type", "2");
+            then.status(200)
+                .header("content-type", "application/xml")
+                .body(
+                    r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>music</Name>
+  <Prefix></Prefix>
+  <KeyCount>1</KeyCount>
+  <MaxKeys>1000</MaxKeys>
+  <IsTruncated>false</IsTruncated>
+  <Contents>
+    <Key>albums/01.Intro.mp3</Key>
+    <LastModified>2026-01-01T00:00:00.000Z</LastModified>
+    <ETag>"0b"</ETag>
+    <Size>5</Size>
+    <StorageClass>STANDARD</StorageClass>
+  </Contents>
+</ListBucketResult>"#,
+                );
+        });
+        s3.mock(|when, then| {
+            when.method(httpmock::Method::HEAD)
+                .path("/music/albums/01.Intro.mp3");
+            then.status(200)
+                .header("content-length", "5")
+                .header("last-modified", "Thu, 01 Jan 2026 00:00:00 GMT");
+        });
+        s3.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/music/albums/01.Intro.mp3");
+            then.status(206)
+                .header("content-range", "bytes 0-4/5")
+                .body("audio");
+        });
+
+        let (_, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+
+        let cfg = serde_json::json!({
+            "media_kind": "track",
+            "endpoint": s3.base_url(),
+            "bucket": "music",
+        });
+        let now = Utc::now().naive_utc();
+        let db_addon = Addon {
+            id: Uuid::new_v4(),
+            name: "test-s3".to_string(),
+            preset: AddonPresetRef {
+                kind: "opendal-s3".to_string(),
+                config: cfg
+                    .clone()
+                    .into(),
+            },
+            resources: vec![ResourceType::Stream, ResourceType::Catalog],
+            types: vec![],
+            enabled: true,
+            priority: 0,
+            system: false,
+            is_default: true,
+            http_redirect_stream: false,
+            service_filter: vec![],
+            created_at: now,
+            updated_at: now,
+        };
+        db_addon
+            .insert(&ctx.db)
+            .await
+            .unwrap();
+        let (operator, root) = build_remote_operator("opendal-s3", &cfg).unwrap();
+        assert_eq!(root, "s3://music");
+        let addon = OpendalAddon {
+            addon_id: db_addon.id,
+            operator: Arc::new(operator),
+            root,
+            backend: "s3".to_string(),
+            media_kind: "track".to_string(),
+        };
+
+        addon
+            .refresh_index(ctx, &db_addon, noop_progress())
+            .await
+            .unwrap();
+        list.assert();
+
+        let catalog: Vec<db::Media> = addon
+            .catalog_stream(ctx, "files")
+            .await
+            .unwrap()
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(catalog[0].title, "Intro");
+
+        let streams = addon
+            .get_streams(&catalog[0], ctx, None)
+            .await
+            .unwrap();
+        assert_eq!(streams.len(), 1);
+        let StreamDescriptor::Opendal { path, .. } = &streams[0].descriptor else {
+            panic!("expected an Opendal descriptor");
+        };
+        assert_eq!(path, "albums/01.Intro.mp3");
+
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert(
+            axum::http::header::RANGE,
+            axum::http::HeaderValue::from_static("bytes=0-4"),
         );
+        let response = addon
+            .serve_stream(&streams[0].descriptor, &headers)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::PARTIAL_CONTENT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"audio");
+    }
+
+    // -----------------------------------------------------------------------
+    // E2E: SMB — a share on the in-memory SMB2 server is listed, indexed and
+    // streamed with ranges.
+    // -----------------------------------------------------------------------
+
+    #[tokio::test]
+    async fn opendal_smb_share_index_and_ranged_stream() {
+        let audio: Vec<u8> = (0..200_000u32)
+            .map(|i| i as u8)
+            .collect();
+        let server = crate::smb::test_server::TestServer::start(
+            crate::smb::test_server::Share {
+                name: "music",
+                user: Some(("remux", "hunter2")),
+                signing_required: true,
+                files: vec![("albums/01.Intro.mp3", audio.clone())],
+            },
+        )
+        .await;
 
         let (_, guard) = new_test_server()
             .await
             .unwrap();
         let ctx = &guard.0;
 
-        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "track").await;
+        let cfg = serde_json::json!({
+            "media_kind": "track",
+            "endpoint": server.addr.to_string(),
+            "share": "music",
+            "username": "remux",
+            "password": "hunter2",
+        });
+        let now = Utc::now().naive_utc();
+        let db_addon = Addon {
+            id: Uuid::new_v4(),
+            name: "test-smb".to_string(),
+            preset: AddonPresetRef {
+                kind: "opendal-smb".to_string(),
+                config: cfg
+                    .clone()
+                    .into(),
+            },
+            resources: vec![ResourceType::Stream, ResourceType::Catalog],
+            types: vec![],
+            enabled: true,
+            priority: 0,
+            system: false,
+            is_default: true,
+            http_redirect_stream: false,
+            service_filter: vec![],
+            created_at: now,
+            updated_at: now,
+        };
+        db_addon
+            .insert(&ctx.db)
+            .await
+            .unwrap();
+        let (operator, root) = build_remote_operator("opendal-smb", &cfg).unwrap();
+        assert_eq!(root, format!("smb://{}/music", server.addr));
+        let addon = OpendalAddon {
+            addon_id: db_addon.id,
+            operator: Arc::new(operator),
+            root,
+            backend: "smb".to_string(),
+            media_kind: "track".to_string(),
+        };
+
         addon
             .refresh_index(ctx, &db_addon, noop_progress())
             .await
             .unwrap();
 
-        // Verify track_number and title are stored correctly.
-        let rows: Vec<(Option<String>, Option<i64>)> = sqlx::query_as(
-            "SELECT title, track_number FROM opendal_files \
-             WHERE addon_id = ? AND media_kind = 'track' ORDER BY COALESCE(track_number, 999)",
-        )
-        .bind(db_addon.id)
-        .fetch_all(&ctx.db)
-        .await
-        .unwrap();
-
-        assert_eq!(rows.len(), 3);
-        assert_eq!(rows[0], (Some("First Song".to_string()), Some(1)));
-        assert_eq!(rows[1], (Some("Second Song".to_string()), Some(2)));
-        assert_eq!(
-            rows[2].1, None,
-            "unnumbered track must have track_number=NULL"
-        );
-
-        // catalog_stream must return one Track per file.
         let catalog: Vec<db::Media> = addon
             .catalog_stream(ctx, "files")
             .await
@@ -3623,32 +6185,71 @@ mod tests {
             .unwrap()
             .collect()
             .await;
-        assert_eq!(catalog.len(), 3);
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(catalog[0].title, "Intro");
+
+        let streams = addon
+            .get_streams(&catalog[0], ctx, None)
+            .await
+            .unwrap();
+        assert_eq!(streams.len(), 1);
+        let StreamDescriptor::Opendal { path, .. } = &streams[0].descriptor else {
+            panic!("expected an Opendal descriptor");
+        };
+        assert_eq!(path, "albums/01.Intro.mp3");
+
+        // A range spanning more than one SMB read.
+        let mut headers = axum::http::HeaderMap::new();
+        headers.insert(
+            axum::http::header::RANGE,
+            axum::http::HeaderValue::from_static("bytes=60000-129999"),
+        );
+        let response = addon
+            .serve_stream(&streams[0].descriptor, &headers)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers()[axum::http::header::CONTENT_RANGE],
+            "bytes 60000-129999/200000"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], &audio[60_000..130_000]);
+    }
+
+    #[test]
+    fn remote_presets_require_their_connection_options() {
+        assert!(build_remote_operator("opendal-s3", &serde_json::json!({})).is_err());
         assert!(
-            catalog
-                .iter()
-                .all(|m| m.kind == db::MediaKind::Track)
+            build_remote_operator(
+                "opendal-s3",
+                &serde_json::json!({"bucket": "media", "access_key_id": "only-half"}),
+            )
+            .is_err()
         );
+        assert!(build_remote_operator("opendal-sftp", &serde_json::json!({})).is_err());
+        let (_, root) = build_remote_operator(
+            "opendal-sftp",
+            &serde_json::json!({"endpoint": "nas.local:2222", "username": "media"}),
+        )
+        .unwrap();
+        assert_eq!(root, "sftp://nas.local:2222");
 
-        // get_streams must return a Local stream for each track (matched by title).
-        for item in &catalog {
-            let streams = addon
-                .get_streams(item, ctx, None)
-                .await
-                .unwrap();
-            assert!(
-                !streams.is_empty(),
-                "get_streams empty for track {:?}",
-                item.title
-            );
-            assert!(
-                streams
-                    .iter()
-                    .all(|s| matches!(s.descriptor, StreamDescriptor::Local(_))),
-                "expected Local descriptor for track {:?}",
-                item.title
-            );
-        }
+        assert!(
+            build_remote_operator(
+                "opendal-smb",
+                &serde_json::json!({"endpoint": "nas.local"})
+            )
+            .is_err()
+        );
+        let (_, root) = build_remote_operator(
+            "opendal-smb",
+            &serde_json::json!({"endpoint": "nas.local", "share": "media"}),
+        )
+        .unwrap();
+        assert_eq!(root, "smb://nas.local/media");
     }
 
     // -----------------------------------------------------------------------
@@ -3764,6 +6365,150 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn opendal_scoped_refresh_only_touches_changed_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let matrix = "[imdbid-tt0133093] The Matrix (1999).mkv";
+        let alien = "Alien (1979)/[imdbid-tt0078748] Alien (1979).mkv";
+        write_files(
+            dir.path(),
+            &[
+                (matrix, b"fake"),
+                ("Heat (1995)/[imdbid-tt0113277] Heat (1995).mkv", b"fake"),
+                (alien, b"fake"),
+            ],
+        );
+
+        let (_, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+
+        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "movie").await;
+        addon
+            .refresh_index(ctx, &db_addon, noop_progress())
+            .await
+            .unwrap();
+
+        let indexed = || async {
+            let paths: Vec<String> = sqlx::query_scalar(
+                "SELECT path FROM opendal_files WHERE addon_id = ? ORDER BY path",
+            )
+            .bind(db_addon.id)
+            .fetch_all(&ctx.db)
+            .await
+            .unwrap();
+            paths
+                .iter()
+                .map(|p| {
+                    p.strip_prefix(
+                        dir.path()
+                            .to_str()
+                            .unwrap(),
+                    )
+                    .unwrap()
+                    .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            indexed()
+                .await
+                .len(),
+            3
+        );
+
+        // Alien goes unreported, so the scoped scan must leave its row alone.
+        std::fs::remove_file(
+            dir.path()
+                .join(matrix),
+        )
+        .unwrap();
+        std::fs::remove_file(
+            dir.path()
+                .join(alien),
+        )
+        .unwrap();
+        write_files(
+            dir.path(),
+            &[("Dune (2021)/[imdbid-tt1160419] Dune (2021).mkv", b"fake")],
+        );
+        refresh_paths(
+            ctx,
+            &db_addon,
+            &[
+                dir.path()
+                    .join(matrix),
+                dir.path()
+                    .join("Dune (2021)"),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            indexed().await,
+            vec![
+                "/Alien (1979)/[imdbid-tt0078748] Alien (1979).mkv",
+                "/Dune (2021)/[imdbid-tt1160419] Dune (2021).mkv",
+                "/Heat (1995)/[imdbid-tt0113277] Heat (1995).mkv",
+            ]
+        );
+
+        // Paths outside the addon's roots change nothing.
+        refresh_paths(ctx, &db_addon, &["/elsewhere/file.mkv".into()])
+            .await
+            .unwrap();
+        assert_eq!(
+            indexed()
+                .await
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn scoped_listings_cover_top_level_folders_and_loose_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_files(dir.path(), &[("Show/S01/e1.mkv", b""), ("loose.mkv", b"")]);
+        let root = dir
+            .path()
+            .to_str()
+            .unwrap();
+
+        let listings = scoped_listings(
+            root,
+            &[
+                dir.path()
+                    .join("Show/S01/e1.mkv"),
+                dir.path()
+                    .join("loose.mkv"),
+                dir.path()
+                    .join("Gone"),
+                "/elsewhere/x.mkv".into(),
+            ],
+        );
+        assert_eq!(
+            listings,
+            [
+                ("/".to_string(), false),
+                ("Gone/".to_string(), true),
+                ("Show/".to_string(), true),
+            ]
+            .into()
+        );
+
+        assert_eq!(
+            scoped_listings(
+                root,
+                &[dir
+                    .path()
+                    .to_path_buf()]
+            ),
+            [("/".to_string(), true)].into()
+        );
+    }
+
     // ---------------------------------------------------------------------------
     // Regression: episode files whose filename starts with "S01E07 - Episode Title"
     // must store the *series* name (from the parent directory) as their title,
@@ -3834,4 +6579,203 @@ mod tests {
             "title must contain the series name from the directory; got: {title:?}"
         );
     }
+
+    // -----------------------------------------------------------------------
+    // E2E: NFO sidecars and local artwork
+    // -----------------------------------------------------------------------
+
+    #[tokio::test]
+    async fn opendal_movie_nfo_and_artwork() {
+        let nfo = br#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<movie>
+  <title>Heat</title>
+  <plot>A group of professional bank robbers.</plot>
+  <rating>8.3</rating>
+  <year>1995</year>
+  <uniqueid type="imdb" default="true">tt0113277</uniqueid>
+</movie>"#;
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                // Nothing in the names identifies the film; the NFO does.
+                ("heat-rip/heat-rip.mkv", b"fake"),
+                ("heat-rip/movie.nfo", nfo),
+                ("heat-rip/poster.jpg", b"jpg"),
+                ("heat-rip/fanart.jpg", b"jpg"),
+                // A shared folder: only `{stem}-poster` belongs to a film.
+                ("Shared/Alien (1979) [imdbid-tt0078748].mkv", b"fake"),
+                ("Shared/Alien (1979) [imdbid-tt0078748]-poster.jpg", b"jpg"),
+                ("Shared/Aliens (1986) [imdbid-tt0090605].mkv", b"fake"),
+                ("Shared/poster.jpg", b"jpg"),
+            ],
+        );
+
+        let (_, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+
+        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "movie").await;
+        addon
+            .refresh_index(ctx, &db_addon, noop_progress())
+            .await
+            .unwrap();
+
+        let items: Vec<db::Media> = addon
+            .catalog_stream(ctx, "files")
+            .await
+            .unwrap()
+            .unwrap()
+            .collect()
+            .await;
+        let by_imdb = |imdb: &str| {
+            items
+                .iter()
+                .find(|m| {
+                    m.external_ids
+                        .imdb
+                        .as_deref()
+                        .map(|s| s.as_str())
+                        == Some(imdb)
+                })
+                .unwrap_or_else(|| panic!("{imdb} missing from catalog"))
+        };
+        let local = |rel: &str| {
+            dir.path()
+                .join(rel)
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        let heat = by_imdb("tt0113277");
+        assert_eq!(heat.title, "Heat");
+        assert_eq!(
+            heat.description
+                .as_deref(),
+            Some("A group of professional bank robbers.")
+        );
+        assert_eq!(heat.rating_audience, Some(8.3));
+        assert_eq!(
+            heat.images
+                .get_path(db::ImageKind::Primary),
+            Some(local("heat-rip/poster.jpg").as_str())
+        );
+        assert_eq!(
+            heat.images
+                .get_path(db::ImageKind::Backdrop),
+            Some(local("heat-rip/fanart.jpg").as_str())
+        );
+
+        let alien = by_imdb("tt0078748");
+        assert_eq!(
+            alien
+                .images
+                .get_path(db::ImageKind::Primary),
+            Some(local("Shared/Alien (1979) [imdbid-tt0078748]-poster.jpg").as_str())
+        );
+        let aliens = by_imdb("tt0090605");
+        assert!(
+            aliens
+                .images
+                .is_empty(),
+            "a shared folder's poster.jpg must not be claimed by one film"
+        );
+    }
+
+    #[tokio::test]
+    async fn opendal_series_nfo_and_artwork() {
+        let tvshow = b"<tvshow><title>Bleach</title><plot>Soul Reapers.</plot>\
+                       <uniqueid type=\"imdb\">tt0434665</uniqueid></tvshow>";
+        let episode = b"<episodedetails><title>The Day I Became a Shinigami</title>\
+                        <season>1</season><episode>1</episode>\
+                        <plot>Ichigo meets Rukia.</plot></episodedetails>";
+        let dir = tempfile::tempdir().unwrap();
+        write_files(
+            dir.path(),
+            &[
+                ("Show/tvshow.nfo", tvshow),
+                ("Show/poster.jpg", b"jpg"),
+                ("Show/season01-poster.jpg", b"jpg"),
+                ("Show/Season 01/pilot.mkv", b"fake"),
+                ("Show/Season 01/pilot.nfo", episode),
+                ("Show/Season 01/pilot-thumb.jpg", b"jpg"),
+            ],
+        );
+
+        let (_, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+
+        let (addon, db_addon) = make_local_addon(ctx, dir.path(), "episode").await;
+        addon
+            .refresh_index(ctx, &db_addon, noop_progress())
+            .await
+            .unwrap();
+        let local = |rel: &str| {
+            dir.path()
+                .join(rel)
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        let series: Vec<db::Media> = addon
+            .catalog_stream(ctx, "files")
+            .await
+            .unwrap()
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].title, "Bleach");
+        assert_eq!(
+            series[0]
+                .external_ids
+                .imdb
+                .as_deref()
+                .map(|s| s.as_str()),
+            Some("tt0434665")
+        );
+        assert_eq!(
+            series[0]
+                .images
+                .get_path(db::ImageKind::Primary),
+            Some(local("Show/poster.jpg").as_str())
+        );
+
+        let seasons = addon
+            .get_children(&series[0], ctx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(seasons.len(), 1);
+        assert_eq!(
+            seasons[0]
+                .images
+                .get_path(db::ImageKind::Primary),
+            Some(local("Show/season01-poster.jpg").as_str())
+        );
+
+        let episodes = addon
+            .get_children(&seasons[0], ctx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(episodes.len(), 1);
+        assert_eq!(episodes[0].idx, Some(1));
+        assert_eq!(episodes[0].title, "The Day I Became a Shinigami");
+        assert_eq!(
+            episodes[0]
+                .description
+                .as_deref(),
+            Some("Ichigo meets Rukia.")
+        );
+        assert_eq!(
+            episodes[0]
+                .images
+                .get_path(db::ImageKind::Primary),
+            Some(local("Show/Season 01/pilot-thumb.jpg").as_str())
+        );
+    }
 }
@@ -0,0 +1,301 @@
+//! Filesystem watcher for local opendal libraries.
+//!
+//! Every enabled `opendal-local` addon with `watch` on gets a recursive
+//! watch (inotify on Linux) on its roots. Changes are debounced per addon,
+//! then only the folders holding them are re-indexed and the addon's
+//! catalogs re-imported, so a new episode shows up without a full library
+//! refresh. Network mounts often report nothing; the scheduled refresh still
+//! covers those.
+
+use anyhow::Result;
+use notify::{
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    event::{AccessKind, AccessMode, ModifyKind},
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc,
+    task::JoinHandle,
+    time::{Instant, MissedTickBehavior},
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use super::{AddonRuntime, opendal};
+use crate::{AppContext, common::ProgressReporter, db, tasks, ws};
+use remux_sdks::stremio::ResourceType;
+
+/// Quiet time after the last change before an addon is re-indexed.
+pub const DEBOUNCE: Duration = Duration::from_secs(2);
+/// Upper bound on how long a steady stream of changes can hold indexing off.
+pub const MAX_DELAY: Duration = Duration::from_secs(30);
+/// How often watches are matched against the configured addons.
+pub const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+struct Watch {
+    roots: Vec<String>,
+    _watcher: RecommendedWatcher,
+}
+
+#[derive(Default)]
+struct Pending {
+    paths: BTreeSet<PathBuf>,
+    first: Option<Instant>,
+    last: Option<Instant>,
+}
+
+impl Pending {
+    fn push(&mut self, path: PathBuf, now: Instant) {
+        self.paths
+            .insert(path);
+        self.first
+            .get_or_insert(now);
+        self.last = Some(now);
+    }
+
+    fn due(&self) -> Option<Instant> {
+        Some((self.last? + DEBOUNCE).min(self.first? + MAX_DELAY))
+    }
+}
+
+pub fn spawn(ctx: AppContext) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let (tx, mut rx) = mpsc::unbounded_channel::<PathBuf>();
+        let mut watches: HashMap<Uuid, Watch> = HashMap::new();
+        let mut pending: HashMap<Uuid, Pending> = HashMap::new();
+        let mut reconcile = tokio::time::interval(RECONCILE_INTERVAL);
+        reconcile.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            let due = pending
+                .values()
+                .filter_map(Pending::due)
+                .min();
+            tokio::select! {
+                _ = reconcile.tick() => {
+                    reconcile_watches(&ctx, &mut watches, &tx).await;
+                    pending.retain(|id, _| watches.contains_key(id));
+                }
+                Some(path) = rx.recv() => {
+                    let now = Instant::now();
+                    for (id, watch) in &watches {
+                        if watch
+                            .roots
+                            .iter()
+                            .any(|root| path.starts_with(root))
+                        {
+                            pending
+                                .entry(*id)
+                                .or_default()
+                                .push(path.clone(), now);
+                        }
+                    }
+                }
+                _ = tokio::time::sleep_until(due.unwrap_or_else(Instant::now)), if due.is_some() => {
+                    let now = Instant::now();
+                    let ready: Vec<Uuid> = pending
+                        .iter()
+                        .filter(|(_, p)| p.due().is_some_and(|due| due <= now))
+                        .map(|(id, _)| *id)
+                        .collect();
+                    for id in ready {
+                        let Some(changes) = pending.remove(&id) else {
+                            continue;
+                        };
+                        let Some(runtime) = ctx.addons.get(id) else {
+                            continue;
+                        };
+                        let changed: Vec<PathBuf> = changes
+                            .paths
+                            .into_iter()
+                            .collect();
+                        if let Err(e) = index_changes(&ctx, &runtime, &changed).await {
+                            warn!(addon = %runtime.row.name, "opendal watch: indexing changes failed: {e:#}");
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Watches the roots of every local addon that wants it, replacing watches
+/// whose roots changed and dropping those of removed or disabled addons.
+async fn reconcile_watches(
+    ctx: &AppContext,
+    watches: &mut HashMap<Uuid, Watch>,
+    tx: &mpsc::UnboundedSender<PathBuf>,
+) {
+    let wanted: HashMap<Uuid, Vec<String>> = ctx
+        .addons
+        .list()
+        .iter()
+        .filter(|r| {
+            r.row
+                .enabled
+                && r.row
+                    .preset
+                    .kind
+                    == "opendal-local"
+        })
+        .filter_map(|r| {
+            let cfg = r
+                .row
+                .preset
+                .config
+                .expose();
+            if !opendal::cfg_watch(cfg) {
+                return None;
+            }
+            Some((
+                r.row
+                    .id,
+                opendal::cfg_paths_local(cfg).ok()?,
+            ))
+        })
+        .collect();
+
+    watches.retain(|id, watch| wanted.get(id) == Some(&watch.roots));
+    for (id, roots) in wanted {
+        if watches.contains_key(&id) {
+            continue;
+        }
+        // Recursive inotify watches are added folder by folder, which takes
+        // a while on large trees.
+        let tx = tx.clone();
+        let watched_roots = roots.clone();
+        match tokio::task::spawn_blocking(move || watch_roots(&watched_roots, tx)).await
+        {
+            Ok(Ok(watcher)) => {
+                info!(addon = %id, roots = ?roots, "opendal watch: watching");
+                watches.insert(
+                    id,
+                    Watch {
+                        roots,
+                        _watcher: watcher,
+                    },
+                );
+            }
+            Ok(Err(e)) => warn!(addon = %id, "opendal watch: failed to watch: {e:#}"),
+            Err(e) => warn!(addon = %id, "opendal watch: failed to watch: {e}"),
+        }
+    }
+}
+
+fn watch_roots(
+    roots: &[String],
+    tx: mpsc::UnboundedSender<PathBuf>,
+) -> Result<RecommendedWatcher> {
+    let rescan_roots: Vec<PathBuf> = roots
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    let mut watcher =
+        notify::recommended_watcher(move |res: notify::Result<Event>| {
+            match res {
+                // Events were dropped (e.g. the inotify queue overflowed):
+                // a root path re-indexes that whole root.
+                Ok(event) if event.need_rescan() => {
+                    for root in &rescan_roots {
+                        let _ = tx.send(root.clone());
+                    }
+                }
+                Ok(event) if is_relevant(&event.kind) => {
+                    for path in event.paths {
+                        let _ = tx.send(path);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => debug!("opendal watch: {e}"),
+            }
+        })?;
+    for root in roots {
+        if let Err(e) = watcher.watch(Path::new(root), RecursiveMode::Recursive) {
+            warn!(root, "opendal watch: failed to watch path: {e}");
+        }
+    }
+    Ok(watcher)
+}
+
+/// Files appearing, disappearing or being renamed, plus writes being
+/// finished so a file copied in is indexed once complete.
+fn is_relevant(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_)
+            | EventKind::Remove(_)
+            | EventKind::Modify(ModifyKind::Name(_))
+            | EventKind::Access(AccessKind::Close(AccessMode::Write))
+    )
+}
+
+/// Re-indexes the folders holding `changed`, re-imports the addon's enabled
+/// catalogs and tells clients the library changed.
+async fn index_changes(
+    ctx: &AppContext,
+    runtime: &AddonRuntime,
+    changed: &[PathBuf],
+) -> Result<()> {
+    debug!(addon = %runtime.row.name, count = changed.len(), "opendal watch: indexing changes");
+    opendal::refresh_paths(ctx, &runtime.row, changed).await?;
+
+    if runtime
+        .row
+        .resources
+        .contains(&ResourceType::Catalog)
+    {
+        let global_max = db::Settings::get_config_or_default(&ctx.db)
+            .await
+            .catalog_max_items
+            .unwrap_or(250) as usize;
+        let catalogs = runtime
+            .resolve_catalogs(ctx)
+            .await?;
+        let enabled: Vec<_> = catalogs
+            .iter()
+            .filter(|cat_info| cat_info.enabled)
+            .collect();
+        tasks::import_catalogs(
+            ctx,
+            &enabled,
+            global_max,
+            &ProgressReporter::new(Default::default()),
+        )
+        .await?;
+    }
+
+    let _ = ctx
+        .ws_tx
+        .send(ws::WsEvent::LibraryChanged);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_changes_wait_for_quiet_but_not_forever() {
+        let start = Instant::now();
+        let mut pending = Pending::default();
+        assert_eq!(pending.due(), None);
+
+        pending.push("/media/a.mkv".into(), start);
+        assert_eq!(pending.due(), Some(start + DEBOUNCE));
+
+        // Each change pushes indexing back, up to MAX_DELAY after the first.
+        let later = start + MAX_DELAY - Duration::from_secs(1);
+        pending.push("/media/b.mkv".into(), later);
+        assert_eq!(pending.due(), Some(start + MAX_DELAY));
+        assert_eq!(
+            pending
+                .paths
+                .len(),
+            2
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use std::{sync::Arc, time::Duration};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
@@ -8,8 +10,31 @@ use super::{
     AddonCapabilities, AddonKind, AddonMetadata, AddonOption, AddonOptionType,
     AddonPreset, AddonPresetRegistration, MediaKind, MetricSnapshot, MetricValue,
     MetricsAddon, MetricsCtx, ResourceType,
+    media_tracker::{
+        AuthFlow, DeviceAuthPoll, DeviceAuthStart, MediaTrackerAddon,
+        MediaTrackerCapabilities, MediaTrackerCredentials, MediaTrackerCtx,
+        MediaTrackerError, MediaTrackerEvent, MediaTrackerEventKind,
+        MediaTrackerResult, MediaTrackerTarget, RemoteWatch, SyncDirection,
+    },
 };
-use crate::{db, sdks};
+use crate::{
+    db,
+    sdks::{
+        self,
+        trakt::{ScrobbleAction, SyncList},
+    },
+};
+
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+/// Renew an access token once it has less than this left, so a delivery never
+/// races the expiry.
+const REFRESH_MARGIN_SECS: i64 = 3600;
+
+/// History page size. Trakt caps pages, so a full sweep walks them.
+const HISTORY_PAGE: u32 = 100;
+/// Stops a runaway history walk; anything older arrives with the next sweep.
+const MAX_HISTORY_PAGES: u32 = 50;
 
 pub struct TraktPreset;
 
@@ -22,23 +47,42 @@ impl AddonPreset for TraktPreset {
         AddonMetadata {
             id: "trakt".to_string(),
             display_name: "Trakt".to_string(),
-            description: "Trakt — crowd-sourced ratings for movies and shows.".to_string(),
+            description: "Trakt — crowd-sourced ratings for movies and shows, and \
+                          per-user scrobbling and watch history sync."
+                .to_string(),
             icon: None,
-            supported_resources: vec![AddonMetadata::simple_resource(ResourceType::Metrics)],
+            supported_resources: vec![
+                AddonMetadata::simple_resource(ResourceType::Metrics),
+                AddonMetadata::simple_resource(ResourceType::Tracking),
+            ],
             supported_types: vec![MediaKind::Movie, MediaKind::Series],
             supported_resources_user: vec![ResourceType::Metrics],
             supported_types_user: vec![MediaKind::Movie, MediaKind::Series],
-            options: vec![AddonOption {
-                id: "client_id".to_string(),
-                name: "Trakt Client ID".to_string(),
-                description: Some(
-                    "Your Trakt application Client ID. Register a free app at trakt.tv/oauth/applications."
-                        .to_string(),
-                ),
-                required: false,
-                default: None,
-                kind: AddonOptionType::Password,
-            }],
+            options: vec![
+                AddonOption {
+                    id: "client_id".to_string(),
+                    name: "Trakt Client ID".to_string(),
+                    description: Some(
+                        "Your Trakt application Client ID. Register a free app at trakt.tv/oauth/applications."
+                            .to_string(),
+                    ),
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::Password,
+                },
+                AddonOption {
+                    id: "client_secret".to_string(),
+                    name: "Trakt Client Secret".to_string(),
+                    description: Some(
+                        "The same application's Client Secret. Needed for users to connect \
+                         their Trakt accounts; ratings work without it."
+                            .to_string(),
+                    ),
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::Password,
+                },
+            ],
         }
     }
 
@@ -48,17 +92,30 @@ impl AddonPreset for TraktPreset {
         cfg: &serde_json::Value,
         _config: &crate::Config,
     ) -> Result<AddonCapabilities> {
-        let client_id = cfg
-            .get("client_id")
-            .and_then(|v| v.as_str())
-            .map(str::trim)
-            .filter(|s| !s.is_empty())
-            .map(str::to_string);
+        let field = |key: &str| {
+            cfg.get(key)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        let addon = Arc::new(TraktAddon {
+            client_id: field("client_id"),
+            client_secret: field("client_secret"),
+            cache: Mutex::new(None),
+        });
+        // Connecting a user takes both halves of the app's credentials, so
+        // without them there is nothing to offer.
+        let media_tracker: Option<Arc<dyn MediaTrackerAddon>> = (addon
+            .client_id
+            .is_some()
+            && addon
+                .client_secret
+                .is_some())
+        .then(|| addon.clone() as Arc<dyn MediaTrackerAddon>);
         Ok(AddonCapabilities {
-            metrics: Some(Arc::new(TraktAddon {
-                client_id,
-                cache: Mutex::new(None),
-            })),
+            metrics: Some(addon),
+            media_tracker,
             ..Default::default()
         })
     }
@@ -70,6 +127,7 @@ inventory::submit! {
 
 pub struct TraktAddon {
     client_id: Option<String>,
+    client_secret: Option<String>,
     // (date, movie_ceiling, show_ceiling) — refreshed daily from #1 popular item stats
     cache: Mutex<Option<(chrono::NaiveDate, f64, f64)>>,
 }
@@ -253,3 +311,1491 @@ impl MetricsAddon for TraktAddon {
         }))
     }
 }
+
+// ---------------------------------------------------------------------------
+// Media tracker
+// ---------------------------------------------------------------------------
+
+/// What a connection's credentials hold for Trakt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TraktCredentials {
+    access_token: String,
+    refresh_token: String,
+    /// Unix seconds.
+    expires_at: i64,
+}
+
+impl TraktCredentials {
+    fn read(creds: &MediaTrackerCredentials) -> MediaTrackerResult<Self> {
+        serde_json::from_value(
+            creds
+                .expose()
+                .clone(),
+        )
+        .map_err(|_| {
+            MediaTrackerError::reauth("stored Trakt credentials are unreadable")
+        })
+    }
+
+    fn into_credentials(self) -> MediaTrackerCredentials {
+        MediaTrackerCredentials::new(serde_json::json!({
+            "access_token": self.access_token,
+            "refresh_token": self.refresh_token,
+            "expires_at": self.expires_at,
+        }))
+    }
+}
+
+impl From<sdks::trakt::TraktToken> for TraktCredentials {
+    fn from(token: sdks::trakt::TraktToken) -> Self {
+        Self {
+            expires_at: token.expires_at(),
+            access_token: token.access_token,
+            refresh_token: token.refresh_token,
+        }
+    }
+}
+
+/// Sorts a Trakt failure into what the dispatcher should do with it.
+fn tracker_error(err: sdks::ClientError) -> MediaTrackerError {
+    match err {
+        sdks::ClientError::Unauthorized => {
+            MediaTrackerError::reauth("Trakt rejected the access token")
+        }
+        sdks::ClientError::RateLimited { retry_after_secs } => {
+            MediaTrackerError::retry_after(
+                "Trakt rate limit",
+                Duration::from_secs(retry_after_secs),
+            )
+        }
+        sdks::ClientError::Http { status, .. } if status >= 500 => {
+            MediaTrackerError::retryable(format!("Trakt returned {status}"))
+        }
+        sdks::ClientError::Http { status: 404, .. } => {
+            MediaTrackerError::permanent("Trakt has no match for this item")
+        }
+        sdks::ClientError::Http { status, .. } => {
+            MediaTrackerError::permanent(format!("Trakt returned {status}"))
+        }
+        sdks::ClientError::Transport(e) => {
+            MediaTrackerError::retryable(format!("reaching Trakt: {e}"))
+        }
+        other => MediaTrackerError::permanent(other.to_string()),
+    }
+}
+
+/// `None` when nothing here is an id Trakt understands.
+fn trakt_ids(ids: &db::ExternalIds) -> Option<sdks::trakt::TraktItemIds> {
+    let out = sdks::trakt::TraktItemIds {
+        trakt: None,
+        imdb: ids
+            .imdb
+            .as_ref()
+            .map(|s| s.to_string()),
+        tmdb: ids.tmdb,
+        tvdb: ids.tvdb,
+    };
+    (out.imdb
+        .is_some()
+        || out
+            .tmdb
+            .is_some()
+        || out
+            .tvdb
+            .is_some())
+    .then_some(out)
+}
+
+fn external_ids(ids: &sdks::trakt::TraktItemIds) -> db::ExternalIds {
+    db::ExternalIds {
+        imdb: ids
+            .imdb
+            .clone()
+            .and_then(|s| db::NonEmptyString::try_new(s).ok()),
+        tmdb: ids.tmdb,
+        tvdb: ids.tvdb,
+        ..Default::default()
+    }
+}
+
+fn unmatchable() -> MediaTrackerError {
+    MediaTrackerError::permanent("no id Trakt could match this item on")
+}
+
+/// An episode is keyed on its show plus numbers when the show has ids, which
+/// is the form every Trakt endpoint accepts.
+fn episode_on_show(
+    target: &MediaTrackerTarget,
+) -> Option<(sdks::trakt::TraktItemIds, i64, i64)> {
+    let show = trakt_ids(
+        &target
+            .series
+            .as_ref()?
+            .ids,
+    )?;
+    Some((show, target.season?, target.episode?))
+}
+
+/// The request body naming `target` for the `sync/*` endpoints.
+fn sync_items(
+    target: &MediaTrackerTarget,
+    watched_at: Option<DateTime<Utc>>,
+    rating: Option<i64>,
+) -> MediaTrackerResult<sdks::trakt::SyncItems> {
+    use sdks::trakt::{SyncEntry, SyncEpisodeNumber, SyncItems, SyncSeason, SyncShow};
+
+    let entry = |ids| SyncEntry {
+        ids,
+        watched_at,
+        rating,
+    };
+    match target.kind {
+        db::MediaKind::Movie => Ok(SyncItems {
+            movies: vec![entry(trakt_ids(&target.ids).ok_or_else(unmatchable)?)],
+            ..Default::default()
+        }),
+        db::MediaKind::Series => Ok(SyncItems {
+            shows: vec![SyncShow {
+                ids: trakt_ids(&target.ids).ok_or_else(unmatchable)?,
+                seasons: Vec::new(),
+                watched_at,
+                rating,
+            }],
+            ..Default::default()
+        }),
+        db::MediaKind::Episode => match episode_on_show(target) {
+            Some((show, season, number)) => Ok(SyncItems {
+                shows: vec![SyncShow {
+                    ids: show,
+                    seasons: vec![SyncSeason {
+                        number: season,
+                        episodes: vec![SyncEpisodeNumber {
+                            number,
+                            watched_at,
+                            rating,
+                        }],
+                    }],
+                    watched_at: None,
+                    rating: None,
+                }],
+                ..Default::default()
+            }),
+            None => Ok(SyncItems {
+                episodes: vec![entry(trakt_ids(&target.ids).ok_or_else(unmatchable)?)],
+                ..Default::default()
+            }),
+        },
+        _ => Err(MediaTrackerError::permanent(
+            "Trakt only tracks movies and episodes",
+        )),
+    }
+}
+
+fn scrobble_body(
+    target: &MediaTrackerTarget,
+    progress: f64,
+) -> MediaTrackerResult<sdks::trakt::ScrobbleBody> {
+    use sdks::trakt::{ScrobbleBody, ScrobbleEpisode, ScrobbleRef};
+
+    match target.kind {
+        db::MediaKind::Movie => Ok(ScrobbleBody {
+            movie: Some(ScrobbleRef {
+                ids: trakt_ids(&target.ids).ok_or_else(unmatchable)?,
+            }),
+            progress,
+            ..Default::default()
+        }),
+        db::MediaKind::Episode => match episode_on_show(target) {
+            Some((show, season, number)) => Ok(ScrobbleBody {
+                show: Some(ScrobbleRef { ids: show }),
+                episode: Some(ScrobbleEpisode {
+                    season: Some(season),
+                    number: Some(number),
+                    ids: None,
+                }),
+                progress,
+                ..Default::default()
+            }),
+            None => Ok(ScrobbleBody {
+                episode: Some(ScrobbleEpisode {
+                    ids: Some(trakt_ids(&target.ids).ok_or_else(unmatchable)?),
+                    ..Default::default()
+                }),
+                progress,
+                ..Default::default()
+            }),
+        },
+        _ => Err(MediaTrackerError::permanent(
+            "Trakt only scrobbles movies and episodes",
+        )),
+    }
+}
+
+/// Percent of `runtime` (seconds) reached at `position_ticks`.
+fn percent(position_ticks: i64, runtime: Option<i64>) -> Option<f64> {
+    let runtime = runtime.filter(|r| *r > 0)?;
+    let secs = position_ticks as f64 / TICKS_PER_SECOND as f64;
+    Some((secs / runtime as f64 * 100.0).clamp(0.0, 100.0))
+}
+
+/// Trakt's 1-10 integer scale from ours.
+fn trakt_rating(rating: f32) -> i64 {
+    (rating.round() as i64).clamp(1, 10)
+}
+
+type WatchKey = (
+    &'static str,
+    sdks::trakt::TraktItemIds,
+    Option<i64>,
+    Option<i64>,
+);
+
+/// Trakt reports watched state, progress and ratings in separate listings.
+/// This folds them into one `RemoteWatch` per item, so a rated, half-watched
+/// episode reaches core once.
+#[derive(Default)]
+struct Merged(HashMap<WatchKey, RemoteWatch>);
+
+impl Merged {
+    fn entry(
+        &mut self,
+        kind: &'static str,
+        ids: &sdks::trakt::TraktItemIds,
+        season: Option<i64>,
+        episode: Option<i64>,
+    ) -> &mut RemoteWatch {
+        self.0
+            .entry((kind, ids.clone(), season, episode))
+            .or_insert_with(|| RemoteWatch {
+                ids: external_ids(ids),
+                title: None,
+                season,
+                episode,
+                watched: false,
+                position_ticks: None,
+                watched_at: None,
+                favorite: None,
+                rating: None,
+            })
+    }
+
+    /// The entry for a history, playback, rating or watchlist row. Episodes
+    /// land on their show's ids, the same way a target names them.
+    fn item(&mut self, item: &sdks::trakt::SyncedItem) -> Option<&mut RemoteWatch> {
+        match item
+            .kind
+            .as_str()
+        {
+            "movie" => {
+                let movie = item
+                    .movie
+                    .as_ref()?;
+                Some(self.entry("movie", &movie.ids, None, None))
+            }
+            "episode" => {
+                let show = item
+                    .show
+                    .as_ref()?;
+                let episode = item
+                    .episode
+                    .as_ref()?;
+                Some(self.entry(
+                    "show",
+                    &show.ids,
+                    Some(episode.season),
+                    Some(episode.number),
+                ))
+            }
+            "show" => {
+                let show = item
+                    .show
+                    .as_ref()?;
+                Some(self.entry("show", &show.ids, None, None))
+            }
+            // Seasons and lists name nothing core tracks.
+            _ => None,
+        }
+    }
+
+    fn watched(&mut self, item: &sdks::trakt::SyncedItem) {
+        if let Some(watch) = self.item(item) {
+            watch.watched = true;
+            let at = item
+                .watched_at
+                .map(|t| t.naive_utc());
+            // History is newest first, but keep the latest regardless.
+            if at > watch.watched_at {
+                watch.watched_at = at;
+            }
+        }
+    }
+
+    fn progress(&mut self, item: &sdks::trakt::SyncedItem) {
+        let runtime_minutes = item
+            .episode
+            .as_ref()
+            .and_then(|e| e.runtime)
+            .or_else(|| {
+                item.movie
+                    .as_ref()
+                    .and_then(|m| m.runtime)
+            });
+        let (Some(progress), Some(minutes)) = (item.progress, runtime_minutes) else {
+            return;
+        };
+        let ticks =
+            (progress / 100.0 * (minutes * 60) as f64) as i64 * TICKS_PER_SECOND;
+        if let Some(watch) = self.item(item) {
+            watch.position_ticks = Some(ticks);
+        }
+    }
+
+    fn rating(&mut self, item: &sdks::trakt::SyncedItem) {
+        let Some(rating) = item.rating else {
+            return;
+        };
+        if let Some(watch) = self.item(item) {
+            watch.rating = Some(rating as f32);
+        }
+    }
+
+    fn into_vec(self) -> Vec<RemoteWatch> {
+        self.0
+            .into_values()
+            .collect()
+    }
+}
+
+impl TraktAddon {
+    /// The app's client id and secret. The preset only offers the media
+    /// tracker with both, so this failing means the config changed under it.
+    fn app(&self) -> MediaTrackerResult<(&str, &str)> {
+        match (
+            self.client_id
+                .as_deref(),
+            self.client_secret
+                .as_deref(),
+        ) {
+            (Some(id), Some(secret)) => Ok((id, secret)),
+            _ => Err(MediaTrackerError::permanent(
+                "Trakt client id and secret are not configured",
+            )),
+        }
+    }
+
+    fn app_client(
+        &self,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<sdks::RestClient<sdks::trakt::TraktAuth>> {
+        let (client_id, _) = self.app()?;
+        sdks::trakt::trakt_client(
+            client_id,
+            &ctx.config
+                .trakt_base_url,
+        )
+        .map_err(|e| MediaTrackerError::permanent(format!("bad Trakt base URL: {e}")))
+    }
+
+    fn user_client(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<sdks::RestClient<sdks::trakt::TraktAuth>> {
+        let (client_id, _) = self.app()?;
+        let creds = TraktCredentials::read(creds)?;
+        sdks::trakt::trakt_user_client(
+            client_id,
+            &creds.access_token,
+            &ctx.config
+                .trakt_base_url,
+        )
+        .map_err(|e| MediaTrackerError::permanent(format!("bad Trakt base URL: {e}")))
+    }
+
+    async fn scrobble(
+        &self,
+        client: &sdks::RestClient<sdks::trakt::TraktAuth>,
+        action: ScrobbleAction,
+        target: &MediaTrackerTarget,
+        progress: f64,
+    ) -> MediaTrackerResult<()> {
+        let body = scrobble_body(target, progress)?;
+        match client
+            .execute(sdks::trakt::ScrobbleEndpoint { action, body })
+            .await
+        {
+            Ok(_) => Ok(()),
+            // Trakt refuses a second stop for the same item within its window.
+            // The watch it is protecting is already recorded.
+            Err(sdks::ClientError::Http { status: 409, .. }) => Ok(()),
+            Err(e) => Err(tracker_error(e)),
+        }
+    }
+
+    async fn sync(
+        &self,
+        client: &sdks::RestClient<sdks::trakt::TraktAuth>,
+        list: SyncList,
+        remove: bool,
+        items: sdks::trakt::SyncItems,
+    ) -> MediaTrackerResult<()> {
+        let resp = client
+            .execute(sdks::trakt::SyncEndpoint {
+                list,
+                remove,
+                items,
+            })
+            .await
+            .map_err(tracker_error)?;
+        if resp.missed_any() {
+            return Err(MediaTrackerError::permanent(
+                "Trakt has no match for this item",
+            ));
+        }
+        Ok(())
+    }
+
+    async fn playback(
+        &self,
+        client: &sdks::RestClient<sdks::trakt::TraktAuth>,
+        start_at: Option<DateTime<Utc>>,
+        merged: &mut Merged,
+    ) -> MediaTrackerResult<()> {
+        for item in client
+            .execute(sdks::trakt::PlaybackEndpoint { start_at })
+            .await
+            .map_err(tracker_error)?
+        {
+            merged.progress(&item);
+        }
+        Ok(())
+    }
+
+    /// Ratings have no server-side filter, so `since` is applied here.
+    async fn ratings(
+        &self,
+        client: &sdks::RestClient<sdks::trakt::TraktAuth>,
+        since: Option<DateTime<Utc>>,
+        merged: &mut Merged,
+    ) -> MediaTrackerResult<()> {
+        for item in client
+            .execute(sdks::trakt::RatingsEndpoint)
+            .await
+            .map_err(tracker_error)?
+            .iter()
+            .filter(|i| since.is_none_or(|s| i.rated_at >= Some(s)))
+        {
+            merged.rating(item);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MediaTrackerAddon for TraktAddon {
+    fn capabilities(&self) -> MediaTrackerCapabilities {
+        MediaTrackerCapabilities {
+            auth_flow: AuthFlow::OAuthDeviceCode,
+            supported_events: vec![
+                MediaTrackerEventKind::PlaybackStart,
+                MediaTrackerEventKind::PlaybackProgress,
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::MarkPlayed,
+                MediaTrackerEventKind::MarkUnplayed,
+                MediaTrackerEventKind::Rating,
+            ],
+            // Shows are rated and synced whole, not only through episodes.
+            media_kinds: vec![
+                db::MediaKind::Movie,
+                db::MediaKind::Series,
+                db::MediaKind::Episode,
+            ],
+            // Progress only matters for pause and resume, and sending it on
+            // every report would spend the user's rate limit.
+            default_event_filter: vec![
+                MediaTrackerEventKind::PlaybackStart,
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::MarkPlayed,
+                MediaTrackerEventKind::MarkUnplayed,
+                MediaTrackerEventKind::Rating,
+            ],
+            history_import: true,
+            progress_import: true,
+            watch_state_sync: SyncDirection::Both,
+            ratings: SyncDirection::Both,
+            watchlist: SyncDirection::Both,
+            ..Default::default()
+        }
+    }
+
+    async fn begin_device_auth(
+        &self,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<DeviceAuthStart> {
+        let (client_id, _) = self.app()?;
+        let code = self
+            .app_client(ctx)?
+            .execute(sdks::trakt::DeviceCodeEndpoint {
+                client_id: client_id.to_string(),
+            })
+            .await
+            .map_err(tracker_error)?;
+        Ok(DeviceAuthStart {
+            verification_url: code.verification_url,
+            user_code: code.user_code,
+            poll_token: code.device_code,
+            interval: Duration::from_secs(code.interval),
+            expires_in: Duration::from_secs(code.expires_in),
+        })
+    }
+
+    async fn poll_device_auth(
+        &self,
+        poll_token: &str,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<DeviceAuthPoll> {
+        let (client_id, client_secret) = self.app()?;
+        let result = self
+            .app_client(ctx)?
+            .execute(sdks::trakt::DeviceTokenEndpoint {
+                device_code: poll_token.to_string(),
+                client_id: client_id.to_string(),
+                client_secret: client_secret.to_string(),
+            })
+            .await;
+        match result {
+            Ok(token) => Ok(DeviceAuthPoll::Approved(
+                TraktCredentials::from(token).into_credentials(),
+            )),
+            // 400: the user has not entered the code yet. 429: polled too fast.
+            Err(sdks::ClientError::Http { status: 400, .. })
+            | Err(sdks::ClientError::RateLimited { .. }) => Ok(DeviceAuthPoll::Pending),
+            // Invalid, already used, expired, or declined.
+            Err(sdks::ClientError::Http {
+                status: 404 | 409 | 410 | 418,
+                ..
+            }) => Ok(DeviceAuthPoll::Denied),
+            Err(e) => Err(tracker_error(e)),
+        }
+    }
+
+    async fn refresh(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<MediaTrackerCredentials> {
+        let current = TraktCredentials::read(creds)?;
+        if current.expires_at - Utc::now().timestamp() > REFRESH_MARGIN_SECS {
+            return Ok(creds.clone());
+        }
+        let (client_id, client_secret) = self.app()?;
+        let result = self
+            .app_client(ctx)?
+            .execute(sdks::trakt::RefreshTokenEndpoint {
+                refresh_token: current.refresh_token,
+                client_id: client_id.to_string(),
+                client_secret: client_secret.to_string(),
+            })
+            .await;
+        match result {
+            Ok(token) => Ok(TraktCredentials::from(token).into_credentials()),
+            // A refresh token that was revoked or already used.
+            Err(sdks::ClientError::Http { status: 400, .. }) => Err(
+                MediaTrackerError::reauth("Trakt refused to renew the access token"),
+            ),
+            Err(e) => Err(tracker_error(e)),
+        }
+    }
+
+    async fn verify(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        self.user_client(creds, ctx)?
+            .execute(sdks::trakt::UserSettingsEndpoint)
+            .await
+            .map_err(tracker_error)?;
+        Ok(())
+    }
+
+    async fn disconnect(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        let (client_id, client_secret) = self.app()?;
+        let current = TraktCredentials::read(creds)?;
+        self.app_client(ctx)?
+            .execute(sdks::trakt::RevokeTokenEndpoint {
+                token: current.access_token,
+                client_id: client_id.to_string(),
+                client_secret: client_secret.to_string(),
+            })
+            .await
+            .map_err(tracker_error)
+    }
+
+    async fn on_event(
+        &self,
+        event: &MediaTrackerEvent,
+        target: &MediaTrackerTarget,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        let client = self.user_client(creds, ctx)?;
+        match event {
+            MediaTrackerEvent::PlaybackStart { position_ticks } => {
+                let progress = percent(*position_ticks, target.runtime).unwrap_or(0.0);
+                self.scrobble(&client, ScrobbleAction::Start, target, progress)
+                    .await
+            }
+            MediaTrackerEvent::PlaybackProgress {
+                position_ticks,
+                is_paused,
+            } => {
+                // Trakt has no progress call: resuming is another start.
+                let action = if *is_paused {
+                    ScrobbleAction::Pause
+                } else {
+                    ScrobbleAction::Start
+                };
+                let progress = percent(*position_ticks, target.runtime).unwrap_or(0.0);
+                self.scrobble(&client, action, target, progress)
+                    .await
+            }
+            MediaTrackerEvent::PlaybackStop {
+                position_ticks,
+                played,
+            } => {
+                // A finish is sent as 100% so Trakt records the watch however
+                // far short of the end the client stopped. An abandon goes at
+                // its real position, which Trakt keeps as paused progress.
+                let progress = if *played {
+                    100.0
+                } else {
+                    match percent(*position_ticks, target.runtime) {
+                        Some(p) => p,
+                        // Without a runtime there is no position worth keeping.
+                        None => return Ok(()),
+                    }
+                };
+                self.scrobble(&client, ScrobbleAction::Stop, target, progress)
+                    .await
+            }
+            MediaTrackerEvent::MarkPlayed => {
+                self.sync(
+                    &client,
+                    SyncList::History,
+                    false,
+                    sync_items(target, Some(Utc::now()), None)?,
+                )
+                .await
+            }
+            MediaTrackerEvent::MarkUnplayed => {
+                self.sync(
+                    &client,
+                    SyncList::History,
+                    true,
+                    sync_items(target, None, None)?,
+                )
+                .await
+            }
+            MediaTrackerEvent::Rating {
+                rating: Some(rating),
+            } => {
+                self.sync(
+                    &client,
+                    SyncList::Ratings,
+                    false,
+                    sync_items(target, None, Some(trakt_rating(*rating)))?,
+                )
+                .await
+            }
+            MediaTrackerEvent::Rating { rating: None } => {
+                self.sync(
+                    &client,
+                    SyncList::Ratings,
+                    true,
+                    sync_items(target, None, None)?,
+                )
+                .await
+            }
+            MediaTrackerEvent::Favorite { .. } => {
+                Err(MediaTrackerError::unsupported("favorites"))
+            }
+        }
+    }
+
+    async fn import_history(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<Vec<RemoteWatch>> {
+        let client = self.user_client(creds, ctx)?;
+        let mut merged = Merged::default();
+
+        for watched in client
+            .execute(sdks::trakt::WatchedMoviesEndpoint)
+            .await
+            .map_err(tracker_error)?
+        {
+            let watch = merged.entry(
+                "movie",
+                &watched
+                    .movie
+                    .ids,
+                None,
+                None,
+            );
+            watch.watched = true;
+            watch.watched_at = watched
+                .last_watched_at
+                .map(|t| t.naive_utc());
+        }
+        for watched in client
+            .execute(sdks::trakt::WatchedShowsEndpoint)
+            .await
+            .map_err(tracker_error)?
+        {
+            for season in &watched.seasons {
+                for episode in &season.episodes {
+                    let watch = merged.entry(
+                        "show",
+                        &watched
+                            .show
+                            .ids,
+                        Some(season.number),
+                        Some(episode.number),
+                    );
+                    watch.watched = true;
+                    watch.watched_at = episode
+                        .last_watched_at
+                        .map(|t| t.naive_utc());
+                }
+            }
+        }
+        self.playback(&client, None, &mut merged)
+            .await?;
+        self.ratings(&client, None, &mut merged)
+            .await?;
+        Ok(merged.into_vec())
+    }
+
+    async fn pull_changes(
+        &self,
+        since: Option<chrono::NaiveDateTime>,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<Vec<RemoteWatch>> {
+        let Some(since) = since else {
+            return self
+                .import_history(creds, ctx)
+                .await;
+        };
+        let since = since.and_utc();
+        let client = self.user_client(creds, ctx)?;
+        let mut merged = Merged::default();
+
+        for page in 1..=MAX_HISTORY_PAGES {
+            let items = client
+                .execute(sdks::trakt::HistoryEndpoint {
+                    start_at: Some(since),
+                    page,
+                    limit: HISTORY_PAGE,
+                })
+                .await
+                .map_err(tracker_error)?;
+            for item in &items {
+                merged.watched(item);
+            }
+            if items.len() < HISTORY_PAGE as usize {
+                break;
+            }
+        }
+        self.playback(&client, Some(since), &mut merged)
+            .await?;
+        self.ratings(&client, Some(since), &mut merged)
+            .await?;
+        Ok(merged.into_vec())
+    }
+
+    async fn pull_watchlist(
+        &self,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<Vec<db::ExternalIds>> {
+        let items = self
+            .user_client(creds, ctx)?
+            .execute(sdks::trakt::WatchlistEndpoint)
+            .await
+            .map_err(tracker_error)?;
+        Ok(items
+            .iter()
+            .filter_map(|item| {
+                match item
+                    .kind
+                    .as_str()
+                {
+                    "movie" => item
+                        .movie
+                        .as_ref()
+                        .map(|m| external_ids(&m.ids)),
+                    "show" => item
+                        .show
+                        .as_ref()
+                        .map(|s| external_ids(&s.ids)),
+                    _ => None,
+                }
+            })
+            .collect())
+    }
+
+    async fn push_watchlist(
+        &self,
+        target: &MediaTrackerTarget,
+        add: bool,
+        creds: &MediaTrackerCredentials,
+        ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        let client = self.user_client(creds, ctx)?;
+        self.sync(
+            &client,
+            SyncList::Watchlist,
+            !add,
+            sync_items(target, None, None)?,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::{Method::POST, MockServer};
+
+    fn addon() -> TraktAddon {
+        TraktAddon {
+            client_id: Some("cid".into()),
+            client_secret: Some("secret".into()),
+            cache: Mutex::new(None),
+        }
+    }
+
+    fn ctx(server: &MockServer) -> MediaTrackerCtx {
+        MediaTrackerCtx {
+            config: Arc::new(crate::Config {
+                trakt_base_url: server.base_url(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Valid for a day, so `refresh` leaves it alone.
+    fn creds() -> MediaTrackerCredentials {
+        TraktCredentials {
+            access_token: "tok".into(),
+            refresh_token: "ref".into(),
+            expires_at: Utc::now().timestamp() + 86_400,
+        }
+        .into_credentials()
+    }
+
+    fn movie() -> MediaTrackerTarget {
+        MediaTrackerTarget {
+            kind: db::MediaKind::Movie,
+            title: "Heat".into(),
+            year: Some(1995),
+            ids: db::ExternalIds {
+                imdb: db::NonEmptyString::try_new("tt0113277".to_string()).ok(),
+                tmdb: Some(949),
+                ..Default::default()
+            },
+            series: None,
+            season: None,
+            episode: None,
+            runtime: Some(10_000),
+            artist: None,
+            album: None,
+        }
+    }
+
+    fn episode() -> MediaTrackerTarget {
+        MediaTrackerTarget {
+            kind: db::MediaKind::Episode,
+            title: "Pilot".into(),
+            year: None,
+            ids: db::ExternalIds::default(),
+            series: Some(Box::new(MediaTrackerTarget {
+                kind: db::MediaKind::Series,
+                title: "The Wire".into(),
+                year: Some(2002),
+                ids: db::ExternalIds {
+                    tvdb: Some(79126),
+                    ..Default::default()
+                },
+                series: None,
+                season: None,
+                episode: None,
+                runtime: None,
+                artist: None,
+                album: None,
+            })),
+            season: Some(1),
+            episode: Some(2),
+            runtime: Some(3600),
+            artist: None,
+            album: None,
+        }
+    }
+
+    #[test]
+    fn the_default_filter_only_names_events_trakt_takes() {
+        let caps = addon().capabilities();
+        assert!(
+            caps.default_event_filter
+                .iter()
+                .all(|k| caps.supports(*k))
+        );
+        assert!(!caps.supports(MediaTrackerEventKind::Favorite));
+    }
+
+    #[test]
+    fn series_events_are_delivered() {
+        let caps = addon().capabilities();
+        assert!(caps.supports_kind(&db::MediaKind::Series));
+        assert!(!caps.supports_kind(&db::MediaKind::Track));
+    }
+
+    #[test]
+    fn the_tracker_needs_both_halves_of_the_app_credentials() {
+        let config = crate::Config::default();
+        let caps = TraktPreset
+            .from_cfg(
+                Uuid::nil(),
+                &serde_json::json!({ "client_id": "cid" }),
+                &config,
+            )
+            .unwrap();
+        assert!(
+            caps.metrics
+                .is_some()
+        );
+        assert!(
+            caps.media_tracker
+                .is_none(),
+            "nobody could connect without the secret"
+        );
+
+        let caps = TraktPreset
+            .from_cfg(
+                Uuid::nil(),
+                &serde_json::json!({ "client_id": "cid", "client_secret": "s" }),
+                &config,
+            )
+            .unwrap();
+        assert!(
+            caps.media_tracker
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn device_auth_waits_for_the_user_then_yields_credentials() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/oauth/device/code")
+                .json_body_partial(r#"{"client_id":"cid"}"#);
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "device_code": "dev",
+                    "user_code": "ABCD1234",
+                    "verification_url": "https://trakt.tv/activate",
+                    "expires_in": 600,
+                    "interval": 5
+                }));
+        });
+        let mut pending = server.mock(|when, then| {
+            when.method(POST)
+                .path("/oauth/device/token")
+                .json_body_partial(r#"{"code":"dev","client_secret":"secret"}"#);
+            then.status(400);
+        });
+
+        let tracker = addon();
+        let start = tracker
+            .begin_device_auth(&ctx(&server))
+            .await
+            .unwrap();
+        assert_eq!(start.user_code, "ABCD1234");
+        assert_eq!(start.interval, Duration::from_secs(5));
+
+        let poll = tracker
+            .poll_device_auth(&start.poll_token, &ctx(&server))
+            .await
+            .unwrap();
+        assert!(matches!(poll, DeviceAuthPoll::Pending));
+
+        pending.delete();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/oauth/device/token");
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "access_token": "tok",
+                    "refresh_token": "ref",
+                    "expires_in": 86400,
+                    "created_at": 1_700_000_000,
+                    "token_type": "bearer",
+                    "scope": "public"
+                }));
+        });
+        let DeviceAuthPoll::Approved(creds) = tracker
+            .poll_device_auth(&start.poll_token, &ctx(&server))
+            .await
+            .unwrap()
+        else {
+            panic!("an approved code must yield credentials");
+        };
+        assert_eq!(creds.get_str("access_token"), Some("tok"));
+        assert_eq!(
+            creds
+                .expose()
+                .get("expires_at")
+                .and_then(|v| v.as_i64()),
+            Some(1_700_086_400)
+        );
+    }
+
+    #[tokio::test]
+    async fn a_declined_code_is_denied_rather_than_an_error() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/oauth/device/token");
+            then.status(418);
+        });
+
+        let poll = addon()
+            .poll_device_auth("dev", &ctx(&server))
+            .await
+            .unwrap();
+        assert!(matches!(poll, DeviceAuthPoll::Denied));
+    }
+
+    #[tokio::test]
+    async fn a_finished_episode_is_scrobbled_on_its_show() {
+        let server = MockServer::start();
+        let stop = server.mock(|when, then| {
+            when.method(POST)
+                .path("/scrobble/stop")
+                .header("authorization", "Bearer tok")
+                .header("trakt-api-key", "cid")
+                .json_body_partial(
+                    r#"{"show":{"ids":{"tvdb":79126}},"episode":{"season":1,"number":2},"progress":100.0}"#,
+                );
+            then.status(201)
+                .json_body(serde_json::json!({ "action": "scrobble", "progress": 100.0 }));
+        });
+
+        addon()
+            .on_event(
+                &MediaTrackerEvent::PlaybackStop {
+                    position_ticks: 60 * TICKS_PER_SECOND,
+                    played: true,
+                },
+                &episode(),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        stop.assert();
+    }
+
+    #[tokio::test]
+    async fn a_start_carries_the_position_as_a_percentage() {
+        let server = MockServer::start();
+        let start = server.mock(|when, then| {
+            when.method(POST)
+                .path("/scrobble/start")
+                .json_body_partial(r#"{"movie":{"ids":{"imdb":"tt0113277","tmdb":949}},"progress":25.0}"#);
+            then.status(201)
+                .json_body(serde_json::json!({ "action": "start" }));
+        });
+
+        addon()
+            .on_event(
+                &MediaTrackerEvent::PlaybackStart {
+                    position_ticks: 2_500 * TICKS_PER_SECOND,
+                },
+                &movie(),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        start.assert();
+    }
+
+    /// Trakt answers 409 to a repeat stop. The watch is already recorded, so
+    /// retrying or failing the row would both be wrong.
+    #[tokio::test]
+    async fn a_repeated_stop_counts_as_delivered() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/scrobble/stop");
+            then.status(409)
+                .json_body(
+                    serde_json::json!({ "watched_at": "2024-01-01T00:00:00.000Z" }),
+                );
+        });
+
+        let result = addon()
+            .on_event(
+                &MediaTrackerEvent::PlaybackStop {
+                    position_ticks: 0,
+                    played: true,
+                },
+                &movie(),
+                &creds(),
+                &ctx(&server),
+            )
+            .await;
+        assert!(result.is_ok(), "got {result:?}");
+    }
+
+    #[tokio::test]
+    async fn a_rejected_token_asks_the_user_to_reconnect() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/scrobble/start");
+            then.status(401);
+        });
+
+        let err = addon()
+            .on_event(
+                &MediaTrackerEvent::PlaybackStart { position_ticks: 0 },
+                &movie(),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.requires_reauth());
+    }
+
+    #[tokio::test]
+    async fn an_outage_is_retried() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/sync/history");
+            then.status(503);
+        });
+
+        let err = addon()
+            .on_event(
+                &MediaTrackerEvent::MarkPlayed,
+                &movie(),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn an_item_trakt_cannot_match_fails_permanently() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/sync/history")
+                .json_body_partial(r#"{"movies":[{"ids":{"imdb":"tt0113277"}}]}"#);
+            then.status(201)
+                .json_body(serde_json::json!({
+                    "added": { "movies": 0, "episodes": 0 },
+                    "not_found": { "movies": [{ "ids": { "imdb": "tt0113277" } }] }
+                }));
+        });
+
+        let err = addon()
+            .on_event(
+                &MediaTrackerEvent::MarkPlayed,
+                &movie(),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap_err();
+        assert!(!err.is_retryable());
+        assert!(
+            !err.requires_reauth(),
+            "one unknown item is not the token's fault"
+        );
+    }
+
+    #[tokio::test]
+    async fn ratings_are_rescaled_and_cleared() {
+        let server = MockServer::start();
+        let add = server.mock(|when, then| {
+            when.method(POST)
+                .path("/sync/ratings")
+                .json_body_partial(
+                    r#"{"shows":[{"ids":{"tvdb":79126},"seasons":[{"number":1,"episodes":[{"number":2,"rating":8}]}]}]}"#,
+                );
+            then.status(201)
+                .json_body(serde_json::json!({}));
+        });
+        let remove = server.mock(|when, then| {
+            when.method(POST)
+                .path("/sync/ratings/remove");
+            then.status(200)
+                .json_body(serde_json::json!({}));
+        });
+
+        let tracker = addon();
+        tracker
+            .on_event(
+                &MediaTrackerEvent::Rating { rating: Some(7.6) },
+                &episode(),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        tracker
+            .on_event(
+                &MediaTrackerEvent::Rating { rating: None },
+                &episode(),
+                &creds(),
+                &ctx(&server),
+            )
+            .await
+            .unwrap();
+        add.assert();
+        remove.assert();
+    }
+
+    #[tokio::test]
+    async fn history_import_folds_watches_progress_and_ratings_together() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.path("/sync/watched/movies");
+            then.status(200)
+                .json_body(serde_json::json!([{
+                    "plays": 1,
+                    "last_watched_at": "2024-03-01T20:00:00.000Z",
+                    "movie": { "title": "Heat", "year": 1995, "ids": { "trakt": 1, "imdb": "tt0113277", "tmdb": 949 } }
+                }]));
+        });
+        server.mock(|when, then| {
+            when.path("/sync/watched/shows");
+            then.status(200)
+                .json_body(serde_json::json!([{
+                    "show": { "title": "The Wire", "year": 2002, "ids": { "trakt": 2, "tvdb": 79126 } },
+                    "seasons": [{ "number": 1, "episodes": [{ "number": 1, "plays": 1, "last_watched_at": "2024-03-02T20:00:00.000Z" }] }]
+                }]));
+        });
+        server.mock(|when, then| {
+            when.path("/sync/playback")
+                .query_param("extended", "full");
+            then.status(200)
+                .json_body(serde_json::json!([{
+                    "type": "episode",
+                    "progress": 50.0,
+                    "paused_at": "2024-03-03T20:00:00.000Z",
+                    "episode": { "season": 1, "number": 2, "ids": { "trakt": 22 }, "runtime": 60 },
+                    "show": { "title": "The Wire", "ids": { "trakt": 2, "tvdb": 79126 } }
+                }]));
+        });
+        server.mock(|when, then| {
+            when.path("/sync/ratings");
+            then.status(200)
+                .json_body(serde_json::json!([{
+                    "type": "movie",
+                    "rating": 9,
+                    "rated_at": "2024-03-01T21:00:00.000Z",
+                    "movie": { "title": "Heat", "ids": { "trakt": 1, "imdb": "tt0113277", "tmdb": 949 } }
+                }]));
+        });
+
+        let watches = addon()
+            .import_history(&creds(), &ctx(&server))
+            .await
+            .unwrap();
+        assert_eq!(watches.len(), 3, "the rating belongs to the watched movie");
+
+        let heat = watches
+            .iter()
+            .find(|w| {
+                w.ids
+                    .tmdb
+                    == Some(949)
+            })
+            .unwrap();
+        assert!(heat.watched);
+        assert_eq!(heat.rating, Some(9.0));
+
+        let paused = watches
+            .iter()
+            .find(|w| w.episode == Some(2))
+            .unwrap();
+        assert!(!paused.watched);
+        assert_eq!(
+            paused
+                .ids
+                .tvdb,
+            Some(79126),
+            "episodes land on their show"
+        );
+        assert_eq!(paused.position_ticks, Some(30 * 60 * TICKS_PER_SECOND));
+
+        let watched = watches
+            .iter()
+            .find(|w| w.episode == Some(1))
+            .unwrap();
+        assert!(watched.watched);
+        assert_eq!(watched.season, Some(1));
+    }
+
+    #[tokio::test]
+    async fn pulling_changes_reads_history_since_the_last_sweep() {
+        let server = MockServer::start();
+        let history = server.mock(|when, then| {
+            when.path("/sync/history")
+                .query_param_exists("start_at")
+                .query_param("page", "1");
+            then.status(200)
+                .json_body(serde_json::json!([{
+                    "id": 1,
+                    "type": "movie",
+                    "action": "watch",
+                    "watched_at": "2024-03-05T20:00:00.000Z",
+                    "movie": { "title": "Heat", "ids": { "trakt": 1, "imdb": "tt0113277" } }
+                }]));
+        });
+        server.mock(|when, then| {
+            when.path("/sync/playback");
+            then.status(200)
+                .json_body(serde_json::json!([]));
+        });
+        server.mock(|when, then| {
+            when.path("/sync/ratings");
+            then.status(200)
+                .json_body(serde_json::json!([{
+                    "type": "movie",
+                    "rating": 3,
+                    "rated_at": "2020-01-01T00:00:00.000Z",
+                    "movie": { "title": "Old", "ids": { "trakt": 9, "tmdb": 1 } }
+                }]));
+        });
+
+        let since = chrono::NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let watches = addon()
+            .pull_changes(Some(since), &creds(), &ctx(&server))
+            .await
+            .unwrap();
+        history.assert();
+        assert_eq!(
+            watches.len(),
+            1,
+            "a rating older than the sweep is not a change"
+        );
+        assert!(watches[0].watched);
+        assert_eq!(
+            watches[0]
+                .ids
+                .imdb
+                .as_deref()
+                .map(|s| s.as_str()),
+            Some("tt0113277")
+        );
+    }
+
+    #[tokio::test]
+    async fn the_watchlist_round_trips() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.path("/sync/watchlist");
+            then.status(200)
+                .json_body(serde_json::json!([
+                    { "type": "movie", "movie": { "title": "Heat", "ids": { "trakt": 1, "tmdb": 949 } } },
+                    { "type": "show", "show": { "title": "The Wire", "ids": { "trakt": 2, "tvdb": 79126 } } },
+                    { "type": "season", "season": { "number": 1 } }
+                ]));
+        });
+        let remove = server.mock(|when, then| {
+            when.method(POST)
+                .path("/sync/watchlist/remove")
+                .json_body_partial(r#"{"movies":[{"ids":{"tmdb":949}}]}"#);
+            then.status(200)
+                .json_body(serde_json::json!({}));
+        });
+
+        let tracker = addon();
+        let ids = tracker
+            .pull_watchlist(&creds(), &ctx(&server))
+            .await
+            .unwrap();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0].tmdb, Some(949));
+        assert_eq!(ids[1].tvdb, Some(79126));
+
+        tracker
+            .push_watchlist(&movie(), false, &creds(), &ctx(&server))
+            .await
+            .unwrap();
+        remove.assert();
+    }
+
+    #[tokio::test]
+    async fn a_token_near_expiry_is_renewed_and_a_fresh_one_is_not() {
+        let server = MockServer::start();
+        let renew = server.mock(|when, then| {
+            when.method(POST)
+                .path("/oauth/token")
+                .json_body_partial(
+                    r#"{"refresh_token":"ref","grant_type":"refresh_token"}"#,
+                );
+            then.status(200)
+                .json_body(serde_json::json!({
+                    "access_token": "tok2",
+                    "refresh_token": "ref2",
+                    "expires_in": 86400,
+                    "created_at": Utc::now().timestamp()
+                }));
+        });
+
+        let tracker = addon();
+        let fresh = creds();
+        assert_eq!(
+            tracker
+                .refresh(&fresh, &ctx(&server))
+                .await
+                .unwrap(),
+            fresh
+        );
+        assert_eq!(renew.hits(), 0);
+
+        let expiring = TraktCredentials {
+            access_token: "tok".into(),
+            refresh_token: "ref".into(),
+            expires_at: Utc::now().timestamp() + 60,
+        }
+        .into_credentials();
+        let renewed = tracker
+            .refresh(&expiring, &ctx(&server))
+            .await
+            .unwrap();
+        assert_eq!(renewed.get_str("access_token"), Some("tok2"));
+        assert_eq!(renewed.get_str("refresh_token"), Some("ref2"));
+    }
+}
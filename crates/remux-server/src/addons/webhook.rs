@@ -0,0 +1,746 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::{Map, Value, json};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use uuid::Uuid;
+
+use super::{
+    AddonCapabilities, AddonKind, AddonMetadata, AddonOption, AddonOptionType,
+    AddonPreset, AddonPresetRegistration, ResourceType,
+    media_tracker::{
+        AuthFlow, MediaTrackerAddon, MediaTrackerCapabilities, MediaTrackerCredentials,
+        MediaTrackerCtx, MediaTrackerError, MediaTrackerEvent, MediaTrackerEventKind,
+        MediaTrackerResult, MediaTrackerTarget,
+    },
+};
+use crate::db;
+
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+/// Receivers are usually chat bots and home automation; one that takes longer
+/// than this is treated as down and retried later.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub struct WebhookPreset;
+
+impl AddonPreset for WebhookPreset {
+    fn id(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn metadata(&self) -> AddonMetadata {
+        AddonMetadata {
+            id: "webhook".to_string(),
+            display_name: "Webhook".to_string(),
+            description:
+                "Posts watch activity as JSON to a URL of the user's choosing, \
+                 e.g. Discord, ntfy or Home Assistant."
+                    .to_string(),
+            icon: None,
+            supported_resources: vec![AddonMetadata::simple_resource(
+                ResourceType::Tracking,
+            )],
+            supported_types: vec![],
+            supported_resources_user: vec![],
+            supported_types_user: vec![],
+            options: vec![AddonOption {
+                id: "allow_local_targets".to_string(),
+                name: "Allow local network targets".to_string(),
+                description: Some(
+                    "Let users post to loopback, link-local and private addresses, \
+                     e.g. a Home Assistant on the LAN. Any user can then reach \
+                     services only this server can see."
+                        .to_string(),
+                ),
+                required: false,
+                default: Some(Value::Bool(false)),
+                kind: AddonOptionType::Boolean,
+            }],
+        }
+    }
+
+    fn from_cfg(
+        &self,
+        _addon_id: Uuid,
+        cfg: &serde_json::Value,
+        _config: &crate::Config,
+    ) -> Result<AddonCapabilities> {
+        let allow_local_targets = cfg
+            .get("allow_local_targets")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let addon = Arc::new(WebhookAddon {
+            allow_local_targets,
+        });
+        Ok(AddonCapabilities {
+            kind: Some(addon.clone()),
+            media_tracker: Some(addon),
+            ..Default::default()
+        })
+    }
+}
+
+inventory::submit! {
+    AddonPresetRegistration(|| Box::new(WebhookPreset))
+}
+
+pub struct WebhookAddon {
+    allow_local_targets: bool,
+}
+
+#[async_trait]
+impl AddonKind for WebhookAddon {
+    fn id(&self) -> &'static str {
+        "webhook"
+    }
+}
+
+/// What a connection stores. Kept as the user typed it; the template is
+/// parsed again per delivery so a stored one never drifts from what was
+/// validated.
+struct WebhookConfig {
+    url: reqwest::Url,
+    template: Option<Value>,
+    header: Option<(String, String)>,
+}
+
+impl WebhookConfig {
+    fn parse(fields: &Value) -> MediaTrackerResult<Self> {
+        let field = |key: &str| {
+            fields
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+        };
+        let url = field("url")
+            .ok_or_else(|| MediaTrackerError::permanent("a webhook URL is required"))?;
+        let url = reqwest::Url::parse(url).map_err(|e| {
+            MediaTrackerError::permanent(format!("invalid webhook URL: {e}"))
+        })?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(MediaTrackerError::permanent(
+                "webhook URL must be http or https",
+            ));
+        }
+        let template = field("template")
+            .map(serde_json::from_str::<Value>)
+            .transpose()
+            .map_err(|e| {
+                MediaTrackerError::permanent(format!("template is not valid JSON: {e}"))
+            })?;
+        let header = match (field("header_name"), field("header_value")) {
+            (Some(name), Some(value)) => {
+                reqwest::header::HeaderName::from_bytes(name.as_bytes()).map_err(
+                    |_| {
+                        MediaTrackerError::permanent(format!(
+                            "invalid header name: {name}"
+                        ))
+                    },
+                )?;
+                Some((name.to_string(), value.to_string()))
+            }
+            (None, None) => None,
+            _ => {
+                return Err(MediaTrackerError::permanent(
+                    "header name and value must be set together",
+                ));
+            }
+        };
+        Ok(Self {
+            url,
+            template,
+            header,
+        })
+    }
+
+    /// Where the URL points. Every address the host resolves to must be
+    /// public unless `allow_local` is set, so a user cannot aim the server at
+    /// itself, the cloud metadata endpoint or the LAN.
+    async fn resolve(&self, allow_local: bool) -> MediaTrackerResult<SocketAddr> {
+        let port = self
+            .url
+            .port_or_known_default()
+            .unwrap_or(80);
+        let addrs: Vec<SocketAddr> = match self
+            .url
+            .host()
+        {
+            Some(url::Host::Ipv4(ip)) => vec![SocketAddr::from((ip, port))],
+            Some(url::Host::Ipv6(ip)) => vec![SocketAddr::from((ip, port))],
+            Some(url::Host::Domain(host)) => tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|e| {
+                    MediaTrackerError::retryable(format!(
+                        "could not resolve {host}: {e}"
+                    ))
+                })?
+                .collect(),
+            None => {
+                return Err(MediaTrackerError::permanent("webhook URL has no host"));
+            }
+        };
+        if !allow_local
+            && addrs
+                .iter()
+                .any(|addr| is_private(addr.ip()))
+        {
+            return Err(MediaTrackerError::permanent(
+                "webhook URL points at a local or private address",
+            ));
+        }
+        addrs
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                MediaTrackerError::retryable(format!(
+                    "{} did not resolve to any address",
+                    self.url
+                ))
+            })
+    }
+
+    /// A client that connects to `addr` only and does not follow redirects,
+    /// so neither a second lookup nor a `Location` header can move the
+    /// request somewhere [`Self::resolve`] did not check.
+    fn client(&self, addr: SocketAddr) -> MediaTrackerResult<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent("remux-server/1.0")
+            .redirect(reqwest::redirect::Policy::none());
+        if let Some(url::Host::Domain(host)) = self
+            .url
+            .host()
+        {
+            builder = builder.resolve(host, addr);
+        }
+        builder
+            .build()
+            .map_err(|e| MediaTrackerError::retryable(format!("HTTP client: {e}")))
+    }
+}
+
+/// Loopback, link-local, private and shared (CGNAT) addresses, plus the
+/// ones nothing should be posting to. IPv4-mapped IPv6 addresses are checked
+/// as the IPv4 address they carry.
+fn is_private(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(v4) => {
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || (v4.octets()[0] == 100 && v4.octets()[1] & 0xc0 == 64)
+        }
+        IpAddr::V6(v6) => {
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || v6.is_unique_local()
+                || v6.is_unicast_link_local()
+        }
+    }
+}
+
+/// Everything a template can reach. With no template this is the body.
+fn event_context(event: &MediaTrackerEvent, target: &MediaTrackerTarget) -> Value {
+    let mut ctx = Map::new();
+    ctx.insert("event".into(), json!(event.kind()));
+    ctx.insert("item".into(), json!(target));
+    ctx.insert("timestamp".into(), json!(Utc::now().to_rfc3339()));
+    if let Some(ticks) = event.position_ticks() {
+        ctx.insert("position_ticks".into(), json!(ticks));
+        ctx.insert("position_seconds".into(), json!(ticks / TICKS_PER_SECOND));
+    }
+    match event {
+        MediaTrackerEvent::PlaybackProgress { is_paused, .. } => {
+            ctx.insert("is_paused".into(), json!(is_paused));
+        }
+        MediaTrackerEvent::PlaybackStop { played, .. } => {
+            ctx.insert("played".into(), json!(played));
+        }
+        MediaTrackerEvent::Favorite { is_favorite } => {
+            ctx.insert("is_favorite".into(), json!(is_favorite));
+        }
+        MediaTrackerEvent::Rating { rating } => {
+            ctx.insert("rating".into(), json!(rating));
+        }
+        MediaTrackerEvent::PlaybackStart { .. }
+        | MediaTrackerEvent::MarkPlayed
+        | MediaTrackerEvent::MarkUnplayed => {}
+    }
+    Value::Object(ctx)
+}
+
+/// Follow a dotted path such as `item.series.title`.
+fn lookup<'a>(ctx: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(ctx, |v, key| v.get(key.trim()))
+        .filter(|v| !v.is_null())
+}
+
+/// A placeholder alone in a string keeps its JSON type, so `"{{rating}}"`
+/// renders as a number; anywhere else it is spliced in as text, and a missing
+/// value renders as nothing.
+fn render_str(s: &str, ctx: &Value) -> Value {
+    let trimmed = s.trim();
+    if let Some(path) = trimmed
+        .strip_prefix("{{")
+        .and_then(|rest| rest.strip_suffix("}}"))
+        .filter(|path| !path.contains("{{") && !path.contains("}}"))
+    {
+        return lookup(ctx, path)
+            .cloned()
+            .unwrap_or(Value::Null);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        match lookup(ctx, &rest[start + 2..start + len]) {
+            Some(Value::String(text)) => out.push_str(text),
+            Some(other) => out.push_str(&other.to_string()),
+            None => {}
+        }
+        rest = &rest[start + len + 2..];
+    }
+    out.push_str(rest);
+    Value::String(out)
+}
+
+fn render(template: &Value, ctx: &Value) -> Value {
+    match template {
+        Value::String(s) => render_str(s, ctx),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|v| render(v, ctx))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), render(v, ctx)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// The receiver decides whether a delivery is worth retrying, the same way
+/// an API provider would.
+fn status_error(resp: &reqwest::Response) -> MediaTrackerError {
+    let status = resp.status();
+    let message = format!("webhook returned {status}");
+    match status.as_u16() {
+        401 | 403 => MediaTrackerError::reauth(message),
+        429 => match resp
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| {
+                v.to_str()
+                    .ok()
+            })
+            .and_then(|v| {
+                v.trim()
+                    .parse::<u64>()
+                    .ok()
+            }) {
+            Some(secs) => {
+                MediaTrackerError::retry_after(message, Duration::from_secs(secs))
+            }
+            None => MediaTrackerError::retryable(message),
+        },
+        408 | 500..=599 => MediaTrackerError::retryable(message),
+        _ => MediaTrackerError::permanent(message),
+    }
+}
+
+#[async_trait]
+impl MediaTrackerAddon for WebhookAddon {
+    fn capabilities(&self) -> MediaTrackerCapabilities {
+        MediaTrackerCapabilities {
+            auth_flow: AuthFlow::Token,
+            connect_fields: vec![
+                AddonOption {
+                    id: "url".to_string(),
+                    name: "Webhook URL".to_string(),
+                    description: Some("Receives a POST for every selected event.".to_string()),
+                    required: true,
+                    default: None,
+                    kind: AddonOptionType::Url,
+                },
+                AddonOption {
+                    id: "template".to_string(),
+                    name: "Body template".to_string(),
+                    description: Some(
+                        "JSON body to send. {{event}}, {{item.title}}, {{item.series.title}}, \
+                         {{item.ids.imdb}}, {{position_seconds}}, {{rating}} and the like are \
+                         filled in. Leave empty to send the whole event."
+                            .to_string(),
+                    ),
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::Textarea,
+                },
+                AddonOption {
+                    id: "header_name".to_string(),
+                    name: "Auth header name".to_string(),
+                    description: Some("e.g. Authorization.".to_string()),
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::String,
+                },
+                AddonOption {
+                    id: "header_value".to_string(),
+                    name: "Auth header value".to_string(),
+                    description: None,
+                    required: false,
+                    default: None,
+                    kind: AddonOptionType::Password,
+                },
+            ],
+            supported_events: vec![
+                MediaTrackerEventKind::PlaybackStart,
+                MediaTrackerEventKind::PlaybackProgress,
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::MarkPlayed,
+                MediaTrackerEventKind::MarkUnplayed,
+                MediaTrackerEventKind::Favorite,
+                MediaTrackerEventKind::Rating,
+            ],
+            media_kinds: vec![
+                db::MediaKind::Movie,
+                db::MediaKind::Episode,
+                db::MediaKind::Track,
+            ],
+            // Progress reports arrive every few seconds, which would flood a
+            // chat channel.
+            default_event_filter: vec![
+                MediaTrackerEventKind::PlaybackStart,
+                MediaTrackerEventKind::PlaybackStop,
+                MediaTrackerEventKind::MarkPlayed,
+                MediaTrackerEventKind::Favorite,
+                MediaTrackerEventKind::Rating,
+            ],
+            ..Default::default()
+        }
+    }
+
+    /// Validates without sending anything: a test message would land in the
+    /// user's channel every time they save the form.
+    async fn connect_with_token(
+        &self,
+        fields: &Value,
+        _ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<MediaTrackerCredentials> {
+        WebhookConfig::parse(fields)?
+            .resolve(self.allow_local_targets)
+            .await?;
+        Ok(MediaTrackerCredentials::new(fields.clone()))
+    }
+
+    async fn on_event(
+        &self,
+        event: &MediaTrackerEvent,
+        target: &MediaTrackerTarget,
+        creds: &MediaTrackerCredentials,
+        _ctx: &MediaTrackerCtx,
+    ) -> MediaTrackerResult<()> {
+        let cfg = WebhookConfig::parse(creds.expose())?;
+        let addr = cfg
+            .resolve(self.allow_local_targets)
+            .await?;
+        let client = cfg.client(addr)?;
+        let ctx = event_context(event, target);
+        let body = match &cfg.template {
+            Some(template) => render(template, &ctx),
+            None => ctx,
+        };
+        let mut req = client
+            .post(cfg.url)
+            .timeout(REQUEST_TIMEOUT)
+            .json(&body);
+        if let Some((name, value)) = &cfg.header {
+            req = req.header(name.as_str(), value.as_str());
+        }
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| {
+                MediaTrackerError::retryable(format!("webhook request failed: {e}"))
+            })?;
+        if resp
+            .status()
+            .is_success()
+        {
+            Ok(())
+        } else {
+            Err(status_error(&resp))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::{Method::POST, MockServer};
+
+    /// The mock receivers listen on loopback.
+    const ADDON: WebhookAddon = WebhookAddon {
+        allow_local_targets: true,
+    };
+
+    fn ctx() -> MediaTrackerCtx {
+        MediaTrackerCtx {
+            config: Arc::new(crate::Config::default()),
+        }
+    }
+
+    fn creds(server: &MockServer, template: Option<&str>) -> MediaTrackerCredentials {
+        let mut fields = json!({ "url": server.url("/hook") });
+        if let Some(template) = template {
+            fields["template"] = json!(template);
+        }
+        MediaTrackerCredentials::new(fields)
+    }
+
+    fn episode() -> MediaTrackerTarget {
+        MediaTrackerTarget {
+            kind: db::MediaKind::Episode,
+            title: "Pilot".into(),
+            year: None,
+            ids: db::ExternalIds::default(),
+            series: Some(Box::new(MediaTrackerTarget {
+                kind: db::MediaKind::Series,
+                title: "The Wire".into(),
+                year: Some(2002),
+                ids: db::ExternalIds {
+                    imdb: db::NonEmptyString::try_new("tt0306414".to_string()).ok(),
+                    ..Default::default()
+                },
+                series: None,
+                season: None,
+                episode: None,
+                runtime: None,
+                artist: None,
+                album: None,
+            })),
+            season: Some(1),
+            episode: Some(1),
+            runtime: Some(3600),
+            artist: None,
+            album: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn a_template_is_filled_from_the_event_and_its_item() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/hook")
+                .json_body(json!({
+                    "content": "playback_stop The Wire S1E1 (tt0306414)",
+                    "season": 1,
+                    "done": true,
+                    "missing": null,
+                }));
+            then.status(204);
+        });
+
+        let template = r#"{
+            "content": "{{event}} {{item.series.title}} S{{item.season}}E{{item.episode}} ({{item.series.ids.imdb}})",
+            "season": "{{item.season}}",
+            "done": "{{played}}",
+            "missing": "{{rating}}"
+        }"#;
+        ADDON
+            .on_event(
+                &MediaTrackerEvent::PlaybackStop {
+                    position_ticks: 0,
+                    played: true,
+                },
+                &episode(),
+                &creds(&server, Some(template)),
+                &ctx(),
+            )
+            .await
+            .unwrap();
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn without_a_template_the_whole_event_is_sent() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/hook")
+                .json_body_partial(
+                    r#"{"event":"playback_start","position_seconds":90,"item":{"title":"Pilot","runtime":3600}}"#,
+                );
+            then.status(200);
+        });
+
+        ADDON
+            .on_event(
+                &MediaTrackerEvent::PlaybackStart {
+                    position_ticks: 90 * TICKS_PER_SECOND,
+                },
+                &episode(),
+                &creds(&server, None),
+                &ctx(),
+            )
+            .await
+            .unwrap();
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn the_configured_header_is_sent() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/hook")
+                .header("authorization", "Bearer s3cret");
+            then.status(200);
+        });
+
+        let creds = MediaTrackerCredentials::new(json!({
+            "url": server.url("/hook"),
+            "header_name": "Authorization",
+            "header_value": "Bearer s3cret",
+        }));
+        ADDON
+            .on_event(&MediaTrackerEvent::MarkPlayed, &episode(), &creds, &ctx())
+            .await
+            .unwrap();
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn receiver_status_decides_whether_to_retry() {
+        for (status, retryable, reauth) in [
+            (500, true, false),
+            (429, true, false),
+            (401, false, true),
+            (400, false, false),
+        ] {
+            let server = MockServer::start();
+            server.mock(|when, then| {
+                when.method(POST)
+                    .path("/hook");
+                then.status(status);
+            });
+            let err = ADDON
+                .on_event(
+                    &MediaTrackerEvent::MarkPlayed,
+                    &episode(),
+                    &creds(&server, None),
+                    &ctx(),
+                )
+                .await
+                .unwrap_err();
+            assert_eq!(err.is_retryable(), retryable, "status {status}");
+            assert_eq!(err.requires_reauth(), reauth, "status {status}");
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_honoured() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/hook");
+            then.status(429)
+                .header("Retry-After", "120");
+        });
+        let err = ADDON
+            .on_event(
+                &MediaTrackerEvent::MarkPlayed,
+                &episode(),
+                &creds(&server, None),
+                &ctx(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            MediaTrackerError::Retryable {
+                retry_after: Some(d),
+                ..
+            } if d == Duration::from_secs(120)
+        ));
+    }
+
+    #[tokio::test]
+    async fn connect_rejects_what_could_never_deliver() {
+        for fields in [
+            json!({}),
+            json!({ "url": "ftp://203.0.113.9/hook" }),
+            json!({ "url": "https://203.0.113.9/hook", "template": "{not json" }),
+            json!({ "url": "https://203.0.113.9/hook", "header_name": "X-Token" }),
+        ] {
+            let err = ADDON
+                .connect_with_token(&fields, &ctx())
+                .await
+                .unwrap_err();
+            assert!(!err.is_retryable(), "{fields}");
+        }
+
+        let fields = json!({ "url": "https://203.0.113.9/hook", "template": "{\"a\":\"{{event}}\"}" });
+        let creds = ADDON
+            .connect_with_token(&fields, &ctx())
+            .await
+            .unwrap();
+        assert_eq!(creds.expose(), &fields);
+    }
+
+    #[tokio::test]
+    async fn local_targets_need_the_admin_to_allow_them() {
+        let addon = WebhookAddon {
+            allow_local_targets: false,
+        };
+        for url in [
+            "http://127.0.0.1:8096/System/Info",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://192.168.1.1/",
+            "http://100.64.0.1/",
+            "http://[::1]/hook",
+            "http://[::ffff:10.0.0.1]/hook",
+            "http://localhost/hook",
+        ] {
+            let err = addon
+                .connect_with_token(&json!({ "url": url }), &ctx())
+                .await
+                .unwrap_err();
+            assert!(!err.is_retryable(), "{url}");
+        }
+
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/hook");
+            then.status(200);
+        });
+        let err = addon
+            .on_event(
+                &MediaTrackerEvent::MarkPlayed,
+                &episode(),
+                &creds(&server, None),
+                &ctx(),
+            )
+            .await
+            .unwrap_err();
+        assert!(!err.is_retryable());
+        mock.assert_hits(0);
+    }
+}
@@ -0,0 +1,725 @@
+//! UPnP description documents and the ContentDirectory and
+//! ConnectionManager control endpoints of the DLNA media server.
+//!
+//! Object ids are item ids, with `0` for the root listing the user views.
+//! Libraries, collections, series, seasons, artists and albums browse as
+//! containers; movies, episodes and tracks are items whose resource is the
+//! direct stream when the renderer's profile plays the source, and a
+//! progressive transcode otherwise.
+
+use anyhow::anyhow;
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use axum_anyhow::ApiResult as Result;
+use http::{HeaderMap, StatusCode, header};
+use remux_macros::{get, post};
+use uuid::Uuid;
+
+use crate::{
+    AppState, IntoApiError, OptionExt,
+    api::{self, MediaType},
+    db::{self, auth},
+    dlna::{
+        self, CONNECTION_MANAGER_URN, CONTENT_DIRECTORY_URN, description,
+        didl::{self, Object, Resource, SoapAction},
+        profile::{self, RendererProfile},
+    },
+    network::ClientAddr,
+};
+
+use super::{
+    items::get_items,
+    system::request_local_address,
+    users::{ensure_access_allowed, user_views},
+};
+
+const ROOT_ID: &str = "0";
+
+fn xml(body: String) -> Response {
+    (
+        [(header::CONTENT_TYPE, r#"text/xml; charset="utf-8""#)],
+        body,
+    )
+        .into_response()
+}
+
+fn fault(code: u16, description: &str) -> Response {
+    let mut response = xml(didl::soap_fault(code, description));
+    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    response
+}
+
+/// The endpoints answer only while `EnableUPnP` is on, and only clients on
+/// the local network: renderers cannot sign in.
+async fn ensure_enabled(state: &AppState, client: &ClientAddr) -> Result<()> {
+    dlna::enabled(
+        &state
+            .ctx
+            .db,
+    )
+    .await
+    .then_some(())
+    .context_not_found("DLNA is disabled")?;
+    if client.is_remote() {
+        return Err(anyhow!("DLNA request from outside the local network")
+            .context_forbidden("DLNA is only served on the local network"));
+    }
+    Ok(())
+}
+
+fn base_url(state: &AppState, headers: &HeaderMap) -> String {
+    request_local_address(
+        headers,
+        state
+            .ctx
+            .config
+            .port,
+    )
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|v| {
+            v.to_str()
+                .ok()
+        })
+}
+
+/// Renderers cannot sign in; they browse as the user an admin picked in the
+/// DLNA settings, and are refused while none is.
+async fn renderer_session(
+    state: &AppState,
+    profile: &RendererProfile,
+) -> Result<auth::AuthSession> {
+    let db = &state
+        .ctx
+        .db;
+    let user_id = db::Settings::get_dlna_config(db)
+        .await?
+        .default_user_id
+        .context_forbidden("no DLNA user is configured")?;
+    let user = db::User::get_by_id(db, &user_id)
+        .await?
+        .context_forbidden("the DLNA user no longer exists")?;
+    ensure_access_allowed(&user)?;
+    let device = auth::Device {
+        id: format!("dlna-{}", profile.name),
+        user_id: user.id,
+        name: profile
+            .name
+            .to_string(),
+        app_name: "DLNA".to_string(),
+        ..Default::default()
+    };
+    Ok(auth::AuthSession { device, user })
+}
+
+#[get("/dlna/description.xml")]
+pub async fn device_description(
+    State(state): State<AppState>,
+    client: ClientAddr,
+    headers: HeaderMap,
+) -> Result<Response> {
+    ensure_enabled(&state, &client).await?;
+    let config = db::Settings::get_config_or_default(
+        &state
+            .ctx
+            .db,
+    )
+    .await;
+    let name = config
+        .server_name
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "remux".to_string());
+    Ok(xml(description::device(
+        &dlna::udn(),
+        &name,
+        &base_url(&state, &headers),
+    )))
+}
+
+#[get("/dlna/contentdirectory.xml")]
+pub async fn content_directory_description(
+    State(state): State<AppState>,
+    client: ClientAddr,
+) -> Result<Response> {
+    ensure_enabled(&state, &client).await?;
+    Ok(xml(description::CONTENT_DIRECTORY.to_string()))
+}
+
+#[get("/dlna/connectionmanager.xml")]
+pub async fn connection_manager_description(
+    State(state): State<AppState>,
+    client: ClientAddr,
+) -> Result<Response> {
+    ensure_enabled(&state, &client).await?;
+    Ok(xml(description::CONNECTION_MANAGER.to_string()))
+}
+
+#[post("/dlna/control/connectionmanager")]
+pub async fn connection_manager_control(
+    State(state): State<AppState>,
+    client: ClientAddr,
+    body: String,
+) -> Result<Response> {
+    ensure_enabled(&state, &client).await?;
+    let Ok(action) = didl::parse_action(&body) else {
+        return Ok(fault(401, "Invalid Action"));
+    };
+    let args: Vec<(&str, String)> = match action
+        .name
+        .as_str()
+    {
+        "GetProtocolInfo" => vec![
+            ("Source", description::SOURCE_PROTOCOL_INFO.to_string()),
+            ("Sink", String::new()),
+        ],
+        "GetCurrentConnectionIDs" => vec![("ConnectionIDs", "0".to_string())],
+        "GetCurrentConnectionInfo" => vec![
+            ("RcsID", "-1".to_string()),
+            ("AVTransportID", "-1".to_string()),
+            ("ProtocolInfo", String::new()),
+            ("PeerConnectionManager", String::new()),
+            ("PeerConnectionID", "-1".to_string()),
+            ("Direction", "Output".to_string()),
+            ("Status", "OK".to_string()),
+        ],
+        _ => return Ok(fault(401, "Invalid Action")),
+    };
+    Ok(xml(didl::soap_response(
+        CONNECTION_MANAGER_URN,
+        &action.name,
+        &args,
+    )))
+}
+
+#[post("/dlna/control/contentdirectory")]
+pub async fn content_directory_control(
+    State(state): State<AppState>,
+    client: ClientAddr,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response> {
+    ensure_enabled(&state, &client).await?;
+    let Ok(action) = didl::parse_action(&body) else {
+        return Ok(fault(401, "Invalid Action"));
+    };
+    let args: Vec<(&str, String)> = match action
+        .name
+        .as_str()
+    {
+        "GetSearchCapabilities" => vec![("SearchCaps", String::new())],
+        "GetSortCapabilities" => vec![("SortCaps", String::new())],
+        "GetSystemUpdateID" => vec![("Id", "0".to_string())],
+        "Browse" => {
+            let profile = profile::for_user_agent(user_agent(&headers));
+            let session = renderer_session(&state, profile).await?;
+            let base = base_url(&state, &headers);
+            match browse(&state, session, &base, profile, &action).await? {
+                Some(args) => args,
+                None => return Ok(fault(701, "No such object")),
+            }
+        }
+        _ => return Ok(fault(401, "Invalid Action")),
+    };
+    Ok(xml(didl::soap_response(
+        CONTENT_DIRECTORY_URN,
+        &action.name,
+        &args,
+    )))
+}
+
+/// `Browse` output arguments, or `None` when the object does not exist.
+async fn browse(
+    state: &AppState,
+    session: auth::AuthSession,
+    base: &str,
+    profile: &RendererProfile,
+    action: &SoapAction,
+) -> Result<Option<Vec<(&'static str, String)>>> {
+    let object_id = action
+        .arg("ObjectID")
+        .unwrap_or(ROOT_ID);
+    let start = action
+        .arg("StartingIndex")
+        .and_then(|v| {
+            v.parse::<u32>()
+                .ok()
+        })
+        .unwrap_or(0);
+    // 0 asks for everything.
+    let count = action
+        .arg("RequestedCount")
+        .and_then(|v| {
+            v.parse::<u32>()
+                .ok()
+        })
+        .filter(|&c| c > 0);
+
+    let (objects, total) = if action.arg("BrowseFlag") == Some("BrowseMetadata") {
+        let object = if object_id == ROOT_ID {
+            Some(root_container())
+        } else {
+            match object_id
+                .parse::<Uuid>()
+                .ok()
+            {
+                Some(id) => item(state, &session, id)
+                    .await?
+                    .and_then(|item| {
+                        let parent = item
+                            .parent_id
+                            .map(|p| p.to_string())
+                            .unwrap_or_else(|| ROOT_ID.to_string());
+                        to_object(item, &parent, base, profile)
+                    }),
+                None => None,
+            }
+        };
+        let Some(object) = object else {
+            return Ok(None);
+        };
+        (vec![object], 1)
+    } else if object_id == ROOT_ID {
+        let views: Vec<Object> = user_views(state, &session, false)
+            .await?
+            .into_iter()
+            .filter(|v| v.collection_type != Some(api::CollectionType::Livetv))
+            .filter_map(|v| to_object(v, ROOT_ID, base, profile))
+            .collect();
+        let total = views.len() as i64;
+        let page = views
+            .into_iter()
+            .skip(start as usize)
+            .take(count.unwrap_or(u32::MAX) as usize)
+            .collect();
+        (page, total)
+    } else {
+        let Some(parent) = object_id
+            .parse::<Uuid>()
+            .ok()
+        else {
+            return Ok(None);
+        };
+        let Some(container) = item(state, &session, parent).await? else {
+            return Ok(None);
+        };
+        let q = api::GetItemsQuery {
+            start_index: Some(start),
+            limit: count,
+            ..children_query(&container)
+        };
+        // Boxed: `get_items` polls deep enough to overflow a 2 MiB stack in
+        // debug builds when it runs under this handler.
+        let result = Box::pin(get_items(state.clone(), session, q, true))
+            .await?
+            .with_permissions()
+            .build();
+        let objects = result
+            .items
+            .into_iter()
+            .filter_map(|i| to_object(i, object_id, base, profile))
+            .collect();
+        (objects, result.total_count)
+    };
+
+    let returned = objects.len();
+    Ok(Some(vec![
+        ("Result", didl::didl(&objects)),
+        ("NumberReturned", returned.to_string()),
+        ("TotalMatches", total.to_string()),
+        ("UpdateID", "0".to_string()),
+    ]))
+}
+
+async fn item(
+    state: &AppState,
+    session: &auth::AuthSession,
+    id: Uuid,
+) -> Result<Option<api::BaseItemDto>> {
+    let q = api::GetItemsQuery {
+        ids: Some(vec![id]),
+        recursive: true,
+        ..Default::default()
+    };
+    Ok(
+        Box::pin(get_items(state.clone(), session.clone(), q, false))
+            .await?
+            .build()
+            .items
+            .into_iter()
+            .next(),
+    )
+}
+
+/// The listing under a container, by what the container holds.
+fn children_query(container: &api::BaseItemDto) -> api::GetItemsQuery {
+    let (types, sort_by, parent_id, artist_ids) = match container.type_ {
+        MediaType::MusicArtist => (
+            Some(vec![MediaType::MusicAlbum]),
+            vec![api::ItemSortBy::ProductionYear, api::ItemSortBy::SortName],
+            None,
+            Some(vec![container.id]),
+        ),
+        MediaType::Series => (
+            Some(vec![MediaType::Season]),
+            vec![api::ItemSortBy::IndexNumber],
+            Some(container.id),
+            None,
+        ),
+        MediaType::Season => (
+            Some(vec![MediaType::Episode]),
+            vec![api::ItemSortBy::IndexNumber],
+            Some(container.id),
+            None,
+        ),
+        MediaType::MusicAlbum => (
+            Some(vec![MediaType::Audio]),
+            vec![
+                api::ItemSortBy::ParentIndexNumber,
+                api::ItemSortBy::IndexNumber,
+            ],
+            Some(container.id),
+            None,
+        ),
+        _ => (
+            None,
+            vec![api::ItemSortBy::SortName],
+            Some(container.id),
+            None,
+        ),
+    };
+    api::GetItemsQuery {
+        recursive: artist_ids.is_some(),
+        parent_id,
+        artist_ids,
+        include_item_types: types,
+        sort_by: Some(sort_by),
+        sort_order: Some(vec![api::SortOrder::Ascending]),
+        ..Default::default()
+    }
+}
+
+fn root_container() -> Object {
+    Object::Container {
+        id: ROOT_ID.to_string(),
+        parent_id: "-1".to_string(),
+        title: "remux".to_string(),
+        class: "object.container.storageFolder",
+        child_count: None,
+        art: None,
+    }
+}
+
+fn image_url(base: &str, id: Uuid) -> String {
+    format!("{base}/items/{id}/images/primary?maxWidth=500")
+}
+
+fn to_object(
+    item: api::BaseItemDto,
+    parent_id: &str,
+    base: &str,
+    profile: &RendererProfile,
+) -> Option<Object> {
+    let has_image = item
+        .image_tags
+        .as_ref()
+        .is_some_and(|t| {
+            t.primary
+                .is_some()
+        });
+    let container_class = match item.type_ {
+        MediaType::MusicAlbum => Some("object.container.album.musicAlbum"),
+        MediaType::MusicArtist => Some("object.container.person.musicArtist"),
+        MediaType::Playlist => Some("object.container.playlistContainer"),
+        MediaType::Series | MediaType::Season | MediaType::BoxSet => {
+            Some("object.container.storageFolder")
+        }
+        _ if item.is_folder => Some("object.container.storageFolder"),
+        _ => None,
+    };
+    if let Some(class) = container_class {
+        return Some(Object::Container {
+            id: item
+                .id
+                .to_string(),
+            parent_id: parent_id.to_string(),
+            class,
+            child_count: item.child_count,
+            art: has_image.then(|| image_url(base, item.id)),
+            title: item
+                .name
+                .unwrap_or_default(),
+        });
+    }
+
+    let (class, is_video) = match item.type_ {
+        MediaType::Movie => ("object.item.videoItem.movie", true),
+        MediaType::Episode | MediaType::Video => ("object.item.videoItem", true),
+        MediaType::Audio => ("object.item.audioItem.musicTrack", false),
+        _ => return None,
+    };
+    // Tracks rarely carry artwork of their own; the album's stands in.
+    let art = if has_image {
+        Some(image_url(base, item.id))
+    } else {
+        item.album_id
+            .filter(|_| {
+                item.album_primary_image_tag
+                    .is_some()
+            })
+            .map(|album| image_url(base, album))
+    };
+    let resource = resource(&item, base, profile, is_video);
+    let artist = item
+        .album_artist
+        .clone()
+        .or_else(|| {
+            (!item
+                .artists
+                .is_empty())
+            .then(|| {
+                item.artists
+                    .join(", ")
+            })
+        });
+    let date = item
+        .premiere_date
+        .map(|d| {
+            d.format("%Y-%m-%d")
+                .to_string()
+        })
+        .or_else(|| {
+            item.production_year
+                .map(|y| format!("{y}-01-01"))
+        });
+    Some(Object::Item {
+        id: item
+            .id
+            .to_string(),
+        parent_id: parent_id.to_string(),
+        class,
+        art,
+        artist,
+        album: item
+            .album
+            .clone(),
+        track: item
+            .index_number
+            .filter(|_| !is_video),
+        date,
+        resource,
+        title: item
+            .name
+            .unwrap_or_default(),
+    })
+}
+
+/// The direct stream when `profile` plays the source as is, otherwise a
+/// progressive transcode to H.264/AAC in MPEG-TS, or MP3 for music.
+fn resource(
+    item: &api::BaseItemDto,
+    base: &str,
+    profile: &RendererProfile,
+    is_video: bool,
+) -> Resource {
+    let source = item
+        .media_sources
+        .as_ref()
+        .and_then(|s| s.first());
+    let source_id = source
+        .map(|s| s.id)
+        .unwrap_or(item.id);
+    let endpoint = if is_video { "videos" } else { "audio" };
+    let direct = source.filter(|s| profile.plays(s, is_video));
+    let (url, mime_type) = match direct {
+        Some(source) => (
+            format!(
+                "{base}/{endpoint}/{}/stream?static=true&mediaSourceId={source_id}",
+                item.id
+            ),
+            profile::mime_type(
+                source
+                    .container
+                    .as_deref()
+                    .unwrap_or_default(),
+                is_video,
+            ),
+        ),
+        None if is_video => {
+            let container = profile.transcode_container;
+            (
+                format!(
+                    "{base}/videos/{}/stream.{container}?mediaSourceId={source_id}&videoCodec=h264&audioCodec=aac",
+                    item.id
+                ),
+                profile::mime_type(container, true),
+            )
+        }
+        None => (
+            format!(
+                "{base}/audio/{}/stream.mp3?mediaSourceId={source_id}&audioCodec=mp3",
+                item.id
+            ),
+            "audio/mpeg",
+        ),
+    };
+    Resource {
+        url,
+        mime_type,
+        transcoded: direct.is_none(),
+        duration_ticks: item.run_time_ticks,
+        size: source.and_then(|s| s.size),
+        bitrate: source.and_then(|s| s.bitrate),
+        resolution: item
+            .width
+            .zip(item.height)
+            .filter(|_| is_video),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_test::{authenticated_server, seed_track};
+
+    fn browse_body(object_id: &str, flag: &str) -> String {
+        format!(
+            r#"<?xml version="1.0"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body><u:Browse xmlns:u="{CONTENT_DIRECTORY_URN}"><ObjectID>{object_id}</ObjectID><BrowseFlag>{flag}</BrowseFlag><Filter>*</Filter><StartingIndex>0</StartingIndex><RequestedCount>0</RequestedCount><SortCriteria></SortCriteria></u:Browse></s:Body></s:Envelope>"#
+        )
+    }
+
+    #[tokio::test]
+    async fn endpoints_are_hidden_until_upnp_is_enabled() {
+        let (server, _guard, _token) = authenticated_server().await;
+        server
+            .get(dlna::DESCRIPTION_PATH)
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    /// Turns UPnP on and, with `user` set, picks the signed-in admin as the
+    /// DLNA user.
+    async fn enable_dlna(db: &sqlx::SqlitePool, user: bool) {
+        let mut config = super::super::networking::network_configuration(db)
+            .await
+            .unwrap();
+        config.enable_u_pn_p = Some(true);
+        db::Settings::set(
+            db,
+            "network_configuration",
+            &serde_json::to_string(&config).unwrap(),
+        )
+        .await
+        .unwrap();
+        if user {
+            let admin = db::User::get_by_username(db, "test")
+                .await
+                .unwrap()
+                .unwrap();
+            db::Settings::set_dlna_config(
+                db,
+                &remux_sdks::remux::DlnaOptions {
+                    default_user_id: Some(admin.id),
+                },
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn browsing_is_refused_until_a_dlna_user_is_picked() {
+        let (server, guard, _token) = authenticated_server().await;
+        enable_dlna(
+            &guard
+                .0
+                .db,
+            false,
+        )
+        .await;
+        server
+            .post("/dlna/control/contentdirectory")
+            .text(browse_body(ROOT_ID, "BrowseDirectChildren"))
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn clients_outside_the_local_network_are_refused() {
+        let peer = std::net::SocketAddr::from(([203, 0, 113, 9], 50000));
+        let (server, guard, _token) =
+            crate::integration_test::authenticated_server_from(peer).await;
+        enable_dlna(
+            &guard
+                .0
+                .db,
+            true,
+        )
+        .await;
+        server
+            .get(dlna::DESCRIPTION_PATH)
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+        server
+            .post("/dlna/control/contentdirectory")
+            .text(browse_body(ROOT_ID, "BrowseDirectChildren"))
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn albums_browse_to_tracks_with_stream_resources() {
+        let (server, guard, _token) = authenticated_server().await;
+        enable_dlna(
+            &guard
+                .0
+                .db,
+            true,
+        )
+        .await;
+        let track = seed_track(&guard.0).await;
+        let album = track
+            .parent_id
+            .unwrap();
+
+        let description = server
+            .get(dlna::DESCRIPTION_PATH)
+            .await
+            .text();
+        assert!(description.contains(&dlna::udn()));
+        assert!(
+            description
+                .contains("<controlURL>/dlna/control/contentdirectory</controlURL>")
+        );
+
+        let response = server
+            .post("/dlna/control/contentdirectory")
+            .text(browse_body(&album.to_string(), "BrowseDirectChildren"))
+            .await
+            .text();
+        assert!(response.contains("<TotalMatches>1</TotalMatches>"));
+        assert!(response.contains("Teardrop"));
+        assert!(response.contains("object.item.audioItem.musicTrack"));
+        assert!(response.contains(&format!("/audio/{}/stream", track.id)));
+
+        let response = server
+            .post("/dlna/control/contentdirectory")
+            .text(browse_body(&Uuid::new_v4().to_string(), "BrowseMetadata"))
+            .expect_failure()
+            .await;
+        response.assert_status(StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(
+            response
+                .text()
+                .contains("<errorCode>701</errorCode>")
+        );
+    }
+}
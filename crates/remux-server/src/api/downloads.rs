@@ -0,0 +1,370 @@
+use anyhow::anyhow;
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDateTime;
+use http::StatusCode;
+use remux_macros::{delete, get, post};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    AppState, IntoApiError, OptionExt, ResultExt,
+    db::{DownloadJob, DownloadProfile, DownloadStatus, Media, auth},
+    downloads,
+    stream::StreamDescriptor,
+};
+use axum_anyhow::ApiResult as Result;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadJobResponse {
+    id: Uuid,
+    item_id: Uuid,
+    name: String,
+    profile: DownloadProfile,
+    status: DownloadStatus,
+    progress: f64,
+    size: Option<i64>,
+    error: Option<String>,
+    created_at: NaiveDateTime,
+    completed_at: Option<NaiveDateTime>,
+    expires_at: Option<NaiveDateTime>,
+}
+
+impl From<DownloadJob> for DownloadJobResponse {
+    fn from(j: DownloadJob) -> Self {
+        Self {
+            id: j.id,
+            item_id: j.media_id,
+            name: j.file_name,
+            profile: j.profile,
+            status: j.status,
+            progress: j.progress,
+            size: j.size,
+            error: j.error,
+            created_at: j.created_at,
+            completed_at: j.completed_at,
+            expires_at: j.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateDownloadPayload {
+    pub item_id: Uuid,
+    #[serde(default)]
+    pub profile: DownloadProfile,
+}
+
+/// A job the session may see: its own, or any for an admin.
+async fn owned_job(
+    state: &AppState,
+    session: &auth::AuthSession,
+    id: Uuid,
+) -> Result<DownloadJob> {
+    let job = DownloadJob::get(
+        &state
+            .ctx
+            .db,
+        id,
+    )
+    .await?
+    .filter(|j| {
+        j.user_id
+            == session
+                .user
+                .id
+            || session
+                .user
+                .is_admin
+    })
+    .context_not_found("download not found")?;
+    Ok(job)
+}
+
+/// Queue a download of an item, or of every episode or track under a series,
+/// season, album or artist.
+#[post("/remux/downloads")]
+pub async fn create_download(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Json(payload): Json<CreateDownloadPayload>,
+) -> Result<impl IntoResponse> {
+    if session
+        .user
+        .policy
+        .as_ref()
+        .is_some_and(|p| !p.enable_content_downloading || !p.enable_sync_transcoding)
+    {
+        return Err(anyhow!("Forbidden").context_forbidden("downloads are disabled"));
+    }
+
+    let db = &state
+        .ctx
+        .db;
+    let media = Media::get_by_id(db, &payload.item_id)
+        .await?
+        .context_not_found("item not found")?;
+    let targets = downloads::targets(db, &media)
+        .await
+        .context_bad_request("item cannot be downloaded")?;
+    if targets.is_empty() {
+        return Err(anyhow!("no items").context_bad_request("nothing to download"));
+    }
+
+    let mut jobs = Vec::with_capacity(targets.len());
+    for item in &targets {
+        let file_name = downloads::file_name(item, &payload.profile)
+            .context_bad_request("profile cannot be used for this item")?;
+        jobs.push(DownloadJob::new(
+            session
+                .user
+                .id,
+            item.id,
+            payload
+                .profile
+                .clone(),
+            file_name,
+        ));
+    }
+    for job in &jobs {
+        job.insert(db)
+            .await?;
+    }
+    state
+        .tasks
+        .run_task(downloads::TASK_KEY)
+        .await?;
+
+    let response: Vec<DownloadJobResponse> = jobs
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[get("/remux/downloads")]
+pub async fn list_downloads(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    let jobs = DownloadJob::list_for_user(
+        &state
+            .ctx
+            .db,
+        session
+            .user
+            .id,
+    )
+    .await?;
+    let response: Vec<DownloadJobResponse> = jobs
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    Ok(Json(response))
+}
+
+#[get("/remux/downloads/{id}")]
+pub async fn get_download(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let job = owned_job(&state, &session, id).await?;
+    Ok(Json(DownloadJobResponse::from(job)))
+}
+
+#[get("/remux/downloads/{id}/file")]
+pub async fn download_file(
+    headers: headers::HeaderMap,
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let job = owned_job(&state, &session, id).await?;
+    if job.status != DownloadStatus::Completed {
+        return Err(anyhow!("not ready").context_not_found("download is not ready"));
+    }
+    serve_job(&state, &headers, &job).await
+}
+
+/// Serve a finished job's file as an attachment under its download name.
+pub(crate) async fn serve_job(
+    state: &AppState,
+    headers: &headers::HeaderMap,
+    job: &DownloadJob,
+) -> Result<axum::response::Response> {
+    let path = downloads::job_path(
+        &state
+            .ctx
+            .config,
+        job,
+    );
+    let mut response = StreamDescriptor::Local(path)
+        .into_source()
+        .serve(state, headers)
+        .await?
+        .into_response();
+    let safe = job
+        .file_name
+        .replace('"', "")
+        .replace('\\', "");
+    if let Ok(val) =
+        http::HeaderValue::from_str(&format!("attachment; filename=\"{}\"", safe))
+    {
+        response
+            .headers_mut()
+            .insert(http::header::CONTENT_DISPOSITION, val);
+    }
+    Ok(response)
+}
+
+#[delete("/remux/downloads/{id}")]
+pub async fn delete_download(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let job = owned_job(&state, &session, id).await?;
+    // A job being transcoded right now has its file removed by the task once
+    // ffmpeg finishes.
+    let _ = std::fs::remove_file(downloads::job_path(
+        &state
+            .ctx
+            .config,
+        &job,
+    ));
+    DownloadJob::delete(
+        &state
+            .ctx
+            .db,
+        job.id,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::MediaKind,
+        integration_test::{auth_header_with_token, authenticated_server, stable_id},
+    };
+    use http::header::HeaderValue;
+    use serde_json::json;
+
+    async fn seed_season(db: &sqlx::SqlitePool) -> Uuid {
+        let external_ids = crate::db::ExternalIds {
+            imdb: crate::db::NonEmptyString::try_new("tt0306414".to_string()).ok(),
+            ..Default::default()
+        };
+        let mut series = Media {
+            id: stable_id(MediaKind::Series, &external_ids),
+            title: "Show".into(),
+            kind: MediaKind::Series,
+            external_ids,
+            ..Default::default()
+        };
+        series
+            .save(db)
+            .await
+            .unwrap();
+        let mut season = Media {
+            id: crate::common::get_uuid(),
+            title: "Season 1".into(),
+            kind: MediaKind::Season,
+            parent_id: Some(series.id),
+            grandparent_id: Some(series.id),
+            idx: Some(1),
+            ..Default::default()
+        };
+        season
+            .save(db)
+            .await
+            .unwrap();
+        for idx in 1..=2 {
+            let mut episode = Media {
+                id: crate::common::get_uuid(),
+                title: format!("Episode {idx}"),
+                kind: MediaKind::Episode,
+                parent_id: Some(season.id),
+                grandparent_id: Some(series.id),
+                parent_idx: Some(1),
+                idx: Some(idx),
+                ..Default::default()
+            };
+            episode
+                .save(db)
+                .await
+                .unwrap();
+        }
+        season.id
+    }
+
+    #[tokio::test]
+    async fn a_season_download_queues_every_episode() {
+        let (server, guard, token) = authenticated_server().await;
+        let season = seed_season(
+            &guard
+                .0
+                .db,
+        )
+        .await;
+        let auth = HeaderValue::from_str(&auth_header_with_token(&token)).unwrap();
+
+        let resp = server
+            .post("/remux/downloads")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .json(&json!({ "itemId": season, "profile": { "maxHeight": 720 } }))
+            .await;
+        resp.assert_status(StatusCode::CREATED);
+        let jobs: Vec<serde_json::Value> = resp.json();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0]["name"], "S01E01 - Episode 1.mp4");
+        assert_eq!(jobs[0]["profile"]["maxHeight"], 720);
+
+        let id = jobs[0]["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        server
+            .delete(&format!("/remux/downloads/{id}"))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        let list: Vec<serde_json::Value> = server
+            .get("/remux/downloads")
+            .add_header(http::header::AUTHORIZATION, auth)
+            .await
+            .json();
+        assert_eq!(list.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn an_unsupported_container_is_rejected() {
+        let (server, guard, token) = authenticated_server().await;
+        let season = seed_season(
+            &guard
+                .0
+                .db,
+        )
+        .await;
+
+        server
+            .post("/remux/downloads")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&token)).unwrap(),
+            )
+            .json(&json!({ "itemId": season, "profile": { "container": "avi" } }))
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
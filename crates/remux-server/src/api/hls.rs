@@ -7,21 +7,23 @@ use axum::{
 };
 use axum_anyhow::ApiResult as Result;
 use axum_extra::extract::Query;
-use http::{Response, StatusCode};
+use http::{HeaderMap, Response, StatusCode};
 use remux_macros::get;
 use tokio_util::io::ReaderStream;
 use tracing::{debug, error, info, trace, warn};
 use uuid::Uuid;
 
-use remux_sdks::remux::HardwareAccelerationType;
+use remux_sdks::remux::{EncodingOptions, HardwareAccelerationType};
 
 use crate::{
-    AppState, IntoApiError, OptionExt, ResultExt, api, common,
+    AppState, IntoApiError, OptionExt, ResultExt, api, base_url, common,
     common::{TickUnit, ToRunTimeTicks},
     db,
     db::auth,
     playback::{
+        audio::{AudioFormat, AudioTranscode},
         hw_accel,
+        segment_map::{SegmentMap, SegmentPlan},
         session::{TranscodeSession, TranscodeState},
     },
 };
@@ -33,12 +35,14 @@ static TRANSCODE_CREATE_LOCKS: crate::keyed_lock::KeyedLock<String> =
     crate::keyed_lock::KeyedLock::new();
 
 /// Shared session setup: look up or create the transcode session for an HLS
-/// request. Returns the session handle and the resolved play_session_id.
+/// (or, with `dash`, DASH) request. Returns the session handle and the
+/// resolved play_session_id.
 async fn create_hls_session(
     state: &AppState,
     auth: &auth::AuthSession,
     id: Uuid,
     q: &api::HlsVideoQuery,
+    dash: bool,
 ) -> Result<(Arc<tokio::sync::RwLock<TranscodeSession>>, String)> {
     let play_session_id = q
         .play_session_id
@@ -240,6 +244,9 @@ async fn create_hls_session(
         };
         let is_live =
             resolved_media.kind == db::MediaKind::TvChannel || parent_is_tv_channel;
+        if dash && is_live {
+            None::<()>.context_bad_request("DASH is not available for live streams")?;
+        }
 
         // --- Why we force audio transcoding for live channels ---
         //
@@ -361,6 +368,40 @@ async fn create_hls_session(
             s.codec
                 .clone()
         });
+        // Music has no video to carry, so tracks go through the audio-only
+        // pipeline when the client asked for a codec it encodes.
+        let audio_transcode = if source_video_stream.is_none() && !is_live && !dash {
+            AudioFormat::parse(&audio_codec).map(|format| AudioTranscode {
+                format,
+                bitrate: format.bitrate(
+                    q.audio_bit_rate
+                        .map(|b| b as u32)
+                        .or(q
+                            .max_streaming_bitrate
+                            .map(|b| b.clamp(0, u32::MAX as i64) as u32)),
+                ),
+                channels: q
+                    .max_audio_channels
+                    .map(|c| c as u32),
+                sample_rate: q
+                    .audio_sample_rate
+                    .map(|r| r as u32),
+                replay_gain: encoding_opts_hls
+                    .apply_replay_gain
+                    .unwrap_or(false)
+                    .then(|| {
+                        resolved_media
+                            .normalization_gain
+                            .or(media.normalization_gain)
+                    })
+                    .flatten(),
+                normalize_loudness: encoding_opts_hls
+                    .normalize_audio_loudness
+                    .unwrap_or(false),
+            })
+        } else {
+            None
+        };
         let burn_subtitle =
             q.subtitle_method == Some(api::SubtitleDeliveryMethod::Encode);
         let session_video_bitrate = if video_codec == "copy" {
@@ -390,6 +431,27 @@ async fn create_hls_session(
                 hw => Some(hw.to_string()),
             }
         };
+        // The eager transcode below is the top rung ("main"); lower rungs
+        // start on their first segment request.
+        let renditions = if encoding_opts_hls
+            .enable_adaptive_bitrate_streaming
+            .unwrap_or(false)
+            && q.enable_adaptive_bitrate_streaming != Some(false)
+            && video_codec != "copy"
+            && !is_live
+        {
+            crate::playback::engine::abr_renditions(
+                source_video_width,
+                source_video_height,
+                q.max_width
+                    .map(|v| v as u32),
+                q.max_height
+                    .map(|v| v as u32),
+                session_video_bitrate,
+            )
+        } else {
+            Vec::new()
+        };
         let session = TranscodeSession::new(
             play_session_id.clone(),
             id,
@@ -422,6 +484,10 @@ async fn create_hls_session(
             source_frame_rate,
             session_video_bitrate,
             session_hw_accel,
+            dash,
+            audio_transcode.clone(),
+            renditions,
+            None,
         );
 
         state
@@ -519,6 +585,8 @@ async fn create_hls_session(
                 .h265_crf
                 .unwrap_or(28),
             is_live,
+            fmp4: dash,
+            audio: audio_transcode,
             normalize_audio_loudness: encoding_opts
                 .normalize_audio_loudness
                 .unwrap_or(false),
@@ -588,15 +656,44 @@ async fn create_hls_session(
 pub async fn master_hls_video(
     State(state): State<AppState>,
     auth: auth::AuthSession,
+    headers: HeaderMap,
     Path(id): Path<Uuid>,
     Query(q): Query<api::HlsVideoQuery>,
 ) -> Result<impl IntoResponse> {
     debug!("master_hls_video: item_id={}, q={:?}", id, q);
-    let (session, _) = match create_hls_session(&state, &auth, id, &q).await {
+    master_hls_inner(state, auth, &headers, id, q).await
+}
+
+/// Music HLS. Tracks share the video session machinery; `create_hls_session`
+/// hands them to the audio-only pipeline, whose fMP4 segments keep the
+/// encoder delay for gapless playback.
+#[get("/audio/{id}/master.m3u8")]
+pub async fn master_hls_audio(
+    State(state): State<AppState>,
+    auth: auth::AuthSession,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Query(q): Query<api::HlsVideoQuery>,
+) -> Result<impl IntoResponse> {
+    debug!("master_hls_audio: item_id={}, q={:?}", id, q);
+    master_hls_inner(state, auth, &headers, id, q).await
+}
+
+async fn master_hls_inner(
+    state: AppState,
+    auth: auth::AuthSession,
+    headers: &HeaderMap,
+    id: Uuid,
+    q: api::HlsVideoQuery,
+) -> Result<Response<Body>> {
+    let (session, _) = match create_hls_session(&state, &auth, id, &q, false).await {
         Ok(s) => s,
         Err(_) => {
-            return Ok(axum::response::Redirect::temporary("/videos/no-streams")
-                .into_response());
+            return Ok(axum::response::Redirect::temporary(&base_url::prefixed(
+                headers,
+                "/videos/no-streams",
+            ))
+            .into_response());
         }
     };
     let session_read = session
@@ -612,6 +709,30 @@ pub async fn master_hls_video(
         .unwrap())
 }
 
+/// MPEG-DASH manifest over the same transcode, for clients whose transcoding
+/// profile asks for the `dash` protocol. Segments are always fMP4 and are
+/// fetched through the HLS segment routes.
+#[get("/videos/{id}/master.mpd")]
+pub async fn master_dash_video(
+    State(state): State<AppState>,
+    auth: auth::AuthSession,
+    Path(id): Path<Uuid>,
+    Query(q): Query<api::HlsVideoQuery>,
+) -> Result<impl IntoResponse> {
+    debug!("master_dash_video: item_id={}, q={:?}", id, q);
+    let (session, _) = create_hls_session(&state, &auth, id, &q, true).await?;
+    let session_read = session
+        .read()
+        .await;
+    let manifest = crate::playback::engine::generate_dash_manifest(&session_read);
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/dash+xml")
+        .header("Cache-Control", "no-cache, no-store")
+        .body(Body::from(manifest))
+        .unwrap())
+}
+
 /// Safari/iOS live TV endpoint: creates the transcode session and returns the
 /// variant playlist directly so the player gets segment URLs without a
 /// master→variant redirect.
@@ -623,11 +744,21 @@ pub async fn live_hls_video(
     Query(mut q): Query<api::HlsVideoQuery>,
 ) -> Result<impl IntoResponse> {
     debug!("live_hls_video: item_id={}, q={:?}", id, q);
-    let (_, play_session_id) = create_hls_session(&state, &auth, id, &q).await?;
+    let (_, play_session_id) = create_hls_session(&state, &auth, id, &q, false).await?;
     q.play_session_id = Some(play_session_id);
     variant_hls_video_inner(state, q).await
 }
 
+/// Variant playlist of a music HLS session.
+#[get("/audio/{id}/main.m3u8")]
+pub async fn variant_hls_audio(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Query(q): Query<api::HlsVideoQuery>,
+) -> Result<impl IntoResponse> {
+    variant_hls_video_inner(state, q).await
+}
+
 /// Variant HLS playlist - alternate URL used by some clients.
 #[get("/videos/{id}/main.m3u8")]
 pub async fn variant_hls_video_alt(
@@ -801,7 +932,7 @@ pub async fn hls_segment(
     Query(q): Query<api::HlsVideoQuery>,
 ) -> Result<impl IntoResponse> {
     let segment_id = strip_segment_extension(&segment_file);
-    hls_segment_inner(state, segment_id, q).await
+    hls_segment_inner(state, segment_id, q, None).await
 }
 
 /// Segment route at the same level as main.m3u8 — browsers resolve bare
@@ -813,7 +944,7 @@ pub async fn hls_segment_flat(
     Query(q): Query<api::HlsVideoQuery>,
 ) -> Result<impl IntoResponse> {
     let segment_id = strip_segment_extension(&segment_file);
-    hls_segment_inner(state, segment_id, q).await
+    hls_segment_inner(state, segment_id, q, None).await
 }
 
 /// Jellyfin-compatible HLS segment route: /Videos/{id}/hls1/{playlistId}/{segmentFile}
@@ -824,7 +955,81 @@ pub async fn hls1_segment(
     Query(q): Query<api::HlsVideoQuery>,
 ) -> Result<impl IntoResponse> {
     let segment_id = strip_segment_extension(&segment_file);
-    hls_segment_inner(state, segment_id, q).await
+    hls_segment_inner(state, segment_id, q, None).await
+}
+
+/// Segments of a music HLS session, next to its main.m3u8.
+#[get("/audio/{id}/{segment_file}")]
+pub async fn hls_audio_segment(
+    State(state): State<AppState>,
+    Path((id, segment_file)): Path<(Uuid, String)>,
+    Query(q): Query<api::HlsVideoQuery>,
+) -> Result<impl IntoResponse> {
+    let segment_id = strip_segment_extension(&segment_file);
+    hls_segment_inner(state, segment_id, q, None).await
+}
+
+/// Jellyfin-compatible music segment route: /Audio/{id}/hls1/{playlistId}/{segmentFile}
+#[get("/audio/{id}/hls1/{playlist_id}/{segment_file}")]
+pub async fn hls1_audio_segment(
+    State(state): State<AppState>,
+    Path((id, _playlist_id, segment_file)): Path<(Uuid, String, String)>,
+    Query(q): Query<api::HlsVideoQuery>,
+) -> Result<impl IntoResponse> {
+    let segment_id = strip_segment_extension(&segment_file);
+    hls_segment_inner(state, segment_id, q, None).await
+}
+
+/// Variant playlist of a lower ABR rendition listed in the master playlist.
+#[get("/videos/{id}/abr/{rendition}/stream.m3u8")]
+pub async fn abr_variant_hls_video(
+    State(state): State<AppState>,
+    Path((id, rendition)): Path<(Uuid, String)>,
+    Query(q): Query<api::HlsVideoQuery>,
+) -> Result<impl IntoResponse> {
+    let play_session_id = q
+        .play_session_id
+        .context_not_found("PlaySessionId is required")?;
+    let session = state
+        .ctx
+        .sessions
+        .get_transcode(&play_session_id)
+        .context_not_found("transcode session not found")?;
+    state
+        .ctx
+        .sessions
+        .ping(&play_session_id);
+
+    let session_read = session
+        .read()
+        .await;
+    session_read
+        .renditions
+        .iter()
+        .skip(1)
+        .find(|r| r.name == rendition)
+        .context_not_found("rendition not found")?;
+    // Segment timing is the same on every rung, so the main playlist's
+    // segment URLs resolve against this one's directory.
+    let content = crate::playback::engine::generate_variant_playlist(&session_read, "");
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/vnd.apple.mpegurl")
+        .header("Cache-Control", "no-cache, no-store")
+        .body(Body::from(content))
+        .unwrap())
+}
+
+/// Segments of a lower ABR rendition.
+#[get("/videos/{id}/abr/{rendition}/{segment_file}")]
+pub async fn abr_hls_segment(
+    State(state): State<AppState>,
+    Path((id, rendition, segment_file)): Path<(Uuid, String, String)>,
+    Query(q): Query<api::HlsVideoQuery>,
+) -> Result<impl IntoResponse> {
+    let segment_id = strip_segment_extension(&segment_file);
+    hls_segment_inner(state, segment_id, q, Some(rendition)).await
 }
 
 fn strip_segment_extension(filename: &str) -> String {
@@ -834,30 +1039,6 @@ fn strip_segment_extension(filename: &str) -> String {
         .unwrap_or_else(|| filename.to_string())
 }
 
-/// Find the highest segment index currently on disk in `dir`.
-fn get_current_transcoding_index(dir: &std::path::Path) -> Option<u32> {
-    let entries = std::fs::read_dir(dir).ok()?;
-    let mut max_idx: Option<u32> = None;
-    for entry in entries.flatten() {
-        let name = entry.file_name();
-        let name = name.to_string_lossy();
-        // Accept both MPEG-TS (.ts) and fMP4 (.m4s) segment files.
-        if let Some(idx_str) = name
-            .strip_suffix(".ts")
-            .or_else(|| name.strip_suffix(".m4s"))
-            .and_then(|s| {
-                s.rsplit('_')
-                    .next()
-            })
-        {
-            if let Ok(idx) = idx_str.parse::<u32>() {
-                max_idx = Some(max_idx.map_or(idx, |m: u32| m.max(idx)));
-            }
-        }
-    }
-    max_idx
-}
-
 /// Serve a complete on-disk file with a `Content-Length` header.
 ///
 /// HLS init/fragments are always fetched whole by players, so byte-range
@@ -921,26 +1102,285 @@ async fn wait_for_file_ready(
     matches!(tokio::fs::metadata(path).await, Ok(m) if m.len() > 0)
 }
 
+/// Seek position of a segment: the `runtimeTicks` our VOD playlist puts on
+/// each segment URL (cumulative ticks to its start), else
+/// `index * segment_length`.
+fn segment_start_ticks(q: &api::HlsVideoQuery, idx: u32, segment_length: u32) -> i64 {
+    q.runtime_ticks
+        .unwrap_or_else(|| {
+            (idx as i64 * segment_length as i64)
+                .to_ticks(TickUnit::Seconds)
+                .unwrap_or(0)
+        })
+}
+
+/// Transcode parameters for (re)starting `s` at `start_time_ticks`. A
+/// rendition session encodes at its rung's size and bitrate; anything else
+/// follows the client's limits.
+fn segment_params(
+    s: &TranscodeSession,
+    q: &api::HlsVideoQuery,
+    encoding_opts: &EncodingOptions,
+    start_time_ticks: i64,
+) -> crate::playback::engine::TranscodeParams {
+    let rendition = s
+        .rendition
+        .as_ref();
+    crate::playback::engine::TranscodeParams {
+        input_url: s
+            .input_url
+            .clone(),
+        output_dir: s
+            .output_dir
+            .clone(),
+        video_codec: s
+            .video_codec
+            .clone(),
+        audio_codec: s
+            .audio_codec
+            .clone(),
+        segment_length: s.segment_length,
+        start_time_ticks: Some(start_time_ticks),
+        max_width: rendition
+            .map(|r| r.width)
+            .or(q
+                .max_width
+                .map(|v| v as u32)),
+        max_height: rendition
+            .map(|r| r.height)
+            .or(q
+                .max_height
+                .map(|v| v as u32)),
+        video_bitrate: rendition
+            .map(|r| r.video_bitrate)
+            .or(q
+                .video_bit_rate
+                .map(|v| v as u32)),
+        audio_bitrate: q
+            .audio_bit_rate
+            .map(|v| v as u32),
+        audio_channels: if s.audio_codec == "copy" {
+            None
+        } else {
+            Some(2)
+        },
+        audio_stream_index: s.audio_stream_index,
+        subtitle_stream_index: s.subtitle_stream_index,
+        burn_subtitle: s.burn_subtitle,
+        subtitle_width: None,
+        subtitle_height: None,
+        encoding_preset: encoding_opts.encoding_preset,
+        source_video_codec: s
+            .source_video_codec
+            .clone(),
+        source_audio_codec: s
+            .source_audio_codec
+            .clone(),
+        accelerator: hw_accel::from_encoding_opts(encoding_opts),
+        source_video_range_type: s.source_video_range_type,
+        enable_tonemapping: encoding_opts
+            .enable_tonemapping
+            .unwrap_or(false),
+        enable_vpp_tonemapping: encoding_opts
+            .enable_vpp_tonemapping
+            .unwrap_or(false),
+        tonemapping_algorithm: encoding_opts
+            .tonemapping_algorithm
+            .clone()
+            .unwrap_or_else(|| "hable".to_string()),
+        tonemapping_desat: encoding_opts
+            .tonemapping_desat
+            .unwrap_or(0.0),
+        tonemapping_peak: encoding_opts
+            .tonemapping_peak
+            .unwrap_or(0.0),
+        allow_hevc_encoding: encoding_opts
+            .allow_hevc_encoding
+            .unwrap_or(false),
+        allow_av1_encoding: encoding_opts
+            .allow_av1_encoding
+            .unwrap_or(false),
+        h264_crf: encoding_opts
+            .h264_crf
+            .unwrap_or(23),
+        h265_crf: encoding_opts
+            .h265_crf
+            .unwrap_or(28),
+        is_live: false,
+        fmp4: s.dash,
+        audio: s
+            .audio
+            .clone(),
+        normalize_audio_loudness: encoding_opts
+            .normalize_audio_loudness
+            .unwrap_or(false),
+    }
+}
+
+/// Returns the child session encoding rendition `name` of `parent`, starting
+/// it at the requested segment if this is the first request for that rung.
+/// Players only fetch the rungs they switch to, so unused renditions never
+/// cost an ffmpeg process.
+async fn rendition_session(
+    state: &AppState,
+    parent: &Arc<tokio::sync::RwLock<TranscodeSession>>,
+    name: &str,
+    q: &api::HlsVideoQuery,
+    requested_idx: Option<u32>,
+) -> Result<Arc<tokio::sync::RwLock<TranscodeSession>>> {
+    let play_session_id = parent
+        .read()
+        .await
+        .id
+        .clone();
+    let _create_guard = TRANSCODE_CREATE_LOCKS
+        .lock(format!("{play_session_id}/{name}"))
+        .await;
+
+    let (child, start_time_ticks) = {
+        let mut p = parent
+            .write()
+            .await;
+        if let Some(existing) = p
+            .rendition_sessions
+            .get(name)
+        {
+            return Ok(existing.clone());
+        }
+        let rendition = p
+            .renditions
+            .iter()
+            .skip(1)
+            .find(|r| r.name == name)
+            .cloned()
+            .context_not_found("rendition not found")?;
+        let start_time_ticks =
+            segment_start_ticks(q, requested_idx.unwrap_or(0), p.segment_length);
+        let child = TranscodeSession::new(
+            p.id.clone(),
+            p.item_id,
+            p.media_source_id,
+            p.input_url
+                .clone(),
+            p.output_dir
+                .join(name),
+            p.video_codec
+                .clone(),
+            p.audio_codec
+                .clone(),
+            p.audio_stream_index,
+            p.subtitle_stream_index,
+            p.burn_subtitle,
+            p.segment_length,
+            p.transcode_reasons
+                .clone(),
+            p.runtime_ticks,
+            p.is_live,
+            p.source_video_codec
+                .clone(),
+            p.source_audio_codec
+                .clone(),
+            p.source_video_profile
+                .clone(),
+            p.source_video_level,
+            p.source_video_range_type,
+            p.source_video_width,
+            p.source_video_height,
+            p.source_frame_rate,
+            Some(rendition.video_bitrate),
+            p.hardware_acceleration_type
+                .clone(),
+            p.dash,
+            p.audio
+                .clone(),
+            Vec::new(),
+            Some(rendition),
+        );
+        p.rendition_sessions
+            .insert(name.to_string(), child.clone());
+        (child, start_time_ticks)
+    };
+
+    let encoding_opts = crate::db::Settings::get_encoding_config(
+        &state
+            .ctx
+            .db,
+    )
+    .await
+    .unwrap_or_default();
+    let params = {
+        let mut s = child
+            .write()
+            .await;
+        s.start_time_secs = (start_time_ticks / 10_000_000) as u32;
+        segment_params(&s, q, &encoding_opts, start_time_ticks)
+    };
+    info!(
+        play_session_id = %play_session_id,
+        rendition = name,
+        resolution = ?params.max_width.zip(params.max_height),
+        video_bitrate = ?params.video_bitrate,
+        start_secs = start_time_ticks / 10_000_000,
+        "ABR rendition started"
+    );
+    let session_clone = child.clone();
+    tokio::spawn(async move {
+        if let Err(e) =
+            crate::playback::engine::start_transcode(session_clone, params).await
+        {
+            error!("Rendition transcode failed: {:#}", e);
+        }
+    });
+    Ok(child)
+}
+
 async fn hls_segment_inner(
     state: AppState,
     segment_id: String,
     q: api::HlsVideoQuery,
+    rendition: Option<String>,
 ) -> Result<impl IntoResponse> {
     let play_session_id = q
         .play_session_id
+        .clone()
         .context_not_found("PlaySessionId is required")?;
 
     trace!(
         segment_id = %segment_id,
         play_session_id = %play_session_id,
         runtime_ticks = ?q.runtime_ticks,
+        ?rendition,
         "HLS segment request"
     );
 
-    let session = state
+    // Parse the requested segment index from the filename.
+    let requested_idx: Option<u32> = segment_id
+        .rsplit('_')
+        .next()
+        .and_then(|n| {
+            n.parse::<u32>()
+                .ok()
+        });
+
+    let mut session = state
         .ctx
         .sessions
         .get_transcode(&play_session_id);
+    // Lower ABR rungs are served by child sessions of the play session's
+    // transcode, writing to a sub-directory of its output.
+    let mut disk_dir = state
+        .ctx
+        .sessions
+        .base_dir()
+        .join(&play_session_id);
+    if let Some(name) = rendition.as_deref() {
+        disk_dir = disk_dir.join(name);
+        if let Some(parent) = session.take() {
+            session = Some(
+                rendition_session(&state, &parent, name, &q, requested_idx).await?,
+            );
+        }
+    }
 
     // The fMP4 init segment is served at "init.mp4" — strip_segment_extension
     // reduces that to "init", so we detect it here and serve it directly.
@@ -950,6 +1390,7 @@ async fn hls_segment_inner(
                 .read()
                 .await
                 .init_segment_path(),
+            None if rendition.is_some() => disk_dir.join("init.mp4"),
             None => state
                 .ctx
                 .sessions
@@ -984,253 +1425,106 @@ async fn hls_segment_inner(
             .read()
             .await
             .segment_path(&segment_id),
+        None if rendition.is_some() => disk_dir.join(format!("{segment_id}.ts")),
         None => state
             .ctx
             .sessions
             .segment_path(&play_session_id, &segment_id),
     };
 
-    // Parse the requested segment index from the filename.
-    let requested_idx: Option<u32> = segment_id
-        .rsplit('_')
-        .next()
-        .and_then(|n| {
-            n.parse::<u32>()
-                .ok()
-        });
-
-    if let Some(ref session) = session {
-        // Update playback position for the buffer monitor.
-        if let Some(idx) = requested_idx {
-            use std::sync::atomic::Ordering;
-            let s = session
-                .read()
-                .await;
-            let prev = s
-                .last_segment_index
-                .load(Ordering::Relaxed);
-            if idx > prev {
-                s.last_segment_index
-                    .store(idx, Ordering::Relaxed);
-            }
-        }
+    if let (Some(session), Some(idx)) = (&session, requested_idx) {
+        // The buffer monitor throttles ffmpeg against the client's last request.
+        session
+            .read()
+            .await
+            .last_segment_index
+            .store(idx, std::sync::atomic::Ordering::Relaxed);
     }
 
-    // If the segment doesn't exist and we have a live session, check whether
-    // FFmpeg needs to be restarted at a different position (like Jellyfin does).
+    // A segment missing from disk is either about to be written by the
+    // running encoder or needs a new encoder started at it (like Jellyfin
+    // does). Segments other runs left on disk stay valid either way.
     if !segment_path.exists() {
         if let (Some(session), Some(requested_idx)) = (&session, requested_idx) {
-            let s = session
-                .read()
-                .await;
-            let output_dir = s
-                .output_dir
-                .clone();
-            let segment_length = s.segment_length;
-            let current_idx = get_current_transcoding_index(&output_dir);
-            let segment_gap_threshold = 24 / segment_length;
-
-            let needs_restart = match current_idx {
-                None => {
-                    // No segments on disk yet. If FFmpeg is still running
-                    // (Starting/Running), just fall through to the wait loop —
-                    // killing it here causes an infinite restart cycle.
-                    matches!(
-                        s.state,
-                        TranscodeState::Error(_) | TranscodeState::Complete
-                    )
-                }
-                Some(cur) if requested_idx < cur => true, // seeking backward
-                Some(cur)
-                    if requested_idx.saturating_sub(cur) > segment_gap_threshold =>
-                {
-                    true
-                } // too far ahead
-                _ => false, // within range — just wait for FFmpeg
-            };
-
-            if needs_restart {
-                // Guard against concurrent restart: only proceed if FFmpeg
-                // is actually running (kill_tx is Some). If another request
-                // already killed it and started a new one, just wait.
-                let has_running_ffmpeg = s
-                    .kill_tx
-                    .is_some();
-                if !has_running_ffmpeg {
-                    drop(s);
-                    // Another request already restarted — fall through to wait loop.
+            // Decide under the write lock so concurrent requests agree on a
+            // single new encoder run.
+            let restart = {
+                let mut s = session
+                    .write()
+                    .await;
+                let segment_length = s
+                    .segment_length
+                    .max(1);
+                let plan = if s.is_live {
+                    SegmentPlan::Wait
                 } else {
-                    debug!(
+                    SegmentMap::scan(&s.output_dir).plan(
                         requested_idx,
-                        ?current_idx,
-                        segment_gap_threshold,
-                        "Segment-driven transcode restart"
-                    );
-
-                    // Gather params we need before dropping the read lock.
-                    let input_url = s
-                        .input_url
-                        .clone();
-                    let video_codec = s
-                        .video_codec
-                        .clone();
-                    let audio_codec = s
-                        .audio_codec
-                        .clone();
-                    let audio_stream_index = s.audio_stream_index;
-                    let subtitle_stream_index = s.subtitle_stream_index;
-                    let burn_subtitle = s.burn_subtitle;
-                    drop(s);
-
-                    // Kill running FFmpeg and clean up stale segments (params
-                    // like bitrate/codec may change, so old segments are invalid).
-                    {
-                        let (kill_tx, wait_done) = {
-                            let mut s = session
-                                .write()
-                                .await;
-                            (
-                                s.kill_tx
-                                    .take(),
-                                s.wait_done
-                                    .clone(),
-                            )
-                        };
-                        if let Some(kill_tx) = kill_tx {
-                            let notification = wait_done.notified();
-                            let _ = kill_tx.send(());
-                            notification.await;
-                        }
-                    }
-                    let _ = std::fs::remove_dir_all(&output_dir);
-                    let _ = std::fs::create_dir_all(&output_dir);
-
+                        s.encoder_start_segment(),
+                        24 / segment_length,
+                    )
+                };
+                if plan == SegmentPlan::Encode {
                     // Calculate the seek position from the runtimeTicks query param
                     // (cumulative ticks to start of this segment) provided by our
                     // server-generated VOD playlist. Fall back to segment_index * segment_length.
-                    let start_time_ticks = q
-                        .runtime_ticks
-                        .unwrap_or_else(|| {
-                            (requested_idx as i64 * segment_length as i64)
-                                .to_ticks(TickUnit::Seconds)
-                                .unwrap_or(0)
-                        });
-
-                    let encoding_opts = crate::db::Settings::get_encoding_config(
-                        &state
-                            .ctx
-                            .db,
-                    )
-                    .await
-                    .unwrap_or_default();
-                    let params = crate::playback::engine::TranscodeParams {
-                        input_url,
-                        output_dir: output_dir.clone(),
-                        video_codec,
-                        audio_codec: audio_codec.clone(),
-                        segment_length,
-                        start_time_ticks: Some(start_time_ticks),
-                        max_width: q
-                            .max_width
-                            .map(|v| v as u32),
-                        max_height: q
-                            .max_height
-                            .map(|v| v as u32),
-                        video_bitrate: q
-                            .video_bit_rate
-                            .map(|v| v as u32),
-                        audio_bitrate: q
-                            .audio_bit_rate
-                            .map(|v| v as u32),
-                        audio_channels: if audio_codec == "copy" {
-                            None
-                        } else {
-                            Some(2)
-                        },
-                        audio_stream_index,
-                        subtitle_stream_index,
-                        burn_subtitle,
-                        subtitle_width: None,
-                        subtitle_height: None,
-                        encoding_preset: encoding_opts.encoding_preset,
-                        source_video_codec: session
-                            .read()
-                            .await
-                            .source_video_codec
-                            .clone(),
-                        source_audio_codec: session
-                            .read()
-                            .await
-                            .source_audio_codec
+                    let start_time_ticks =
+                        segment_start_ticks(&q, requested_idx, segment_length);
+                    debug!(
+                        requested_idx,
+                        encoder_start = ?s.encoder_start_segment(),
+                        start_time_ticks,
+                        "Starting encoder at requested segment"
+                    );
+                    s.state = TranscodeState::Starting;
+                    let _ = s
+                        .state_tx
+                        .send(TranscodeState::Starting);
+                    s.start_time_secs = (start_time_ticks / 10_000_000) as u32;
+                    Some((
+                        s.kill_tx
+                            .take(),
+                        s.wait_done
                             .clone(),
-                        accelerator: hw_accel::from_encoding_opts(&encoding_opts),
-                        source_video_range_type: session
-                            .read()
+                        start_time_ticks,
+                    ))
+                } else {
+                    None
+                }
+            };
+
+            if let Some((kill_tx, wait_done, start_time_ticks)) = restart {
+                if let Some(kill_tx) = kill_tx {
+                    let notification = wait_done.notified();
+                    let _ = kill_tx.send(());
+                    notification.await;
+                }
+
+                let encoding_opts = crate::db::Settings::get_encoding_config(
+                    &state
+                        .ctx
+                        .db,
+                )
+                .await
+                .unwrap_or_default();
+                let params = segment_params(
+                    &*session
+                        .read()
+                        .await,
+                    &q,
+                    &encoding_opts,
+                    start_time_ticks,
+                );
+                let session_clone = session.clone();
+                tokio::spawn(async move {
+                    if let Err(e) =
+                        crate::playback::engine::start_transcode(session_clone, params)
                             .await
-                            .source_video_range_type,
-                        enable_tonemapping: encoding_opts
-                            .enable_tonemapping
-                            .unwrap_or(false),
-                        enable_vpp_tonemapping: encoding_opts
-                            .enable_vpp_tonemapping
-                            .unwrap_or(false),
-                        tonemapping_algorithm: encoding_opts
-                            .tonemapping_algorithm
-                            .unwrap_or_else(|| "hable".to_string()),
-                        tonemapping_desat: encoding_opts
-                            .tonemapping_desat
-                            .unwrap_or(0.0),
-                        tonemapping_peak: encoding_opts
-                            .tonemapping_peak
-                            .unwrap_or(0.0),
-                        allow_hevc_encoding: encoding_opts
-                            .allow_hevc_encoding
-                            .unwrap_or(false),
-                        allow_av1_encoding: encoding_opts
-                            .allow_av1_encoding
-                            .unwrap_or(false),
-                        h264_crf: encoding_opts
-                            .h264_crf
-                            .unwrap_or(23),
-                        h265_crf: encoding_opts
-                            .h265_crf
-                            .unwrap_or(28),
-                        is_live: false,
-                        normalize_audio_loudness: encoding_opts
-                            .normalize_audio_loudness
-                            .unwrap_or(false),
-                    };
-
-                    // Reinitialise the session's state for the new transcode run.
                     {
-                        let mut s = session
-                            .write()
-                            .await;
-                        s.state = TranscodeState::Starting;
-                        let _ = s
-                            .state_tx
-                            .send(TranscodeState::Starting);
-                        s.start_time_secs = (start_time_ticks / 10_000_000) as u32;
-                        s.playback_offset_secs
-                            .store(
-                                s.start_time_secs,
-                                std::sync::atomic::Ordering::Relaxed,
-                            );
+                        error!("Transcode restart failed: {:#}", e);
                     }
-
-                    let session_clone = session.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = crate::playback::engine::start_transcode(
-                            session_clone,
-                            params,
-                        )
-                        .await
-                        {
-                            error!("Transcode restart failed: {:#}", e);
-                        }
-                    });
-                } // else: has_running_ffmpeg
-            } // needs_restart
+                });
+            }
         }
     }
 
@@ -52,6 +52,51 @@ async fn fetch_upstream(url: &str) -> anyhow::Result<(Vec<u8>, String)> {
     Ok((bytes, ct))
 }
 
+/// Read artwork found by an opendal scan, `{addon_id}/{path}`, through the
+/// addon that indexed it.
+async fn fetch_opendal(
+    state: &AppState,
+    reference: &str,
+) -> anyhow::Result<(Vec<u8>, String)> {
+    let (addon_id, path) = reference
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("malformed opendal image path"))?;
+    let addon_id: Uuid = addon_id.parse()?;
+    let addon = state
+        .ctx
+        .addons
+        .get(addon_id)
+        .ok_or_else(|| anyhow::anyhow!("addon not found for image"))?;
+    let stream_cap = addon
+        .stream
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("addon has no stream capability"))?;
+    let resp = stream_cap
+        .serve_stream(
+            &crate::stream::StreamDescriptor::Opendal {
+                addon_id,
+                path: path.to_string(),
+            },
+            &axum::http::HeaderMap::new(),
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("upstream serve failed: {e:?}"))?;
+    let ct = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| {
+            v.to_str()
+                .ok()
+        })
+        .unwrap_or("image/jpeg")
+        .to_string();
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+        .await
+        .map_err(|e| anyhow::anyhow!("image read failed: {e}"))?
+        .to_vec();
+    Ok((bytes, ct))
+}
+
 async fn items_images_inner(
     state: AppState,
     id: Uuid,
@@ -129,6 +174,14 @@ async fn items_images_inner(
                             .await
                             .context_not_found("image file not found")?;
                         (b, ct.to_string(), source_key, false)
+                    } else if let Some(reference) = img
+                        .path
+                        .strip_prefix("opendal://")
+                    {
+                        let (b, ct) = fetch_opendal(&state, reference)
+                            .await
+                            .context_not_found("image file not found")?;
+                        (b, ct, source_key, false)
                     } else {
                         // Always proxy external URLs rather than redirecting — some clients
                         // (e.g. Infuse) do not follow redirects for image requests.
@@ -1920,6 +1920,45 @@ async fn item_for_user(
         base_item.media_sources = Some(vec![source]);
     }
 
+    // Sheets are stored per stream record; key them by the MediaSource id the
+    // client sees, which for the first source is the stamped item id.
+    if fields.map_or(true, |f| f.contains(&api::ItemFields::Trickplay))
+        && matches!(media.kind, db::MediaKind::Movie | db::MediaKind::Episode)
+    {
+        let data_dir = &state
+            .ctx
+            .config
+            .data_dir;
+        let mut trickplay = std::collections::HashMap::new();
+        if let (Some(streams), Some(sources)) = (
+            media
+                .sources
+                .as_deref(),
+            base_item
+                .media_sources
+                .as_deref(),
+        ) {
+            for (stream, source) in streams
+                .iter()
+                .zip(sources)
+            {
+                let widths = crate::trickplay::load_all(data_dir, stream.id).await;
+                if !widths.is_empty() {
+                    trickplay.insert(
+                        source
+                            .id
+                            .simple()
+                            .to_string(),
+                        widths,
+                    );
+                }
+            }
+        }
+        if !trickplay.is_empty() {
+            base_item.trickplay = Some(trickplay);
+        }
+    }
+
     if media.kind == db::MediaKind::Episode {
         if let Some(sid) = media.grandparent_id {
             if let Ok(Some(s)) = db::Media::get_by_id(
@@ -1,4 +1,4 @@
-use crate::{OptionExt, ResultExt};
+use crate::{IntoApiError, OptionExt, ResultExt};
 use axum::{
     Json,
     extract::{Path, State},
@@ -10,11 +10,13 @@ use chrono::{Duration, Utc};
 use http::StatusCode;
 use remux_macros::{delete, get, patch, post, query};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 use crate::{
     AppState, api, db,
     db::auth::{AdminSession, AuthSession},
+    stream::StreamDescriptor,
 };
 
 // --------------------------------------------------------------------------
@@ -202,11 +204,18 @@ pub async fn livetv_programs_recommended(
     )
     .await?;
 
-    let dtos: Vec<_> = result
+    let mut dtos: Vec<_> = result
         .records
         .into_iter()
         .map(|m| api::db_media_to_item(m, false))
         .collect();
+    annotate_timers(
+        &state
+            .ctx
+            .db,
+        &mut dtos,
+    )
+    .await?;
     Ok(Json(api::QueryResult {
         total_record_count: dtos.len() as i64,
         start_index: 0,
@@ -334,11 +343,18 @@ pub async fn livetv_programs(
     )
     .await?;
 
-    let dtos: Vec<_> = result
+    let mut dtos: Vec<_> = result
         .records
         .into_iter()
         .map(|m| api::db_media_to_item(m, false))
         .collect();
+    annotate_timers(
+        &state
+            .ctx
+            .db,
+        &mut dtos,
+    )
+    .await?;
     Ok(Json(api::QueryResult {
         total_record_count: result.total_count as i64,
         start_index: q
@@ -419,11 +435,18 @@ pub async fn livetv_programs_post(
     )
     .await?;
 
-    let dtos: Vec<_> = result
+    let mut dtos: Vec<_> = result
         .records
         .into_iter()
         .map(|m| api::db_media_to_item(m, false))
         .collect();
+    annotate_timers(
+        &state
+            .ctx
+            .db,
+        &mut dtos,
+    )
+    .await?;
     Ok(Json(api::QueryResult {
         total_record_count: result.total_count as i64,
         start_index: body
@@ -434,31 +457,408 @@ pub async fn livetv_programs_post(
 }
 
 // --------------------------------------------------------------------------
-// GET /livetv/seriestimers
+// DVR helpers
+// --------------------------------------------------------------------------
+
+fn require_live_tv_management(session: &AuthSession) -> Result<()> {
+    if session
+        .user
+        .can_manage_live_tv()
+    {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("user may not manage live tv")
+            .context_forbidden("live tv management is not allowed"))
+    }
+}
+
+fn parse_id(id: Option<&str>) -> Option<Uuid> {
+    id.and_then(|s| Uuid::parse_str(s).ok())
+}
+
+async fn channel_names(
+    db: &sqlx::SqlitePool,
+    ids: &[Uuid],
+) -> Result<HashMap<Uuid, String>> {
+    let mut names = HashMap::new();
+    for &id in ids {
+        if names.contains_key(&id) {
+            continue;
+        }
+        if let Some(channel) = db::Media::get_by_id(db, &id).await? {
+            names.insert(
+                id,
+                channel
+                    .custom_name
+                    .unwrap_or(channel.title),
+            );
+        }
+    }
+    Ok(names)
+}
+
+fn timer_to_dto(timer: &db::Timer, channel_name: Option<String>) -> api::TimerInfoDto {
+    api::TimerInfoDto {
+        id: Some(
+            timer
+                .id
+                .simple()
+                .to_string(),
+        ),
+        channel_id: Some(timer.channel_id),
+        channel_name,
+        program_id: timer
+            .program_id
+            .map(|id| {
+                id.simple()
+                    .to_string()
+            }),
+        series_timer_id: timer
+            .series_timer_id
+            .map(|id| {
+                id.simple()
+                    .to_string()
+            }),
+        name: Some(
+            timer
+                .name
+                .clone(),
+        ),
+        overview: timer
+            .overview
+            .clone(),
+        start_date: Some(
+            timer
+                .start_date
+                .and_utc(),
+        ),
+        end_date: Some(
+            timer
+                .end_date
+                .and_utc(),
+        ),
+        pre_padding_seconds: timer.pre_padding_seconds as i32,
+        post_padding_seconds: timer.post_padding_seconds as i32,
+        status: timer
+            .status
+            .into(),
+        run_time_ticks: Some(
+            (timer.end_date - timer.start_date).num_seconds() * 10_000_000,
+        ),
+        ..Default::default()
+    }
+}
+
+fn series_timer_to_dto(
+    series: &db::SeriesTimer,
+    channel_name: Option<String>,
+) -> api::SeriesTimerInfoDto {
+    api::SeriesTimerInfoDto {
+        id: Some(
+            series
+                .id
+                .simple()
+                .to_string(),
+        ),
+        channel_id: Some(series.channel_id),
+        channel_name,
+        program_id: series
+            .program_id
+            .map(|id| {
+                id.simple()
+                    .to_string()
+            }),
+        name: Some(
+            series
+                .name
+                .clone(),
+        ),
+        pre_padding_seconds: series.pre_padding_seconds as i32,
+        post_padding_seconds: series.post_padding_seconds as i32,
+        record_any_channel: series.record_any_channel,
+        keep_up_to: series.keep_up_to as i32,
+        ..Default::default()
+    }
+}
+
+/// Marks guide entries that a timer or series rule will record.
+async fn annotate_timers(
+    db: &sqlx::SqlitePool,
+    items: &mut [api::BaseItemDto],
+) -> Result<()> {
+    let ids: Vec<Uuid> = items
+        .iter()
+        .map(|i| i.id)
+        .collect();
+    let timers = db::Timer::by_program_ids(db, &ids).await?;
+    for item in items.iter_mut() {
+        let Some(timer) = timers.get(&item.id) else {
+            continue;
+        };
+        item.timer_id = Some(
+            timer
+                .id
+                .simple()
+                .to_string(),
+        );
+        item.series_timer_id = timer
+            .series_timer_id
+            .map(|id| {
+                id.simple()
+                    .to_string()
+            });
+        item.has_timer = Some(true);
+        item.has_series_timer = Some(
+            timer
+                .series_timer_id
+                .is_some(),
+        );
+    }
+    Ok(())
+}
+
+async fn get_program(db: &sqlx::SqlitePool, id: Option<Uuid>) -> Result<db::Media> {
+    let program_id = id.context_bad_request("ProgramId is required")?;
+    db::Media::get_by_id(db, &program_id)
+        .await?
+        .filter(|m| m.kind == db::MediaKind::TvProgram)
+        .context_not_found("program not found")
+}
+
+// --------------------------------------------------------------------------
+// GET + POST /livetv/seriestimers
 // --------------------------------------------------------------------------
 
 #[get("/livetv/seriestimers")]
-pub async fn livetv_series_timers(_session: AuthSession) -> Result<impl IntoResponse> {
-    Ok(Json(api::QueryResult::<api::BaseItemDto> {
-        total_record_count: 0,
+pub async fn livetv_series_timers(
+    State(state): State<AppState>,
+    _session: AuthSession,
+) -> Result<impl IntoResponse> {
+    let db = &state
+        .ctx
+        .db;
+    let series = db::SeriesTimer::get_all(db).await?;
+    let ids: Vec<_> = series
+        .iter()
+        .map(|s| s.channel_id)
+        .collect();
+    let names = channel_names(db, &ids).await?;
+    let items: Vec<_> = series
+        .iter()
+        .map(|s| {
+            series_timer_to_dto(
+                s,
+                names
+                    .get(&s.channel_id)
+                    .cloned(),
+            )
+        })
+        .collect();
+    Ok(Json(api::QueryResult {
+        total_record_count: items.len() as i64,
         start_index: 0,
-        items: vec![],
+        items,
     }))
 }
 
+/// Creates a series rule from the program it was started on.
+#[post("/livetv/seriestimers")]
+pub async fn livetv_create_series_timer(
+    State(state): State<AppState>,
+    session: AuthSession,
+    Json(body): Json<api::SeriesTimerInfoDto>,
+) -> Result<impl IntoResponse> {
+    require_live_tv_management(&session)?;
+    let ctx = &state.ctx;
+    let program = get_program(
+        &ctx.db,
+        parse_id(
+            body.program_id
+                .as_deref(),
+        ),
+    )
+    .await?;
+    let series = db::SeriesTimer {
+        id: crate::common::get_uuid(),
+        name: program
+            .title
+            .clone(),
+        channel_id: program
+            .parent_id
+            .context_bad_request("program has no channel")?,
+        program_id: Some(program.id),
+        record_any_channel: body.record_any_channel,
+        pre_padding_seconds: body.pre_padding_seconds as i64,
+        post_padding_seconds: body.post_padding_seconds as i64,
+        keep_up_to: body.keep_up_to as i64,
+        created_at: Utc::now().naive_utc(),
+    };
+    series
+        .save(&ctx.db)
+        .await?;
+    crate::dvr::expand_series_timers(ctx, Utc::now().naive_utc()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 // --------------------------------------------------------------------------
-// GET /livetv/timers
+// GET + POST + DELETE /livetv/seriestimers/{timerId}
 // --------------------------------------------------------------------------
 
+#[get("/livetv/seriestimers/{timer_id}")]
+pub async fn livetv_series_timer(
+    State(state): State<AppState>,
+    _session: AuthSession,
+    Path(timer_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let db = &state
+        .ctx
+        .db;
+    let series = db::SeriesTimer::get(db, &timer_id)
+        .await?
+        .context_not_found("series timer not found")?;
+    let name = channel_names(db, &[series.channel_id])
+        .await?
+        .remove(&series.channel_id);
+    Ok(Json(series_timer_to_dto(&series, name)))
+}
+
+#[post("/livetv/seriestimers/{timer_id}")]
+pub async fn livetv_update_series_timer(
+    State(state): State<AppState>,
+    session: AuthSession,
+    Path(timer_id): Path<Uuid>,
+    Json(body): Json<api::SeriesTimerInfoDto>,
+) -> Result<impl IntoResponse> {
+    require_live_tv_management(&session)?;
+    let ctx = &state.ctx;
+    let mut series = db::SeriesTimer::get(&ctx.db, &timer_id)
+        .await?
+        .context_not_found("series timer not found")?;
+    series.record_any_channel = body.record_any_channel;
+    series.pre_padding_seconds = body.pre_padding_seconds as i64;
+    series.post_padding_seconds = body.post_padding_seconds as i64;
+    series.keep_up_to = body.keep_up_to as i64;
+    series
+        .save(&ctx.db)
+        .await?;
+    crate::dvr::expand_series_timers(ctx, Utc::now().naive_utc()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Deletes the rule and its airings that have not started recording.
+#[delete("/livetv/seriestimers/{timer_id}")]
+pub async fn livetv_delete_series_timer(
+    State(state): State<AppState>,
+    session: AuthSession,
+    Path(timer_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    require_live_tv_management(&session)?;
+    if !db::SeriesTimer::delete(
+        &state
+            .ctx
+            .db,
+        &timer_id,
+    )
+    .await?
+    {
+        return Err(anyhow::anyhow!("series timer {timer_id} not found")
+            .context_not_found("series timer not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --------------------------------------------------------------------------
+// GET + POST /livetv/timers
+// --------------------------------------------------------------------------
+
+#[query]
+#[derive(Debug, Default)]
+pub struct GetTimersQuery {
+    pub channel_id: Option<Uuid>,
+    pub series_timer_id: Option<Uuid>,
+    pub is_active: Option<bool>,
+    pub is_scheduled: Option<bool>,
+}
+
 #[get("/livetv/timers")]
-pub async fn livetv_timers(_session: AuthSession) -> Result<impl IntoResponse> {
-    Ok(Json(api::QueryResult::<api::BaseItemDto> {
-        total_record_count: 0,
+pub async fn livetv_timers(
+    State(state): State<AppState>,
+    _session: AuthSession,
+    Query(q): Query<GetTimersQuery>,
+) -> Result<impl IntoResponse> {
+    let db = &state
+        .ctx
+        .db;
+    let timers = db::Timer::get_by_filter(
+        db,
+        &db::TimerFilter {
+            channel_id: q.channel_id,
+            series_timer_id: q.series_timer_id,
+            active: q.is_active == Some(true) || q.is_scheduled == Some(true),
+        },
+    )
+    .await?;
+    let timers: Vec<_> = timers
+        .into_iter()
+        .filter(|t| match q.is_scheduled {
+            Some(true) => t.status == db::TimerStatus::New,
+            _ => true,
+        })
+        .collect();
+    let ids: Vec<_> = timers
+        .iter()
+        .map(|t| t.channel_id)
+        .collect();
+    let names = channel_names(db, &ids).await?;
+    let items: Vec<_> = timers
+        .iter()
+        .map(|t| {
+            timer_to_dto(
+                t,
+                names
+                    .get(&t.channel_id)
+                    .cloned(),
+            )
+        })
+        .collect();
+    Ok(Json(api::QueryResult {
+        total_record_count: items.len() as i64,
         start_index: 0,
-        items: vec![],
+        items,
     }))
 }
 
+/// Schedules one airing. Re-scheduling a cancelled airing revives its timer.
+#[post("/livetv/timers")]
+pub async fn livetv_create_timer(
+    State(state): State<AppState>,
+    session: AuthSession,
+    Json(body): Json<api::TimerInfoDto>,
+) -> Result<impl IntoResponse> {
+    require_live_tv_management(&session)?;
+    let db = &state
+        .ctx
+        .db;
+    let program = get_program(
+        db,
+        parse_id(
+            body.program_id
+                .as_deref(),
+        ),
+    )
+    .await?;
+    let timer = db::Timer::from_program(
+        &program,
+        body.pre_padding_seconds as i64,
+        body.post_padding_seconds as i64,
+    )
+    .context_bad_request("program has no channel or airing time")?;
+    timer
+        .schedule(db)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 // --------------------------------------------------------------------------
 // GET /livetv/timers/defaults
 // --------------------------------------------------------------------------
@@ -469,36 +869,125 @@ pub struct TimerDefaultsQuery {
     program_id: Option<Uuid>,
 }
 
+/// A series rule prefilled from the program, which clients edit and post
+/// back to either `/timers` or `/seriestimers`.
 #[get("/livetv/timers/defaults")]
 pub async fn livetv_timer_defaults(
+    State(state): State<AppState>,
     _session: AuthSession,
     Query(q): Query<TimerDefaultsQuery>,
 ) -> Result<impl IntoResponse> {
-    #[derive(Serialize)]
-    #[serde(rename_all = "PascalCase")]
-    struct TimerDefaults {
-        id: String,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        program_id: Option<String>,
-        pre_padding_seconds: i32,
-        post_padding_seconds: i32,
-        is_pre_padding_required: bool,
-        is_post_padding_required: bool,
-        priority: i32,
-    }
-    Ok(Json(TimerDefaults {
-        id: String::new(),
-        program_id: q
-            .program_id
-            .map(|id| id.to_string()),
-        pre_padding_seconds: 0,
-        post_padding_seconds: 0,
-        is_pre_padding_required: false,
-        is_post_padding_required: false,
-        priority: 0,
+    let db = &state
+        .ctx
+        .db;
+    let Some(program_id) = q.program_id else {
+        return Ok(Json(api::SeriesTimerInfoDto::default()));
+    };
+    let program = get_program(db, Some(program_id)).await?;
+    let channel_name = match program.parent_id {
+        Some(id) => channel_names(db, &[id])
+            .await?
+            .remove(&id),
+        None => None,
+    };
+    Ok(Json(api::SeriesTimerInfoDto {
+        channel_id: program.parent_id,
+        channel_name,
+        program_id: Some(
+            program
+                .id
+                .simple()
+                .to_string(),
+        ),
+        name: Some(
+            program
+                .title
+                .clone(),
+        ),
+        overview: program
+            .description
+            .clone(),
+        start_date: program
+            .live_start
+            .map(|d| d.and_utc()),
+        end_date: program
+            .live_end
+            .map(|d| d.and_utc()),
+        ..Default::default()
     }))
 }
 
+// --------------------------------------------------------------------------
+// GET + POST + DELETE /livetv/timers/{timerId}
+// --------------------------------------------------------------------------
+
+#[get("/livetv/timers/{timer_id}")]
+pub async fn livetv_timer(
+    State(state): State<AppState>,
+    _session: AuthSession,
+    Path(timer_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let db = &state
+        .ctx
+        .db;
+    let timer = db::Timer::get(db, &timer_id)
+        .await?
+        .context_not_found("timer not found")?;
+    let name = channel_names(db, &[timer.channel_id])
+        .await?
+        .remove(&timer.channel_id);
+    Ok(Json(timer_to_dto(&timer, name)))
+}
+
+/// Updates a timer's padding.
+#[post("/livetv/timers/{timer_id}")]
+pub async fn livetv_update_timer(
+    State(state): State<AppState>,
+    session: AuthSession,
+    Path(timer_id): Path<Uuid>,
+    Json(body): Json<api::TimerInfoDto>,
+) -> Result<impl IntoResponse> {
+    require_live_tv_management(&session)?;
+    let db = &state
+        .ctx
+        .db;
+    db::Timer::get(db, &timer_id)
+        .await?
+        .context_not_found("timer not found")?;
+    db::Timer::set_padding(
+        db,
+        &timer_id,
+        body.pre_padding_seconds as i64,
+        body.post_padding_seconds as i64,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Cancels a timer. A running recording stops and keeps what it wrote.
+#[delete("/livetv/timers/{timer_id}")]
+pub async fn livetv_cancel_timer(
+    State(state): State<AppState>,
+    session: AuthSession,
+    Path(timer_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    require_live_tv_management(&session)?;
+    let ctx = &state.ctx;
+    let timer = db::Timer::get(&ctx.db, &timer_id)
+        .await?
+        .context_not_found("timer not found")?;
+    // A running recording marks itself cancelled once ffmpeg has stopped.
+    if !ctx
+        .dvr
+        .stop(&timer.id)
+        && timer.status == db::TimerStatus::New
+    {
+        db::Timer::set_status(&ctx.db, &timer.id, db::TimerStatus::Cancelled, None)
+            .await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
 // --------------------------------------------------------------------------
 // GET /livetv/recordings/folders
 // --------------------------------------------------------------------------
@@ -518,12 +1007,106 @@ pub async fn livetv_recording_folders(
 // GET /livetv/recordings
 // --------------------------------------------------------------------------
 
+#[query]
+#[derive(Debug, Default)]
+pub struct GetRecordingsQuery {
+    pub channel_id: Option<Uuid>,
+    pub series_timer_id: Option<Uuid>,
+    pub is_in_progress: Option<bool>,
+    pub start_index: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+/// Recording items with their channel and timer filled in.
+async fn recording_items(
+    db: &sqlx::SqlitePool,
+    recordings: Vec<db::Media>,
+    timers: &HashMap<Uuid, db::Timer>,
+) -> Result<Vec<api::BaseItemDto>> {
+    let ids: Vec<_> = recordings
+        .iter()
+        .filter_map(|m| timers.get(&m.id))
+        .map(|t| t.channel_id)
+        .collect();
+    let names = channel_names(db, &ids).await?;
+    Ok(recordings
+        .into_iter()
+        .map(|m| {
+            let timer = timers.get(&m.id);
+            let mut item = api::db_media_to_item(m, false);
+            if let Some(timer) = timer {
+                item.channel_id = Some(timer.channel_id);
+                item.channel_name = names
+                    .get(&timer.channel_id)
+                    .cloned();
+                item.timer_id = Some(
+                    timer
+                        .id
+                        .simple()
+                        .to_string(),
+                );
+                item.series_timer_id = timer
+                    .series_timer_id
+                    .map(|id| {
+                        id.simple()
+                            .to_string()
+                    });
+            }
+            item
+        })
+        .collect())
+}
+
 #[get("/livetv/recordings")]
-pub async fn livetv_recordings(_session: AuthSession) -> Result<impl IntoResponse> {
-    Ok(Json(api::QueryResult::<api::BaseItemDto> {
-        total_record_count: 0,
-        start_index: 0,
-        items: vec![],
+pub async fn livetv_recordings(
+    State(state): State<AppState>,
+    _session: AuthSession,
+    Query(q): Query<GetRecordingsQuery>,
+) -> Result<impl IntoResponse> {
+    let db = &state
+        .ctx
+        .db;
+    let timers = db::Timer::by_recording(db).await?;
+    let recordings: Vec<_> = db::Media::get_by_filter(
+        db,
+        &db::MediaFilter {
+            kind: Some(vec![db::MediaKind::Recording]),
+            sort_by: vec![api::ItemSortBy::PremiereDate],
+            sort_order: vec![api::SortOrder::Descending],
+            ..Default::default()
+        },
+    )
+    .await?
+    .records
+    .into_iter()
+    .filter(|m| {
+        let timer = timers.get(&m.id);
+        q.channel_id
+            .is_none_or(|c| timer.is_some_and(|t| t.channel_id == c))
+            && q.series_timer_id
+                .is_none_or(|s| timer.is_some_and(|t| t.series_timer_id == Some(s)))
+            && q.is_in_progress
+                .is_none_or(|p| {
+                    timer.is_some_and(|t| t.status == db::TimerStatus::InProgress) == p
+                })
+    })
+    .collect();
+    let total = recordings.len();
+    let start = q
+        .start_index
+        .unwrap_or(0) as usize;
+    let page: Vec<_> = recordings
+        .into_iter()
+        .skip(start)
+        .take(
+            q.limit
+                .map_or(usize::MAX, |l| l as usize),
+        )
+        .collect();
+    Ok(Json(api::QueryResult {
+        total_record_count: total as i64,
+        start_index: start as i32,
+        items: recording_items(db, page, &timers).await?,
     }))
 }
 
@@ -573,32 +1156,78 @@ pub async fn livetv_recordings_series(
 // GET + DELETE /livetv/recordings/{recordingId}
 // --------------------------------------------------------------------------
 
+async fn get_recording(db: &sqlx::SqlitePool, id: &Uuid) -> Result<db::Media> {
+    db::Media::get_by_id(db, id)
+        .await?
+        .filter(|m| m.kind == db::MediaKind::Recording)
+        .context_not_found("recording not found")
+}
+
 #[get("/livetv/recordings/{recording_id}")]
 pub async fn livetv_recording(
+    State(state): State<AppState>,
     _session: AuthSession,
-    Path(_recording_id): Path<Uuid>,
+    Path(recording_id): Path<Uuid>,
 ) -> Result<impl IntoResponse> {
-    Ok(StatusCode::NOT_FOUND)
+    let db = &state
+        .ctx
+        .db;
+    let recording = get_recording(db, &recording_id).await?;
+    let timers = db::Timer::by_recording(db).await?;
+    let mut items = recording_items(db, vec![recording], &timers).await?;
+    Ok(Json(items.remove(0)))
 }
 
+/// Deletes the recording's file, item and timer.
 #[delete("/livetv/recordings/{recording_id}")]
 pub async fn livetv_delete_recording(
-    _session: AuthSession,
-    Path(_recording_id): Path<Uuid>,
+    State(state): State<AppState>,
+    session: AuthSession,
+    Path(recording_id): Path<Uuid>,
 ) -> Result<impl IntoResponse> {
-    Ok(StatusCode::NOT_FOUND)
+    require_live_tv_management(&session)?;
+    let ctx = &state.ctx;
+    let recording = get_recording(&ctx.db, &recording_id).await?;
+    crate::dvr::delete_recording(ctx, &recording).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 // --------------------------------------------------------------------------
 // GET /livetv/liverecordings/{recordingId}/stream
 // --------------------------------------------------------------------------
 
+/// Streams the recording's file as written so far, so a recording can be
+/// watched while it is still in progress.
 #[get("/livetv/liverecordings/{recording_id}/stream")]
 pub async fn livetv_live_recording_stream(
+    State(state): State<AppState>,
     _session: AuthSession,
-    Path(_recording_id): Path<Uuid>,
+    Path(recording_id): Path<Uuid>,
 ) -> Result<impl IntoResponse> {
-    Ok(StatusCode::NOT_FOUND)
+    let recording = get_recording(
+        &state
+            .ctx
+            .db,
+        &recording_id,
+    )
+    .await?;
+    let Some(StreamDescriptor::Local(path)) = recording
+        .stream_info
+        .map(|si| si.descriptor)
+    else {
+        return Err(anyhow::anyhow!("recording {recording_id} has no file")
+            .context_not_found("recording file not found"));
+    };
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!("recording file missing: {}", path.display())
+                .context_not_found("recording file not found")
+        })?;
+    Ok((
+        [(http::header::CONTENT_TYPE, "video/mp2t")],
+        axum::body::Body::from_stream(tokio_util::io::ReaderStream::new(file)),
+    ))
 }
 
 // --------------------------------------------------------------------------
@@ -627,7 +1256,15 @@ pub async fn livetv_program(
         &mut records,
     )
     .await;
-    Ok(Json(api::db_media_to_item(records.remove(0), false)))
+    let mut dtos = vec![api::db_media_to_item(records.remove(0), false)];
+    annotate_timers(
+        &state
+            .ctx
+            .db,
+        &mut dtos,
+    )
+    .await?;
+    Ok(Json(dtos.remove(0)))
 }
 
 // --------------------------------------------------------------------------
@@ -1064,3 +1701,447 @@ fn channel_to_editor_dto(m: &db::Media) -> ChannelEditorDto {
             .clone(),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        Config,
+        integration_test::{
+            AUTH_HEADER, TestGuard, auth_header_with_token, new_test_server_with_config,
+        },
+        stream::StreamInfo,
+    };
+    use axum_test::TestServer;
+    use http::header::HeaderValue;
+    use serde_json::json;
+
+    /// Test server writing recordings into `recordings_dir`, plus an auth header.
+    async fn server_with_recordings_dir(
+        recordings_dir: &std::path::Path,
+    ) -> (TestServer, TestGuard, HeaderValue) {
+        let (server, guard) = new_test_server_with_config(Config {
+            database_url: Some("sqlite::memory:".into()),
+            torrent_http_port: None,
+            disable_dht: true,
+            recordings_dir: Some(
+                recordings_dir
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let body: serde_json::Value = server
+            .post("/users/authenticatebyname")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_static(AUTH_HEADER),
+            )
+            .json(&json!({ "Username": "test", "Pw": "test" }))
+            .await
+            .json();
+        let auth = auth_header_with_token(
+            body["AccessToken"]
+                .as_str()
+                .unwrap(),
+        );
+        (server, guard, HeaderValue::from_str(&auth).unwrap())
+    }
+
+    async fn insert_channel(guard: &TestGuard, url: &str) -> db::Media {
+        let channel = db::Media {
+            id: Uuid::new_v4(),
+            title: "Test Channel".to_string(),
+            kind: db::MediaKind::TvChannel,
+            enabled: true,
+            stream_info: Some(StreamInfo {
+                descriptor: StreamDescriptor::http(url),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        db::Media::upsert(
+            &guard
+                .0
+                .db,
+            std::slice::from_ref(&channel),
+        )
+        .await
+        .unwrap();
+        channel
+    }
+
+    async fn insert_program(
+        guard: &TestGuard,
+        channel: &db::Media,
+        title: &str,
+        start: chrono::NaiveDateTime,
+        minutes: i64,
+    ) -> db::Media {
+        let program = db::Media {
+            id: Uuid::new_v4(),
+            title: title.to_string(),
+            kind: db::MediaKind::TvProgram,
+            parent_id: Some(channel.id),
+            live_start: Some(start),
+            live_end: Some(start + Duration::minutes(minutes)),
+            ..Default::default()
+        };
+        db::Media::upsert(
+            &guard
+                .0
+                .db,
+            std::slice::from_ref(&program),
+        )
+        .await
+        .unwrap();
+        program
+    }
+
+    #[tokio::test]
+    async fn timers_and_series_timers_schedule_programs() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, guard, auth) = server_with_recordings_dir(dir.path()).await;
+        let channel = insert_channel(&guard, "http://127.0.0.1:9/live.ts").await;
+        let soon = Utc::now().naive_utc() + Duration::hours(2);
+        let news = insert_program(&guard, &channel, "Evening News", soon, 30).await;
+        let show = insert_program(&guard, &channel, "Quiz Night", soon, 60).await;
+        let rerun = insert_program(
+            &guard,
+            &channel,
+            "quiz night",
+            soon + Duration::days(1),
+            60,
+        )
+        .await;
+
+        server
+            .post("/livetv/timers")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .json(&json!({
+                "ProgramId": news.id.simple().to_string(),
+                "PrePaddingSeconds": 60,
+                "PostPaddingSeconds": 120,
+            }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        let timers: serde_json::Value = server
+            .get("/livetv/timers")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .json();
+        assert_eq!(timers["TotalRecordCount"], 1);
+        let timer = &timers["Items"][0];
+        assert_eq!(timer["Name"], "Evening News");
+        assert_eq!(timer["ChannelName"], "Test Channel");
+        assert_eq!(timer["PrePaddingSeconds"], 60);
+        assert_eq!(timer["Status"], "New");
+        let timer_id = timer["Id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let program: serde_json::Value = server
+            .get(&format!("/livetv/programs/{}", news.id))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .json();
+        assert_eq!(program["TimerId"], timer_id.as_str());
+
+        server
+            .post("/livetv/seriestimers")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .json(&json!({ "ProgramId": show.id.simple().to_string(), "KeepUpTo": 2 }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        let series: serde_json::Value = server
+            .get("/livetv/seriestimers")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .json();
+        assert_eq!(series["Items"][0]["Name"], "Quiz Night");
+        assert_eq!(series["Items"][0]["KeepUpTo"], 2);
+        let series_id = series["Items"][0]["Id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        // Both airings match the rule, regardless of title case.
+        let series_timers: serde_json::Value = server
+            .get(&format!("/livetv/timers?seriesTimerId={series_id}"))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .json();
+        assert_eq!(series_timers["TotalRecordCount"], 2);
+        let rerun_dto: serde_json::Value = server
+            .get(&format!("/livetv/programs/{}", rerun.id))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .json();
+        assert_eq!(rerun_dto["SeriesTimerId"], series_id.as_str());
+
+        server
+            .delete(&format!("/livetv/timers/{timer_id}"))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        let active: serde_json::Value = server
+            .get("/livetv/timers?isActive=true")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .json();
+        assert_eq!(active["TotalRecordCount"], 2);
+
+        server
+            .delete(&format!("/livetv/seriestimers/{series_id}"))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        let remaining: serde_json::Value = server
+            .get("/livetv/timers")
+            .add_header(http::header::AUTHORIZATION, auth)
+            .await
+            .json();
+        assert_eq!(remaining["TotalRecordCount"], 1);
+        assert_eq!(remaining["Items"][0]["Status"], "Cancelled");
+    }
+
+    #[tokio::test]
+    async fn a_series_from_any_channel_records_a_simulcast_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, guard, auth) = server_with_recordings_dir(dir.path()).await;
+        let first = insert_channel(&guard, "http://127.0.0.1:9/one.ts").await;
+        let second = insert_channel(&guard, "http://127.0.0.1:9/two.ts").await;
+        let soon = Utc::now().naive_utc() + Duration::hours(2);
+        let show = insert_program(&guard, &first, "Quiz Night", soon, 60).await;
+        insert_program(&guard, &second, "Quiz Night", soon, 60).await;
+        insert_program(&guard, &second, "Quiz Night", soon + Duration::days(1), 60)
+            .await;
+
+        server
+            .post("/livetv/seriestimers")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .json(&json!({
+                "ProgramId": show.id.simple().to_string(),
+                "RecordAnyChannel": true,
+            }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        // A later sweep sees the same airings again.
+        crate::dvr::expand_series_timers(&guard.0, Utc::now().naive_utc())
+            .await
+            .unwrap();
+
+        let timers: serde_json::Value = server
+            .get("/livetv/timers")
+            .add_header(http::header::AUTHORIZATION, auth)
+            .await
+            .json();
+        assert_eq!(timers["TotalRecordCount"], 2);
+    }
+
+    #[tokio::test]
+    async fn timer_defaults_are_prefilled_from_the_program() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, guard, auth) = server_with_recordings_dir(dir.path()).await;
+        let channel = insert_channel(&guard, "http://127.0.0.1:9/live.ts").await;
+        let program = insert_program(
+            &guard,
+            &channel,
+            "Late Movie",
+            Utc::now().naive_utc() + Duration::hours(3),
+            90,
+        )
+        .await;
+
+        let defaults: serde_json::Value = server
+            .get(&format!("/livetv/timers/defaults?programId={}", program.id))
+            .add_header(http::header::AUTHORIZATION, auth)
+            .await
+            .json();
+        assert_eq!(defaults["Name"], "Late Movie");
+        assert_eq!(defaults["ChannelName"], "Test Channel");
+        assert_eq!(defaults["Type"], "SeriesTimer");
+        assert_eq!(defaults["RecordAnyTime"], true);
+    }
+
+    fn ffmpeg_available() -> bool {
+        std::process::Command::new(
+            std::env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".into()),
+        )
+        .arg("-version")
+        .output()
+        .is_ok_and(|o| {
+            o.status
+                .success()
+        })
+    }
+
+    /// A few seconds of MPEG-TS test pattern for the fake channel to serve.
+    fn test_pattern_ts(dir: &std::path::Path) -> Vec<u8> {
+        let path = dir.join("source.ts");
+        let status = std::process::Command::new(
+            std::env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".into()),
+        )
+        .args([
+            "-nostdin",
+            "-loglevel",
+            "error",
+            "-f",
+            "lavfi",
+            "-i",
+            "testsrc=duration=2:size=160x120:rate=10",
+            "-c:v",
+            "mpeg2video",
+            "-f",
+            "mpegts",
+        ])
+        .arg(&path)
+        .status()
+        .unwrap();
+        assert!(status.success());
+        std::fs::read(path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn due_timer_records_the_channel() {
+        if !ffmpeg_available() {
+            tracing::warn!("ffmpeg not found, skipping");
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let ts = test_pattern_ts(dir.path());
+        let channel_server = httpmock::MockServer::start_async().await;
+        channel_server
+            .mock_async(|when, then| {
+                when.method(httpmock::Method::GET)
+                    .path("/live.ts");
+                then.status(200)
+                    .header("Content-Type", "video/mp2t")
+                    .body(ts);
+            })
+            .await;
+
+        let recordings = dir
+            .path()
+            .join("recordings");
+        let (server, guard, auth) = server_with_recordings_dir(&recordings).await;
+        let channel = insert_channel(&guard, &channel_server.url("/live.ts")).await;
+        let now = Utc::now().naive_utc();
+        let program = insert_program(
+            &guard,
+            &channel,
+            "Test Pattern",
+            now - Duration::minutes(1),
+            2,
+        )
+        .await;
+
+        server
+            .post("/livetv/timers")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .json(&json!({ "ProgramId": program.id.simple().to_string() }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        crate::dvr::tick(&guard.0, now)
+            .await
+            .unwrap();
+
+        // The stand-in stream ends after two seconds, which ends the recording.
+        let mut timer = serde_json::Value::Null;
+        for _ in 0..100 {
+            let timers: serde_json::Value = server
+                .get("/livetv/timers")
+                .add_header(http::header::AUTHORIZATION, auth.clone())
+                .await
+                .json();
+            timer = timers["Items"][0].clone();
+            if timer["Status"] != "New" && timer["Status"] != "InProgress" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(timer["Status"], "Completed");
+
+        let list: serde_json::Value = server
+            .get("/livetv/recordings")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .json();
+        assert_eq!(list["TotalRecordCount"], 1);
+        let recording = &list["Items"][0];
+        assert_eq!(recording["Type"], "Recording");
+        assert_eq!(recording["Name"], "Test Pattern");
+        assert_eq!(recording["ChannelName"], "Test Channel");
+        let recording_id = recording["Id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let stream = server
+            .get(&format!("/livetv/liverecordings/{recording_id}/stream"))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await;
+        stream.assert_status_ok();
+        assert!(
+            !stream
+                .as_bytes()
+                .is_empty()
+        );
+
+        server
+            .delete(&format!("/livetv/recordings/{recording_id}"))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        assert_eq!(
+            std::fs::read_dir(&recordings)
+                .unwrap()
+                .count(),
+            0
+        );
+        let timers: serde_json::Value = server
+            .get("/livetv/timers")
+            .add_header(http::header::AUTHORIZATION, auth)
+            .await
+            .json();
+        assert_eq!(timers["TotalRecordCount"], 0);
+    }
+
+    #[tokio::test]
+    async fn missed_timer_is_marked_as_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, guard, auth) = server_with_recordings_dir(dir.path()).await;
+        let channel = insert_channel(&guard, "http://127.0.0.1:9/live.ts").await;
+        let now = Utc::now().naive_utc();
+        let program = insert_program(
+            &guard,
+            &channel,
+            "Breakfast",
+            now + Duration::minutes(5),
+            30,
+        )
+        .await;
+        server
+            .post("/livetv/timers")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .json(&json!({ "ProgramId": program.id.simple().to_string() }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        // The scheduler was not running while the airing went by.
+        crate::dvr::tick(&guard.0, now + Duration::hours(1))
+            .await
+            .unwrap();
+        let timers: serde_json::Value = server
+            .get("/livetv/timers")
+            .add_header(http::header::AUTHORIZATION, auth)
+            .await
+            .json();
+        assert_eq!(timers["Items"][0]["Status"], "Error");
+    }
+}
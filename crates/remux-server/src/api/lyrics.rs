@@ -40,7 +40,12 @@ pub async fn get_lyrics(
     let lyrics = state
         .ctx
         .addons
-        .lyric_fetch(&req)
+        .lyric_fetch(
+            &req,
+            &state
+                .ctx
+                .db,
+        )
         .await?
         .context_not_found("lyrics not found")?;
 
@@ -76,7 +81,12 @@ pub async fn search_remote_lyrics(
     let results = state
         .ctx
         .addons
-        .lyric_search(&req)
+        .lyric_search(
+            &req,
+            &state
+                .ctx
+                .db,
+        )
         .await?;
 
     Ok(Json(results).into_response())
@@ -92,18 +102,24 @@ pub async fn get_provider_lyrics(
     let lyrics = state
         .ctx
         .addons
-        .lyric_get_by_composite_id(&lyric_id)
+        .lyric_get_by_composite_id(
+            &lyric_id,
+            &state
+                .ctx
+                .db,
+        )
         .await?
         .context_not_found("lyrics not found")?;
     Ok(Json(lyrics).into_response())
 }
 
-async fn build_search_request(
+pub(crate) async fn build_search_request(
     db: &sqlx::SqlitePool,
     media: &db::Media,
 ) -> LyricSearchRequest {
     let (artist, album) = resolve_music_titles(db, media).await;
     LyricSearchRequest {
+        media_id: media.id,
         title: media
             .title
             .clone(),
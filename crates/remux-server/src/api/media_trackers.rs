@@ -0,0 +1,589 @@
+//! A user's own media tracker connections: connecting one through whichever
+//! flow its provider declares, importing its history, and removing it. Events
+//! reach a connection through the delivery queue, not through here.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use axum_anyhow::{ApiError, ApiResult as Result};
+use http::StatusCode;
+use remux_macros::{delete, get, post};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    AppState, IntoApiError, OptionExt,
+    addons::media_tracker::{
+        AuthFlow, DeviceAuthPoll, MediaTrackerAddon, MediaTrackerCredentials,
+        MediaTrackerCtx, MediaTrackerError,
+    },
+    db::{self, auth},
+    services::media_tracker::{ImportSummary, import_watches},
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeviceAuthResponse {
+    verification_url: String,
+    user_code: String,
+    poll_token: String,
+    interval_seconds: u64,
+    expires_in_seconds: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DevicePollRequest {
+    pub poll_token: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum DevicePollStatus {
+    Pending,
+    Connected,
+    Denied,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DevicePollResponse {
+    status: DevicePollStatus,
+}
+
+fn tracker_ctx(state: &AppState) -> MediaTrackerCtx {
+    MediaTrackerCtx {
+        config: Arc::new(
+            state
+                .ctx
+                .config
+                .clone(),
+        ),
+    }
+}
+
+/// A provider failure as the user sees it: an outage is the provider's, and
+/// anything else is something the user has to fix on their side.
+fn provider_error(err: MediaTrackerError) -> ApiError {
+    let message = err.to_string();
+    if err.is_retryable() {
+        anyhow::anyhow!(message).context_bad_gateway("media tracker unavailable")
+    } else {
+        anyhow::anyhow!(message)
+            .context_bad_request("media tracker refused the request")
+    }
+}
+
+fn provider(state: &AppState, addon_id: Uuid) -> Result<Arc<dyn MediaTrackerAddon>> {
+    state
+        .ctx
+        .addons
+        .media_tracker_for(addon_id)
+        .context_not_found("media tracker not found")
+}
+
+fn require_flow(addon: &dyn MediaTrackerAddon, flow: AuthFlow) -> Result<()> {
+    if addon
+        .capabilities()
+        .auth_flow
+        == flow
+    {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("wrong auth flow")
+            .context_bad_request("media tracker does not connect this way"))
+    }
+}
+
+/// Store a connection with the provider's default filters, or renew the
+/// credentials of an existing one and leave the user's filters alone.
+async fn save_connection(
+    state: &AppState,
+    addon: &dyn MediaTrackerAddon,
+    user_id: Uuid,
+    addon_id: Uuid,
+    credentials: MediaTrackerCredentials,
+) -> Result<()> {
+    let db = &state
+        .ctx
+        .db;
+    let tracker =
+        match db::UserMediaTracker::get_for_user_and_addon(db, user_id, addon_id)
+            .await?
+        {
+            Some(mut tracker) => {
+                tracker.credentials = credentials;
+                tracker.status = db::MediaTrackerStatus::Connected;
+                tracker
+            }
+            None => db::UserMediaTracker::new(
+                user_id,
+                addon_id,
+                credentials,
+                addon
+                    .capabilities()
+                    .default_event_filter,
+            ),
+        };
+    tracker
+        .upsert(db)
+        .await?;
+    Ok(())
+}
+
+#[get("/remux/mediatrackers")]
+pub async fn list_media_trackers(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    let trackers = db::UserMediaTracker::list_for_user(
+        &state
+            .ctx
+            .db,
+        session
+            .user
+            .id,
+    )
+    .await?;
+    Ok(Json(trackers))
+}
+
+/// Connects with the fields the provider's `connect_fields` describe.
+#[post("/remux/mediatrackers/{addon_id}/token")]
+pub async fn connect_media_tracker_with_token(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Path(addon_id): Path<Uuid>,
+    Json(fields): Json<serde_json::Value>,
+) -> Result<impl IntoResponse> {
+    let addon = provider(&state, addon_id)?;
+    require_flow(addon.as_ref(), AuthFlow::Token)?;
+    let credentials = addon
+        .connect_with_token(&fields, &tracker_ctx(&state))
+        .await
+        .map_err(provider_error)?;
+    save_connection(
+        &state,
+        addon.as_ref(),
+        session
+            .user
+            .id,
+        addon_id,
+        credentials,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Starts a device-code connect. The client shows the code and polls.
+#[post("/remux/mediatrackers/{addon_id}/device")]
+pub async fn begin_media_tracker_device_auth(
+    State(state): State<AppState>,
+    _session: auth::AuthSession,
+    Path(addon_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let addon = provider(&state, addon_id)?;
+    require_flow(addon.as_ref(), AuthFlow::OAuthDeviceCode)?;
+    let start = addon
+        .begin_device_auth(&tracker_ctx(&state))
+        .await
+        .map_err(provider_error)?;
+    Ok(Json(DeviceAuthResponse {
+        verification_url: start.verification_url,
+        user_code: start.user_code,
+        poll_token: start.poll_token,
+        interval_seconds: start
+            .interval
+            .as_secs(),
+        expires_in_seconds: start
+            .expires_in
+            .as_secs(),
+    }))
+}
+
+/// One poll of a device-code connect; stores the connection once approved.
+#[post("/remux/mediatrackers/{addon_id}/device/poll")]
+pub async fn poll_media_tracker_device_auth(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Path(addon_id): Path<Uuid>,
+    Json(body): Json<DevicePollRequest>,
+) -> Result<impl IntoResponse> {
+    let addon = provider(&state, addon_id)?;
+    require_flow(addon.as_ref(), AuthFlow::OAuthDeviceCode)?;
+    let status = match addon
+        .poll_device_auth(&body.poll_token, &tracker_ctx(&state))
+        .await
+        .map_err(provider_error)?
+    {
+        DeviceAuthPoll::Pending => DevicePollStatus::Pending,
+        DeviceAuthPoll::Denied => DevicePollStatus::Denied,
+        DeviceAuthPoll::Approved(credentials) => {
+            save_connection(
+                &state,
+                addon.as_ref(),
+                session
+                    .user
+                    .id,
+                addon_id,
+                credentials,
+            )
+            .await?;
+            DevicePollStatus::Connected
+        }
+    };
+    Ok(Json(DevicePollResponse { status }))
+}
+
+/// Reads the user's history from the provider into their watch state.
+#[post("/remux/mediatrackers/{addon_id}/import")]
+pub async fn import_media_tracker_history(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Path(addon_id): Path<Uuid>,
+) -> Result<Json<ImportSummary>> {
+    let addon = provider(&state, addon_id)?;
+    if !addon
+        .capabilities()
+        .history_import
+    {
+        return Err(anyhow::anyhow!("no history import")
+            .context_bad_request("media tracker cannot import history"));
+    }
+    let db = &state
+        .ctx
+        .db;
+    let conn = db::UserMediaTracker::get_for_user_and_addon(
+        db,
+        session
+            .user
+            .id,
+        addon_id,
+    )
+    .await?
+    .context_not_found("media tracker not connected")?;
+
+    let tctx = tracker_ctx(&state);
+    // Renewed the way a delivery renews it, and saved before use for the same
+    // reason: a rotated refresh token has already replaced the stored one.
+    let creds = addon
+        .refresh(&conn.credentials, &tctx)
+        .await
+        .map_err(provider_error)?;
+    if creds != conn.credentials {
+        db::UserMediaTracker::set_credentials(db, conn.id, &creds).await?;
+    }
+    let watches = addon
+        .import_history(&creds, &tctx)
+        .await
+        .map_err(provider_error)?;
+    Ok(Json(import_watches(db, &session.user, &watches).await?))
+}
+
+/// Disconnects at the provider where it supports that, then forgets the
+/// connection. A provider that is down does not keep the user connected.
+#[delete("/remux/mediatrackers/{addon_id}")]
+pub async fn delete_media_tracker(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Path(addon_id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    let db = &state
+        .ctx
+        .db;
+    let conn = db::UserMediaTracker::get_for_user_and_addon(
+        db,
+        session
+            .user
+            .id,
+        addon_id,
+    )
+    .await?
+    .context_not_found("media tracker not connected")?;
+    if let Some(addon) = state
+        .ctx
+        .addons
+        .media_tracker_for(addon_id)
+    {
+        if let Err(e) = addon
+            .disconnect(&conn.credentials, &tracker_ctx(&state))
+            .await
+        {
+            tracing::warn!(error = %e, "media tracker disconnect failed");
+        }
+    }
+    db::UserMediaTracker::delete(db, conn.id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        addons::{
+            AddonKind,
+            media_tracker::{
+                DeviceAuthStart, MediaTrackerCapabilities, MediaTrackerEvent,
+                MediaTrackerResult, MediaTrackerTarget, RemoteWatch,
+            },
+        },
+        integration_test::{
+            auth_header_with_token, authenticated_server, register_media_tracker,
+            seed_episode, seed_movie,
+        },
+    };
+    use async_trait::async_trait;
+    use axum_test::TestServer;
+    use http::header::HeaderValue;
+    use serde_json::json;
+    use std::time::Duration;
+
+    /// Approves the poll token "approved" and reads back a fixed history: a
+    /// watched movie, an episode in progress, and a film the library lacks.
+    struct ScriptedTracker;
+
+    impl AddonKind for ScriptedTracker {
+        fn id(&self) -> &'static str {
+            "scripted"
+        }
+    }
+
+    fn watch(ids: db::ExternalIds) -> RemoteWatch {
+        RemoteWatch {
+            ids,
+            title: None,
+            season: None,
+            episode: None,
+            watched: false,
+            position_ticks: None,
+            watched_at: None,
+            favorite: None,
+            rating: None,
+        }
+    }
+
+    #[async_trait]
+    impl MediaTrackerAddon for ScriptedTracker {
+        fn capabilities(&self) -> MediaTrackerCapabilities {
+            MediaTrackerCapabilities {
+                auth_flow: AuthFlow::OAuthDeviceCode,
+                default_event_filter: vec![
+                    crate::addons::media_tracker::MediaTrackerEventKind::MarkPlayed,
+                ],
+                history_import: true,
+                ..Default::default()
+            }
+        }
+
+        async fn begin_device_auth(
+            &self,
+            _ctx: &MediaTrackerCtx,
+        ) -> MediaTrackerResult<DeviceAuthStart> {
+            Ok(DeviceAuthStart {
+                verification_url: "https://tracker.example/activate".into(),
+                user_code: "ABCD1234".into(),
+                poll_token: "approved".into(),
+                interval: Duration::from_secs(5),
+                expires_in: Duration::from_secs(600),
+            })
+        }
+
+        async fn poll_device_auth(
+            &self,
+            poll_token: &str,
+            _ctx: &MediaTrackerCtx,
+        ) -> MediaTrackerResult<DeviceAuthPoll> {
+            Ok(match poll_token {
+                "approved" => DeviceAuthPoll::Approved(json!({ "token": "t" }).into()),
+                _ => DeviceAuthPoll::Pending,
+            })
+        }
+
+        async fn on_event(
+            &self,
+            _event: &MediaTrackerEvent,
+            _target: &MediaTrackerTarget,
+            _creds: &MediaTrackerCredentials,
+            _ctx: &MediaTrackerCtx,
+        ) -> MediaTrackerResult<()> {
+            Ok(())
+        }
+
+        async fn import_history(
+            &self,
+            _creds: &MediaTrackerCredentials,
+            _ctx: &MediaTrackerCtx,
+        ) -> MediaTrackerResult<Vec<RemoteWatch>> {
+            let heat = db::ExternalIds {
+                imdb: db::NonEmptyString::try_new("tt0113277".to_string()).ok(),
+                ..Default::default()
+            };
+            let wire = db::ExternalIds {
+                tvdb: Some(79126),
+                ..Default::default()
+            };
+            let missing = db::ExternalIds {
+                tmdb: Some(1),
+                ..Default::default()
+            };
+            Ok(vec![
+                RemoteWatch {
+                    watched: true,
+                    rating: Some(8.0),
+                    ..watch(heat)
+                },
+                RemoteWatch {
+                    season: Some(1),
+                    episode: Some(1),
+                    position_ticks: Some(600 * 10_000_000),
+                    ..watch(wire)
+                },
+                RemoteWatch {
+                    watched: true,
+                    ..watch(missing)
+                },
+            ])
+        }
+    }
+
+    async fn post(server: &TestServer, token: &str, path: &str) -> serde_json::Value {
+        server
+            .post(path)
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(token)).unwrap(),
+            )
+            .json(&json!({ "pollToken": "approved" }))
+            .await
+            .json()
+    }
+
+    #[tokio::test]
+    async fn a_device_code_connect_stores_the_connection_once_approved() {
+        let (server, guard, token) = authenticated_server().await;
+        let ctx = &guard.0;
+        let addon =
+            register_media_tracker(ctx, "scripted", Arc::new(ScriptedTracker)).await;
+
+        let start = post(
+            &server,
+            &token,
+            &format!("/remux/mediatrackers/{}/device", addon.id),
+        )
+        .await;
+        assert_eq!(start["userCode"], "ABCD1234");
+        assert_eq!(start["intervalSeconds"], 5);
+
+        let pending: serde_json::Value = server
+            .post(&format!("/remux/mediatrackers/{}/device/poll", addon.id))
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&token)).unwrap(),
+            )
+            .json(&json!({ "pollToken": "waiting" }))
+            .await
+            .json();
+        assert_eq!(pending["status"], "pending");
+        let user = db::User::get_by_username(&ctx.db, "test")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            db::UserMediaTracker::get_for_user_and_addon(&ctx.db, user.id, addon.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let done = post(
+            &server,
+            &token,
+            &format!("/remux/mediatrackers/{}/device/poll", addon.id),
+        )
+        .await;
+        assert_eq!(done["status"], "connected");
+        let tracker =
+            db::UserMediaTracker::get_for_user_and_addon(&ctx.db, user.id, addon.id)
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(tracker.status, db::MediaTrackerStatus::Connected);
+        assert_eq!(
+            tracker.event_filters,
+            ScriptedTracker
+                .capabilities()
+                .default_event_filter
+        );
+    }
+
+    #[tokio::test]
+    async fn an_import_writes_history_into_watch_state() {
+        let (server, guard, token) = authenticated_server().await;
+        let ctx = &guard.0;
+        let addon =
+            register_media_tracker(ctx, "scripted", Arc::new(ScriptedTracker)).await;
+        let movie = seed_movie(ctx).await;
+        let episode = seed_episode(ctx).await;
+        post(
+            &server,
+            &token,
+            &format!("/remux/mediatrackers/{}/device/poll", addon.id),
+        )
+        .await;
+
+        let summary = post(
+            &server,
+            &token,
+            &format!("/remux/mediatrackers/{}/import", addon.id),
+        )
+        .await;
+        assert_eq!(summary, json!({ "imported": 2, "unmatched": 1 }));
+
+        let user = db::User::get_by_username(&ctx.db, "test")
+            .await
+            .unwrap()
+            .unwrap();
+        let heat = db::UserMediaState::get_by_user_and_media(&ctx.db, &user, &movie)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            heat.played_at
+                .is_some()
+        );
+        assert_eq!(heat.rating, Some(8.0));
+        let target =
+            db::UserMediaState::get_by_user_and_media(&ctx.db, &user, &episode)
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(target.playback_position, 600);
+        assert!(
+            target
+                .played_at
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn importing_needs_a_connection() {
+        let (server, guard, token) = authenticated_server().await;
+        let addon =
+            register_media_tracker(&guard.0, "scripted", Arc::new(ScriptedTracker))
+                .await;
+
+        server
+            .post(&format!("/remux/mediatrackers/{}/import", addon.id))
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&token)).unwrap(),
+            )
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
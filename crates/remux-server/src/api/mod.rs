@@ -8,6 +8,8 @@ pub mod artists;
 pub mod client_log;
 pub mod collections;
 pub mod devices;
+pub mod dlna;
+pub mod downloads;
 pub mod hls;
 pub mod image;
 pub mod images;
@@ -17,6 +19,7 @@ pub mod items;
 pub mod livetv;
 pub mod localization;
 pub mod lyrics;
+pub mod media_trackers;
 pub mod metadata;
 pub mod movies;
 pub mod music;
@@ -28,12 +31,17 @@ pub mod remux;
 pub mod search;
 pub mod session;
 pub mod shows;
+pub mod sso;
 pub mod startup;
 pub mod stream;
 pub mod stream_group;
+pub mod stremio;
+pub mod subsonic;
 pub mod subtitles;
+pub mod syncplay;
 pub mod system;
 pub mod tasks;
+pub mod trickplay;
 pub mod users;
 
 use axum::{Json, extract::State, response::IntoResponse};
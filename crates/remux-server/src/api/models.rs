@@ -172,6 +172,7 @@ impl Into<MediaType> for db::MediaKind {
             db::MediaKind::Stream | db::MediaKind::StreamGroup => MediaType::Video,
             db::MediaKind::Subtitle => MediaType::Video,
             db::MediaKind::Intro => MediaType::Video,
+            db::MediaKind::Recording => MediaType::Recording,
         }
     }
 }
@@ -190,6 +191,7 @@ pub fn db_media_kind_to_collection_type(
 }
 
 pub fn db_user_to_dto(data_dir: &std::path::Path, user: db::User) -> UserDto {
+    let sync_play_access = user.sync_play_access();
     let config = user
         .configuration
         .map(|c| c.0)
@@ -199,9 +201,7 @@ pub fn db_user_to_dto(data_dir: &std::path::Path, user: db::User) -> UserDto {
         .map(|p| p.0)
         .unwrap_or_default();
     policy.is_administrator = user.is_admin;
-    // SyncPlay requires group lifecycle, playback commands, and websocket
-    // coordination. Do not advertise it while those server APIs are absent.
-    policy.sync_play_access = SyncPlayUserAccessType::None;
+    policy.sync_play_access = sync_play_access;
     let defaults = UserPolicy::default();
     macro_rules! default_if_empty {
         ($field:ident) => {
@@ -394,7 +394,8 @@ pub fn db_media_to_item(media: db::Media, hide_sources: bool) -> BaseItemDto {
             | db::MediaKind::Episode
             | db::MediaKind::TvChannel
             | db::MediaKind::TvProgram
-            | db::MediaKind::Intro => MediaType::Video,
+            | db::MediaKind::Intro
+            | db::MediaKind::Recording => MediaType::Video,
             db::MediaKind::Track => MediaType::Audio,
             db::MediaKind::Playlist => match media.collection_media_kind {
                 Some(db::CollectionMediaKind::Music) => MediaType::Audio,
@@ -551,7 +552,30 @@ pub fn db_media_to_item(media: db::Media, hide_sources: bool) -> BaseItemDto {
                 .external_ids
                 .tvdb
                 .map(|id| id.to_string()),
-            ..Default::default()
+            musicbrainz_recording: media
+                .external_ids
+                .musicbrainz_recording
+                .clone(),
+            musicbrainz_track: media
+                .external_ids
+                .musicbrainz_track
+                .clone(),
+            musicbrainz_album: media
+                .external_ids
+                .musicbrainz_album
+                .clone(),
+            musicbrainz_release_group: media
+                .external_ids
+                .musicbrainz_release_group
+                .clone(),
+            musicbrainz_artist: media
+                .external_ids
+                .musicbrainz_artist
+                .clone(),
+            musicbrainz_album_artist: media
+                .external_ids
+                .musicbrainz_album_artist
+                .clone(),
         }),
         run_time_ticks: media
             .runtime
@@ -742,6 +766,9 @@ pub fn db_media_to_item(media: db::Media, hide_sources: bool) -> BaseItemDto {
                     })
             })
             .flatten(),
+        normalization_gain: media
+            .normalization_gain
+            .map(|gain| gain as f32),
         album_artists: if matches!(
             media.kind,
             db::MediaKind::Track | db::MediaKind::Album
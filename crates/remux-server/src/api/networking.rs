@@ -33,23 +33,30 @@ fn default_network_configuration() -> api::NetworkConfiguration {
     }
 }
 
+/// The saved network configuration, or the defaults when none was saved.
+pub(crate) async fn network_configuration(
+    db: &sqlx::SqlitePool,
+) -> anyhow::Result<api::NetworkConfiguration> {
+    Ok(
+        match crate::db::Settings::get(db, NETWORK_CONFIG_KEY).await? {
+            Some(json) => serde_json::from_str(&json)
+                .unwrap_or_else(|_| default_network_configuration()),
+            None => default_network_configuration(),
+        },
+    )
+}
+
 #[get("/system/configuration/network")]
 pub async fn get_network_configuration(
     State(state): State<AppState>,
     session: auth::AdminSession,
 ) -> Result<impl IntoResponse> {
-    let config = match crate::db::Settings::get(
+    let config = network_configuration(
         &state
             .ctx
             .db,
-        NETWORK_CONFIG_KEY,
     )
-    .await?
-    {
-        Some(json) => serde_json::from_str(&json)
-            .unwrap_or_else(|_| default_network_configuration()),
-        None => default_network_configuration(),
-    };
+    .await?;
     Ok(Json(config))
 }
 
@@ -68,5 +75,11 @@ pub async fn update_network_configuration(
         &json,
     )
     .await?;
+    state
+        .ctx
+        .network
+        .store(std::sync::Arc::new(crate::network::NetworkPolicy::from(
+            &config,
+        )));
     Ok(StatusCode::NO_CONTENT)
 }
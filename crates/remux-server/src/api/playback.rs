@@ -29,17 +29,18 @@ use uuid::Uuid;
 use crate::{
     AppState, api,
     api::MediaSourceInfoExt,
-    common,
+    base_url, common,
     common::{TickUnit, ToRunTimeTicks},
     db,
     db::auth,
-    playback::hw_accel,
+    network::{self, ClientAddr},
 };
 
 use crate::{
     IntoApiError, OptionExt, ResultExt,
     device_profile::{DeviceProfileExt, SubtitleCodec, subtitle_codec_matches_profile},
     playback::{
+        audio::{AudioFormat, AudioStreamParams, AudioTranscode},
         decision::{
             PlaybackConfig, TranscodeDecision, apply_subtitle_delivery,
             build_transcode_decision,
@@ -56,6 +57,7 @@ use axum_anyhow::ApiResult as Result;
 pub async fn items_playbackinfo(
     State(state): State<AppState>,
     session: auth::AuthSession,
+    client: ClientAddr,
     Path(id): Path<Uuid>,
     Query(query): Query<api::PlaybackInfoQuery>,
     Json(payload): Json<api::PlaybackInfoQuery>,
@@ -71,17 +73,18 @@ pub async fn items_playbackinfo(
     q.device_profile = q
         .device_profile
         .or(query.device_profile);
-    items_playbackinfo_inner(state, session, id, q).await
+    items_playbackinfo_inner(state, session, client, id, q).await
 }
 
 #[get("/items/{id}/playbackinfo")]
 pub async fn items_playbackinfo_get(
     State(state): State<AppState>,
     session: auth::AuthSession,
+    client: ClientAddr,
     Path(id): Path<Uuid>,
     Query(q): Query<api::PlaybackInfoQuery>,
 ) -> Result<impl IntoResponse> {
-    items_playbackinfo_inner(state, session, id, q).await
+    items_playbackinfo_inner(state, session, client, id, q).await
 }
 
 /// Load remembered audio/subtitle stream selections for a user+item
@@ -139,9 +142,37 @@ fn playback_original_language(
     .or(selected_source_language)
 }
 
+/// Refuse playback with 403 outside the user's access schedule or once the
+/// daily watch time is used up.
+pub(crate) async fn ensure_within_parental_limits(
+    state: &AppState,
+    user: &db::User,
+) -> Result<()> {
+    if let Err(e) = crate::playback_session::check_parental_limits(
+        &state
+            .ctx
+            .db,
+        user,
+    )
+    .await
+    {
+        return Err(
+            match e
+                .downcast_ref::<crate::playback_session::PlaybackDenied>()
+                .map(ToString::to_string)
+            {
+                Some(reason) => e.context_forbidden(&reason),
+                None => e.context_internal("failed to check playback limits"),
+            },
+        );
+    }
+    Ok(())
+}
+
 async fn items_playbackinfo_inner(
     state: AppState,
     session: auth::AuthSession,
+    client: ClientAddr,
     id: Uuid,
     q: api::PlaybackInfoQuery,
 ) -> Result<impl IntoResponse> {
@@ -179,6 +210,7 @@ async fn items_playbackinfo_inner(
         return Err(anyhow::anyhow!("Forbidden")
             .context_forbidden("media playback is disabled"));
     }
+    ensure_within_parental_limits(&state, &session.user).await?;
 
     let media =
         MediaResolveService::resolve_item(media_source_id.unwrap_or(id), &state.ctx)
@@ -238,15 +270,22 @@ async fn items_playbackinfo_inner(
             .map_or(false, |m| m.is_track());
     let has_lyrics = is_track;
 
-    let max_bitrate: Option<i64> = match (
+    // Remote clients are held to their remote bitrate limit on top of what
+    // they ask for.
+    let remote_limit = client
+        .is_remote()
+        .then(|| network::remote_bitrate_limit(&session.user, &probe_cfg))
+        .flatten();
+    let max_bitrate: Option<i64> = [
         q.max_streaming_bitrate,
         device_profile
             .as_ref()
             .and_then(|p| p.max_streaming_bitrate),
-    ) {
-        (Some(a), Some(b)) => Some(a.min(b)),
-        (a, b) => a.or(b),
-    };
+        remote_limit,
+    ]
+    .into_iter()
+    .flatten()
+    .min();
 
     let play_session_id = common::get_uuid()
         .as_simple()
@@ -717,7 +756,7 @@ pub async fn videos_no_streams() -> impl IntoResponse {
 /// The `Range` header is forwarded to the upstream server. If no `Range` is provided,
 /// the full video is sent.
 ///
-#[get("/items/{id}/file", "/items/{id}/download")]
+#[get("/items/{id}/file")]
 pub async fn items_file(
     headers: headers::HeaderMap,
     State(state): State<AppState>,
@@ -767,6 +806,35 @@ pub async fn items_file(
     Ok(response)
 }
 
+/// Like [`items_file`], except that a finished offline download of the item
+/// (see `api::downloads`) is served in place of the original, so clients'
+/// download button picks up the transcoded file.
+#[get("/items/{id}/download")]
+pub async fn items_download(
+    headers: headers::HeaderMap,
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Path(id): Path<Uuid>,
+    q: Query<api::VideoStreamQuery>,
+) -> Result<axum::response::Response> {
+    if let Some(job) = db::DownloadJob::latest_completed(
+        &state
+            .ctx
+            .db,
+        session
+            .user
+            .id,
+        id,
+    )
+    .await?
+    {
+        return super::downloads::serve_job(&state, &headers, &job).await;
+    }
+    Ok(items_file(headers, State(state), session, Path(id), q)
+        .await?
+        .into_response())
+}
+
 /// # Static
 ///
 /// If the `static_` query parameter is set to `true`, the response will be a static
@@ -851,7 +919,7 @@ fn ext_from_descriptor(descriptor: &crate::stream::StreamDescriptor) -> String {
     }
 }
 
-async fn videos_stream_inner(
+pub(crate) async fn videos_stream_inner(
     headers: headers::HeaderMap,
     state: AppState,
     user_id: Option<Uuid>,
@@ -957,6 +1025,70 @@ async fn videos_stream_inner(
     )
     .await
     .unwrap_or_default();
+
+    // Music: encode the audio stream alone (see playback::audio) instead of
+    // remuxing it through the video pipeline.
+    let is_music = media
+        .probe_data
+        .as_ref()
+        .is_some_and(|p| {
+            p.video_stream()
+                .is_none()
+                && p.audio_stream()
+                    .is_some()
+        });
+    if let Some(format) = q
+        .audio_codec
+        .as_deref()
+        .and_then(AudioFormat::parse)
+        .filter(|_| {
+            is_music
+                && video_codec == "copy"
+                && encoding_opts
+                    .enable_audio_transcoding
+                    .unwrap_or(true)
+        })
+    {
+        let params = AudioStreamParams {
+            input_url: url,
+            start_time_ticks: q.start_time_ticks,
+            audio_stream_index: q
+                .audio_stream_index
+                .map(|v| v as i32)
+                .filter(|&v| v >= 0),
+            audio: AudioTranscode {
+                format,
+                bitrate: format.bitrate(
+                    q.audio_bit_rate
+                        .map(|b| b.clamp(0, u32::MAX as i64) as u32),
+                ),
+                channels: q
+                    .max_audio_channels
+                    .or(q.audio_channels)
+                    .map(|c| c as u32),
+                sample_rate: q
+                    .audio_sample_rate
+                    .map(|r| r as u32),
+                replay_gain: encoding_opts
+                    .apply_replay_gain
+                    .unwrap_or(false)
+                    .then_some(media.normalization_gain)
+                    .flatten(),
+                normalize_loudness: encoding_opts
+                    .normalize_audio_loudness
+                    .unwrap_or(false),
+            },
+        };
+        let stream = crate::playback::engine::start_audio_transcode(params)?;
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", format.content_type())
+            .header("Cache-Control", "no-cache, no-store")
+            .body(Body::from_stream(stream))
+            .unwrap()
+            .into_response());
+    }
+
     let video_transcode_enabled = encoding_opts
         .enable_video_transcoding
         .unwrap_or(true);
@@ -1011,7 +1143,6 @@ async fn videos_stream_inner(
         == Some("Encode");
 
     let params = crate::playback::engine::ProgressiveTranscodeParams {
-        input_url: url,
         container: container.clone(),
         video_codec,
         audio_codec,
@@ -1045,41 +1176,10 @@ async fn videos_stream_inner(
         burn_subtitle: burn_subtitle_prog,
         subtitle_width: None,
         subtitle_height: None,
-        encoding_preset: encoding_opts.encoding_preset,
         source_video_codec,
         source_audio_codec,
-        accelerator: hw_accel::from_encoding_opts(&encoding_opts),
         source_video_range_type,
-        enable_tonemapping: encoding_opts
-            .enable_tonemapping
-            .unwrap_or(false),
-        enable_vpp_tonemapping: encoding_opts
-            .enable_vpp_tonemapping
-            .unwrap_or(false),
-        tonemapping_algorithm: encoding_opts
-            .tonemapping_algorithm
-            .unwrap_or_else(|| "hable".to_string()),
-        tonemapping_desat: encoding_opts
-            .tonemapping_desat
-            .unwrap_or(0.0),
-        tonemapping_peak: encoding_opts
-            .tonemapping_peak
-            .unwrap_or(0.0),
-        allow_hevc_encoding: encoding_opts
-            .allow_hevc_encoding
-            .unwrap_or(false),
-        allow_av1_encoding: encoding_opts
-            .allow_av1_encoding
-            .unwrap_or(false),
-        h264_crf: encoding_opts
-            .h264_crf
-            .unwrap_or(23),
-        h265_crf: encoding_opts
-            .h265_crf
-            .unwrap_or(28),
-        normalize_audio_loudness: encoding_opts
-            .normalize_audio_loudness
-            .unwrap_or(false),
+        ..crate::playback::engine::ProgressiveTranscodeParams::new(url, &encoding_opts)
     };
 
     let stream = crate::playback::engine::start_progressive_transcode(params)?;
@@ -1117,21 +1217,53 @@ pub async fn video_additional_parts(
     Ok(Json(api::BaseItemDtoQueryResult::default()))
 }
 
+/// `/audio/{id}/universal` parameters. `Container` lists what the client
+/// plays as is; the transcoding fields describe what to send otherwise.
+#[query]
+#[derive(Debug)]
+pub struct UniversalAudioQuery {
+    pub container: Option<String>,
+    pub media_source_id: Option<Uuid>,
+    pub play_session_id: Option<String>,
+    pub audio_codec: Option<String>,
+    pub transcoding_container: Option<String>,
+    pub transcoding_protocol: Option<String>,
+    pub max_streaming_bitrate: Option<i64>,
+    pub audio_bit_rate: Option<i64>,
+    pub max_audio_channels: Option<i64>,
+    pub start_time_ticks: Option<i64>,
+}
+
+/// Redirects to the track itself when the client plays its container and
+/// codec within its bitrate, else to a music transcode: HLS when the client
+/// asks for it, a progressive stream otherwise.
 #[get("/audio/{id}/universal")]
 pub async fn audio_universal(
     State(state): State<AppState>,
     session: auth::AuthSession,
+    client: ClientAddr,
+    headers: headers::HeaderMap,
     Path(id): Path<Uuid>,
-    Query(q): Query<api::HlsVideoQuery>,
+    Query(mut q): Query<UniversalAudioQuery>,
 ) -> Result<impl IntoResponse> {
-    let mut media = db::Media::get_by_id(
-        &state
-            .ctx
-            .db,
-        &id,
-    )
-    .await?
-    .context_not_found("track not found")?;
+    let db = &state
+        .ctx
+        .db;
+    if client.is_remote() {
+        let config = db::Settings::get_config_or_default(db).await;
+        if let Some(limit) = network::remote_bitrate_limit(&session.user, &config) {
+            q.max_streaming_bitrate = Some(
+                q.max_streaming_bitrate
+                    .map_or(limit, |max| max.min(limit)),
+            );
+            q.audio_bit_rate = q
+                .audio_bit_rate
+                .map(|bitrate| bitrate.min(limit));
+        }
+    }
+    let mut media = db::Media::get_by_id(db, &id)
+        .await?
+        .context_not_found("track not found")?;
 
     state
         .ctx
@@ -1148,26 +1280,159 @@ pub async fn audio_universal(
         .await
         .inspect_err(|e| error!("refresh_streams failed: {e:#}"));
 
-    let play_session_id = q
-        .play_session_id
-        .unwrap_or_else(|| {
-            common::get_uuid()
-                .as_simple()
-                .to_string()
+    let probe = match media
+        .probe_data
+        .clone()
+    {
+        Some(probe) => Some(probe),
+        None => media
+            .streams(db)
+            .await
+            .ok()
+            .and_then(|streams| {
+                streams
+                    .into_iter()
+                    .find_map(|s| s.probe_data)
+            }),
+    };
+    let media_source_id = q
+        .media_source_id
+        .unwrap_or(id);
+    let api_key = session
+        .device
+        .access_token
+        .expose();
+
+    let direct_play = probe
+        .as_ref()
+        .is_some_and(|p| {
+            let codec = p
+                .audio_stream()
+                .and_then(|s| {
+                    s.codec
+                        .as_deref()
+                })
+                .unwrap_or_default();
+            let within_bitrate = match (p.bitrate, q.max_streaming_bitrate) {
+                (Some(bitrate), Some(max)) => bitrate <= max,
+                _ => true,
+            };
+            within_bitrate
+                && q.container
+                    .as_deref()
+                    .is_some_and(|list| {
+                        universal_container_matches(
+                            list,
+                            p.container
+                                .as_deref()
+                                .unwrap_or_default(),
+                            codec,
+                        )
+                    })
         });
+    if direct_play {
+        let url = format!(
+            "/audio/{id}/stream?Static=true&MediaSourceId={media_source_id}&ApiKey={api_key}"
+        );
+        return Ok(axum::response::Redirect::temporary(&base_url::prefixed(
+            &headers, &url,
+        ))
+        .into_response());
+    }
 
-    let transcoding_url = format!(
-        "/videos/{}/master.m3u8?PlaySessionId={}&MediaSourceId={}&VideoCodec=copy&AudioCodec=aac&ApiKey={}",
-        id,
-        play_session_id,
-        id,
-        session
-            .device
-            .access_token
-            .expose()
-    );
+    let encoding_opts = db::Settings::get_encoding_config(db)
+        .await
+        .unwrap_or_default();
+    let transcode_allowed = encoding_opts
+        .enable_audio_transcoding
+        .unwrap_or(true)
+        && session
+            .user
+            .policy
+            .as_ref()
+            .map(|p| p.enable_audio_playback_transcoding)
+            .unwrap_or(true);
+    let format = q
+        .audio_codec
+        .as_deref()
+        .and_then(|codecs| {
+            codecs
+                .split(',')
+                .find_map(AudioFormat::parse)
+        })
+        .or_else(|| {
+            q.transcoding_container
+                .as_deref()
+                .and_then(AudioFormat::parse)
+        })
+        .unwrap_or(AudioFormat::Aac);
+    let audio_codec = if transcode_allowed {
+        format.codec()
+    } else {
+        "copy"
+    };
+    let mut params =
+        format!("MediaSourceId={media_source_id}&AudioCodec={audio_codec}");
+    if let Some(bitrate) = q
+        .audio_bit_rate
+        .or(q.max_streaming_bitrate)
+    {
+        params.push_str(&format!("&AudioBitRate={bitrate}"));
+    }
+    if let Some(channels) = q.max_audio_channels {
+        params.push_str(&format!("&MaxAudioChannels={channels}"));
+    }
+    if let Some(ticks) = q.start_time_ticks {
+        params.push_str(&format!("&StartTimeTicks={ticks}"));
+    }
+
+    let url = if q
+        .transcoding_protocol
+        .as_deref()
+        .is_some_and(|p| p.eq_ignore_ascii_case("hls"))
+    {
+        let play_session_id = q
+            .play_session_id
+            .unwrap_or_else(|| {
+                common::get_uuid()
+                    .as_simple()
+                    .to_string()
+            });
+        format!(
+            "/audio/{id}/master.m3u8?PlaySessionId={play_session_id}&{params}&ApiKey={api_key}"
+        )
+    } else {
+        let container = q
+            .transcoding_container
+            .as_deref()
+            .unwrap_or(format.container());
+        format!("/audio/{id}/stream.{container}?{params}&ApiKey={api_key}")
+    };
 
-    Ok(axum::response::Redirect::temporary(&transcoding_url).into_response())
+    Ok(
+        axum::response::Redirect::temporary(&base_url::prefixed(&headers, &url))
+            .into_response(),
+    )
+}
+
+/// Whether the universal endpoint's `Container` list covers a source: each
+/// comma-separated entry is a container, optionally with `|codec`. ffprobe
+/// reports some containers as a list of names (`mov,mp4,m4a,…`), any of
+/// which may match.
+fn universal_container_matches(list: &str, container: &str, codec: &str) -> bool {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            let (entry_container, entry_codec) = match entry.split_once('|') {
+                Some((c, codec)) => (c, Some(codec)),
+                None => (entry, None),
+            };
+            container
+                .split(',')
+                .any(|c| c.eq_ignore_ascii_case(entry_container))
+                && entry_codec.is_none_or(|e| e.eq_ignore_ascii_case(codec))
+        })
 }
 
 /// Bitrate test endpoint - returns a body of the requested size for bandwidth measurement.
@@ -1200,8 +1465,9 @@ mod tests {
 
     use crate::integration_test::{
         AUTH_HEADER, assert_api_keys_are_real, auth_header_with_token,
-        authenticated_server, insert_test_source, insert_test_source_of_kind,
-        insert_test_source_with_external_subtitle, new_test_server,
+        authenticated_server, authenticated_server_from, insert_test_source,
+        insert_test_source_of_kind, insert_test_source_with_external_subtitle,
+        new_test_server,
     };
 
     #[test]
@@ -1225,6 +1491,27 @@ mod tests {
         assert_eq!(source.run_time_ticks, Some(900_000_000));
     }
 
+    #[test]
+    fn universal_container_list_matches_container_and_codec() {
+        let list = "opus,webm|opus,mp3,aac,m4a|aac,flac";
+        assert!(super::universal_container_matches(list, "flac", "flac"));
+        assert!(super::universal_container_matches(
+            list,
+            "mov,mp4,m4a,3gp,3g2,mj2",
+            "aac"
+        ));
+        assert!(!super::universal_container_matches(
+            list,
+            "mov,mp4,m4a,3gp,3g2,mj2",
+            "alac"
+        ));
+        assert!(!super::universal_container_matches(
+            list,
+            "wav",
+            "pcm_s16le"
+        ));
+    }
+
     #[test]
     fn parent_item_language_overrides_missing_or_stale_source_language() {
         let parent = crate::db::Media {
@@ -2010,6 +2297,47 @@ mod tests {
         );
     }
 
+    /// Clients outside the local network are held to the remote bitrate limit
+    /// even when they ask for more.
+    #[tokio::test]
+    async fn test_playbackinfo_caps_remote_clients() {
+        let peer = std::net::SocketAddr::from(([203, 0, 113, 9], 50000));
+        let (server, guard, token) = authenticated_server_from(peer).await;
+        let auth = auth_header_with_token(&token);
+        let media = insert_test_source(&guard.0).await;
+        crate::db::Settings::set_config(
+            &guard
+                .0
+                .db,
+            &crate::api::ServerConfiguration {
+                remote_client_bitrate_limit: Some(2_000_000),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let resp = server
+            .post(&format!("/items/{}/playbackinfo", media.id))
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth).unwrap(),
+            )
+            .json(&json!({ "MaxStreamingBitrate": 20_000_000 }))
+            .await;
+
+        resp.assert_status_ok();
+        let body: serde_json::Value = resp.json();
+        let url = body["MediaSources"][0]["TranscodingUrl"]
+            .as_str()
+            .expect("the 8 Mbps source should be transcoded");
+        assert!(
+            url.contains("MaxStreamingBitrate=2000000"),
+            "TranscodingUrl should carry the remote limit: {}",
+            url
+        );
+    }
+
     /// The session token is carried in the URL the client is told to fetch, so
     /// it has to be the real one. It is wrapped in a `Secret`, which only ever
     /// prints as `<redacted>`.
@@ -3742,4 +4070,87 @@ mod tests {
             }
         }
     }
+
+    /// Playback is charged to the user's day, and once the daily allowance is
+    /// used up new playback is refused.
+    #[tokio::test]
+    async fn daily_watch_time_limit_stops_new_playback() {
+        let (server, ctx, admin_token) = authenticated_server().await;
+        let user_id = crate::integration_test::create_user_with_policy(
+            &server,
+            &admin_token,
+            "limited",
+            "pass1234",
+            json!({ "DailyWatchTimeLimitMinutes": 2 }),
+        )
+        .await;
+        let token = server
+            .post("/users/authenticatebyname")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_static(AUTH_HEADER),
+            )
+            .json(&json!({ "Username": "limited", "Pw": "pass1234" }))
+            .await
+            .json::<serde_json::Value>()["AccessToken"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let auth = HeaderValue::from_str(&auth_header_with_token(&token)).unwrap();
+
+        let report = |path: &'static str, psid: &'static str| {
+            server
+                .post(path)
+                .add_header(http::header::AUTHORIZATION, auth.clone())
+                .json(&json!({
+                    "ItemId": "80ce1832bb797ffafaf65059b8b3dc9e",
+                    "PlaySessionId": psid,
+                    "PositionTicks": 0
+                }))
+        };
+        // Pretend `secs` of unpaused playback went by since the last report.
+        let elapse = |psid: &str, secs: i64| {
+            ctx.0
+                .sessions
+                .update(psid, |ps| {
+                    ps.watch_accounted_at -= chrono::Duration::seconds(secs)
+                });
+        };
+        let watched = || async {
+            crate::db::UserWatchTime::get(
+                &ctx.0
+                    .db,
+                &user_id
+                    .parse()
+                    .unwrap(),
+                chrono::Local::now().date_naive(),
+            )
+            .await
+            .unwrap()
+        };
+
+        report("/sessions/playing", "limit-1")
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        elapse("limit-1", 90);
+        report("/sessions/playing/stopped", "limit-1")
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        assert!((90..=95).contains(&watched().await));
+
+        // 90 s of a 120 s allowance: still allowed.
+        report("/sessions/playing", "limit-2")
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        elapse("limit-2", 60);
+        report("/sessions/playing/progress", "limit-2")
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+        assert!(watched().await >= 150);
+
+        report("/sessions/playing", "limit-3")
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+    }
 }
@@ -23,6 +23,7 @@ use crate::{
     db,
     db::auth,
     playback::session::TranscodeSession,
+    playback_session::PlaybackDenied,
     services::{self, MediaResolveService},
 };
 
@@ -135,19 +136,31 @@ pub async fn report_playback_start(
         )
         .await
         .map_err(|e| {
-            // Re-raise stream-limit errors as 403 Forbidden; everything else as 500.
-            if e.to_string()
-                .contains("Stream limit reached")
+            // Refused playback is 403 Forbidden; everything else is 500.
+            match e
+                .downcast_ref::<PlaybackDenied>()
+                .map(ToString::to_string)
             {
-                e.context_forbidden("Maximum concurrent streams reached")
-            } else {
-                e.context_internal("failed to start session")
+                Some(reason) => e.context_forbidden(&reason),
+                None => e.context_internal("failed to start session"),
             }
         })?;
     let _ = state
         .ctx
         .ws_tx
         .send(crate::ws::WsEvent::SessionsChanged);
+    crate::trickplay::spawn_for_playback(
+        state
+            .ctx
+            .clone(),
+        data.item_id,
+        data.media_source_id
+            .as_deref()
+            .and_then(|id| Uuid::parse_str(id).ok()),
+        session
+            .user
+            .id,
+    );
     track_by_id(
         &state,
         &session,
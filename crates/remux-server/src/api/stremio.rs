@@ -0,0 +1,1189 @@
+//! The library published as a Stremio addon.
+//!
+//! Every user can mint a token that makes `/stremio/{token}/manifest.json`
+//! installable in Stremio. The token only opens the `/stremio` routes. Smart
+//! movie and series collections become catalogs, items are addressed as
+//! `remux:{id}`, and streams point at `/stremio/play`, which serves the
+//! static stream, so torrent and opendal sources are proxied through the
+//! server like they are for Jellyfin clients. Stream and subtitle URLs carry
+//! a short-lived token signed for the item instead of the addon token.
+
+use std::{collections::HashSet, sync::OnceLock};
+
+use anyhow::anyhow;
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use axum_extra::extract::Query;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use http::{HeaderMap, StatusCode};
+use remux_macros::{delete, get, post};
+use remux_sdks::stremio::{
+    BehaviorHints, Catalog, CatalogResponse, Episode, ExtraProp, Manifest, MediaType,
+    Meta, MetaResponse, Resource, ResourceType, Stream, StreamsResponse, Subtitle,
+    SubtitlesResponse,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    AppState, IntoApiError, OptionExt, ResultExt, api,
+    common::get_uuid,
+    db::{self, auth},
+    network::{self, ClientAddr, ensure_remote_access},
+    stream::StreamDescriptor,
+};
+use axum_anyhow::ApiResult as Result;
+use ring::{hmac, rand::SystemRandom};
+
+use super::{
+    items::get_items,
+    playback::{ensure_within_parental_limits, videos_stream_inner},
+    subtitles::subtitles_stream_inner,
+    system::request_local_address,
+    users::ensure_access_allowed,
+};
+
+const ID_PREFIX: &str = "remux:";
+
+/// Stremio pages catalogs by 100 through the `skip` extra.
+const PAGE_SIZE: u32 = 100;
+
+/// How long the stream and subtitle URLs in a `streams` response play.
+const STREAM_TOKEN_TTL: chrono::Duration = chrono::Duration::hours(6);
+
+/// The device row that holds a user's addon token.
+fn device_id(user_id: Uuid) -> String {
+    format!("stremio-{user_id}")
+}
+
+/// Whether `device` holds an addon token, which opens nothing outside
+/// `/stremio`.
+pub(crate) fn is_addon_device(device: &auth::Device) -> bool {
+    device.id == device_id(device.user_id)
+}
+
+/// The key stream tokens are signed with. It only lives as long as the
+/// process, so a restart also ends every outstanding token.
+fn signing_key() -> &'static hmac::Key {
+    static KEY: OnceLock<hmac::Key> = OnceLock::new();
+    KEY.get_or_init(|| {
+        hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+            .expect("the system random number generator failed")
+    })
+}
+
+/// `{user}.{expiry}.{signature}`, where the signature also covers `item_id`
+/// so the token plays nothing else.
+fn stream_token(user_id: Uuid, item_id: Uuid) -> String {
+    let expires = (Utc::now() + STREAM_TOKEN_TTL).timestamp();
+    let claims = format!("{}.{expires}", user_id.simple());
+    let tag = hmac::sign(signing_key(), format!("{claims}.{item_id}").as_bytes());
+    format!("{claims}.{}", URL_SAFE_NO_PAD.encode(tag.as_ref()))
+}
+
+/// The user a stream token was issued to for `item_id`, when it is genuine
+/// and unexpired.
+fn verify_stream_token(token: &str, item_id: Uuid) -> Option<Uuid> {
+    let (claims, tag) = token.rsplit_once('.')?;
+    let tag = URL_SAFE_NO_PAD
+        .decode(tag)
+        .ok()?;
+    hmac::verify(
+        signing_key(),
+        format!("{claims}.{item_id}").as_bytes(),
+        &tag,
+    )
+    .ok()?;
+    let (user_id, expires) = claims.split_once('.')?;
+    if expires
+        .parse::<i64>()
+        .ok()?
+        <= Utc::now().timestamp()
+    {
+        return None;
+    }
+    user_id
+        .parse()
+        .ok()
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AddonResponse {
+    manifest_url: String,
+    /// The same manifest behind the `stremio://` scheme, which opens the
+    /// install dialog directly.
+    install_url: String,
+}
+
+impl AddonResponse {
+    fn new(base: &str, token: &str) -> Self {
+        let manifest_url = format!("{base}/stremio/{token}/manifest.json");
+        let install_url = format!(
+            "stremio://{}",
+            manifest_url
+                .split_once("://")
+                .map_or(manifest_url.as_str(), |(_, rest)| rest)
+        );
+        Self {
+            manifest_url,
+            install_url,
+        }
+    }
+}
+
+#[get("/remux/stremio")]
+pub async fn get_addon(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    let device = auth::Device::get_by_id(
+        &state
+            .ctx
+            .db,
+        &device_id(
+            session
+                .user
+                .id,
+        ),
+    )
+    .await?
+    .context_not_found("no stremio addon token")?;
+    Ok(Json(AddonResponse::new(
+        &base_url(&state, &headers),
+        device
+            .access_token
+            .expose(),
+    )))
+}
+
+/// Create the caller's addon token, or replace it so installs using the old
+/// one stop working.
+#[post("/remux/stremio")]
+pub async fn create_addon(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    let token = get_uuid()
+        .simple()
+        .to_string();
+    auth::Device {
+        id: device_id(
+            session
+                .user
+                .id,
+        ),
+        access_token: token
+            .clone()
+            .into(),
+        user_id: session
+            .user
+            .id,
+        name: "Stremio".into(),
+        app_name: "Stremio".into(),
+        app_version: env!("CARGO_PKG_VERSION").into(),
+        ..Default::default()
+    }
+    .save(
+        &state
+            .ctx
+            .db,
+    )
+    .await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(AddonResponse::new(&base_url(&state, &headers), &token)),
+    ))
+}
+
+#[delete("/remux/stremio")]
+pub async fn delete_addon(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    auth::Device::delete_by_id(
+        &state
+            .ctx
+            .db,
+        &device_id(
+            session
+                .user
+                .id,
+        ),
+        &session
+            .user
+            .id,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn base_url(state: &AppState, headers: &HeaderMap) -> String {
+    request_local_address(
+        headers,
+        state
+            .ctx
+            .config
+            .port,
+    )
+}
+
+/// The session an addon token stands for. Only tokens minted for Stremio are
+/// accepted, so regular session tokens never end up in addon URLs.
+async fn addon_session(
+    state: &AppState,
+    client: &ClientAddr,
+    token: &str,
+) -> Result<auth::AuthSession> {
+    let device = auth::Device::get_by_access_token(
+        &state
+            .ctx
+            .db,
+        token,
+    )
+    .await?
+    .filter(is_addon_device)
+    .context_unauthorized("unknown addon token")?;
+    device_session(state, client, device).await
+}
+
+/// The session behind a stream token for `item_id`. It ends with the addon
+/// token it was issued under.
+async fn stream_session(
+    state: &AppState,
+    client: &ClientAddr,
+    token: &str,
+    item_id: Uuid,
+) -> Result<auth::AuthSession> {
+    let user_id = verify_stream_token(token, item_id)
+        .context_unauthorized("invalid stream token")?;
+    let device = auth::Device::get_by_id(
+        &state
+            .ctx
+            .db,
+        &device_id(user_id),
+    )
+    .await?
+    .context_unauthorized("invalid stream token")?;
+    device_session(state, client, device).await
+}
+
+/// The addon device's user, held to the same remote access and access
+/// schedule rules as a sign-in.
+async fn device_session(
+    state: &AppState,
+    client: &ClientAddr,
+    device: auth::Device,
+) -> Result<auth::AuthSession> {
+    let user = db::User::get_by_id(
+        &state
+            .ctx
+            .db,
+        &device.user_id,
+    )
+    .await?
+    .context_unauthorized("unknown addon token")?;
+    ensure_remote_access(&user, client)?;
+    ensure_access_allowed(&user)?;
+    Ok(auth::AuthSession { device, user })
+}
+
+/// Resource ids arrive as the last path segment, `.json` included.
+fn strip_json(segment: &str) -> Result<&str> {
+    segment
+        .strip_suffix(".json")
+        .context_not_found("not found")
+}
+
+fn parse_item_id(id: &str) -> Result<Uuid> {
+    id.strip_prefix(ID_PREFIX)
+        .and_then(|id| {
+            id.parse()
+                .ok()
+        })
+        .context_not_found("unknown id")
+}
+
+/// The stremio types a smart collection is published under.
+fn catalog_types(collection: &db::Media) -> &'static [&'static str] {
+    match collection.collection_media_kind {
+        Some(db::CollectionMediaKind::Movie) | None => &["movie"],
+        Some(db::CollectionMediaKind::Series) => &["series"],
+        Some(db::CollectionMediaKind::Mixed) => &["movie", "series"],
+        _ => &[],
+    }
+}
+
+/// The smart collections `session`'s user can see.
+async fn smart_collections(
+    state: &AppState,
+    session: &auth::AuthSession,
+) -> Result<Vec<db::Media>> {
+    let mut collections: Vec<db::Media> = db::Media::get_by_filter(
+        &state
+            .ctx
+            .db,
+        &db::MediaFilter {
+            kind: Some(vec![db::MediaKind::Collection]),
+            ..Default::default()
+        },
+    )
+    .await?
+    .records
+    .into_iter()
+    .filter(|c| {
+        c.collection_kind == Some(db::CollectionKind::Smart)
+            && !catalog_types(c).is_empty()
+    })
+    .collect();
+    if collections.is_empty() {
+        return Ok(collections);
+    }
+    let visible: HashSet<Uuid> = visible_items(
+        state,
+        session,
+        collections
+            .iter()
+            .map(|c| c.id)
+            .collect(),
+    )
+    .await?
+    .into_iter()
+    .map(|item| item.id)
+    .collect();
+    collections.retain(|c| visible.contains(&c.id));
+    collections.sort_by(|a, b| {
+        a.title
+            .cmp(&b.title)
+    });
+    Ok(collections)
+}
+
+/// Those of `ids` the user can see, through the same query the Jellyfin API
+/// answers with, so library access and parental ratings apply.
+async fn visible_items(
+    state: &AppState,
+    session: &auth::AuthSession,
+    ids: Vec<Uuid>,
+) -> Result<Vec<api::BaseItemDto>> {
+    let q = api::GetItemsQuery {
+        ids: Some(ids),
+        user_id: Some(
+            session
+                .user
+                .id,
+        ),
+        ..Default::default()
+    };
+    Ok(get_items(state.clone(), session.clone(), q, false)
+        .await?
+        .build()
+        .items)
+}
+
+#[get("/stremio/{token}/manifest.json")]
+pub async fn manifest(
+    State(state): State<AppState>,
+    client: ClientAddr,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse> {
+    let session = addon_session(&state, &client, &token).await?;
+    let db = &state
+        .ctx
+        .db;
+    let server_name = db::Settings::get_config_or_default(db)
+        .await
+        .server_name
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| "Remux".into());
+
+    let mut catalogs = Vec::new();
+    for collection in smart_collections(&state, &session).await? {
+        for kind in catalog_types(&collection) {
+            catalogs.push(Catalog {
+                id: collection
+                    .id
+                    .to_string(),
+                kind: kind.to_string(),
+                name: collection
+                    .title
+                    .clone(),
+                extra: vec![
+                    ExtraProp {
+                        name: "search".into(),
+                        is_required: false,
+                        options: None,
+                    },
+                    ExtraProp {
+                        name: "skip".into(),
+                        is_required: false,
+                        options: None,
+                    },
+                ],
+            });
+        }
+    }
+
+    Ok(Json(Manifest {
+        id: format!("remux.{}", crate::common::server_id()),
+        name: server_name.clone(),
+        version: env!("CARGO_PKG_VERSION").into(),
+        description: Some(format!("Movies and series from {server_name}")),
+        resources: vec![
+            Resource::Simple(ResourceType::Catalog),
+            Resource::Simple(ResourceType::Meta),
+            Resource::Simple(ResourceType::Stream),
+            Resource::Simple(ResourceType::Subtitles),
+        ],
+        types: vec!["movie".into(), "series".into()],
+        catalogs,
+        id_prefixes: Some(vec![ID_PREFIX.into()]),
+        logo: None,
+    }))
+}
+
+/// The `search=…&skip=…` segment Stremio appends to catalog requests.
+#[derive(Debug, Default, Deserialize)]
+struct CatalogExtra {
+    search: Option<String>,
+    skip: Option<u32>,
+}
+
+#[get("/stremio/{token}/catalog/{kind}/{id}")]
+pub async fn catalog(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    client: ClientAddr,
+    Path((token, kind, id)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse> {
+    let session = addon_session(&state, &client, &token).await?;
+    catalog_inner(&headers, &state, session, &kind, strip_json(&id)?, None).await
+}
+
+#[get("/stremio/{token}/catalog/{kind}/{id}/{extra}")]
+pub async fn catalog_with_extra(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    client: ClientAddr,
+    Path((token, kind, id, extra)): Path<(String, String, String, String)>,
+) -> Result<impl IntoResponse> {
+    let session = addon_session(&state, &client, &token).await?;
+    catalog_inner(
+        &headers,
+        &state,
+        session,
+        &kind,
+        &id,
+        Some(strip_json(&extra)?),
+    )
+    .await
+}
+
+async fn catalog_inner(
+    headers: &HeaderMap,
+    state: &AppState,
+    session: auth::AuthSession,
+    kind: &str,
+    id: &str,
+    extra: Option<&str>,
+) -> Result<axum::response::Response> {
+    let extra: CatalogExtra = extra
+        .map(serde_urlencoded::from_str)
+        .transpose()
+        .context_bad_request("invalid catalog extra")?
+        .unwrap_or_default();
+
+    let collection = smart_collections(state, &session)
+        .await?
+        .into_iter()
+        .find(|c| {
+            c.id.to_string() == id && catalog_types(c).contains(&kind)
+        })
+        .context_not_found("catalog not found")?;
+
+    let q = api::GetItemsQuery {
+        parent_id: Some(collection.id),
+        include_item_types: Some(vec![item_type(kind)]),
+        start_index: extra.skip,
+        limit: Some(PAGE_SIZE),
+        search_term: extra
+            .search
+            .filter(|s| !s.is_empty()),
+        user_id: Some(
+            session
+                .user
+                .id,
+        ),
+        ..Default::default()
+    };
+    let items = get_items(state.clone(), session, q, false)
+        .await?
+        .build();
+
+    let base = base_url(state, headers);
+    let metas = items
+        .items
+        .iter()
+        .map(|item| to_meta(&base, item))
+        .collect();
+    Ok(Json(CatalogResponse { metas }).into_response())
+}
+
+fn item_type(kind: &str) -> api::MediaType {
+    match kind {
+        "series" => api::MediaType::Series,
+        _ => api::MediaType::Movie,
+    }
+}
+
+fn image_url(base: &str, id: Uuid, image_type: &str) -> String {
+    format!("{base}/items/{id}/images/{image_type}")
+}
+
+fn to_meta(base: &str, item: &api::BaseItemDto) -> Meta {
+    let media_type = match item.type_ {
+        api::MediaType::Series => MediaType::Series,
+        _ => MediaType::Movie,
+    };
+    let tags = item
+        .image_tags
+        .as_ref();
+    Meta {
+        name: item
+            .name
+            .clone(),
+        description: item
+            .overview
+            .clone(),
+        released: item.premiere_date,
+        genres: (!item
+            .genres
+            .is_empty())
+        .then(|| {
+            item.genres
+                .clone()
+        }),
+        imdb_id: item
+            .provider_ids
+            .as_ref()
+            .and_then(|p| {
+                p.imdb
+                    .clone()
+            }),
+        imdb_rating: item.community_rating,
+        certification: item
+            .official_rating
+            .clone(),
+        poster: tags
+            .and_then(|t| {
+                t.primary
+                    .as_ref()
+            })
+            .map(|_| image_url(base, item.id, "primary")),
+        logo: tags
+            .and_then(|t| {
+                t.logo
+                    .as_ref()
+            })
+            .map(|_| image_url(base, item.id, "logo")),
+        background: (!item
+            .backdrop_image_tags
+            .is_empty())
+        .then(|| image_url(base, item.id, "backdrop")),
+        ..Meta::new(format!("{ID_PREFIX}{}", item.id), media_type)
+    }
+}
+
+#[get("/stremio/{token}/meta/{kind}/{id}")]
+pub async fn meta(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    client: ClientAddr,
+    Path((token, _kind, id)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse> {
+    let session = addon_session(&state, &client, &token).await?;
+    let id = parse_item_id(strip_json(&id)?)?;
+    let user_id = session
+        .user
+        .id;
+
+    let item = visible_items(&state, &session, vec![id])
+        .await?
+        .into_iter()
+        .next()
+        .context_not_found("item not found")?;
+
+    let base = base_url(&state, &headers);
+    let mut meta = to_meta(&base, &item);
+    if item.type_ == api::MediaType::Series {
+        let q = api::GetItemsQuery {
+            series_id: Some(item.id),
+            include_item_types: Some(vec![api::MediaType::Episode]),
+            sort_by: Some(vec![
+                api::ItemSortBy::ParentIndexNumber,
+                api::ItemSortBy::IndexNumber,
+            ]),
+            sort_order: Some(vec![api::SortOrder::Ascending]),
+            user_id: Some(user_id),
+            ..Default::default()
+        };
+        let episodes = get_items(state.clone(), session, q, false)
+            .await?
+            .build()
+            .items;
+        meta.videos = Some(
+            episodes
+                .iter()
+                .map(|ep| Episode {
+                    id: format!("{ID_PREFIX}{}", ep.id),
+                    title: ep
+                        .name
+                        .clone(),
+                    released: ep.premiere_date,
+                    thumbnail: ep
+                        .image_tags
+                        .as_ref()
+                        .and_then(|t| {
+                            t.primary
+                                .as_ref()
+                        })
+                        .map(|_| image_url(&base, ep.id, "primary")),
+                    season: ep.parent_index_number,
+                    episode: ep.index_number,
+                    overview: ep
+                        .overview
+                        .clone(),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+    }
+    Ok(Json(MetaResponse { meta }))
+}
+
+/// The sources of a playable item, in the order Jellyfin clients list them,
+/// or `None` when the user cannot see the item. Items that resolve their
+/// streams on demand have none stored; they get a single stream that lets
+/// the server pick.
+async fn playable_sources(
+    state: &AppState,
+    session: &auth::AuthSession,
+    id: Uuid,
+) -> Result<Option<Vec<db::Media>>> {
+    if session
+        .user
+        .policy
+        .as_ref()
+        .is_some_and(|p| !p.enable_media_playback)
+    {
+        return Err(
+            anyhow!("Forbidden").context_forbidden("media playback is disabled")
+        );
+    }
+    if visible_items(state, session, vec![id])
+        .await?
+        .is_empty()
+    {
+        return Ok(None);
+    }
+    let db = &state
+        .ctx
+        .db;
+    let Some(mut item) = db::Media::get_by_id(db, &id)
+        .await?
+        .filter(|m| matches!(m.kind, db::MediaKind::Movie | db::MediaKind::Episode))
+    else {
+        return Ok(None);
+    };
+    let mut sources = item
+        .streams(db)
+        .await?;
+    if sources.is_empty()
+        && item
+            .stream_info
+            .is_some()
+    {
+        sources.push(item);
+    }
+    Ok(Some(sources))
+}
+
+fn source_filename(source: &db::Media) -> Option<String> {
+    let name = match &source
+        .stream_info
+        .as_ref()?
+        .descriptor
+    {
+        StreamDescriptor::Local(path) => path
+            .file_name()?
+            .to_str()?
+            .to_string(),
+        StreamDescriptor::Torrent { file_hint, .. } => file_hint.clone()?,
+        StreamDescriptor::Opendal { path, .. } => path
+            .rsplit('/')
+            .next()?
+            .to_string(),
+        StreamDescriptor::Http { .. } | StreamDescriptor::Rtsp { .. } => return None,
+    };
+    Some(name)
+}
+
+#[get("/stremio/{token}/stream/{kind}/{id}")]
+pub async fn streams(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    client: ClientAddr,
+    Path((token, _kind, id)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse> {
+    let session = addon_session(&state, &client, &token).await?;
+    let id = parse_item_id(strip_json(&id)?)?;
+    let Some(sources) = playable_sources(&state, &session, id).await? else {
+        return Ok(Json(StreamsResponse { streams: vec![] }));
+    };
+
+    let base = base_url(&state, &headers);
+    let stream_token = stream_token(
+        session
+            .user
+            .id,
+        id,
+    );
+    let stream_url = |source_id: Option<Uuid>| {
+        let source = source_id
+            .map(|s| format!("?mediaSourceId={s}"))
+            .unwrap_or_default();
+        format!("{base}/stremio/play/{stream_token}/{id}{source}")
+    };
+
+    if sources.is_empty() {
+        return Ok(Json(StreamsResponse {
+            streams: vec![Stream {
+                url: Some(stream_url(None)),
+                name: Some("Remux".into()),
+                ..Default::default()
+            }],
+        }));
+    }
+
+    let streams = sources
+        .iter()
+        .map(|source| {
+            // Source titles carry "name\ndescription".
+            let (name, description) = match source
+                .title
+                .split_once('\n')
+            {
+                Some((n, d)) => (n.trim(), Some(d.trim())),
+                None => (
+                    source
+                        .title
+                        .trim(),
+                    None,
+                ),
+            };
+            let name = if name.is_empty() { "Remux" } else { name };
+            let probe = source
+                .probe_data
+                .as_ref();
+            Stream {
+                url: Some(stream_url(Some(source.id))),
+                name: Some(name.to_string()),
+                description: description.map(str::to_string),
+                behavior_hints: Some(BehaviorHints {
+                    filename: source_filename(source),
+                    binge_group: Some(format!("remux-{name}")),
+                    // Stremio's web player only handles MP4 and WebM itself.
+                    not_web_ready: Some(
+                        !probe
+                            .and_then(|p| {
+                                p.container
+                                    .as_deref()
+                            })
+                            .is_some_and(|c| {
+                                c.split(',')
+                                    .any(|c| matches!(c, "mp4" | "mov" | "webm"))
+                            }),
+                    ),
+                    video_size: probe.and_then(|p| p.size),
+                    media_info: None,
+                }),
+                ..Default::default()
+            }
+        })
+        .collect();
+    Ok(Json(StreamsResponse { streams }))
+}
+
+/// The static stream of an item, for a token from `streams`.
+#[get("/stremio/play/{stream_token}/{id}")]
+pub async fn play(
+    headers: HeaderMap,
+    State(state): State<AppState>,
+    client: ClientAddr,
+    Path((stream_token, id)): Path<(String, Uuid)>,
+    Query(q): Query<api::VideoStreamQuery>,
+) -> Result<axum::response::Response> {
+    let session = stream_session(&state, &client, &stream_token, id).await?;
+    ensure_within_parental_limits(&state, &session.user).await?;
+    let mut q = api::VideoStreamQuery {
+        static_: Some(true),
+        ..q
+    };
+    // Remote clients are held to their remote bitrate limit: a source above
+    // it is transcoded down to the limit instead of served as is.
+    if client.is_remote() {
+        let db = &state
+            .ctx
+            .db;
+        let config = db::Settings::get_config_or_default(db).await;
+        if let Some(limit) = network::remote_bitrate_limit(&session.user, &config) {
+            let source_id = q
+                .media_source_id
+                .unwrap_or(id);
+            let bitrate = db::Media::get_by_id(db, &source_id)
+                .await?
+                .and_then(|m| m.probe_data)
+                .and_then(|p| p.bitrate);
+            if bitrate.is_some_and(|b| b > limit) {
+                q.static_ = Some(false);
+                q.video_codec = Some("h264".to_string());
+                q.video_bit_rate = Some(limit);
+            }
+        }
+    }
+    Ok(videos_stream_inner(
+        headers,
+        state,
+        Some(
+            session
+                .user
+                .id,
+        ),
+        id,
+        q,
+    )
+    .await?
+    .into_response())
+}
+
+/// A text subtitle track as WebVTT, for a token from `subtitles`.
+#[get("/stremio/play/{stream_token}/{id}/subtitles/{source_id}/{index}")]
+pub async fn play_subtitle(
+    State(state): State<AppState>,
+    client: ClientAddr,
+    Path((stream_token, id, source_id, index)): Path<(String, Uuid, Uuid, i64)>,
+) -> Result<axum::response::Response> {
+    let session = stream_session(&state, &client, &stream_token, id).await?;
+    Ok(
+        subtitles_stream_inner(state, session, id, source_id, index, "vtt".into())
+            .await?
+            .into_response(),
+    )
+}
+
+#[get("/stremio/{token}/subtitles/{kind}/{id}")]
+pub async fn subtitles(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    client: ClientAddr,
+    Path((token, _kind, id)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse> {
+    let session = addon_session(&state, &client, &token).await?;
+    subtitles_inner(&headers, &state, session, strip_json(&id)?).await
+}
+
+/// Stremio appends the video hash and size as an extra; the stored probe is
+/// all that's needed, so it is ignored.
+#[get("/stremio/{token}/subtitles/{kind}/{id}/{extra}")]
+pub async fn subtitles_with_extra(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    client: ClientAddr,
+    Path((token, _kind, id, _extra)): Path<(String, String, String, String)>,
+) -> Result<impl IntoResponse> {
+    let session = addon_session(&state, &client, &token).await?;
+    subtitles_inner(&headers, &state, session, &id).await
+}
+
+async fn subtitles_inner(
+    headers: &HeaderMap,
+    state: &AppState,
+    session: auth::AuthSession,
+    id: &str,
+) -> Result<axum::response::Response> {
+    let id = parse_item_id(id)?;
+    let sources = playable_sources(state, &session, id)
+        .await?
+        .unwrap_or_default();
+
+    let base = base_url(state, headers);
+    let stream_token = stream_token(
+        session
+            .user
+            .id,
+        id,
+    );
+    // Every source of an item is the same title, so the first one that was
+    // probed speaks for all of them.
+    let subtitles = sources
+        .iter()
+        .find_map(|s| {
+            s.probe_data
+                .as_ref()
+                .map(|p| (s.id, p))
+        })
+        .map(|(source_id, probe)| {
+            probe
+                .media_streams
+                .iter()
+                .filter(|s| {
+                    matches!(s.type_, Some(api::MediaStreamType::Subtitle))
+                        && s.is_text_subtitle_stream
+                })
+                .map(|s| Subtitle {
+                    id: format!("{source_id}-{}", s.index),
+                    url: format!(
+                        "{base}/stremio/play/{stream_token}/{id}/subtitles/{source_id}/{}",
+                        s.index
+                    ),
+                    sub_encoding: None,
+                    lang: s
+                        .language
+                        .clone(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(Json(SubtitlesResponse { subtitles }).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_test::{
+        AUTH_HEADER, auth_header_with_token, authenticated_server,
+        create_user_with_policy, stable_id,
+    };
+    use http::header::HeaderValue;
+
+    #[test]
+    fn install_url_swaps_the_scheme() {
+        let addon = AddonResponse::new("https://media.example.com", "abc");
+        assert_eq!(
+            addon.manifest_url,
+            "https://media.example.com/stremio/abc/manifest.json"
+        );
+        assert_eq!(
+            addon.install_url,
+            "stremio://media.example.com/stremio/abc/manifest.json"
+        );
+    }
+
+    #[test]
+    fn stream_tokens_play_only_their_item() {
+        let user = Uuid::new_v4();
+        let item = Uuid::new_v4();
+        let token = stream_token(user, item);
+        assert_eq!(verify_stream_token(&token, item), Some(user));
+        assert_eq!(verify_stream_token(&token, Uuid::new_v4()), None);
+
+        let (claims, tag) = token
+            .rsplit_once('.')
+            .unwrap();
+        let other_user = format!(
+            "{}.{}",
+            Uuid::new_v4().simple(),
+            claims
+                .split_once('.')
+                .unwrap()
+                .1
+        );
+        assert_eq!(
+            verify_stream_token(&format!("{other_user}.{tag}"), item),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn smart_collections_are_published_as_catalogs() {
+        let (server, guard, token) = authenticated_server().await;
+        let db = &guard
+            .0
+            .db;
+        let mut collection = db::Media {
+            title: "Favourites".into(),
+            kind: db::MediaKind::Collection,
+            collection_kind: Some(db::CollectionKind::Smart),
+            collection_media_kind: Some(db::CollectionMediaKind::Mixed),
+            ..Default::default()
+        };
+        collection
+            .save(db)
+            .await
+            .unwrap();
+        let external_ids = db::ExternalIds {
+            imdb: db::NonEmptyString::try_new("tt0078748".to_string()).ok(),
+            ..Default::default()
+        };
+        let mut movie = db::Media {
+            id: stable_id(db::MediaKind::Movie, &external_ids),
+            title: "Alien".into(),
+            kind: db::MediaKind::Movie,
+            external_ids,
+            ..Default::default()
+        };
+        movie
+            .save(db)
+            .await
+            .unwrap();
+
+        let addon: serde_json::Value = server
+            .post("/remux/stremio")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&token)).unwrap(),
+            )
+            .await
+            .json();
+        let manifest_url = addon["manifestUrl"]
+            .as_str()
+            .unwrap();
+        let prefix = manifest_url
+            .find("/stremio/")
+            .unwrap();
+        let root = manifest_url[prefix..].trim_end_matches("/manifest.json");
+
+        let manifest: serde_json::Value = server
+            .get(&format!("{root}/manifest.json"))
+            .await
+            .json();
+        let catalogs = manifest["catalogs"]
+            .as_array()
+            .unwrap();
+        assert_eq!(catalogs.len(), 2);
+        assert_eq!(
+            catalogs[0]["id"],
+            collection
+                .id
+                .to_string()
+        );
+        assert_eq!(catalogs[0]["type"], "movie");
+        assert_eq!(catalogs[1]["type"], "series");
+
+        let meta: serde_json::Value = server
+            .get(&format!("{root}/meta/movie/remux:{}.json", movie.id))
+            .await
+            .json();
+        assert_eq!(meta["meta"]["name"], "Alien");
+        assert_eq!(meta["meta"]["type"], "movie");
+
+        // The player's own session token is not an addon token.
+        server
+            .get(&format!("/stremio/{token}/manifest.json"))
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        // Nor does the addon token open the rest of the API.
+        let addon_token = root.trim_start_matches("/stremio/");
+        server
+            .get("/users/me")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(addon_token)).unwrap(),
+            )
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn play_is_refused_once_the_daily_watch_time_is_used_up() {
+        let (server, guard, admin_token) = authenticated_server().await;
+        let user_id: Uuid = create_user_with_policy(
+            &server,
+            &admin_token,
+            "limited",
+            "pass1234",
+            serde_json::json!({ "DailyWatchTimeLimitMinutes": 1 }),
+        )
+        .await
+        .parse()
+        .unwrap();
+        let token = server
+            .post("/users/authenticatebyname")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_static(AUTH_HEADER),
+            )
+            .json(&serde_json::json!({ "Username": "limited", "Pw": "pass1234" }))
+            .await
+            .json::<serde_json::Value>()["AccessToken"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        server
+            .post("/remux/stremio")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&token)).unwrap(),
+            )
+            .await
+            .assert_status_ok();
+
+        db::UserWatchTime::add(
+            &guard
+                .0
+                .db,
+            &user_id,
+            chrono::Local::now().date_naive(),
+            60,
+        )
+        .await
+        .unwrap();
+        let item = Uuid::new_v4();
+        server
+            .get(&format!(
+                "/stremio/play/{}/{item}",
+                stream_token(user_id, item)
+            ))
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn streams_of_an_unknown_item_are_empty() {
+        let (server, _guard, token) = authenticated_server().await;
+        let addon: serde_json::Value = server
+            .post("/remux/stremio")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&token)).unwrap(),
+            )
+            .await
+            .json();
+        let manifest_url = addon["manifestUrl"]
+            .as_str()
+            .unwrap();
+        let prefix = manifest_url
+            .find("/stremio/")
+            .unwrap();
+        let root = manifest_url[prefix..].trim_end_matches("/manifest.json");
+
+        let streams: serde_json::Value = server
+            .get(&format!(
+                "{root}/stream/movie/remux:{}.json",
+                Uuid::new_v4()
+            ))
+            .await
+            .json();
+        assert_eq!(streams["streams"], serde_json::json!([]));
+    }
+}
@@ -0,0 +1,1754 @@
+//! OpenSubsonic API for music clients.
+//!
+//! Subsonic apps (Symfonium, Feishin, ...) talk to `/rest/{method}` with the
+//! credentials and arguments in the query string, or in a form body with the
+//! `formPost` extension. Every method answers HTTP 200 with a
+//! `subsonic-response` envelope — XML unless `f=json` — and failures are
+//! reported inside it with a Subsonic error code. Artists, albums and tracks
+//! are the library's music rows, listed through the same item query as the
+//! Jellyfin API so permissions and user data apply unchanged.
+//!
+//! Clients sign in with `apiKey` or with `u` and `p`. Salted token auth
+//! (`t`/`s`), the default in Symfonium and Feishin, is refused, so those
+//! clients have to be switched to plain password ("legacy") auth or an API
+//! key.
+
+use std::{collections::BTreeMap, sync::LazyLock, time::Duration};
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, RawQuery, State},
+    response::{IntoResponse, Response},
+};
+use axum_anyhow::ApiError;
+use axum_extra::extract::Query;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use http::{HeaderMap, StatusCode, header::CONTENT_TYPE};
+use remux_macros::route;
+use ring::{hmac, rand::SystemRandom};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use serde_with::skip_serializing_none;
+use uuid::Uuid;
+
+use crate::{
+    AppState,
+    addons::media_tracker::MediaTrackerEvent,
+    api::{self, GetItemsQuery, ItemFilter, ItemSortBy, MediaType},
+    db::{self, auth},
+    network::{self, ClientAddr, ensure_remote_access},
+    playback::audio::AudioFormat,
+    services::{self, MediaResolveService},
+};
+
+use super::{images::items_images, items::get_items, lyrics::build_search_request};
+
+/// Subsonic API level the responses claim to implement.
+const API_VERSION: &str = "1.16.1";
+
+const XMLNS: &str = "http://subsonic.org/restapi";
+
+/// `getAlbumList2` caps `size` at 500 like the reference server.
+const MAX_LIST_SIZE: u32 = 500;
+
+const TICKS_PER_SECOND: i64 = 10_000_000;
+
+/// Users who recently signed in with `u` and `p`, keyed by a digest of the
+/// credentials. Clients send them with every request, each cover and stream
+/// included, and checking them is an argon2 hash and maybe an LDAP bind.
+static VERIFIED: LazyLock<moka::sync::Cache<Vec<u8>, Uuid>> = LazyLock::new(|| {
+    moka::sync::Cache::builder()
+        .max_capacity(1_000)
+        .time_to_live(Duration::from_secs(5 * 60))
+        .build()
+});
+
+/// Forget every remembered sign-in, so a changed password stops working at
+/// once.
+pub(crate) fn forget_verified_passwords() {
+    VERIFIED.invalidate_all();
+}
+
+/// A digest of a username and password under a key that only lives as long
+/// as the process, so the cache holds nothing a password can be read from.
+fn credentials_digest(username: &str, password: &str) -> Vec<u8> {
+    static KEY: LazyLock<hmac::Key> = LazyLock::new(|| {
+        hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+            .expect("the system random number generator failed")
+    });
+    hmac::sign(&KEY, format!("{username}\0{password}").as_bytes())
+        .as_ref()
+        .to_vec()
+}
+
+#[route("/rest/{method}", method = "GET", method = "POST")]
+pub async fn rest(
+    State(state): State<AppState>,
+    Path(method): Path<String>,
+    headers: HeaderMap,
+    client: ClientAddr,
+    RawQuery(query): RawQuery,
+    body: Bytes,
+) -> Response {
+    let params = Params::parse(query.as_deref(), &body);
+    let format = Format::of(&params);
+    let session = authenticate(&state, &params)
+        .await
+        .and_then(|session| {
+            ensure_remote_access(&session.user, &client)?;
+            Ok(session)
+        });
+    let reply = match session {
+        Ok(session) => {
+            dispatch(&state, session, headers, &client, &method, &params).await
+        }
+        Err(err) => Err(err),
+    };
+    match reply {
+        Ok(Reply::Fields(fields)) => render(format, "ok", fields),
+        Ok(Reply::Raw(response)) => response,
+        Err(err) => render(format, "failed", err.into_fields()),
+    }
+}
+
+async fn dispatch(
+    state: &AppState,
+    session: auth::AuthSession,
+    headers: HeaderMap,
+    client: &ClientAddr,
+    method: &str,
+    params: &Params,
+) -> Result<Reply, SubsonicError> {
+    // Clients append `.view` for servers that predate extension-less routes.
+    let method = method
+        .trim_end_matches(".view")
+        .to_ascii_lowercase();
+    match method.as_str() {
+        "ping" => Ok(Reply::Fields(Map::new())),
+        "getlicense" => Ok(Reply::field("license", json!({ "valid": true }))),
+        "getopensubsonicextensions" => Ok(Reply::field(
+            "openSubsonicExtensions",
+            json!([
+                { "name": "apiKeyAuthentication", "versions": [1] },
+                { "name": "formPost", "versions": [1] },
+                { "name": "songLyrics", "versions": [1] },
+                { "name": "transcodeOffset", "versions": [1] },
+            ]),
+        )),
+        "getmusicfolders" => Ok(Reply::field(
+            "musicFolders",
+            json!({ "musicFolder": [{ "id": 1, "name": "Music" }] }),
+        )),
+        method => match endpoint(state, session, headers, client, method, params) {
+            Some(endpoint) => endpoint.await,
+            None => Err(SubsonicError::new(0, format!("Unknown method: {method}"))),
+        },
+    }
+}
+
+/// The future for a method that does I/O. Each one is boxed here, outside
+/// any async frame, so `dispatch` holds a single pointer rather than room
+/// for every endpoint.
+fn endpoint<'a>(
+    state: &'a AppState,
+    session: auth::AuthSession,
+    headers: HeaderMap,
+    client: &'a ClientAddr,
+    method: &str,
+    params: &'a Params,
+) -> Option<BoxFuture<'a, Result<Reply, SubsonicError>>> {
+    Some(match method {
+        "getartists" => Box::pin(get_artists(state, session)),
+        "getartist" => Box::pin(get_artist(state, session, params)),
+        "getalbum" => Box::pin(get_album(state, session, params)),
+        "getalbumlist2" => Box::pin(get_album_list2(state, session, params)),
+        "search3" => Box::pin(search3(state, session, params)),
+        "getplaylists" => Box::pin(get_playlists(state, session)),
+        "getplaylist" => Box::pin(get_playlist(state, session, params)),
+        "star" => Box::pin(star(state, session, params, true)),
+        "unstar" => Box::pin(star(state, session, params, false)),
+        "scrobble" => Box::pin(scrobble(state, session, params)),
+        "getlyricsbysongid" => Box::pin(get_lyrics_by_song_id(state, params)),
+        "getcoverart" => Box::pin(get_cover_art(state, params)),
+        "stream" => Box::pin(stream(state, session, headers, client, params, false)),
+        "download" => Box::pin(stream(state, session, headers, client, params, true)),
+        _ => return None,
+    })
+}
+
+/// What a method answers with.
+enum Reply {
+    /// Fields merged into the `subsonic-response` envelope.
+    Fields(Map<String, Value>),
+    /// Media bytes (streams, cover art), sent without an envelope.
+    Raw(Response),
+}
+
+impl Reply {
+    fn field(name: &str, value: impl Serialize) -> Self {
+        let mut fields = Map::new();
+        fields.insert(
+            name.to_string(),
+            serde_json::to_value(value).unwrap_or(Value::Null),
+        );
+        Self::Fields(fields)
+    }
+}
+
+#[derive(Debug)]
+struct SubsonicError {
+    code: u32,
+    message: String,
+}
+
+impl SubsonicError {
+    fn new(code: u32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn missing(name: &str) -> Self {
+        Self::new(10, format!("Required parameter is missing: {name}"))
+    }
+
+    fn not_found() -> Self {
+        Self::new(70, "The requested data was not found")
+    }
+
+    fn not_authorized() -> Self {
+        Self::new(50, "User is not authorized for the given operation")
+    }
+
+    fn into_fields(self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        fields.insert(
+            "error".into(),
+            json!({ "code": self.code, "message": self.message }),
+        );
+        fields
+    }
+}
+
+impl From<ApiError> for SubsonicError {
+    fn from(err: ApiError) -> Self {
+        match err
+            .into_response()
+            .status()
+        {
+            StatusCode::NOT_FOUND => Self::not_found(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Self::not_authorized(),
+            status => Self::new(
+                0,
+                status
+                    .canonical_reason()
+                    .unwrap_or("Request failed"),
+            ),
+        }
+    }
+}
+
+impl From<anyhow::Error> for SubsonicError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::new(0, format!("{err:#}"))
+    }
+}
+
+impl From<sqlx::Error> for SubsonicError {
+    fn from(err: sqlx::Error) -> Self {
+        anyhow::Error::from(err).into()
+    }
+}
+
+/// Query string and form body arguments. Subsonic repeats a name for lists
+/// (`id=a&id=b`), so this keeps every pair in order.
+struct Params(Vec<(String, String)>);
+
+impl Params {
+    fn parse(query: Option<&str>, body: &[u8]) -> Self {
+        let query = url::form_urlencoded::parse(
+            query
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+        let body = url::form_urlencoded::parse(body);
+        Self(
+            query
+                .chain(body)
+                .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                .collect(),
+        )
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.0
+            .iter()
+            .filter(move |(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn require(&self, name: &str) -> Result<&str, SubsonicError> {
+        self.get(name)
+            .ok_or_else(|| SubsonicError::missing(name))
+    }
+
+    fn number<T: std::str::FromStr>(
+        &self,
+        name: &str,
+    ) -> Result<Option<T>, SubsonicError> {
+        self.get(name)
+            .map(|v| {
+                v.parse()
+                    .map_err(|_| {
+                        SubsonicError::new(0, format!("Invalid value for {name}: {v}"))
+                    })
+            })
+            .transpose()
+    }
+
+    fn bool(&self, name: &str) -> Option<bool> {
+        self.get(name)
+            .map(|v| v.eq_ignore_ascii_case("true"))
+    }
+
+    fn id(&self, name: &str) -> Result<Uuid, SubsonicError> {
+        parse_id(self.require(name)?)
+    }
+}
+
+fn parse_id(id: &str) -> Result<Uuid, SubsonicError> {
+    id.parse()
+        .map_err(|_| SubsonicError::not_found())
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Xml,
+    Json,
+}
+
+impl Format {
+    fn of(params: &Params) -> Self {
+        match params.get("f") {
+            Some("json") => Self::Json,
+            _ => Self::Xml,
+        }
+    }
+}
+
+fn render(format: Format, status: &str, fields: Map<String, Value>) -> Response {
+    let mut root = Map::new();
+    root.insert("status".into(), status.into());
+    root.insert("version".into(), API_VERSION.into());
+    root.insert("type".into(), "remux".into());
+    root.insert("serverVersion".into(), env!("CARGO_PKG_VERSION").into());
+    root.insert("openSubsonic".into(), true.into());
+    root.extend(fields);
+    match format {
+        Format::Json => Json(json!({ "subsonic-response": root })).into_response(),
+        Format::Xml => {
+            ([(CONTENT_TYPE, "text/xml; charset=utf-8")], to_xml(&root)).into_response()
+        }
+    }
+}
+
+/// The XML form of the envelope: scalar fields become attributes, objects
+/// child elements, arrays repeated elements, and a `value` field the text.
+fn to_xml(root: &Map<String, Value>) -> String {
+    let mut out = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    write_element(&mut out, "subsonic-response", root, Some(XMLNS));
+    out
+}
+
+fn write_element(
+    out: &mut String,
+    name: &str,
+    fields: &Map<String, Value>,
+    xmlns: Option<&str>,
+) {
+    out.push('<');
+    out.push_str(name);
+    if let Some(xmlns) = xmlns {
+        out.push_str(&format!(r#" xmlns="{xmlns}""#));
+    }
+    let mut text = None;
+    let mut children = Vec::new();
+    for (key, value) in fields {
+        match value {
+            Value::Null => {}
+            Value::Array(_) | Value::Object(_) => children.push((key, value)),
+            _ if key == "value" => text = Some(scalar_text(value)),
+            _ => out.push_str(&format!(
+                r#" {key}="{}""#,
+                quick_xml::escape::escape(scalar_text(value))
+            )),
+        }
+    }
+    if text.is_none() && children.is_empty() {
+        out.push_str("/>");
+        return;
+    }
+    out.push('>');
+    if let Some(text) = text {
+        out.push_str(&quick_xml::escape::escape(text));
+    }
+    for (key, value) in children {
+        write_value(out, key, value);
+    }
+    out.push_str(&format!("</{name}>"));
+}
+
+fn write_value(out: &mut String, name: &str, value: &Value) {
+    match value {
+        Value::Null => {}
+        Value::Object(fields) => write_element(out, name, fields, None),
+        Value::Array(items) => {
+            for item in items {
+                write_value(out, name, item);
+            }
+        }
+        _ => out.push_str(&format!(
+            "<{name}>{}</{name}>",
+            quick_xml::escape::escape(scalar_text(value))
+        )),
+    }
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Resolve the caller from `apiKey` (a device or API key token) or from
+/// `u` and `p`. Salted token auth (`t`/`s`) needs the cleartext password,
+/// which is never stored, so it is refused with the code that tells
+/// clients to fall back.
+async fn authenticate(
+    state: &AppState,
+    params: &Params,
+) -> Result<auth::AuthSession, SubsonicError> {
+    let db = &state
+        .ctx
+        .db;
+    if let Some(key) = params.get("apiKey") {
+        if params
+            .get("u")
+            .is_some()
+        {
+            return Err(SubsonicError::new(
+                43,
+                "Multiple conflicting authentication mechanisms provided",
+            ));
+        }
+        if let Some(device) = auth::Device::get_by_access_token(db, key)
+            .await?
+            .filter(|device| !super::stremio::is_addon_device(device))
+        {
+            let user = db::User::get_by_id(db, &device.user_id)
+                .await?
+                .ok_or_else(|| SubsonicError::new(44, "Invalid API key"))?;
+            super::users::ensure_access_allowed(&user)?;
+            return Ok(auth::AuthSession { device, user });
+        }
+        let session = auth::AuthSession::from_api_key(db, key)
+            .await?
+            .ok_or_else(|| SubsonicError::new(44, "Invalid API key"))?;
+        super::users::ensure_access_allowed(&session.user)?;
+        return Ok(session);
+    }
+
+    let username = params.require("u")?;
+    let Some(password) = params.get("p") else {
+        if params
+            .get("t")
+            .is_some()
+        {
+            return Err(SubsonicError::new(
+                41,
+                "Token authentication not supported, use a password or API key",
+            ));
+        }
+        return Err(SubsonicError::missing("p"));
+    };
+    let password = decode_password(password)?;
+    let digest = credentials_digest(username, &password);
+    let cached = match VERIFIED.get(&digest) {
+        Some(user_id) => db::User::get_by_id(db, &user_id).await?,
+        None => None,
+    };
+    let user = match cached {
+        Some(user) => user,
+        None => {
+            let user = super::users::authenticate_password(db, username, &password)
+                .await?
+                .ok_or_else(|| SubsonicError::new(40, "Wrong username or password"))?;
+            VERIFIED.insert(digest, user.id);
+            user
+        }
+    };
+    super::users::ensure_access_allowed(&user)?;
+
+    let client = params
+        .get("c")
+        .unwrap_or("Subsonic");
+    let device = auth::Device {
+        id: format!("subsonic-{client}"),
+        user_id: user.id,
+        name: client.to_string(),
+        app_name: client.to_string(),
+        app_version: params
+            .get("v")
+            .unwrap_or(API_VERSION)
+            .to_string(),
+        ..Default::default()
+    };
+    Ok(auth::AuthSession { device, user })
+}
+
+/// Passwords may arrive hex encoded as `enc:...`.
+fn decode_password(password: &str) -> Result<String, SubsonicError> {
+    let Some(hex) = password.strip_prefix("enc:") else {
+        return Ok(password.to_string());
+    };
+    let invalid = || SubsonicError::new(40, "Wrong username or password");
+    if hex.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(invalid)?;
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+async fn query_items(
+    state: &AppState,
+    session: &auth::AuthSession,
+    q: GetItemsQuery,
+) -> Result<Vec<api::BaseItemDto>, SubsonicError> {
+    let q = GetItemsQuery {
+        user_id: Some(
+            session
+                .user
+                .id,
+        ),
+        recursive: true,
+        ..q
+    };
+    // Boxed: `get_items` polls deep enough to overflow a 2 MiB stack in
+    // debug builds when it runs under this handler.
+    Ok(
+        Box::pin(get_items(state.clone(), session.clone(), q, false))
+            .await?
+            .preload_playlist_runtimes(
+                &state
+                    .ctx
+                    .db,
+            )
+            .await
+            .with_permissions()
+            .build()
+            .items,
+    )
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Artist {
+    id: Uuid,
+    name: String,
+    cover_art: Option<String>,
+    album_count: Option<i64>,
+    starred: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    album: Vec<Album>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Album {
+    id: Uuid,
+    name: String,
+    artist: Option<String>,
+    artist_id: Option<Uuid>,
+    cover_art: Option<String>,
+    song_count: i64,
+    duration: i64,
+    play_count: Option<i64>,
+    created: Option<String>,
+    year: Option<i64>,
+    genre: Option<String>,
+    starred: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    song: Vec<Song>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Song {
+    id: Uuid,
+    parent: Option<Uuid>,
+    is_dir: bool,
+    title: String,
+    album: Option<String>,
+    artist: Option<String>,
+    track: Option<i64>,
+    year: Option<i64>,
+    genre: Option<String>,
+    cover_art: Option<String>,
+    duration: i64,
+    bit_rate: Option<i64>,
+    suffix: Option<String>,
+    content_type: Option<String>,
+    album_id: Option<Uuid>,
+    artist_id: Option<Uuid>,
+    disc_number: Option<i64>,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    media_type: &'static str,
+    play_count: Option<i64>,
+    starred: Option<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Playlist {
+    id: Uuid,
+    name: String,
+    owner: String,
+    public: bool,
+    song_count: usize,
+    duration: i64,
+    created: Option<String>,
+    changed: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    entry: Vec<Song>,
+}
+
+fn seconds(ticks: Option<i64>) -> i64 {
+    ticks.unwrap_or_default() / TICKS_PER_SECOND
+}
+
+/// Favorites carry no timestamp until they are re-saved, so the last user
+/// data update stands in for it.
+fn starred(item: &api::BaseItemDto) -> Option<String> {
+    item.user_data
+        .as_ref()
+        .filter(|u| u.is_favorite)
+        .map(|u| {
+            u.favorite_added_date
+                .or(u.last_updated)
+                .unwrap_or(DateTime::<Utc>::UNIX_EPOCH)
+                .to_rfc3339()
+        })
+}
+
+fn play_count(item: &api::BaseItemDto) -> Option<i64> {
+    item.user_data
+        .as_ref()
+        .map(|u| u.play_count as i64)
+}
+
+fn to_artist(item: api::BaseItemDto) -> Artist {
+    Artist {
+        id: item.id,
+        cover_art: Some(
+            item.id
+                .to_string(),
+        ),
+        album_count: item
+            .album_count
+            .or(item.child_count),
+        starred: starred(&item),
+        name: item
+            .name
+            .unwrap_or_default(),
+        album: Vec::new(),
+    }
+}
+
+fn to_album(item: api::BaseItemDto) -> Album {
+    let (artist, artist_id) = item
+        .album_artists
+        .first()
+        .or(item
+            .artist_items
+            .first())
+        .map(|a| {
+            (
+                Some(
+                    a.name
+                        .clone(),
+                ),
+                Some(a.id),
+            )
+        })
+        .unwrap_or((
+            item.album_artist
+                .clone(),
+            None,
+        ));
+    Album {
+        id: item.id,
+        artist,
+        artist_id,
+        cover_art: Some(
+            item.id
+                .to_string(),
+        ),
+        song_count: item
+            .song_count
+            .or(item.child_count)
+            .unwrap_or_default(),
+        duration: seconds(item.run_time_ticks),
+        play_count: play_count(&item),
+        created: item
+            .date_created
+            .clone(),
+        year: item.production_year,
+        genre: item
+            .genres
+            .first()
+            .cloned(),
+        starred: starred(&item),
+        name: item
+            .name
+            .unwrap_or_default(),
+        song: Vec::new(),
+    }
+}
+
+fn to_song(item: api::BaseItemDto) -> Song {
+    let artist = item
+        .album_artist
+        .clone()
+        .or_else(|| {
+            (!item
+                .artists
+                .is_empty())
+            .then(|| {
+                item.artists
+                    .join(", ")
+            })
+        });
+    let artist_id = item
+        .album_artists
+        .first()
+        .or(item
+            .artist_items
+            .first())
+        .map(|a| a.id);
+    let suffix = item
+        .container
+        .as_deref()
+        .and_then(|c| {
+            c.split(',')
+                .next()
+        })
+        .map(str::to_ascii_lowercase);
+    let content_type = suffix
+        .as_deref()
+        .map(|s| {
+            AudioFormat::parse(s)
+                .map(|f| f.content_type())
+                .unwrap_or("application/octet-stream")
+                .to_string()
+        });
+    // Tracks rarely carry artwork of their own; the album's stands in.
+    let cover_art = item
+        .album_id
+        .filter(|_| {
+            item.album_primary_image_tag
+                .is_some()
+                || item
+                    .image_tags
+                    .as_ref()
+                    .and_then(|t| {
+                        t.primary
+                            .as_ref()
+                    })
+                    .is_none()
+        })
+        .unwrap_or(item.id);
+    Song {
+        id: item.id,
+        parent: item.album_id,
+        is_dir: false,
+        album: item
+            .album
+            .clone(),
+        artist,
+        track: item.index_number,
+        year: item.production_year,
+        genre: item
+            .genres
+            .first()
+            .cloned(),
+        cover_art: Some(cover_art.to_string()),
+        duration: seconds(item.run_time_ticks),
+        bit_rate: item
+            .bitrate
+            .map(|b| b / 1000),
+        suffix,
+        content_type,
+        album_id: item.album_id,
+        artist_id,
+        disc_number: item.parent_index_number,
+        kind: "music",
+        media_type: "song",
+        play_count: play_count(&item),
+        starred: starred(&item),
+        title: item
+            .name
+            .unwrap_or_default(),
+    }
+}
+
+async fn get_artists(
+    state: &AppState,
+    session: auth::AuthSession,
+) -> Result<Reply, SubsonicError> {
+    let artists = query_items(
+        state,
+        &session,
+        GetItemsQuery {
+            include_item_types: Some(vec![MediaType::MusicArtist]),
+            sort_by: Some(vec![ItemSortBy::SortName]),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let mut index: BTreeMap<String, Vec<Artist>> = BTreeMap::new();
+    for artist in artists
+        .into_iter()
+        .map(to_artist)
+    {
+        let letter = artist
+            .name
+            .chars()
+            .next()
+            .filter(|c| c.is_alphabetic())
+            .map(|c| {
+                c.to_uppercase()
+                    .to_string()
+            })
+            .unwrap_or_else(|| "#".into());
+        index
+            .entry(letter)
+            .or_default()
+            .push(artist);
+    }
+    let index: Vec<Value> = index
+        .into_iter()
+        .map(|(name, artist)| json!({ "name": name, "artist": artist }))
+        .collect();
+    Ok(Reply::field(
+        "artists",
+        json!({ "ignoredArticles": "", "index": index }),
+    ))
+}
+
+async fn get_artist(
+    state: &AppState,
+    session: auth::AuthSession,
+    params: &Params,
+) -> Result<Reply, SubsonicError> {
+    let id = params.id("id")?;
+    let artist = query_items(
+        state,
+        &session,
+        GetItemsQuery {
+            ids: Some(vec![id]),
+            include_item_types: Some(vec![MediaType::MusicArtist]),
+            ..Default::default()
+        },
+    )
+    .await?
+    .into_iter()
+    .next()
+    .ok_or_else(SubsonicError::not_found)?;
+    let albums = query_items(
+        state,
+        &session,
+        GetItemsQuery {
+            artist_ids: Some(vec![id]),
+            include_item_types: Some(vec![MediaType::MusicAlbum]),
+            sort_by: Some(vec![ItemSortBy::ProductionYear, ItemSortBy::SortName]),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let mut artist = to_artist(artist);
+    artist.album = albums
+        .into_iter()
+        .map(to_album)
+        .collect();
+    artist.album_count = Some(
+        artist
+            .album
+            .len() as i64,
+    );
+    Ok(Reply::field("artist", artist))
+}
+
+async fn album_songs(
+    state: &AppState,
+    session: &auth::AuthSession,
+    album_id: Uuid,
+) -> Result<Vec<Song>, SubsonicError> {
+    Ok(query_items(
+        state,
+        session,
+        GetItemsQuery {
+            parent_id: Some(album_id),
+            include_item_types: Some(vec![MediaType::Audio]),
+            sort_by: Some(vec![
+                ItemSortBy::ParentIndexNumber,
+                ItemSortBy::IndexNumber,
+                ItemSortBy::SortName,
+            ]),
+            ..Default::default()
+        },
+    )
+    .await?
+    .into_iter()
+    .map(to_song)
+    .collect())
+}
+
+async fn get_album(
+    state: &AppState,
+    session: auth::AuthSession,
+    params: &Params,
+) -> Result<Reply, SubsonicError> {
+    let id = params.id("id")?;
+    let album = query_items(
+        state,
+        &session,
+        GetItemsQuery {
+            ids: Some(vec![id]),
+            include_item_types: Some(vec![MediaType::MusicAlbum]),
+            ..Default::default()
+        },
+    )
+    .await?
+    .into_iter()
+    .next()
+    .ok_or_else(SubsonicError::not_found)?;
+    let mut album = to_album(album);
+    album.song = album_songs(state, &session, id).await?;
+    album.song_count = album
+        .song
+        .len() as i64;
+    album.duration = album
+        .song
+        .iter()
+        .map(|s| s.duration)
+        .sum();
+    Ok(Reply::field("album", album))
+}
+
+async fn get_album_list2(
+    state: &AppState,
+    session: auth::AuthSession,
+    params: &Params,
+) -> Result<Reply, SubsonicError> {
+    let kind = params.require("type")?;
+    let mut q = GetItemsQuery {
+        include_item_types: Some(vec![MediaType::MusicAlbum]),
+        start_index: params.number("offset")?,
+        limit: Some(
+            params
+                .number("size")?
+                .unwrap_or(10)
+                .min(MAX_LIST_SIZE),
+        ),
+        ..Default::default()
+    };
+    match kind {
+        "random" => q.sort_by = Some(vec![ItemSortBy::Random]),
+        "newest" => {
+            q.sort_by = Some(vec![ItemSortBy::DateCreated]);
+            q.sort_order = Some(vec![api::SortOrder::Descending]);
+        }
+        "recent" | "frequent" => {
+            q.sort_by = Some(vec![if kind == "recent" {
+                ItemSortBy::DatePlayed
+            } else {
+                ItemSortBy::PlayCount
+            }]);
+            q.sort_order = Some(vec![api::SortOrder::Descending]);
+            q.filters = Some(vec![ItemFilter::IsPlayed]);
+        }
+        "starred" => {
+            q.is_favorite = Some(true);
+            q.sort_by = Some(vec![ItemSortBy::SortName]);
+        }
+        "alphabeticalByName" => q.sort_by = Some(vec![ItemSortBy::SortName]),
+        "alphabeticalByArtist" => {
+            q.sort_by = Some(vec![ItemSortBy::AlbumArtist, ItemSortBy::SortName]);
+        }
+        "byYear" => {
+            let from: i64 = params
+                .number("fromYear")?
+                .ok_or_else(|| SubsonicError::missing("fromYear"))?;
+            let to: i64 = params
+                .number("toYear")?
+                .ok_or_else(|| SubsonicError::missing("toYear"))?;
+            q.years = Some((from.min(to)..=from.max(to)).collect());
+            q.sort_by = Some(vec![ItemSortBy::ProductionYear, ItemSortBy::SortName]);
+            if from > to {
+                q.sort_order = Some(vec![api::SortOrder::Descending]);
+            }
+        }
+        "byGenre" => {
+            q.genres = Some(vec![
+                params
+                    .require("genre")?
+                    .to_string(),
+            ]);
+            q.sort_by = Some(vec![ItemSortBy::SortName]);
+        }
+        other => {
+            return Err(SubsonicError::new(
+                0,
+                format!("Unknown album list type: {other}"),
+            ));
+        }
+    }
+    let albums: Vec<Album> = query_items(state, &session, q)
+        .await?
+        .into_iter()
+        .map(to_album)
+        .collect();
+    Ok(Reply::field("albumList2", json!({ "album": albums })))
+}
+
+async fn search3(
+    state: &AppState,
+    session: auth::AuthSession,
+    params: &Params,
+) -> Result<Reply, SubsonicError> {
+    // Clients sync the whole library with an empty (or `""`) query.
+    let term = params
+        .get("query")
+        .unwrap_or_default()
+        .trim_matches('"')
+        .to_string();
+    let mut result = Map::new();
+    for (key, kind) in [
+        ("artist", MediaType::MusicArtist),
+        ("album", MediaType::MusicAlbum),
+        ("song", MediaType::Audio),
+    ] {
+        let count: u32 = params
+            .number(&format!("{key}Count"))?
+            .unwrap_or(20);
+        if count == 0 {
+            continue;
+        }
+        let items = query_items(
+            state,
+            &session,
+            GetItemsQuery {
+                include_item_types: Some(vec![kind]),
+                search_term: (!term.is_empty()).then(|| term.clone()),
+                sort_by: Some(vec![ItemSortBy::SortName]),
+                start_index: params.number(&format!("{key}Offset"))?,
+                limit: Some(count),
+                ..Default::default()
+            },
+        )
+        .await?;
+        let value = match kind {
+            MediaType::MusicArtist => json!(
+                items
+                    .into_iter()
+                    .map(to_artist)
+                    .collect::<Vec<_>>()
+            ),
+            MediaType::MusicAlbum => json!(
+                items
+                    .into_iter()
+                    .map(to_album)
+                    .collect::<Vec<_>>()
+            ),
+            _ => json!(
+                items
+                    .into_iter()
+                    .map(to_song)
+                    .collect::<Vec<_>>()
+            ),
+        };
+        result.insert(key.into(), value);
+    }
+    Ok(Reply::field("searchResult3", result))
+}
+
+/// Playlists are shared by everyone on the server, so each is reported as a
+/// public playlist owned by the caller.
+async fn to_playlist(
+    state: &AppState,
+    session: &auth::AuthSession,
+    playlist: db::Media,
+    with_entries: bool,
+) -> Result<Playlist, SubsonicError> {
+    let db = &state
+        .ctx
+        .db;
+    let ids: Vec<Uuid> = db::MediaRelation::get_playlist_items(db, &playlist.id)
+        .await?
+        .into_iter()
+        .map(|r| r.right_media_id)
+        .collect();
+    let mut tracks = db::Media::get_by_ids(db, &ids).await?;
+    tracks.retain(|m| m.kind == db::MediaKind::Track);
+    tracks.sort_by_key(|m| {
+        ids.iter()
+            .position(|id| *id == m.id)
+    });
+    let duration = tracks
+        .iter()
+        .filter_map(|m| m.runtime)
+        .sum::<i64>();
+    let song_count = tracks.len();
+    let entry = if with_entries {
+        let ids: Vec<Uuid> = tracks
+            .iter()
+            .map(|m| m.id)
+            .collect();
+        // Re-read through the item query for user data and permissions.
+        let items = query_items(
+            state,
+            session,
+            GetItemsQuery {
+                ids: Some(ids.clone()),
+                include_item_types: Some(vec![MediaType::Audio]),
+                ..Default::default()
+            },
+        )
+        .await?;
+        ids.iter()
+            .filter_map(|id| {
+                items
+                    .iter()
+                    .find(|i| i.id == *id)
+                    .cloned()
+            })
+            .map(to_song)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Ok(Playlist {
+        id: playlist.id,
+        name: playlist.title,
+        owner: session
+            .user
+            .username
+            .clone(),
+        public: true,
+        song_count,
+        duration,
+        created: Some(
+            playlist
+                .created_at
+                .and_utc()
+                .to_rfc3339(),
+        ),
+        changed: Some(
+            playlist
+                .updated_at
+                .and_utc()
+                .to_rfc3339(),
+        ),
+        entry,
+    })
+}
+
+async fn get_playlists(
+    state: &AppState,
+    session: auth::AuthSession,
+) -> Result<Reply, SubsonicError> {
+    let playlists = db::Media::get_by_filter(
+        &state
+            .ctx
+            .db,
+        &db::MediaFilter {
+            kind: Some(vec![db::MediaKind::Playlist]),
+            ..Default::default()
+        },
+    )
+    .await?
+    .records;
+    let mut out = Vec::with_capacity(playlists.len());
+    for p in playlists {
+        out.push(to_playlist(state, &session, p, false).await?);
+    }
+    Ok(Reply::field("playlists", json!({ "playlist": out })))
+}
+
+async fn get_playlist(
+    state: &AppState,
+    session: auth::AuthSession,
+    params: &Params,
+) -> Result<Reply, SubsonicError> {
+    let id = params.id("id")?;
+    let p = db::Media::get_by_id(
+        &state
+            .ctx
+            .db,
+        &id,
+    )
+    .await?
+    .filter(|m| m.kind == db::MediaKind::Playlist)
+    .ok_or_else(SubsonicError::not_found)?;
+    Ok(Reply::field(
+        "playlist",
+        to_playlist(state, &session, p, true).await?,
+    ))
+}
+
+async fn resolve(state: &AppState, id: &str) -> Result<db::Media, SubsonicError> {
+    MediaResolveService::resolve_item(parse_id(id)?, &state.ctx)
+        .await?
+        .ok_or_else(SubsonicError::not_found)
+}
+
+async fn star(
+    state: &AppState,
+    session: auth::AuthSession,
+    params: &Params,
+    favorite: bool,
+) -> Result<Reply, SubsonicError> {
+    let db = &state
+        .ctx
+        .db;
+    let ids = params
+        .all("id")
+        .chain(params.all("albumId"))
+        .chain(params.all("artistId"));
+    for id in ids {
+        let media = resolve(state, id).await?;
+        if favorite {
+            media
+                .mark_favorite(db, &session.user)
+                .await?;
+        } else {
+            media
+                .unmark_favorite(db, &session.user)
+                .await?;
+        }
+        services::media_tracker::enqueue_and_wake(
+            state,
+            session
+                .user
+                .id,
+            &media,
+            MediaTrackerEvent::Favorite {
+                is_favorite: favorite,
+            },
+        )
+        .await;
+    }
+    Ok(Reply::Fields(Map::new()))
+}
+
+/// `submission=false` announces a song that just started; anything else
+/// records a finished listen.
+async fn scrobble(
+    state: &AppState,
+    session: auth::AuthSession,
+    params: &Params,
+) -> Result<Reply, SubsonicError> {
+    let db = &state
+        .ctx
+        .db;
+    let submission = params
+        .bool("submission")
+        .unwrap_or(true);
+    let server_config = db::Settings::get_config_or_default(db).await;
+    for id in params.all("id") {
+        let media = resolve(state, id).await?;
+        let event = if submission {
+            media
+                .mark_played(
+                    db,
+                    &session.user,
+                    true,
+                    server_config.release_date_threshold(),
+                )
+                .await?;
+            MediaTrackerEvent::PlaybackStop {
+                position_ticks: media
+                    .runtime
+                    .unwrap_or_default()
+                    * TICKS_PER_SECOND,
+                played: true,
+            }
+        } else {
+            MediaTrackerEvent::PlaybackStart { position_ticks: 0 }
+        };
+        services::media_tracker::enqueue_and_wake(
+            state,
+            session
+                .user
+                .id,
+            &media,
+            event,
+        )
+        .await;
+    }
+    Ok(Reply::Fields(Map::new()))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StructuredLyrics {
+    display_artist: Option<String>,
+    display_title: Option<String>,
+    lang: &'static str,
+    synced: bool,
+    offset: i64,
+    line: Vec<LyricLine>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+struct LyricLine {
+    /// Milliseconds from the start of the song.
+    start: Option<i64>,
+    value: String,
+}
+
+async fn get_lyrics_by_song_id(
+    state: &AppState,
+    params: &Params,
+) -> Result<Reply, SubsonicError> {
+    let db = &state
+        .ctx
+        .db;
+    let media = db::Media::get_by_id(db, &params.id("id")?)
+        .await?
+        .filter(|m| m.kind == db::MediaKind::Track)
+        .ok_or_else(SubsonicError::not_found)?;
+    let req = build_search_request(db, &media).await;
+    let lyrics: Vec<StructuredLyrics> = state
+        .ctx
+        .addons
+        .lyric_fetch(&req, db)
+        .await?
+        .into_iter()
+        .map(|lyrics| {
+            let synced = lyrics
+                .metadata
+                .is_synced
+                .unwrap_or_else(|| {
+                    lyrics
+                        .lyrics
+                        .iter()
+                        .all(|l| {
+                            l.start
+                                .is_some()
+                        })
+                });
+            StructuredLyrics {
+                display_artist: lyrics
+                    .metadata
+                    .artist
+                    .or_else(|| {
+                        req.artist
+                            .clone()
+                    }),
+                display_title: lyrics
+                    .metadata
+                    .title
+                    .or_else(|| {
+                        Some(
+                            req.title
+                                .clone(),
+                        )
+                    }),
+                lang: "und",
+                synced,
+                offset: 0,
+                line: lyrics
+                    .lyrics
+                    .into_iter()
+                    .map(|l| LyricLine {
+                        start: l
+                            .start
+                            .filter(|_| synced)
+                            .map(|ticks| ticks / 10_000),
+                        value: l.text,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    Ok(Reply::field(
+        "lyricsList",
+        json!({ "structuredLyrics": lyrics }),
+    ))
+}
+
+async fn get_cover_art(
+    state: &AppState,
+    params: &Params,
+) -> Result<Reply, SubsonicError> {
+    let id = params.id("id")?;
+    let size = params.number("size")?;
+    let response = items_images(
+        State(state.clone()),
+        Path((id, api::ImageType::Primary)),
+        Query(api::ImageQuery {
+            max_width: size,
+            max_height: size,
+            ..Default::default()
+        }),
+    )
+    .await?;
+    Ok(Reply::Raw(response.into_response()))
+}
+
+/// The original file unless the client asks for another `format` or a
+/// `maxBitRate` (kbps), in which case the track goes through the music
+/// transcode pipeline. Downloads are always the original file.
+async fn stream(
+    state: &AppState,
+    session: auth::AuthSession,
+    headers: HeaderMap,
+    client: &ClientAddr,
+    params: &Params,
+    download: bool,
+) -> Result<Reply, SubsonicError> {
+    let id = params.id("id")?;
+    let policy = session
+        .user
+        .policy
+        .as_ref();
+    if download
+        && !policy
+            .map(|p| p.enable_content_downloading)
+            .unwrap_or(true)
+    {
+        return Err(SubsonicError::not_authorized());
+    }
+    super::playback::ensure_within_parental_limits(state, &session.user).await?;
+    let mut max_bit_rate: Option<u32> = params
+        .number("maxBitRate")?
+        .filter(|&kbps| kbps > 0);
+    // Remote clients are held to their remote bitrate limit on top of what
+    // they ask for.
+    if client.is_remote() {
+        let config = db::Settings::get_config_or_default(
+            &state
+                .ctx
+                .db,
+        )
+        .await;
+        if let Some(limit) = network::remote_bitrate_limit(&session.user, &config) {
+            let limit = (limit / 1000).clamp(1, u32::MAX as i64) as u32;
+            max_bit_rate = Some(max_bit_rate.map_or(limit, |max| max.min(limit)));
+        }
+    }
+    let requested = params
+        .get("format")
+        .filter(|f| *f != "raw");
+    let transcode_allowed = policy
+        .map(|p| p.enable_audio_playback_transcoding)
+        .unwrap_or(true);
+    let format = (!download
+        && transcode_allowed
+        && (requested.is_some() || max_bit_rate.is_some()))
+    .then(|| {
+        requested
+            .and_then(AudioFormat::parse)
+            .unwrap_or(AudioFormat::Mp3)
+    });
+    let q = match format {
+        Some(format) => api::VideoStreamQuery {
+            media_source_id: Some(id),
+            audio_codec: Some(
+                format
+                    .codec()
+                    .to_string(),
+            ),
+            audio_bit_rate: max_bit_rate.map(|kbps| kbps as i64 * 1000),
+            container: Some(
+                format
+                    .container()
+                    .to_string(),
+            ),
+            start_time_ticks: params
+                .number::<i64>("timeOffset")?
+                .map(|s| s * TICKS_PER_SECOND),
+            ..Default::default()
+        },
+        None => api::VideoStreamQuery {
+            static_: Some(true),
+            media_source_id: Some(id),
+            ..Default::default()
+        },
+    };
+    let response = super::playback::audio_stream(
+        headers,
+        State(state.clone()),
+        Path(id),
+        Query(q),
+    )
+    .await?;
+    Ok(Reply::Raw(response.into_response()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_test::{authenticated_server, seed_track};
+
+    fn json_url(method: &str, extra: &str) -> String {
+        format!("/rest/{method}?u=test&p=test&v=1.16.1&c=tests&f=json{extra}")
+    }
+
+    #[test]
+    fn xml_envelope_uses_attributes_and_repeated_elements() {
+        let mut root = Map::new();
+        root.insert("status".into(), "ok".into());
+        root.insert(
+            "artists".into(),
+            json!({ "index": [{ "name": "M", "artist": [{ "id": 1, "name": "Massive & Co" }] }] }),
+        );
+        assert_eq!(
+            to_xml(&root),
+            r#"<?xml version="1.0" encoding="UTF-8"?><subsonic-response xmlns="http://subsonic.org/restapi" status="ok"><artists><index name="M"><artist id="1" name="Massive &amp; Co"/></index></artists></subsonic-response>"#
+        );
+    }
+
+    #[test]
+    fn hex_encoded_passwords_are_decoded() {
+        assert_eq!(decode_password("enc:736563726574").unwrap(), "secret");
+        assert_eq!(decode_password("plain").unwrap(), "plain");
+        assert_eq!(
+            decode_password("enc:7")
+                .unwrap_err()
+                .code,
+            40
+        );
+    }
+
+    #[tokio::test]
+    async fn ping_answers_in_json_and_xml() {
+        let (server, _guard, _token) = authenticated_server().await;
+
+        let body: Value = server
+            .get(&json_url("ping", ""))
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["status"], "ok");
+        assert_eq!(body["subsonic-response"]["openSubsonic"], true);
+
+        let xml = server
+            .get("/rest/ping.view?u=test&p=enc:74657374")
+            .await
+            .text();
+        assert!(
+            xml.contains(r#"<subsonic-response xmlns="http://subsonic.org/restapi" "#)
+        );
+        assert!(xml.contains(r#"status="ok""#));
+
+        let body: Value = server
+            .get("/rest/ping?u=test&p=wrong&f=json")
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["status"], "failed");
+        assert_eq!(body["subsonic-response"]["error"]["code"], 40);
+    }
+
+    #[tokio::test]
+    async fn remembered_sign_ins_end_with_the_account() {
+        let (server, _guard, token) = authenticated_server().await;
+        let user_id = crate::integration_test::create_user_with_policy(
+            &server,
+            &token,
+            "sam",
+            "secret",
+            serde_json::json!({}),
+        )
+        .await;
+        let ping = || server.get("/rest/ping?u=sam&p=secret&f=json");
+
+        for _ in 0..2 {
+            let body: Value = ping()
+                .await
+                .json();
+            assert_eq!(body["subsonic-response"]["status"], "ok");
+        }
+
+        server
+            .delete(&format!("/users/{user_id}"))
+            .add_header(
+                http::header::AUTHORIZATION,
+                http::HeaderValue::from_str(
+                    &crate::integration_test::auth_header_with_token(&token),
+                )
+                .unwrap(),
+            )
+            .await
+            .assert_status_success();
+        let body: Value = ping()
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["error"]["code"], 40);
+    }
+
+    #[tokio::test]
+    async fn a_changed_password_stops_working_at_once() {
+        let (server, _guard, token) = authenticated_server().await;
+        let user_id = crate::integration_test::create_user_with_policy(
+            &server,
+            &token,
+            "kim",
+            "secret",
+            serde_json::json!({}),
+        )
+        .await;
+        let body: Value = server
+            .get("/rest/ping?u=kim&p=secret&f=json")
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["status"], "ok");
+
+        server
+            .post(&format!("/users/{user_id}/password"))
+            .add_header(
+                http::header::AUTHORIZATION,
+                http::HeaderValue::from_str(
+                    &crate::integration_test::auth_header_with_token(&token),
+                )
+                .unwrap(),
+            )
+            .json(&json!({ "NewPw": "changed" }))
+            .await
+            .assert_status_success();
+        let body: Value = server
+            .get("/rest/ping?u=kim&p=secret&f=json")
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["error"]["code"], 40);
+        let body: Value = server
+            .get("/rest/ping?u=kim&p=changed&f=json")
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["status"], "ok");
+    }
+
+    #[tokio::test]
+    async fn streams_are_refused_once_the_daily_watch_time_is_used_up() {
+        let (server, guard, token) = authenticated_server().await;
+        let user_id = crate::integration_test::create_user_with_policy(
+            &server,
+            &token,
+            "lee",
+            "secret",
+            serde_json::json!({ "DailyWatchTimeLimitMinutes": 1 }),
+        )
+        .await;
+        db::UserWatchTime::add(
+            &guard
+                .0
+                .db,
+            &user_id
+                .parse()
+                .unwrap(),
+            chrono::Local::now().date_naive(),
+            60,
+        )
+        .await
+        .unwrap();
+
+        let body: Value = server
+            .get(&format!(
+                "/rest/stream?u=lee&p=secret&f=json&id={}",
+                Uuid::new_v4()
+            ))
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["error"]["code"], 50);
+    }
+
+    #[tokio::test]
+    async fn api_key_authenticates_with_a_session_token() {
+        let (server, _guard, token) = authenticated_server().await;
+
+        let body: Value = server
+            .get(&format!("/rest/ping?apiKey={token}&f=json"))
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["status"], "ok");
+
+        let body: Value = server
+            .get(&format!("/rest/ping?apiKey={token}&u=test&f=json"))
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["error"]["code"], 43);
+    }
+
+    #[tokio::test]
+    async fn album_lists_its_songs_and_stars_stick() {
+        let (server, guard, _token) = authenticated_server().await;
+        let track = seed_track(&guard.0).await;
+        let album_id = track
+            .parent_id
+            .unwrap();
+
+        let body: Value = server
+            .get(&json_url("getAlbum", &format!("&id={album_id}")))
+            .await
+            .json();
+        let album = &body["subsonic-response"]["album"];
+        assert_eq!(album["name"], "Mezzanine");
+        assert_eq!(album["song"][0]["title"], "Teardrop");
+        assert_eq!(album["song"][0]["duration"], 330);
+
+        let album_param = album_id.to_string();
+        let body: Value = server
+            .post("/rest/star")
+            .form(&[
+                ("u", "test"),
+                ("p", "test"),
+                ("f", "json"),
+                ("albumId", album_param.as_str()),
+            ])
+            .await
+            .json();
+        assert_eq!(body["subsonic-response"]["status"], "ok");
+
+        let body: Value = server
+            .get(&json_url("getAlbumList2", "&type=starred"))
+            .await
+            .json();
+        let albums = &body["subsonic-response"]["albumList2"]["album"];
+        assert_eq!(albums[0]["id"], album_id.to_string());
+        assert!(albums[0]["starred"].is_string());
+    }
+}
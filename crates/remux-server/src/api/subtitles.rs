@@ -387,7 +387,7 @@ async fn sidecar_subtitle_response(
     )
 }
 
-async fn subtitles_stream_inner(
+pub(crate) async fn subtitles_stream_inner(
     state: AppState,
     session: auth::AuthSession,
     item_id: Uuid,
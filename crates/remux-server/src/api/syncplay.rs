@@ -0,0 +1,369 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use axum_anyhow::ApiResult as Result;
+use http::StatusCode;
+use remux_macros::{get, post};
+use uuid::Uuid;
+
+use crate::{AppState, IntoApiError, OptionExt, api, db, db::auth, syncplay::Member};
+
+/// Rejects users whose policy keeps them out of SyncPlay; creating a group
+/// takes the stronger of the two grants.
+fn require_access(session: &auth::AuthSession, create: bool) -> Result<()> {
+    let allowed = match session
+        .user
+        .sync_play_access()
+    {
+        api::SyncPlayUserAccessType::CreateAndJoinGroups => true,
+        api::SyncPlayUserAccessType::JoinGroups => !create,
+        api::SyncPlayUserAccessType::None => false,
+    };
+    if !allowed {
+        return Err(anyhow::anyhow!("Forbidden")
+            .context_forbidden("SyncPlay is not enabled for this user"));
+    }
+    Ok(())
+}
+
+#[get("/syncplay/list")]
+pub async fn syncplay_list(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    Ok(Json(
+        state
+            .ctx
+            .syncplay
+            .list(),
+    ))
+}
+
+#[get("/syncplay/{id}")]
+pub async fn syncplay_get(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    let group = state
+        .ctx
+        .syncplay
+        .get(&id)
+        .context_not_found("group not found")?;
+    Ok(Json(group))
+}
+
+#[post("/syncplay/new")]
+pub async fn syncplay_new(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Json(req): Json<api::NewGroupRequestDto>,
+) -> Result<impl IntoResponse> {
+    require_access(&session, true)?;
+    state
+        .ctx
+        .syncplay
+        .new_group(Member::from_session(&session), req.group_name);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/join")]
+pub async fn syncplay_join(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Json(req): Json<api::JoinGroupRequestDto>,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    state
+        .ctx
+        .syncplay
+        .join(Member::from_session(&session), req.group_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/leave")]
+pub async fn syncplay_leave(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    state
+        .ctx
+        .syncplay
+        .leave(
+            &session
+                .device
+                .id,
+        );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/setnewqueue")]
+pub async fn syncplay_set_new_queue(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Json(req): Json<api::PlayRequestDto>,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    for id in &req.playing_queue {
+        db::Media::get_by_id(
+            &state
+                .ctx
+                .db,
+            id,
+        )
+        .await?
+        .context_bad_request("queue holds an unknown item")?;
+    }
+    state
+        .ctx
+        .syncplay
+        .set_new_queue(
+            &session
+                .device
+                .id,
+            req.playing_queue,
+            req.playing_item_position,
+            req.start_position_ticks,
+        );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/pause")]
+pub async fn syncplay_pause(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    state
+        .ctx
+        .syncplay
+        .pause(
+            &session
+                .device
+                .id,
+        );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/unpause")]
+pub async fn syncplay_unpause(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    state
+        .ctx
+        .syncplay
+        .unpause(
+            &session
+                .device
+                .id,
+        );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/stop")]
+pub async fn syncplay_stop(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    state
+        .ctx
+        .syncplay
+        .stop(
+            &session
+                .device
+                .id,
+        );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/seek")]
+pub async fn syncplay_seek(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Json(req): Json<api::SeekRequestDto>,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    state
+        .ctx
+        .syncplay
+        .seek(
+            &session
+                .device
+                .id,
+            req.position_ticks,
+        );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/buffering")]
+pub async fn syncplay_buffering(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Json(req): Json<api::BufferRequestDto>,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    state
+        .ctx
+        .syncplay
+        .buffering(
+            &session
+                .device
+                .id,
+            req.playlist_item_id,
+            req.position_ticks,
+        );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/ready")]
+pub async fn syncplay_ready(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Json(req): Json<api::BufferRequestDto>,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    state
+        .ctx
+        .syncplay
+        .ready(
+            &session
+                .device
+                .id,
+            req.playlist_item_id,
+        );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[post("/syncplay/ping")]
+pub async fn syncplay_ping(
+    State(state): State<AppState>,
+    session: auth::AuthSession,
+    Json(req): Json<api::PingRequestDto>,
+) -> Result<impl IntoResponse> {
+    require_access(&session, false)?;
+    state
+        .ctx
+        .syncplay
+        .ping(
+            &session
+                .device
+                .id,
+            req.ping,
+        );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::integration_test::{
+        auth_header_with_token, authenticated_server, new_test_server,
+    };
+    use http::header::HeaderValue;
+    use serde_json::json;
+
+    // --- GET /syncplay/list ---
+
+    #[tokio::test]
+    async fn syncplay_list_requires_auth() {
+        let (server, _ctx) = new_test_server()
+            .await
+            .unwrap();
+        server
+            .get("/syncplay/list")
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn syncplay_list_test() {
+        let (server, _ctx, token) = authenticated_server().await;
+        let auth = auth_header_with_token(&token);
+
+        let resp = server
+            .get("/syncplay/list")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth).unwrap(),
+            )
+            .await;
+
+        resp.assert_status_ok();
+        resp.assert_json(&json!([]));
+    }
+
+    #[tokio::test]
+    async fn a_new_group_is_listed_with_its_creator() {
+        let (server, _ctx, token) = authenticated_server().await;
+        let auth = HeaderValue::from_str(&auth_header_with_token(&token)).unwrap();
+
+        server
+            .post("/syncplay/new")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .json(&json!({ "GroupName": "movie night" }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        let groups: serde_json::Value = server
+            .get("/syncplay/list")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .json();
+        assert_eq!(groups[0]["GroupName"], "movie night");
+        assert_eq!(groups[0]["State"], "Idle");
+        assert_eq!(groups[0]["Participants"], json!(["test"]));
+
+        let group_id = groups[0]["GroupId"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        server
+            .get(&format!("/syncplay/{group_id}"))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await
+            .assert_status_ok();
+
+        server
+            .post("/syncplay/leave")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await;
+        server
+            .get(&format!("/syncplay/{group_id}"))
+            .add_header(http::header::AUTHORIZATION, auth)
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn a_queue_of_unknown_items_is_rejected() {
+        let (server, _ctx, token) = authenticated_server().await;
+        let auth = HeaderValue::from_str(&auth_header_with_token(&token)).unwrap();
+
+        server
+            .post("/syncplay/new")
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .json(&json!({ "GroupName": "movie night" }))
+            .await;
+        server
+            .post("/syncplay/setnewqueue")
+            .add_header(http::header::AUTHORIZATION, auth)
+            .json(&json!({
+                "PlayingQueue": [Uuid::new_v4()],
+                "PlayingItemPosition": 0,
+                "StartPositionTicks": 0,
+            }))
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+    }
+}
@@ -13,16 +13,16 @@ use tracing::info;
 use uuid::Uuid;
 
 use crate::{
-    AppState, IntoApiError, OptionExt, ResultExt, api,
+    AppState, IntoApiError, OptionExt, ResultExt, api, base_url,
     common::{self, get_uuid, server_id},
     db::{self, auth},
     intro,
 };
 use anyhow;
 use axum_anyhow::ApiResult as Result;
-use remux_sdks::remux::IntroOptions;
+use remux_sdks::remux::{DlnaOptions, IntroOptions, LdapOptions, OidcOptions};
 
-fn request_local_address(headers: &HeaderMap, fallback_port: u16) -> String {
+pub(crate) fn request_local_address(headers: &HeaderMap, fallback_port: u16) -> String {
     let scheme = headers
         .get("x-forwarded-proto")
         .and_then(|value| {
@@ -59,10 +59,11 @@ fn request_local_address(headers: &HeaderMap, fallback_port: u16) -> String {
                 .ok()
         });
 
-    authority.map_or_else(
+    let base = authority.map_or_else(
         || format!("http://127.0.0.1:{fallback_port}"),
         |authority| format!("{scheme}://{authority}"),
-    )
+    );
+    format!("{base}{}", base_url::prefix(headers))
 }
 
 #[get("/system/info/public")]
@@ -369,6 +370,142 @@ pub async fn update_intro_configuration(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Get OpenID Connect configuration
+#[get("/system/configuration/oidc")]
+pub async fn get_oidc_configuration(
+    State(state): State<AppState>,
+    _session: auth::AdminSession,
+) -> axum_anyhow::ApiResult<impl IntoResponse> {
+    let opts = db::Settings::get_oidc_config(
+        &state
+            .ctx
+            .db,
+    )
+    .await?;
+    Ok(Json(opts))
+}
+
+/// Update OpenID Connect configuration
+#[post("/system/configuration/oidc")]
+pub async fn update_oidc_configuration(
+    State(state): State<AppState>,
+    _session: auth::AdminSession,
+    Json(opts): Json<OidcOptions>,
+) -> axum_anyhow::ApiResult<impl IntoResponse> {
+    if opts.enabled
+        && (opts
+            .issuer_url
+            .trim()
+            .is_empty()
+            || opts
+                .client_id
+                .trim()
+                .is_empty())
+    {
+        return Err(anyhow::anyhow!("incomplete OpenID Connect configuration")
+            .context_bad_request("an issuer URL and a client ID are required"));
+    }
+    db::Settings::set_oidc_config(
+        &state
+            .ctx
+            .db,
+        &opts,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get LDAP configuration
+#[get("/system/configuration/ldap")]
+pub async fn get_ldap_configuration(
+    State(state): State<AppState>,
+    _session: auth::AdminSession,
+) -> axum_anyhow::ApiResult<impl IntoResponse> {
+    let opts = db::Settings::get_ldap_config(
+        &state
+            .ctx
+            .db,
+    )
+    .await?;
+    Ok(Json(opts))
+}
+
+/// Update LDAP configuration
+#[post("/system/configuration/ldap")]
+pub async fn update_ldap_configuration(
+    State(state): State<AppState>,
+    _session: auth::AdminSession,
+    Json(opts): Json<LdapOptions>,
+) -> axum_anyhow::ApiResult<impl IntoResponse> {
+    if opts.enabled
+        && (opts
+            .server_url
+            .trim()
+            .is_empty()
+            || opts
+                .base_dn
+                .trim()
+                .is_empty()
+            || !opts
+                .user_filter
+                .contains("{username}"))
+    {
+        return Err(anyhow::anyhow!("incomplete LDAP configuration").context_bad_request(
+            "a server URL, a base DN and a user filter containing {username} are required",
+        ));
+    }
+    db::Settings::set_ldap_config(
+        &state
+            .ctx
+            .db,
+        &opts,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Get DLNA configuration
+#[get("/system/configuration/dlna")]
+pub async fn get_dlna_configuration(
+    State(state): State<AppState>,
+    _session: auth::AdminSession,
+) -> axum_anyhow::ApiResult<impl IntoResponse> {
+    let opts = db::Settings::get_dlna_config(
+        &state
+            .ctx
+            .db,
+    )
+    .await?;
+    Ok(Json(opts))
+}
+
+/// Update DLNA configuration
+#[post("/system/configuration/dlna")]
+pub async fn update_dlna_configuration(
+    State(state): State<AppState>,
+    _session: auth::AdminSession,
+    Json(opts): Json<DlnaOptions>,
+) -> axum_anyhow::ApiResult<impl IntoResponse> {
+    if let Some(user_id) = opts.default_user_id {
+        db::User::get_by_id(
+            &state
+                .ctx
+                .db,
+            &user_id,
+        )
+        .await?
+        .context_bad_request("the DLNA user does not exist")?;
+    }
+    db::Settings::set_dlna_config(
+        &state
+            .ctx
+            .db,
+        &opts,
+    )
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 #[get("/system/endpoint")]
 pub async fn system_endpoint(
     State(state): State<AppState>,
@@ -380,14 +517,6 @@ pub async fn system_endpoint(
     })))
 }
 
-#[get("/syncplay/list")]
-pub async fn syncplay_list(
-    State(_state): State<AppState>,
-    _session: auth::AuthSession,
-) -> Result<impl IntoResponse> {
-    Ok(Json(Vec::<serde_json::Value>::new()))
-}
-
 #[route("/quickconnect/enabled", method = "GET", method = "POST")]
 pub async fn quickconnect_enabled(
     State(state): State<AppState>,
@@ -1102,37 +1231,6 @@ mod test {
         }));
     }
 
-    // --- GET /syncplay/list ---
-
-    #[tokio::test]
-    async fn syncplay_list_requires_auth() {
-        let (server, _ctx) = new_test_server()
-            .await
-            .unwrap();
-        server
-            .get("/syncplay/list")
-            .expect_failure()
-            .await
-            .assert_status(StatusCode::UNAUTHORIZED);
-    }
-
-    #[tokio::test]
-    async fn syncplay_list_test() {
-        let (server, _ctx, token) = authenticated_server().await;
-        let auth = auth_header_with_token(&token);
-
-        let resp = server
-            .get("/syncplay/list")
-            .add_header(
-                http::header::AUTHORIZATION,
-                HeaderValue::from_str(&auth).unwrap(),
-            )
-            .await;
-
-        resp.assert_status_ok();
-        resp.assert_json(&json!([]));
-    }
-
     // --- GET+POST /quickconnect/enabled ---
 
     #[tokio::test]
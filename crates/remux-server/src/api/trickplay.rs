@@ -0,0 +1,213 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use axum_anyhow::ApiResult as Result;
+use axum_extra::extract::Query;
+use http::header;
+use remux_macros::{get, query};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{AppState, IntoApiError, OptionExt, db::auth, trickplay};
+
+#[query]
+#[derive(Debug)]
+pub struct TrickplayQuery {
+    pub media_source_id: Option<Uuid>,
+    pub api_key: Option<String>,
+}
+
+/// The query each sheet URL in the playlist carries, so players that cannot
+/// send headers for image requests stay authorized.
+fn sheet_query(source_id: Uuid, api_key: Option<&str>) -> String {
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    query.append_pair(
+        "MediaSourceId",
+        &source_id
+            .simple()
+            .to_string(),
+    );
+    if let Some(key) = api_key {
+        query.append_pair("api_key", key);
+    }
+    query.finish()
+}
+
+/// Serves `tiles.m3u8` (the HLS image playlist) or one `{index}.jpg` tile sheet.
+#[get("/videos/{id}/trickplay/{width}/{file}")]
+pub async fn get_trickplay_file(
+    State(state): State<AppState>,
+    _session: auth::AuthSession,
+    Path((id, width, file)): Path<(Uuid, u32, String)>,
+    Query(q): Query<TrickplayQuery>,
+) -> Result<impl IntoResponse> {
+    let (source_id, info) = trickplay::find(&state.ctx, id, q.media_source_id, width)
+        .await?
+        .context_not_found("trickplay not found")?;
+
+    if file == "tiles.m3u8" {
+        return Ok((
+            [(header::CONTENT_TYPE, "application/x-mpegURL")],
+            trickplay::tiles_playlist(
+                &info,
+                &sheet_query(
+                    source_id,
+                    q.api_key
+                        .as_deref(),
+                ),
+            ),
+        )
+            .into_response());
+    }
+
+    let index = file
+        .strip_suffix(".jpg")
+        .and_then(|n| {
+            n.parse::<u32>()
+                .ok()
+        })
+        .context_not_found("trickplay file not found")?;
+    let path = trickplay::sheet_path(
+        &state
+            .ctx
+            .config
+            .data_dir,
+        source_id,
+        width,
+        index,
+    );
+    let bytes = tokio::fs::read(&path)
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!("tile sheet not found")
+                .context_not_found("trickplay file not found")
+        })?;
+    Ok(([(header::CONTENT_TYPE, "image/jpeg")], bytes).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        Config,
+        integration_test::{
+            AUTH_HEADER, auth_header_with_token, new_test_server_with_config,
+        },
+    };
+    use axum_test::TestServer;
+    use http::{StatusCode, header::HeaderValue};
+    use serde_json::json;
+
+    /// Test server whose data dir is a fresh temp dir, plus an auth header.
+    async fn server_with_data_dir(
+        data_dir: &std::path::Path,
+    ) -> (TestServer, crate::integration_test::TestGuard, HeaderValue) {
+        let (server, guard) = new_test_server_with_config(Config {
+            database_url: Some("sqlite::memory:".into()),
+            torrent_http_port: None,
+            disable_dht: true,
+            data_dir: data_dir.to_path_buf(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let body: serde_json::Value = server
+            .post("/users/authenticatebyname")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_static(AUTH_HEADER),
+            )
+            .json(&json!({ "Username": "test", "Pw": "test" }))
+            .await
+            .json();
+        let auth = auth_header_with_token(
+            body["AccessToken"]
+                .as_str()
+                .unwrap(),
+        );
+        (server, guard, HeaderValue::from_str(&auth).unwrap())
+    }
+
+    async fn write_sheets(data_dir: &std::path::Path, source_id: Uuid) {
+        let dir = trickplay::sheet_dir(data_dir, source_id, 320);
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .unwrap();
+        let info = crate::api::TrickplayInfo {
+            width: 320,
+            height: 180,
+            tile_width: 10,
+            tile_height: 10,
+            thumbnail_count: 12,
+            interval: 10_000,
+            bandwidth: 800,
+        };
+        tokio::fs::write(dir.join("info.json"), serde_json::to_vec(&info).unwrap())
+            .await
+            .unwrap();
+        tokio::fs::write(dir.join("0.jpg"), b"\xff\xd8\xff\xe0jpeg")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn trickplay_playlist_and_sheets_are_served() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let (server, _guard, auth) = server_with_data_dir(data_dir.path()).await;
+        let item_id = Uuid::new_v4();
+        write_sheets(data_dir.path(), item_id).await;
+
+        let playlist = server
+            .get(&format!("/videos/{item_id}/trickplay/320/tiles.m3u8"))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await;
+        playlist.assert_status_ok();
+        let text = playlist.text();
+        assert!(text.contains("#EXT-X-IMAGES-ONLY"));
+        assert!(text.contains(&format!("0.jpg?MediaSourceId={}", item_id.simple())));
+
+        let sheet = server
+            .get(&format!("/videos/{item_id}/trickplay/320/0.jpg"))
+            .add_header(http::header::AUTHORIZATION, auth.clone())
+            .await;
+        sheet.assert_status_ok();
+        assert_eq!(sheet.header(http::header::CONTENT_TYPE), "image/jpeg");
+
+        server
+            .get(&format!("/videos/{item_id}/trickplay/320/1.jpg"))
+            .add_header(http::header::AUTHORIZATION, auth)
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn sheet_urls_encode_the_api_key() {
+        let id = Uuid::new_v4();
+        assert_eq!(
+            sheet_query(id, Some("a+b&c=d")),
+            format!("MediaSourceId={}&api_key=a%2Bb%26c%3Dd", id.simple())
+        );
+        assert_eq!(
+            sheet_query(id, None),
+            format!("MediaSourceId={}", id.simple())
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_trickplay_is_not_found() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let (server, _guard, auth) = server_with_data_dir(data_dir.path()).await;
+
+        server
+            .get(&format!(
+                "/videos/{}/trickplay/320/tiles.m3u8",
+                Uuid::new_v4()
+            ))
+            .add_header(http::header::AUTHORIZATION, auth)
+            .expect_failure()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
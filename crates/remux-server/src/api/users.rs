@@ -23,6 +23,7 @@ use crate::{
     common::{get_uuid, server_id},
     db,
     db::{auth, user::User},
+    network::{ClientAddr, ensure_remote_access},
     services::{self, MediaResolveService},
     ws::WsEvent,
 };
@@ -205,7 +206,47 @@ fn require_self_or_admin(target_id: Uuid, session: &auth::AuthSession) -> Result
     Ok(())
 }
 
-fn build_auth_response(
+/// Refuse sign-in, and requests on an existing session, outside the user's
+/// parental access schedule.
+pub(crate) fn ensure_access_allowed(user: &User) -> Result<()> {
+    if !user.is_within_access_schedule(chrono::Local::now().naive_local()) {
+        return Err(anyhow::anyhow!(
+            "{} signed in outside their access schedule",
+            user.username
+        )
+        .context_unauthorized("User is not allowed access at this time"));
+    }
+    Ok(())
+}
+
+/// Checks a username and password: the local account first, then the LDAP
+/// directory when it is enabled.
+pub(crate) async fn authenticate_password(
+    db: &sqlx::SqlitePool,
+    username: &str,
+    password: &str,
+) -> anyhow::Result<Option<User>> {
+    if let Some(user) = User::authenticate(db, username, password).await? {
+        return Ok(Some(user));
+    }
+
+    let ldap = db::Settings::get_ldap_config(db).await?;
+    if !ldap.enabled {
+        return Ok(None);
+    }
+    // An unreachable or misconfigured directory must not lock out local
+    // accounts, so it counts as a refused sign-in. The directory exchange is
+    // boxed to keep it off the callers' stack.
+    match Box::pin(crate::ldap::authenticate(db, &ldap, username, password)).await {
+        Ok(user) => Ok(user),
+        Err(err) => {
+            tracing::warn!(username, "LDAP sign-in failed: {err:#}");
+            Ok(None)
+        }
+    }
+}
+
+pub(crate) fn build_auth_response(
     data_dir: &std::path::Path,
     device: auth::Device,
     user: db::User,
@@ -274,9 +315,10 @@ fn build_auth_response(
 pub async fn users_authenticatebyname(
     State(state): State<AppState>,
     auth_header: auth::JellyfinAuthHeader,
+    client: ClientAddr,
     Json(data): Json<api::AuthenticateUserByName>,
 ) -> Result<impl IntoResponse> {
-    let user = User::authenticate(
+    let user = authenticate_password(
         &state
             .ctx
             .db,
@@ -289,6 +331,8 @@ pub async fn users_authenticatebyname(
     )
     .await?
     .context_unauthorized("not found")?;
+    ensure_access_allowed(&user)?;
+    ensure_remote_access(&user, &client)?;
     let device = auth::Device::new_from_header(auth_header, &user)?;
     device
         .save(
@@ -312,6 +356,7 @@ pub async fn users_authenticatebyname(
 pub async fn authenticate_with_quickconnect(
     State(state): State<AppState>,
     auth_header: auth::JellyfinAuthHeader,
+    client: ClientAddr,
     Json(body): Json<api::AuthenticateWithQuickConnect>,
 ) -> Result<impl IntoResponse> {
     let entry = state
@@ -337,6 +382,8 @@ pub async fn authenticate_with_quickconnect(
     )
     .await?
     .context_unauthorized("User not found")?;
+    ensure_access_allowed(&user)?;
+    ensure_remote_access(&user, &client)?;
 
     let device = auth::Device {
         id: auth_header
@@ -886,6 +933,7 @@ pub async fn change_password(
             .db,
     )
     .await?;
+    super::subsonic::forget_verified_passwords();
 
     db::auth::Device::delete_all_for_user(
         &state
@@ -1110,6 +1158,25 @@ pub async fn userviews(
     session: auth::AuthSession,
     Query(q): Query<UserViewsQuery>,
 ) -> Result<impl IntoResponse> {
+    let items = user_views(&state, &session, q.include_hidden == Some(true)).await?;
+    let count = items.len() as i64;
+    let result = ItemsQueryResultBuilder::with_dtos(session, items, count)
+        .with_client_patches()
+        .build();
+    Ok(Json(api::BaseItemDtoQueryResult {
+        items: result.items,
+        total_record_count: result.total_count,
+        ..Default::default()
+    }))
+}
+
+/// The libraries `session`'s user can see, in their configured order, plus
+/// the Live TV view when channels exist.
+pub(crate) async fn user_views(
+    state: &AppState,
+    session: &auth::AuthSession,
+    include_hidden: bool,
+) -> Result<Vec<api::BaseItemDto>> {
     let library_filter = db::MediaFilter {
         kind: Some(vec![db::MediaKind::Collection, db::MediaKind::Folder]),
         promoted: Some(true),
@@ -1175,7 +1242,7 @@ pub async fn userviews(
     }
 
     // Exclude hidden views unless the caller explicitly requests them.
-    if q.include_hidden != Some(true) {
+    if !include_hidden {
         if let Some(cfg) = config {
             if !cfg
                 .my_media_excludes
@@ -1224,16 +1291,7 @@ pub async fn userviews(
     {
         items.push(livetv_view_item());
     }
-
-    let count = items.len() as i64;
-    let result = ItemsQueryResultBuilder::with_dtos(session, items, count)
-        .with_client_patches()
-        .build();
-    Ok(Json(api::BaseItemDtoQueryResult {
-        items: result.items,
-        total_record_count: result.total_count,
-        ..Default::default()
-    }))
+    Ok(items)
 }
 
 #[get("/userviews/groupingoptions")]
@@ -1615,10 +1673,38 @@ pub async fn delete_user_image_indexed(
 
 #[get("/auth/providers")]
 pub async fn get_auth_providers(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     _session: auth::AuthSession,
 ) -> Result<impl IntoResponse> {
-    Ok(Json(Vec::<serde_json::Value>::new()))
+    let mut providers = vec![serde_json::json!({
+        "Name": "Default",
+        "Id": "Jellyfin.Server.Implementations.Users.DefaultAuthenticationProvider",
+    })];
+    let oidc = db::Settings::get_oidc_config(
+        &state
+            .ctx
+            .db,
+    )
+    .await?;
+    if oidc.enabled {
+        providers.push(serde_json::json!({
+            "Name": "OpenID Connect",
+            "Id": crate::oidc::PROVIDER_ID,
+        }));
+    }
+    let ldap = db::Settings::get_ldap_config(
+        &state
+            .ctx
+            .db,
+    )
+    .await?;
+    if ldap.enabled {
+        providers.push(serde_json::json!({
+            "Name": "LDAP",
+            "Id": crate::ldap::PROVIDER_ID,
+        }));
+    }
+    Ok(Json(providers))
 }
 
 #[get("/auth/passwordresetproviders")]
@@ -3797,4 +3883,350 @@ mod e2e_tests {
             "OrderedViews ordering not respected"
         );
     }
+
+    /// A user with access schedules can only sign in inside one of them, and a
+    /// session signed in earlier stops working outside them.
+    #[tokio::test]
+    async fn authenticate_outside_access_schedule_is_refused() {
+        use chrono::Datelike;
+
+        let (server, _ctx, admin_token) = authenticated_server().await;
+        let tomorrow = chrono::Local::now()
+            .weekday()
+            .succ();
+        let day = [
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+            "Sunday",
+        ][tomorrow.num_days_from_monday() as usize];
+
+        let user_id = crate::integration_test::create_user_with_policy(
+            &server,
+            &admin_token,
+            "scheduled",
+            "pass1234",
+            json!({
+                "AccessSchedules": [{ "DayOfWeek": day, "StartHour": 0, "EndHour": 24 }]
+            }),
+        )
+        .await;
+
+        server
+            .post("/users/authenticatebyname")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_static(AUTH_HEADER),
+            )
+            .json(&json!({ "Username": "scheduled", "Pw": "pass1234" }))
+            .expect_failure()
+            .await
+            .assert_status_unauthorized();
+
+        server
+            .post(&format!("/users/{user_id}/policy"))
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&admin_token)).unwrap(),
+            )
+            .json(&json!({
+                "AccessSchedules": [{ "DayOfWeek": "Everyday", "StartHour": 0, "EndHour": 24 }]
+            }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        let signed_in = server
+            .post("/users/authenticatebyname")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_static(AUTH_HEADER),
+            )
+            .json(&json!({ "Username": "scheduled", "Pw": "pass1234" }))
+            .await;
+        signed_in.assert_status_ok();
+        let token = signed_in.json::<serde_json::Value>()["AccessToken"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        server
+            .post(&format!("/users/{user_id}/policy"))
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&admin_token)).unwrap(),
+            )
+            .json(&json!({
+                "AccessSchedules": [{ "DayOfWeek": day, "StartHour": 0, "EndHour": 24 }]
+            }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        server
+            .get("/users/me")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&token)).unwrap(),
+            )
+            .expect_failure()
+            .await
+            .assert_status_unauthorized();
+    }
+
+    const LDAP_SERVICE_DN: &str = "cn=remux,dc=example,dc=com";
+    const LDAP_ADMINS: &str = "cn=admins,ou=groups,dc=example,dc=com";
+
+    async fn ldap_directory() -> crate::integration_test::LdapStandIn {
+        use crate::integration_test::{LdapEntry, LdapStandIn};
+        LdapStandIn::start(vec![
+            LdapEntry::new(LDAP_SERVICE_DN, "service", &[]),
+            LdapEntry::new(
+                "uid=alice,ou=people,dc=example,dc=com",
+                "wonderland",
+                &[("uid", &["alice"]), ("memberOf", &[LDAP_ADMINS])],
+            ),
+            LdapEntry::new(
+                "uid=bob,ou=people,dc=example,dc=com",
+                "builder",
+                &[("uid", &["bob"])],
+            ),
+        ])
+        .await
+    }
+
+    async fn configure_ldap(
+        server: &axum_test::TestServer,
+        admin_token: &str,
+        url: &str,
+        extra: serde_json::Value,
+    ) {
+        let mut config = json!({
+            "Enabled": true,
+            "ServerUrl": url,
+            "BindDn": LDAP_SERVICE_DN,
+            "BindPassword": "service",
+            "BaseDn": "ou=people,dc=example,dc=com",
+            "AdminGroup": LDAP_ADMINS,
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(
+                extra
+                    .as_object()
+                    .cloned()
+                    .unwrap_or_default(),
+            );
+        server
+            .post("/system/configuration/ldap")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(admin_token)).unwrap(),
+            )
+            .json(&config)
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+    }
+
+    fn sign_in(
+        server: &axum_test::TestServer,
+        username: &str,
+        password: &str,
+    ) -> axum_test::TestRequest {
+        server
+            .post("/users/authenticatebyname")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_static(AUTH_HEADER),
+            )
+            .json(&json!({ "Username": username, "Pw": password }))
+    }
+
+    #[tokio::test]
+    async fn ldap_sign_in_provisions_the_user_with_the_admin_group() {
+        let directory = ldap_directory().await;
+        let (server, _ctx, admin_token) = authenticated_server().await;
+        configure_ldap(&server, &admin_token, &directory.url, json!({})).await;
+
+        sign_in(&server, "alice", "wrong")
+            .expect_failure()
+            .await
+            .assert_status_unauthorized();
+        sign_in(&server, "alice", "")
+            .expect_failure()
+            .await
+            .assert_status_unauthorized();
+
+        let resp = sign_in(&server, "alice", "wonderland").await;
+        let body: serde_json::Value = resp.json();
+        assert_eq!(body["User"]["Name"], "alice");
+        assert_eq!(body["User"]["Policy"]["IsAdministrator"], true);
+        assert_eq!(
+            body["User"]["Policy"]["AuthenticationProviderId"],
+            crate::ldap::PROVIDER_ID
+        );
+
+        let resp = sign_in(&server, "bob", "builder").await;
+        let body: serde_json::Value = resp.json();
+        assert_eq!(body["User"]["Policy"]["IsAdministrator"], false);
+
+        // The second sign-in finds the provisioned account instead of making another.
+        sign_in(&server, "bob", "builder")
+            .await
+            .assert_status_ok();
+        let users: serde_json::Value = server
+            .get("/users")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&admin_token)).unwrap(),
+            )
+            .await
+            .json();
+        let bobs = users
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|u| u["Name"] == "bob")
+            .count();
+        assert_eq!(bobs, 1);
+
+        let providers: serde_json::Value = server
+            .get("/auth/providers")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&admin_token)).unwrap(),
+            )
+            .await
+            .json();
+        assert!(
+            providers
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|p| p["Id"] == crate::ldap::PROVIDER_ID)
+        );
+    }
+
+    #[tokio::test]
+    async fn ldap_without_provisioning_signs_in_accounts_assigned_to_it_only() {
+        let directory = ldap_directory().await;
+        let (server, _ctx, admin_token) = authenticated_server().await;
+        configure_ldap(
+            &server,
+            &admin_token,
+            &directory.url,
+            json!({ "AutoProvision": false, "AdminGroup": null }),
+        )
+        .await;
+
+        sign_in(&server, "bob", "builder")
+            .expect_failure()
+            .await
+            .assert_status_unauthorized();
+
+        let bob_id = crate::integration_test::create_user_with_policy(
+            &server,
+            &admin_token,
+            "bob",
+            "local-pass",
+            json!({}),
+        )
+        .await;
+
+        // A local account with the same name is not the directory's to sign into.
+        sign_in(&server, "bob", "builder")
+            .expect_failure()
+            .await
+            .assert_status_unauthorized();
+
+        server
+            .post(&format!("/users/{bob_id}/policy"))
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&admin_token)).unwrap(),
+            )
+            .json(&json!({ "AuthenticationProviderId": crate::ldap::PROVIDER_ID }))
+            .await
+            .assert_status(StatusCode::NO_CONTENT);
+
+        // Once an admin assigns it, both the directory and the local password work.
+        sign_in(&server, "bob", "builder")
+            .await
+            .assert_status_ok();
+        sign_in(&server, "bob", "local-pass")
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn ldap_sign_in_leaves_a_local_admin_with_the_same_name_alone() {
+        let directory = ldap_directory().await;
+        let (server, ctx, admin_token) = authenticated_server().await;
+        configure_ldap(&server, &admin_token, &directory.url, json!({})).await;
+
+        crate::integration_test::create_user_with_policy(
+            &server,
+            &admin_token,
+            "bob",
+            "local-pass",
+            json!({ "IsAdministrator": true }),
+        )
+        .await;
+
+        // bob is outside the directory's admin group, which must not demote
+        // or hand over the local account.
+        sign_in(&server, "bob", "builder")
+            .expect_failure()
+            .await
+            .assert_status_unauthorized();
+        let bob = User::get_by_username(
+            &ctx.0
+                .db,
+            "bob",
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(bob.is_admin);
+        assert_ne!(
+            bob.authentication_provider(),
+            Some(crate::ldap::PROVIDER_ID)
+        );
+    }
+
+    #[tokio::test]
+    async fn unreachable_ldap_leaves_local_sign_in_working() {
+        let (server, _ctx, admin_token) = authenticated_server().await;
+        configure_ldap(&server, &admin_token, "ldap://127.0.0.1:1", json!({})).await;
+
+        sign_in(&server, "alice", "wonderland")
+            .expect_failure()
+            .await
+            .assert_status_unauthorized();
+        sign_in(&server, "test", "test")
+            .await
+            .assert_status_ok();
+    }
+
+    #[tokio::test]
+    async fn ldap_configuration_needs_a_username_placeholder() {
+        let (server, _ctx, admin_token) = authenticated_server().await;
+        server
+            .post("/system/configuration/ldap")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&admin_token)).unwrap(),
+            )
+            .json(&json!({
+                "Enabled": true,
+                "ServerUrl": "ldap://localhost",
+                "BaseDn": "dc=example,dc=com",
+                "UserFilter": "(uid=alice)",
+            }))
+            .expect_failure()
+            .await
+            .assert_status_bad_request();
+    }
 }
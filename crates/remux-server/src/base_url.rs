@@ -0,0 +1,190 @@
+//! Hosting under a sub-path, e.g. `https://example.com/remux/`.
+//!
+//! With `BaseUrl` set in the network configuration, the prefix is stripped
+//! from incoming paths before routing, so handlers only ever see root paths.
+//! Requests without it are still served, for clients on the LAN that reach
+//! the port directly. The prefix travels on in `X-Forwarded-Prefix`, the
+//! header proxies that strip a prefix themselves already set, and is put back
+//! on the absolute paths the server hands out: redirects, `LocalAddress`,
+//! the scripts injected into the web client and the dashboard shell.
+//! Changing `BaseUrl` takes a restart.
+
+use axum::{Router, extract::Request};
+use http::{HeaderMap, HeaderValue};
+use tower::{Layer, util::MapRequestLayer};
+
+use crate::rewrite_request_uri;
+
+pub const PREFIX_HEADER: &str = "x-forwarded-prefix";
+
+/// `/remux`, from any of `remux`, `/remux/` or `/remux`. Empty for the root.
+pub fn normalize(base_url: &str) -> String {
+    let trimmed = base_url
+        .trim()
+        .trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{trimmed}")
+    }
+}
+
+/// `path` below `base`, if it is under it. Proxies and clients disagree on
+/// case, so the comparison ignores it.
+fn strip<'a>(path: &'a str, base: &str) -> Option<&'a str> {
+    let head = path.get(..base.len())?;
+    let rest = &path[base.len()..];
+    if !head.eq_ignore_ascii_case(base) {
+        return None;
+    }
+    match rest {
+        "" => Some("/"),
+        rest if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
+}
+
+/// Serve `router` under `base_url` as well as at the root.
+pub fn nest(router: Router, base_url: &str) -> Router {
+    let base = normalize(base_url);
+    if base.is_empty() {
+        return router;
+    }
+    let value = HeaderValue::from_str(&base).ok();
+    let strip_base = move |mut req: Request| {
+        // Only this server decides the prefix once one is configured.
+        req.headers_mut()
+            .remove(PREFIX_HEADER);
+        let stripped = strip(
+            req.uri()
+                .path(),
+            &base,
+        )
+        .map(|path| {
+            let query = req
+                .uri()
+                .query()
+                .map(|q| format!("?{q}"))
+                .unwrap_or_default();
+            format!("{path}{query}")
+        });
+        if let (Some(path_and_query), Some(value)) = (stripped, value.clone()) {
+            if let Ok(uri) = path_and_query.parse() {
+                *req.uri_mut() = uri;
+                req.headers_mut()
+                    .insert(PREFIX_HEADER, value);
+            }
+        }
+        // The outer rewrite ran on the prefixed path; route keywords behind
+        // the prefix still need normalizing.
+        rewrite_request_uri(req)
+    };
+    Router::new().fallback_service(MapRequestLayer::new(strip_base).layer(router))
+}
+
+/// The sub-path the request came in under, or `""`. The header may come from
+/// a proxy we do not configure and ends up in HTML and redirects, so anything
+/// beyond plain path characters is ignored.
+pub fn prefix(headers: &HeaderMap) -> String {
+    headers
+        .get(PREFIX_HEADER)
+        .and_then(|value| {
+            value
+                .to_str()
+                .ok()
+        })
+        .filter(|value| {
+            value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "/-._~".contains(c))
+        })
+        .map(normalize)
+        .unwrap_or_default()
+}
+
+/// `path` as the client has to request it.
+pub fn prefixed(headers: &HeaderMap, path: &str) -> String {
+    format!("{}{path}", prefix(headers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_test::authenticated_server_under;
+    use serde_json::Value;
+
+    #[test]
+    fn base_urls_are_normalized() {
+        assert_eq!(normalize(""), "");
+        assert_eq!(normalize("/"), "");
+        assert_eq!(normalize("remux"), "/remux");
+        assert_eq!(normalize(" /remux/ "), "/remux");
+        assert_eq!(normalize("/apps/remux"), "/apps/remux");
+    }
+
+    #[test]
+    fn only_whole_segments_are_stripped() {
+        assert_eq!(strip("/remux", "/remux"), Some("/"));
+        assert_eq!(strip("/remux/web/", "/remux"), Some("/web/"));
+        assert_eq!(strip("/Remux/Items", "/remux"), Some("/Items"));
+        assert_eq!(strip("/remuxed/items", "/remux"), None);
+        assert_eq!(strip("/items", "/remux"), None);
+    }
+
+    #[test]
+    fn forwarded_prefixes_must_be_plain_paths() {
+        let mut headers = HeaderMap::new();
+        headers.insert(PREFIX_HEADER, HeaderValue::from_static("/remux/"));
+        assert_eq!(prefix(&headers), "/remux");
+        headers.insert(
+            PREFIX_HEADER,
+            HeaderValue::from_static("/x\"</script><script>alert(1)"),
+        );
+        assert_eq!(prefix(&headers), "");
+    }
+
+    #[tokio::test]
+    async fn api_routes_are_served_under_the_base_url() {
+        let (server, _guard, _token) = authenticated_server_under("/remux").await;
+
+        let info: Value = server
+            .get("/remux/System/Info/Public")
+            .await
+            .json();
+        let local_address = info["LocalAddress"]
+            .as_str()
+            .unwrap();
+        assert!(
+            local_address.ends_with("/remux"),
+            "LocalAddress should carry the base URL: {local_address}"
+        );
+
+        // Direct requests without the prefix keep working, without it.
+        let info: Value = server
+            .get("/system/info/public")
+            .await
+            .json();
+        assert!(
+            !info["LocalAddress"]
+                .as_str()
+                .unwrap()
+                .contains("/remux")
+        );
+    }
+
+    #[tokio::test]
+    async fn clients_cannot_claim_another_prefix() {
+        let (server, _guard, _token) = authenticated_server_under("/remux").await;
+        let info: Value = server
+            .get("/system/info/public")
+            .add_header(PREFIX_HEADER, HeaderValue::from_static("/elsewhere"))
+            .await
+            .json();
+        assert!(
+            !info["LocalAddress"]
+                .as_str()
+                .unwrap()
+                .contains("/elsewhere")
+        );
+    }
+}
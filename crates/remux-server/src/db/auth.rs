@@ -1,7 +1,10 @@
 use axum::response::Html;
 use reqwest;
 
-use crate::{IntoApiError, OptionExt, ResultExt};
+use crate::{
+    IntoApiError, OptionExt, ResultExt,
+    network::{ClientAddr, ensure_remote_access},
+};
 use anyhow::{Context, Result, anyhow};
 use async_trait::async_trait;
 use axum::{
@@ -405,34 +408,14 @@ impl FromRequestParts<AppState> for AuthSession {
             .device_id
             .as_deref();
 
-        // Capture client IP from proxy headers or peer address.
-        let remote_ip = parts
-            .headers
-            .get("X-Forwarded-For")
-            .and_then(|v| {
-                v.to_str()
-                    .ok()
-            })
-            .and_then(|v| {
-                v.split(',')
-                    .next()
-            })
-            .map(|s| {
-                s.trim()
-                    .to_string()
-            })
-            .or_else(|| {
-                parts
-                    .headers
-                    .get("X-Real-IP")
-                    .and_then(|v| {
-                        v.to_str()
-                            .ok()
-                    })
-                    .map(|s| s.to_string())
-            });
+        // Forwarded headers only count when they come from a known proxy.
+        let Ok(client) = ClientAddr::from_request_parts(parts, state).await;
+        let remote_ip = client
+            .ip
+            .map(|ip| ip.to_string());
 
-        // First try the devices table (normal session token).
+        // First try the devices table (normal session token). Stremio addon
+        // tokens end up in shared manifest URLs, so they only open `/stremio`.
         if let Some(mut device) = Device::get_by_access_token(
             &state
                 .ctx
@@ -441,6 +424,10 @@ impl FromRequestParts<AppState> for AuthSession {
         )
         .await?
         {
+            if crate::api::stremio::is_addon_device(&device) {
+                return Err(anyhow!("addon token used outside /stremio")
+                    .context_unauthorized("forbidden"));
+            }
             device.merge_runtime_metadata_from_header(&jfauth);
             let _ = device
                 .touch(
@@ -463,11 +450,13 @@ impl FromRequestParts<AppState> for AuthSession {
                 user.username
                     .as_str(),
             );
+            ensure_remote_access(&user, &client)?;
+            crate::api::users::ensure_access_allowed(&user)?;
             return Ok(AuthSession { device, user });
         }
 
         // Fall back to the api_keys table. API keys are admin-scoped tokens.
-        let api_key = db::ApiKey::get_by_token(
+        let session = AuthSession::from_api_key(
             &state
                 .ctx
                 .db,
@@ -476,16 +465,37 @@ impl FromRequestParts<AppState> for AuthSession {
         .await?
         .context_unauthorized("forbidden")?;
 
-        let user = sqlx::query_as::<_, db::User>(
+        tracing::Span::current().record(
+            "user",
+            session
+                .user
+                .username
+                .as_str(),
+        );
+        ensure_remote_access(&session.user, &client)?;
+        crate::api::users::ensure_access_allowed(&session.user)?;
+        Ok(session)
+    }
+}
+
+impl AuthSession {
+    /// Resolve an api_keys token to the admin user and a synthetic device,
+    /// or `None` when `token` is not an API key.
+    pub(crate) async fn from_api_key(
+        db: &SqlitePool,
+        token: &str,
+    ) -> Result<Option<Self>> {
+        let Some(api_key) = db::ApiKey::get_by_token(db, token).await? else {
+            return Ok(None);
+        };
+        let Some(user) = sqlx::query_as::<_, db::User>(
             "SELECT * FROM users WHERE is_admin = 1 LIMIT 1",
         )
-        .fetch_optional(
-            &state
-                .ctx
-                .db,
-        )
+        .fetch_optional(db)
         .await?
-        .context_unauthorized("forbidden")?;
+        else {
+            return Ok(None);
+        };
 
         let synthetic_device = Device {
             id: format!(
@@ -506,16 +516,10 @@ impl FromRequestParts<AppState> for AuthSession {
             remote_ip: None,
             created_at: None,
         };
-
-        tracing::Span::current().record(
-            "user",
-            user.username
-                .as_str(),
-        );
-        Ok(AuthSession {
+        Ok(Some(AuthSession {
             device: synthetic_device,
             user,
-        })
+        }))
     }
 }
 
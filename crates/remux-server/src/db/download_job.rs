@@ -0,0 +1,394 @@
+use anyhow::Result;
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[derive(
+    strum_macros::EnumString,
+    strum_macros::Display,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum DownloadStatus {
+    #[default]
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// What a download is transcoded to. Codecs use the same names as the
+/// streaming endpoints' `VideoCodec` / `AudioCodec` parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DownloadProfile {
+    /// `mp4`, `mkv` or `webm`. Music ignores it: the audio codec picks the
+    /// container.
+    pub container: String,
+    /// `h264`, or `copy` to keep the source video.
+    pub video_codec: String,
+    pub audio_codec: String,
+    pub max_height: Option<u32>,
+    pub video_bitrate: Option<u32>,
+    pub audio_bitrate: Option<u32>,
+    pub audio_channels: Option<u32>,
+}
+
+impl Default for DownloadProfile {
+    fn default() -> Self {
+        Self {
+            container: "mp4".into(),
+            video_codec: "h264".into(),
+            audio_codec: "aac".into(),
+            max_height: None,
+            video_bitrate: None,
+            audio_bitrate: None,
+            audio_channels: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DownloadJob {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub media_id: Uuid,
+    #[sqlx(json)]
+    pub profile: DownloadProfile,
+    pub status: DownloadStatus,
+    pub progress: f64,
+    pub file_name: String,
+    pub size: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub completed_at: Option<NaiveDateTime>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+const COLS: &str = "id, user_id, media_id, profile, status, progress, file_name, \
+     size, error, created_at, completed_at, expires_at";
+
+impl DownloadJob {
+    pub fn new(
+        user_id: Uuid,
+        media_id: Uuid,
+        profile: DownloadProfile,
+        file_name: String,
+    ) -> Self {
+        Self {
+            id: crate::common::get_uuid(),
+            user_id,
+            media_id,
+            profile,
+            status: DownloadStatus::Queued,
+            progress: 0.0,
+            file_name,
+            size: None,
+            error: None,
+            created_at: Utc::now().naive_utc(),
+            completed_at: None,
+            expires_at: None,
+        }
+    }
+
+    pub async fn insert(&self, db: &SqlitePool) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO download_jobs \
+             (id, user_id, media_id, profile, status, progress, file_name, \
+              size, error, created_at, completed_at, expires_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        )
+        .bind(self.id)
+        .bind(self.user_id)
+        .bind(self.media_id)
+        .bind(sqlx::types::Json(&self.profile))
+        .bind(self.status)
+        .bind(self.progress)
+        .bind(&self.file_name)
+        .bind(self.size)
+        .bind(&self.error)
+        .bind(self.created_at)
+        .bind(self.completed_at)
+        .bind(self.expires_at)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get(db: &SqlitePool, id: Uuid) -> Result<Option<Self>> {
+        Ok(sqlx::query_as::<_, Self>(&format!(
+            "SELECT {COLS} FROM download_jobs WHERE id = ?1"
+        ))
+        .bind(id)
+        .fetch_optional(db)
+        .await?)
+    }
+
+    pub async fn list_for_user(db: &SqlitePool, user_id: Uuid) -> Result<Vec<Self>> {
+        Ok(sqlx::query_as::<_, Self>(&format!(
+            "SELECT {COLS} FROM download_jobs WHERE user_id = ?1 \
+             ORDER BY created_at DESC"
+        ))
+        .bind(user_id)
+        .fetch_all(db)
+        .await?)
+    }
+
+    /// The newest finished download of `media_id` for `user_id`, which is what
+    /// the item download endpoint hands out in place of the original file.
+    pub async fn latest_completed(
+        db: &SqlitePool,
+        user_id: Uuid,
+        media_id: Uuid,
+    ) -> Result<Option<Self>> {
+        Ok(sqlx::query_as::<_, Self>(&format!(
+            "SELECT {COLS} FROM download_jobs \
+             WHERE user_id = ?1 AND media_id = ?2 AND status = 'completed' \
+             ORDER BY completed_at DESC LIMIT 1"
+        ))
+        .bind(user_id)
+        .bind(media_id)
+        .fetch_optional(db)
+        .await?)
+    }
+
+    /// The worker's claim query: the oldest queued job.
+    pub async fn next_queued(db: &SqlitePool) -> Result<Option<Self>> {
+        Ok(sqlx::query_as::<_, Self>(&format!(
+            "SELECT {COLS} FROM download_jobs WHERE status = 'queued' \
+             ORDER BY created_at ASC LIMIT 1"
+        ))
+        .fetch_optional(db)
+        .await?)
+    }
+
+    pub async fn count_queued(db: &SqlitePool) -> Result<i64> {
+        Ok(sqlx::query_scalar(
+            "SELECT COUNT(*) FROM download_jobs WHERE status = 'queued'",
+        )
+        .fetch_one(db)
+        .await?)
+    }
+
+    /// Put jobs cut short by a restart or a stopped task back in the queue.
+    pub async fn requeue_interrupted(db: &SqlitePool) -> Result<u64> {
+        let res = sqlx::query(
+            "UPDATE download_jobs SET status = 'queued', progress = 0 \
+             WHERE status = 'running'",
+        )
+        .execute(db)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    pub async fn mark_running(db: &SqlitePool, id: Uuid) -> Result<()> {
+        sqlx::query(
+            "UPDATE download_jobs SET status = 'running', progress = 0, error = NULL \
+             WHERE id = ?1",
+        )
+        .bind(id)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_progress(db: &SqlitePool, id: Uuid, progress: f64) -> Result<()> {
+        sqlx::query("UPDATE download_jobs SET progress = ?2 WHERE id = ?1")
+            .bind(id)
+            .bind(progress)
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns whether the job still exists; one deleted mid-transcode leaves
+    /// its file for the caller to remove.
+    pub async fn mark_completed(
+        db: &SqlitePool,
+        id: Uuid,
+        size: i64,
+        expires_at: NaiveDateTime,
+    ) -> Result<bool> {
+        let res = sqlx::query(
+            "UPDATE download_jobs \
+             SET status = 'completed', progress = 100, size = ?2, \
+                 completed_at = ?3, expires_at = ?4 \
+             WHERE id = ?1",
+        )
+        .bind(id)
+        .bind(size)
+        .bind(Utc::now().naive_utc())
+        .bind(expires_at)
+        .execute(db)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    pub async fn mark_failed(db: &SqlitePool, id: Uuid, error: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE download_jobs SET status = 'failed', error = ?2 WHERE id = ?1",
+        )
+        .bind(id)
+        .bind(error)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn expired(db: &SqlitePool, now: NaiveDateTime) -> Result<Vec<Self>> {
+        Ok(sqlx::query_as::<_, Self>(&format!(
+            "SELECT {COLS} FROM download_jobs \
+             WHERE expires_at IS NOT NULL AND expires_at <= ?1"
+        ))
+        .bind(now)
+        .fetch_all(db)
+        .await?)
+    }
+
+    /// Every job id, for telling orphaned files apart from live ones.
+    pub async fn all_ids(db: &SqlitePool) -> Result<HashSet<Uuid>> {
+        Ok(sqlx::query_scalar("SELECT id FROM download_jobs")
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .collect())
+    }
+
+    pub async fn delete(db: &SqlitePool, id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM download_jobs WHERE id = ?1")
+            .bind(id)
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{Media, MediaKind},
+        integration_test::new_test_server,
+    };
+
+    async fn seed(db: &SqlitePool) -> (Uuid, Uuid) {
+        let mut user = crate::db::User::new_with_password(
+            String::new(),
+            "alice".into(),
+            "pw",
+            None,
+        )
+        .unwrap();
+        user.save(db)
+            .await
+            .unwrap();
+        let external_ids = crate::db::ExternalIds {
+            imdb: crate::db::NonEmptyString::try_new("tt0113277".to_string()).ok(),
+            ..Default::default()
+        };
+        let mut media = Media {
+            id: crate::integration_test::stable_id(MediaKind::Movie, &external_ids),
+            title: "Movie".into(),
+            kind: MediaKind::Movie,
+            external_ids,
+            ..Default::default()
+        };
+        media
+            .save(db)
+            .await
+            .unwrap();
+        (user.id, media.id)
+    }
+
+    #[tokio::test]
+    async fn a_job_moves_through_the_queue() {
+        let (_s, guard) = new_test_server()
+            .await
+            .unwrap();
+        let db = &guard
+            .0
+            .db;
+        let (user_id, media_id) = seed(db).await;
+        let job = DownloadJob::new(
+            user_id,
+            media_id,
+            DownloadProfile::default(),
+            "Movie.mp4".into(),
+        );
+        job.insert(db)
+            .await
+            .unwrap();
+
+        let next = DownloadJob::next_queued(db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.id, job.id);
+        assert_eq!(next.profile, DownloadProfile::default());
+
+        // A restart mid-transcode hands the job back to the queue.
+        DownloadJob::mark_running(db, job.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            DownloadJob::requeue_interrupted(db)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            DownloadJob::count_queued(db)
+                .await
+                .unwrap(),
+            1
+        );
+
+        let expires_at = Utc::now().naive_utc() + chrono::Duration::days(1);
+        assert!(
+            DownloadJob::mark_completed(db, job.id, 42, expires_at)
+                .await
+                .unwrap()
+        );
+        let done = DownloadJob::latest_completed(db, user_id, media_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(done.status, DownloadStatus::Completed);
+        assert_eq!(done.size, Some(42));
+        assert!(
+            DownloadJob::expired(db, Utc::now().naive_utc())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            DownloadJob::expired(db, expires_at)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Deleting it mid-transcode is reported back to the worker.
+        DownloadJob::delete(db, job.id)
+            .await
+            .unwrap();
+        assert!(
+            !DownloadJob::mark_completed(db, job.id, 42, expires_at)
+                .await
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,466 @@
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{api, common::get_uuid, db::Media};
+
+#[derive(
+    strum_macros::EnumString,
+    strum_macros::Display,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum TimerStatus {
+    #[default]
+    New,
+    InProgress,
+    Completed,
+    Cancelled,
+    Error,
+}
+
+impl From<TimerStatus> for api::RecordingStatus {
+    fn from(status: TimerStatus) -> Self {
+        match status {
+            TimerStatus::New => Self::New,
+            TimerStatus::InProgress => Self::InProgress,
+            TimerStatus::Completed => Self::Completed,
+            TimerStatus::Cancelled => Self::Cancelled,
+            TimerStatus::Error => Self::Error,
+        }
+    }
+}
+
+/// One scheduled or finished recording of one airing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Timer {
+    pub id: Uuid,
+    pub series_timer_id: Option<Uuid>,
+    pub channel_id: Uuid,
+    pub program_id: Option<Uuid>,
+    pub name: String,
+    pub overview: Option<String>,
+    pub start_date: NaiveDateTime,
+    pub end_date: NaiveDateTime,
+    pub pre_padding_seconds: i64,
+    pub post_padding_seconds: i64,
+    pub status: TimerStatus,
+    pub recording_id: Option<Uuid>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Default)]
+pub struct TimerFilter {
+    pub channel_id: Option<Uuid>,
+    pub series_timer_id: Option<Uuid>,
+    /// Only timers that are scheduled or recording.
+    pub active: bool,
+}
+
+impl Timer {
+    /// A timer for an EPG program, or `None` when the program has no channel
+    /// or airing window.
+    pub fn from_program(
+        program: &Media,
+        pre_padding: i64,
+        post_padding: i64,
+    ) -> Option<Self> {
+        let now = Utc::now().naive_utc();
+        Some(Self {
+            id: get_uuid(),
+            series_timer_id: None,
+            channel_id: program.parent_id?,
+            program_id: Some(program.id),
+            name: program
+                .title
+                .clone(),
+            overview: program
+                .description
+                .clone(),
+            start_date: program.live_start?,
+            end_date: program.live_end?,
+            pre_padding_seconds: pre_padding.max(0),
+            post_padding_seconds: post_padding.max(0),
+            status: TimerStatus::New,
+            recording_id: None,
+            last_error: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// When recording starts: the airing minus its pre-padding.
+    pub fn record_start(&self) -> NaiveDateTime {
+        self.start_date - Duration::seconds(self.pre_padding_seconds)
+    }
+
+    /// When recording stops: the airing plus its post-padding.
+    pub fn record_end(&self) -> NaiveDateTime {
+        self.end_date + Duration::seconds(self.post_padding_seconds)
+    }
+
+    /// Schedules the timer. A program that already has one keeps it; a
+    /// cancelled or failed one is put back on the schedule with the new
+    /// padding. Returns the stored row.
+    pub async fn schedule(&self, db: &SqlitePool) -> Result<Self> {
+        sqlx::query(
+            r#"
+            INSERT INTO timers (id, series_timer_id, channel_id, program_id, name, overview,
+                                start_date, end_date, pre_padding_seconds, post_padding_seconds,
+                                status, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, 'new', $11, $11)
+            ON CONFLICT (program_id) DO UPDATE SET
+                status               = 'new',
+                pre_padding_seconds  = excluded.pre_padding_seconds,
+                post_padding_seconds = excluded.post_padding_seconds,
+                last_error           = NULL,
+                updated_at           = excluded.updated_at
+            WHERE timers.status IN ('cancelled', 'error') AND timers.recording_id IS NULL
+            "#,
+        )
+        .bind(self.id)
+        .bind(self.series_timer_id)
+        .bind(self.channel_id)
+        .bind(self.program_id)
+        .bind(&self.name)
+        .bind(&self.overview)
+        .bind(self.start_date)
+        .bind(self.end_date)
+        .bind(self.pre_padding_seconds)
+        .bind(self.post_padding_seconds)
+        .bind(self.updated_at)
+        .execute(db)
+        .await?;
+        let stored = match self.program_id {
+            Some(program_id) => {
+                sqlx::query_as::<_, Self>("SELECT * FROM timers WHERE program_id = $1")
+                    .bind(program_id)
+                    .fetch_optional(db)
+                    .await?
+            }
+            None => Self::get(db, &self.id).await?,
+        };
+        stored.ok_or_else(|| anyhow::anyhow!("timer {} was not stored", self.id))
+    }
+
+    /// Adds a series rule's timer unless the program already has one, so a
+    /// cancelled airing stays cancelled. The rule's timers are also checked
+    /// for the same start: a rule recording from any channel matches one
+    /// airing once per channel that simulcasts it. Returns whether a row was
+    /// added.
+    pub async fn insert_if_absent(&self, db: &SqlitePool) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO timers (id, series_timer_id, channel_id, program_id, name,
+                                          overview, start_date, end_date, pre_padding_seconds,
+                                          post_padding_seconds, status, created_at, updated_at)
+            SELECT $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, 'new', $11, $11
+            WHERE $2 IS NULL
+               OR NOT EXISTS (SELECT 1 FROM timers WHERE series_timer_id = $2 AND start_date = $7)
+            "#,
+        )
+        .bind(self.id)
+        .bind(self.series_timer_id)
+        .bind(self.channel_id)
+        .bind(self.program_id)
+        .bind(&self.name)
+        .bind(&self.overview)
+        .bind(self.start_date)
+        .bind(self.end_date)
+        .bind(self.pre_padding_seconds)
+        .bind(self.post_padding_seconds)
+        .bind(self.updated_at)
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get(db: &SqlitePool, id: &Uuid) -> Result<Option<Self>> {
+        Ok(
+            sqlx::query_as::<_, Self>("SELECT * FROM timers WHERE id = $1")
+                .bind(id)
+                .fetch_optional(db)
+                .await?,
+        )
+    }
+
+    pub async fn get_by_recording(
+        db: &SqlitePool,
+        recording_id: &Uuid,
+    ) -> Result<Option<Self>> {
+        Ok(
+            sqlx::query_as::<_, Self>("SELECT * FROM timers WHERE recording_id = $1")
+                .bind(recording_id)
+                .fetch_optional(db)
+                .await?,
+        )
+    }
+
+    pub async fn get_by_filter(
+        db: &SqlitePool,
+        filter: &TimerFilter,
+    ) -> Result<Vec<Self>> {
+        let mut qb = sqlx::QueryBuilder::new("SELECT * FROM timers WHERE 1 = 1");
+        if let Some(channel_id) = filter.channel_id {
+            qb.push(" AND channel_id = ");
+            qb.push_bind(channel_id);
+        }
+        if let Some(series_timer_id) = filter.series_timer_id {
+            qb.push(" AND series_timer_id = ");
+            qb.push_bind(series_timer_id);
+        }
+        if filter.active {
+            qb.push(" AND status IN ('new', 'in_progress')");
+        }
+        qb.push(" ORDER BY start_date");
+        Ok(qb
+            .build_query_as::<Self>()
+            .fetch_all(db)
+            .await?)
+    }
+
+    /// Scheduled timers whose recording window has opened by `now`.
+    pub async fn due(db: &SqlitePool, now: NaiveDateTime) -> Result<Vec<Self>> {
+        let scheduled = sqlx::query_as::<_, Self>(
+            "SELECT * FROM timers WHERE status = 'new' ORDER BY start_date",
+        )
+        .fetch_all(db)
+        .await?;
+        Ok(scheduled
+            .into_iter()
+            .filter(|t| t.record_start() <= now)
+            .collect())
+    }
+
+    /// Timer ids keyed by program, for marking guide entries as scheduled.
+    pub async fn by_program_ids(
+        db: &SqlitePool,
+        program_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, Self>> {
+        if program_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let mut qb = sqlx::QueryBuilder::new(
+            "SELECT * FROM timers WHERE status IN ('new', 'in_progress') AND program_id IN (",
+        );
+        let mut sep = qb.separated(", ");
+        for id in program_ids {
+            sep.push_bind(*id);
+        }
+        qb.push(")");
+        Ok(qb
+            .build_query_as::<Self>()
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .filter_map(|t| {
+                t.program_id
+                    .map(|p| (p, t))
+            })
+            .collect())
+    }
+
+    pub async fn set_status(
+        db: &SqlitePool,
+        id: &Uuid,
+        status: TimerStatus,
+        last_error: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE timers SET status = $2, last_error = $3, updated_at = $4 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(status)
+        .bind(last_error)
+        .bind(Utc::now().naive_utc())
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Links the timer to the `recording` media row holding its file.
+    pub async fn set_recording(
+        db: &SqlitePool,
+        id: &Uuid,
+        recording_id: Option<Uuid>,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE timers SET recording_id = $2, updated_at = $3 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(recording_id)
+        .bind(Utc::now().naive_utc())
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Timers that produced a recording, keyed by the recording's media id.
+    pub async fn by_recording(db: &SqlitePool) -> Result<HashMap<Uuid, Self>> {
+        Ok(sqlx::query_as::<_, Self>(
+            "SELECT * FROM timers WHERE recording_id IS NOT NULL",
+        )
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .filter_map(|t| {
+            t.recording_id
+                .map(|r| (r, t))
+        })
+        .collect())
+    }
+
+    /// A series rule's finished recordings, newest airing first.
+    pub async fn completed_for_series(
+        db: &SqlitePool,
+        series_timer_id: &Uuid,
+    ) -> Result<Vec<Self>> {
+        Ok(sqlx::query_as::<_, Self>(
+            "SELECT * FROM timers WHERE series_timer_id = $1 AND status = 'completed' \
+             AND recording_id IS NOT NULL ORDER BY start_date DESC",
+        )
+        .bind(series_timer_id)
+        .fetch_all(db)
+        .await?)
+    }
+
+    pub async fn set_padding(
+        db: &SqlitePool,
+        id: &Uuid,
+        pre_padding: i64,
+        post_padding: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE timers SET pre_padding_seconds = $2, post_padding_seconds = $3, \
+             updated_at = $4 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(pre_padding.max(0))
+        .bind(post_padding.max(0))
+        .bind(Utc::now().naive_utc())
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    /// Recordings cut short by a restart: ffmpeg died with the process, so
+    /// whatever was written is all there is.
+    pub async fn fail_interrupted(db: &SqlitePool) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE timers SET status = 'error', \
+             last_error = 'interrupted by a server restart', updated_at = $1 \
+             WHERE status = 'in_progress'",
+        )
+        .bind(Utc::now().naive_utc())
+        .execute(db)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Drops a series rule's airings that have not started recording yet.
+    pub async fn delete_scheduled_for_series(
+        db: &SqlitePool,
+        series_timer_id: &Uuid,
+    ) -> Result<()> {
+        sqlx::query("DELETE FROM timers WHERE series_timer_id = $1 AND status = 'new'")
+            .bind(series_timer_id)
+            .execute(db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(db: &SqlitePool, id: &Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM timers WHERE id = $1")
+            .bind(id)
+            .execute(db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// A rule that schedules a timer for every airing of a series.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SeriesTimer {
+    pub id: Uuid,
+    /// Series title; airings are matched on it, case-insensitively.
+    pub name: String,
+    pub channel_id: Uuid,
+    pub program_id: Option<Uuid>,
+    pub record_any_channel: bool,
+    pub pre_padding_seconds: i64,
+    pub post_padding_seconds: i64,
+    /// Completed recordings to keep; 0 keeps all.
+    pub keep_up_to: i64,
+    pub created_at: NaiveDateTime,
+}
+
+impl SeriesTimer {
+    pub async fn save(&self, db: &SqlitePool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO series_timers (id, name, channel_id, program_id, record_any_channel,
+                                       pre_padding_seconds, post_padding_seconds, keep_up_to,
+                                       created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (id) DO UPDATE SET
+                record_any_channel   = excluded.record_any_channel,
+                pre_padding_seconds  = excluded.pre_padding_seconds,
+                post_padding_seconds = excluded.post_padding_seconds,
+                keep_up_to           = excluded.keep_up_to
+            "#,
+        )
+        .bind(self.id)
+        .bind(&self.name)
+        .bind(self.channel_id)
+        .bind(self.program_id)
+        .bind(self.record_any_channel)
+        .bind(self.pre_padding_seconds.max(0))
+        .bind(self.post_padding_seconds.max(0))
+        .bind(self.keep_up_to.max(0))
+        .bind(self.created_at)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get(db: &SqlitePool, id: &Uuid) -> Result<Option<Self>> {
+        Ok(
+            sqlx::query_as::<_, Self>("SELECT * FROM series_timers WHERE id = $1")
+                .bind(id)
+                .fetch_optional(db)
+                .await?,
+        )
+    }
+
+    pub async fn get_all(db: &SqlitePool) -> Result<Vec<Self>> {
+        Ok(
+            sqlx::query_as::<_, Self>("SELECT * FROM series_timers ORDER BY name")
+                .fetch_all(db)
+                .await?,
+        )
+    }
+
+    pub async fn delete(db: &SqlitePool, id: &Uuid) -> Result<bool> {
+        Timer::delete_scheduled_for_series(db, id).await?;
+        let result = sqlx::query("DELETE FROM series_timers WHERE id = $1")
+            .bind(id)
+            .execute(db)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -184,6 +184,8 @@ pub enum MediaKind {
     StreamGroup,
     Subtitle,
     Intro,
+    /// A Live TV airing captured by the DVR.
+    Recording,
 }
 
 impl MediaKind {
@@ -205,7 +207,11 @@ impl MediaKind {
     pub fn is_playable_leaf(&self) -> bool {
         matches!(
             self,
-            Self::Movie | Self::Episode | Self::Track | Self::TvChannel
+            Self::Movie
+                | Self::Episode
+                | Self::Track
+                | Self::TvChannel
+                | Self::Recording
         )
     }
 }
@@ -295,6 +301,7 @@ impl Into<sdks::remux::MediaKind> for MediaKind {
             MediaKind::StreamGroup => sdks::remux::MediaKind::Stream,
             MediaKind::Subtitle => sdks::remux::MediaKind::Stream,
             MediaKind::Intro => sdks::remux::MediaKind::Stream,
+            MediaKind::Recording => sdks::remux::MediaKind::Stream,
         }
     }
 }
@@ -350,6 +357,7 @@ impl TryFrom<api::MediaType> for MediaKind {
             api::MediaType::MusicAlbum => Ok(MediaKind::Album),
             api::MediaType::MusicArtist => Ok(MediaKind::Artist),
             api::MediaType::Playlist => Ok(MediaKind::Playlist),
+            api::MediaType::Recording => Ok(MediaKind::Recording),
             _ => Err(()),
         }
     }
@@ -889,6 +897,13 @@ pub struct ExternalIds {
     pub deezer_album: Option<i64>,
     pub deezer_track: Option<i64>,
     pub deezer_playlist: Option<i64>,
+    /// MusicBrainz ids read from embedded tags.
+    pub musicbrainz_recording: Option<String>,
+    pub musicbrainz_track: Option<String>,
+    pub musicbrainz_album: Option<String>,
+    pub musicbrainz_release_group: Option<String>,
+    pub musicbrainz_artist: Option<String>,
+    pub musicbrainz_album_artist: Option<String>,
     pub youtube_id: Option<String>,
     pub iptv_source_id: Option<String>,
     pub iptv_group: Option<String>,
@@ -1164,6 +1179,36 @@ impl ExternalIds {
         merge_option(&mut self.deezer_album, &source.deezer_album, replace);
         merge_option(&mut self.deezer_track, &source.deezer_track, replace);
         merge_option(&mut self.deezer_playlist, &source.deezer_playlist, replace);
+        merge_option(
+            &mut self.musicbrainz_recording,
+            &source.musicbrainz_recording,
+            replace,
+        );
+        merge_option(
+            &mut self.musicbrainz_track,
+            &source.musicbrainz_track,
+            replace,
+        );
+        merge_option(
+            &mut self.musicbrainz_album,
+            &source.musicbrainz_album,
+            replace,
+        );
+        merge_option(
+            &mut self.musicbrainz_release_group,
+            &source.musicbrainz_release_group,
+            replace,
+        );
+        merge_option(
+            &mut self.musicbrainz_artist,
+            &source.musicbrainz_artist,
+            replace,
+        );
+        merge_option(
+            &mut self.musicbrainz_album_artist,
+            &source.musicbrainz_album_artist,
+            replace,
+        );
         merge_option(&mut self.youtube_id, &source.youtube_id, replace);
         merge_option(&mut self.iptv_source_id, &source.iptv_source_id, replace);
         merge_option(&mut self.iptv_group, &source.iptv_group, replace);
@@ -1389,6 +1434,9 @@ pub struct Media {
     pub images: MediaImages,
     pub status: Option<MediaStatus>,
     pub album_kind: Option<AlbumKind>,
+    /// ReplayGain track gain in dB, from embedded tags.
+    #[sqlx(default)]
+    pub normalization_gain: Option<f64>,
     pub idx: Option<i64>,
     pub parent_idx: Option<i64>,
     pub parent_id: Option<Uuid>,
@@ -2224,9 +2272,9 @@ impl Media {
             live_start, live_end, tvg_id, channel_number, enabled, sort_order, custom_name, digital_released_at, status, refreshed_at, grandparent_id,
             collection_smart_filter, country, program_kind, collection_latest_auto_unplayed, collection_latest_sort_digital,
             collection_default_sort, collection_default_sort_order,
-            original_language, is_locked, locked_fields, album_kind
+            original_language, is_locked, locked_fields, album_kind, normalization_gain
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40, $41, $42, $43, $44, $45, $46)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29, $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40, $41, $42, $43, $44, $45, $46, $47)
         ON CONFLICT (id) DO UPDATE SET
             title = excluded.title,
             kind = excluded.kind,
@@ -2270,7 +2318,8 @@ impl Media {
             original_language = COALESCE(excluded.original_language, media.original_language),
             is_locked = excluded.is_locked,
             locked_fields = excluded.locked_fields,
-            album_kind = COALESCE(excluded.album_kind, media.album_kind)
+            album_kind = COALESCE(excluded.album_kind, media.album_kind),
+            normalization_gain = COALESCE(excluded.normalization_gain, media.normalization_gain)
         "#,
         )
         .bind(self.id)
@@ -2319,6 +2368,7 @@ impl Media {
         .bind(self.is_locked)
         .bind(sqlx::types::Json(&self.locked_fields))
         .bind(&self.album_kind)
+        .bind(self.normalization_gain)
         .execute(db)
         .await?;
 
@@ -2371,7 +2421,7 @@ impl Media {
                 external_ids, external_ratings, created_at, updated_at, certification, certification_age, parent_idx,
                 live_start, live_end, tvg_id, channel_number, enabled, sort_order, custom_name, digital_released_at, status, grandparent_id, country, program_kind, collection_latest_auto_unplayed, collection_latest_sort_digital,
                 collection_default_sort, collection_default_sort_order,
-                original_language, is_locked, locked_fields, album_kind
+                original_language, is_locked, locked_fields, album_kind, normalization_gain
             )",
         );
             for item in chunk {
@@ -2428,7 +2478,8 @@ impl Media {
                     .push_bind(&item.original_language)
                     .push_bind(&item.is_locked)
                     .push_bind(sqlx::types::Json(&item.locked_fields))
-                    .push_bind(&item.album_kind);
+                    .push_bind(&item.album_kind)
+                    .push_bind(&item.normalization_gain);
             });
 
             query_builder.push(" ON CONFLICT DO NOTHING");
@@ -2485,7 +2536,7 @@ impl Media {
                 external_ids, external_ratings, created_at, updated_at, certification, certification_age, parent_idx,
                 live_start, live_end, tvg_id, channel_number, enabled, sort_order, custom_name, digital_released_at, status, refreshed_at, grandparent_id, country, program_kind, collection_latest_auto_unplayed, collection_latest_sort_digital,
                 collection_default_sort, collection_default_sort_order,
-                original_language, is_locked, locked_fields, album_kind
+                original_language, is_locked, locked_fields, album_kind, normalization_gain
             )",
         );
 
@@ -2541,7 +2592,8 @@ impl Media {
                     .push_bind(&item.original_language)
                     .push_bind(&item.is_locked)
                     .push_bind(sqlx::types::Json(&item.locked_fields))
-                    .push_bind(&item.album_kind);
+                    .push_bind(&item.album_kind)
+                    .push_bind(&item.normalization_gain);
             });
 
             query_builder.push(
@@ -2583,7 +2635,8 @@ impl Media {
                 -- preserve user-set locks; never let a provider refresh overwrite them
                 is_locked = CASE WHEN media.id IS NOT NULL THEN media.is_locked ELSE excluded.is_locked END,
                 locked_fields = CASE WHEN media.id IS NOT NULL THEN media.locked_fields ELSE excluded.locked_fields END,
-                album_kind = COALESCE(excluded.album_kind, media.album_kind)",
+                album_kind = COALESCE(excluded.album_kind, media.album_kind),
+                normalization_gain = COALESCE(excluded.normalization_gain, media.normalization_gain)",
             );
 
             query_builder
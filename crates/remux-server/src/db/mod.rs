@@ -13,25 +13,33 @@ pub mod activity;
 pub mod api_key;
 pub mod auth;
 pub mod delivery_queue;
+pub mod download_job;
 pub mod image;
 pub mod iptv;
+pub mod live_tv;
 pub mod media;
 pub mod settings;
 pub mod stream_group;
 pub mod task;
 pub mod user;
+pub mod user_identity;
 pub mod user_media_tracker;
+pub mod user_watch_time;
 pub use activity::*;
 pub use api_key::*;
 pub use delivery_queue::*;
+pub use download_job::*;
 pub use image::*;
 pub use iptv::*;
+pub use live_tv::*;
 pub use media::*;
 pub use settings::*;
 pub use stream_group::*;
 pub use task::*;
 pub use user::*;
+pub use user_identity::*;
 pub use user_media_tracker::*;
+pub use user_watch_time::*;
 
 pub async fn connect(url: &str, slow_query_threshold_ms: u64) -> Result<SqlitePool> {
     let opts = SqliteConnectOptions::from_str(url)?
@@ -3,11 +3,14 @@ use sqlx::SqlitePool;
 use uuid::Uuid;
 
 use crate::api::{EncodingOptions, ServerConfiguration};
-use remux_sdks::remux::IntroOptions;
+use remux_sdks::remux::{DlnaOptions, IntroOptions, LdapOptions, OidcOptions};
 
 const SERVER_CONFIG_KEY: &str = "server_configuration";
 const ENCODING_CONFIG_KEY: &str = "encoding_configuration";
 const INTRO_CONFIG_KEY: &str = "intro_configuration";
+const OIDC_CONFIG_KEY: &str = "oidc_configuration";
+const LDAP_CONFIG_KEY: &str = "ldap_configuration";
+const DLNA_CONFIG_KEY: &str = "dlna_configuration";
 
 pub struct Settings;
 
@@ -64,6 +67,42 @@ impl Settings {
         Self::set(db, INTRO_CONFIG_KEY, &json).await
     }
 
+    pub async fn get_oidc_config(db: &SqlitePool) -> Result<OidcOptions> {
+        Ok(match Self::get(db, OIDC_CONFIG_KEY).await? {
+            Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+            None => OidcOptions::default(),
+        })
+    }
+
+    pub async fn set_oidc_config(db: &SqlitePool, opts: &OidcOptions) -> Result<()> {
+        let json = serde_json::to_string(opts)?;
+        Self::set(db, OIDC_CONFIG_KEY, &json).await
+    }
+
+    pub async fn get_ldap_config(db: &SqlitePool) -> Result<LdapOptions> {
+        Ok(match Self::get(db, LDAP_CONFIG_KEY).await? {
+            Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+            None => LdapOptions::default(),
+        })
+    }
+
+    pub async fn set_ldap_config(db: &SqlitePool, opts: &LdapOptions) -> Result<()> {
+        let json = serde_json::to_string(opts)?;
+        Self::set(db, LDAP_CONFIG_KEY, &json).await
+    }
+
+    pub async fn get_dlna_config(db: &SqlitePool) -> Result<DlnaOptions> {
+        Ok(match Self::get(db, DLNA_CONFIG_KEY).await? {
+            Some(json) => serde_json::from_str(&json).unwrap_or_default(),
+            None => DlnaOptions::default(),
+        })
+    }
+
+    pub async fn set_dlna_config(db: &SqlitePool, opts: &DlnaOptions) -> Result<()> {
+        let json = serde_json::to_string(opts)?;
+        Self::set(db, DLNA_CONFIG_KEY, &json).await
+    }
+
     pub async fn init_server_id(db: &SqlitePool) -> Result<()> {
         let id = match Self::get(db, "server_id").await? {
             Some(existing) => Uuid::parse_str(&existing)
@@ -208,6 +208,52 @@ impl User {
         })
     }
 
+    /// A user that signs in through an external provider. Its local password
+    /// is random, so only the provider can authenticate it.
+    pub fn new_external(
+        username: String,
+        provider_id: &str,
+        is_admin: bool,
+    ) -> Result<Self> {
+        let password = SaltString::generate(&mut OsRng);
+        let mut user =
+            Self::new_with_password(String::new(), username, password.as_str(), None)?;
+        user.is_admin = is_admin;
+        user.policy = Some(sqlx::types::Json(crate::api::UserPolicy {
+            is_administrator: is_admin,
+            authentication_provider_id: provider_id.to_string(),
+            ..Default::default()
+        }));
+        Ok(user)
+    }
+
+    /// The policy's `AuthenticationProviderId`. External sign-in only takes
+    /// over an existing account an admin has assigned to that provider.
+    pub fn authentication_provider(&self) -> Option<&str> {
+        self.policy
+            .as_deref()
+            .map(|p| {
+                p.authentication_provider_id
+                    .as_str()
+            })
+    }
+
+    /// Apply the admin flag an external provider reports, saving on change.
+    pub async fn sync_admin(&mut self, db: &SqlitePool, is_admin: bool) -> Result<()> {
+        if self.is_admin == is_admin {
+            return Ok(());
+        }
+        self.is_admin = is_admin;
+        if let Some(policy) = self
+            .policy
+            .as_mut()
+        {
+            policy.is_administrator = is_admin;
+        }
+        self.save(db)
+            .await
+    }
+
     pub async fn get_by_filter(
         db: &sqlx::SqlitePool,
         filter: &UserFilter,
@@ -323,6 +369,62 @@ impl User {
                 .map_or(false, |p| p.enable_remote_control_of_other_users)
     }
 
+    pub fn can_manage_live_tv(&self) -> bool {
+        self.is_admin
+            || self
+                .policy
+                .as_deref()
+                .map_or(false, |p| p.enable_live_tv_management)
+    }
+
+    pub fn sync_play_access(&self) -> crate::api::SyncPlayUserAccessType {
+        if self.is_admin {
+            return crate::api::SyncPlayUserAccessType::CreateAndJoinGroups;
+        }
+        self.policy
+            .as_deref()
+            .map(|p| {
+                p.sync_play_access
+                    .clone()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether `now` (server local time) falls inside one of the user's
+    /// access schedules. Admins, and users without schedules, are never
+    /// restricted.
+    pub fn is_within_access_schedule(&self, now: NaiveDateTime) -> bool {
+        if self.is_admin {
+            return true;
+        }
+        let Some(policy) = self
+            .policy
+            .as_deref()
+        else {
+            return true;
+        };
+        policy
+            .access_schedules
+            .is_empty()
+            || policy
+                .access_schedules
+                .iter()
+                .any(|schedule| schedule_allows(schedule, now))
+    }
+
+    /// The user's daily playback allowance in seconds, or `None` when
+    /// unlimited. Admins are never limited.
+    pub fn daily_watch_limit_secs(&self) -> Option<i64> {
+        if self.is_admin {
+            return None;
+        }
+        self.policy
+            .as_deref()
+            .map(|p| p.daily_watch_time_limit_minutes)
+            .filter(|minutes| *minutes > 0)
+            .map(|minutes| minutes * 60)
+    }
+
     pub async fn get_media_state(
         &self,
         db: &SqlitePool,
@@ -332,6 +434,27 @@ impl User {
     }
 }
 
+fn schedule_allows(schedule: &crate::api::AccessSchedule, now: NaiveDateTime) -> bool {
+    use crate::api::DynamicDayOfWeek as Day;
+
+    let weekend = matches!(now.weekday(), Weekday::Sat | Weekday::Sun);
+    let day_matches = match schedule.day_of_week {
+        Day::Everyday => true,
+        Day::Weekday => !weekend,
+        Day::Weekend => weekend,
+        Day::Sunday => now.weekday() == Weekday::Sun,
+        Day::Monday => now.weekday() == Weekday::Mon,
+        Day::Tuesday => now.weekday() == Weekday::Tue,
+        Day::Wednesday => now.weekday() == Weekday::Wed,
+        Day::Thursday => now.weekday() == Weekday::Thu,
+        Day::Friday => now.weekday() == Weekday::Fri,
+        Day::Saturday => now.weekday() == Weekday::Sat,
+    };
+    let hour =
+        now.hour() as f64 + now.minute() as f64 / 60.0 + now.second() as f64 / 3600.0;
+    day_matches && hour >= schedule.start_hour && hour < schedule.end_hour
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct CustomData {
     pub id: String,
@@ -1244,3 +1367,90 @@ mod playback_threshold_tests {
         assert!(!crossed, "no runtime means no threshold was applied");
     }
 }
+
+#[cfg(test)]
+mod access_schedule_tests {
+    use super::*;
+    use crate::api::{AccessSchedule, DynamicDayOfWeek, UserPolicy};
+
+    fn user_with(schedules: Vec<AccessSchedule>) -> User {
+        User {
+            policy: Some(sqlx::types::Json(UserPolicy {
+                access_schedules: schedules,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn schedule(
+        day_of_week: DynamicDayOfWeek,
+        start_hour: f64,
+        end_hour: f64,
+    ) -> AccessSchedule {
+        AccessSchedule {
+            day_of_week,
+            start_hour,
+            end_hour,
+            ..Default::default()
+        }
+    }
+
+    // 2026-03-07 is a Saturday.
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn no_schedules_means_no_restriction() {
+        assert!(user_with(vec![]).is_within_access_schedule(at(7, 3, 0)));
+        assert!(User::default().is_within_access_schedule(at(7, 3, 0)));
+    }
+
+    #[test]
+    fn hours_are_half_open() {
+        let user = user_with(vec![schedule(DynamicDayOfWeek::Everyday, 18.0, 20.5)]);
+        assert!(!user.is_within_access_schedule(at(7, 17, 59)));
+        assert!(user.is_within_access_schedule(at(7, 18, 0)));
+        assert!(user.is_within_access_schedule(at(7, 20, 29)));
+        assert!(!user.is_within_access_schedule(at(7, 20, 30)));
+    }
+
+    #[test]
+    fn day_groups_match_their_days() {
+        let weekend = user_with(vec![schedule(DynamicDayOfWeek::Weekend, 0.0, 24.0)]);
+        let weekday = user_with(vec![schedule(DynamicDayOfWeek::Weekday, 0.0, 24.0)]);
+        let monday = user_with(vec![schedule(DynamicDayOfWeek::Monday, 0.0, 24.0)]);
+
+        // Saturday, Sunday, Monday.
+        assert!(weekend.is_within_access_schedule(at(7, 12, 0)));
+        assert!(weekend.is_within_access_schedule(at(8, 12, 0)));
+        assert!(!weekend.is_within_access_schedule(at(9, 12, 0)));
+        assert!(!weekday.is_within_access_schedule(at(8, 12, 0)));
+        assert!(weekday.is_within_access_schedule(at(9, 12, 0)));
+        assert!(monday.is_within_access_schedule(at(9, 23, 59)));
+        assert!(!monday.is_within_access_schedule(at(10, 0, 0)));
+    }
+
+    #[test]
+    fn admins_are_never_restricted() {
+        let mut user = user_with(vec![schedule(DynamicDayOfWeek::Monday, 0.0, 1.0)]);
+        user.is_admin = true;
+        assert!(user.is_within_access_schedule(at(7, 12, 0)));
+        assert_eq!(user.daily_watch_limit_secs(), None);
+    }
+
+    #[test]
+    fn a_zero_watch_limit_is_unlimited() {
+        let mut user = user_with(vec![]);
+        assert_eq!(user.daily_watch_limit_secs(), None);
+        user.policy
+            .as_mut()
+            .unwrap()
+            .daily_watch_time_limit_minutes = 90;
+        assert_eq!(user.daily_watch_limit_secs(), Some(90 * 60));
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+/// Links between external identities and remux users.
+pub struct UserIdentity;
+
+impl UserIdentity {
+    pub async fn find_user(
+        db: &SqlitePool,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<Uuid>> {
+        Ok(sqlx::query_scalar(
+            "SELECT user_id FROM user_identities WHERE provider = ?1 AND subject = ?2",
+        )
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(db)
+        .await?)
+    }
+
+    /// Whether any external identity signs in as `user_id`.
+    pub async fn is_linked(db: &SqlitePool, user_id: &Uuid) -> Result<bool> {
+        Ok(sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM user_identities WHERE user_id = ?1",
+        )
+        .bind(user_id)
+        .fetch_one(db)
+        .await?
+            > 0)
+    }
+
+    /// Link `subject` to `user_id`, moving it off any user it was linked to.
+    pub async fn link(
+        db: &SqlitePool,
+        provider: &str,
+        subject: &str,
+        user_id: &Uuid,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_identities (provider, subject, user_id, created_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (provider, subject) DO UPDATE SET user_id = excluded.user_id
+            "#,
+        )
+        .bind(provider)
+        .bind(subject)
+        .bind(user_id)
+        .bind(Utc::now())
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+}
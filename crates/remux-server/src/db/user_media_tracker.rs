@@ -231,6 +231,25 @@ impl UserMediaTracker {
         Ok(())
     }
 
+    /// Store credentials a provider renewed on its own, leaving status and
+    /// filters alone.
+    pub async fn set_credentials(
+        db: &SqlitePool,
+        id: Uuid,
+        credentials: &MediaTrackerCredentials,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE user_media_trackers SET credentials = ?2, updated_at = ?3 \
+             WHERE id = ?1",
+        )
+        .bind(id)
+        .bind(sqlx::types::Json(credentials))
+        .bind(Utc::now().naive_utc())
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
     pub async fn mark_success(db: &SqlitePool, id: Uuid) -> Result<()> {
         let now = Utc::now().naive_utc();
         sqlx::query(
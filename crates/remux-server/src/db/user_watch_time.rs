@@ -0,0 +1,44 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+/// Seconds of playback a user has accumulated per local day.
+pub struct UserWatchTime;
+
+impl UserWatchTime {
+    pub async fn add(
+        db: &SqlitePool,
+        user_id: &Uuid,
+        day: NaiveDate,
+        seconds: i64,
+    ) -> Result<()> {
+        if seconds <= 0 {
+            return Ok(());
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO user_watch_time (user_id, day, seconds)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, day) DO UPDATE SET seconds = seconds + excluded.seconds
+            "#,
+        )
+        .bind(user_id)
+        .bind(day)
+        .bind(seconds)
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get(db: &SqlitePool, user_id: &Uuid, day: NaiveDate) -> Result<i64> {
+        Ok(sqlx::query_scalar(
+            "SELECT seconds FROM user_watch_time WHERE user_id = $1 AND day = $2",
+        )
+        .bind(user_id)
+        .bind(day)
+        .fetch_optional(db)
+        .await?
+        .unwrap_or(0))
+    }
+}
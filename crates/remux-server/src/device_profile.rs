@@ -8,6 +8,7 @@ use remux_sdks::remux::{
 pub trait DeviceProfileExt {
     fn video_transcoding_profile(&self) -> Option<&TranscodingProfile>;
     fn audio_transcoding_profile(&self) -> Option<&TranscodingProfile>;
+    fn music_transcoding_bitrate(&self, codec: &str) -> Option<i64>;
     fn subtitle_delivery_method(&self, codec: &str) -> Option<SubtitleDeliveryMethod>;
     fn supports_direct_play(&self, media_source: &MediaSourceInfo) -> bool;
     fn check_direct_play(&self, media_source: &MediaSourceInfo) -> TranscodeReasons;
@@ -69,6 +70,38 @@ impl DeviceProfileExt for DeviceProfile {
             })
     }
 
+    /// Highest bitrate for music transcoded to `codec`: the profile's
+    /// `MusicStreamingTranscodingBitrate`, lowered by any `AudioBitrate
+    /// LessThanEqual` condition of an audio codec profile for that codec.
+    fn music_transcoding_bitrate(&self, codec: &str) -> Option<i64> {
+        self.codec_profiles
+            .iter()
+            .filter(|cp| {
+                cp.type_
+                    .as_deref()
+                    .is_some_and(|t| t.eq_ignore_ascii_case("Audio"))
+                    && cp.applies_to_codec(codec)
+            })
+            .flat_map(|cp| &cp.conditions)
+            .filter(|c| {
+                c.property
+                    .as_deref()
+                    == Some("AudioBitrate")
+                    && c.condition
+                        .as_deref()
+                        .is_some_and(|c| c.eq_ignore_ascii_case("LessThanEqual"))
+            })
+            .filter_map(|c| {
+                c.value
+                    .as_deref()?
+                    .trim()
+                    .parse::<i64>()
+                    .ok()
+            })
+            .chain(self.music_streaming_transcoding_bitrate)
+            .min()
+    }
+
     fn subtitle_delivery_method(&self, codec: &str) -> Option<SubtitleDeliveryMethod> {
         self.subtitle_profiles
             .iter()
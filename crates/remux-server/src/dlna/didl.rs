@@ -0,0 +1,334 @@
+//! SOAP envelopes and DIDL-Lite, the XML UPnP control requests speak.
+
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow};
+use quick_xml::{Reader, escape::escape, events::Event};
+
+/// A parsed SOAP control request: the action element and its arguments.
+#[derive(Debug, Default)]
+pub struct SoapAction {
+    pub name: String,
+    pub args: HashMap<String, String>,
+}
+
+impl SoapAction {
+    pub fn arg(&self, name: &str) -> Option<&str> {
+        self.args
+            .get(name)
+            .map(String::as_str)
+    }
+}
+
+pub fn parse_action(xml: &str) -> Result<SoapAction> {
+    let mut reader = Reader::from_str(xml);
+    reader
+        .config_mut()
+        .trim_text(true);
+    let mut action = SoapAction::default();
+    // Envelope > Body > action > argument
+    let mut depth = 0;
+    let mut arg: Option<String> = None;
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                depth += 1;
+                let name = String::from_utf8_lossy(
+                    e.local_name()
+                        .as_ref(),
+                )
+                .into_owned();
+                match depth {
+                    3 => action.name = name,
+                    4 => {
+                        action
+                            .args
+                            .insert(name.clone(), String::new());
+                        arg = Some(name);
+                    }
+                    _ => {}
+                }
+            }
+            Event::Empty(e) if depth == 2 => {
+                action.name = String::from_utf8_lossy(
+                    e.local_name()
+                        .as_ref(),
+                )
+                .into_owned();
+            }
+            Event::Empty(e) if depth == 3 => {
+                action
+                    .args
+                    .insert(
+                        String::from_utf8_lossy(
+                            e.local_name()
+                                .as_ref(),
+                        )
+                        .into_owned(),
+                        String::new(),
+                    );
+            }
+            Event::Text(t) => {
+                if let Some(name) = &arg {
+                    action
+                        .args
+                        .insert(
+                            name.clone(),
+                            t.unescape()?
+                                .into_owned(),
+                        );
+                }
+            }
+            Event::End(_) => {
+                depth -= 1;
+                arg = None;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if action
+        .name
+        .is_empty()
+    {
+        return Err(anyhow!("no SOAP action in request body"));
+    }
+    Ok(action)
+}
+
+const ENVELOPE_OPEN: &str = r#"<?xml version="1.0" encoding="utf-8"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/"><s:Body>"#;
+const ENVELOPE_CLOSE: &str = "</s:Body></s:Envelope>";
+
+pub fn soap_response(service: &str, action: &str, args: &[(&str, String)]) -> String {
+    let mut out = format!(r#"{ENVELOPE_OPEN}<u:{action}Response xmlns:u="{service}">"#);
+    for (name, value) in args {
+        out.push_str(&format!("<{name}>{}</{name}>", escape(value.as_str())));
+    }
+    out.push_str(&format!("</u:{action}Response>{ENVELOPE_CLOSE}"));
+    out
+}
+
+/// UPnP errors used here: 401 invalid action, 402 invalid args, 701 no
+/// such object.
+pub fn soap_fault(code: u16, description: &str) -> String {
+    format!(
+        r#"{ENVELOPE_OPEN}<s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail><UPnPError xmlns="urn:schemas-upnp-org:control-1-0"><errorCode>{code}</errorCode><errorDescription>{}</errorDescription></UPnPError></detail></s:Fault>{ENVELOPE_CLOSE}"#,
+        escape(description)
+    )
+}
+
+/// A stream of an item and how it is delivered.
+pub struct Resource {
+    pub url: String,
+    pub mime_type: &'static str,
+    /// Direct streams support byte ranges; transcodes are converted content
+    /// that can only be read front to back.
+    pub transcoded: bool,
+    pub duration_ticks: Option<i64>,
+    pub size: Option<i64>,
+    pub bitrate: Option<i64>,
+    pub resolution: Option<(i64, i64)>,
+}
+
+impl Resource {
+    pub fn protocol_info(&self) -> String {
+        let flags = if self.transcoded {
+            "DLNA.ORG_OP=00;DLNA.ORG_CI=1;DLNA.ORG_FLAGS=01100000000000000000000000000000"
+        } else {
+            "DLNA.ORG_OP=01;DLNA.ORG_CI=0;DLNA.ORG_FLAGS=01700000000000000000000000000000"
+        };
+        format!("http-get:*:{}:{flags}", self.mime_type)
+    }
+}
+
+pub enum Object {
+    Container {
+        id: String,
+        parent_id: String,
+        title: String,
+        class: &'static str,
+        child_count: Option<i64>,
+        art: Option<String>,
+    },
+    Item {
+        id: String,
+        parent_id: String,
+        title: String,
+        class: &'static str,
+        art: Option<String>,
+        artist: Option<String>,
+        album: Option<String>,
+        track: Option<i64>,
+        date: Option<String>,
+        resource: Resource,
+    },
+}
+
+/// `H:MM:SS.mmm`, the UPnP duration format.
+pub fn duration(ticks: i64) -> String {
+    let ms = ticks / 10_000;
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+fn element(out: &mut String, name: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        out.push_str(&format!("<{name}>{}</{name}>", escape(value)));
+    }
+}
+
+pub fn didl(objects: &[Object]) -> String {
+    let mut out = String::from(
+        r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/" xmlns:dlna="urn:schemas-dlna-org:metadata-1-0/">"#,
+    );
+    for object in objects {
+        match object {
+            Object::Container {
+                id,
+                parent_id,
+                title,
+                class,
+                child_count,
+                art,
+            } => {
+                out.push_str(&format!(
+                    r#"<container id="{}" parentID="{}" restricted="1" searchable="0""#,
+                    escape(id.as_str()),
+                    escape(parent_id.as_str())
+                ));
+                if let Some(count) = child_count {
+                    out.push_str(&format!(r#" childCount="{count}""#));
+                }
+                out.push('>');
+                element(&mut out, "dc:title", Some(title));
+                element(&mut out, "upnp:class", Some(class));
+                element(&mut out, "upnp:albumArtURI", art.as_deref());
+                out.push_str("</container>");
+            }
+            Object::Item {
+                id,
+                parent_id,
+                title,
+                class,
+                art,
+                artist,
+                album,
+                track,
+                date,
+                resource,
+            } => {
+                out.push_str(&format!(
+                    r#"<item id="{}" parentID="{}" restricted="1">"#,
+                    escape(id.as_str()),
+                    escape(parent_id.as_str())
+                ));
+                element(&mut out, "dc:title", Some(title));
+                element(&mut out, "upnp:class", Some(class));
+                element(&mut out, "dc:creator", artist.as_deref());
+                element(&mut out, "upnp:artist", artist.as_deref());
+                element(&mut out, "upnp:album", album.as_deref());
+                element(
+                    &mut out,
+                    "upnp:originalTrackNumber",
+                    track
+                        .map(|t| t.to_string())
+                        .as_deref(),
+                );
+                element(&mut out, "dc:date", date.as_deref());
+                element(&mut out, "upnp:albumArtURI", art.as_deref());
+                out.push_str(&format!(
+                    r#"<res protocolInfo="{}""#,
+                    escape(resource.protocol_info())
+                ));
+                if let Some(ticks) = resource.duration_ticks {
+                    out.push_str(&format!(r#" duration="{}""#, duration(ticks)));
+                }
+                if let Some(size) = resource
+                    .size
+                    .filter(|_| !resource.transcoded)
+                {
+                    out.push_str(&format!(r#" size="{size}""#));
+                }
+                if let Some(bitrate) = resource.bitrate {
+                    // DIDL bitrates are bytes per second.
+                    out.push_str(&format!(r#" bitrate="{}""#, bitrate / 8));
+                }
+                if let Some((width, height)) = resource.resolution {
+                    out.push_str(&format!(r#" resolution="{width}x{height}""#));
+                }
+                out.push_str(&format!(
+                    ">{}</res></item>",
+                    escape(
+                        resource
+                            .url
+                            .as_str()
+                    )
+                ));
+            }
+        }
+    }
+    out.push_str("</DIDL-Lite>");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browse_requests_are_parsed() {
+        let body = r#"<?xml version="1.0"?>
+            <s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/">
+              <s:Body>
+                <u:Browse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1">
+                  <ObjectID>0</ObjectID>
+                  <BrowseFlag>BrowseDirectChildren</BrowseFlag>
+                  <Filter>*</Filter>
+                  <StartingIndex>0</StartingIndex>
+                  <RequestedCount>20</RequestedCount>
+                  <SortCriteria/>
+                </u:Browse>
+              </s:Body>
+            </s:Envelope>"#;
+        let action = parse_action(body).unwrap();
+        assert_eq!(action.name, "Browse");
+        assert_eq!(action.arg("ObjectID"), Some("0"));
+        assert_eq!(action.arg("RequestedCount"), Some("20"));
+        assert_eq!(action.arg("SortCriteria"), Some(""));
+    }
+
+    #[test]
+    fn items_carry_escaped_metadata_and_a_resource() {
+        let xml = didl(&[Object::Item {
+            id: "a".into(),
+            parent_id: "0".into(),
+            title: "Tom & Jerry".into(),
+            class: "object.item.videoItem.movie",
+            art: None,
+            artist: None,
+            album: None,
+            track: None,
+            date: None,
+            resource: Resource {
+                url: "http://10.0.0.2:8096/videos/a/stream?static=true&mediaSourceId=a"
+                    .into(),
+                mime_type: "video/mp4",
+                transcoded: false,
+                duration_ticks: Some(73_000_000_000),
+                size: Some(1024),
+                bitrate: None,
+                resolution: Some((1920, 1080)),
+            },
+        }]);
+        assert!(xml.contains("<dc:title>Tom &amp; Jerry</dc:title>"));
+        assert!(xml.contains(r#"duration="2:01:40.000""#));
+        assert!(xml.contains(r#"size="1024""#));
+        assert!(xml.contains(r#"resolution="1920x1080""#));
+        assert!(xml.contains("stream?static=true&amp;mediaSourceId=a</res>"));
+    }
+}
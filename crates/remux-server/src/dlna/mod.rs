@@ -0,0 +1,109 @@
+//! DLNA/UPnP MediaServer for TVs and AV receivers that speak nothing else.
+//!
+//! With `EnableUPnP` on in the network configuration, the server announces
+//! itself over SSDP (see [`ssdp`]) and answers ContentDirectory and
+//! ConnectionManager control requests under `/dlna` (see `api::dlna`).
+//! Renderers cannot sign in, so only clients on the local network are
+//! answered, and they browse as the user picked in the DLNA settings.
+
+pub mod description;
+pub mod didl;
+pub mod profile;
+pub mod ssdp;
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::{net::UdpSocket, task::JoinHandle};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{AppContext, api::networking, common::server_id, network::NetworkPolicy};
+
+pub const MEDIA_SERVER_URN: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+pub const CONTENT_DIRECTORY_URN: &str =
+    "urn:schemas-upnp-org:service:ContentDirectory:1";
+pub const CONNECTION_MANAGER_URN: &str =
+    "urn:schemas-upnp-org:service:ConnectionManager:1";
+
+pub const DESCRIPTION_PATH: &str = "/dlna/description.xml";
+
+/// How often the announcer is matched against the `EnableUPnP` setting.
+pub const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The device's unique name, stable across restarts.
+pub fn udn() -> String {
+    let id = server_id();
+    let uuid = Uuid::parse_str(&id)
+        .unwrap_or_else(|_| Uuid::new_v5(&Uuid::NAMESPACE_OID, id.as_bytes()));
+    format!("uuid:{}", uuid.hyphenated())
+}
+
+pub async fn enabled(db: &sqlx::SqlitePool) -> bool {
+    match networking::network_configuration(db).await {
+        Ok(config) => config
+            .enable_u_pn_p
+            .unwrap_or(false),
+        Err(e) => {
+            warn!("failed to read the network configuration: {e:#}");
+            false
+        }
+    }
+}
+
+struct Announcer {
+    socket: Arc<UdpSocket>,
+    task: JoinHandle<()>,
+}
+
+impl Announcer {
+    fn start(
+        http_port: u16,
+        network: Arc<arc_swap::ArcSwap<NetworkPolicy>>,
+    ) -> std::io::Result<Self> {
+        let socket = Arc::new(ssdp::bind(ssdp::SSDP_PORT)?);
+        let task = tokio::spawn(ssdp::serve(socket.clone(), udn(), http_port, network));
+        Ok(Self { socket, task })
+    }
+
+    async fn stop(self, http_port: u16) {
+        self.task
+            .abort();
+        ssdp::announce(&self.socket, &udn(), http_port, false).await;
+    }
+}
+
+/// Starts and stops SSDP as `EnableUPnP` is switched.
+pub fn spawn(ctx: AppContext) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let http_port = ctx
+            .config
+            .port;
+        let mut announcer: Option<Announcer> = None;
+        let mut ticker = tokio::time::interval(RECONCILE_INTERVAL);
+        loop {
+            ticker
+                .tick()
+                .await;
+            match (enabled(&ctx.db).await, announcer.take()) {
+                (true, None) => match Announcer::start(
+                    http_port,
+                    ctx.network
+                        .clone(),
+                ) {
+                    Ok(started) => {
+                        info!(udn = %udn(), "announcing DLNA media server");
+                        announcer = Some(started);
+                    }
+                    Err(e) => warn!("failed to start SSDP: {e}"),
+                },
+                (false, Some(running)) => {
+                    info!("DLNA media server disabled");
+                    running
+                        .stop(http_port)
+                        .await;
+                }
+                (_, running) => announcer = running,
+            }
+        }
+    })
+}
@@ -0,0 +1,215 @@
+//! What DLNA renderers play without help.
+//!
+//! Renderers do not say what they decode, so a profile is picked from the
+//! `User-Agent` of their control requests. Anything a profile does not list
+//! is sent through the progressive transcode instead.
+
+use remux_sdks::remux::{MediaSourceInfo, MediaStreamType};
+
+pub struct RendererProfile {
+    pub name: &'static str,
+    /// Lowercase substrings of the renderer's `User-Agent`.
+    user_agents: &'static [&'static str],
+    video_containers: &'static [&'static str],
+    video_codecs: &'static [&'static str],
+    audio_codecs: &'static [&'static str],
+    /// Containers of music played as is.
+    audio_containers: &'static [&'static str],
+    /// Container of video transcodes; MPEG-TS is the one every DLNA TV takes.
+    pub transcode_container: &'static str,
+}
+
+pub const PROFILES: &[RendererProfile] = &[
+    RendererProfile {
+        name: "Samsung",
+        user_agents: &["samsung", "sec_hhp"],
+        video_containers: &["mp4", "mkv", "ts", "avi"],
+        video_codecs: &["h264", "hevc", "mpeg2video", "mpeg4"],
+        audio_codecs: &["aac", "ac3", "eac3", "mp3", "dts"],
+        audio_containers: &["mp3", "flac", "m4a", "wav", "ogg"],
+        transcode_container: "ts",
+    },
+    RendererProfile {
+        name: "LG",
+        user_agents: &["lge", "webos", "lg-"],
+        video_containers: &["mp4", "mkv", "ts"],
+        video_codecs: &["h264", "hevc", "mpeg2video"],
+        audio_codecs: &["aac", "ac3", "eac3", "mp3"],
+        audio_containers: &["mp3", "flac", "m4a", "wav"],
+        transcode_container: "ts",
+    },
+    RendererProfile {
+        name: "Sony",
+        user_agents: &["sony", "bravia"],
+        video_containers: &["mp4", "ts", "mkv"],
+        video_codecs: &["h264", "mpeg2video"],
+        audio_codecs: &["aac", "ac3", "mp3"],
+        audio_containers: &["mp3", "m4a", "wav"],
+        transcode_container: "ts",
+    },
+    RendererProfile {
+        name: "Panasonic",
+        user_agents: &["panasonic", "viera"],
+        video_containers: &["mp4", "ts", "mkv"],
+        video_codecs: &["h264", "mpeg2video"],
+        audio_codecs: &["aac", "ac3", "mp3"],
+        audio_containers: &["mp3", "m4a", "wav"],
+        transcode_container: "ts",
+    },
+    RendererProfile {
+        name: "Denon/Marantz",
+        user_agents: &["denon", "marantz", "heos"],
+        video_containers: &[],
+        video_codecs: &[],
+        audio_codecs: &[],
+        audio_containers: &["mp3", "flac", "m4a", "wav"],
+        transcode_container: "ts",
+    },
+];
+
+/// For renderers no profile matches: what the DLNA guidelines make mandatory.
+pub const GENERIC: RendererProfile = RendererProfile {
+    name: "Generic",
+    user_agents: &[],
+    video_containers: &["mp4", "ts"],
+    video_codecs: &["h264"],
+    audio_codecs: &["aac", "mp3"],
+    audio_containers: &["mp3"],
+    transcode_container: "ts",
+};
+
+pub fn for_user_agent(user_agent: Option<&str>) -> &'static RendererProfile {
+    let Some(user_agent) = user_agent.map(str::to_ascii_lowercase) else {
+        return &GENERIC;
+    };
+    PROFILES
+        .iter()
+        .find(|p| {
+            p.user_agents
+                .iter()
+                .any(|ua| user_agent.contains(ua))
+        })
+        .unwrap_or(&GENERIC)
+}
+
+/// The short container name for an ffprobe format list like
+/// `mov,mp4,m4a,3gp,3g2,mj2`.
+pub fn container_name(container: &str) -> &str {
+    let container = container
+        .split(',')
+        .next()
+        .unwrap_or_default()
+        .trim();
+    match container {
+        "mov" => "mp4",
+        "matroska" => "mkv",
+        "mpegts" => "ts",
+        "ipod" => "m4a",
+        other => other,
+    }
+}
+
+pub fn mime_type(container: &str, is_video: bool) -> &'static str {
+    match (container_name(container), is_video) {
+        ("mp4", true) => "video/mp4",
+        ("mkv" | "webm", true) => "video/x-matroska",
+        ("ts", _) => "video/mpeg",
+        ("avi", _) => "video/avi",
+        ("mp3", _) => "audio/mpeg",
+        ("flac", _) => "audio/flac",
+        ("m4a" | "mp4", false) => "audio/mp4",
+        ("ogg", _) => "audio/ogg",
+        ("wav", _) => "audio/wav",
+        (_, true) => "video/mpeg",
+        (_, false) => "audio/mpeg",
+    }
+}
+
+impl RendererProfile {
+    /// Whether `source` plays as is. Sources with an unknown container or
+    /// codecs are transcoded rather than gambled on.
+    pub fn plays(&self, source: &MediaSourceInfo, is_video: bool) -> bool {
+        let Some(container) = source
+            .container
+            .as_deref()
+            .map(container_name)
+        else {
+            return false;
+        };
+        let codec = |kind: MediaStreamType| {
+            source
+                .media_streams
+                .iter()
+                .find(|s| s.type_ == Some(kind))
+                .and_then(|s| {
+                    s.codec
+                        .as_deref()
+                })
+                .map(str::to_ascii_lowercase)
+        };
+        if !is_video {
+            return self
+                .audio_containers
+                .contains(&container);
+        }
+        let audio_ok = codec(MediaStreamType::Audio).is_none_or(|c| {
+            self.audio_codecs
+                .contains(&c.as_str())
+        });
+        self.video_containers
+            .contains(&container)
+            && codec(MediaStreamType::Video).is_some_and(|c| {
+                self.video_codecs
+                    .contains(&c.as_str())
+            })
+            && audio_ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use remux_sdks::remux::MediaStream;
+
+    fn source(container: &str, video: &str, audio: &str) -> MediaSourceInfo {
+        MediaSourceInfo {
+            container: Some(container.into()),
+            media_streams: vec![
+                MediaStream {
+                    type_: Some(MediaStreamType::Video),
+                    codec: Some(video.into()),
+                    ..Default::default()
+                },
+                MediaStream {
+                    type_: Some(MediaStreamType::Audio),
+                    codec: Some(audio.into()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn renderers_are_matched_by_user_agent() {
+        assert_eq!(
+            for_user_agent(Some("SEC_HHP_[TV] Samsung Q7 Series/1.0")).name,
+            "Samsung"
+        );
+        assert_eq!(
+            for_user_agent(Some("Linux/4.4 UPnP/1.0 LGE WebOS TV")).name,
+            "LG"
+        );
+        assert_eq!(for_user_agent(Some("VLC/3.0")).name, "Generic");
+        assert_eq!(for_user_agent(None).name, "Generic");
+    }
+
+    #[test]
+    fn unsupported_codecs_are_transcoded() {
+        let samsung = for_user_agent(Some("Samsung"));
+        assert!(samsung.plays(&source("matroska,webm", "hevc", "eac3"), true));
+        assert!(!GENERIC.plays(&source("matroska,webm", "hevc", "eac3"), true));
+        assert!(GENERIC.plays(&source("mov,mp4,m4a,3gp,3g2,mj2", "h264", "aac"), true));
+        assert!(!GENERIC.plays(&MediaSourceInfo::default(), true));
+    }
+}
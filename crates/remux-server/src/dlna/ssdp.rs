@@ -0,0 +1,296 @@
+//! SSDP: how DLNA renderers find the server.
+//!
+//! Control points multicast `M-SEARCH` requests to 239.255.255.250:1900 and
+//! expect a unicast reply per matching search target. The server also
+//! multicasts `ssdp:alive` for each of its targets every few minutes, and
+//! `ssdp:byebye` when it goes away. Both point at the device description
+//! served over HTTP.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::Arc,
+    time::Duration,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tracing::{debug, warn};
+
+use super::{
+    CONNECTION_MANAGER_URN, CONTENT_DIRECTORY_URN, DESCRIPTION_PATH, MEDIA_SERVER_URN,
+};
+use crate::network::{NetworkPolicy, local_ip_for};
+
+pub const MULTICAST_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+pub const SSDP_PORT: u16 = 1900;
+
+/// Announcements are valid this long; they are repeated well within it.
+const MAX_AGE: Duration = Duration::from_secs(1800);
+pub const NOTIFY_INTERVAL: Duration = Duration::from_secs(600);
+
+fn server_header() -> String {
+    format!(
+        "{}/1.0 UPnP/1.0 remux/{}",
+        std::env::consts::OS,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// Every `(NT, USN)` pair the server advertises for the device `udn`.
+pub fn targets(udn: &str) -> Vec<(String, String)> {
+    let mut targets = vec![
+        (
+            "upnp:rootdevice".to_string(),
+            format!("{udn}::upnp:rootdevice"),
+        ),
+        (udn.to_string(), udn.to_string()),
+    ];
+    for urn in [
+        MEDIA_SERVER_URN,
+        CONTENT_DIRECTORY_URN,
+        CONNECTION_MANAGER_URN,
+    ] {
+        targets.push((urn.to_string(), format!("{udn}::{urn}")));
+    }
+    targets
+}
+
+/// The search target of an `M-SEARCH` discovery request.
+pub fn parse_search(message: &str) -> Option<String> {
+    let mut lines = message.lines();
+    if !lines
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("M-SEARCH * HTTP/1.1")
+    {
+        return None;
+    }
+    let mut discover = false;
+    let mut st = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name
+            .trim()
+            .to_ascii_uppercase()
+            .as_str()
+        {
+            "MAN" => discover = value.trim_matches('"') == "ssdp:discover",
+            "ST" => st = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    st.filter(|_| discover)
+}
+
+/// The targets a search for `st` is answered with.
+pub fn matching(st: &str, udn: &str) -> Vec<(String, String)> {
+    targets(udn)
+        .into_iter()
+        .filter(|(nt, _)| st == "ssdp:all" || nt.eq_ignore_ascii_case(st))
+        .collect()
+}
+
+fn http_date() -> String {
+    chrono::Utc::now()
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+pub fn search_response(location: &str, st: &str, usn: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\n\
+         CACHE-CONTROL: max-age={}\r\n\
+         DATE: {}\r\n\
+         EXT:\r\n\
+         LOCATION: {location}\r\n\
+         SERVER: {}\r\n\
+         ST: {st}\r\n\
+         USN: {usn}\r\n\
+         CONTENT-LENGTH: 0\r\n\r\n",
+        MAX_AGE.as_secs(),
+        http_date(),
+        server_header(),
+    )
+}
+
+pub fn notify(location: &str, nt: &str, usn: &str, alive: bool) -> String {
+    let nts = if alive { "ssdp:alive" } else { "ssdp:byebye" };
+    format!(
+        "NOTIFY * HTTP/1.1\r\n\
+         HOST: {MULTICAST_ADDR}:{SSDP_PORT}\r\n\
+         CACHE-CONTROL: max-age={}\r\n\
+         LOCATION: {location}\r\n\
+         NT: {nt}\r\n\
+         NTS: {nts}\r\n\
+         SERVER: {}\r\n\
+         USN: {usn}\r\n\r\n",
+        MAX_AGE.as_secs(),
+        server_header(),
+    )
+}
+
+fn location(ip: IpAddr, http_port: u16) -> String {
+    format!(
+        "http://{}{DESCRIPTION_PATH}",
+        SocketAddr::new(ip, http_port)
+    )
+}
+
+/// A UDP socket on `port` that receives SSDP multicast. Other UPnP software
+/// on the host usually holds 1900 too, so the address is shared. Joining
+/// the group fails on hosts without a multicast route; unicast searches
+/// still work then.
+pub fn bind(port: u16) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into())?;
+    if let Err(e) = socket.join_multicast_v4(&MULTICAST_ADDR, &Ipv4Addr::UNSPECIFIED) {
+        warn!("failed to join the SSDP multicast group: {e}");
+    }
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// Multicast an `ssdp:alive` (or `ssdp:byebye`) for every target.
+pub async fn announce(socket: &UdpSocket, udn: &str, http_port: u16, alive: bool) {
+    let group = SocketAddr::from((MULTICAST_ADDR, SSDP_PORT));
+    let ip = match local_ip_for(group) {
+        Ok(ip) => ip,
+        Err(e) => {
+            debug!("no route for SSDP announcements: {e}");
+            return;
+        }
+    };
+    let location = location(ip, http_port);
+    for (nt, usn) in targets(udn) {
+        let message = notify(&location, &nt, &usn, alive);
+        if let Err(e) = socket
+            .send_to(message.as_bytes(), group)
+            .await
+        {
+            debug!("failed to send SSDP announcement: {e}");
+            return;
+        }
+    }
+}
+
+/// Answer searches from the local network and repeat announcements until
+/// aborted.
+pub async fn serve(
+    socket: Arc<UdpSocket>,
+    udn: String,
+    http_port: u16,
+    network: Arc<arc_swap::ArcSwap<NetworkPolicy>>,
+) {
+    let mut buf = [0u8; 2048];
+    let mut ticker = tokio::time::interval(NOTIFY_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => announce(&socket, &udn, http_port, true).await,
+            received = socket.recv_from(&mut buf) => {
+                let (len, peer) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        debug!("SSDP receive failed: {e}");
+                        continue;
+                    }
+                };
+                if !network
+                    .load()
+                    .is_local(peer.ip())
+                {
+                    continue;
+                }
+                let Some(st) = std::str::from_utf8(&buf[..len])
+                    .ok()
+                    .and_then(parse_search)
+                else {
+                    continue;
+                };
+                let Ok(ip) = local_ip_for(peer) else {
+                    continue;
+                };
+                let location = location(ip, http_port);
+                for (nt, usn) in matching(&st, &udn) {
+                    let response = search_response(&location, &nt, &usn);
+                    if let Err(e) = socket.send_to(response.as_bytes(), peer).await {
+                        debug!(%peer, "failed to answer SSDP search: {e}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UDN: &str = "uuid:6ba7b810-9dad-11d1-80b4-00c04fd430c8";
+
+    fn search(st: &str) -> String {
+        format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: {st}\r\n\r\n"
+        )
+    }
+
+    #[test]
+    fn searches_are_matched_against_the_targets() {
+        assert_eq!(
+            parse_search(&search(MEDIA_SERVER_URN)).as_deref(),
+            Some(MEDIA_SERVER_URN)
+        );
+        assert_eq!(parse_search("NOTIFY * HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(matching("ssdp:all", UDN).len(), 5);
+        assert_eq!(
+            matching("upnp:rootdevice", UDN)[0].1,
+            format!("{UDN}::upnp:rootdevice")
+        );
+        assert!(
+            matching("urn:schemas-upnp-org:device:MediaRenderer:1", UDN).is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn loopback_searches_get_the_description_location() {
+        let socket = Arc::new(bind(0).unwrap());
+        let port = socket
+            .local_addr()
+            .unwrap()
+            .port();
+        let network = Arc::new(arc_swap::ArcSwap::from_pointee(NetworkPolicy::from(
+            &crate::api::NetworkConfiguration::default(),
+        )));
+        let server = tokio::spawn(serve(socket, UDN.to_string(), 8096, network));
+
+        let client = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        client
+            .send_to(search(MEDIA_SERVER_URN).as_bytes(), ("127.0.0.1", port))
+            .await
+            .unwrap();
+        let mut buf = [0u8; 2048];
+        let (len, _) =
+            tokio::time::timeout(Duration::from_secs(5), client.recv_from(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+        let response = std::str::from_utf8(&buf[..len]).unwrap();
+        server.abort();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(
+            response
+                .contains("LOCATION: http://127.0.0.1:8096/dlna/description.xml\r\n")
+        );
+        assert!(response.contains(&format!("USN: {UDN}::{MEDIA_SERVER_URN}\r\n")));
+    }
+}
@@ -0,0 +1,304 @@
+//! Offline downloads: items transcoded to a file ahead of time.
+//!
+//! A user asks for an item, or a whole series, season, album or artist, in a
+//! chosen [`DownloadProfile`]. Every playable item underneath becomes a queued
+//! [`DownloadJob`]; the `ProcessDownloads` task transcodes them one at a time
+//! through the progressive ffmpeg pipeline into the downloads folder, and the
+//! finished file is served until it expires.
+
+use anyhow::{Result, anyhow, bail};
+use std::path::PathBuf;
+use tracing::info;
+
+use crate::{
+    AppContext, Config,
+    db::{self, DownloadJob, DownloadProfile, Media, MediaKind},
+    playback::{
+        audio::{self, AudioFormat, AudioStreamParams, AudioTranscode},
+        engine::{self, ProgressiveTranscodeParams},
+    },
+    services::StreamService,
+};
+
+/// How long a finished download is kept before the task deletes it.
+pub const RETENTION_DAYS: i64 = 7;
+
+/// The key of the task that works through the queue.
+pub const TASK_KEY: &str = "ProcessDownloads";
+
+/// Where finished and in-progress downloads are written.
+pub fn downloads_dir(config: &Config) -> PathBuf {
+    config
+        .data_dir
+        .join("downloads")
+}
+
+/// The job's file on disk: its id plus the extension of its download name.
+pub fn job_path(config: &Config, job: &DownloadJob) -> PathBuf {
+    let ext = std::path::Path::new(&job.file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("bin");
+    downloads_dir(config).join(format!("{}.{ext}", job.id))
+}
+
+/// The playable items a download of `media` covers, in playback order.
+pub async fn targets(db: &sqlx::SqlitePool, media: &Media) -> Result<Vec<Media>> {
+    let mut items = match media.kind {
+        MediaKind::Movie
+        | MediaKind::Episode
+        | MediaKind::Track
+        | MediaKind::Recording => {
+            vec![media.clone()]
+        }
+        MediaKind::Series
+        | MediaKind::Season
+        | MediaKind::Album
+        | MediaKind::Artist => {
+            let ids: Vec<uuid::Uuid> = sqlx::query_scalar(
+                "SELECT id FROM media \
+                 WHERE (parent_id = ?1 OR grandparent_id = ?1) \
+                   AND kind IN ('episode', 'track') \
+                 ORDER BY parent_idx, idx",
+            )
+            .bind(media.id)
+            .fetch_all(db)
+            .await?;
+            Media::get_by_ids(db, &ids).await?
+        }
+        _ => bail!("{} items cannot be downloaded", media.kind),
+    };
+    Media::preload_parents(db, &mut items).await;
+    Ok(items)
+}
+
+/// The name a download of `media` in `profile` is offered under.
+///
+/// Fails when the profile cannot produce a file for this kind of item.
+pub fn file_name(media: &Media, profile: &DownloadProfile) -> Result<String> {
+    let ext = if media.is_track() {
+        AudioFormat::parse(&profile.audio_codec)
+            .ok_or_else(|| anyhow!("unsupported audio codec: {}", profile.audio_codec))?
+            .container()
+    } else {
+        match (
+            profile
+                .container
+                .as_str(),
+            profile
+                .video_codec
+                .as_str(),
+        ) {
+            // The engine promotes stream-copied MP4 to Matroska.
+            ("mp4", "copy") | ("mkv", _) => "mkv",
+            ("mp4", _) => "mp4",
+            (other, _) => bail!("unsupported container: {other}"),
+        }
+    };
+    let name: String = media
+        .full_title()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.' | '(' | ')') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_matches(|c: char| c == ' ' || c == '.');
+    let name = if name.is_empty() { "Download" } else { name };
+    Ok(format!("{name}.{ext}"))
+}
+
+/// Transcode `job` into its file, calling `on_progress` with 0-100 as ffmpeg
+/// works through the item. Returns the size of the finished file; a failed
+/// run leaves no file behind.
+pub async fn transcode(
+    ctx: &AppContext,
+    job: &DownloadJob,
+    mut on_progress: impl FnMut(f64),
+) -> Result<u64> {
+    let media =
+        StreamService::lookup(ctx, job.media_id, None, None, Some(job.user_id)).await?;
+    let si = media
+        .stream_info
+        .clone()
+        .ok_or_else(|| anyhow!("no stream for {}", media.title))?;
+    let url = si
+        .descriptor
+        .server_input(
+            media.id,
+            ctx.config
+                .port,
+        );
+    let encoding_opts = db::Settings::get_encoding_config(&ctx.db)
+        .await
+        .unwrap_or_default();
+    let profile = &job.profile;
+    let probe = media
+        .probe_data
+        .as_ref();
+
+    let (args, env_overrides) = if media.is_track() {
+        let format = AudioFormat::parse(&profile.audio_codec).ok_or_else(|| {
+            anyhow!("unsupported audio codec: {}", profile.audio_codec)
+        })?;
+        let params = AudioStreamParams {
+            input_url: url,
+            start_time_ticks: None,
+            audio_stream_index: None,
+            audio: AudioTranscode {
+                format,
+                bitrate: format.bitrate(profile.audio_bitrate),
+                channels: profile.audio_channels,
+                sample_rate: None,
+                replay_gain: encoding_opts
+                    .apply_replay_gain
+                    .unwrap_or(false)
+                    .then_some(media.normalization_gain)
+                    .flatten(),
+                normalize_loudness: encoding_opts
+                    .normalize_audio_loudness
+                    .unwrap_or(false),
+            },
+        };
+        (audio::build_progressive_args(&params), Vec::new())
+    } else {
+        let source_video = probe.and_then(|p| p.video_stream());
+        let params = ProgressiveTranscodeParams {
+            container: profile
+                .container
+                .clone(),
+            video_codec: profile
+                .video_codec
+                .clone(),
+            audio_codec: profile
+                .audio_codec
+                .clone(),
+            max_height: profile.max_height,
+            video_bitrate: match (
+                source_video.and_then(|s| s.bit_rate),
+                profile.video_bitrate,
+            ) {
+                (Some(source), Some(cap)) => Some((source as u32).min(cap)),
+                (source, cap) => cap.or(source.map(|b| b as u32)),
+            },
+            audio_bitrate: profile.audio_bitrate,
+            audio_channels: profile.audio_channels,
+            source_video_codec: source_video.and_then(|s| {
+                s.codec
+                    .clone()
+            }),
+            source_audio_codec: probe
+                .and_then(|p| p.audio_stream())
+                .and_then(|s| {
+                    s.codec
+                        .clone()
+                }),
+            source_video_range_type: source_video.and_then(|s| s.video_range_type),
+            ..ProgressiveTranscodeParams::new(url, &encoding_opts)
+        };
+        let env_overrides = params
+            .accelerator
+            .env_overrides();
+        (engine::build_progressive_args(&params), env_overrides)
+    };
+
+    let duration = probe
+        .and_then(|p| p.run_time_ticks)
+        .map(|t| t as f64 / 10_000_000.0)
+        .or(media
+            .runtime
+            .map(|r| r as f64))
+        .filter(|d| *d > 0.0);
+
+    let path = job_path(&ctx.config, job);
+    tokio::fs::create_dir_all(downloads_dir(&ctx.config)).await?;
+    info!(job = %job.id, title = %media.title, "transcoding download");
+    let result = engine::transcode_to_file(args, env_overrides, &path, |secs| {
+        if let Some(duration) = duration {
+            on_progress((secs / duration * 100.0).clamp(0.0, 100.0));
+        }
+    })
+    .await;
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(e);
+    }
+    Ok(tokio::fs::metadata(&path)
+        .await?
+        .len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movie(title: &str) -> Media {
+        Media {
+            title: title.into(),
+            kind: MediaKind::Movie,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn file_name_follows_the_profile_container() {
+        let profile = DownloadProfile::default();
+        assert_eq!(
+            file_name(&movie("Alien: Covenant"), &profile).unwrap(),
+            "Alien_ Covenant.mp4"
+        );
+
+        let copy = DownloadProfile {
+            video_codec: "copy".into(),
+            ..Default::default()
+        };
+        assert_eq!(file_name(&movie("Alien"), &copy).unwrap(), "Alien.mkv");
+
+        let webm = DownloadProfile {
+            container: "webm".into(),
+            ..Default::default()
+        };
+        assert!(file_name(&movie("Alien"), &webm).is_err());
+    }
+
+    #[test]
+    fn music_takes_its_extension_from_the_audio_codec() {
+        let track = Media {
+            title: "Song".into(),
+            kind: MediaKind::Track,
+            ..Default::default()
+        };
+        let opus = DownloadProfile {
+            audio_codec: "opus".into(),
+            ..Default::default()
+        };
+        assert_eq!(file_name(&track, &opus).unwrap(), "Song.ogg");
+
+        let copy = DownloadProfile {
+            audio_codec: "copy".into(),
+            ..Default::default()
+        };
+        assert!(file_name(&track, &copy).is_err());
+    }
+
+    #[test]
+    fn job_files_are_named_by_id() {
+        let config = Config {
+            data_dir: PathBuf::from("/srv/remux"),
+            ..Default::default()
+        };
+        let job = DownloadJob::new(
+            uuid::Uuid::nil(),
+            uuid::Uuid::nil(),
+            DownloadProfile::default(),
+            "Alien.mp4".into(),
+        );
+        assert_eq!(
+            job_path(&config, &job),
+            PathBuf::from(format!("/srv/remux/downloads/{}.mp4", job.id))
+        );
+    }
+}
@@ -0,0 +1,591 @@
+//! Live TV DVR: records IPTV channels on a schedule.
+//!
+//! Timers come from EPG programs, either one at a time or through series
+//! rules that match future airings by title. A scheduler loop starts each
+//! timer's recording when its padded window opens: ffmpeg copies the channel
+//! stream into an MPEG-TS file under the recordings folder, and the file is
+//! exposed as a `recording` media item that plays like any local video.
+
+use anyhow::{Result, anyhow};
+use chrono::{NaiveDateTime, Utc};
+use dashmap::DashMap;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::Mutex, task::JoinHandle};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    AppContext, Config,
+    common::{HideConsole, get_uuid},
+    db::{Media, MediaKind, SeriesTimer, Timer, TimerStatus},
+    stream::{StreamDescriptor, StreamInfo},
+};
+
+/// How often the scheduler looks for timers to start.
+pub const SCHEDULER_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Slack on top of a recording's length before ffmpeg is killed outright.
+const RECORDING_GRACE: Duration = Duration::from_secs(60);
+
+fn ffmpeg_bin() -> String {
+    std::env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".into())
+}
+
+/// Tracks running recordings so they can be stopped early. Cheap to clone;
+/// clones share state.
+#[derive(Clone, Default)]
+pub struct DvrManager {
+    active: Arc<DashMap<Uuid, CancellationToken>>,
+    /// Serialises scheduler passes so a timer is never started twice.
+    tick_lock: Arc<Mutex<()>>,
+}
+
+impl DvrManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_recording(&self, timer_id: &Uuid) -> bool {
+        self.active
+            .contains_key(timer_id)
+    }
+
+    /// Stops a running recording; what was written so far is kept. Returns
+    /// whether the timer was recording.
+    pub fn stop(&self, timer_id: &Uuid) -> bool {
+        match self
+            .active
+            .get(timer_id)
+        {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Where recordings are written.
+pub fn recordings_dir(config: &Config) -> PathBuf {
+    config
+        .recordings_dir
+        .as_deref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            config
+                .data_dir
+                .join("recordings")
+        })
+}
+
+/// Runs [`tick`] every `interval`. Recordings cut short by the previous run
+/// are marked as failed first.
+pub fn spawn_scheduler(ctx: AppContext, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        match Timer::fail_interrupted(&ctx.db).await {
+            Ok(0) => {}
+            Ok(n) => warn!(count = n, "recordings were interrupted by a restart"),
+            Err(e) => warn!("failed to reset interrupted recordings: {e:#}"),
+        }
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker
+                .tick()
+                .await;
+            if let Err(e) = tick(&ctx, Utc::now().naive_utc()).await {
+                warn!("dvr scheduler pass failed: {e:#}");
+            }
+        }
+    })
+}
+
+/// One scheduler pass: expand series rules, start due timers, fail the ones
+/// whose airing ended unrecorded, and prune old series recordings.
+pub async fn tick(ctx: &AppContext, now: NaiveDateTime) -> Result<()> {
+    let _guard = ctx
+        .dvr
+        .tick_lock
+        .lock()
+        .await;
+
+    let added = expand_series_timers(ctx, now).await?;
+    if added > 0 {
+        info!(count = added, "scheduled timers from series rules");
+    }
+
+    for timer in Timer::due(&ctx.db, now).await? {
+        if ctx
+            .dvr
+            .is_recording(&timer.id)
+        {
+            continue;
+        }
+        if timer.record_end() <= now {
+            Timer::set_status(
+                &ctx.db,
+                &timer.id,
+                TimerStatus::Error,
+                Some("the airing ended before recording could start"),
+            )
+            .await?;
+            continue;
+        }
+        if let Err(e) = start_recording(ctx, &timer, now).await {
+            warn!(timer_id = %timer.id, "failed to start recording: {e:#}");
+            Timer::set_status(
+                &ctx.db,
+                &timer.id,
+                TimerStatus::Error,
+                Some(&format!("{e:#}")),
+            )
+            .await?;
+        }
+    }
+
+    for series in SeriesTimer::get_all(&ctx.db).await? {
+        if series.keep_up_to > 0 {
+            prune_series(ctx, &series).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Upcoming airings a series rule matches: same title, ignoring case, on the
+/// rule's channel unless it records from any channel.
+pub async fn series_matches(
+    db: &sqlx::SqlitePool,
+    series: &SeriesTimer,
+    now: NaiveDateTime,
+) -> Result<Vec<Media>> {
+    let mut qb = sqlx::QueryBuilder::new(
+        "SELECT * FROM media WHERE kind = 'tv_program' AND lower(title) = lower(",
+    );
+    qb.push_bind(&series.name);
+    qb.push(") AND live_end > ");
+    qb.push_bind(now);
+    if !series.record_any_channel {
+        qb.push(" AND parent_id = ");
+        qb.push_bind(series.channel_id);
+    }
+    qb.push(" ORDER BY live_start");
+    Ok(qb
+        .build_query_as::<Media>()
+        .fetch_all(db)
+        .await?)
+}
+
+/// Schedules a timer for every matching airing that has none. Returns how
+/// many were added.
+pub async fn expand_series_timers(
+    ctx: &AppContext,
+    now: NaiveDateTime,
+) -> Result<usize> {
+    let mut added = 0;
+    for series in SeriesTimer::get_all(&ctx.db).await? {
+        for program in series_matches(&ctx.db, &series, now).await? {
+            let Some(mut timer) = Timer::from_program(
+                &program,
+                series.pre_padding_seconds,
+                series.post_padding_seconds,
+            ) else {
+                continue;
+            };
+            timer.series_timer_id = Some(series.id);
+            if timer
+                .insert_if_absent(&ctx.db)
+                .await?
+            {
+                added += 1;
+            }
+        }
+    }
+    Ok(added)
+}
+
+/// `{name}_{yyyymmdd_hhmm}.ts`, with characters that are unsafe in file names
+/// replaced.
+pub fn recording_file_name(timer: &Timer) -> String {
+    let name: String = timer
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.' | '(' | ')') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_matches(|c: char| c == ' ' || c == '.');
+    let name = if name.is_empty() { "Recording" } else { name };
+    format!(
+        "{}_{}.ts",
+        name,
+        timer
+            .start_date
+            .format("%Y%m%d_%H%M")
+    )
+}
+
+async fn start_recording(
+    ctx: &AppContext,
+    timer: &Timer,
+    now: NaiveDateTime,
+) -> Result<()> {
+    let channel = Media::get_by_id(&ctx.db, &timer.channel_id)
+        .await?
+        .ok_or_else(|| anyhow!("channel {} no longer exists", timer.channel_id))?;
+    let input = channel
+        .stream_info
+        .as_ref()
+        .map(|si| {
+            si.descriptor
+                .server_input(
+                    channel.id,
+                    ctx.config
+                        .port,
+                )
+        })
+        .ok_or_else(|| anyhow!("channel {} has no stream", channel.title))?;
+    let is_http = matches!(
+        channel
+            .stream_info
+            .as_ref()
+            .map(|si| &si.descriptor),
+        Some(StreamDescriptor::Http { .. })
+    );
+
+    let dir = recordings_dir(&ctx.config);
+    tokio::fs::create_dir_all(&dir).await?;
+    let mut path = dir.join(recording_file_name(timer));
+    if tokio::fs::try_exists(&path)
+        .await
+        .unwrap_or(false)
+    {
+        path = path.with_file_name(format!(
+            "{}_{}.ts",
+            path.file_stem()
+                .map(|s| {
+                    s.to_string_lossy()
+                        .into_owned()
+                })
+                .unwrap_or_default(),
+            timer
+                .id
+                .simple()
+        ));
+    }
+
+    // The guide row may be gone by now; the timer carries what it needs.
+    let program = match timer.program_id {
+        Some(id) => Media::get_by_id(&ctx.db, &id).await?,
+        None => None,
+    };
+    let mut recording = Media {
+        id: get_uuid(),
+        title: timer
+            .name
+            .clone(),
+        kind: MediaKind::Recording,
+        description: timer
+            .overview
+            .clone(),
+        released_at: Some(timer.start_date),
+        live_start: Some(timer.start_date),
+        live_end: Some(timer.end_date),
+        program_kind: program
+            .as_ref()
+            .and_then(|p| {
+                p.program_kind
+                    .clone()
+            }),
+        images: program
+            .as_ref()
+            .map(|p| {
+                p.images
+                    .clone()
+            })
+            .unwrap_or_default(),
+        stream_info: Some(StreamInfo {
+            descriptor: StreamDescriptor::Local(path.clone()),
+            filename: path
+                .file_name()
+                .map(|n| {
+                    n.to_string_lossy()
+                        .into_owned()
+                }),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    recording
+        .save(&ctx.db)
+        .await?;
+    Timer::set_recording(&ctx.db, &timer.id, Some(recording.id)).await?;
+    Timer::set_status(&ctx.db, &timer.id, TimerStatus::InProgress, None).await?;
+
+    let length = (timer.record_end() - now.max(timer.record_start()))
+        .to_std()
+        .unwrap_or_default();
+    info!(
+        timer_id = %timer.id,
+        channel = %channel.title,
+        path = %path.display(),
+        secs = length.as_secs(),
+        "recording started"
+    );
+
+    let token = CancellationToken::new();
+    ctx.dvr
+        .active
+        .insert(timer.id, token.clone());
+    let ctx = ctx.clone();
+    let timer_id = timer.id;
+    let recording_id = recording.id;
+    tokio::spawn(async move {
+        let result = record(&input, is_http, &path, length, &token).await;
+        ctx.dvr
+            .active
+            .remove(&timer_id);
+        if let Err(e) = finish_recording(
+            &ctx,
+            timer_id,
+            recording_id,
+            &path,
+            result,
+            token.is_cancelled(),
+        )
+        .await
+        {
+            warn!(%timer_id, "failed to finalise recording: {e:#}");
+        }
+    });
+    Ok(())
+}
+
+/// Copies `input` into `path` for `length`, or until cancelled.
+async fn record(
+    input: &str,
+    is_http: bool,
+    path: &Path,
+    length: Duration,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let mut cmd = tokio::process::Command::new(ffmpeg_bin());
+    cmd.hide_console();
+    cmd.kill_on_drop(true);
+    cmd.args(["-nostdin", "-hide_banner", "-loglevel", "error", "-y"]);
+    if is_http {
+        // IPTV servers drop long-lived connections; pick the stream back up
+        // instead of ending the recording early.
+        cmd.args([
+            "-reconnect",
+            "1",
+            "-reconnect_streamed",
+            "1",
+            "-reconnect_delay_max",
+            "10",
+        ]);
+    }
+    cmd.arg("-i")
+        .arg(input)
+        .args(["-map", "0:v?", "-map", "0:a?", "-c", "copy", "-t"])
+        .arg(format!("{:.3}", length.as_secs_f64()))
+        .args(["-f", "mpegts"])
+        .arg(path);
+    cmd.stdout(std::process::Stdio::null());
+    cmd.stderr(std::process::Stdio::piped());
+
+    let child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("failed to run ffmpeg: {e}"))?;
+    let output = tokio::select! {
+        output = tokio::time::timeout(length + RECORDING_GRACE, child.wait_with_output()) => output,
+        // Dropping the child kills ffmpeg; the file keeps what was written.
+        _ = cancel.cancelled() => return Ok(()),
+    };
+    let output = output
+        .map_err(|_| anyhow!("ffmpeg did not stop after the recording window"))?
+        .map_err(|e| anyhow!("failed to run ffmpeg: {e}"))?;
+    if !output
+        .status
+        .success()
+    {
+        return Err(anyhow!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Records the outcome: probes whatever was written, or drops the recording
+/// item when nothing was.
+async fn finish_recording(
+    ctx: &AppContext,
+    timer_id: Uuid,
+    recording_id: Uuid,
+    path: &Path,
+    result: Result<()>,
+    cancelled: bool,
+) -> Result<()> {
+    let written = tokio::fs::metadata(path)
+        .await
+        .map(|m| m.len() > 0)
+        .unwrap_or(false);
+    if written {
+        let url = path
+            .to_string_lossy()
+            .into_owned();
+        match tokio::task::spawn_blocking(move || {
+            crate::playback::probe::probe_media(&url)
+        })
+        .await?
+        {
+            Ok((source_info, _segments)) => {
+                if let Some(mut recording) =
+                    Media::get_by_id(&ctx.db, &recording_id).await?
+                {
+                    recording.runtime = source_info
+                        .run_time_ticks
+                        .map(|t| t / 10_000_000);
+                    recording.probe_data = Some(source_info);
+                    recording
+                        .save(&ctx.db)
+                        .await?;
+                }
+            }
+            Err(e) => {
+                debug!(path = %path.display(), "failed to probe recording: {e:#}")
+            }
+        }
+    } else {
+        Media::delete(&ctx.db, &recording_id).await?;
+        Timer::set_recording(&ctx.db, &timer_id, None).await?;
+    }
+
+    let (status, error) = match (&result, cancelled) {
+        (_, true) => (TimerStatus::Cancelled, None),
+        (Ok(()), false) if written => (TimerStatus::Completed, None),
+        (Ok(()), false) => (
+            TimerStatus::Error,
+            Some("the channel produced no data".to_string()),
+        ),
+        (Err(e), false) => (TimerStatus::Error, Some(format!("{e:#}"))),
+    };
+    info!(%timer_id, %status, "recording finished");
+    Timer::set_status(&ctx.db, &timer_id, status, error.as_deref()).await
+}
+
+/// Deletes a recording's file, media item and timer.
+pub async fn delete_recording(ctx: &AppContext, recording: &Media) -> Result<()> {
+    if let Some(timer) = Timer::get_by_recording(&ctx.db, &recording.id).await? {
+        ctx.dvr
+            .stop(&timer.id);
+        Timer::delete(&ctx.db, &timer.id).await?;
+    }
+    if let Some(StreamDescriptor::Local(path)) = recording
+        .stream_info
+        .as_ref()
+        .map(|si| &si.descriptor)
+    {
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+    }
+    Media::delete(&ctx.db, &recording.id).await
+}
+
+/// Keeps only a series rule's newest `keep_up_to` completed recordings.
+async fn prune_series(ctx: &AppContext, series: &SeriesTimer) -> Result<()> {
+    let completed = Timer::completed_for_series(&ctx.db, &series.id).await?;
+    for timer in completed
+        .iter()
+        .skip(series.keep_up_to as usize)
+    {
+        let Some(recording_id) = timer.recording_id else {
+            continue;
+        };
+        match Media::get_by_id(&ctx.db, &recording_id).await? {
+            Some(recording) => {
+                info!(series = %series.name, id = %recording_id, "removing old recording");
+                delete_recording(ctx, &recording).await?;
+            }
+            None => {
+                Timer::delete(&ctx.db, &timer.id).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as ChronoDuration;
+
+    fn timer(name: &str) -> Timer {
+        let start = chrono::NaiveDate::from_ymd_opt(2026, 3, 1)
+            .unwrap()
+            .and_hms_opt(20, 15, 0)
+            .unwrap();
+        Timer {
+            id: Uuid::new_v4(),
+            series_timer_id: None,
+            channel_id: Uuid::new_v4(),
+            program_id: None,
+            name: name.to_string(),
+            overview: None,
+            start_date: start,
+            end_date: start + ChronoDuration::minutes(45),
+            pre_padding_seconds: 60,
+            post_padding_seconds: 300,
+            status: TimerStatus::New,
+            recording_id: None,
+            last_error: None,
+            created_at: start,
+            updated_at: start,
+        }
+    }
+
+    #[test]
+    fn padding_widens_the_recording_window() {
+        let t = timer("News");
+        assert_eq!(t.record_start(), t.start_date - ChronoDuration::seconds(60));
+        assert_eq!(t.record_end(), t.end_date + ChronoDuration::seconds(300));
+    }
+
+    #[test]
+    fn file_name_is_sanitised_and_dated() {
+        assert_eq!(
+            recording_file_name(&timer("Doctor Who: The Return?")),
+            "Doctor Who_ The Return__20260301_2015.ts"
+        );
+        assert_eq!(recording_file_name(&timer("../")), "__20260301_2015.ts");
+        assert_eq!(
+            recording_file_name(&timer("")),
+            "Recording_20260301_2015.ts"
+        );
+    }
+
+    #[test]
+    fn recordings_dir_defaults_under_data_dir() {
+        let config = Config {
+            data_dir: PathBuf::from("/srv/remux"),
+            recordings_dir: None,
+            ..Default::default()
+        };
+        assert_eq!(
+            recordings_dir(&config),
+            PathBuf::from("/srv/remux/recordings")
+        );
+    }
+}
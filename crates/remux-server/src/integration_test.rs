@@ -1,4 +1,7 @@
+use std::net::SocketAddr;
+
 use anyhow::Result;
+use axum::{Router, extract::connect_info::MockConnectInfo};
 use axum_test::TestServer;
 use chrono::Utc;
 use http::header::HeaderValue;
@@ -39,8 +42,17 @@ impl Drop for TestGuard {
 /// and returns the server alongside a [`TestGuard`].
 pub async fn new_test_server_with_config(
     config: Config,
+) -> Result<(TestServer, TestGuard)> {
+    seeded_test_server(config, |app| app).await
+}
+
+/// [`new_test_server_with_config`] with `wrap` applied to the app first.
+async fn seeded_test_server(
+    config: Config,
+    wrap: impl FnOnce(Router) -> Router,
 ) -> Result<(TestServer, TestGuard)> {
     let (app, ctx) = init_app_with_ctx(config).await?;
+    let app = wrap(app);
 
     let server = TestServer::builder()
         .save_cookies()
@@ -65,13 +77,16 @@ pub async fn new_test_server_with_config(
 /// "test"/"test", and returns the server alongside a [`TestGuard`] (which
 /// carries the `AppContext` and shuts down background services on drop).
 pub async fn new_test_server() -> Result<(TestServer, TestGuard)> {
-    new_test_server_with_config(Config {
+    new_test_server_with_config(test_config()).await
+}
+
+fn test_config() -> Config {
+    Config {
         database_url: Some("sqlite::memory:".into()),
         torrent_http_port: None, // OS picks a free ephemeral port
         disable_dht: true,       // no DHT needed in tests; avoids socket conflicts
         ..Default::default()
-    })
-    .await
+    }
 }
 
 /// Spins up a test server and authenticates as the seeded "test" user.
@@ -80,7 +95,37 @@ pub async fn authenticated_server() -> (TestServer, TestGuard, String) {
     let (server, guard) = new_test_server()
         .await
         .unwrap();
+    sign_in(server, guard).await
+}
 
+/// Like [`authenticated_server`], with every request arriving from `peer`.
+pub async fn authenticated_server_from(
+    peer: SocketAddr,
+) -> (TestServer, TestGuard, String) {
+    // The mock transport has no socket; this stands in for its peer address.
+    let (server, guard) =
+        seeded_test_server(test_config(), |app| app.layer(MockConnectInfo(peer)))
+            .await
+            .unwrap();
+    sign_in(server, guard).await
+}
+
+/// Like [`authenticated_server`], hosted under `base_url` as configured in
+/// the network settings.
+pub async fn authenticated_server_under(
+    base_url: &str,
+) -> (TestServer, TestGuard, String) {
+    let (server, guard) =
+        seeded_test_server(test_config(), |app| crate::base_url::nest(app, base_url))
+            .await
+            .unwrap();
+    sign_in(server, guard).await
+}
+
+async fn sign_in(
+    server: TestServer,
+    guard: TestGuard,
+) -> (TestServer, TestGuard, String) {
     let resp = server
         .post("/users/authenticatebyname")
         .add_header(
@@ -98,6 +143,210 @@ pub async fn authenticated_server() -> (TestServer, TestGuard, String) {
     (server, guard, token)
 }
 
+/// Creates a non-admin user through the API, signed in as the admin behind
+/// `admin_token`, and applies `policy` (a `UserPolicy` JSON body) to it.
+/// Returns the new user's id.
+pub async fn create_user_with_policy(
+    server: &TestServer,
+    admin_token: &str,
+    name: &str,
+    password: &str,
+    policy: serde_json::Value,
+) -> String {
+    let admin_auth =
+        HeaderValue::from_str(&auth_header_with_token(admin_token)).unwrap();
+    let user: serde_json::Value = server
+        .post("/users/new")
+        .add_header(http::header::AUTHORIZATION, admin_auth.clone())
+        .json(&json!({ "Name": name, "Password": password }))
+        .await
+        .json();
+    let user_id = user["Id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    server
+        .post(&format!("/users/{user_id}/policy"))
+        .add_header(http::header::AUTHORIZATION, admin_auth)
+        .json(&policy)
+        .await
+        .assert_status(http::StatusCode::NO_CONTENT);
+    user_id
+}
+
+/// An entry served by [`LdapStandIn`].
+pub struct LdapEntry {
+    pub dn: String,
+    pub password: String,
+    pub attributes: Vec<(String, Vec<String>)>,
+}
+
+impl LdapEntry {
+    pub fn new(dn: &str, password: &str, attributes: &[(&str, &[&str])]) -> Self {
+        Self {
+            dn: dn.to_string(),
+            password: password.to_string(),
+            attributes: attributes
+                .iter()
+                .map(|(name, values)| {
+                    (
+                        name.to_string(),
+                        values
+                            .iter()
+                            .map(|v| v.to_string())
+                            .collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn values(&self, attribute: &str) -> &[String] {
+        self.attributes
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(attribute))
+            .map(|(_, values)| values.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn matches(&self, filter: &ldap3_proto::proto::LdapFilter) -> bool {
+        use ldap3_proto::proto::LdapFilter;
+        match filter {
+            LdapFilter::And(filters) => filters
+                .iter()
+                .all(|f| self.matches(f)),
+            LdapFilter::Or(filters) => filters
+                .iter()
+                .any(|f| self.matches(f)),
+            LdapFilter::Not(filter) => !self.matches(filter),
+            LdapFilter::Equality(attribute, value) => self
+                .values(attribute)
+                .iter()
+                .any(|v| v.eq_ignore_ascii_case(value)),
+            LdapFilter::Present(attribute) => !self
+                .values(attribute)
+                .is_empty(),
+            _ => false,
+        }
+    }
+}
+
+/// An in-process LDAP directory on a local port. It answers simple binds
+/// against its entries' passwords and subtree searches with equality and
+/// presence filters, which is all the sign-in flow uses.
+pub struct LdapStandIn {
+    pub url: String,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl LdapStandIn {
+    pub async fn start(entries: Vec<LdapEntry>) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let url = format!(
+            "ldap://{}",
+            listener
+                .local_addr()
+                .unwrap()
+        );
+        let entries = std::sync::Arc::new(entries);
+        let task = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener
+                .accept()
+                .await
+            {
+                tokio::spawn(serve_ldap(socket, entries.clone()));
+            }
+        });
+        Self { url, task }
+    }
+}
+
+impl Drop for LdapStandIn {
+    fn drop(&mut self) {
+        self.task
+            .abort();
+    }
+}
+
+async fn serve_ldap(
+    socket: tokio::net::TcpStream,
+    entries: std::sync::Arc<Vec<LdapEntry>>,
+) {
+    use futures::{SinkExt, StreamExt};
+    use ldap3_proto::{
+        LdapCodec,
+        proto::{LdapPartialAttribute, LdapSearchResultEntry},
+        simple::ServerOps,
+    };
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    let (reader, writer) = tokio::io::split(socket);
+    let mut requests = FramedRead::new(reader, LdapCodec::default());
+    let mut responses = FramedWrite::new(writer, LdapCodec::default());
+    while let Some(Ok(message)) = requests
+        .next()
+        .await
+    {
+        let replies = match ServerOps::try_from(message) {
+            Ok(ServerOps::SimpleBind(bind)) => {
+                let known = entries
+                    .iter()
+                    .any(|e| e.dn == bind.dn && e.password == bind.pw);
+                vec![if known {
+                    bind.gen_success()
+                } else {
+                    bind.gen_invalid_cred()
+                }]
+            }
+            Ok(ServerOps::Search(search)) => {
+                let mut replies: Vec<_> = entries
+                    .iter()
+                    .filter(|e| {
+                        e.dn.ends_with(&search.base) && e.matches(&search.filter)
+                    })
+                    .map(|e| {
+                        search.gen_result_entry(LdapSearchResultEntry {
+                            dn: e
+                                .dn
+                                .clone(),
+                            attributes: e
+                                .attributes
+                                .iter()
+                                .map(|(atype, vals)| LdapPartialAttribute {
+                                    atype: atype.clone(),
+                                    vals: vals
+                                        .iter()
+                                        .map(|v| {
+                                            v.as_bytes()
+                                                .to_vec()
+                                        })
+                                        .collect(),
+                                })
+                                .collect(),
+                        })
+                    })
+                    .collect();
+                replies.push(search.gen_success());
+                replies
+            }
+            // Unbind, or anything the sign-in flow never sends.
+            _ => return,
+        };
+        for reply in replies {
+            if responses
+                .send(reply)
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
 /// Inserts a test video source with pre-populated probe data (container="mp4",
 /// bitrate=8_000_000, 1920×1080 h264). No ffprobe or network needed — the
 /// fields are set directly so playbackinfo tests behave identically in CI and
@@ -247,7 +496,8 @@ pub fn assert_api_keys_are_real(value: &serde_json::Value, token: &str) {
 /// say "stopped at 95%" without the threshold turning on a rounding.
 pub const MOVIE_RUNTIME_SECONDS: i64 = 6_000;
 
-fn stable_id(kind: db::MediaKind, external_ids: &db::ExternalIds) -> Uuid {
+/// The id a movie or series row must carry for its external ids to validate.
+pub fn stable_id(kind: db::MediaKind, external_ids: &db::ExternalIds) -> Uuid {
     Uuid::from(&db::MediaIdRaw {
         kind,
         external_ids: external_ids.clone(),
@@ -330,6 +580,58 @@ pub async fn seed_episode(ctx: &AppContext) -> db::Media {
     episode
 }
 
+/// A track filed under its album and artist the way the Deezer tree does it:
+/// the album is the parent, the artist the grandparent. The Deezer ids are
+/// there because a row needs them; a music service matches on the names.
+pub async fn seed_track(ctx: &AppContext) -> db::Media {
+    let mut artist = db::Media {
+        title: "Massive Attack".into(),
+        kind: db::MediaKind::Artist,
+        external_ids: db::ExternalIds {
+            deezer_artist: Some(12),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    artist
+        .save(&ctx.db)
+        .await
+        .unwrap();
+
+    let mut album = db::Media {
+        title: "Mezzanine".into(),
+        kind: db::MediaKind::Album,
+        parent_id: Some(artist.id),
+        external_ids: db::ExternalIds {
+            deezer_album: Some(301937),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    album
+        .save(&ctx.db)
+        .await
+        .unwrap();
+
+    let mut track = db::Media {
+        title: "Teardrop".into(),
+        kind: db::MediaKind::Track,
+        parent_id: Some(album.id),
+        grandparent_id: Some(artist.id),
+        runtime: Some(330),
+        external_ids: db::ExternalIds {
+            deezer_track: Some(3129775),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    track
+        .save(&ctx.db)
+        .await
+        .unwrap();
+    track
+}
+
 /// Stores an addon row and installs `provider` as its media-tracker
 /// capability. The runtime list has to be rebuilt wholesale because the real
 /// one is built from registered presets, which have no way to carry a stub.
@@ -0,0 +1,257 @@
+//! LDAP sign-in: search, then bind.
+//!
+//! The user's entry is looked up with the service account (or anonymously),
+//! and the password is checked by binding as that entry. A first successful
+//! sign-in creates the remux user, the same way OpenID Connect does.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use ldap3::{LdapConnAsync, LdapConnSettings, Scope, SearchEntry, ldap_escape};
+use remux_sdks::remux::{LdapOptions, Username};
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+
+use crate::db;
+
+/// `AuthenticationProviderId` of users created by an LDAP sign-in.
+pub const PROVIDER_ID: &str = "Remux.Server.LdapAuthenticationProvider";
+
+/// Upper bound on the whole exchange with the directory.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Result code of a bind with a wrong password.
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// A directory entry whose password checked out.
+#[derive(Debug, Clone)]
+pub struct DirectoryUser {
+    pub dn: String,
+    pub username: String,
+    pub groups: Vec<String>,
+}
+
+impl DirectoryUser {
+    /// Group DNs compare case-insensitively.
+    pub fn is_member(&self, group: &str) -> bool {
+        self.groups
+            .iter()
+            .any(|g| {
+                g.trim()
+                    .eq_ignore_ascii_case(group.trim())
+            })
+    }
+}
+
+/// `template` with `{username}` replaced by the escaped login name.
+pub fn user_filter(template: &str, username: &str) -> String {
+    template.replace("{username}", &ldap_escape(username))
+}
+
+/// Attribute names are case-insensitive, and servers answer in their own case.
+fn attribute<'a>(entry: &'a SearchEntry, name: &str) -> &'a [String] {
+    entry
+        .attrs
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, values)| values.as_slice())
+        .unwrap_or_default()
+}
+
+/// Checks the credentials against the directory. `None` means they were
+/// refused, or the filter did not match exactly one entry.
+pub async fn verify(
+    opts: &LdapOptions,
+    username: &str,
+    password: &str,
+) -> Result<Option<DirectoryUser>> {
+    // A simple bind with an empty password is an unauthenticated bind, which
+    // most servers accept.
+    if username.is_empty() || password.is_empty() {
+        return Ok(None);
+    }
+    tokio::time::timeout(TIMEOUT, search_and_bind(opts, username, password))
+        .await
+        .context("LDAP server did not answer in time")?
+}
+
+async fn search_and_bind(
+    opts: &LdapOptions,
+    username: &str,
+    password: &str,
+) -> Result<Option<DirectoryUser>> {
+    let settings = LdapConnSettings::new()
+        .set_conn_timeout(TIMEOUT)
+        .set_starttls(opts.start_tls);
+    let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &opts.server_url)
+        .await
+        .with_context(|| format!("connecting to {}", opts.server_url))?;
+    ldap3::drive!(conn);
+
+    if let Some(bind_dn) = opts
+        .bind_dn
+        .as_deref()
+        .filter(|dn| !dn.is_empty())
+    {
+        ldap.simple_bind(
+            bind_dn,
+            opts.bind_password
+                .as_deref()
+                .unwrap_or_default(),
+        )
+        .await?
+        .success()
+        .context("service account bind failed")?;
+    }
+
+    let filter = user_filter(&opts.user_filter, username);
+    let (mut entries, _) = ldap
+        .search(
+            &opts.base_dn,
+            Scope::Subtree,
+            &filter,
+            vec![
+                opts.username_attribute
+                    .as_str(),
+                opts.group_attribute
+                    .as_str(),
+            ],
+        )
+        .await?
+        .success()
+        .context("user search failed")?;
+    if entries.len() != 1 {
+        if entries.len() > 1 {
+            warn!(%filter, count = entries.len(), "LDAP user filter matched several entries");
+        }
+        let _ = ldap
+            .unbind()
+            .await;
+        return Ok(None);
+    }
+    let entry = SearchEntry::construct(
+        entries
+            .pop()
+            .expect("one entry"),
+    );
+
+    let bind = ldap
+        .simple_bind(&entry.dn, password)
+        .await?;
+    let _ = ldap
+        .unbind()
+        .await;
+    if bind.rc == INVALID_CREDENTIALS {
+        return Ok(None);
+    }
+    bind.success()
+        .context("user bind failed")?;
+
+    let name = attribute(&entry, &opts.username_attribute)
+        .first()
+        .map(String::as_str)
+        .unwrap_or(username);
+    let username = Username::try_new(name)
+        .with_context(|| format!("`{name}` is not a valid username"))?
+        .into_inner();
+    Ok(Some(DirectoryUser {
+        username,
+        groups: attribute(&entry, &opts.group_attribute).to_vec(),
+        dn: entry.dn,
+    }))
+}
+
+/// Signs in through the directory. A directory user signs in as the remux
+/// account with the same username only if that account belongs to LDAP:
+/// created by an earlier sign-in, or assigned to it by an admin. Local
+/// accounts are left alone. Without an account, one is created when
+/// `auto_provision` is on.
+pub async fn authenticate(
+    db: &SqlitePool,
+    opts: &LdapOptions,
+    username: &str,
+    password: &str,
+) -> Result<Option<db::User>> {
+    let Some(entry) = verify(opts, username, password).await? else {
+        return Ok(None);
+    };
+    let admin = opts
+        .admin_group
+        .as_deref()
+        .filter(|group| !group.is_empty())
+        .map(|group| entry.is_member(group));
+
+    let mut user = match db::User::get_by_username(db, &entry.username).await? {
+        Some(user) if user.authentication_provider() == Some(PROVIDER_ID) => user,
+        Some(user) => {
+            warn!(user = %user.username, dn = %entry.dn, "LDAP entry names an account not assigned to LDAP");
+            return Ok(None);
+        }
+        None if opts.auto_provision => {
+            let is_admin = admin.unwrap_or(false);
+            let mut user = db::User::new_external(
+                entry
+                    .username
+                    .clone(),
+                PROVIDER_ID,
+                is_admin,
+            )?;
+            user.save(db)
+                .await?;
+            info!(user = %user.username, dn = %entry.dn, is_admin, "Created user from LDAP sign-in");
+            user
+        }
+        None => return Ok(None),
+    };
+    if let Some(admin) = admin {
+        user.sync_admin(db, admin)
+            .await?;
+    }
+    Ok(Some(user))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_filter_escapes_the_login_name() {
+        assert_eq!(user_filter("(uid={username})", "alice"), "(uid=alice)");
+        assert_eq!(
+            user_filter("(uid={username})", "a*)(uid=*"),
+            "(uid=a\\2a\\29\\28uid=\\2a)"
+        );
+        assert_eq!(
+            user_filter("(|(uid={username})(mail={username}))", "bob"),
+            "(|(uid=bob)(mail=bob))"
+        );
+    }
+
+    #[test]
+    fn group_membership_ignores_dn_case() {
+        let user = DirectoryUser {
+            dn: "uid=alice,dc=example,dc=com".into(),
+            username: "alice".into(),
+            groups: vec!["CN=Admins,OU=Groups,DC=example,DC=com".into()],
+        };
+        assert!(user.is_member("cn=admins,ou=groups,dc=example,dc=com"));
+        assert!(!user.is_member("cn=users,ou=groups,dc=example,dc=com"));
+    }
+
+    #[tokio::test]
+    async fn empty_password_is_refused_without_asking_the_directory() {
+        let opts = LdapOptions {
+            enabled: true,
+            // Nothing listens here; reaching the network would fail the test.
+            server_url: "ldap://127.0.0.1:1".into(),
+            base_dn: "dc=example,dc=com".into(),
+            ..Default::default()
+        };
+        assert!(
+            verify(&opts, "alice", "")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
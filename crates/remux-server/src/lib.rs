@@ -44,13 +44,21 @@ use uuid::Uuid;
 
 mod conversions;
 pub mod device_profile;
+mod dlna;
+mod downloads;
+mod dvr;
 mod errors;
 mod keyed_lock;
+mod ldap;
+mod network;
+mod oidc;
+mod smb;
 pub mod sdks {
     pub use remux_sdks::*;
 }
 mod addons;
 pub mod api;
+mod base_url;
 mod common;
 pub mod jellyfin_client;
 pub use common::stable_media_uuid;
@@ -64,8 +72,10 @@ pub mod playback;
 pub mod playback_session;
 pub mod services;
 pub mod stream;
+pub mod syncplay;
 pub mod tasks;
 mod torrent;
+mod trickplay;
 mod web_client;
 mod web_patches;
 mod web_transform;
@@ -190,7 +200,11 @@ pub async fn bind_and_serve(router: Router, port: u16) -> Result<()> {
     let app = MapRequestLayer::new(rewrite_request_uri).layer(router);
     info!("starting webserver at {addr}");
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app.into_make_service()).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
     Ok(())
 }
 
@@ -342,13 +356,16 @@ pub async fn init_app(
 
     let addons = addons::AddonService::from_db(&conn, &config).await?;
     let transcode_sessions_dir = resolve_transcode_dir(&config.data_dir);
+    let ws_tx = tokio::sync::broadcast::channel(128).0;
     let ctx = AppContext {
         config,
         db: conn.clone(),
         store: Store::new_weighted(128 * 1024 * 1024),
         sessions: playback_session::PlaybackSessionManager::new(transcode_sessions_dir),
         torrent: Arc::new(torrent_mgr),
-        ws_tx: tokio::sync::broadcast::channel(128).0,
+        syncplay: syncplay::SyncPlayManager::new(ws_tx.clone()),
+        dvr: dvr::DvrManager::new(),
+        ws_tx,
         default_web_client: Arc::new(tokio::sync::RwLock::new(
             web_client::normalize_web_client(saved_config.default_web_client)
                 .as_str()
@@ -356,6 +373,9 @@ pub async fn init_app(
         )),
         web_paths,
         addons,
+        network: Arc::new(arc_swap::ArcSwap::from_pointee(
+            network::NetworkPolicy::load(&conn).await,
+        )),
         started_at: Utc::now(),
     };
 
@@ -373,6 +393,23 @@ pub async fn init_app(
             std::time::Duration::from_secs(60 * 15),
         );
 
+    // Read once: the router below is built for it, so a change takes a restart.
+    let prefix = base_url::normalize(
+        api::networking::network_configuration(&ctx.db)
+            .await?
+            .base_url
+            .as_deref()
+            .unwrap_or_default(),
+    );
+    if !prefix.is_empty() {
+        info!("serving under base URL {prefix}");
+    }
+
+    dvr::spawn_scheduler(ctx.clone(), dvr::SCHEDULER_INTERVAL);
+    addons::opendal_watch::spawn(ctx.clone());
+    dlna::spawn(ctx.clone());
+    network::discovery::spawn(ctx.clone(), prefix.clone());
+
     db::StreamGroup::migrate_from_settings(&conn).await;
 
     let task_service = tasks::TaskService::new(ctx.clone()).await?;
@@ -428,6 +465,8 @@ pub async fn init_app(
         )
         .layer(cors);
 
+    let router = base_url::nest(router, &prefix);
+
     Ok((router, ctx))
 }
 
@@ -439,10 +478,14 @@ pub struct AppContext {
     pub sessions: playback_session::PlaybackSessionManager,
     pub torrent: Arc<torrent::TorrentManager>,
     pub ws_tx: tokio::sync::broadcast::Sender<ws::WsEvent>,
+    pub syncplay: syncplay::SyncPlayManager,
+    pub dvr: dvr::DvrManager,
     pub default_web_client: Arc<tokio::sync::RwLock<String>>,
     /// Present in filesystem builds; `None` in desktop (assets are embedded).
     pub web_paths: Option<FilesystemPaths>,
     pub addons: addons::AddonService,
+    /// The parsed network configuration, swapped when an admin saves it.
+    pub network: Arc<arc_swap::ArcSwap<network::NetworkPolicy>>,
     /// When this server process started.
     pub started_at: chrono::DateTime<chrono::Utc>,
 }
@@ -501,6 +544,9 @@ pub struct Config {
     pub database_url: Option<String>,
     /// `None` means derive from `data_dir` — call `resolve()` after loading.
     pub torrent_data_dir: Option<String>,
+    /// Where Live TV recordings are written.
+    /// `None` means derive from `data_dir` — call `resolve()` after loading.
+    pub recordings_dir: Option<String>,
     #[serde(default = "default_port")]
     pub port: u16,
     /// Explicit port for the internal torrent HTTP server.
@@ -529,6 +575,19 @@ pub struct Config {
     /// Base URL for the Trakt API. Overridable for testing.
     #[serde(default = "default_trakt_base_url")]
     pub trakt_base_url: String,
+    /// Base URL for the Kitsu API, above `edge/` and `oauth/`. Overridable for
+    /// testing.
+    #[serde(default = "default_kitsu_base_url")]
+    pub kitsu_base_url: String,
+    /// Base URL for the AniList GraphQL API. Overridable for testing.
+    #[serde(default = "default_anilist_base_url")]
+    pub anilist_base_url: String,
+    /// Base URL for the ListenBrainz API. Overridable for testing.
+    #[serde(default = "default_listenbrainz_base_url")]
+    pub listenbrainz_base_url: String,
+    /// Base URL for the Last.fm API. Overridable for testing.
+    #[serde(default = "default_lastfm_base_url")]
+    pub lastfm_base_url: String,
     /// Base URL for remuxdb. When set, probe results are submitted after each live probe.
     #[serde(default = "default_remuxdb_url")]
     pub remuxdb_url: Option<String>,
@@ -558,6 +617,22 @@ fn default_trakt_base_url() -> String {
     "https://api.trakt.tv".to_string()
 }
 
+fn default_kitsu_base_url() -> String {
+    "https://kitsu.io/api".to_string()
+}
+
+fn default_anilist_base_url() -> String {
+    "https://graphql.anilist.co".to_string()
+}
+
+fn default_listenbrainz_base_url() -> String {
+    "https://api.listenbrainz.org".to_string()
+}
+
+fn default_lastfm_base_url() -> String {
+    "https://ws.audioscrobbler.com/2.0".to_string()
+}
+
 fn default_bgutil_script_path() -> std::path::PathBuf {
     std::path::PathBuf::from("/usr/local/bin/bgutil-pot")
 }
@@ -644,6 +719,17 @@ impl Config {
                     .into_owned(),
             );
         }
+        if self
+            .recordings_dir
+            .is_none()
+        {
+            self.recordings_dir = Some(
+                self.data_dir
+                    .join("recordings")
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
         self
     }
 }
@@ -654,6 +740,7 @@ impl Default for Config {
             data_dir: default_data_dir(),
             database_url: None,
             torrent_data_dir: None,
+            recordings_dir: None,
             port: default_port(),
             torrent_http_port: default_torrent_http_port_opt(),
             slow_query_threshold_ms: default_slow_query_threshold_ms(),
@@ -662,6 +749,10 @@ impl Default for Config {
             bgutil_script_path: default_bgutil_script_path(),
             tmdb_base_url: default_tmdb_base_url(),
             trakt_base_url: default_trakt_base_url(),
+            kitsu_base_url: default_kitsu_base_url(),
+            anilist_base_url: default_anilist_base_url(),
+            listenbrainz_base_url: default_listenbrainz_base_url(),
+            lastfm_base_url: default_lastfm_base_url(),
             remuxdb_url: Some("https://remuxdb.1632022.xyz".to_string()),
             activity_log_retention_days: default_activity_log_retention_days(),
             jellyfin_version: default_jellyfin_version(),
@@ -0,0 +1,221 @@
+//! Client auto-discovery: Jellyfin apps broadcast `who is JellyfinServer?`
+//! to UDP 7359 and list every server that answers with its address.
+
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    time::Duration,
+};
+
+use serde_json::json;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{net::UdpSocket, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+use super::{NetworkPolicy, local_ip_for};
+use crate::{AppContext, api::networking, common::server_id, db};
+
+pub const PORT: u16 = 7359;
+
+/// How often the responder is matched against the `AutoDiscovery` setting.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn is_query(message: &[u8]) -> bool {
+    std::str::from_utf8(message).is_ok_and(|m| {
+        m.trim()
+            .eq_ignore_ascii_case("who is JellyfinServer?")
+    })
+}
+
+pub fn response(address: &str, name: &str) -> String {
+    json!({
+        "Address": address,
+        "Id": server_id(),
+        "Name": name,
+        "EndpointAddress": null,
+    })
+    .to_string()
+}
+
+/// A UDP socket on `port` that receives broadcasts.
+pub fn bind(port: u16) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_broadcast(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into())?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// The server URL handed to the client at `peer`: its published URL when one
+/// applies, else the address it reaches this host on, under `base_url`.
+fn address_for(
+    policy: &NetworkPolicy,
+    peer: SocketAddr,
+    http_port: u16,
+    base_url: &str,
+) -> Option<String> {
+    match policy.published_uri(peer.ip()) {
+        Some(uri) => Some(uri.to_string()),
+        None => local_ip_for(peer)
+            .ok()
+            .map(|ip| format!("http://{}{base_url}", SocketAddr::new(ip, http_port))),
+    }
+}
+
+/// Answer discovery queries until aborted.
+pub async fn serve(socket: UdpSocket, ctx: AppContext, base_url: String) {
+    let mut buf = [0u8; 512];
+    loop {
+        let (len, peer) = match socket
+            .recv_from(&mut buf)
+            .await
+        {
+            Ok(received) => received,
+            Err(e) => {
+                debug!("discovery receive failed: {e}");
+                continue;
+            }
+        };
+        if !is_query(&buf[..len]) {
+            continue;
+        }
+        let policy = ctx
+            .network
+            .load();
+        let Some(address) = address_for(
+            &policy,
+            peer,
+            ctx.config
+                .port,
+            &base_url,
+        ) else {
+            continue;
+        };
+        let name = db::Settings::get_config_or_default(&ctx.db)
+            .await
+            .server_name
+            .unwrap_or_default();
+        if let Err(e) = socket
+            .send_to(response(&address, &name).as_bytes(), peer)
+            .await
+        {
+            debug!(%peer, "failed to answer discovery query: {e}");
+        }
+    }
+}
+
+async fn enabled(db: &sqlx::SqlitePool) -> bool {
+    match networking::network_configuration(db).await {
+        Ok(config) => config
+            .auto_discovery
+            .unwrap_or(true),
+        Err(e) => {
+            warn!("failed to read the network configuration: {e:#}");
+            false
+        }
+    }
+}
+
+/// Starts and stops the responder as `AutoDiscovery` is switched.
+pub fn spawn(ctx: AppContext, base_url: String) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut responder: Option<JoinHandle<()>> = None;
+        let mut ticker = tokio::time::interval(RECONCILE_INTERVAL);
+        loop {
+            ticker
+                .tick()
+                .await;
+            match (enabled(&ctx.db).await, responder.take()) {
+                (true, None) => match bind(PORT) {
+                    Ok(socket) => {
+                        info!("answering client discovery on UDP {PORT}");
+                        responder = Some(tokio::spawn(serve(
+                            socket,
+                            ctx.clone(),
+                            base_url.clone(),
+                        )));
+                    }
+                    Err(e) => warn!("failed to bind the discovery port: {e}"),
+                },
+                (false, Some(running)) => {
+                    info!("client discovery disabled");
+                    running.abort();
+                }
+                (_, running) => responder = running,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_test::new_test_server;
+
+    #[test]
+    fn only_discovery_queries_are_answered() {
+        assert!(is_query(b"who is JellyfinServer?"));
+        assert!(is_query(b"Who is JellyfinServer?\n"));
+        assert!(!is_query(b"who is EmbyServer?"));
+        assert!(!is_query(&[0xff, 0xfe]));
+    }
+
+    #[test]
+    fn local_addresses_carry_the_base_url() {
+        let policy = NetworkPolicy::from(&crate::api::NetworkConfiguration::default());
+        let peer = SocketAddr::from((Ipv4Addr::LOCALHOST, 50000));
+        assert_eq!(
+            address_for(&policy, peer, 8096, "/remux").as_deref(),
+            Some("http://127.0.0.1:8096/remux")
+        );
+    }
+
+    #[tokio::test]
+    async fn queries_get_the_server_address() {
+        let (_server, guard) = new_test_server()
+            .await
+            .unwrap();
+        let socket = bind(0).unwrap();
+        let port = socket
+            .local_addr()
+            .unwrap()
+            .port();
+        let responder = tokio::spawn(serve(
+            socket,
+            guard
+                .0
+                .clone(),
+            String::new(),
+        ));
+
+        let client = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        client
+            .send_to(b"who is JellyfinServer?", ("127.0.0.1", port))
+            .await
+            .unwrap();
+        let mut buf = [0u8; 512];
+        let (len, _) =
+            tokio::time::timeout(Duration::from_secs(5), client.recv_from(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+        responder.abort();
+
+        let body: serde_json::Value = serde_json::from_slice(&buf[..len]).unwrap();
+        assert_eq!(
+            body["Address"],
+            format!(
+                "http://127.0.0.1:{}",
+                guard
+                    .0
+                    .config
+                    .port
+            )
+        );
+        assert_eq!(body["Id"], server_id());
+        assert!(body["EndpointAddress"].is_null());
+    }
+}
@@ -0,0 +1,578 @@
+//! LAN/WAN policy from the network configuration.
+//!
+//! Decides who a request came from (forwarded headers are only believed when
+//! the connection comes from one of `KnownProxies`), whether that client is
+//! on the local network (`LocalNetworkSubnets`, or the private ranges when
+//! none are configured), and which published server URL it should be given.
+
+pub mod discovery;
+
+use std::{
+    convert::Infallible,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    str::FromStr,
+};
+
+use anyhow::anyhow;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum_anyhow::ApiResult as Result;
+use http::{HeaderMap, request::Parts};
+use tracing::warn;
+
+use crate::{AppState, IntoApiError, api, api::networking, db};
+
+/// Ranges treated as local when `LocalNetworkSubnets` is empty. Carrier-grade
+/// NAT (100.64.0.0/10) is left out: an ISP hands it to many subscribers at
+/// once, so a peer there is no more on the LAN than one on a public address.
+/// Overlays that reuse it, such as Tailscale, belong in `LocalNetworkSubnets`.
+const PRIVATE_RANGES: &[&str] = &[
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+];
+
+/// An address range in CIDR notation; a bare address is a single host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subnet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Subnet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (addr, prefix) = match s
+            .trim()
+            .split_once('/')
+        {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()?
+            .to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>()?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(anyhow!("prefix /{prefix} is too long for {addr}"));
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// Which clients a `PublishedServerUriBySubnet` entry applies to.
+#[derive(Debug, Clone, PartialEq)]
+enum Scope {
+    All,
+    Internal,
+    External,
+    Subnet(Subnet),
+}
+
+/// The parsed, enforceable parts of the network configuration.
+#[derive(Debug, Clone)]
+pub struct NetworkPolicy {
+    local_subnets: Vec<Subnet>,
+    known_proxies: Vec<Subnet>,
+    published_uris: Vec<(Scope, String)>,
+}
+
+/// Parses every entry, skipping (and logging) the ones that are not
+/// addresses or CIDR ranges.
+fn subnets<'a>(
+    setting: &str,
+    entries: impl IntoIterator<Item = &'a str>,
+) -> Vec<Subnet> {
+    entries
+        .into_iter()
+        .filter_map(|entry| match entry.parse() {
+            Ok(subnet) => Some(subnet),
+            Err(e) => {
+                warn!("ignoring {setting} entry {entry:?}: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+impl From<&api::NetworkConfiguration> for NetworkPolicy {
+    fn from(config: &api::NetworkConfiguration) -> Self {
+        let configured = config
+            .local_network_subnets
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|s| {
+                !s.trim()
+                    .is_empty()
+            });
+        let mut local_subnets = subnets("LocalNetworkSubnets", configured);
+        if local_subnets.is_empty() {
+            local_subnets = subnets(
+                "private range",
+                PRIVATE_RANGES
+                    .iter()
+                    .copied(),
+            );
+        }
+        let known_proxies = subnets(
+            "KnownProxies",
+            config
+                .known_proxies
+                .iter()
+                .flatten()
+                .map(String::as_str),
+        );
+        let published_uris = config
+            .published_server_uri_by_subnet
+            .iter()
+            .flatten()
+            .filter_map(|entry| {
+                let (scope, uri) = entry.split_once('=')?;
+                let scope = match scope
+                    .trim()
+                    .to_ascii_lowercase()
+                    .as_str()
+                {
+                    "all" => Scope::All,
+                    "internal" => Scope::Internal,
+                    "external" => Scope::External,
+                    subnet => match subnet.parse() {
+                        Ok(subnet) => Scope::Subnet(subnet),
+                        Err(e) => {
+                            warn!("ignoring PublishedServerUriBySubnet entry {entry:?}: {e}");
+                            return None;
+                        }
+                    },
+                };
+                Some((
+                    scope,
+                    uri.trim()
+                        .trim_end_matches('/')
+                        .to_string(),
+                ))
+            })
+            .collect();
+        Self {
+            local_subnets,
+            known_proxies,
+            published_uris,
+        }
+    }
+}
+
+/// An address from a forwarding header, which may carry a port.
+fn parse_forwarded(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    value
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| {
+            value
+                .parse::<SocketAddr>()
+                .ok()
+                .map(|addr| addr.ip())
+        })
+        .map(|ip| ip.to_canonical())
+}
+
+impl NetworkPolicy {
+    /// Reads the saved configuration. Requests use the copy kept in
+    /// `AppContext::network` instead.
+    pub async fn load(db: &sqlx::SqlitePool) -> Self {
+        match networking::network_configuration(db).await {
+            Ok(config) => Self::from(&config),
+            Err(e) => {
+                warn!("failed to read the network configuration: {e:#}");
+                Self::from(&api::NetworkConfiguration::default())
+            }
+        }
+    }
+
+    fn is_proxy(&self, ip: IpAddr) -> bool {
+        self.known_proxies
+            .iter()
+            .any(|proxy| proxy.contains(ip))
+    }
+
+    pub fn is_local(&self, ip: IpAddr) -> bool {
+        self.local_subnets
+            .iter()
+            .any(|subnet| subnet.contains(ip))
+    }
+
+    /// The client behind a connection from `peer`. `X-Forwarded-For` and
+    /// `X-Real-IP` are only read when `peer` is a known proxy; proxies append
+    /// to `X-Forwarded-For`, so the client is the last hop that is not one.
+    pub fn client_ip(
+        &self,
+        peer: Option<IpAddr>,
+        headers: &HeaderMap,
+    ) -> Option<IpAddr> {
+        let peer = peer.map(|ip| ip.to_canonical());
+        if !peer.is_some_and(|ip| self.is_proxy(ip)) {
+            return peer;
+        }
+        let forwarded: Vec<IpAddr> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| {
+                value
+                    .to_str()
+                    .ok()
+            })
+            .flat_map(|value| value.split(','))
+            .filter_map(parse_forwarded)
+            .collect();
+        forwarded
+            .iter()
+            .rev()
+            .find(|ip| !self.is_proxy(**ip))
+            .or(forwarded.first())
+            .copied()
+            .or_else(|| {
+                headers
+                    .get("x-real-ip")
+                    .and_then(|value| {
+                        value
+                            .to_str()
+                            .ok()
+                    })
+                    .and_then(parse_forwarded)
+            })
+            .or(peer)
+    }
+
+    /// The first `PublishedServerUriBySubnet` URL that applies to `ip`.
+    pub fn published_uri(&self, ip: IpAddr) -> Option<&str> {
+        self.published_uris
+            .iter()
+            .find(|(scope, _)| match scope {
+                Scope::All => true,
+                Scope::Internal => self.is_local(ip),
+                Scope::External => !self.is_local(ip),
+                Scope::Subnet(subnet) => subnet.contains(ip),
+            })
+            .map(|(_, uri)| uri.as_str())
+    }
+}
+
+/// Where a request came from, after proxy resolution. Requests without a
+/// socket address (in-process tests) count as local.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientAddr {
+    pub ip: Option<IpAddr>,
+    pub is_local: bool,
+}
+
+impl ClientAddr {
+    pub fn is_remote(&self) -> bool {
+        !self.is_local
+    }
+}
+
+impl FromRequestParts<AppState> for ClientAddr {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        if let Some(client) = parts
+            .extensions
+            .get::<ClientAddr>()
+        {
+            return Ok(*client);
+        }
+        let peer = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let policy = state
+            .ctx
+            .network
+            .load();
+        let ip = policy.client_ip(peer, &parts.headers);
+        let client = ClientAddr {
+            ip,
+            is_local: ip.is_none_or(|ip| policy.is_local(ip)),
+        };
+        parts
+            .extensions
+            .insert(client);
+        Ok(client)
+    }
+}
+
+/// Rejects remote clients of users whose policy disables remote access.
+pub fn ensure_remote_access(user: &db::User, client: &ClientAddr) -> Result<()> {
+    let allowed = user
+        .policy
+        .as_ref()
+        .is_none_or(|p| p.enable_remote_access);
+    if client.is_remote() && !allowed {
+        return Err(anyhow!(
+            "{} connected from outside the local network",
+            user.username
+        )
+        .context_forbidden("Remote access is disabled for this user"));
+    }
+    Ok(())
+}
+
+/// The bitrate cap for a remote client: the user's own limit, else the
+/// server-wide one. `None` when neither is set.
+pub fn remote_bitrate_limit(
+    user: &db::User,
+    config: &api::ServerConfiguration,
+) -> Option<i64> {
+    user.policy
+        .as_ref()
+        .map(|p| p.remote_client_bitrate_limit)
+        .filter(|limit| *limit > 0)
+        .or(config
+            .remote_client_bitrate_limit
+            .filter(|limit| *limit > 0))
+}
+
+/// The local address `peer` reaches the server on. Connecting a UDP socket
+/// sends nothing; it only asks the routing table.
+pub fn local_ip_for(peer: SocketAddr) -> io::Result<IpAddr> {
+    let probe =
+        std::net::UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)))?;
+    probe.connect(peer)?;
+    Ok(probe
+        .local_addr()?
+        .ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration_test::{
+        auth_header_with_token, authenticated_server_from, create_user_with_policy,
+    };
+    use axum_test::TestServer;
+    use http::header::HeaderValue;
+    use serde_json::json;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse()
+            .unwrap()
+    }
+
+    fn policy(config: serde_json::Value) -> NetworkPolicy {
+        NetworkPolicy::from(
+            &serde_json::from_value::<api::NetworkConfiguration>(config).unwrap(),
+        )
+    }
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            value
+                .parse()
+                .unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn subnets_match_by_prefix() {
+        let subnet: Subnet = "192.168.1.0/24"
+            .parse()
+            .unwrap();
+        assert!(subnet.contains(ip("192.168.1.77")));
+        assert!(!subnet.contains(ip("192.168.2.1")));
+        assert!(subnet.contains(ip("::ffff:192.168.1.5")));
+        assert!(
+            "10.0.0.1"
+                .parse::<Subnet>()
+                .unwrap()
+                .contains(ip("10.0.0.1"))
+        );
+        assert!(
+            "0.0.0.0/0"
+                .parse::<Subnet>()
+                .unwrap()
+                .contains(ip("8.8.8.8"))
+        );
+        assert!(
+            "fd00::/8"
+                .parse::<Subnet>()
+                .unwrap()
+                .contains(ip("fd12::1"))
+        );
+        assert!(
+            "10.0.0.0/33"
+                .parse::<Subnet>()
+                .is_err()
+        );
+        assert!(
+            "proxy.lan"
+                .parse::<Subnet>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn private_ranges_are_local_until_subnets_are_configured() {
+        let default = policy(json!({}));
+        assert!(default.is_local(ip("192.168.0.10")));
+        assert!(default.is_local(ip("127.0.0.1")));
+        assert!(!default.is_local(ip("203.0.113.9")));
+
+        let configured = policy(json!({ "LocalNetworkSubnets": ["203.0.113.0/24"] }));
+        assert!(configured.is_local(ip("203.0.113.9")));
+        assert!(!configured.is_local(ip("192.168.0.10")));
+    }
+
+    #[test]
+    fn forwarded_headers_are_only_trusted_from_known_proxies() {
+        let policy = policy(json!({ "KnownProxies": ["10.0.0.2"] }));
+        let headers = forwarded("198.51.100.7, 10.0.0.2");
+
+        // A direct client cannot claim another address.
+        assert_eq!(
+            policy.client_ip(Some(ip("203.0.113.9")), &headers),
+            Some(ip("203.0.113.9"))
+        );
+        // Behind the proxy, the last hop that is not a proxy is the client.
+        assert_eq!(
+            policy.client_ip(Some(ip("10.0.0.2")), &headers),
+            Some(ip("198.51.100.7"))
+        );
+        // A spoofed leading entry doesn't win over the hop the proxy saw.
+        assert_eq!(
+            policy.client_ip(
+                Some(ip("10.0.0.2")),
+                &forwarded("192.168.0.1, 198.51.100.7")
+            ),
+            Some(ip("198.51.100.7"))
+        );
+        assert_eq!(
+            policy.client_ip(Some(ip("10.0.0.2")), &HeaderMap::new()),
+            Some(ip("10.0.0.2"))
+        );
+    }
+
+    #[test]
+    fn published_uris_are_picked_by_subnet() {
+        let policy = policy(json!({
+            "PublishedServerUriBySubnet": [
+                "192.168.1.0/24=http://192.168.1.2:8096",
+                "external=https://media.example.com/",
+                "eth0=http://ignored",
+            ]
+        }));
+        assert_eq!(
+            policy.published_uri(ip("192.168.1.50")),
+            Some("http://192.168.1.2:8096")
+        );
+        assert_eq!(
+            policy.published_uri(ip("203.0.113.9")),
+            Some("https://media.example.com")
+        );
+        assert_eq!(policy.published_uri(ip("10.1.1.1")), None);
+    }
+
+    async fn set_network_configuration(
+        server: &TestServer,
+        token: &str,
+        config: serde_json::Value,
+    ) {
+        server
+            .post("/system/configuration/network")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(token)).unwrap(),
+            )
+            .json(&config)
+            .await
+            .assert_status(http::StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn remote_access_is_enforced_per_user() {
+        let peer = SocketAddr::from(([10, 0, 0, 2], 50000));
+        let (server, _guard, token) = authenticated_server_from(peer).await;
+        create_user_with_policy(
+            &server,
+            &token,
+            "lan-only",
+            "pw",
+            json!({ "EnableRemoteAccess": false }),
+        )
+        .await;
+        set_network_configuration(
+            &server,
+            &token,
+            json!({ "KnownProxies": ["10.0.0.2"] }),
+        )
+        .await;
+
+        let sign_in = |from: &'static str| {
+            server
+                .post("/users/authenticatebyname")
+                .add_header(
+                    http::header::AUTHORIZATION,
+                    HeaderValue::from_static(crate::integration_test::AUTH_HEADER),
+                )
+                .add_header("X-Forwarded-For", HeaderValue::from_static(from))
+                .json(&json!({ "Username": "lan-only", "Pw": "pw" }))
+        };
+        sign_in("203.0.113.9")
+            .expect_failure()
+            .await
+            .assert_status(http::StatusCode::FORBIDDEN);
+        let user_token = sign_in("192.168.1.20")
+            .await
+            .json::<serde_json::Value>()["AccessToken"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        // The same token stops working once the client leaves the LAN.
+        server
+            .get("/users/me")
+            .add_header(
+                http::header::AUTHORIZATION,
+                HeaderValue::from_str(&auth_header_with_token(&user_token)).unwrap(),
+            )
+            .add_header("X-Forwarded-For", HeaderValue::from_static("203.0.113.9"))
+            .expect_failure()
+            .await
+            .assert_status(http::StatusCode::FORBIDDEN);
+    }
+}
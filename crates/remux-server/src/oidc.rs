@@ -0,0 +1,884 @@
+//! OpenID Connect sign-in: authorization code flow with PKCE.
+//!
+//! `/sso/oidc/start` sends the browser to the provider, which returns it to
+//! `/sso/oidc/callback`. The callback resolves the remux user and hands the
+//! browser a one-time login code; the client trades that code for an access
+//! token at `/sso/oidc/authenticate`, under its own device header, the same
+//! way QuickConnect does.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use rand::RngCore;
+use remux_sdks::{
+    BearerAuth, CachedEndpoint, RestClient,
+    oidc::{
+        AuthorizationCodeEndpoint, DiscoveryEndpoint, Jwk, JwksEndpoint,
+        ProviderMetadata, UserInfoEndpoint,
+    },
+    remux::{OidcOptions, Username},
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::db;
+
+/// `AuthenticationProviderId` of users created by an OpenID Connect sign-in.
+pub const PROVIDER_ID: &str = "Remux.Server.OidcAuthenticationProvider";
+
+/// How long a browser may spend at the provider.
+pub const PENDING_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How long the client has to claim a finished sign-in.
+pub const LOGIN_CODE_TTL: Duration = Duration::from_secs(2 * 60);
+
+const DISCOVERY_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Accepted clock difference with the provider when checking `exp`.
+const CLOCK_SKEW_SECS: i64 = 60;
+
+/// Where the browser lands when the login page did not say.
+pub const DEFAULT_RETURN_TO: &str = "/web/";
+
+/// Why a provider-authenticated identity may still not sign in. Handlers
+/// answer these with 403.
+#[derive(Debug, thiserror::Error)]
+pub enum OidcDenied {
+    #[error("Your account is not in a group that may sign in")]
+    NotInAllowedGroup,
+    #[error("No remux account matches this identity")]
+    NoAccount,
+    #[error("This account has not been set up for single sign-on")]
+    NotLinked,
+}
+
+/// What `/sso/oidc/start` keeps, under the `state` value, for the callback.
+#[derive(Clone)]
+pub struct PendingLogin {
+    pub nonce: String,
+    pub code_verifier: String,
+    pub redirect_uri: String,
+    pub return_to: String,
+}
+
+/// A finished sign-in waiting for the client to claim it.
+#[derive(Clone)]
+pub struct CompletedLogin {
+    pub user_id: Uuid,
+}
+
+/// The parts of the provider's claims remux acts on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub subject: String,
+    pub username: String,
+    pub groups: Vec<String>,
+}
+
+impl Identity {
+    fn in_any(&self, groups: &[String]) -> bool {
+        groups
+            .iter()
+            .any(|group| {
+                self.groups
+                    .contains(group)
+            })
+    }
+}
+
+/// 256 random bits, URL-safe. Used for `state`, `nonce`, PKCE verifiers and
+/// login codes.
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The S256 PKCE challenge for `verifier`.
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Keep only same-origin paths, so the login flow cannot be used as an open
+/// redirect.
+pub fn safe_return_to(return_to: Option<&str>) -> String {
+    return_to
+        .filter(|path| {
+            path.starts_with('/') && !path.starts_with("//") && !path.starts_with("/\\")
+        })
+        .unwrap_or(DEFAULT_RETURN_TO)
+        .to_string()
+}
+
+pub async fn discover(opts: &OidcOptions) -> Result<ProviderMetadata> {
+    let metadata = RestClient::new(&opts.issuer_url)?
+        .execute(DiscoveryEndpoint.with_cache(DISCOVERY_TTL))
+        .await
+        .context("OpenID Connect discovery failed")?;
+    if metadata
+        .issuer
+        .trim_end_matches('/')
+        != opts
+            .issuer_url
+            .trim_end_matches('/')
+    {
+        bail!(
+            "discovery document is for issuer {}, not {}",
+            metadata.issuer,
+            opts.issuer_url
+        );
+    }
+    Ok(metadata)
+}
+
+pub fn authorization_url(
+    metadata: &ProviderMetadata,
+    opts: &OidcOptions,
+    state: &str,
+    pending: &PendingLogin,
+) -> Result<url::Url> {
+    let scope = std::iter::once("openid")
+        .chain(
+            opts.scopes
+                .split_whitespace()
+                .filter(|scope| *scope != "openid"),
+        )
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut url = url::Url::parse(&metadata.authorization_endpoint)?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &opts.client_id)
+        .append_pair("redirect_uri", &pending.redirect_uri)
+        .append_pair("scope", &scope)
+        .append_pair("state", state)
+        .append_pair("nonce", &pending.nonce)
+        .append_pair("code_challenge", &pkce_challenge(&pending.code_verifier))
+        .append_pair("code_challenge_method", "S256");
+    Ok(url)
+}
+
+/// Trade the authorization code for tokens and return the validated ID token
+/// claims, topped up with anything extra the userinfo endpoint reports.
+pub async fn exchange_code(
+    metadata: &ProviderMetadata,
+    opts: &OidcOptions,
+    pending: &PendingLogin,
+    code: &str,
+) -> Result<Map<String, Value>> {
+    let client = RestClient::new(&metadata.issuer)?;
+    let tokens = client
+        .execute(AuthorizationCodeEndpoint {
+            token_endpoint: metadata
+                .token_endpoint
+                .clone(),
+            code: code.to_string(),
+            redirect_uri: pending
+                .redirect_uri
+                .clone(),
+            code_verifier: pending
+                .code_verifier
+                .clone(),
+            client_id: opts
+                .client_id
+                .clone(),
+            client_secret: opts
+                .client_secret
+                .clone()
+                .filter(|secret| !secret.is_empty()),
+        })
+        .await
+        .context("token exchange failed")?;
+    let id_token = tokens
+        .id_token
+        .as_deref()
+        .context("token response has no id_token")?;
+    let mut claims = verify_id_token(&client, metadata, opts, id_token).await?;
+    validate_id_token(
+        &claims,
+        &metadata.issuer,
+        &opts.client_id,
+        &pending.nonce,
+        Utc::now().timestamp(),
+    )?;
+
+    // Many providers only put groups and profile claims in userinfo.
+    if let Some(userinfo_endpoint) = &metadata.userinfo_endpoint {
+        match client
+            .with_auth(BearerAuth {
+                token: tokens
+                    .access_token
+                    .clone(),
+            })
+            .execute(UserInfoEndpoint {
+                userinfo_endpoint: userinfo_endpoint.clone(),
+            })
+            .await
+        {
+            Ok(userinfo) => {
+                if userinfo.get("sub") != claims.get("sub") {
+                    bail!("userinfo subject does not match the ID token");
+                }
+                for (claim, value) in userinfo {
+                    claims
+                        .entry(claim)
+                        .or_insert(value);
+                }
+            }
+            Err(e) => warn!(error = %e, "OpenID Connect userinfo request failed"),
+        }
+    }
+    Ok(claims)
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+/// A compact JWS split into what the signature covers and what it says.
+struct Jwt<'a> {
+    header: JwtHeader,
+    signing_input: &'a str,
+    signature: Vec<u8>,
+    claims: Map<String, Value>,
+}
+
+fn decode_segment(segment: &str) -> Result<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(segment.trim_end_matches('='))
+        .context("not base64url")
+}
+
+impl<'a> Jwt<'a> {
+    fn parse(token: &'a str) -> Result<Self> {
+        let mut parts = token.splitn(3, '.');
+        let (Some(header), Some(payload), Some(signature)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            bail!("ID token is not a JWT");
+        };
+        let header = serde_json::from_slice(&decode_segment(header)?)
+            .context("ID token header is not valid")?;
+        let claims = serde_json::from_slice(&decode_segment(payload)?)
+            .context("ID token payload is not a JSON object")?;
+        Ok(Self {
+            header,
+            signing_input: &token[..token.len() - signature.len() - 1],
+            signature: decode_segment(signature)
+                .context("ID token signature is not base64url")?,
+            claims,
+        })
+    }
+
+    /// HS256/384/512, keyed with the client secret (OpenID Connect Core
+    /// §10.1).
+    fn verify_with_secret(&self, secret: &str) -> Result<()> {
+        use ring::hmac;
+        let algorithm = match self
+            .header
+            .alg
+            .as_str()
+        {
+            "HS256" => hmac::HMAC_SHA256,
+            "HS384" => hmac::HMAC_SHA384,
+            "HS512" => hmac::HMAC_SHA512,
+            alg => bail!("unsupported ID token algorithm {alg}"),
+        };
+        hmac::verify(
+            &hmac::Key::new(algorithm, secret.as_bytes()),
+            self.signing_input
+                .as_bytes(),
+            &self.signature,
+        )
+        .map_err(|_| anyhow!("ID token signature is invalid"))
+    }
+
+    /// Accept the token if one of `keys` that could have signed it did.
+    fn verify_with_any(&self, keys: &[Jwk]) -> Result<()> {
+        let verified = keys
+            .iter()
+            .filter(|key| {
+                self.header
+                    .kid
+                    .as_ref()
+                    .is_none_or(|kid| {
+                        key.kid
+                            .as_ref()
+                            == Some(kid)
+                    })
+            })
+            .filter(|key| {
+                key.use_
+                    .as_deref()
+                    != Some("enc")
+            })
+            .filter(|key| {
+                key.alg
+                    .as_ref()
+                    .is_none_or(|alg| {
+                        *alg == self
+                            .header
+                            .alg
+                    })
+            })
+            .any(|key| {
+                self.verify_with_key(key)
+                    .is_ok()
+            });
+        if !verified {
+            bail!("ID token signature does not match any of the provider's keys");
+        }
+        Ok(())
+    }
+
+    fn verify_with_key(&self, key: &Jwk) -> Result<()> {
+        use ring::signature as sig;
+        let member = |value: &Option<String>| -> Result<Vec<u8>> {
+            decode_segment(
+                value
+                    .as_deref()
+                    .context("incomplete key")?,
+            )
+        };
+        let message = self
+            .signing_input
+            .as_bytes();
+        let curve = key
+            .crv
+            .as_deref();
+        let verified = match (
+            self.header
+                .alg
+                .as_str(),
+            key.kty
+                .as_str(),
+            curve,
+        ) {
+            (
+                alg @ ("RS256" | "RS384" | "RS512" | "PS256" | "PS384" | "PS512"),
+                "RSA",
+                _,
+            ) => {
+                let params: &sig::RsaParameters = match alg {
+                    "RS256" => &sig::RSA_PKCS1_2048_8192_SHA256,
+                    "RS384" => &sig::RSA_PKCS1_2048_8192_SHA384,
+                    "RS512" => &sig::RSA_PKCS1_2048_8192_SHA512,
+                    "PS256" => &sig::RSA_PSS_2048_8192_SHA256,
+                    "PS384" => &sig::RSA_PSS_2048_8192_SHA384,
+                    _ => &sig::RSA_PSS_2048_8192_SHA512,
+                };
+                sig::RsaPublicKeyComponents {
+                    n: member(&key.n)?,
+                    e: member(&key.e)?,
+                }
+                .verify(params, message, &self.signature)
+            }
+            ("ES256", "EC", Some("P-256")) | ("ES384", "EC", Some("P-384")) => {
+                let params = if curve == Some("P-256") {
+                    &sig::ECDSA_P256_SHA256_FIXED
+                } else {
+                    &sig::ECDSA_P384_SHA384_FIXED
+                };
+                let mut point = vec![0x04];
+                point.extend(member(&key.x)?);
+                point.extend(member(&key.y)?);
+                sig::UnparsedPublicKey::new(params, point)
+                    .verify(message, &self.signature)
+            }
+            ("EdDSA", "OKP", Some("Ed25519")) => {
+                sig::UnparsedPublicKey::new(&sig::ED25519, member(&key.x)?)
+                    .verify(message, &self.signature)
+            }
+            (alg, kty, _) => bail!("{kty} key cannot verify {alg}"),
+        };
+        verified.map_err(|_| anyhow!("ID token signature is invalid"))
+    }
+}
+
+/// The claims of an ID token whose signature checks out: against the
+/// provider's published keys, or the client secret for HMAC tokens. Keys
+/// are fetched again when none match, since the provider may have rotated
+/// them since they were cached.
+async fn verify_id_token(
+    client: &RestClient,
+    metadata: &ProviderMetadata,
+    opts: &OidcOptions,
+    token: &str,
+) -> Result<Map<String, Value>> {
+    let jwt = Jwt::parse(token)?;
+    if jwt
+        .header
+        .alg
+        .starts_with("HS")
+    {
+        let secret = opts
+            .client_secret
+            .as_deref()
+            .filter(|secret| !secret.is_empty())
+            .context(
+                "ID token is signed with a client secret, but none is configured",
+            )?;
+        jwt.verify_with_secret(secret)?;
+        return Ok(jwt.claims);
+    }
+    let jwks_uri = metadata
+        .jwks_uri
+        .clone()
+        .context("discovery document has no jwks_uri")?;
+    let keys = client
+        .execute(
+            JwksEndpoint {
+                jwks_uri: jwks_uri.clone(),
+            }
+            .with_cache(DISCOVERY_TTL),
+        )
+        .await
+        .context("fetching the provider's signing keys failed")?;
+    if jwt
+        .verify_with_any(&keys.keys)
+        .is_err()
+    {
+        let keys = client
+            .execute(JwksEndpoint { jwks_uri })
+            .await
+            .context("fetching the provider's signing keys failed")?;
+        jwt.verify_with_any(&keys.keys)?;
+    }
+    Ok(jwt.claims)
+}
+
+fn validate_id_token(
+    claims: &Map<String, Value>,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<()> {
+    let iss = claims
+        .get("iss")
+        .and_then(Value::as_str);
+    if iss.map(|iss| iss.trim_end_matches('/')) != Some(issuer.trim_end_matches('/')) {
+        bail!("ID token issuer {iss:?} does not match {issuer}");
+    }
+    let for_us = match claims.get("aud") {
+        Some(Value::String(aud)) => aud == client_id,
+        Some(Value::Array(aud)) => aud
+            .iter()
+            .any(|aud| aud.as_str() == Some(client_id)),
+        _ => false,
+    };
+    if !for_us {
+        bail!("ID token was not issued to this client");
+    }
+    match claims
+        .get("exp")
+        .and_then(Value::as_i64)
+    {
+        Some(exp) if exp + CLOCK_SKEW_SECS >= now => {}
+        _ => bail!("ID token has expired"),
+    }
+    if claims
+        .get("nonce")
+        .and_then(Value::as_str)
+        != Some(nonce)
+    {
+        bail!("ID token nonce does not match");
+    }
+    Ok(())
+}
+
+pub fn identity_from_claims(
+    opts: &OidcOptions,
+    claims: &Map<String, Value>,
+) -> Result<Identity> {
+    let subject = claims
+        .get("sub")
+        .and_then(Value::as_str)
+        .filter(|sub| !sub.is_empty())
+        .context("claims have no subject")?
+        .to_string();
+    let username = claims
+        .get(&opts.username_claim)
+        .and_then(Value::as_str)
+        .with_context(|| format!("claims have no `{}`", opts.username_claim))?;
+    let username = Username::try_new(username)
+        .with_context(|| format!("`{username}` is not a valid username"))?
+        .into_inner();
+    let groups = match claims.get(&opts.groups_claim) {
+        Some(Value::Array(groups)) => groups
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        Some(Value::String(group)) => vec![group.clone()],
+        _ => Vec::new(),
+    };
+    Ok(Identity {
+        subject,
+        username,
+        groups,
+    })
+}
+
+/// Find, link or create the remux user behind `identity`, then apply the
+/// admin group mapping.
+///
+/// A subject seen before keeps its user even if the username changes at the
+/// provider. A new subject only adopts the account with the same username
+/// when `adoptable` allows it: the username claim is whatever the provider
+/// lets its users pick, so it alone must not hand over an account.
+pub async fn resolve_user(
+    db: &SqlitePool,
+    opts: &OidcOptions,
+    issuer: &str,
+    identity: &Identity,
+) -> Result<db::User> {
+    if !opts
+        .allowed_groups
+        .is_empty()
+        && !identity.in_any(&opts.allowed_groups)
+    {
+        return Err(OidcDenied::NotInAllowedGroup.into());
+    }
+    let admin = (!opts
+        .admin_groups
+        .is_empty())
+    .then(|| identity.in_any(&opts.admin_groups));
+
+    let linked =
+        match db::UserIdentity::find_user(db, issuer, &identity.subject).await? {
+            Some(user_id) => db::User::get_by_id(db, &user_id).await?,
+            None => None,
+        };
+    let mut user = match linked {
+        Some(user) => user,
+        None => {
+            let user = match db::User::get_by_username(db, &identity.username).await? {
+                Some(user) if adoptable(db, &user).await? => user,
+                Some(user) => {
+                    warn!(
+                        user = %user.username,
+                        "OpenID Connect identity names an account not assigned to it"
+                    );
+                    return Err(OidcDenied::NotLinked.into());
+                }
+                None if opts.auto_provision => {
+                    provision(db, identity, admin.unwrap_or(false)).await?
+                }
+                None => return Err(OidcDenied::NoAccount.into()),
+            };
+            db::UserIdentity::link(db, issuer, &identity.subject, &user.id).await?;
+            user
+        }
+    };
+
+    if let Some(admin) = admin {
+        user.sync_admin(db, admin)
+            .await?;
+    }
+    Ok(user)
+}
+
+/// Whether a new subject may take over `user` by username: an account an
+/// admin assigned to OpenID Connect that no other identity signs in as yet.
+async fn adoptable(db: &SqlitePool, user: &db::User) -> Result<bool> {
+    Ok(!user.is_admin
+        && user.authentication_provider() == Some(PROVIDER_ID)
+        && !db::UserIdentity::is_linked(db, &user.id).await?)
+}
+
+async fn provision(
+    db: &SqlitePool,
+    identity: &Identity,
+    is_admin: bool,
+) -> Result<db::User> {
+    let mut user = db::User::new_external(
+        identity
+            .username
+            .clone(),
+        PROVIDER_ID,
+        is_admin,
+    )?;
+    user.save(db)
+        .await?;
+    info!(user = %user.username, is_admin, "Created user from OpenID Connect sign-in");
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn claims(value: Value) -> Map<String, Value> {
+        value
+            .as_object()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc_7636() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn return_to_stays_on_this_origin() {
+        assert_eq!(safe_return_to(Some("/admin")), "/admin");
+        assert_eq!(safe_return_to(Some("/web/#/home")), "/web/#/home");
+        assert_eq!(safe_return_to(Some("//evil.example")), DEFAULT_RETURN_TO);
+        assert_eq!(safe_return_to(Some("/\\evil.example")), DEFAULT_RETURN_TO);
+        assert_eq!(
+            safe_return_to(Some("https://evil.example")),
+            DEFAULT_RETURN_TO
+        );
+        assert_eq!(safe_return_to(None), DEFAULT_RETURN_TO);
+    }
+
+    fn sign(header: Value, claims: Value, signer: impl Fn(&[u8]) -> Vec<u8>) -> String {
+        let input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = URL_SAFE_NO_PAD.encode(signer(input.as_bytes()));
+        format!("{input}.{signature}")
+    }
+
+    #[test]
+    fn id_tokens_must_be_signed_by_a_published_key() {
+        use ring::signature::{self as sig, KeyPair};
+        let rng = ring::rand::SystemRandom::new();
+        let ed = sig::Ed25519KeyPair::from_pkcs8(
+            sig::Ed25519KeyPair::generate_pkcs8(&rng)
+                .unwrap()
+                .as_ref(),
+        )
+        .unwrap();
+        let ec = sig::EcdsaKeyPair::from_pkcs8(
+            &sig::ECDSA_P256_SHA256_FIXED_SIGNING,
+            sig::EcdsaKeyPair::generate_pkcs8(
+                &sig::ECDSA_P256_SHA256_FIXED_SIGNING,
+                &rng,
+            )
+            .unwrap()
+            .as_ref(),
+            &rng,
+        )
+        .unwrap();
+        let point = ec
+            .public_key()
+            .as_ref();
+        let keys = vec![
+            Jwk {
+                kty: "OKP".to_string(),
+                kid: Some("ed".to_string()),
+                crv: Some("Ed25519".to_string()),
+                x: Some(URL_SAFE_NO_PAD.encode(ed.public_key())),
+                ..Default::default()
+            },
+            Jwk {
+                kty: "EC".to_string(),
+                kid: Some("ec".to_string()),
+                crv: Some("P-256".to_string()),
+                x: Some(URL_SAFE_NO_PAD.encode(&point[1..33])),
+                y: Some(URL_SAFE_NO_PAD.encode(&point[33..])),
+                ..Default::default()
+            },
+        ];
+        let claims = json!({ "sub": "abc" });
+        let ed_sign = |input: &[u8]| {
+            ed.sign(input)
+                .as_ref()
+                .to_vec()
+        };
+        let ec_sign = |input: &[u8]| {
+            ec.sign(&rng, input)
+                .unwrap()
+                .as_ref()
+                .to_vec()
+        };
+        let verify = |token: &str| {
+            Jwt::parse(token)
+                .unwrap()
+                .verify_with_any(&keys)
+        };
+
+        let token = sign(
+            json!({ "alg": "EdDSA", "kid": "ed" }),
+            claims.clone(),
+            ed_sign,
+        );
+        assert!(verify(&token).is_ok());
+        assert_eq!(
+            Jwt::parse(&token)
+                .unwrap()
+                .claims["sub"],
+            "abc"
+        );
+        assert!(
+            verify(&sign(
+                json!({ "alg": "ES256", "kid": "ec" }),
+                claims.clone(),
+                ec_sign
+            ))
+            .is_ok()
+        );
+        assert!(
+            verify(&sign(json!({ "alg": "ES256" }), claims.clone(), ec_sign)).is_ok(),
+            "no kid"
+        );
+
+        let forged = sign(
+            json!({ "alg": "EdDSA", "kid": "ed" }),
+            json!({ "sub": "admin" }),
+            |_| vec![0; 64],
+        );
+        assert!(verify(&forged).is_err(), "bad signature");
+        let swapped = format!(
+            "{}.{}",
+            token
+                .rsplit_once('.')
+                .unwrap()
+                .0
+                .replace(
+                    &URL_SAFE_NO_PAD.encode(claims.to_string()),
+                    &URL_SAFE_NO_PAD.encode(json!({ "sub": "admin" }).to_string())
+                ),
+            token
+                .rsplit_once('.')
+                .unwrap()
+                .1
+        );
+        assert!(verify(&swapped).is_err(), "altered claims");
+        assert!(
+            verify(&sign(
+                json!({ "alg": "EdDSA", "kid": "ec" }),
+                claims.clone(),
+                ed_sign
+            ))
+            .is_err(),
+            "wrong kid"
+        );
+        assert!(
+            verify(&sign(json!({ "alg": "none" }), claims.clone(), |_| {
+                Vec::new()
+            }))
+            .is_err(),
+            "unsigned"
+        );
+        assert!(Jwt::parse("not-a-jwt").is_err());
+    }
+
+    #[test]
+    fn hmac_id_tokens_are_keyed_with_the_client_secret() {
+        use ring::hmac;
+        let token = sign(
+            json!({ "alg": "HS256" }),
+            json!({ "sub": "abc" }),
+            |input| {
+                hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, b"s3cret"), input)
+                    .as_ref()
+                    .to_vec()
+            },
+        );
+        let jwt = Jwt::parse(&token).unwrap();
+        assert!(
+            jwt.verify_with_secret("s3cret")
+                .is_ok()
+        );
+        assert!(
+            jwt.verify_with_secret("guess")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn id_token_checks() {
+        let good = json!({
+            "iss": "https://id.example/",
+            "aud": ["other", "remux"],
+            "exp": 1_000,
+            "nonce": "n",
+            "sub": "abc"
+        });
+        let check = |value: Value, now: i64| {
+            validate_id_token(&claims(value), "https://id.example", "remux", "n", now)
+        };
+        assert!(check(good.clone(), 1_000 + CLOCK_SKEW_SECS).is_ok());
+        assert!(
+            check(good.clone(), 1_001 + CLOCK_SKEW_SECS).is_err(),
+            "expired"
+        );
+
+        let mut wrong = good.clone();
+        wrong["aud"] = json!("someone-else");
+        assert!(check(wrong, 0).is_err(), "audience");
+
+        let mut wrong = good.clone();
+        wrong["iss"] = json!("https://other.example");
+        assert!(check(wrong, 0).is_err(), "issuer");
+
+        let mut wrong = good;
+        wrong["nonce"] = json!("replayed");
+        assert!(check(wrong, 0).is_err(), "nonce");
+    }
+
+    #[test]
+    fn identity_reads_the_configured_claims() {
+        let opts = OidcOptions {
+            username_claim: "email".to_string(),
+            groups_claim: "roles".to_string(),
+            ..Default::default()
+        };
+        let identity = identity_from_claims(
+            &opts,
+            &claims(json!({
+                "sub": "abc",
+                "email": " ada@example.com ",
+                "roles": ["media", "admins"]
+            })),
+        )
+        .unwrap();
+        assert_eq!(
+            identity,
+            Identity {
+                subject: "abc".to_string(),
+                username: "ada@example.com".to_string(),
+                groups: vec!["media".to_string(), "admins".to_string()],
+            }
+        );
+
+        let single = identity_from_claims(
+            &opts,
+            &claims(json!({ "sub": "abc", "email": "ada", "roles": "media" })),
+        )
+        .unwrap();
+        assert_eq!(single.groups, vec!["media".to_string()]);
+
+        assert!(
+            identity_from_claims(&opts, &claims(json!({ "sub": "abc" }))).is_err(),
+            "a missing username claim is an error"
+        );
+        assert!(
+            identity_from_claims(
+                &opts,
+                &claims(json!({ "sub": "abc", "email": "a/b" }))
+            )
+            .is_err(),
+            "the username must be one remux accepts"
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Music transcodes.
+//!
+//! Tracks are not sent through the video pipeline: only the audio stream is
+//! mapped, so embedded cover art never ends up as a video stream, the bitrate
+//! is capped per target codec, and ReplayGain can be applied as a fixed gain.
+//!
+//! Lossy encoders prepend priming samples, which a player has to trim for
+//! consecutive tracks to join without a click. The output keeps that encoder
+//! delay signalled: Opus in Ogg carries it as the pre-skip, and MP4 output
+//! (fragmented for progressive streams, fMP4 segments for HLS) as an edit
+//! list, which ffmpeg only writes when the `moov` is delayed until the first
+//! fragment. FLAC has no delay. A progressive MP3 is piped, so its LAME
+//! header cannot be finalized; clients that want gapless MP3 should use HLS.
+
+use std::path::Path;
+
+use super::engine::{DEFAULT_AUDIO_BITRATE, TranscodeParams, hls_start_number};
+
+/// Target codec of a music transcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    Opus,
+    Aac,
+    Mp3,
+    Flac,
+}
+
+impl AudioFormat {
+    /// Codec names clients put in `AudioCodec`; `None` for anything the
+    /// music pipeline does not encode, including `copy`.
+    pub fn parse(codec: &str) -> Option<Self> {
+        match codec
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "opus" | "libopus" => Some(Self::Opus),
+            "aac" | "m4a" => Some(Self::Aac),
+            "mp3" | "libmp3lame" => Some(Self::Mp3),
+            "flac" => Some(Self::Flac),
+            _ => None,
+        }
+    }
+
+    pub fn codec(self) -> &'static str {
+        match self {
+            Self::Opus => "opus",
+            Self::Aac => "aac",
+            Self::Mp3 => "mp3",
+            Self::Flac => "flac",
+        }
+    }
+
+    fn encoder(self) -> &'static str {
+        match self {
+            Self::Opus => "libopus",
+            Self::Aac => "aac",
+            Self::Mp3 => "libmp3lame",
+            Self::Flac => "flac",
+        }
+    }
+
+    /// Container of a progressive stream, as used in `stream.{container}`.
+    pub fn container(self) -> &'static str {
+        match self {
+            Self::Opus => "ogg",
+            Self::Aac => "m4a",
+            Self::Mp3 => "mp3",
+            Self::Flac => "flac",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Opus => "audio/ogg",
+            Self::Aac => "audio/mp4",
+            Self::Mp3 => "audio/mpeg",
+            Self::Flac => "audio/flac",
+        }
+    }
+
+    /// `CODECS` value of fMP4 HLS segments.
+    pub fn hls_codec(self) -> &'static str {
+        match self {
+            Self::Opus => "opus",
+            Self::Aac => "mp4a.40.2",
+            Self::Mp3 => "mp4a.40.34",
+            Self::Flac => "fLaC",
+        }
+    }
+
+    /// Bitrate to encode at for a client allowing up to `requested` bps:
+    /// the codec's default when unset, clamped to the range the encoder is
+    /// sensible in. `None` for FLAC, which is lossless.
+    pub fn bitrate(self, requested: Option<u32>) -> Option<u32> {
+        let (min, max) = match self {
+            Self::Opus => (24_000, 256_000),
+            Self::Aac => (64_000, 320_000),
+            Self::Mp3 => (64_000, 320_000),
+            Self::Flac => return None,
+        };
+        let default = match self {
+            Self::Opus => 128_000,
+            _ => 192_000,
+        };
+        Some(
+            requested
+                .unwrap_or(default)
+                .clamp(min, max),
+        )
+    }
+}
+
+/// Encoding settings of a music transcode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioTranscode {
+    pub format: AudioFormat,
+    pub bitrate: Option<u32>,
+    pub channels: Option<u32>,
+    pub sample_rate: Option<u32>,
+    /// ReplayGain track gain in dB, applied as a fixed volume change.
+    pub replay_gain: Option<f64>,
+    /// `loudnorm` when no ReplayGain is known. See
+    /// EncodingOptions::normalize_audio_loudness.
+    pub normalize_loudness: bool,
+}
+
+impl AudioTranscode {
+    /// Bandwidth advertised in the HLS master playlist.
+    pub fn bandwidth(&self) -> u32 {
+        self.bitrate
+            .unwrap_or(match self.format {
+                AudioFormat::Flac => 1_000_000,
+                _ => DEFAULT_AUDIO_BITRATE,
+            })
+    }
+
+    fn filter(&self) -> Option<String> {
+        match self.replay_gain {
+            // Positive gain can push peaks over full scale.
+            Some(gain) if gain > 0.0 => Some(format!(
+                "volume={gain:.2}dB:precision=float,alimiter=limit=0.95:level=false"
+            )),
+            Some(gain) => Some(format!("volume={gain:.2}dB:precision=float")),
+            None if self.normalize_loudness => {
+                Some("loudnorm=I=-14:TP=-1:LRA=11".into())
+            }
+            None => None,
+        }
+    }
+}
+
+/// A progressive music transcode piped to the client.
+#[derive(Debug, Clone)]
+pub struct AudioStreamParams {
+    pub input_url: String,
+    pub start_time_ticks: Option<i64>,
+    pub audio_stream_index: Option<i32>,
+    pub audio: AudioTranscode,
+}
+
+/// ffmpeg args of a progressive music transcode written to stdout.
+pub fn build_progressive_args(params: &AudioStreamParams) -> Vec<String> {
+    let mut args = input_args(
+        &params.input_url,
+        params.start_time_ticks,
+        params.audio_stream_index,
+        false,
+    );
+    args.extend(encode_args(&params.audio));
+    match params
+        .audio
+        .format
+    {
+        AudioFormat::Opus => args.extend(["-f".into(), "ogg".into()]),
+        AudioFormat::Aac => args.extend([
+            "-f".into(),
+            "mp4".into(),
+            "-frag_duration".into(),
+            "1000000".into(),
+            "-movflags".into(),
+            "+delay_moov+default_base_moof".into(),
+        ]),
+        AudioFormat::Mp3 => args.extend(["-f".into(), "mp3".into()]),
+        AudioFormat::Flac => args.extend(["-f".into(), "flac".into()]),
+    }
+    args.push("pipe:1".into());
+    args
+}
+
+/// ffmpeg args of a music HLS transcode. Segments are always fMP4, so the
+/// init segment's edit list carries the encoder delay. Source timestamps are
+/// kept, as for video, so a run started by a seek lines up with the playlist.
+pub fn build_hls_args(params: &TranscodeParams, audio: &AudioTranscode) -> Vec<String> {
+    let mut args = input_args(
+        &params.input_url,
+        params.start_time_ticks,
+        params.audio_stream_index,
+        true,
+    );
+    args.extend(encode_args(audio));
+    let start_number = hls_start_number(params);
+    args.extend([
+        "-f".into(),
+        "hls".into(),
+        "-hls_time".into(),
+        params
+            .segment_length
+            .to_string(),
+        "-start_number".into(),
+        start_number.to_string(),
+        "-hls_segment_type".into(),
+        "fmp4".into(),
+        "-hls_fmp4_init_filename".into(),
+        "init.mp4".into(),
+        "-hls_segment_filename".into(),
+        path_arg(
+            &params
+                .output_dir
+                .join("segment_%05d.m4s"),
+        ),
+        "-hls_playlist_type".into(),
+        "event".into(),
+        "-hls_list_size".into(),
+        "0".into(),
+        path_arg(
+            &params
+                .output_dir
+                .join("main.m3u8"),
+        ),
+    ]);
+    args
+}
+
+fn input_args(
+    input_url: &str,
+    start_time_ticks: Option<i64>,
+    audio_stream_index: Option<i32>,
+    copyts: bool,
+) -> Vec<String> {
+    let mut args: Vec<String> = vec![
+        "-v".into(),
+        "error".into(),
+        "-reconnect".into(),
+        "1".into(),
+        "-reconnect_streamed".into(),
+        "1".into(),
+        "-reconnect_delay_max".into(),
+        "5".into(),
+    ];
+    if let Some(ticks) = start_time_ticks.filter(|t| *t > 0) {
+        args.extend(["-ss".into(), format!("{:.6}", ticks as f64 / 10_000_000.0)]);
+    }
+    if copyts {
+        args.push("-copyts".into());
+    }
+    args.extend(["-i".into(), input_url.to_string()]);
+    if copyts {
+        args.extend(["-avoid_negative_ts".into(), "disabled".into()]);
+    }
+    args.extend([
+        "-map".into(),
+        audio_stream_index
+            .map_or_else(|| "0:a:0".to_string(), |idx| format!("0:{idx}")),
+        "-map_metadata".into(),
+        "-1".into(),
+    ]);
+    args
+}
+
+fn encode_args(audio: &AudioTranscode) -> Vec<String> {
+    let mut args: Vec<String> = vec![
+        "-c:a".into(),
+        audio
+            .format
+            .encoder()
+            .into(),
+    ];
+    if let Some(bitrate) = audio.bitrate {
+        args.extend(["-b:a".into(), bitrate.to_string()]);
+    }
+    if let Some(channels) = audio.channels {
+        args.extend(["-ac".into(), channels.to_string()]);
+    }
+    // libopus only encodes at 48 kHz and its divisors; ffmpeg resamples.
+    if let Some(rate) = audio
+        .sample_rate
+        .filter(|_| audio.format != AudioFormat::Opus)
+    {
+        args.extend(["-ar".into(), rate.to_string()]);
+    }
+    if let Some(filter) = audio.filter() {
+        args.extend(["-af".into(), filter]);
+    }
+    // Opus and FLAC in MP4 are still flagged experimental in older ffmpeg.
+    if matches!(audio.format, AudioFormat::Opus | AudioFormat::Flac) {
+        args.extend(["-strict".into(), "-2".into()]);
+    }
+    args
+}
+
+fn path_arg(path: &Path) -> String {
+    path.to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn opus() -> AudioTranscode {
+        AudioTranscode {
+            format: AudioFormat::Opus,
+            bitrate: AudioFormat::Opus.bitrate(Some(64_000)),
+            channels: Some(2),
+            sample_rate: None,
+            replay_gain: None,
+            normalize_loudness: false,
+        }
+    }
+
+    fn arg_after<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        args.iter()
+            .position(|a| a == flag)
+            .and_then(|i| args.get(i + 1))
+            .map(String::as_str)
+    }
+
+    #[test]
+    fn bitrates_are_capped_per_codec() {
+        assert_eq!(AudioFormat::Opus.bitrate(Some(64_000)), Some(64_000));
+        assert_eq!(AudioFormat::Opus.bitrate(Some(8_000)), Some(24_000));
+        assert_eq!(AudioFormat::Opus.bitrate(None), Some(128_000));
+        assert_eq!(AudioFormat::Mp3.bitrate(Some(140_000_000)), Some(320_000));
+        assert_eq!(AudioFormat::Flac.bitrate(Some(320_000)), None);
+        assert_eq!(AudioFormat::parse("libopus"), Some(AudioFormat::Opus));
+        assert_eq!(AudioFormat::parse("copy"), None);
+    }
+
+    #[test]
+    fn progressive_opus_maps_only_the_audio_stream() {
+        let args = build_progressive_args(&AudioStreamParams {
+            input_url: "http://127.0.0.1/track.flac".into(),
+            start_time_ticks: Some(300_000_000),
+            audio_stream_index: None,
+            audio: opus(),
+        });
+        assert_eq!(arg_after(&args, "-ss"), Some("30.000000"));
+        assert_eq!(arg_after(&args, "-map"), Some("0:a:0"));
+        assert_eq!(arg_after(&args, "-c:a"), Some("libopus"));
+        assert_eq!(arg_after(&args, "-b:a"), Some("64000"));
+        assert_eq!(arg_after(&args, "-f"), Some("ogg"));
+        assert!(!args.contains(&"-af".to_string()));
+        assert_eq!(
+            args.last()
+                .map(String::as_str),
+            Some("pipe:1")
+        );
+    }
+
+    #[test]
+    fn progressive_aac_delays_the_moov_for_an_edit_list() {
+        let args = build_progressive_args(&AudioStreamParams {
+            input_url: "http://127.0.0.1/track.flac".into(),
+            start_time_ticks: None,
+            audio_stream_index: Some(1),
+            audio: AudioTranscode {
+                format: AudioFormat::Aac,
+                bitrate: AudioFormat::Aac.bitrate(None),
+                replay_gain: Some(-6.5),
+                ..opus()
+            },
+        });
+        assert_eq!(arg_after(&args, "-map"), Some("0:1"));
+        assert_eq!(arg_after(&args, "-f"), Some("mp4"));
+        assert_eq!(
+            arg_after(&args, "-movflags"),
+            Some("+delay_moov+default_base_moof")
+        );
+        assert_eq!(
+            arg_after(&args, "-af"),
+            Some("volume=-6.50dB:precision=float")
+        );
+    }
+
+    #[test]
+    fn replay_gain_wins_over_loudness_normalization() {
+        let boosted = AudioTranscode {
+            replay_gain: Some(2.0),
+            normalize_loudness: true,
+            ..opus()
+        };
+        assert_eq!(
+            boosted
+                .filter()
+                .as_deref(),
+            Some("volume=2.00dB:precision=float,alimiter=limit=0.95:level=false")
+        );
+        let unknown = AudioTranscode {
+            normalize_loudness: true,
+            ..opus()
+        };
+        assert_eq!(
+            unknown
+                .filter()
+                .as_deref(),
+            Some("loudnorm=I=-14:TP=-1:LRA=11")
+        );
+    }
+
+    #[test]
+    fn hls_writes_fmp4_segments() {
+        let params = TranscodeParams {
+            input_url: "http://127.0.0.1/track.flac".into(),
+            output_dir: PathBuf::from("/tmp/test_audio_hls"),
+            start_time_ticks: Some(120_000_000),
+            ..Default::default()
+        };
+        let args = build_hls_args(&params, &opus());
+        assert_eq!(arg_after(&args, "-hls_segment_type"), Some("fmp4"));
+        assert_eq!(arg_after(&args, "-start_number"), Some("2"));
+        assert_eq!(
+            arg_after(&args, "-hls_segment_filename"),
+            Some("/tmp/test_audio_hls/segment_%05d.m4s")
+        );
+        assert!(args.contains(&"-copyts".to_string()));
+        assert!(!args.contains(&"-c:v".to_string()));
+    }
+}
@@ -136,14 +136,41 @@ fn build_audio_transcode(
         .start_time_ticks
         .map(|t| format!("&StartTimeTicks={t}"))
         .unwrap_or_default();
+    let bitrate = if audio_codec == "copy" {
+        None
+    } else {
+        cfg.device_profile
+            .as_ref()
+            .and_then(|p| p.music_transcoding_bitrate(&audio_codec))
+            .into_iter()
+            .chain(cfg.max_bitrate)
+            .min()
+    }
+    .map(|b| format!("&AudioBitRate={b}"))
+    .unwrap_or_default();
+    let hls = trans_profile
+        .and_then(|p| {
+            p.protocol
+                .as_deref()
+        })
+        .is_some_and(|p| p.eq_ignore_ascii_case("hls"));
+    let (path, sub_protocol) = if hls {
+        (
+            format!("master.m3u8?PlaySessionId={}&", cfg.play_session_id),
+            "hls",
+        )
+    } else {
+        (format!("stream.{container}?"), "http")
+    };
 
     TranscodeOutcome {
         url: format!(
-            "/videos/{}/stream.{}?MediaSourceId={}&AudioCodec={}{}&ApiKey={}",
+            "/audio/{}/{}MediaSourceId={}&AudioCodec={}{}{}&ApiKey={}",
             cfg.item_id,
-            container,
+            path,
             source.id,
             audio_codec,
+            bitrate,
             start_time,
             session
                 .device
@@ -151,7 +178,7 @@ fn build_audio_transcode(
                 .expose(),
         ),
         container,
-        sub_protocol: "http".to_string(),
+        sub_protocol: sub_protocol.to_string(),
     }
 }
 
@@ -284,46 +311,32 @@ fn build_video_transcode(
         .map(|t| format!("&StartTimeTicks={t}"))
         .unwrap_or_default();
 
-    let url = if protocol.eq_ignore_ascii_case("hls") {
-        format!(
-            "/videos/{}/master.m3u8?PlaySessionId={}&MediaSourceId={}&VideoCodec={}&AudioCodec={}{}{}{}{}{}{}&ApiKey={}",
-            cfg.item_id,
-            cfg.play_session_id,
-            source.id,
-            video_codec,
-            audio_codec,
-            bitrate,
-            reasons_param,
-            audio_idx,
-            sub_idx,
-            sub_method,
-            start_time,
-            session
-                .device
-                .access_token
-                .expose(),
-        )
+    let file = if protocol.eq_ignore_ascii_case("hls") {
+        "master.m3u8".to_string()
+    } else if protocol.eq_ignore_ascii_case("dash") {
+        "master.mpd".to_string()
     } else {
-        format!(
-            "/videos/{}/stream.{}?PlaySessionId={}&MediaSourceId={}&VideoCodec={}&AudioCodec={}{}{}{}{}{}{}&ApiKey={}",
-            cfg.item_id,
-            container,
-            cfg.play_session_id,
-            source.id,
-            video_codec,
-            audio_codec,
-            bitrate,
-            reasons_param,
-            audio_idx,
-            sub_idx,
-            sub_method,
-            start_time,
-            session
-                .device
-                .access_token
-                .expose(),
-        )
+        format!("stream.{container}")
     };
+    let url = format!(
+        "/videos/{}/{}?PlaySessionId={}&MediaSourceId={}&VideoCodec={}&AudioCodec={}{}{}{}{}{}{}&ApiKey={}",
+        cfg.item_id,
+        file,
+        cfg.play_session_id,
+        source.id,
+        video_codec,
+        audio_codec,
+        bitrate,
+        reasons_param,
+        audio_idx,
+        sub_idx,
+        sub_method,
+        start_time,
+        session
+            .device
+            .access_token
+            .expose(),
+    );
 
     TranscodeDecision::Transcode(TranscodeOutcome {
         url,
@@ -609,6 +622,141 @@ mod tests {
         }
     }
 
+    #[test]
+    fn dash_transcoding_profile_gets_the_mpd_url() {
+        let session = db::auth::AuthSession {
+            device: db::auth::Device {
+                access_token: "tok"
+                    .to_string()
+                    .into(),
+                ..Default::default()
+            },
+            user: db::User::default(),
+        };
+        let mut cfg = base_cfg(EncodingOptions::default());
+        cfg.device_profile = Some(api::DeviceProfile {
+            transcoding_profiles: vec![api::TranscodingProfile {
+                container: Some("mp4".to_string()),
+                protocol: Some("dash".to_string()),
+                type_: Some("Video".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let mut reasons = api::TranscodeReasons::default();
+        reasons.insert(api::TranscodeReason::VideoCodecNotSupported(
+            "hevc".to_string(),
+        ));
+        let TranscodeDecision::Transcode(outcome) = build_transcode_decision(
+            &make_video_source("mkv"),
+            &reasons,
+            None,
+            &force_transcode_query(),
+            &session,
+            &cfg,
+        ) else {
+            panic!("expected transcode outcome");
+        };
+        assert!(
+            outcome
+                .url
+                .starts_with(&format!(
+                    "/videos/{}/master.mpd?PlaySessionId=s&",
+                    cfg.item_id
+                )),
+            "{}",
+            outcome.url
+        );
+        assert_eq!(outcome.container, "mp4");
+        assert_eq!(outcome.sub_protocol, "dash");
+    }
+
+    #[test]
+    fn music_transcode_is_capped_by_the_device_profile() {
+        let session = db::auth::AuthSession {
+            device: db::auth::Device {
+                access_token: "tok"
+                    .to_string()
+                    .into(),
+                ..Default::default()
+            },
+            user: db::User::default(),
+        };
+        let mut cfg = base_cfg(EncodingOptions::default());
+        cfg.device_profile = Some(api::DeviceProfile {
+            music_streaming_transcoding_bitrate: Some(192_000),
+            transcoding_profiles: vec![api::TranscodingProfile {
+                container: Some("ogg".to_string()),
+                protocol: Some("http".to_string()),
+                audio_codec: Some("opus".to_string()),
+                type_: Some("Audio".to_string()),
+                ..Default::default()
+            }],
+            codec_profiles: vec![api::CodecProfile {
+                type_: Some("Audio".to_string()),
+                codec: Some("opus".to_string()),
+                conditions: vec![api::ProfileCondition {
+                    condition: Some("LessThanEqual".to_string()),
+                    property: Some("AudioBitrate".to_string()),
+                    value: Some("96000".to_string()),
+                    is_required: None,
+                }],
+            }],
+            ..Default::default()
+        });
+        let source = api::MediaSourceInfo {
+            id: Uuid::new_v4(),
+            media_streams: vec![api::MediaStream {
+                codec: Some("flac".to_string()),
+                type_: Some(api::MediaStreamType::Audio),
+                index: 0,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let decide = |cfg: &PlaybackConfig| match build_transcode_decision(
+            &source,
+            &api::TranscodeReasons::default(),
+            None,
+            &force_transcode_query(),
+            &session,
+            cfg,
+        ) {
+            TranscodeDecision::Transcode(outcome) => outcome,
+            TranscodeDecision::DirectPlay => panic!("expected transcode outcome"),
+        };
+
+        let outcome = decide(&cfg);
+        assert_eq!(
+            outcome.url,
+            format!(
+                "/audio/{}/stream.ogg?MediaSourceId={}&AudioCodec=opus&AudioBitRate=96000&ApiKey=tok",
+                cfg.item_id, source.id
+            )
+        );
+        assert_eq!(outcome.sub_protocol, "http");
+
+        // An HLS profile gets the music HLS playlist.
+        if let Some(profile) = cfg
+            .device_profile
+            .as_mut()
+        {
+            profile.transcoding_profiles[0].protocol = Some("hls".to_string());
+        }
+        let outcome = decide(&cfg);
+        assert!(
+            outcome
+                .url
+                .starts_with(&format!(
+                    "/audio/{}/master.m3u8?PlaySessionId=s&",
+                    cfg.item_id
+                )),
+            "{}",
+            outcome.url
+        );
+        assert_eq!(outcome.sub_protocol, "hls");
+    }
+
     #[test]
     fn remuxing_disabled_by_policy_returns_direct_play() {
         let mut policy = remux_sdks::remux::UserPolicy::default();
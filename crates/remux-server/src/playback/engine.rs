@@ -15,11 +15,17 @@ use tracing::{debug, error, info, warn};
 use crate::{
     common::{HideConsole, TickUnit, ToRunTimeTicks},
     device_profile::{AudioCodec, VideoCodec},
-    playback::hw_accel::{Accelerator, NoAccel},
+    playback::hw_accel::{self, Accelerator, NoAccel},
+};
+use remux_sdks::remux::{
+    EncodingOptions, EncodingPreset, HardwareAccelerationType, VideoRangeType,
 };
-use remux_sdks::remux::{EncodingPreset, HardwareAccelerationType, VideoRangeType};
 
-use super::session::{TranscodeSession, TranscodeState};
+use super::{
+    audio::{self, AudioTranscode},
+    segment_map::SegmentMap,
+    session::{Rendition, TranscodeSession, TranscodeState},
+};
 
 pub async fn detect_hardware_acceleration() -> HardwareAccelerationType {
     let detected = probe_hw_accel().await;
@@ -136,10 +142,21 @@ pub(crate) fn select_hw_accel(
     }
 }
 
-/// Max seconds to buffer ahead of the current playback position.
+/// Seconds the encoder may run ahead of the client's last segment request
+/// before it is paused.
 const MAX_BUFFER_SECS: u32 = 300;
-/// Seconds behind the playback position before a segment is eligible for deletion.
+/// Seconds behind the client's last segment request before a segment is
+/// eligible for deletion.
 const SEGMENT_KEEP_SECS: u32 = 30;
+/// `-b:a` when transcoding audio without a requested bitrate.
+pub(crate) const DEFAULT_AUDIO_BITRATE: u32 = 128_000;
+/// ABR ladder rungs as (height, video bitrate in bps), tallest first.
+const ABR_LADDER: &[(u32, u32)] = &[
+    (1080, 6_000_000),
+    (720, 3_000_000),
+    (480, 1_500_000),
+    (360, 800_000),
+];
 
 fn ffmpeg_bin() -> String {
     std::env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".into())
@@ -153,15 +170,37 @@ fn send_signal(pid: u32, sig: libc::c_int) {
 #[cfg(not(unix))]
 fn send_signal(_pid: u32, _sig: i32) {}
 
-/// Spawn the buffer-throttle task. It pauses/resumes ffmpeg so it never
-/// encodes more than MAX_BUFFER_SECS ahead of what the client has requested.
+/// Whether the encoder should be paused, given the segments on disk and the
+/// client's last segment request. Pauses once `MAX_BUFFER_SECS` are buffered
+/// past that request and resumes two segments below, so ffmpeg is not
+/// signalled on every check.
+fn throttle(
+    map: &SegmentMap,
+    last_requested: u32,
+    segment_length: u32,
+    paused: bool,
+) -> bool {
+    let max_ahead = (MAX_BUFFER_SECS / segment_length.max(1)).max(3);
+    let ahead = map.buffered_after(last_requested);
+    if paused {
+        ahead >= max_ahead - 2
+    } else {
+        ahead >= max_ahead
+    }
+}
+
+/// Spawn the buffer-throttle task. Like Jellyfin's throttling it pauses
+/// ffmpeg (SIGSTOP) once it is far enough ahead of the segments the client
+/// requests and resumes it (SIGCONT) as the client catches up. Live streams
+/// are never paused. Segments well behind the client are deleted.
 fn spawn_buffer_monitor(
     output_dir: PathBuf,
     segment_length: u32,
-    playback_offset_secs: Arc<AtomicU32>,
+    last_segment_index: Arc<AtomicU32>,
     ffmpeg_pid: Arc<AtomicU32>,
     mut stop_rx: tokio::sync::oneshot::Receiver<()>,
     play_session_id: String,
+    is_live: bool,
 ) {
     tokio::spawn(async move {
         let mut paused = false;
@@ -174,37 +213,35 @@ fn spawn_buffer_monitor(
             ticks += 1;
 
             let pid = ffmpeg_pid.load(Ordering::Relaxed);
-            let produced = count_segments(&output_dir);
-            let buffered_secs = produced * segment_length;
-            // playback_offset_secs is how far the client has actually played
-            // relative to the start of this transcode session (from progress reports).
-            let playback_secs = playback_offset_secs.load(Ordering::Relaxed);
-
-            let ahead = buffered_secs.saturating_sub(playback_secs);
+            let mut map = SegmentMap::scan(&output_dir);
+            let last_requested = last_segment_index.load(Ordering::Relaxed);
 
-            if pid != 0 && !paused && ahead >= MAX_BUFFER_SECS {
-                debug!(play_session_id, pid, ahead, "Buffer full — pausing ffmpeg");
-                #[cfg(unix)]
-                send_signal(pid, libc::SIGSTOP);
-                paused = true;
-            } else if pid != 0
-                && paused
-                && ahead < MAX_BUFFER_SECS.saturating_sub(segment_length * 2)
-            {
-                debug!(
-                    play_session_id,
-                    pid, ahead, "Buffer drained — resuming ffmpeg"
-                );
-                #[cfg(unix)]
-                send_signal(pid, libc::SIGCONT);
-                paused = false;
+            let pause =
+                !is_live && throttle(&map, last_requested, segment_length, paused);
+            if pid != 0 && pause != paused {
+                if pause {
+                    debug!(
+                        play_session_id,
+                        pid, last_requested, "Buffer full — pausing ffmpeg"
+                    );
+                    #[cfg(unix)]
+                    send_signal(pid, libc::SIGSTOP);
+                } else {
+                    debug!(
+                        play_session_id,
+                        pid, last_requested, "Buffer drained — resuming ffmpeg"
+                    );
+                    #[cfg(unix)]
+                    send_signal(pid, libc::SIGCONT);
+                }
+                paused = pause;
             }
 
-            // Every 30 seconds, delete segments that are more than SEGMENT_KEEP_SECS
-            // behind the current playback position.
-            if ticks % 30 == 0 && playback_secs > SEGMENT_KEEP_SECS {
-                let cutoff_idx = (playback_secs - SEGMENT_KEEP_SECS) / segment_length;
-                delete_old_segments(&output_dir, cutoff_idx);
+            // Every 30 seconds, delete segments that are more than
+            // SEGMENT_KEEP_SECS behind the client's last request.
+            if ticks % 30 == 0 {
+                let keep = SEGMENT_KEEP_SECS / segment_length.max(1);
+                map.delete_before(&output_dir, last_requested.saturating_sub(keep));
             }
         }
 
@@ -219,49 +256,6 @@ fn spawn_buffer_monitor(
     });
 }
 
-/// Delete segment files whose index is less than `cutoff_idx`.
-fn delete_old_segments(dir: &PathBuf, cutoff_idx: u32) {
-    let Ok(entries) = std::fs::read_dir(dir) else {
-        return;
-    };
-    for entry in entries.flatten() {
-        let name = entry.file_name();
-        let name = name.to_string_lossy();
-        // segment_00042.ts / segment_00042.m4s / etc. — strip everything after the last '_'
-        let Some(idx_str) = name
-            .rsplit('_')
-            .next()
-            .and_then(|s| {
-                s.split('.')
-                    .next()
-            })
-        else {
-            continue;
-        };
-        let Ok(idx) = idx_str.parse::<u32>() else {
-            continue;
-        };
-        if idx < cutoff_idx {
-            let _ = std::fs::remove_file(entry.path());
-        }
-    }
-}
-
-fn count_segments(dir: &PathBuf) -> u32 {
-    std::fs::read_dir(dir)
-        .map(|entries| {
-            entries
-                .flatten()
-                .filter(|e| {
-                    e.file_name()
-                        .to_string_lossy()
-                        .ends_with(".ts")
-                })
-                .count() as u32
-        })
-        .unwrap_or(0)
-}
-
 /// Parameters for starting a new HLS transcode job.
 pub struct TranscodeParams {
     pub input_url: String,
@@ -313,6 +307,10 @@ pub struct TranscodeParams {
     pub h265_crf: u32,
     /// True for live TV / RTSP streams — disables seeking and enables auto-restart on exit.
     pub is_live: bool,
+    /// Write fMP4 segments even where MPEG-TS would do, as DASH needs them.
+    pub fmp4: bool,
+    /// Music transcode: only the audio stream is encoded, see `audio`.
+    pub audio: Option<AudioTranscode>,
     /// Apply `loudnorm=I=-14:TP=-1:LRA=11` when transcoding audio. Has no effect
     /// when audio_codec is "copy". See EncodingOptions::normalize_audio_loudness.
     pub normalize_audio_loudness: bool,
@@ -352,6 +350,8 @@ impl Default for TranscodeParams {
             h264_crf: 23,
             h265_crf: 28,
             is_live: false,
+            fmp4: false,
+            audio: None,
             normalize_audio_loudness: false,
         }
     }
@@ -477,6 +477,9 @@ fn is_hdr(range_type: Option<&VideoRangeType>) -> bool {
 
 /// Build the ffmpeg CLI args for an HLS transcode.
 pub(crate) fn build_hls_args(params: &TranscodeParams) -> Vec<String> {
+    if let Some(audio) = &params.audio {
+        return audio::build_hls_args(params, audio);
+    }
     let accel = params
         .accelerator
         .as_ref();
@@ -836,6 +839,20 @@ pub(crate) fn build_hls_args(params: &TranscodeParams) -> Vec<String> {
         args.extend(["-b:v".into(), bitrate.to_string()]);
     }
 
+    // Keyframe on every segment boundary, counted from the start of the
+    // media (-copyts keeps source timestamps), so every encoder run cuts the
+    // same segments and a seek can reuse what earlier runs wrote.
+    if ffmpeg_video_codec != "copy" && !params.is_live {
+        args.extend([
+            "-force_key_frames:0".into(),
+            format!(
+                "expr:gte(t,{}+n_forced*{})",
+                hls_start_number(params) * params.segment_length,
+                params.segment_length
+            ),
+        ]);
+    }
+
     // Audio codec
     args.extend(["-c:a".into(), ffmpeg_audio_codec.into()]);
     if ffmpeg_audio_codec == "copy" {
@@ -857,7 +874,7 @@ pub(crate) fn build_hls_args(params: &TranscodeParams) -> Vec<String> {
     } else {
         let audio_bitrate = params
             .audio_bitrate
-            .unwrap_or(128_000);
+            .unwrap_or(DEFAULT_AUDIO_BITRATE);
         args.extend(["-b:a".into(), audio_bitrate.to_string()]);
         if let Some(ch) = params.audio_channels {
             args.extend(["-ac".into(), ch.to_string()]);
@@ -871,17 +888,13 @@ pub(crate) fn build_hls_args(params: &TranscodeParams) -> Vec<String> {
     let playlist = params
         .output_dir
         .join("main.m3u8");
-    let seg_ext = if is_hevc_copy { "m4s" } else { "ts" };
+    let fmp4 = is_hevc_copy || params.fmp4;
+    let seg_ext = if fmp4 { "m4s" } else { "ts" };
     let segment = params
         .output_dir
         .join(format!("segment_%05d.{}", seg_ext));
 
-    let start_number = params
-        .start_time_ticks
-        .map(|t| {
-            (t as f64 / 10_000_000.0 / params.segment_length as f64).floor() as u32
-        })
-        .unwrap_or(0);
+    let start_number = hls_start_number(params);
 
     args.extend([
         "-f".into(),
@@ -902,7 +915,7 @@ pub(crate) fn build_hls_args(params: &TranscodeParams) -> Vec<String> {
         "0".into(),
     ]);
 
-    if is_hevc_copy {
+    if fmp4 {
         // Apple HLS spec: HEVC must be delivered in fMP4/CMAF segments, not MPEG-TS.
         // Use just the filename for init; ffmpeg places it alongside the segments.
         //
@@ -929,6 +942,16 @@ pub(crate) fn build_hls_args(params: &TranscodeParams) -> Vec<String> {
     args
 }
 
+/// Index of the first segment a run starting at `start_time_ticks` writes.
+pub(crate) fn hls_start_number(params: &TranscodeParams) -> u32 {
+    params
+        .start_time_ticks
+        .map(|t| {
+            (t as f64 / 10_000_000.0 / params.segment_length as f64).floor() as u32
+        })
+        .unwrap_or(0)
+}
+
 /// Spawn an ffmpeg process, drain its stderr to DEBUG logs, and wait for it
 /// to finish (or be killed via `kill_rx`).  Returns the exit result and the
 /// accumulated stderr text for error reporting.
@@ -1062,11 +1085,12 @@ pub async fn start_transcode(
                     s.output_dir
                         .clone(),
                     s.segment_length,
-                    s.playback_offset_secs
+                    s.last_segment_index
                         .clone(),
                     ffmpeg_pid.clone(),
                     monitor_stop_rx,
                     s.id.clone(),
+                    params.is_live,
                 );
                 s.output_dir
                     .clone()
@@ -1242,6 +1266,66 @@ pub struct ProgressiveTranscodeParams {
     pub normalize_audio_loudness: bool,
 }
 
+impl ProgressiveTranscodeParams {
+    /// A remux of `input_url` into MP4 with the server's encoder settings;
+    /// callers override the stream-specific fields.
+    pub fn new(input_url: String, opts: &EncodingOptions) -> Self {
+        Self {
+            input_url,
+            container: "mp4".into(),
+            video_codec: "copy".into(),
+            audio_codec: "aac".into(),
+            start_time_ticks: None,
+            max_width: None,
+            max_height: None,
+            video_bitrate: None,
+            audio_bitrate: None,
+            audio_channels: None,
+            audio_stream_index: None,
+            subtitle_stream_index: None,
+            burn_subtitle: false,
+            subtitle_width: None,
+            subtitle_height: None,
+            encoding_preset: opts.encoding_preset,
+            source_video_codec: None,
+            source_audio_codec: None,
+            accelerator: hw_accel::from_encoding_opts(opts),
+            source_video_range_type: None,
+            enable_tonemapping: opts
+                .enable_tonemapping
+                .unwrap_or(false),
+            enable_vpp_tonemapping: opts
+                .enable_vpp_tonemapping
+                .unwrap_or(false),
+            tonemapping_algorithm: opts
+                .tonemapping_algorithm
+                .clone()
+                .unwrap_or_else(|| "hable".to_string()),
+            tonemapping_desat: opts
+                .tonemapping_desat
+                .unwrap_or(0.0),
+            tonemapping_peak: opts
+                .tonemapping_peak
+                .unwrap_or(0.0),
+            allow_hevc_encoding: opts
+                .allow_hevc_encoding
+                .unwrap_or(false),
+            allow_av1_encoding: opts
+                .allow_av1_encoding
+                .unwrap_or(false),
+            h264_crf: opts
+                .h264_crf
+                .unwrap_or(23),
+            h265_crf: opts
+                .h265_crf
+                .unwrap_or(28),
+            normalize_audio_loudness: opts
+                .normalize_audio_loudness
+                .unwrap_or(false),
+        }
+    }
+}
+
 /// Build the ffmpeg CLI args for a progressive transcode piped to stdout.
 pub(crate) fn build_progressive_args(
     params: &ProgressiveTranscodeParams,
@@ -1637,10 +1721,50 @@ pub fn start_progressive_transcode(
 > {
     let args = build_progressive_args(&params);
     debug!("ffmpeg progressive args: {:?}", args);
+    info!(
+        "Starting progressive transcode (container={}, vcodec={}, acodec={})",
+        params.container, params.video_codec, params.audio_codec
+    );
+    spawn_piped_ffmpeg(
+        args,
+        params
+            .accelerator
+            .env_overrides(),
+    )
+}
 
-    let env_overrides = params
-        .accelerator
-        .env_overrides();
+/// Start a progressive music transcode that returns a readable byte stream.
+pub fn start_audio_transcode(
+    params: audio::AudioStreamParams,
+) -> Result<
+    impl futures::Stream<Item = std::result::Result<bytes::Bytes, std::io::Error>>,
+> {
+    let args = audio::build_progressive_args(&params);
+    debug!("ffmpeg audio args: {:?}", args);
+    info!(
+        "Starting audio transcode (codec={}, bitrate={:?}, replay_gain={:?})",
+        params
+            .audio
+            .format
+            .codec(),
+        params
+            .audio
+            .bitrate,
+        params
+            .audio
+            .replay_gain
+    );
+    spawn_piped_ffmpeg(args, Vec::new())
+}
+
+/// Spawn ffmpeg writing to stdout and stream its output; stderr is logged
+/// and the child reaped in the background.
+fn spawn_piped_ffmpeg(
+    args: Vec<String>,
+    env_overrides: Vec<(&'static str, String)>,
+) -> Result<
+    impl futures::Stream<Item = std::result::Result<bytes::Bytes, std::io::Error>>,
+> {
     let mut cmd = tokio::process::Command::new(ffmpeg_bin());
     cmd.hide_console();
     cmd.args(&args)
@@ -1694,12 +1818,105 @@ pub fn start_progressive_transcode(
         }
     });
 
-    info!(
-        "Starting progressive transcode (container={}, vcodec={}, acodec={})",
-        params.container, params.video_codec, params.audio_codec
+    Ok(tokio_util::io::ReaderStream::new(stdout))
+}
+
+/// Run a progressive transcode into `output` instead of stdout, calling
+/// `on_progress` with the encoded position in seconds as ffmpeg reports it.
+///
+/// `args` are as built by [`build_progressive_args`] or
+/// [`audio::build_progressive_args`]. Dropping the future kills ffmpeg.
+pub async fn transcode_to_file(
+    mut args: Vec<String>,
+    env_overrides: Vec<(&'static str, String)>,
+    output: &std::path::Path,
+    mut on_progress: impl FnMut(f64),
+) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    // A file can be seeked, so MP4 gets its moov up front rather than being
+    // fragmented for a pipe.
+    if args
+        .last()
+        .is_some_and(|a| a == "pipe:1")
+    {
+        args.pop();
+    }
+    for arg in args.iter_mut() {
+        if arg == "frag_keyframe+empty_moov+default_base_moof" {
+            *arg = "+faststart".into();
+        }
+    }
+    args.splice(
+        0..0,
+        ["-nostdin", "-y", "-nostats", "-progress", "pipe:1"].map(String::from),
     );
+    args.push(
+        output
+            .to_string_lossy()
+            .into_owned(),
+    );
+    debug!("ffmpeg file transcode args: {:?}", args);
 
-    Ok(tokio_util::io::ReaderStream::new(stdout))
+    let mut cmd = tokio::process::Command::new(ffmpeg_bin());
+    cmd.hide_console();
+    cmd.kill_on_drop(true);
+    cmd.args(&args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    for (k, v) in env_overrides {
+        cmd.env(k, v);
+    }
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("Failed to spawn ffmpeg: {}", e))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("Failed to capture ffmpeg stdout"))?;
+    let mut stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| anyhow!("Failed to capture ffmpeg stderr"))?;
+    let stderr_task = tokio::spawn(async move {
+        let mut buf = String::new();
+        let _ = stderr
+            .read_to_string(&mut buf)
+            .await;
+        buf
+    });
+
+    let mut lines = tokio::io::BufReader::new(stdout).lines();
+    while let Some(line) = lines
+        .next_line()
+        .await?
+    {
+        if let Some(secs) = parse_progress_time(&line) {
+            on_progress(secs);
+        }
+    }
+
+    let status = child
+        .wait()
+        .await?;
+    let stderr = stderr_task
+        .await
+        .unwrap_or_default();
+    if !status.success() {
+        return Err(anyhow!("ffmpeg failed ({status}): {}", stderr.trim()));
+    }
+    Ok(())
+}
+
+/// The encoded position in a `-progress` report line, in seconds.
+fn parse_progress_time(line: &str) -> Option<f64> {
+    let us: i64 = line
+        .strip_prefix("out_time_us=")?
+        .trim()
+        .parse()
+        .ok()?;
+    (us >= 0).then(|| us as f64 / 1_000_000.0)
 }
 
 /// Generate the variant (child) HLS playlist server-side as a VOD playlist.
@@ -1832,12 +2049,73 @@ fn hevc_hls_codec_string(profile: Option<&str>, level: Option<f64>) -> String {
     format!("hvc1.{}.L{}.B0", profile_part, level_val)
 }
 
-/// Generate a master HLS playlist that references the variant playlist.
-pub fn generate_master_playlist(session: &TranscodeSession) -> String {
-    use remux_sdks::remux::VideoRangeType;
+/// `avc1` High profile at the lowest level that covers `height` at 30 fps.
+fn h264_hls_codec_string(height: u32) -> &'static str {
+    match height {
+        0..=480 => "avc1.64001e",
+        481..=720 => "avc1.64001f",
+        _ => "avc1.640028",
+    }
+}
 
-    let play_session_id = &session.id;
+/// The ABR ladder of a video transcode: the session's own output (the source
+/// fitted into `max_width`×`max_height`) followed by every ladder rung below
+/// it in both height and bitrate. Empty when no rung fits under the main
+/// output, in which case a single variant is served.
+pub fn abr_renditions(
+    source_width: Option<i64>,
+    source_height: Option<i64>,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    video_bitrate: Option<u32>,
+) -> Vec<Rendition> {
+    let (src_w, src_h) = match (source_width, source_height) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => (w as f64, h as f64),
+        _ => (1920.0, 1080.0),
+    };
+    let scale = [
+        max_width.map(|w| w as f64 / src_w),
+        max_height.map(|h| h as f64 / src_h),
+    ]
+    .into_iter()
+    .flatten()
+    .fold(1.0, f64::min);
+    let even = |v: f64| ((v / 2.0).round() as u32 * 2).max(2);
+    let main_height = even(src_h * scale);
+    let main_bitrate = video_bitrate.unwrap_or_else(|| {
+        ABR_LADDER
+            .iter()
+            .find(|(height, _)| *height <= main_height)
+            .or(ABR_LADDER.last())
+            .map_or(0, |(_, bitrate)| *bitrate)
+    });
 
+    let rungs: Vec<Rendition> = ABR_LADDER
+        .iter()
+        .filter(|(height, bitrate)| *height < main_height && *bitrate < main_bitrate)
+        .map(|&(height, video_bitrate)| Rendition {
+            name: format!("{height}p"),
+            width: even(height as f64 * src_w / src_h),
+            height,
+            video_bitrate,
+        })
+        .collect();
+    if rungs.is_empty() {
+        return Vec::new();
+    }
+    std::iter::once(Rendition {
+        name: Rendition::MAIN.to_string(),
+        width: even(src_w * scale),
+        height: main_height,
+        video_bitrate: main_bitrate,
+    })
+    .chain(rungs)
+    .collect()
+}
+
+/// `CODECS` values of the session's output: the video codec string (the
+/// source's when copying) and the audio one.
+fn variant_codecs(session: &TranscodeSession) -> (String, &'static str) {
     let video_codec_str: String = match session
         .video_codec
         .as_str()
@@ -1891,6 +2169,85 @@ pub fn generate_master_playlist(session: &TranscodeSession) -> String {
     } else {
         "mp4a.40.2"
     };
+    (video_codec_str, audio_codec_str)
+}
+
+/// Video `CODECS` value of one ABR rendition: H.264 at the level its height
+/// needs; HEVC output keeps the session's string.
+fn rendition_video_codec<'a>(
+    session: &TranscodeSession,
+    video_codec: &'a str,
+    height: u32,
+) -> &'a str {
+    if matches!(
+        session
+            .video_codec
+            .as_str(),
+        "hevc" | "libx265"
+    ) {
+        video_codec
+    } else {
+        h264_hls_codec_string(height)
+    }
+}
+
+/// Master playlist of an ABR session: one variant per rendition, tallest
+/// first. `main` is the session's own variant playlist; the others are served
+/// from `abr/{name}/`.
+fn abr_master_playlist(
+    session: &TranscodeSession,
+    video_codec: &str,
+    audio_codec: &str,
+    frame_rate_attr: &str,
+) -> String {
+    let mut buf = String::from(
+        "#EXTM3U\n\
+         #EXT-X-VERSION:6\n\
+         #EXT-X-INDEPENDENT-SEGMENTS\n",
+    );
+    for rendition in &session.renditions {
+        // -maxrate caps the video, so this is also the peak.
+        let bandwidth = rendition.video_bitrate + DEFAULT_AUDIO_BITRATE;
+        let uri = if rendition.name == Rendition::MAIN {
+            "main.m3u8".to_string()
+        } else {
+            format!("abr/{}/stream.m3u8", rendition.name)
+        };
+        buf.push_str(&format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={bandwidth},AVERAGE-BANDWIDTH={bandwidth},\
+             CODECS=\"{video},{audio_codec}\",VIDEO-RANGE=SDR,RESOLUTION={w}x{h}{frame_rate_attr}\n\
+             {uri}?PlaySessionId={psid}\n",
+            video = rendition_video_codec(session, video_codec, rendition.height),
+            w = rendition.width,
+            h = rendition.height,
+            psid = session.id,
+        ));
+    }
+    buf
+}
+
+/// Generate a master HLS playlist that references the variant playlist, or
+/// one variant per rendition for ABR sessions.
+pub fn generate_master_playlist(session: &TranscodeSession) -> String {
+    use remux_sdks::remux::VideoRangeType;
+
+    let play_session_id = &session.id;
+
+    if let Some(audio) = &session.audio {
+        return format!(
+            "#EXTM3U\n\
+             #EXT-X-VERSION:7\n\
+             #EXT-X-INDEPENDENT-SEGMENTS\n\
+             #EXT-X-STREAM-INF:BANDWIDTH={bandwidth},AVERAGE-BANDWIDTH={bandwidth},CODECS=\"{codec}\"\n\
+             main.m3u8?PlaySessionId={play_session_id}\n",
+            bandwidth = audio.bandwidth(),
+            codec = audio
+                .format
+                .hls_codec(),
+        );
+    }
+
+    let (video_codec_str, audio_codec_str) = variant_codecs(session);
     let codecs = format!("{},{}", video_codec_str, audio_codec_str);
 
     // VIDEO-RANGE: only meaningful for copy-mode (passthrough) video. Transcoded output is SDR.
@@ -1930,9 +2287,22 @@ pub fn generate_master_playlist(session: &TranscodeSession) -> String {
         source_frame_rate = session.source_frame_rate,
         video_codec = session.video_codec.as_str(),
         codecs = codecs.as_str(),
+        renditions = session.renditions.len(),
         "generating master HLS playlist"
     );
 
+    if !session
+        .renditions
+        .is_empty()
+    {
+        return abr_master_playlist(
+            session,
+            &video_codec_str,
+            audio_codec_str,
+            &frame_rate_attr,
+        );
+    }
+
     format!(
         "#EXTM3U\n\
          #EXT-X-VERSION:6\n\
@@ -1947,6 +2317,100 @@ pub fn generate_master_playlist(session: &TranscodeSession) -> String {
     )
 }
 
+/// MPEG-DASH manifest over the same fMP4 segments the HLS playlists list: a
+/// static MPD with one muxed audio+video representation per rendition, each
+/// addressed by segment number.
+pub fn generate_dash_manifest(session: &TranscodeSession) -> String {
+    let (video_codec, audio_codec) = variant_codecs(session);
+    let play_session_id = quick_xml::escape::escape(&session.id);
+    let segment_length = session
+        .segment_length
+        .max(1);
+    let frame_rate = session
+        .source_frame_rate
+        .filter(|fps| *fps > 0.0)
+        .map(|fps| format!(" frameRate=\"{}\"", dash_frame_rate(fps)))
+        .unwrap_or_default();
+
+    let representation = |id: &str,
+                          prefix: &str,
+                          codecs: String,
+                          bandwidth: u32,
+                          size: String| {
+        format!(
+            "      <Representation id=\"{id}\" bandwidth=\"{bandwidth}\" codecs=\"{codecs}\"{size}>\n\
+             \x20       <SegmentTemplate timescale=\"1\" duration=\"{segment_length}\" startNumber=\"0\" \
+             initialization=\"{prefix}init.mp4?PlaySessionId={play_session_id}\" \
+             media=\"{prefix}segment_$Number%05d$.m4s?PlaySessionId={play_session_id}\"/>\n\
+             \x20     </Representation>\n"
+        )
+    };
+    let representations: String = if session
+        .renditions
+        .is_empty()
+    {
+        let size = match (session.source_video_width, session.source_video_height) {
+            (Some(w), Some(h)) => format!(" width=\"{w}\" height=\"{h}\""),
+            _ => String::new(),
+        };
+        let bandwidth = session
+            .video_bitrate
+            .map_or(2_000_000, |b| b + DEFAULT_AUDIO_BITRATE);
+        representation(
+            Rendition::MAIN,
+            "",
+            format!("{video_codec},{audio_codec}"),
+            bandwidth,
+            size,
+        )
+    } else {
+        session
+            .renditions
+            .iter()
+            .map(|r| {
+                let prefix = if r.name == Rendition::MAIN {
+                    String::new()
+                } else {
+                    format!("abr/{}/", r.name)
+                };
+                representation(
+                    &r.name,
+                    &prefix,
+                    format!(
+                        "{},{audio_codec}",
+                        rendition_video_codec(session, &video_codec, r.height)
+                    ),
+                    r.video_bitrate + DEFAULT_AUDIO_BITRATE,
+                    format!(" width=\"{}\" height=\"{}\"", r.width, r.height),
+                )
+            })
+            .collect()
+    };
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\" profiles=\"urn:mpeg:dash:profile:isoff-live:2011\" \
+         type=\"static\" mediaPresentationDuration=\"PT{duration:.3}S\" minBufferTime=\"PT{segment_length}S\">\n\
+         \x20 <Period id=\"0\" start=\"PT0S\">\n\
+         \x20   <AdaptationSet id=\"0\" contentType=\"video\" mimeType=\"video/mp4\" segmentAlignment=\"true\" startWithSAP=\"1\"{frame_rate}>\n\
+         {representations}\
+         \x20   </AdaptationSet>\n\
+         \x20 </Period>\n\
+         </MPD>\n",
+        duration = session.runtime_ticks as f64 / 10_000_000.0,
+    )
+}
+
+/// DASH `frameRate` is a whole number or a fraction: 23.976 → `24000/1001`.
+fn dash_frame_rate(fps: f32) -> String {
+    let ntsc = (fps * 1.001).round();
+    if (fps - fps.round()).abs() > 0.01 && (ntsc / 1.001 - fps).abs() < 0.01 {
+        format!("{}/1001", ntsc as u32 * 1000)
+    } else {
+        (fps.round() as u32).to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2340,6 +2804,38 @@ mod tests {
         assert_eq!(arg_after(&args, "-start_number"), Some("5"));
     }
 
+    #[test]
+    fn hls_transcode_keyframes_align_to_segment_boundaries() {
+        let ticks: i64 = 33i64
+            .to_ticks(TickUnit::Seconds)
+            .unwrap();
+        let args = build_hls_args(&TranscodeParams {
+            video_codec: "libx264".into(),
+            start_time_ticks: Some(ticks),
+            ..default_hls(PathBuf::from("/tmp/test_keyframes"))
+        });
+        // Segment 5 starts at 30s: keyframes at 30, 36, 42, …
+        assert_eq!(
+            arg_after(&args, "-force_key_frames:0"),
+            Some("expr:gte(t,30+n_forced*6)")
+        );
+
+        let copy = build_hls_args(&default_hls(PathBuf::from("/tmp/test_keyframes")));
+        assert!(!args_contains(&copy, "-force_key_frames:0"));
+    }
+
+    #[test]
+    fn throttle_pauses_far_ahead_of_the_client_and_resumes_below() {
+        // 300s at 6s segments: pause 50 segments ahead, resume under 48.
+        let map: SegmentMap = (0..=60).collect();
+        assert!(!throttle(&map, 11, 6, false));
+        assert!(throttle(&map, 10, 6, false));
+        assert!(throttle(&map, 12, 6, true));
+        assert!(!throttle(&map, 13, 6, true));
+        // A seek past the buffered stretch always resumes.
+        assert!(!throttle(&map, 100, 6, true));
+    }
+
     #[test]
     fn hls_no_seek_start_number_zero() {
         let dir = PathBuf::from("/tmp/test_noseek");
@@ -2370,7 +2866,6 @@ mod tests {
             wait_done: Arc::new(tokio::sync::Notify::new()),
             last_segment_index: Arc::new(AtomicU32::new(0)),
             start_time_secs: 30,
-            playback_offset_secs: Arc::new(AtomicU32::new(0)),
             runtime_ticks: 120i64
                 .to_ticks(TickUnit::Seconds)
                 .unwrap(),
@@ -2385,6 +2880,11 @@ mod tests {
             source_frame_rate: None,
             video_bitrate: None,
             hardware_acceleration_type: None,
+            dash: false,
+            audio: None,
+            renditions: Vec::new(),
+            rendition_sessions: Default::default(),
+            rendition: None,
         };
 
         let playlist = generate_variant_playlist(&session, "");
@@ -2719,6 +3219,18 @@ mod tests {
         assert!(args_contains(&args, "-movflags"));
     }
 
+    #[test]
+    fn progress_reports_are_read_in_seconds() {
+        assert_eq!(parse_progress_time("out_time_us=1500000"), Some(1.5));
+        assert_eq!(parse_progress_time("out_time_us=N/A"), None);
+        assert_eq!(
+            parse_progress_time("out_time_us=-9223372036854775807"),
+            None
+        );
+        assert_eq!(parse_progress_time("out_time_ms=1500000"), None);
+        assert_eq!(parse_progress_time("progress=continue"), None);
+    }
+
     #[test]
     fn progressive_ts_container() {
         let args = build_progressive_args(&ProgressiveTranscodeParams {
@@ -2865,4 +3377,236 @@ mod tests {
         });
         assert!(!args_contains(&args, "loudnorm=I=-14:TP=-1:LRA=11"));
     }
+
+    #[test]
+    fn abr_ladder_steps_down_from_a_1080p_source() {
+        let renditions =
+            abr_renditions(Some(1920), Some(1080), None, None, Some(8_000_000));
+        let rungs: Vec<_> = renditions
+            .iter()
+            .map(|r| {
+                (
+                    r.name
+                        .as_str(),
+                    r.width,
+                    r.height,
+                    r.video_bitrate,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rungs,
+            vec![
+                ("main", 1920, 1080, 8_000_000),
+                ("720p", 1280, 720, 3_000_000),
+                ("480p", 854, 480, 1_500_000),
+                ("360p", 640, 360, 800_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn abr_ladder_respects_client_limits_and_skips_small_sources() {
+        // A 4K source capped to 720p by the client: main is 1280x720 and only
+        // rungs below it are offered.
+        let renditions =
+            abr_renditions(Some(3840), Some(2160), None, Some(720), Some(4_000_000));
+        let names: Vec<_> = renditions
+            .iter()
+            .map(|r| {
+                r.name
+                    .as_str()
+            })
+            .collect();
+        assert_eq!(names, vec!["main", "480p", "360p"]);
+        assert_eq!((renditions[0].width, renditions[0].height), (1280, 720));
+
+        // Nothing below a 360p source, nor below a bitrate already at the
+        // bottom of the ladder.
+        assert!(abr_renditions(Some(640), Some(360), None, None, None).is_empty());
+        assert!(
+            abr_renditions(Some(1920), Some(1080), None, None, Some(700_000))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn abr_master_playlist_lists_every_rendition() {
+        let (state_tx, _) = tokio::sync::watch::channel(TranscodeState::Starting);
+        let mut session = TranscodeSession {
+            id: "psid".into(),
+            item_id: Uuid::nil(),
+            media_source_id: Uuid::nil(),
+            output_dir: PathBuf::from("/tmp/test_abr"),
+            input_url: "http://example.invalid/video".into(),
+            state: TranscodeState::Starting,
+            state_tx: Arc::new(state_tx),
+            created_at: std::time::Instant::now(),
+            video_codec: "libx264".into(),
+            audio_codec: "aac".into(),
+            audio_stream_index: None,
+            subtitle_stream_index: None,
+            burn_subtitle: false,
+            segment_length: 6,
+            transcode_reasons: TranscodeReasons::default(),
+            kill_tx: None,
+            wait_done: Arc::new(tokio::sync::Notify::new()),
+            last_segment_index: Arc::new(AtomicU32::new(0)),
+            start_time_secs: 0,
+            runtime_ticks: 0,
+            is_live: false,
+            source_video_codec: Some("h264".into()),
+            source_audio_codec: Some("aac".into()),
+            source_video_profile: None,
+            source_video_level: None,
+            source_video_range_type: None,
+            source_video_width: Some(1920),
+            source_video_height: Some(1080),
+            source_frame_rate: Some(23.976),
+            video_bitrate: Some(8_000_000),
+            hardware_acceleration_type: None,
+            dash: false,
+            audio: None,
+            renditions: abr_renditions(
+                Some(1920),
+                Some(1080),
+                None,
+                None,
+                Some(8_000_000),
+            ),
+            rendition_sessions: Default::default(),
+            rendition: None,
+        };
+
+        let playlist = generate_master_playlist(&session);
+        assert_eq!(
+            playlist
+                .matches("#EXT-X-STREAM-INF")
+                .count(),
+            4
+        );
+        assert!(playlist.contains(
+            "#EXT-X-STREAM-INF:BANDWIDTH=8128000,AVERAGE-BANDWIDTH=8128000,\
+             CODECS=\"avc1.640028,mp4a.40.2\",VIDEO-RANGE=SDR,RESOLUTION=1920x1080,FRAME-RATE=23.976\n\
+             main.m3u8?PlaySessionId=psid\n"
+        ));
+        assert!(playlist.contains(
+            "#EXT-X-STREAM-INF:BANDWIDTH=928000,AVERAGE-BANDWIDTH=928000,\
+             CODECS=\"avc1.64001e,mp4a.40.2\",VIDEO-RANGE=SDR,RESOLUTION=640x360,FRAME-RATE=23.976\n\
+             abr/360p/stream.m3u8?PlaySessionId=psid\n"
+        ));
+
+        // Without a ladder the single-variant playlist is unchanged.
+        session.renditions = Vec::new();
+        let playlist = generate_master_playlist(&session);
+        assert_eq!(
+            playlist
+                .matches("#EXT-X-STREAM-INF")
+                .count(),
+            1
+        );
+        assert!(!playlist.contains("abr/"));
+    }
+
+    #[test]
+    fn dash_manifest_lists_fmp4_segment_templates() {
+        let (state_tx, _) = tokio::sync::watch::channel(TranscodeState::Starting);
+        let mut session = TranscodeSession {
+            id: "psid".into(),
+            item_id: Uuid::nil(),
+            media_source_id: Uuid::nil(),
+            output_dir: PathBuf::from("/tmp/test_dash"),
+            input_url: "http://example.invalid/video".into(),
+            state: TranscodeState::Starting,
+            state_tx: Arc::new(state_tx),
+            created_at: std::time::Instant::now(),
+            video_codec: "libx264".into(),
+            audio_codec: "aac".into(),
+            audio_stream_index: None,
+            subtitle_stream_index: None,
+            burn_subtitle: false,
+            segment_length: 6,
+            transcode_reasons: TranscodeReasons::default(),
+            kill_tx: None,
+            wait_done: Arc::new(tokio::sync::Notify::new()),
+            last_segment_index: Arc::new(AtomicU32::new(0)),
+            start_time_secs: 0,
+            runtime_ticks: 5_400i64
+                .to_ticks(TickUnit::Seconds)
+                .unwrap(),
+            is_live: false,
+            source_video_codec: Some("h264".into()),
+            source_audio_codec: Some("aac".into()),
+            source_video_profile: None,
+            source_video_level: None,
+            source_video_range_type: None,
+            source_video_width: Some(1920),
+            source_video_height: Some(1080),
+            source_frame_rate: Some(23.976),
+            video_bitrate: Some(8_000_000),
+            hardware_acceleration_type: None,
+            dash: true,
+            audio: None,
+            renditions: Vec::new(),
+            rendition_sessions: Default::default(),
+            rendition: None,
+        };
+        assert!(session.use_fmp4());
+        assert_eq!(
+            session.segment_path("segment_00003"),
+            PathBuf::from("/tmp/test_dash/segment_00003.m4s")
+        );
+
+        let mpd = generate_dash_manifest(&session);
+        assert!(
+            mpd.contains(r#"type="static" mediaPresentationDuration="PT5400.000S""#)
+        );
+        assert!(mpd.contains(r#"frameRate="24000/1001""#));
+        assert!(mpd.contains(
+            r#"<Representation id="main" bandwidth="8128000" codecs="avc1.640028,mp4a.40.2" width="1920" height="1080">"#
+        ));
+        assert!(mpd.contains(
+            r#"initialization="init.mp4?PlaySessionId=psid" media="segment_$Number%05d$.m4s?PlaySessionId=psid""#
+        ));
+
+        // ABR: one representation per rung, lower ones under abr/.
+        session.renditions =
+            abr_renditions(Some(1920), Some(1080), None, None, Some(8_000_000));
+        let mpd = generate_dash_manifest(&session);
+        assert_eq!(
+            mpd.matches("<Representation ")
+                .count(),
+            4
+        );
+        assert!(mpd.contains(
+            r#"<Representation id="360p" bandwidth="928000" codecs="avc1.64001e,mp4a.40.2" width="640" height="360">"#
+        ));
+        assert!(mpd.contains(
+            r#"media="abr/360p/segment_$Number%05d$.m4s?PlaySessionId=psid""#
+        ));
+    }
+
+    #[test]
+    fn dash_frame_rates_are_whole_or_ntsc_fractions() {
+        assert_eq!(dash_frame_rate(25.0), "25");
+        assert_eq!(dash_frame_rate(23.976), "24000/1001");
+        assert_eq!(dash_frame_rate(29.97), "30000/1001");
+        assert_eq!(dash_frame_rate(59.94), "60000/1001");
+    }
+
+    #[test]
+    fn hls_fmp4_segments_on_request() {
+        let args = build_hls_args(&TranscodeParams {
+            video_codec: "libx264".into(),
+            fmp4: true,
+            ..default_hls(PathBuf::from("/tmp/test_fmp4"))
+        });
+        assert_eq!(arg_after(&args, "-hls_segment_type"), Some("fmp4"));
+        assert!(
+            args.iter()
+                .any(|a| a.ends_with("segment_%05d.m4s"))
+        );
+        // HEVC-only flags stay off for a transcode.
+        assert!(!args_contains(&args, "-tag:v"));
+    }
 }
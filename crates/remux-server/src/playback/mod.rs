@@ -1,5 +1,7 @@
+pub mod audio;
 pub mod decision;
 pub mod engine;
 pub mod hw_accel;
 pub mod probe;
+pub mod segment_map;
 pub mod session;
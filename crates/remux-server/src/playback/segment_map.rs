@@ -0,0 +1,235 @@
+//! Which HLS segments of a VOD transcode are on disk.
+//!
+//! Every encoder run writes `segment_{index}` files into the session's output
+//! directory, so after a few seeks the directory holds several runs' worth of
+//! segments with gaps between them. Transcoded video gets a keyframe on every
+//! segment boundary, so a segment is the same whichever run wrote it: any
+//! segment on disk is served as is, and a new encoder is only started for a
+//! segment no running encoder is about to reach.
+
+use std::{collections::BTreeSet, path::Path};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SegmentMap {
+    indices: BTreeSet<u32>,
+}
+
+/// What a request for a segment missing from disk needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentPlan {
+    /// On disk already.
+    Ready,
+    /// The running encoder will write it shortly.
+    Wait,
+    /// Start an encoder at this segment.
+    Encode,
+}
+
+impl SegmentMap {
+    /// Reads `segment_00042.ts`/`.m4s` names from `dir`; a missing directory
+    /// is an empty map.
+    pub fn scan(dir: &Path) -> Self {
+        let indices = std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|e| {
+                        segment_index(
+                            &e.file_name()
+                                .to_string_lossy(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { indices }
+    }
+
+    pub fn contains(&self, idx: u32) -> bool {
+        self.indices
+            .contains(&idx)
+    }
+
+    /// Last segment of the unbroken stretch starting at `from`, `None` when
+    /// `from` itself is missing.
+    pub fn contiguous_end(&self, from: u32) -> Option<u32> {
+        if !self.contains(from) {
+            return None;
+        }
+        let mut end = from;
+        for &idx in self
+            .indices
+            .range(from + 1..)
+        {
+            if idx != end + 1 {
+                break;
+            }
+            end = idx;
+        }
+        Some(end)
+    }
+
+    /// Segment an encoder that started at `start` is writing. Segments behind
+    /// the client are deleted as playback moves on, so this is the end of the
+    /// stretch holding the first segment at or after `start`.
+    pub fn encoder_position(&self, start: u32) -> Option<u32> {
+        let first = *self
+            .indices
+            .range(start..)
+            .next()?;
+        self.contiguous_end(first)
+    }
+
+    /// How many segments after `idx` the client can fetch without waiting.
+    pub fn buffered_after(&self, idx: u32) -> u32 {
+        self.contiguous_end(idx + 1)
+            .map_or(0, |end| end - idx)
+    }
+
+    /// Decides how to serve segment `idx`. `encoder_start` is the first
+    /// segment of the running encoder run, if any; a request up to `max_gap`
+    /// segments past its position is worth waiting for.
+    pub fn plan(
+        &self,
+        idx: u32,
+        encoder_start: Option<u32>,
+        max_gap: u32,
+    ) -> SegmentPlan {
+        if self.contains(idx) {
+            return SegmentPlan::Ready;
+        }
+        let Some(start) = encoder_start else {
+            return SegmentPlan::Encode;
+        };
+        let position = self
+            .encoder_position(start)
+            .unwrap_or(start);
+        if idx >= start && idx <= position.saturating_add(max_gap) {
+            SegmentPlan::Wait
+        } else {
+            SegmentPlan::Encode
+        }
+    }
+
+    /// Removes segments before `cutoff` from `dir`.
+    pub fn delete_before(&mut self, dir: &Path, cutoff: u32) {
+        let stale: Vec<u32> = self
+            .indices
+            .range(..cutoff)
+            .copied()
+            .collect();
+        if stale.is_empty() {
+            return;
+        }
+        if let Ok(entries) = std::fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                if segment_index(
+                    &entry
+                        .file_name()
+                        .to_string_lossy(),
+                )
+                .is_some_and(|idx| idx < cutoff)
+                {
+                    let _ = std::fs::remove_file(entry.path());
+                }
+            }
+        }
+        for idx in stale {
+            self.indices
+                .remove(&idx);
+        }
+    }
+}
+
+impl FromIterator<u32> for SegmentMap {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        Self {
+            indices: iter
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+/// `segment_00042.ts` → 42.
+fn segment_index(name: &str) -> Option<u32> {
+    name.strip_suffix(".ts")
+        .or_else(|| name.strip_suffix(".m4s"))?
+        .strip_prefix("segment_")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plans_against_the_running_encoder() {
+        // An earlier run left 0..=9; the current one started at 40 and has
+        // written 40..=42.
+        let map: SegmentMap = (0..=9)
+            .chain(40..=42)
+            .collect();
+        assert_eq!(map.plan(5, Some(40), 4), SegmentPlan::Ready);
+        assert_eq!(map.plan(43, Some(40), 4), SegmentPlan::Wait);
+        assert_eq!(map.plan(46, Some(40), 4), SegmentPlan::Wait);
+        // Too far ahead of the encoder, or behind where it started.
+        assert_eq!(map.plan(47, Some(40), 4), SegmentPlan::Encode);
+        assert_eq!(map.plan(20, Some(40), 4), SegmentPlan::Encode);
+        // Nothing running: anything missing needs an encoder.
+        assert_eq!(map.plan(10, None, 4), SegmentPlan::Encode);
+        // A run that has not written anything yet.
+        assert_eq!(map.plan(60, Some(60), 4), SegmentPlan::Wait);
+    }
+
+    #[test]
+    fn encoder_position_survives_deleted_segments() {
+        // Run started at 10; 10..=14 were deleted behind the client.
+        let map: SegmentMap = (15..=30).collect();
+        assert_eq!(map.encoder_position(10), Some(30));
+        assert_eq!(map.buffered_after(20), 10);
+        assert_eq!(map.buffered_after(30), 0);
+        assert_eq!(map.contiguous_end(12), None);
+    }
+
+    #[test]
+    fn scans_and_deletes_segment_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "segment_00000.ts",
+            "segment_00001.ts",
+            "segment_00002.m4s",
+            "init.mp4",
+            "main.m3u8",
+        ] {
+            std::fs::write(
+                dir.path()
+                    .join(name),
+                b"x",
+            )
+            .unwrap();
+        }
+        std::fs::create_dir(
+            dir.path()
+                .join("720p"),
+        )
+        .unwrap();
+
+        let mut map = SegmentMap::scan(dir.path());
+        assert_eq!(map, (0..=2).collect::<SegmentMap>());
+
+        map.delete_before(dir.path(), 2);
+        assert_eq!(map, SegmentMap::scan(dir.path()));
+        assert_eq!(
+            map,
+            [2].into_iter()
+                .collect::<SegmentMap>()
+        );
+        assert!(
+            dir.path()
+                .join("init.mp4")
+                .exists()
+        );
+    }
+}
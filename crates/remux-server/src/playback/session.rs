@@ -1,5 +1,6 @@
 use remux_sdks::remux::TranscodeReasons;
 use std::{
+    collections::HashMap,
     path::PathBuf,
     sync::{Arc, atomic::AtomicU32},
     time::Instant,
@@ -7,6 +8,8 @@ use std::{
 use tokio::sync::{Notify, watch};
 use uuid::Uuid;
 
+use super::audio::AudioTranscode;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TranscodeState {
     Starting,
@@ -15,6 +18,23 @@ pub enum TranscodeState {
     Error(String),
 }
 
+/// One variant of an adaptive-bitrate HLS ladder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rendition {
+    /// `main` for the session's own output, else e.g. `720p`; used in the
+    /// variant URL and as the rendition's output sub-directory.
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Target video bitrate in bps.
+    pub video_bitrate: u32,
+}
+
+impl Rendition {
+    /// Name of the rendition a session encodes itself.
+    pub const MAIN: &'static str = "main";
+}
+
 pub struct TranscodeSession {
     pub id: String,
     pub item_id: Uuid,
@@ -39,8 +59,6 @@ pub struct TranscodeSession {
     pub last_segment_index: Arc<AtomicU32>,
     /// Start offset of this transcode in seconds (from start_time_ticks).
     pub start_time_secs: u32,
-    /// Playback offset in seconds relative to start_time_secs, updated from progress reports.
-    pub playback_offset_secs: Arc<AtomicU32>,
     /// Total runtime of the media in Jellyfin ticks (100-ns units).
     pub runtime_ticks: i64,
     /// True for live TV — variant playlist is served from the ffmpeg-written EVENT file.
@@ -67,6 +85,17 @@ pub struct TranscodeSession {
     pub video_bitrate: Option<u32>,
     /// Hardware acceleration type used for this transcode, e.g. "VAAPI", "QSV".
     pub hardware_acceleration_type: Option<String>,
+    /// Served through a DASH manifest, so segments are always fMP4.
+    pub dash: bool,
+    /// Set for music: the audio-only encode every run of this session uses.
+    pub audio: Option<AudioTranscode>,
+    /// ABR ladder offered in the master playlist, this session's own output
+    /// first. Empty when a single variant is served.
+    pub renditions: Vec<Rendition>,
+    /// Child sessions encoding the lower renditions, started on first request.
+    pub rendition_sessions: HashMap<String, Arc<tokio::sync::RwLock<TranscodeSession>>>,
+    /// Set on a child session: the rendition it encodes.
+    pub rendition: Option<Rendition>,
 }
 
 impl TranscodeSession {
@@ -95,6 +124,10 @@ impl TranscodeSession {
         source_frame_rate: Option<f32>,
         video_bitrate: Option<u32>,
         hardware_acceleration_type: Option<String>,
+        dash: bool,
+        audio: Option<AudioTranscode>,
+        renditions: Vec<Rendition>,
+        rendition: Option<Rendition>,
     ) -> Arc<tokio::sync::RwLock<Self>> {
         let _ = std::fs::create_dir_all(&output_dir);
         let (state_tx, _) = watch::channel(TranscodeState::Starting);
@@ -118,7 +151,6 @@ impl TranscodeSession {
             wait_done: Arc::new(Notify::new()),
             last_segment_index: Arc::new(AtomicU32::new(0)),
             start_time_secs: 0,
-            playback_offset_secs: Arc::new(AtomicU32::new(0)),
             runtime_ticks,
             is_live,
             source_video_codec,
@@ -131,6 +163,11 @@ impl TranscodeSession {
             source_frame_rate,
             video_bitrate,
             hardware_acceleration_type,
+            dash,
+            audio,
+            renditions,
+            rendition_sessions: HashMap::new(),
+            rendition,
         }))
     }
 
@@ -145,14 +182,34 @@ impl TranscodeSession {
     }
 
     /// Returns true if this session should use fragmented MP4 (fMP4) segments
-    /// rather than MPEG-TS. iOS Safari (and the HLS spec) require fMP4 for HEVC.
+    /// rather than MPEG-TS. iOS Safari (and the HLS spec) require fMP4 for HEVC,
+    /// and DASH for everything. Music always uses it for gapless playback.
     pub fn use_fmp4(&self) -> bool {
-        self.video_codec == "copy"
-            && matches!(
-                self.source_video_codec
-                    .as_deref(),
-                Some("hevc") | Some("h265") | Some("hvc1") | Some("hev1")
-            )
+        self.dash
+            || self
+                .audio
+                .is_some()
+            || (self.video_codec == "copy"
+                && matches!(
+                    self.source_video_codec
+                        .as_deref(),
+                    Some("hevc") | Some("h265") | Some("hvc1") | Some("hev1")
+                ))
+    }
+
+    /// First segment of the running encoder run (its `-start_number`), `None`
+    /// once ffmpeg has exited.
+    pub fn encoder_start_segment(&self) -> Option<u32> {
+        matches!(
+            self.state,
+            TranscodeState::Starting | TranscodeState::Running
+        )
+        .then(|| {
+            self.start_time_secs
+                / self
+                    .segment_length
+                    .max(1)
+        })
     }
 
     pub fn segment_path(&self, segment_id: &str) -> PathBuf {
@@ -29,6 +29,8 @@ pub struct PlaybackSession {
     pub playlist_item_id: Option<String>,
     pub started_at: DateTime<Utc>,
     pub last_activity: DateTime<Utc>,
+    /// When playback time was last added to the user's daily watch time.
+    pub watch_accounted_at: DateTime<Utc>,
     /// Active transcode session owned by this playback session, if any.
     pub transcode: Option<Arc<tokio::sync::RwLock<TranscodeSession>>>,
     /// Stream group UUID that the selected source belongs to, if any.
@@ -37,6 +39,61 @@ pub struct PlaybackSession {
     pub item_kind: Option<db::MediaKind>,
 }
 
+/// Why a playback start was refused. Handlers answer all of these with 403.
+#[derive(Debug, thiserror::Error)]
+pub enum PlaybackDenied {
+    #[error("Maximum concurrent streams reached")]
+    StreamLimit,
+    #[error("Playback is not allowed at this time")]
+    OutsideAccessSchedule,
+    #[error("Daily watch time limit reached")]
+    WatchTimeExceeded,
+}
+
+/// Refuse playback outside the user's access schedules or once today's
+/// watch-time allowance is used up.
+pub async fn check_parental_limits(
+    db: &sqlx::SqlitePool,
+    user: &db::User,
+) -> anyhow::Result<()> {
+    let now = chrono::Local::now().naive_local();
+    if !user.is_within_access_schedule(now) {
+        return Err(PlaybackDenied::OutsideAccessSchedule.into());
+    }
+    if let Some(limit) = user.daily_watch_limit_secs() {
+        let watched = db::UserWatchTime::get(db, &user.id, now.date()).await?;
+        if watched >= limit {
+            return Err(PlaybackDenied::WatchTimeExceeded.into());
+        }
+    }
+    Ok(())
+}
+
+/// Longest gap between two reports that still counts as watch time. Anything
+/// longer means the client went away without saying so.
+const MAX_WATCH_TIME_STEP_SECS: i64 = 5 * 60;
+
+/// Add the session's playback since its last report to the user's daily
+/// watch time. The state at the last report decides whether the gap counted,
+/// so time spent paused is not charged.
+async fn record_watch_time(
+    db: &sqlx::SqlitePool,
+    user: &db::User,
+    ps: &PlaybackSession,
+    now: DateTime<Utc>,
+) {
+    if ps.is_paused {
+        return;
+    }
+    let secs = (now - ps.watch_accounted_at)
+        .num_seconds()
+        .clamp(0, MAX_WATCH_TIME_STEP_SECS);
+    let today = chrono::Local::now().date_naive();
+    if let Err(e) = db::UserWatchTime::add(db, &user.id, today, secs).await {
+        warn!(user = %user.username, error = %e, "Failed to record watch time");
+    }
+}
+
 #[derive(Clone)]
 pub struct PlaybackSessionManager {
     sessions: Arc<DashMap<String, PlaybackSession>>,
@@ -95,11 +152,12 @@ impl PlaybackSessionManager {
                 ),
             );
             if current >= max_sessions as usize {
-                return Err(anyhow::anyhow!("Stream limit reached")
-                    .context("Maximum concurrent streams reached"));
+                return Err(PlaybackDenied::StreamLimit.into());
             }
         }
 
+        check_parental_limits(db, &auth_session.user).await?;
+
         let item_id = data.item_id;
         if item_id.is_nil() {
             warn!(
@@ -174,6 +232,7 @@ impl PlaybackSessionManager {
                 .clone(),
             started_at: Utc::now(),
             last_activity: Utc::now(),
+            watch_accounted_at: Utc::now(),
             transcode: None,
             group_id,
             item_kind,
@@ -328,7 +387,11 @@ impl PlaybackSessionManager {
             );
         }
 
+        let now = Utc::now();
+        record_watch_time(db, user, ps, now).await;
+
         self.update(psid, |ps| {
+            ps.watch_accounted_at = now;
             if !data
                 .item_id
                 .is_nil()
@@ -360,18 +423,6 @@ impl PlaybackSessionManager {
             ps.last_activity = Utc::now();
         });
 
-        // Update transcode buffer monitor with actual playback position.
-        if let Some(position_ticks) = data.position_ticks {
-            if let Some(ref ts_lock) = ps.transcode {
-                if let Ok(ts) = ts_lock.try_read() {
-                    let position_secs = (position_ticks / 10_000_000) as u32;
-                    let offset = position_secs.saturating_sub(ts.start_time_secs);
-                    ts.playback_offset_secs
-                        .store(offset, std::sync::atomic::Ordering::Relaxed);
-                }
-            }
-        }
-
         // Persist position to DB (no watched-threshold check on progress).
         let position_ticks = data
             .position_ticks
@@ -432,6 +483,9 @@ impl PlaybackSessionManager {
         let ps = self
             .stop(psid)
             .await;
+        if let Some(ref ps) = ps {
+            record_watch_time(db, user, ps, Utc::now()).await;
+        }
 
         let item_id = Some(data.item_id)
             .filter(|id| !id.is_nil())
@@ -673,6 +727,7 @@ impl PlaybackSessionManager {
                         playlist_item_id: None,
                         started_at: Utc::now(),
                         last_activity: Utc::now(),
+                        watch_accounted_at: Utc::now(),
                         group_id: None,
                         item_kind: None,
                     },
@@ -716,6 +771,12 @@ impl PlaybackSessionManager {
         let session_dir = self
             .base_dir
             .join(play_session_id);
+        // Sub-directories may also hold ABR renditions, so a segment of the
+        // session's own output wins.
+        let flat = session_dir.join(format!("{}.ts", segment_id));
+        if flat.exists() {
+            return flat;
+        }
 
         if let Ok(entries) = std::fs::read_dir(&session_dir) {
             let mut latest_dir: Option<(PathBuf, std::time::SystemTime)> = None;
@@ -739,7 +800,7 @@ impl PlaybackSessionManager {
             }
         }
 
-        session_dir.join(format!("{}.ts", segment_id))
+        flat
     }
 
     pub fn base_dir(&self) -> &std::path::Path {
@@ -795,6 +856,22 @@ impl PlaybackSessionManager {
 
 /// Kill an ffmpeg process and wait for it to exit before returning.
 async fn kill_transcode(ts: Arc<tokio::sync::RwLock<TranscodeSession>>) {
+    let renditions: Vec<_> = ts
+        .write()
+        .await
+        .rendition_sessions
+        .drain()
+        .map(|(_, child)| child)
+        .collect();
+    for child in renditions {
+        kill_ffmpeg(&child).await;
+    }
+    let output_dir = kill_ffmpeg(&ts).await;
+    let _ = std::fs::remove_dir_all(&output_dir);
+}
+
+/// Stops the session's ffmpeg, if running, and returns its output directory.
+async fn kill_ffmpeg(ts: &tokio::sync::RwLock<TranscodeSession>) -> PathBuf {
     let (kill_tx, wait_done, output_dir) = {
         let mut s = ts
             .write()
@@ -813,5 +890,5 @@ async fn kill_transcode(ts: Arc<tokio::sync::RwLock<TranscodeSession>>) {
         let _ = kill_tx.send(());
         notification.await;
     }
-    let _ = std::fs::remove_dir_all(&output_dir);
+    output_dir
 }
@@ -1,20 +1,27 @@
-//! Queueing a user's actions for their media trackers, and describing the item
-//! a delivery names. Describing it at delivery keeps the walk to the series off
-//! the playback path. Nothing here talks to a provider; the sync task does that.
+//! Queueing a user's actions for their media trackers, describing the item a
+//! delivery names, and writing back what a history import read. Describing at
+//! delivery keeps the walk to the series off the playback path. Nothing here
+//! talks to a provider; the sync task and the connect API do that.
 
 use anyhow::Result;
 use chrono::Datelike;
+use serde::Serialize;
 use sqlx::SqlitePool;
 use tracing::warn;
 use uuid::Uuid;
 
 use crate::{
     AppContext,
-    addons::media_tracker::{MediaTrackerEvent, MediaTrackerTarget},
+    addons::media_tracker::{MediaTrackerEvent, MediaTrackerTarget, RemoteWatch},
     db,
 };
 
-fn describe(media: &db::Media, series: Option<&db::Media>) -> MediaTrackerTarget {
+fn describe(
+    media: &db::Media,
+    series: Option<&db::Media>,
+    album: Option<&db::Media>,
+    artist: Option<&db::Media>,
+) -> MediaTrackerTarget {
     MediaTrackerTarget {
         kind: media
             .kind
@@ -28,29 +35,74 @@ fn describe(media: &db::Media, series: Option<&db::Media>) -> MediaTrackerTarget
         ids: media
             .external_ids
             .clone(),
-        series: series.map(|s| Box::new(describe(s, None))),
+        series: series.map(|s| Box::new(describe(s, None, None, None))),
         season: media.parent_idx,
         episode: media.idx,
+        runtime: media.runtime,
+        // Playlist imports carry flat names instead of album and artist rows.
+        artist: artist
+            .map(|a| {
+                a.title
+                    .clone()
+            })
+            .or_else(|| {
+                media
+                    .external_ids
+                    .artist_name
+                    .clone()
+            }),
+        album: album
+            .map(|a| {
+                a.title
+                    .clone()
+            })
+            .or_else(|| {
+                media
+                    .external_ids
+                    .album_title
+                    .clone()
+            }),
     }
 }
 
 /// The item as a provider needs to see it, or `None` when nothing about it
 /// carries an id one could match on. Episodes carry their series, because a
-/// provider keys an episode on the show's ids plus season and episode.
+/// provider keys an episode on the show's ids plus season and episode; tracks
+/// carry their album and artist names, which is what music services key on.
 pub async fn resolve_target(
     db: &SqlitePool,
     media: &db::Media,
 ) -> Result<Option<MediaTrackerTarget>> {
-    let series = if media.kind == db::MediaKind::Episode {
-        db::Media::get_ancestors(db, &media.id)
-            .await?
-            .into_iter()
-            .find(|m| m.kind == db::MediaKind::Series)
-    } else {
-        None
+    let target = match media.kind {
+        db::MediaKind::Episode => {
+            let series = db::Media::get_ancestors(db, &media.id)
+                .await?
+                .into_iter()
+                .find(|m| m.kind == db::MediaKind::Series);
+            describe(media, series.as_ref(), None, None)
+        }
+        db::MediaKind::Track => {
+            // A track's grandparent is its artist, which is not always on its
+            // parent chain, so both are read directly.
+            let ids: Vec<Uuid> = [media.parent_id, media.grandparent_id]
+                .into_iter()
+                .flatten()
+                .collect();
+            let related = db::Media::get_by_ids(db, &ids).await?;
+            let of_kind = |kind: db::MediaKind| {
+                related
+                    .iter()
+                    .find(|m| m.kind == kind)
+            };
+            describe(
+                media,
+                None,
+                of_kind(db::MediaKind::Album),
+                of_kind(db::MediaKind::Artist),
+            )
+        }
+        _ => describe(media, None, None, None),
     };
-
-    let target = describe(media, series.as_ref());
     Ok(target
         .is_matchable()
         .then_some(target))
@@ -73,7 +125,8 @@ pub async fn enqueue(
 
     let kind = event.kind();
     // Both filters have to hold: the user asked for it, and the provider said
-    // it can take it. Queueing past either only produces permanent failures.
+    // it can take it, for this kind of item. Queueing past either only
+    // produces permanent failures.
     let wanted: Vec<db::UserMediaTracker> =
         db::UserMediaTracker::list_for_user(&ctx.db, user_id)
             .await?
@@ -83,8 +136,8 @@ pub async fn enqueue(
                 ctx.addons
                     .media_tracker_for(t.addon_id)
                     .is_some_and(|a| {
-                        a.capabilities()
-                            .supports(kind)
+                        let caps = a.capabilities();
+                        caps.supports(kind) && caps.supports_kind(&media.kind)
                     })
             })
             .collect();
@@ -126,6 +179,137 @@ pub async fn enqueue_and_wake(
     }
 }
 
+/// What a history import did with the items a provider returned.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Items the library does not hold, or holds without a matching id.
+    pub unmatched: usize,
+}
+
+/// A library item of `kind` carrying any of these ids.
+async fn find_by_ids(
+    db: &SqlitePool,
+    kind: db::MediaKind,
+    ids: &db::ExternalIds,
+) -> Result<Option<Uuid>> {
+    let imdb = ids
+        .imdb
+        .as_deref()
+        .map(|s| s.as_str());
+    if imdb.is_none()
+        && ids
+            .tmdb
+            .is_none()
+        && ids
+            .tvdb
+            .is_none()
+    {
+        return Ok(None);
+    }
+    Ok(sqlx::query_scalar(
+        "SELECT id FROM media WHERE kind = ?1 \
+         AND (json_extract(external_ids, '$.imdb') = ?2 \
+           OR json_extract(external_ids, '$.tmdb') = ?3 \
+           OR json_extract(external_ids, '$.tvdb') = ?4) \
+         LIMIT 1",
+    )
+    .bind(kind)
+    .bind(imdb)
+    .bind(ids.tmdb)
+    .bind(ids.tvdb)
+    .fetch_optional(db)
+    .await?)
+}
+
+/// The library item a watch names. Episodes arrive on their show's ids, so
+/// they are found through the series, the way a target describes them.
+async fn find_watched(
+    db: &SqlitePool,
+    watch: &RemoteWatch,
+) -> Result<Option<db::Media>> {
+    let id = match (watch.season, watch.episode) {
+        (Some(season), Some(episode)) => {
+            let Some(series) =
+                find_by_ids(db, db::MediaKind::Series, &watch.ids).await?
+            else {
+                return Ok(None);
+            };
+            sqlx::query_scalar(
+                "SELECT id FROM media WHERE kind = ?1 AND grandparent_id = ?2 \
+                 AND parent_idx = ?3 AND idx = ?4 LIMIT 1",
+            )
+            .bind(db::MediaKind::Episode)
+            .bind(series)
+            .bind(season)
+            .bind(episode)
+            .fetch_optional(db)
+            .await?
+        }
+        _ => match find_by_ids(db, db::MediaKind::Movie, &watch.ids).await? {
+            Some(id) => Some(id),
+            None => find_by_ids(db, db::MediaKind::Series, &watch.ids).await?,
+        },
+    };
+    match id {
+        Some(id) => Ok(db::Media::get_by_id(db, &id).await?),
+        None => Ok(None),
+    }
+}
+
+/// Write watches read back from a provider into `user`'s state. Only what the
+/// provider reported is touched, and nothing is queued back out, so an import
+/// never echoes to the tracker it came from.
+pub async fn import_watches(
+    db: &SqlitePool,
+    user: &db::User,
+    watches: &[RemoteWatch],
+) -> Result<ImportSummary> {
+    let release_threshold = db::Settings::get_config_or_default(db)
+        .await
+        .release_date_threshold();
+    let mut summary = ImportSummary::default();
+    for watch in watches {
+        let Some(media) = find_watched(db, watch).await? else {
+            summary.unmatched += 1;
+            continue;
+        };
+        if watch.watched {
+            media
+                .mark_played(db, user, true, release_threshold)
+                .await?;
+        } else if let Some(ticks) = watch.position_ticks {
+            db::UserMediaState::update_playback(
+                db, user, &media, ticks, None, None, None,
+            )
+            .await?;
+        }
+        match watch.favorite {
+            Some(true) => {
+                media
+                    .mark_favorite(db, user)
+                    .await?;
+            }
+            Some(false) => {
+                media
+                    .unmark_favorite(db, user)
+                    .await?;
+            }
+            None => {}
+        }
+        // A rating outside 0-10 is the provider's bug; keep the rest of the item.
+        if let Some(rating) = watch
+            .rating
+            .and_then(|r| db::UserRating::try_from(f64::from(r)).ok())
+        {
+            db::UserMediaState::set_rating(db, user, &media, Some(rating)).await?;
+        }
+        summary.imported += 1;
+    }
+    Ok(summary)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -177,6 +361,7 @@ mod tests {
                 supported_events: self
                     .0
                     .clone(),
+                media_kinds: vec![db::MediaKind::Movie, db::MediaKind::Episode],
                 ..Default::default()
             }
         }
@@ -358,6 +543,67 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn an_item_the_provider_cannot_match_is_not_queued() {
+        let (_s, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+        let tracker = connect(
+            ctx,
+            "video",
+            MediaTrackerStatus::Connected,
+            vec![MediaTrackerEventKind::MarkPlayed],
+        )
+        .await;
+        let track = crate::integration_test::seed_track(ctx).await;
+
+        let n = enqueue(
+            ctx,
+            user_id(ctx).await,
+            &track,
+            MediaTrackerEvent::MarkPlayed,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(n, 0, "a video tracker has nothing to match a track against");
+        assert!(
+            queued(ctx, tracker)
+                .await
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn a_track_is_described_by_its_album_and_artist() {
+        let (_s, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+        let track = crate::integration_test::seed_track(ctx).await;
+
+        let target = resolve_target(&ctx.db, &track)
+            .await
+            .unwrap()
+            .expect("a named artist is enough to match a track on");
+
+        assert_eq!(target.title, "Teardrop");
+        assert_eq!(
+            target
+                .artist
+                .as_deref(),
+            Some("Massive Attack")
+        );
+        assert_eq!(
+            target
+                .album
+                .as_deref(),
+            Some("Mezzanine")
+        );
+        assert_eq!(target.runtime, Some(330));
+    }
+
     #[tokio::test]
     async fn nothing_is_queued_when_no_addon_can_track() {
         let (_s, guard) = new_test_server()
@@ -0,0 +1,205 @@
+//! The client as an opendal service, so an SMB share scans and streams like
+//! any other storage preset.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use opendal::{
+    Buffer, Capability, EntryMode, ErrorKind, Metadata, Scheme,
+    raw::{
+        Access, AccessorInfo, OpList, OpRead, OpStat, OperatorBuilder, RpList, RpRead,
+        RpStat, oio,
+    },
+};
+
+use super::{DirEntry, Error, FileInfo, SmbClient, SmbConfig};
+
+/// How much one `read` call asks the share for.
+const READ_CHUNK: u64 = 1024 * 1024;
+
+pub fn operator(config: SmbConfig) -> opendal::Operator {
+    OperatorBuilder::new(SmbBackend {
+        client: SmbClient::new(config),
+    })
+    .finish()
+}
+
+#[derive(Debug)]
+struct SmbBackend {
+    client: SmbClient,
+}
+
+impl Access for SmbBackend {
+    type Reader = SmbReader;
+    type Writer = ();
+    type Lister = SmbLister;
+    type Deleter = ();
+    type BlockingReader = ();
+    type BlockingWriter = ();
+    type BlockingLister = ();
+    type BlockingDeleter = ();
+
+    fn info(&self) -> Arc<AccessorInfo> {
+        let mut info = AccessorInfo::default();
+        info.set_scheme(Scheme::Custom("smb"))
+            .set_root("/")
+            .set_native_capability(Capability {
+                stat: true,
+                read: true,
+                list: true,
+                list_with_recursive: true,
+                ..Default::default()
+            });
+        Arc::new(info)
+    }
+
+    async fn stat(&self, path: &str, _: OpStat) -> opendal::Result<RpStat> {
+        let info = self
+            .client
+            .stat(path)
+            .await
+            .map_err(to_opendal)?;
+        Ok(RpStat::new(metadata(&info)))
+    }
+
+    async fn read(
+        &self,
+        path: &str,
+        args: OpRead,
+    ) -> opendal::Result<(RpRead, SmbReader)> {
+        let range = args.range();
+        Ok((
+            RpRead::new(),
+            SmbReader {
+                client: self
+                    .client
+                    .clone(),
+                path: path.to_string(),
+                offset: range.offset(),
+                remaining: range.size(),
+            },
+        ))
+    }
+
+    async fn list(
+        &self,
+        path: &str,
+        args: OpList,
+    ) -> opendal::Result<(RpList, SmbLister)> {
+        let dir = path.trim_start_matches('/');
+        Ok((
+            RpList::default(),
+            SmbLister {
+                client: self
+                    .client
+                    .clone(),
+                recursive: args.recursive(),
+                entries: VecDeque::new(),
+                dirs: VecDeque::from([dir.to_string()]),
+            },
+        ))
+    }
+}
+
+struct SmbReader {
+    client: SmbClient,
+    path: String,
+    offset: u64,
+    /// `None` reads to the end of the file.
+    remaining: Option<u64>,
+}
+
+impl oio::Read for SmbReader {
+    async fn read(&mut self) -> opendal::Result<Buffer> {
+        let want = self
+            .remaining
+            .unwrap_or(READ_CHUNK)
+            .min(READ_CHUNK);
+        if want == 0 {
+            return Ok(Buffer::new());
+        }
+        let data = self
+            .client
+            .read(&self.path, self.offset, want as u32)
+            .await
+            .map_err(to_opendal)?;
+        self.offset += data.len() as u64;
+        if let Some(remaining) = &mut self.remaining {
+            // A short read is the end of the file; stop there.
+            *remaining = if (data.len() as u64) < want {
+                0
+            } else {
+                *remaining - want
+            };
+        }
+        Ok(Buffer::from(data))
+    }
+}
+
+/// Lists a directory at a time, breadth first when recursive. Paths keep
+/// opendal's form: relative, with a trailing `/` on directories.
+struct SmbLister {
+    client: SmbClient,
+    recursive: bool,
+    entries: VecDeque<oio::Entry>,
+    dirs: VecDeque<String>,
+}
+
+impl oio::List for SmbLister {
+    async fn next(&mut self) -> opendal::Result<Option<oio::Entry>> {
+        loop {
+            if let Some(entry) = self
+                .entries
+                .pop_front()
+            {
+                return Ok(Some(entry));
+            }
+            let Some(dir) = self
+                .dirs
+                .pop_front()
+            else {
+                return Ok(None);
+            };
+            let listed = self
+                .client
+                .list(&dir)
+                .await
+                .map_err(to_opendal)?;
+            for DirEntry { name, info } in listed {
+                let mut path = format!("{dir}{name}");
+                if info.is_dir {
+                    path.push('/');
+                    if self.recursive {
+                        self.dirs
+                            .push_back(path.clone());
+                    }
+                }
+                self.entries
+                    .push_back(oio::Entry::new(&path, metadata(&info)));
+            }
+        }
+    }
+}
+
+fn metadata(info: &FileInfo) -> Metadata {
+    if info.is_dir {
+        return Metadata::new(EntryMode::DIR);
+    }
+    let meta = Metadata::new(EntryMode::FILE).with_content_length(info.size);
+    match info.modified {
+        Some(modified) => meta.with_last_modified(modified),
+        None => meta,
+    }
+}
+
+fn to_opendal(err: Error) -> opendal::Error {
+    let kind = if err.is_not_found() {
+        ErrorKind::NotFound
+    } else if err.is_denied() {
+        ErrorKind::PermissionDenied
+    } else {
+        ErrorKind::Unexpected
+    };
+    let temporary = matches!(err, Error::Io(_));
+    let err = opendal::Error::new(kind, "smb").set_source(err);
+    if temporary { err.set_temporary() } else { err }
+}
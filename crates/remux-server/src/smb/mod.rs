@@ -0,0 +1,1025 @@
+//! A read-only SMB2 client, enough for the opendal addon to index and stream
+//! a share: sign in with NTLMv2, connect to one share, then stat, list and
+//! read.
+//!
+//! Dialects 2.0.2 and 2.1 only. Every SMB server still speaks them, and they
+//! keep signing to HMAC-SHA256 and encryption out of the picture. Requests
+//! are signed when the server requires it.
+
+mod backend;
+mod ntlm;
+#[cfg(test)]
+pub(crate) mod test_server;
+
+pub use backend::operator;
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+const DEFAULT_PORT: u16 = 445;
+
+/// Upper bound on one request and its response.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Servers drop sessions that sit idle; one unused for this long is
+/// replaced rather than trusted.
+const IDLE_LIMIT: Duration = Duration::from_secs(5 * 60);
+
+/// The largest read or listing that needs no multi-credit charge.
+const MAX_TRANSFER: u32 = 64 * 1024;
+
+/// How many credits each request asks for. Requests go one at a time, so a
+/// handful keeps the window open.
+const CREDITS: u16 = 16;
+
+const NEGOTIATE: u16 = 0x00;
+const SESSION_SETUP: u16 = 0x01;
+const TREE_CONNECT: u16 = 0x03;
+const CREATE: u16 = 0x05;
+const CLOSE: u16 = 0x06;
+const READ: u16 = 0x08;
+const QUERY_DIRECTORY: u16 = 0x0e;
+
+const STATUS_SUCCESS: u32 = 0x0000_0000;
+const STATUS_PENDING: u32 = 0x0000_0103;
+const STATUS_NO_MORE_FILES: u32 = 0x8000_0006;
+const STATUS_NO_SUCH_FILE: u32 = 0xc000_000f;
+const STATUS_END_OF_FILE: u32 = 0xc000_0011;
+const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc000_0016;
+const STATUS_ACCESS_DENIED: u32 = 0xc000_0022;
+const STATUS_OBJECT_NAME_NOT_FOUND: u32 = 0xc000_0034;
+const STATUS_OBJECT_PATH_NOT_FOUND: u32 = 0xc000_003a;
+const STATUS_LOGON_FAILURE: u32 = 0xc000_006d;
+const STATUS_BAD_NETWORK_NAME: u32 = 0xc000_00cc;
+
+const FLAGS_ASYNC: u32 = 0x0000_0002;
+const FLAGS_SIGNED: u32 = 0x0000_0008;
+
+const DIALECT_2_0_2: u16 = 0x0202;
+const DIALECT_2_1: u16 = 0x0210;
+
+const SIGNING_ENABLED: u16 = 0x0001;
+const SIGNING_REQUIRED: u16 = 0x0002;
+
+const SESSION_FLAG_IS_GUEST: u16 = 0x0001;
+const SESSION_FLAG_IS_NULL: u16 = 0x0002;
+
+/// Read data, read attributes, read extended attributes, read control and
+/// synchronize.
+const READ_ACCESS: u32 = 0x0012_0089;
+const SHARE_ALL: u32 = 0x0000_0007;
+const FILE_OPEN: u32 = 0x0000_0001;
+const FILE_DIRECTORY_FILE: u32 = 0x0000_0001;
+const FILE_NON_DIRECTORY_FILE: u32 = 0x0000_0040;
+const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x0000_0010;
+const FILE_DIRECTORY_INFORMATION: u8 = 0x01;
+const RESTART_SCANS: u8 = 0x01;
+
+/// Seconds between the FILETIME epoch, 1601, and the Unix one.
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("SMB server returned status {0:#010x}")]
+    Status(u32),
+    #[error("SMB protocol error: {0}")]
+    Protocol(&'static str),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            Self::Status(
+                STATUS_NO_SUCH_FILE
+                    | STATUS_OBJECT_NAME_NOT_FOUND
+                    | STATUS_OBJECT_PATH_NOT_FOUND
+                    | STATUS_BAD_NETWORK_NAME
+            )
+        )
+    }
+
+    pub fn is_denied(&self) -> bool {
+        matches!(
+            self,
+            Self::Status(STATUS_ACCESS_DENIED | STATUS_LOGON_FAILURE)
+        )
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Where the share is and who to sign in as. No username signs in as a guest.
+#[derive(Debug, Clone, Default)]
+pub struct SmbConfig {
+    /// `host` or `host:port`.
+    pub endpoint: String,
+    pub share: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub domain: Option<String>,
+}
+
+impl SmbConfig {
+    fn addr(&self) -> String {
+        if self
+            .endpoint
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| {
+                port.parse::<u16>()
+                    .is_ok()
+            })
+        {
+            self.endpoint
+                .clone()
+        } else {
+            format!("{}:{DEFAULT_PORT}", self.endpoint)
+        }
+    }
+
+    /// `\\host\share`, as TREE_CONNECT names it.
+    fn unc(&self) -> String {
+        let host = match self
+            .endpoint
+            .rsplit_once(':')
+        {
+            Some((host, port))
+                if port
+                    .parse::<u16>()
+                    .is_ok() =>
+            {
+                host
+            }
+            _ => &self.endpoint,
+        };
+        format!(r"\\{}\{}", host.trim_matches(['[', ']']), self.share)
+    }
+}
+
+/// What a stat or a directory listing says about an entry.
+#[derive(Debug, Clone)]
+pub struct FileInfo {
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub info: FileInfo,
+}
+
+/// A pool of signed-in connections to one share. Each operation takes a
+/// connection for itself, so concurrent streams do not queue behind a scan.
+#[derive(Clone)]
+pub struct SmbClient {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: SmbConfig,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl std::fmt::Debug for SmbClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SmbClient")
+            .field(
+                "endpoint",
+                &self
+                    .inner
+                    .config
+                    .endpoint,
+            )
+            .field(
+                "share",
+                &self
+                    .inner
+                    .config
+                    .share,
+            )
+            .finish()
+    }
+}
+
+impl SmbClient {
+    pub fn new(config: SmbConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                idle: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub async fn stat(&self, path: &str) -> Result<FileInfo> {
+        let path = share_path(path);
+        self.with_connection(|conn| Box::pin(conn.stat(path.clone())))
+            .await
+    }
+
+    /// The entries of one directory, without `.` and `..`.
+    pub async fn list(&self, dir: &str) -> Result<Vec<DirEntry>> {
+        let path = share_path(dir);
+        self.with_connection(|conn| Box::pin(conn.list(path.clone())))
+            .await
+    }
+
+    /// Up to `len` bytes from `offset`; fewer only at the end of the file.
+    pub async fn read(&self, path: &str, offset: u64, len: u32) -> Result<Vec<u8>> {
+        let path = share_path(path);
+        self.with_connection(|conn| Box::pin(conn.read(path.clone(), offset, len)))
+            .await
+    }
+
+    /// Run `op` on a pooled connection, or a new one when none is idle. A
+    /// pooled connection the server has since dropped fails with an I/O
+    /// error, and the operation is tried again on another.
+    async fn with_connection<T>(
+        &self,
+        op: impl Fn(&mut Connection) -> BoxFuture<'_, Result<T>>,
+    ) -> Result<T> {
+        loop {
+            let pooled = {
+                let mut idle = self
+                    .inner
+                    .idle
+                    .lock()
+                    .unwrap();
+                idle.retain(|conn| {
+                    conn.last_used
+                        .elapsed()
+                        < IDLE_LIMIT
+                });
+                idle.pop()
+            };
+            let reused = pooled.is_some();
+            let mut conn = match pooled {
+                Some(conn) => conn,
+                None => {
+                    Connection::connect(
+                        &self
+                            .inner
+                            .config,
+                    )
+                    .await?
+                }
+            };
+            match op(&mut conn).await {
+                Err(Error::Io(_)) if reused => continue,
+                // The stream may be mid-message; the connection goes.
+                result @ Err(Error::Io(_) | Error::Protocol(_)) => return result,
+                result => {
+                    conn.last_used = Instant::now();
+                    self.inner
+                        .idle
+                        .lock()
+                        .unwrap()
+                        .push(conn);
+                    return result;
+                }
+            }
+        }
+    }
+}
+
+/// An opendal path as the share names it: relative, with backslashes.
+fn share_path(path: &str) -> String {
+    path.trim_matches('/')
+        .replace('/', r"\")
+}
+
+fn filetime(value: u64) -> Option<DateTime<Utc>> {
+    let secs = (value / 10_000_000).checked_sub(FILETIME_UNIX_OFFSET)?;
+    DateTime::from_timestamp(secs as i64, (value % 10_000_000) as u32 * 100)
+}
+
+fn le16(buf: &[u8], at: usize) -> Result<u16> {
+    buf.get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or(Error::Protocol("truncated message"))
+}
+
+fn le32(buf: &[u8], at: usize) -> Result<u32> {
+    buf.get(at..at + 4)
+        .map(|b| {
+            u32::from_le_bytes(
+                b.try_into()
+                    .unwrap(),
+            )
+        })
+        .ok_or(Error::Protocol("truncated message"))
+}
+
+fn le64(buf: &[u8], at: usize) -> Result<u64> {
+    buf.get(at..at + 8)
+        .map(|b| {
+            u64::from_le_bytes(
+                b.try_into()
+                    .unwrap(),
+            )
+        })
+        .ok_or(Error::Protocol("truncated message"))
+}
+
+/// A slice of the message named by an offset from the start of its header.
+fn slice(msg: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    msg.get(offset..offset + len)
+        .ok_or(Error::Protocol("buffer out of bounds"))
+}
+
+/// The 64-byte header every request starts with.
+fn header(
+    command: u16,
+    message_id: u64,
+    credit_charge: u16,
+    tree_id: u32,
+    session_id: u64,
+) -> Vec<u8> {
+    let mut h = Vec::with_capacity(128);
+    h.extend_from_slice(b"\xfeSMB");
+    h.extend(64u16.to_le_bytes());
+    h.extend(credit_charge.to_le_bytes());
+    h.extend(0u32.to_le_bytes());
+    h.extend(command.to_le_bytes());
+    h.extend(CREDITS.to_le_bytes());
+    h.extend(0u32.to_le_bytes());
+    h.extend(0u32.to_le_bytes());
+    h.extend(message_id.to_le_bytes());
+    h.extend(0u32.to_le_bytes());
+    h.extend(tree_id.to_le_bytes());
+    h.extend(session_id.to_le_bytes());
+    h.extend([0; 16]);
+    h
+}
+
+/// HMAC-SHA256 over the whole message, truncated into the signature field.
+fn sign(key: &[u8], msg: &mut [u8]) {
+    let flags = u32::from_le_bytes(
+        msg[16..20]
+            .try_into()
+            .unwrap(),
+    ) | FLAGS_SIGNED;
+    msg[16..20].copy_from_slice(&flags.to_le_bytes());
+    msg[48..64].fill(0);
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(msg);
+    msg[48..64].copy_from_slice(
+        &mac.finalize()
+            .into_bytes()[..16],
+    );
+}
+
+struct Response {
+    status: u32,
+    /// The whole message; buffer offsets count from its header.
+    data: Vec<u8>,
+}
+
+impl Response {
+    fn body(&self) -> &[u8] {
+        &self.data[64..]
+    }
+
+    fn ok(self) -> Result<Self> {
+        match self.status {
+            STATUS_SUCCESS => Ok(self),
+            status => Err(Error::Status(status)),
+        }
+    }
+}
+
+type FileId = [u8; 16];
+
+struct Connection {
+    stream: TcpStream,
+    message_id: u64,
+    /// 2.1 counts credits per request; 2.0.2 leaves the field zero.
+    credit_charge: u16,
+    session_id: u64,
+    tree_id: u32,
+    signing_key: Option<[u8; 16]>,
+    max_read: u32,
+    last_used: Instant,
+}
+
+impl Connection {
+    async fn connect(config: &SmbConfig) -> Result<Self> {
+        let stream = tokio::time::timeout(TIMEOUT, TcpStream::connect(config.addr()))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+        stream.set_nodelay(true)?;
+        let mut conn = Self {
+            stream,
+            message_id: 0,
+            credit_charge: 0,
+            session_id: 0,
+            tree_id: 0,
+            signing_key: None,
+            max_read: MAX_TRANSFER,
+            last_used: Instant::now(),
+        };
+        let signing_required = conn
+            .negotiate()
+            .await?;
+        conn.session_setup(config, signing_required)
+            .await?;
+        conn.tree_connect(&config.unc())
+            .await?;
+        Ok(conn)
+    }
+
+    async fn call(&mut self, command: u16, body: &[u8]) -> Result<Response> {
+        tokio::time::timeout(TIMEOUT, self.exchange(command, body))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+    }
+
+    async fn exchange(&mut self, command: u16, body: &[u8]) -> Result<Response> {
+        let message_id = self.message_id;
+        self.message_id += 1;
+        let mut msg = header(
+            command,
+            message_id,
+            self.credit_charge,
+            self.tree_id,
+            self.session_id,
+        );
+        msg.extend_from_slice(body);
+        if let Some(key) = &self.signing_key {
+            sign(key, &mut msg);
+        }
+        let mut frame = (msg.len() as u32).to_be_bytes();
+        frame[0] = 0;
+        self.stream
+            .write_all(&frame)
+            .await?;
+        self.stream
+            .write_all(&msg)
+            .await?;
+
+        loop {
+            let mut frame = [0; 4];
+            self.stream
+                .read_exact(&mut frame)
+                .await?;
+            let len = u32::from_be_bytes(frame) as usize & 0x00ff_ffff;
+            if len < 64 {
+                return Err(Error::Protocol("short message"));
+            }
+            let mut data = vec![0; len];
+            self.stream
+                .read_exact(&mut data)
+                .await?;
+            if &data[..4] != b"\xfeSMB" {
+                return Err(Error::Protocol("not an SMB2 message"));
+            }
+            let status = le32(&data, 8)?;
+            let flags = le32(&data, 16)?;
+            // An interim answer; the real one follows under the same id.
+            if status == STATUS_PENDING && flags & FLAGS_ASYNC != 0 {
+                continue;
+            }
+            if le64(&data, 24)? != message_id {
+                return Err(Error::Protocol("response to another request"));
+            }
+            return Ok(Response { status, data });
+        }
+    }
+
+    /// Settle on a dialect. Returns whether the server requires signing.
+    async fn negotiate(&mut self) -> Result<bool> {
+        let mut guid = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut guid);
+        let mut body = Vec::with_capacity(40);
+        body.extend(36u16.to_le_bytes());
+        body.extend(2u16.to_le_bytes());
+        body.extend(SIGNING_ENABLED.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend(0u32.to_le_bytes());
+        body.extend(guid);
+        body.extend(0u64.to_le_bytes());
+        body.extend(DIALECT_2_0_2.to_le_bytes());
+        body.extend(DIALECT_2_1.to_le_bytes());
+
+        let resp = self
+            .call(NEGOTIATE, &body)
+            .await?
+            .ok()?;
+        let body = resp.body();
+        let security_mode = le16(body, 2)?;
+        match le16(body, 4)? {
+            DIALECT_2_0_2 => {}
+            DIALECT_2_1 => self.credit_charge = 1,
+            _ => return Err(Error::Protocol("server picked an unsupported dialect")),
+        }
+        self.max_read = le32(body, 32)?.clamp(4096, MAX_TRANSFER);
+        Ok(security_mode & SIGNING_REQUIRED != 0)
+    }
+
+    async fn session_setup(
+        &mut self,
+        config: &SmbConfig,
+        signing_required: bool,
+    ) -> Result<()> {
+        let creds = ntlm::Credentials {
+            user: config
+                .username
+                .as_deref()
+                .unwrap_or_default(),
+            domain: config
+                .domain
+                .as_deref()
+                .unwrap_or_default(),
+            password: config
+                .password
+                .as_deref()
+                .unwrap_or_default(),
+        };
+
+        let first = ntlm::spnego_init(&ntlm::negotiate_message());
+        let resp = self
+            .call(SESSION_SETUP, &session_setup_body(&first))
+            .await?;
+        if resp.status != STATUS_MORE_PROCESSING_REQUIRED {
+            return Err(Error::Status(resp.status));
+        }
+        self.session_id = le64(&resp.data, 40)?;
+        let blob = slice(
+            &resp.data,
+            le16(resp.body(), 4)? as usize,
+            le16(resp.body(), 6)? as usize,
+        )?;
+        let challenge = ntlm::Challenge::parse(ntlm::spnego_token(blob)?)?;
+
+        let mut client_challenge = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut client_challenge);
+        let auth =
+            ntlm::authenticate(&challenge, &creds, client_challenge, now_filetime());
+        let resp = self
+            .call(
+                SESSION_SETUP,
+                &session_setup_body(&ntlm::spnego_response(&auth.message)),
+            )
+            .await?
+            .ok()?;
+
+        // Guest and anonymous sessions have no key to sign with.
+        let session_flags = le16(resp.body(), 2)?;
+        if session_flags & (SESSION_FLAG_IS_GUEST | SESSION_FLAG_IS_NULL) == 0
+            && signing_required
+        {
+            self.signing_key = auth.session_key;
+        }
+        Ok(())
+    }
+
+    async fn tree_connect(&mut self, unc: &str) -> Result<()> {
+        let path = ntlm::utf16le(unc);
+        let mut body = Vec::with_capacity(8 + path.len());
+        body.extend(9u16.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend(72u16.to_le_bytes());
+        body.extend((path.len() as u16).to_le_bytes());
+        body.extend(path);
+        let resp = self
+            .call(TREE_CONNECT, &body)
+            .await?
+            .ok()?;
+        self.tree_id = le32(&resp.data, 36)?;
+        Ok(())
+    }
+
+    async fn create(&mut self, path: &str, options: u32) -> Result<(FileId, FileInfo)> {
+        let name = ntlm::utf16le(path);
+        let mut body = Vec::with_capacity(57 + name.len());
+        body.extend(57u16.to_le_bytes());
+        body.push(0); // security flags
+        body.push(0); // no oplock
+        body.extend(2u32.to_le_bytes()); // impersonation
+        body.extend(0u64.to_le_bytes());
+        body.extend(0u64.to_le_bytes());
+        body.extend(READ_ACCESS.to_le_bytes());
+        body.extend(0u32.to_le_bytes()); // file attributes
+        body.extend(SHARE_ALL.to_le_bytes());
+        body.extend(FILE_OPEN.to_le_bytes());
+        body.extend(options.to_le_bytes());
+        body.extend(120u16.to_le_bytes());
+        body.extend((name.len() as u16).to_le_bytes());
+        body.extend(0u32.to_le_bytes()); // no create contexts
+        body.extend(0u32.to_le_bytes());
+        if name.is_empty() {
+            // The share root has no name, but the buffer may not be empty.
+            body.push(0);
+        }
+        body.extend(name);
+
+        let resp = self
+            .call(CREATE, &body)
+            .await?
+            .ok()?;
+        let body = resp.body();
+        let info = FileInfo {
+            size: le64(body, 48)?,
+            modified: filetime(le64(body, 24)?),
+            is_dir: le32(body, 56)? & FILE_ATTRIBUTE_DIRECTORY != 0,
+        };
+        Ok((
+            slice(body, 64, 16)?
+                .try_into()
+                .unwrap(),
+            info,
+        ))
+    }
+
+    async fn stat(&mut self, path: String) -> Result<FileInfo> {
+        let (file, info) = self
+            .create(&path, 0)
+            .await?;
+        self.close(&file)
+            .await?;
+        Ok(info)
+    }
+
+    async fn list(&mut self, path: String) -> Result<Vec<DirEntry>> {
+        let (file, _) = self
+            .create(&path, FILE_DIRECTORY_FILE)
+            .await?;
+        let entries = self
+            .read_dir(&file)
+            .await;
+        self.close(&file)
+            .await?;
+        entries
+    }
+
+    async fn read(&mut self, path: String, offset: u64, len: u32) -> Result<Vec<u8>> {
+        let (file, _) = self
+            .create(&path, FILE_NON_DIRECTORY_FILE)
+            .await?;
+        let mut data = Vec::with_capacity(len as usize);
+        let result = async {
+            while data.len() < len as usize {
+                let want = (len - data.len() as u32).min(self.max_read);
+                let chunk = self
+                    .read_at(&file, offset + data.len() as u64, want)
+                    .await?;
+                if chunk.is_empty() {
+                    break;
+                }
+                data.extend(chunk);
+            }
+            Ok(())
+        }
+        .await;
+        self.close(&file)
+            .await?;
+        result.map(|()| data)
+    }
+
+    async fn close(&mut self, file: &FileId) -> Result<()> {
+        let mut body = Vec::with_capacity(24);
+        body.extend(24u16.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend(0u32.to_le_bytes());
+        body.extend(file);
+        self.call(CLOSE, &body)
+            .await?
+            .ok()?;
+        Ok(())
+    }
+
+    /// Empty at the end of the file.
+    async fn read_at(
+        &mut self,
+        file: &FileId,
+        offset: u64,
+        len: u32,
+    ) -> Result<Vec<u8>> {
+        let mut body = Vec::with_capacity(49);
+        body.extend(49u16.to_le_bytes());
+        body.push(0x50); // data right after the response header
+        body.push(0);
+        body.extend(len.to_le_bytes());
+        body.extend(offset.to_le_bytes());
+        body.extend(file);
+        body.extend(0u32.to_le_bytes()); // minimum count
+        body.extend(0u32.to_le_bytes()); // channel
+        body.extend(0u32.to_le_bytes()); // remaining bytes
+        body.extend(0u16.to_le_bytes()); // no channel info
+        body.extend(0u16.to_le_bytes());
+        body.push(0);
+
+        let resp = self
+            .call(READ, &body)
+            .await?;
+        if resp.status == STATUS_END_OF_FILE {
+            return Ok(Vec::new());
+        }
+        let resp = resp.ok()?;
+        let offset = resp.body()[2] as usize;
+        let len = le32(resp.body(), 4)? as usize;
+        Ok(slice(&resp.data, offset, len)?.to_vec())
+    }
+
+    async fn read_dir(&mut self, dir: &FileId) -> Result<Vec<DirEntry>> {
+        let pattern = ntlm::utf16le("*");
+        let mut entries = Vec::new();
+        let mut flags = RESTART_SCANS;
+        loop {
+            let mut body = Vec::with_capacity(32 + pattern.len());
+            body.extend(33u16.to_le_bytes());
+            body.push(FILE_DIRECTORY_INFORMATION);
+            body.push(flags);
+            body.extend(0u32.to_le_bytes());
+            body.extend(dir);
+            body.extend(96u16.to_le_bytes());
+            body.extend((pattern.len() as u16).to_le_bytes());
+            body.extend(MAX_TRANSFER.to_le_bytes());
+            body.extend(&pattern);
+            flags = 0;
+
+            let resp = self
+                .call(QUERY_DIRECTORY, &body)
+                .await?;
+            if resp.status == STATUS_NO_MORE_FILES {
+                return Ok(entries);
+            }
+            let resp = resp.ok()?;
+            let mut buf = slice(
+                &resp.data,
+                le16(resp.body(), 2)? as usize,
+                le32(resp.body(), 4)? as usize,
+            )?;
+            loop {
+                let name_len = le32(buf, 60)? as usize;
+                let name = slice(buf, 64, name_len)?
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]));
+                let name = char::decode_utf16(name)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect::<String>();
+                if name != "." && name != ".." {
+                    entries.push(DirEntry {
+                        name,
+                        info: FileInfo {
+                            size: le64(buf, 40)?,
+                            modified: filetime(le64(buf, 24)?),
+                            is_dir: le32(buf, 56)? & FILE_ATTRIBUTE_DIRECTORY != 0,
+                        },
+                    });
+                }
+                match le32(buf, 0)? as usize {
+                    0 => break,
+                    next => {
+                        buf = buf
+                            .get(next..)
+                            .ok_or(Error::Protocol("directory entry out of bounds"))?
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn session_setup_body(token: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(24 + token.len());
+    body.extend(25u16.to_le_bytes());
+    body.push(0); // flags
+    body.push(SIGNING_ENABLED as u8);
+    body.extend(0u32.to_le_bytes()); // capabilities
+    body.extend(0u32.to_le_bytes()); // channel
+    body.extend(88u16.to_le_bytes());
+    body.extend((token.len() as u16).to_le_bytes());
+    body.extend(0u64.to_le_bytes()); // previous session
+    body.extend_from_slice(token);
+    body
+}
+
+fn now_filetime() -> u64 {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    (since_epoch.as_secs() + FILETIME_UNIX_OFFSET) * 10_000_000
+        + since_epoch.subsec_nanos() as u64 / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_server::*, *};
+
+    fn share(signing_required: bool) -> Share {
+        Share {
+            name: "media",
+            user: Some(("remux", "hunter2")),
+            signing_required,
+            files: vec![
+                (
+                    "Movies/Heat (1995)/Heat (1995).mkv",
+                    (0..200_000u32)
+                        .map(|i| i as u8)
+                        .collect(),
+                ),
+                ("Movies/Heat (1995)/movie.nfo", b"<movie/>".to_vec()),
+                ("Movies/Alien (1979).mkv", b"alien".to_vec()),
+                ("readme.txt", b"hello".to_vec()),
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_stats_and_reads_a_share() {
+        let server = TestServer::start(share(false)).await;
+        let client = SmbClient::new(server.config());
+
+        let root = client
+            .list("/")
+            .await
+            .unwrap();
+        let names: Vec<_> = root
+            .iter()
+            .map(|e| {
+                (
+                    e.name
+                        .as_str(),
+                    e.info
+                        .is_dir,
+                )
+            })
+            .collect();
+        assert_eq!(names, [("Movies", true), ("readme.txt", false)]);
+
+        let movies = client
+            .list("Movies/")
+            .await
+            .unwrap();
+        let names: Vec<_> = movies
+            .iter()
+            .map(|e| {
+                e.name
+                    .as_str()
+            })
+            .collect();
+        assert_eq!(names, ["Alien (1979).mkv", "Heat (1995)"]);
+
+        let info = client
+            .stat("Movies/Heat (1995)/Heat (1995).mkv")
+            .await
+            .unwrap();
+        assert_eq!(info.size, 200_000);
+        assert!(!info.is_dir);
+        assert_eq!(
+            info.modified
+                .unwrap()
+                .to_rfc3339(),
+            "2026-01-01T00:00:00+00:00"
+        );
+
+        // Larger than one SMB read, and running past the end of the file.
+        let data = client
+            .read("Movies/Heat (1995)/Heat (1995).mkv", 100_000, 150_000)
+            .await
+            .unwrap();
+        assert_eq!(data.len(), 100_000);
+        assert!(
+            data.iter()
+                .zip(100_000u32..)
+                .all(|(b, i)| *b == i as u8)
+        );
+        let past_end = client
+            .read("readme.txt", 5, 10)
+            .await
+            .unwrap();
+        assert!(past_end.is_empty());
+
+        // Every call above shared one signed-in connection.
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn signs_requests_when_the_server_requires_it() {
+        let server = TestServer::start(share(true)).await;
+        let client = SmbClient::new(server.config());
+
+        let data = client
+            .read("readme.txt", 0, 64)
+            .await
+            .unwrap();
+        assert_eq!(data, b"hello");
+        assert_eq!(
+            client
+                .list("Movies")
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn a_wrong_password_is_refused() {
+        let server = TestServer::start(share(false)).await;
+        let client = SmbClient::new(SmbConfig {
+            password: Some("hunter3".to_string()),
+            ..server.config()
+        });
+
+        let err = client
+            .stat("readme.txt")
+            .await
+            .unwrap_err();
+        assert!(err.is_denied(), "{err}");
+    }
+
+    #[tokio::test]
+    async fn missing_paths_and_shares_are_not_found() {
+        let server = TestServer::start(share(false)).await;
+        let client = SmbClient::new(server.config());
+        let err = client
+            .stat("Movies/Ran (1985).mkv")
+            .await
+            .unwrap_err();
+        assert!(err.is_not_found(), "{err}");
+
+        let client = SmbClient::new(SmbConfig {
+            share: "backups".to_string(),
+            ..server.config()
+        });
+        let err = client
+            .list("")
+            .await
+            .unwrap_err();
+        assert!(err.is_not_found(), "{err}");
+    }
+
+    #[tokio::test]
+    async fn signs_in_as_a_guest_without_a_username() {
+        let server = TestServer::start(Share {
+            user: None,
+            ..share(false)
+        })
+        .await;
+        let client = SmbClient::new(server.config());
+
+        let data = client
+            .read("readme.txt", 1, 3)
+            .await
+            .unwrap();
+        assert_eq!(data, b"ell");
+    }
+
+    #[tokio::test]
+    async fn a_dropped_connection_is_replaced() {
+        let server = TestServer::start(share(false)).await;
+        let client = SmbClient::new(server.config());
+        client
+            .stat("readme.txt")
+            .await
+            .unwrap();
+
+        server.hang_up();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let info = client
+            .stat("readme.txt")
+            .await
+            .unwrap();
+        assert_eq!(info.size, 5);
+        assert_eq!(server.connections(), 2);
+    }
+
+    #[test]
+    fn endpoints_default_to_port_445() {
+        let config = SmbConfig {
+            endpoint: "nas.local".to_string(),
+            share: "media".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(config.addr(), "nas.local:445");
+        assert_eq!(config.unc(), r"\\nas.local\media");
+
+        let config = SmbConfig {
+            endpoint: "[::1]:1445".to_string(),
+            ..config
+        };
+        assert_eq!(config.addr(), "[::1]:1445");
+        assert_eq!(config.unc(), r"\\::1\media");
+    }
+}
@@ -0,0 +1,446 @@
+//! NTLMv2 wrapped in SPNEGO, the one sign-in every SMB server accepts.
+//!
+//! Only the client side, and only what a session needs: no key exchange, no
+//! MIC, so the exported session key is the NTLMv2 session base key.
+
+use hmac::{Hmac, Mac};
+use md4::{Digest, Md4};
+use md5::Md5;
+
+use super::{Error, Result};
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+const NEGOTIATE: u32 = 1;
+const CHALLENGE: u32 = 2;
+const AUTHENTICATE: u32 = 3;
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_SIGN: u32 = 0x0000_0010;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ANONYMOUS: u32 = 0x0000_0800;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+
+const CLIENT_FLAGS: u32 = NEGOTIATE_UNICODE
+    | REQUEST_TARGET
+    | NEGOTIATE_SIGN
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_TARGET_INFO
+    | NEGOTIATE_128
+    | NEGOTIATE_56;
+
+const AV_EOL: u16 = 0;
+const AV_TIMESTAMP: u16 = 7;
+
+/// SPNEGO, 1.3.6.1.5.5.2.
+const SPNEGO_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x02];
+/// NTLMSSP, 1.3.6.1.4.1.311.2.2.10.
+const NTLMSSP_OID: &[u8] =
+    &[0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x02, 0x0a];
+
+pub fn utf16le(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+pub fn hmac_md5(key: &[u8], parts: &[&[u8]]) -> [u8; 16] {
+    let mut mac = Hmac::<Md5>::new_from_slice(key).expect("HMAC takes any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize()
+        .into_bytes()
+        .into()
+}
+
+/// NTOWFv2: the password hash keyed on the upper-cased user and the domain.
+pub fn nt_owf_v2(user: &str, domain: &str, password: &str) -> [u8; 16] {
+    let nt_hash = Md4::digest(utf16le(password));
+    hmac_md5(&nt_hash, &[&utf16le(&(user.to_uppercase() + domain))])
+}
+
+/// Who signs in. No user means an anonymous session.
+pub struct Credentials<'a> {
+    pub user: &'a str,
+    pub domain: &'a str,
+    pub password: &'a str,
+}
+
+impl Credentials<'_> {
+    fn is_anonymous(&self) -> bool {
+        self.user
+            .is_empty()
+    }
+}
+
+pub fn negotiate_message() -> Vec<u8> {
+    let mut msg = Vec::with_capacity(32);
+    msg.extend_from_slice(SIGNATURE);
+    msg.extend(NEGOTIATE.to_le_bytes());
+    msg.extend(CLIENT_FLAGS.to_le_bytes());
+    // Empty domain and workstation fields: length, capacity, offset.
+    msg.extend([0; 16]);
+    msg
+}
+
+/// What the server's CHALLENGE carries that the answer depends on.
+pub struct Challenge {
+    pub flags: u32,
+    pub server_challenge: [u8; 8],
+    pub target_info: Vec<u8>,
+}
+
+impl Challenge {
+    pub fn parse(msg: &[u8]) -> Result<Self> {
+        if msg.len() < 48 || &msg[..8] != SIGNATURE || le32(msg, 8) != CHALLENGE {
+            return Err(Error::Protocol("not an NTLM challenge"));
+        }
+        Ok(Self {
+            flags: le32(msg, 20),
+            server_challenge: msg[24..32]
+                .try_into()
+                .unwrap(),
+            target_info: field(msg, 40)?.to_vec(),
+        })
+    }
+
+    /// The server's own clock, when it sent one. NTLMv2 then has to use it
+    /// and leave the LMv2 response empty.
+    fn timestamp(&self) -> Option<u64> {
+        let mut pairs = self
+            .target_info
+            .as_slice();
+        while pairs.len() >= 4 {
+            let id = u16::from_le_bytes([pairs[0], pairs[1]]);
+            let len = u16::from_le_bytes([pairs[2], pairs[3]]) as usize;
+            let value = pairs.get(4..4 + len)?;
+            match id {
+                AV_EOL => return None,
+                AV_TIMESTAMP if len == 8 => {
+                    return Some(u64::from_le_bytes(
+                        value
+                            .try_into()
+                            .ok()?,
+                    ));
+                }
+                _ => pairs = &pairs[4 + len..],
+            }
+        }
+        None
+    }
+}
+
+pub struct Authenticate {
+    pub message: Vec<u8>,
+    /// Signs the session. Anonymous sessions have none.
+    pub session_key: Option<[u8; 16]>,
+}
+
+/// Answer `challenge`. `now` is a FILETIME, used when the server sent no
+/// timestamp of its own.
+pub fn authenticate(
+    challenge: &Challenge,
+    creds: &Credentials,
+    client_challenge: [u8; 8],
+    now: u64,
+) -> Authenticate {
+    let flags = challenge.flags & CLIENT_FLAGS;
+    if creds.is_anonymous() {
+        return Authenticate {
+            message: authenticate_message(
+                flags | NEGOTIATE_ANONYMOUS,
+                &[0],
+                &[],
+                "",
+                "",
+            ),
+            session_key: None,
+        };
+    }
+
+    let key = nt_owf_v2(creds.user, creds.domain, creds.password);
+    let server_time = challenge.timestamp();
+    let mut temp = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    temp.extend(
+        server_time
+            .unwrap_or(now)
+            .to_le_bytes(),
+    );
+    temp.extend(client_challenge);
+    temp.extend([0; 4]);
+    temp.extend(&challenge.target_info);
+    temp.extend([0; 4]);
+
+    let nt_proof = hmac_md5(&key, &[&challenge.server_challenge, &temp]);
+    let mut nt_response = nt_proof.to_vec();
+    nt_response.extend(&temp);
+    let lm_response = match server_time {
+        Some(_) => vec![0; 24],
+        None => {
+            let mut lm =
+                hmac_md5(&key, &[&challenge.server_challenge, &client_challenge])
+                    .to_vec();
+            lm.extend(client_challenge);
+            lm
+        }
+    };
+
+    Authenticate {
+        message: authenticate_message(
+            flags,
+            &lm_response,
+            &nt_response,
+            creds.domain,
+            creds.user,
+        ),
+        session_key: Some(hmac_md5(&key, &[&nt_proof])),
+    }
+}
+
+fn authenticate_message(
+    flags: u32,
+    lm_response: &[u8],
+    nt_response: &[u8],
+    domain: &str,
+    user: &str,
+) -> Vec<u8> {
+    const HEADER: usize = 64;
+    let domain = utf16le(domain);
+    let user = utf16le(user);
+    // LM, NT, domain, user, workstation, encrypted session key.
+    let payloads: [&[u8]; 6] = [lm_response, nt_response, &domain, &user, &[], &[]];
+
+    let mut msg = Vec::with_capacity(
+        HEADER
+            + payloads
+                .iter()
+                .map(|p| p.len())
+                .sum::<usize>(),
+    );
+    msg.extend_from_slice(SIGNATURE);
+    msg.extend(AUTHENTICATE.to_le_bytes());
+    let mut offset = HEADER;
+    for payload in payloads {
+        let len = payload.len() as u16;
+        msg.extend(len.to_le_bytes());
+        msg.extend(len.to_le_bytes());
+        msg.extend((offset as u32).to_le_bytes());
+        offset += payload.len();
+    }
+    msg.extend(flags.to_le_bytes());
+    for payload in payloads {
+        msg.extend_from_slice(payload);
+    }
+    msg
+}
+
+fn le32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(
+        buf[at..at + 4]
+            .try_into()
+            .unwrap(),
+    )
+}
+
+/// A length, capacity and offset triple pointing into the message.
+pub fn field(msg: &[u8], at: usize) -> Result<&[u8]> {
+    let header = msg
+        .get(at..at + 8)
+        .ok_or(Error::Protocol("truncated NTLM message"))?;
+    let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+    let offset = u32::from_le_bytes(
+        header[4..8]
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    msg.get(offset..offset + len)
+        .ok_or(Error::Protocol("NTLM field out of bounds"))
+}
+
+// ---------------------------------------------------------------------------
+// SPNEGO
+// ---------------------------------------------------------------------------
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match content.len() {
+        len @ 0..0x80 => out.push(len as u8),
+        len @ 0x80..0x100 => out.extend([0x81, len as u8]),
+        len => {
+            out.push(0x82);
+            out.extend((len as u16).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// One TLV: its tag, its content and whatever follows it.
+fn der_read(buf: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = buf.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let len = rest[..n]
+            .iter()
+            .fold(0usize, |len, &b| len << 8 | b as usize);
+        (len, &rest[n..])
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
+
+/// NegTokenInit offering NTLMSSP alone, with `token` as its first leg.
+pub fn spnego_init(token: &[u8]) -> Vec<u8> {
+    let mech_types = der(0xa0, &der(0x30, &der(0x06, NTLMSSP_OID)));
+    let mech_token = der(0xa2, &der(0x04, token));
+    let init = der(0xa0, &der(0x30, &[mech_types, mech_token].concat()));
+    der(0x60, &[der(0x06, SPNEGO_OID), init].concat())
+}
+
+/// NegTokenResp carrying the next leg.
+pub fn spnego_response(token: &[u8]) -> Vec<u8> {
+    der(0xa1, &der(0x30, &der(0xa2, &der(0x04, token))))
+}
+
+/// The mechanism token in a NegTokenInit or NegTokenResp; both keep it at
+/// context tag 2.
+pub fn spnego_token(blob: &[u8]) -> Result<&[u8]> {
+    const MALFORMED: Error = Error::Protocol("malformed SPNEGO token");
+    let (mut tag, mut body, _) = der_read(blob).ok_or(MALFORMED)?;
+    if tag == 0x60 {
+        let (_, _, after_oid) = der_read(body).ok_or(MALFORMED)?;
+        (tag, body, _) = der_read(after_oid).ok_or(MALFORMED)?;
+    }
+    if tag != 0xa0 && tag != 0xa1 {
+        return Err(MALFORMED);
+    }
+    let (0x30, mut fields, _) = der_read(body).ok_or(MALFORMED)? else {
+        return Err(MALFORMED);
+    };
+    while let Some((tag, value, rest)) = der_read(fields) {
+        if tag == 0xa2 {
+            return match der_read(value) {
+                Some((0x04, token, _)) => Ok(token),
+                _ => Err(MALFORMED),
+            };
+        }
+        fields = rest;
+    }
+    Err(Error::Protocol("SPNEGO token carries no NTLM message"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MS-NLMP 4.2.4, the NTLMv2 example.
+    fn spec_challenge() -> Challenge {
+        let mut target_info = Vec::new();
+        for (id, name) in [(2u16, "Domain"), (1, "Server")] {
+            let name = utf16le(name);
+            target_info.extend(id.to_le_bytes());
+            target_info.extend((name.len() as u16).to_le_bytes());
+            target_info.extend(name);
+        }
+        target_info.extend([0; 4]);
+        Challenge {
+            flags: CLIENT_FLAGS,
+            server_challenge: [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef],
+            target_info,
+        }
+    }
+
+    #[test]
+    fn ntlmv2_matches_the_specification_example() {
+        assert_eq!(
+            nt_owf_v2("User", "Domain", "Password"),
+            [
+                0x0c, 0x86, 0x8a, 0x40, 0x3b, 0xfd, 0x7a, 0x93, 0xa3, 0x00, 0x1e, 0xf2,
+                0x2e, 0xf0, 0x2e, 0x3f
+            ]
+        );
+
+        let creds = Credentials {
+            user: "User",
+            domain: "Domain",
+            password: "Password",
+        };
+        let auth = authenticate(&spec_challenge(), &creds, [0xaa; 8], 0);
+        let lm = field(&auth.message, 12).unwrap();
+        let nt = field(&auth.message, 20).unwrap();
+        assert_eq!(
+            lm,
+            [
+                0x86, 0xc3, 0x50, 0x97, 0xac, 0x9c, 0xec, 0x10, 0x25, 0x54, 0x76, 0x4a,
+                0x57, 0xcc, 0xcc, 0x19, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa
+            ]
+        );
+        assert_eq!(
+            nt[..16],
+            [
+                0x68, 0xcd, 0x0a, 0xb8, 0x51, 0xe5, 0x1c, 0x96, 0xaa, 0xbc, 0x92, 0x7b,
+                0xeb, 0xef, 0x6a, 0x1c
+            ]
+        );
+        assert_eq!(
+            auth.session_key,
+            Some([
+                0x8d, 0xe4, 0x0c, 0xca, 0xdb, 0xc1, 0x4a, 0x82, 0xf1, 0x5c, 0xb0, 0xad,
+                0x0d, 0xe9, 0x5c, 0xa3
+            ])
+        );
+        assert_eq!(field(&auth.message, 36).unwrap(), utf16le("User"));
+    }
+
+    /// With a server timestamp the LMv2 response is zeroed and the NTLMv2
+    /// blob carries the server's time, not ours.
+    #[test]
+    fn a_server_timestamp_replaces_the_client_clock() {
+        let mut challenge = spec_challenge();
+        let eol = challenge
+            .target_info
+            .len()
+            - 4;
+        challenge
+            .target_info
+            .splice(
+                eol..eol,
+                [7u16.to_le_bytes(), 8u16.to_le_bytes()]
+                    .concat()
+                    .into_iter()
+                    .chain(42u64.to_le_bytes()),
+            );
+        let creds = Credentials {
+            user: "User",
+            domain: "Domain",
+            password: "Password",
+        };
+        let auth = authenticate(&challenge, &creds, [0xaa; 8], 7);
+        assert_eq!(field(&auth.message, 12).unwrap(), [0; 24]);
+        assert_eq!(
+            field(&auth.message, 20).unwrap()[24..32],
+            42u64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn spnego_wraps_and_unwraps_the_ntlm_legs() {
+        let negotiate = negotiate_message();
+        assert_eq!(spnego_token(&spnego_init(&negotiate)).unwrap(), negotiate);
+        let long = vec![7u8; 300];
+        assert_eq!(spnego_token(&spnego_response(&long)).unwrap(), long);
+        assert!(spnego_token(&[0x30, 0x00]).is_err());
+    }
+}
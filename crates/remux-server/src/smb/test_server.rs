@@ -0,0 +1,639 @@
+//! An in-memory SMB2 server standing in for a NAS in tests. It checks the
+//! NTLMv2 answer and, when asked to require signing, every signature, so a
+//! client mistake shows up as a refused request rather than passing quietly.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+
+use super::{ntlm, *};
+
+const SERVER_CHALLENGE: [u8; 8] = *b"remux!!!";
+const STATUS_NOT_A_DIRECTORY: u32 = 0xc000_0103;
+const STATUS_FILE_IS_A_DIRECTORY: u32 = 0xc000_00ba;
+const STATUS_INVALID_PARAMETER: u32 = 0xc000_000d;
+/// 2026-01-01T00:00:00Z.
+const MODIFIED: u64 = (1_767_225_600 + FILETIME_UNIX_OFFSET) * 10_000_000;
+/// Directory entries per QUERY_DIRECTORY answer, small to make the client
+/// page.
+const ENTRIES_PER_ANSWER: usize = 2;
+
+pub struct Share {
+    pub name: &'static str,
+    /// `None` admits anonymous sessions only.
+    pub user: Option<(&'static str, &'static str)>,
+    pub signing_required: bool,
+    /// Paths with forward slashes; directories are implied.
+    pub files: Vec<(&'static str, Vec<u8>)>,
+}
+
+pub struct TestServer {
+    pub addr: SocketAddr,
+    share: Arc<Share>,
+    connections: Arc<AtomicUsize>,
+    hang_up: watch::Sender<u64>,
+}
+
+impl TestServer {
+    pub async fn start(share: Share) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = listener
+            .local_addr()
+            .unwrap();
+        let share = Arc::new(share);
+        let connections = Arc::new(AtomicUsize::new(0));
+        let (hang_up, _) = watch::channel(0);
+        let server = Self {
+            addr,
+            share: share.clone(),
+            connections: connections.clone(),
+            hang_up: hang_up.clone(),
+        };
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener
+                .accept()
+                .await
+            {
+                connections.fetch_add(1, Ordering::SeqCst);
+                let mut session = ServerSession::new(share.clone());
+                let mut hang_up = hang_up.subscribe();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        _ = session.serve(stream) => {}
+                        _ = hang_up.changed() => {}
+                    }
+                });
+            }
+        });
+        server
+    }
+
+    /// A client for the share, signed in as its user.
+    pub fn config(&self) -> SmbConfig {
+        SmbConfig {
+            endpoint: self
+                .addr
+                .to_string(),
+            share: self
+                .share
+                .name
+                .to_string(),
+            username: self
+                .share
+                .user
+                .map(|(user, _)| user.to_string()),
+            password: self
+                .share
+                .user
+                .map(|(_, password)| password.to_string()),
+            domain: Some("WORKGROUP".to_string()),
+        }
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections
+            .load(Ordering::SeqCst)
+    }
+
+    /// Close every open connection, as a server does to idle sessions.
+    pub fn hang_up(&self) {
+        self.hang_up
+            .send_modify(|n| *n += 1);
+    }
+}
+
+struct ServerSession {
+    share: Arc<Share>,
+    signing_key: Option<[u8; 16]>,
+    handles: HashMap<u64, String>,
+    next_handle: u64,
+    listings: HashMap<u64, Vec<(String, FileInfo)>>,
+    pending_sent: bool,
+}
+
+impl ServerSession {
+    fn new(share: Arc<Share>) -> Self {
+        Self {
+            share,
+            signing_key: None,
+            handles: HashMap::new(),
+            next_handle: 1,
+            listings: HashMap::new(),
+            pending_sent: false,
+        }
+    }
+
+    async fn serve(&mut self, mut stream: TcpStream) {
+        loop {
+            let mut frame = [0; 4];
+            if stream
+                .read_exact(&mut frame)
+                .await
+                .is_err()
+            {
+                return;
+            }
+            let mut msg = vec![0; u32::from_be_bytes(frame) as usize];
+            if stream
+                .read_exact(&mut msg)
+                .await
+                .is_err()
+            {
+                return;
+            }
+            let command = le16(&msg, 12).unwrap();
+            let message_id = le64(&msg, 24).unwrap();
+
+            let (status, body) = if !self.signature_ok(&mut msg) {
+                (STATUS_ACCESS_DENIED, error_body())
+            } else {
+                if command == READ && !self.pending_sent {
+                    // Answer the first read the slow way, as a busy server does.
+                    self.pending_sent = true;
+                    let interim = response(
+                        READ,
+                        message_id,
+                        STATUS_PENDING,
+                        FLAGS_ASYNC,
+                        &error_body(),
+                    );
+                    send(&mut stream, &interim).await;
+                }
+                self.handle(command, &msg)
+            };
+            let resp = response(command, message_id, status, 0, &body);
+            send(&mut stream, &resp).await;
+        }
+    }
+
+    /// Once the session is keyed, every request must carry a valid signature.
+    fn signature_ok(&self, msg: &mut [u8]) -> bool {
+        let Some(key) = &self.signing_key else {
+            return true;
+        };
+        if le32(msg, 16).unwrap() & FLAGS_SIGNED == 0 {
+            return false;
+        }
+        let signature: [u8; 16] = msg[48..64]
+            .try_into()
+            .unwrap();
+        msg[48..64].fill(0);
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(msg);
+        mac.finalize()
+            .into_bytes()[..16]
+            == signature
+    }
+
+    fn handle(&mut self, command: u16, msg: &[u8]) -> (u32, Vec<u8>) {
+        let body = &msg[64..];
+        match command {
+            NEGOTIATE => {
+                let mode = SIGNING_ENABLED
+                    | if self
+                        .share
+                        .signing_required
+                    {
+                        SIGNING_REQUIRED
+                    } else {
+                        0
+                    };
+                let mut out = Vec::new();
+                out.extend(65u16.to_le_bytes());
+                out.extend(mode.to_le_bytes());
+                out.extend(DIALECT_2_1.to_le_bytes());
+                out.extend(0u16.to_le_bytes());
+                out.extend([0x5a; 16]);
+                out.extend(0u32.to_le_bytes());
+                out.extend(MAX_TRANSFER.to_le_bytes());
+                out.extend(MAX_TRANSFER.to_le_bytes());
+                out.extend(MAX_TRANSFER.to_le_bytes());
+                out.extend(MODIFIED.to_le_bytes());
+                out.extend(0u64.to_le_bytes());
+                out.extend(128u16.to_le_bytes());
+                out.extend(0u16.to_le_bytes());
+                out.extend(0u32.to_le_bytes());
+                out.push(0);
+                (STATUS_SUCCESS, out)
+            }
+            SESSION_SETUP => {
+                let blob = slice(
+                    msg,
+                    le16(body, 12).unwrap() as usize,
+                    le16(body, 14).unwrap() as usize,
+                )
+                .unwrap();
+                let token = ntlm::spnego_token(blob).unwrap();
+                match le32(token, 8).unwrap() {
+                    1 => (
+                        STATUS_MORE_PROCESSING_REQUIRED,
+                        session_setup_response(0, &ntlm::spnego_response(&challenge())),
+                    ),
+                    _ => match self.check_authenticate(token) {
+                        Some(flags) => {
+                            (STATUS_SUCCESS, session_setup_response(flags, &[]))
+                        }
+                        None => (STATUS_LOGON_FAILURE, error_body()),
+                    },
+                }
+            }
+            TREE_CONNECT => {
+                let path = utf16(
+                    slice(
+                        msg,
+                        le16(body, 4).unwrap() as usize,
+                        le16(body, 6).unwrap() as usize,
+                    )
+                    .unwrap(),
+                );
+                let share = path
+                    .rsplit('\\')
+                    .next()
+                    .unwrap_or_default();
+                if !share.eq_ignore_ascii_case(
+                    self.share
+                        .name,
+                ) {
+                    return (STATUS_BAD_NETWORK_NAME, error_body());
+                }
+                let mut out = Vec::new();
+                out.extend(16u16.to_le_bytes());
+                out.push(1); // disk
+                out.push(0);
+                out.extend(0u32.to_le_bytes());
+                out.extend(0u32.to_le_bytes());
+                out.extend(READ_ACCESS.to_le_bytes());
+                (STATUS_SUCCESS, out)
+            }
+            CREATE => {
+                let name = utf16(
+                    slice(
+                        msg,
+                        le16(body, 44).unwrap() as usize,
+                        le16(body, 46).unwrap() as usize,
+                    )
+                    .unwrap(),
+                )
+                .replace('\\', "/");
+                let options = le32(body, 40).unwrap();
+                let Some(info) = self.lookup(&name) else {
+                    return (STATUS_OBJECT_NAME_NOT_FOUND, error_body());
+                };
+                if options & FILE_DIRECTORY_FILE != 0 && !info.is_dir {
+                    return (STATUS_NOT_A_DIRECTORY, error_body());
+                }
+                if options & FILE_NON_DIRECTORY_FILE != 0 && info.is_dir {
+                    return (STATUS_FILE_IS_A_DIRECTORY, error_body());
+                }
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                self.handles
+                    .insert(handle, name);
+                let mut out = Vec::new();
+                out.extend(89u16.to_le_bytes());
+                out.push(0);
+                out.push(0);
+                out.extend(1u32.to_le_bytes()); // opened
+                for _ in 0..4 {
+                    out.extend(MODIFIED.to_le_bytes());
+                }
+                out.extend(
+                    info.size
+                        .to_le_bytes(),
+                );
+                out.extend(
+                    info.size
+                        .to_le_bytes(),
+                );
+                out.extend(attributes(&info).to_le_bytes());
+                out.extend(0u32.to_le_bytes());
+                out.extend(handle.to_le_bytes());
+                out.extend(handle.to_le_bytes());
+                out.extend(0u32.to_le_bytes());
+                out.extend(0u32.to_le_bytes());
+                (STATUS_SUCCESS, out)
+            }
+            CLOSE => {
+                let handle = le64(body, 8).unwrap();
+                if self
+                    .handles
+                    .remove(&handle)
+                    .is_none()
+                {
+                    return (STATUS_INVALID_PARAMETER, error_body());
+                }
+                self.listings
+                    .remove(&handle);
+                let mut out = Vec::new();
+                out.extend(60u16.to_le_bytes());
+                out.extend([0; 58]);
+                (STATUS_SUCCESS, out)
+            }
+            READ => {
+                let len = le32(body, 4).unwrap() as usize;
+                let offset = le64(body, 8).unwrap() as usize;
+                let handle = le64(body, 16).unwrap();
+                let Some(data) = self
+                    .handles
+                    .get(&handle)
+                    .and_then(|path| self.file(path))
+                else {
+                    return (STATUS_INVALID_PARAMETER, error_body());
+                };
+                if offset >= data.len() {
+                    return (STATUS_END_OF_FILE, error_body());
+                }
+                let chunk = &data[offset..(offset + len).min(data.len())];
+                let mut out = Vec::new();
+                out.extend(17u16.to_le_bytes());
+                out.push(0x50);
+                out.push(0);
+                out.extend((chunk.len() as u32).to_le_bytes());
+                out.extend(0u32.to_le_bytes());
+                out.extend(0u32.to_le_bytes());
+                out.extend(chunk);
+                (STATUS_SUCCESS, out)
+            }
+            QUERY_DIRECTORY => {
+                let handle = le64(body, 8).unwrap();
+                let Some(dir) = self
+                    .handles
+                    .get(&handle)
+                    .cloned()
+                else {
+                    return (STATUS_INVALID_PARAMETER, error_body());
+                };
+                if body[3] & RESTART_SCANS != 0 {
+                    let listing = self.children(&dir);
+                    self.listings
+                        .insert(handle, listing);
+                }
+                let listing = self
+                    .listings
+                    .entry(handle)
+                    .or_default();
+                if listing.is_empty() {
+                    return (STATUS_NO_MORE_FILES, error_body());
+                }
+                let page: Vec<_> = listing
+                    .drain(..ENTRIES_PER_ANSWER.min(listing.len()))
+                    .collect();
+                let mut entries = Vec::new();
+                for (i, (name, info)) in page
+                    .iter()
+                    .enumerate()
+                {
+                    let name = ntlm::utf16le(name);
+                    let mut entry = Vec::new();
+                    entry.extend(0u32.to_le_bytes());
+                    entry.extend(0u32.to_le_bytes());
+                    for _ in 0..4 {
+                        entry.extend(MODIFIED.to_le_bytes());
+                    }
+                    entry.extend(
+                        info.size
+                            .to_le_bytes(),
+                    );
+                    entry.extend(
+                        info.size
+                            .to_le_bytes(),
+                    );
+                    entry.extend(attributes(info).to_le_bytes());
+                    entry.extend((name.len() as u32).to_le_bytes());
+                    entry.extend(name);
+                    while entry.len() % 8 != 0 {
+                        entry.push(0);
+                    }
+                    if i + 1 < page.len() {
+                        let next = entry.len() as u32;
+                        entry[..4].copy_from_slice(&next.to_le_bytes());
+                    }
+                    entries.extend(entry);
+                }
+                let mut out = Vec::new();
+                out.extend(9u16.to_le_bytes());
+                out.extend(72u16.to_le_bytes());
+                out.extend((entries.len() as u32).to_le_bytes());
+                out.extend(entries);
+                (STATUS_SUCCESS, out)
+            }
+            _ => (STATUS_INVALID_PARAMETER, error_body()),
+        }
+    }
+
+    /// Session flags for a good answer, or `None` for a refused one.
+    fn check_authenticate(&mut self, msg: &[u8]) -> Option<u16> {
+        let user = utf16(ntlm::field(msg, 36).ok()?);
+        let domain = utf16(ntlm::field(msg, 28).ok()?);
+        let nt = ntlm::field(msg, 20).ok()?;
+        let Some((expected_user, password)) = self
+            .share
+            .user
+        else {
+            return user
+                .is_empty()
+                .then_some(SESSION_FLAG_IS_NULL);
+        };
+        if !user.eq_ignore_ascii_case(expected_user) || nt.len() < 16 {
+            return None;
+        }
+        let key = ntlm::nt_owf_v2(&user, &domain, password);
+        let proof = ntlm::hmac_md5(&key, &[&SERVER_CHALLENGE, &nt[16..]]);
+        if proof != nt[..16] {
+            return None;
+        }
+        if self
+            .share
+            .signing_required
+        {
+            self.signing_key = Some(ntlm::hmac_md5(&key, &[&proof]));
+        }
+        Some(0)
+    }
+
+    fn file(&self, path: &str) -> Option<&[u8]> {
+        self.share
+            .files
+            .iter()
+            .find(|(p, _)| *p == path)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    fn lookup(&self, path: &str) -> Option<FileInfo> {
+        if let Some(data) = self.file(path) {
+            return Some(FileInfo {
+                size: data.len() as u64,
+                modified: None,
+                is_dir: false,
+            });
+        }
+        let prefix = format!("{path}/");
+        (path.is_empty()
+            || self
+                .share
+                .files
+                .iter()
+                .any(|(p, _)| p.starts_with(&prefix)))
+        .then_some(FileInfo {
+            size: 0,
+            modified: None,
+            is_dir: true,
+        })
+    }
+
+    fn children(&self, dir: &str) -> Vec<(String, FileInfo)> {
+        let prefix = if dir.is_empty() {
+            String::new()
+        } else {
+            format!("{dir}/")
+        };
+        let mut names: Vec<String> = self
+            .share
+            .files
+            .iter()
+            .filter_map(|(p, _)| p.strip_prefix(&prefix))
+            .map(|rest| {
+                rest.split('/')
+                    .next()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        let mut listing = vec![
+            (
+                ".".to_string(),
+                self.lookup(dir)
+                    .unwrap(),
+            ),
+            (
+                "..".to_string(),
+                self.lookup("")
+                    .unwrap(),
+            ),
+        ];
+        for name in names {
+            let info = self
+                .lookup(&format!("{prefix}{name}"))
+                .unwrap();
+            listing.push((name, info));
+        }
+        listing
+    }
+}
+
+fn attributes(info: &FileInfo) -> u32 {
+    if info.is_dir {
+        FILE_ATTRIBUTE_DIRECTORY
+    } else {
+        0x80 // normal
+    }
+}
+
+fn utf16(bytes: &[u8]) -> String {
+    String::from_utf16_lossy(
+        &bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn challenge() -> Vec<u8> {
+    let mut target_info = Vec::new();
+    for (id, value) in [
+        (2u16, ntlm::utf16le("WORKGROUP")),
+        (1, ntlm::utf16le("NAS")),
+        (
+            7,
+            MODIFIED
+                .to_le_bytes()
+                .to_vec(),
+        ),
+    ] {
+        target_info.extend(id.to_le_bytes());
+        target_info.extend((value.len() as u16).to_le_bytes());
+        target_info.extend(value);
+    }
+    target_info.extend([0; 4]);
+
+    let mut msg = Vec::new();
+    msg.extend(b"NTLMSSP\0");
+    msg.extend(2u32.to_le_bytes());
+    msg.extend([0, 0, 0, 0, 48, 0, 0, 0]); // empty target name
+    msg.extend(0xe288_8215u32.to_le_bytes());
+    msg.extend(SERVER_CHALLENGE);
+    msg.extend([0; 8]);
+    msg.extend((target_info.len() as u16).to_le_bytes());
+    msg.extend((target_info.len() as u16).to_le_bytes());
+    msg.extend(48u32.to_le_bytes());
+    msg.extend(target_info);
+    msg
+}
+
+fn session_setup_response(flags: u16, token: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(9u16.to_le_bytes());
+    out.extend(flags.to_le_bytes());
+    out.extend(72u16.to_le_bytes());
+    out.extend((token.len() as u16).to_le_bytes());
+    out.extend(token);
+    out
+}
+
+fn error_body() -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(9u16.to_le_bytes());
+    out.extend([0; 7]);
+    out
+}
+
+fn response(
+    command: u16,
+    message_id: u64,
+    status: u32,
+    flags: u32,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut msg = Vec::new();
+    msg.extend(b"\xfeSMB");
+    msg.extend(64u16.to_le_bytes());
+    msg.extend(1u16.to_le_bytes());
+    msg.extend(status.to_le_bytes());
+    msg.extend(command.to_le_bytes());
+    msg.extend(CREDITS.to_le_bytes());
+    msg.extend((flags | 1).to_le_bytes());
+    msg.extend(0u32.to_le_bytes());
+    msg.extend(message_id.to_le_bytes());
+    msg.extend(0u32.to_le_bytes());
+    msg.extend(7u32.to_le_bytes()); // tree id
+    msg.extend(0x1234u64.to_le_bytes()); // session id
+    msg.extend([0; 16]);
+    msg.extend(body);
+    msg
+}
+
+async fn send(stream: &mut TcpStream, msg: &[u8]) {
+    let _ = stream
+        .write_all(&(msg.len() as u32).to_be_bytes())
+        .await;
+    let _ = stream
+        .write_all(msg)
+        .await;
+}
@@ -0,0 +1,1090 @@
+//! Server-side state for SyncPlay (watch-together) groups.
+//!
+//! A group moves between the Jellyfin states `Idle`, `Waiting`, `Paused` and
+//! `Playing`. Whenever members have to load or re-buffer (a new queue, a seek,
+//! someone joining mid-play or stalling) the group waits until every member
+//! reports `Ready`, then resumes for everyone at once. Members learn about
+//! changes through `SyncPlayCommand` and `SyncPlayGroupUpdate` websocket
+//! messages.
+
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast;
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::{
+    api::{
+        GroupInfoDto, GroupStateType, GroupStateUpdate, GroupUpdate, GroupUpdateType,
+        PlayQueueUpdate, PlayQueueUpdateReason, PlaybackRequestType, SendCommand,
+        SendCommandType, SyncPlayQueueItem,
+    },
+    common::get_uuid,
+    db::auth::AuthSession,
+    ws::WsEvent,
+};
+
+const TICKS_PER_MILLISECOND: i64 = 10_000;
+
+/// Lead time given to a synchronised unpause on top of the slowest member's
+/// ping, so the command reaches everyone before it is due.
+const UNPAUSE_LEAD_MS: i64 = 500;
+
+/// Who is acting on a group: one websocket-connected device.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub device_id: String,
+    pub user_id: Uuid,
+    pub user_name: String,
+}
+
+impl Member {
+    pub fn from_session(session: &AuthSession) -> Self {
+        Self {
+            device_id: session
+                .device
+                .id
+                .clone(),
+            user_id: session
+                .user
+                .id,
+            user_name: session
+                .user
+                .username
+                .clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Participant {
+    member: Member,
+    /// Has the current item loaded at the group's position.
+    ready: bool,
+    ping_ms: i64,
+}
+
+#[derive(Debug)]
+struct Group {
+    id: Uuid,
+    name: String,
+    state: GroupStateType,
+    participants: Vec<Participant>,
+    queue: Vec<SyncPlayQueueItem>,
+    playing_index: Option<usize>,
+    /// Position at `position_at`; while playing, the clock adds the rest.
+    position_ticks: i64,
+    position_at: DateTime<Utc>,
+    /// Whether a waiting group plays or stays paused once everyone is ready.
+    resume_playing: bool,
+    last_updated: DateTime<Utc>,
+}
+
+impl Group {
+    fn info(&self) -> GroupInfoDto {
+        GroupInfoDto {
+            group_id: self.id,
+            group_name: self
+                .name
+                .clone(),
+            state: self.state,
+            participants: self
+                .participants
+                .iter()
+                .map(|p| {
+                    p.member
+                        .user_name
+                        .clone()
+                })
+                .collect(),
+            last_updated_at: self.last_updated,
+        }
+    }
+
+    fn device_ids(&self) -> Vec<String> {
+        self.participants
+            .iter()
+            .map(|p| {
+                p.member
+                    .device_id
+                    .clone()
+            })
+            .collect()
+    }
+
+    fn participant_mut(&mut self, device_id: &str) -> Option<&mut Participant> {
+        self.participants
+            .iter_mut()
+            .find(|p| {
+                p.member
+                    .device_id
+                    == device_id
+            })
+    }
+
+    fn current_item(&self) -> Option<&SyncPlayQueueItem> {
+        self.queue
+            .get(self.playing_index?)
+    }
+
+    fn position_now(&self, now: DateTime<Utc>) -> i64 {
+        match self.state {
+            GroupStateType::Playing => {
+                let elapsed = (now - self.position_at)
+                    .num_milliseconds()
+                    .max(0);
+                self.position_ticks + elapsed * TICKS_PER_MILLISECOND
+            }
+            _ => self.position_ticks,
+        }
+    }
+
+    fn set_position(&mut self, ticks: i64, at: DateTime<Utc>) {
+        self.position_ticks = ticks.max(0);
+        self.position_at = at;
+    }
+
+    fn all_ready(&self) -> bool {
+        self.participants
+            .iter()
+            .all(|p| p.ready)
+    }
+
+    fn mark_all_waiting(&mut self) {
+        for p in &mut self.participants {
+            p.ready = false;
+        }
+    }
+
+    fn highest_ping(&self) -> i64 {
+        self.participants
+            .iter()
+            .map(|p| p.ping_ms)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn play_queue(
+        &self,
+        reason: PlayQueueUpdateReason,
+        now: DateTime<Utc>,
+    ) -> PlayQueueUpdate {
+        PlayQueueUpdate {
+            reason,
+            last_update: self.last_updated,
+            playlist: self
+                .queue
+                .clone(),
+            playing_item_index: self
+                .playing_index
+                .map_or(-1, |i| i as i32),
+            start_position_ticks: self.position_now(now),
+            is_playing: self.state == GroupStateType::Playing || self.resume_playing,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Groups {
+    groups: HashMap<Uuid, Group>,
+    /// Which group each device is in; a device is in at most one.
+    members: HashMap<String, Uuid>,
+}
+
+/// Owns every SyncPlay group. Cheap to clone; clones share state.
+#[derive(Clone)]
+pub struct SyncPlayManager {
+    inner: Arc<Mutex<Groups>>,
+    ws_tx: broadcast::Sender<WsEvent>,
+}
+
+impl SyncPlayManager {
+    pub fn new(ws_tx: broadcast::Sender<WsEvent>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Groups::default())),
+            ws_tx,
+        }
+    }
+
+    pub fn list(&self) -> Vec<GroupInfoDto> {
+        let inner = self.lock();
+        let mut groups: Vec<_> = inner
+            .groups
+            .values()
+            .map(Group::info)
+            .collect();
+        groups.sort_by(|a, b| {
+            a.group_name
+                .cmp(&b.group_name)
+        });
+        groups
+    }
+
+    pub fn get(&self, group_id: &Uuid) -> Option<GroupInfoDto> {
+        self.lock()
+            .groups
+            .get(group_id)
+            .map(Group::info)
+    }
+
+    /// Creates a group with `member` as its only participant, taking them out
+    /// of any group they were in.
+    pub fn new_group(&self, member: Member, name: String) -> GroupInfoDto {
+        let mut inner = self.lock();
+        self.remove_member(&mut inner, &member.device_id, true);
+        let now = Utc::now();
+        let name = if name
+            .trim()
+            .is_empty()
+        {
+            format!("{}'s group", member.user_name)
+        } else {
+            name
+        };
+        let group = Group {
+            id: get_uuid(),
+            name,
+            state: GroupStateType::Idle,
+            participants: vec![Participant {
+                member: member.clone(),
+                ready: true,
+                ping_ms: 0,
+            }],
+            queue: vec![],
+            playing_index: None,
+            position_ticks: 0,
+            position_at: now,
+            resume_playing: false,
+            last_updated: now,
+        };
+        let info = group.info();
+        debug!(group = %group.id, device_id = %member.device_id, "SyncPlay group created");
+        self.update(
+            vec![
+                member
+                    .device_id
+                    .clone(),
+            ],
+            group.id,
+            GroupUpdateType::GroupJoined,
+            &info,
+        );
+        self.state_update(
+            &group,
+            vec![
+                member
+                    .device_id
+                    .clone(),
+            ],
+            PlaybackRequestType::Play,
+        );
+        inner
+            .members
+            .insert(member.device_id, group.id);
+        inner
+            .groups
+            .insert(group.id, group);
+        info
+    }
+
+    pub fn join(&self, member: Member, group_id: Uuid) {
+        let mut inner = self.lock();
+        if !inner
+            .groups
+            .contains_key(&group_id)
+        {
+            self.update(
+                vec![member.device_id],
+                group_id,
+                GroupUpdateType::GroupDoesNotExist,
+                &"",
+            );
+            return;
+        }
+        if inner
+            .members
+            .get(&member.device_id)
+            != Some(&group_id)
+        {
+            self.remove_member(&mut inner, &member.device_id, true);
+        }
+        inner
+            .members
+            .insert(
+                member
+                    .device_id
+                    .clone(),
+                group_id,
+            );
+        let now = Utc::now();
+        let group = inner
+            .groups
+            .get_mut(&group_id)
+            .expect("checked above");
+        let joiner = vec![
+            member
+                .device_id
+                .clone(),
+        ];
+        let others: Vec<String> = group
+            .device_ids()
+            .into_iter()
+            .filter(|d| *d != member.device_id)
+            .collect();
+        let idle = group.state == GroupStateType::Idle;
+        match group.participant_mut(&member.device_id) {
+            Some(p) => p.ready = idle,
+            None => group
+                .participants
+                .push(Participant {
+                    member: member.clone(),
+                    ready: idle,
+                    ping_ms: 0,
+                }),
+        }
+        group.last_updated = now;
+
+        self.update(
+            joiner.clone(),
+            group_id,
+            GroupUpdateType::GroupJoined,
+            &group.info(),
+        );
+        self.update(
+            others,
+            group_id,
+            GroupUpdateType::UserJoined,
+            &member.user_name,
+        );
+        if idle {
+            self.state_update(group, joiner, PlaybackRequestType::Play);
+            return;
+        }
+        // The newcomer has to load the item first; everyone else holds.
+        self.update(
+            joiner,
+            group_id,
+            GroupUpdateType::PlayQueue,
+            &group.play_queue(PlayQueueUpdateReason::NewPlaylist, now),
+        );
+        self.wait(group, now, PlaybackRequestType::Buffer);
+    }
+
+    /// Takes `device_id` out of its group, telling it and the rest.
+    pub fn leave(&self, device_id: &str) {
+        let mut inner = self.lock();
+        if !self.remove_member(&mut inner, device_id, true) {
+            self.not_in_group(device_id);
+        }
+    }
+
+    /// A device went away without leaving; the rest of its group is told.
+    pub fn disconnect(&self, device_id: &str) {
+        let mut inner = self.lock();
+        self.remove_member(&mut inner, device_id, false);
+    }
+
+    pub fn set_new_queue(
+        &self,
+        device_id: &str,
+        items: Vec<Uuid>,
+        playing_position: i32,
+        start_position_ticks: i64,
+    ) {
+        if items.is_empty() {
+            return self.stop(device_id);
+        }
+        self.with_group(device_id, |group| {
+            let now = Utc::now();
+            group.queue = items
+                .into_iter()
+                .map(|item_id| SyncPlayQueueItem {
+                    item_id,
+                    playlist_item_id: get_uuid(),
+                })
+                .collect();
+            group.playing_index = Some(
+                (playing_position.max(0) as usize).min(
+                    group
+                        .queue
+                        .len()
+                        - 1,
+                ),
+            );
+            group.set_position(start_position_ticks, now);
+            group.resume_playing = true;
+            group.state = GroupStateType::Waiting;
+            group.mark_all_waiting();
+            group.last_updated = now;
+            self.update(
+                group.device_ids(),
+                group.id,
+                GroupUpdateType::PlayQueue,
+                &group.play_queue(PlayQueueUpdateReason::NewPlaylist, now),
+            );
+            self.state_update(group, group.device_ids(), PlaybackRequestType::Play);
+        });
+    }
+
+    pub fn pause(&self, device_id: &str) {
+        self.with_group(device_id, |group| {
+            let now = Utc::now();
+            match group.state {
+                GroupStateType::Playing => {
+                    group.set_position(group.position_now(now), now);
+                    group.state = GroupStateType::Paused;
+                }
+                GroupStateType::Waiting => group.resume_playing = false,
+                GroupStateType::Paused | GroupStateType::Idle => return,
+            }
+            group.last_updated = now;
+            self.command(group, SendCommandType::Pause, now);
+            self.state_update(group, group.device_ids(), PlaybackRequestType::Pause);
+        });
+    }
+
+    pub fn unpause(&self, device_id: &str) {
+        self.with_group(device_id, |group| {
+            let now = Utc::now();
+            match group.state {
+                GroupStateType::Paused if group.all_ready() => {
+                    self.play(group, now, PlaybackRequestType::Unpause)
+                }
+                GroupStateType::Paused | GroupStateType::Waiting => {
+                    group.resume_playing = true;
+                    group.state = GroupStateType::Waiting;
+                    group.last_updated = now;
+                    self.state_update(
+                        group,
+                        group.device_ids(),
+                        PlaybackRequestType::Unpause,
+                    );
+                }
+                GroupStateType::Playing | GroupStateType::Idle => {}
+            }
+        });
+    }
+
+    pub fn stop(&self, device_id: &str) {
+        self.with_group(device_id, |group| {
+            if group.state == GroupStateType::Idle {
+                return;
+            }
+            let now = Utc::now();
+            group.state = GroupStateType::Idle;
+            group.resume_playing = false;
+            group.set_position(0, now);
+            for p in &mut group.participants {
+                p.ready = true;
+            }
+            group.last_updated = now;
+            self.command(group, SendCommandType::Stop, now);
+            self.state_update(group, group.device_ids(), PlaybackRequestType::Stop);
+        });
+    }
+
+    pub fn seek(&self, device_id: &str, position_ticks: i64) {
+        self.with_group(device_id, |group| {
+            let now = Utc::now();
+            group.resume_playing = match group.state {
+                GroupStateType::Idle => return,
+                GroupStateType::Playing => true,
+                GroupStateType::Paused => false,
+                GroupStateType::Waiting => group.resume_playing,
+            };
+            group.set_position(position_ticks, now);
+            group.state = GroupStateType::Waiting;
+            group.mark_all_waiting();
+            group.last_updated = now;
+            self.command(group, SendCommandType::Seek, now);
+            self.state_update(group, group.device_ids(), PlaybackRequestType::Seek);
+        });
+    }
+
+    /// A member stalled at `position_ticks`; the others pause there with it.
+    pub fn buffering(
+        &self,
+        device_id: &str,
+        playlist_item_id: Option<Uuid>,
+        position_ticks: i64,
+    ) {
+        self.with_group(device_id, |group| {
+            if !self.on_current_item(group, device_id, playlist_item_id) {
+                return;
+            }
+            let now = Utc::now();
+            if let Some(p) = group.participant_mut(device_id) {
+                p.ready = false;
+            }
+            match group.state {
+                GroupStateType::Playing => {
+                    group.set_position(position_ticks, now);
+                    self.wait(group, now, PlaybackRequestType::Buffer);
+                }
+                GroupStateType::Paused => {
+                    group.resume_playing = false;
+                    group.state = GroupStateType::Waiting;
+                    group.last_updated = now;
+                    self.state_update(
+                        group,
+                        group.device_ids(),
+                        PlaybackRequestType::Buffer,
+                    );
+                }
+                GroupStateType::Waiting | GroupStateType::Idle => {}
+            }
+        });
+    }
+
+    /// A member has the current item loaded. Once the last one is, a waiting
+    /// group plays or pauses for everyone.
+    pub fn ready(&self, device_id: &str, playlist_item_id: Option<Uuid>) {
+        self.with_group(device_id, |group| {
+            if !self.on_current_item(group, device_id, playlist_item_id) {
+                return;
+            }
+            if let Some(p) = group.participant_mut(device_id) {
+                p.ready = true;
+            }
+            self.resume_if_ready(group);
+        });
+    }
+
+    pub fn ping(&self, device_id: &str, ping_ms: i64) {
+        let mut inner = self.lock();
+        let Some(group_id) = inner
+            .members
+            .get(device_id)
+            .copied()
+        else {
+            return;
+        };
+        if let Some(p) = inner
+            .groups
+            .get_mut(&group_id)
+            .and_then(|g| g.participant_mut(device_id))
+        {
+            p.ping_ms = ping_ms.max(0);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Groups> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs `f` on the caller's group, or tells the caller it has none.
+    fn with_group(&self, device_id: &str, f: impl FnOnce(&mut Group)) {
+        let mut inner = self.lock();
+        let group = inner
+            .members
+            .get(device_id)
+            .copied()
+            .and_then(|id| {
+                inner
+                    .groups
+                    .get_mut(&id)
+            });
+        match group {
+            Some(group) => f(group),
+            None => self.not_in_group(device_id),
+        }
+    }
+
+    /// Removes a device from its group, deleting the group once empty.
+    /// Returns whether the device was in one.
+    fn remove_member(
+        &self,
+        inner: &mut Groups,
+        device_id: &str,
+        notify_leaver: bool,
+    ) -> bool {
+        let Some(group_id) = inner
+            .members
+            .remove(device_id)
+        else {
+            return false;
+        };
+        let Some(group) = inner
+            .groups
+            .get_mut(&group_id)
+        else {
+            return true;
+        };
+        let Some(index) = group
+            .participants
+            .iter()
+            .position(|p| {
+                p.member
+                    .device_id
+                    == device_id
+            })
+        else {
+            return true;
+        };
+        let leaver = group
+            .participants
+            .remove(index)
+            .member;
+        group.last_updated = Utc::now();
+        if notify_leaver {
+            self.update(
+                vec![leaver.device_id],
+                group_id,
+                GroupUpdateType::GroupLeft,
+                &group_id.to_string(),
+            );
+        }
+        if group
+            .participants
+            .is_empty()
+        {
+            debug!(group = %group_id, "SyncPlay group closed");
+            inner
+                .groups
+                .remove(&group_id);
+            return true;
+        }
+        self.update(
+            group.device_ids(),
+            group_id,
+            GroupUpdateType::UserLeft,
+            &leaver.user_name,
+        );
+        // The group may only have been waiting on the one who left.
+        self.resume_if_ready(group);
+        true
+    }
+
+    /// Whether a buffering or ready report is about the item the group is
+    /// on. A member that is out of step is sent the queue again instead.
+    fn on_current_item(
+        &self,
+        group: &Group,
+        device_id: &str,
+        playlist_item_id: Option<Uuid>,
+    ) -> bool {
+        let Some(current) = group.current_item() else {
+            return false;
+        };
+        if playlist_item_id.is_none_or(|id| id == current.playlist_item_id) {
+            return true;
+        }
+        self.update(
+            vec![device_id.to_string()],
+            group.id,
+            GroupUpdateType::PlayQueue,
+            &group.play_queue(PlayQueueUpdateReason::SetCurrentItem, Utc::now()),
+        );
+        false
+    }
+
+    /// Holds the whole group at its position until everyone is ready again.
+    fn wait(&self, group: &mut Group, now: DateTime<Utc>, reason: PlaybackRequestType) {
+        if group.state == GroupStateType::Playing {
+            group.set_position(group.position_now(now), now);
+            group.resume_playing = true;
+            self.command(group, SendCommandType::Pause, now);
+        } else if group.state == GroupStateType::Paused {
+            group.resume_playing = false;
+        }
+        group.state = GroupStateType::Waiting;
+        group.last_updated = now;
+        self.state_update(group, group.device_ids(), reason);
+    }
+
+    fn resume_if_ready(&self, group: &mut Group) {
+        if group.state != GroupStateType::Waiting || !group.all_ready() {
+            return;
+        }
+        let now = Utc::now();
+        if group.resume_playing {
+            self.play(group, now, PlaybackRequestType::Ready);
+        } else {
+            group.state = GroupStateType::Paused;
+            group.last_updated = now;
+            self.command(group, SendCommandType::Pause, now);
+            self.state_update(group, group.device_ids(), PlaybackRequestType::Ready);
+        }
+    }
+
+    /// Starts everyone at once: far enough ahead that the slowest member
+    /// gets the command in time.
+    fn play(&self, group: &mut Group, now: DateTime<Utc>, reason: PlaybackRequestType) {
+        let when = now
+            + chrono::Duration::milliseconds(group.highest_ping() + UNPAUSE_LEAD_MS);
+        group.position_at = when;
+        group.state = GroupStateType::Playing;
+        group.resume_playing = false;
+        group.last_updated = now;
+        self.send_command(group, SendCommandType::Unpause, when, now);
+        self.state_update(group, group.device_ids(), reason);
+    }
+
+    fn command(&self, group: &Group, command: SendCommandType, now: DateTime<Utc>) {
+        self.send_command(group, command, now, now);
+    }
+
+    fn send_command(
+        &self,
+        group: &Group,
+        command: SendCommandType,
+        when: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) {
+        let data = SendCommand {
+            group_id: group.id,
+            playlist_item_id: group
+                .current_item()
+                .map(|i| i.playlist_item_id)
+                .unwrap_or_default(),
+            when,
+            position_ticks: (command != SendCommandType::Stop)
+                .then_some(group.position_ticks),
+            command,
+            emitted_at: now,
+        };
+        let _ = self
+            .ws_tx
+            .send(WsEvent::SyncPlayCommand {
+                device_ids: group.device_ids(),
+                data,
+            });
+    }
+
+    fn state_update(
+        &self,
+        group: &Group,
+        device_ids: Vec<String>,
+        reason: PlaybackRequestType,
+    ) {
+        self.update(
+            device_ids,
+            group.id,
+            GroupUpdateType::StateUpdate,
+            &GroupStateUpdate {
+                state: group.state,
+                reason,
+            },
+        );
+    }
+
+    fn not_in_group(&self, device_id: &str) {
+        self.update(
+            vec![device_id.to_string()],
+            Uuid::nil(),
+            GroupUpdateType::NotInGroup,
+            &"",
+        );
+    }
+
+    fn update(
+        &self,
+        device_ids: Vec<String>,
+        group_id: Uuid,
+        kind: GroupUpdateType,
+        data: &impl serde::Serialize,
+    ) {
+        if device_ids.is_empty() {
+            return;
+        }
+        let _ = self
+            .ws_tx
+            .send(WsEvent::SyncPlayGroupUpdate {
+                device_ids,
+                data: GroupUpdate {
+                    group_id,
+                    kind,
+                    data: serde_json::to_value(data).unwrap_or_default(),
+                },
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(name: &str) -> Member {
+        Member {
+            device_id: format!("{name}-device"),
+            user_id: Uuid::new_v4(),
+            user_name: name.to_string(),
+        }
+    }
+
+    fn manager() -> (SyncPlayManager, broadcast::Receiver<WsEvent>) {
+        let (tx, rx) = broadcast::channel(64);
+        (SyncPlayManager::new(tx), rx)
+    }
+
+    fn drain(rx: &mut broadcast::Receiver<WsEvent>) -> Vec<WsEvent> {
+        std::iter::from_fn(|| {
+            rx.try_recv()
+                .ok()
+        })
+        .collect()
+    }
+
+    fn commands(events: &[WsEvent]) -> Vec<(Vec<String>, SendCommand)> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                WsEvent::SyncPlayCommand { device_ids, data } => {
+                    Some((device_ids.clone(), data.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn updates(
+        events: &[WsEvent],
+        kind: GroupUpdateType,
+    ) -> Vec<(Vec<String>, GroupUpdate)> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                WsEvent::SyncPlayGroupUpdate { device_ids, data }
+                    if data.kind == kind =>
+                {
+                    Some((device_ids.clone(), data.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn state(manager: &SyncPlayManager, group_id: &Uuid) -> GroupStateType {
+        manager
+            .get(group_id)
+            .unwrap()
+            .state
+    }
+
+    fn playlist_item(manager: &SyncPlayManager, device_id: &str) -> Uuid {
+        let inner = manager.lock();
+        let group = &inner.groups[&inner.members[device_id]];
+        group
+            .current_item()
+            .unwrap()
+            .playlist_item_id
+    }
+
+    /// Two members in a group that is playing one item.
+    fn playing_pair() -> (
+        SyncPlayManager,
+        broadcast::Receiver<WsEvent>,
+        Uuid,
+        Member,
+        Member,
+    ) {
+        let (manager, mut rx) = manager();
+        let (ann, bob) = (member("ann"), member("bob"));
+        let group = manager
+            .new_group(ann.clone(), "movie night".into())
+            .group_id;
+        manager.join(bob.clone(), group);
+        manager.set_new_queue(&ann.device_id, vec![Uuid::new_v4()], 0, 0);
+        let item = playlist_item(&manager, &ann.device_id);
+        manager.ready(&ann.device_id, Some(item));
+        manager.ready(&bob.device_id, Some(item));
+        drain(&mut rx);
+        (manager, rx, group, ann, bob)
+    }
+
+    #[test]
+    fn joining_tells_the_joiner_and_the_rest() {
+        let (manager, mut rx) = manager();
+        let (ann, bob) = (member("ann"), member("bob"));
+        let group = manager
+            .new_group(ann.clone(), "movie night".into())
+            .group_id;
+        drain(&mut rx);
+
+        manager.join(bob.clone(), group);
+        let events = drain(&mut rx);
+        let joined = updates(&events, GroupUpdateType::GroupJoined);
+        assert_eq!(
+            joined[0].0,
+            vec![
+                bob.device_id
+                    .clone()
+            ]
+        );
+        assert_eq!(
+            joined[0]
+                .1
+                .data["Participants"],
+            serde_json::json!(["ann", "bob"])
+        );
+        let user_joined = updates(&events, GroupUpdateType::UserJoined);
+        assert_eq!(user_joined[0].0, vec![ann.device_id]);
+        assert_eq!(
+            user_joined[0]
+                .1
+                .data,
+            "bob"
+        );
+    }
+
+    #[test]
+    fn joining_a_missing_group_says_so() {
+        let (manager, mut rx) = manager();
+        manager.join(member("ann"), Uuid::new_v4());
+        assert_eq!(
+            updates(&drain(&mut rx), GroupUpdateType::GroupDoesNotExist).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn a_new_queue_plays_once_everyone_is_ready() {
+        let (manager, mut rx) = manager();
+        let (ann, bob) = (member("ann"), member("bob"));
+        let group = manager
+            .new_group(ann.clone(), String::new())
+            .group_id;
+        manager.join(bob.clone(), group);
+        manager.set_new_queue(&ann.device_id, vec![Uuid::new_v4()], 0, 42);
+        assert_eq!(state(&manager, &group), GroupStateType::Waiting);
+        let item = playlist_item(&manager, &ann.device_id);
+        drain(&mut rx);
+
+        manager.ready(&ann.device_id, Some(item));
+        assert!(commands(&drain(&mut rx)).is_empty(), "bob is still loading");
+        manager.ready(&bob.device_id, Some(item));
+        let sent = commands(&drain(&mut rx));
+        assert_eq!(sent.len(), 1);
+        let (targets, cmd) = &sent[0];
+        assert_eq!(targets.len(), 2);
+        assert_eq!(cmd.command, SendCommandType::Unpause);
+        assert_eq!(cmd.position_ticks, Some(42));
+        assert!(cmd.when > cmd.emitted_at);
+        assert_eq!(state(&manager, &group), GroupStateType::Playing);
+    }
+
+    #[test]
+    fn a_stalled_member_pauses_everyone_until_it_catches_up() {
+        let (manager, mut rx, group, ann, bob) = playing_pair();
+        let item = playlist_item(&manager, &ann.device_id);
+
+        manager.buffering(&bob.device_id, Some(item), 1_000);
+        let sent = commands(&drain(&mut rx));
+        assert_eq!(
+            sent[0]
+                .1
+                .command,
+            SendCommandType::Pause
+        );
+        assert_eq!(
+            sent[0]
+                .1
+                .position_ticks,
+            Some(1_000)
+        );
+        assert_eq!(state(&manager, &group), GroupStateType::Waiting);
+
+        manager.ready(&bob.device_id, Some(item));
+        let sent = commands(&drain(&mut rx));
+        assert_eq!(
+            sent[0]
+                .1
+                .command,
+            SendCommandType::Unpause
+        );
+        assert_eq!(state(&manager, &group), GroupStateType::Playing);
+    }
+
+    #[test]
+    fn a_seek_while_paused_stays_paused() {
+        let (manager, mut rx, group, ann, bob) = playing_pair();
+        let item = playlist_item(&manager, &ann.device_id);
+        manager.pause(&bob.device_id);
+        assert_eq!(state(&manager, &group), GroupStateType::Paused);
+
+        manager.seek(&ann.device_id, 5_000);
+        manager.ready(&ann.device_id, Some(item));
+        manager.ready(&bob.device_id, Some(item));
+        let sent = commands(&drain(&mut rx));
+        let kinds: Vec<_> = sent
+            .iter()
+            .map(|(_, c)| c.command)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SendCommandType::Pause,
+                SendCommandType::Seek,
+                SendCommandType::Pause
+            ]
+        );
+        assert_eq!(
+            sent[2]
+                .1
+                .position_ticks,
+            Some(5_000)
+        );
+        assert_eq!(state(&manager, &group), GroupStateType::Paused);
+    }
+
+    #[test]
+    fn a_ready_report_for_another_item_resends_the_queue() {
+        let (manager, mut rx, _group, _ann, bob) = playing_pair();
+        manager.ready(&bob.device_id, Some(Uuid::new_v4()));
+        let queue = updates(&drain(&mut rx), GroupUpdateType::PlayQueue);
+        assert_eq!(queue[0].0, vec![bob.device_id]);
+    }
+
+    #[test]
+    fn the_last_member_leaving_closes_the_group() {
+        let (manager, mut rx, group, ann, bob) = playing_pair();
+        manager.leave(&bob.device_id);
+        let events = drain(&mut rx);
+        assert_eq!(
+            updates(&events, GroupUpdateType::GroupLeft)[0].0,
+            vec![bob.device_id]
+        );
+        assert_eq!(
+            updates(&events, GroupUpdateType::UserLeft)[0].0,
+            vec![
+                ann.device_id
+                    .clone()
+            ]
+        );
+
+        manager.disconnect(&ann.device_id);
+        assert!(
+            manager
+                .get(&group)
+                .is_none()
+        );
+        manager.pause(&ann.device_id);
+        assert_eq!(
+            updates(&drain(&mut rx), GroupUpdateType::NotInGroup).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn a_member_leaving_releases_a_group_waiting_on_it() {
+        let (manager, mut rx, group, ann, bob) = playing_pair();
+        let item = playlist_item(&manager, &ann.device_id);
+        manager.buffering(&bob.device_id, Some(item), 1_000);
+        drain(&mut rx);
+
+        manager.disconnect(&bob.device_id);
+        assert_eq!(
+            commands(&drain(&mut rx))[0]
+                .1
+                .command,
+            SendCommandType::Unpause
+        );
+        assert_eq!(state(&manager, &group), GroupStateType::Playing);
+    }
+}
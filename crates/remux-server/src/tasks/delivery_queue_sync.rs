@@ -197,8 +197,23 @@ async fn deliver_media_tracker(
                 .clone(),
         ),
     };
+    // Providers with expiring tokens renew here, ahead of the call that needs
+    // them. A renewed token is saved before use: a provider that rotates
+    // refresh tokens has already invalidated the old one.
+    let creds = addon
+        .refresh(&conn.credentials, &tctx)
+        .await?;
+    if creds != conn.credentials {
+        db::UserMediaTracker::set_credentials(&ctx.db, conn.id, &creds)
+            .await
+            .map_err(|e| {
+                MediaTrackerError::retryable(format!(
+                    "saving refreshed credentials: {e}"
+                ))
+            })?;
+    }
     addon
-        .on_event(&payload.event, &target, &conn.credentials, &tctx)
+        .on_event(&payload.event, &target, &creds, &tctx)
         .await
 }
 
@@ -234,6 +249,9 @@ mod tests {
         script: Mutex<VecDeque<MediaTrackerResult<()>>>,
         seen: Mutex<Vec<(MediaTrackerEventKind, String)>>,
         targets: Mutex<Vec<MediaTrackerTarget>>,
+        /// What `refresh` hands back instead of the credentials it was given.
+        renewed: Option<MediaTrackerCredentials>,
+        creds: Mutex<Vec<MediaTrackerCredentials>>,
     }
 
     impl ScriptedAddon {
@@ -243,9 +261,30 @@ mod tests {
                 script: Mutex::new(script.into()),
                 seen: Mutex::new(Vec::new()),
                 targets: Mutex::new(Vec::new()),
+                renewed: None,
+                creds: Mutex::new(Vec::new()),
             })
         }
 
+        /// A provider whose token has expired: `refresh` swaps it for `to`.
+        fn renewing(to: serde_json::Value) -> Arc<Self> {
+            Arc::new(Self {
+                script: Mutex::new(VecDeque::new()),
+                seen: Mutex::new(Vec::new()),
+                targets: Mutex::new(Vec::new()),
+                renewed: Some(MediaTrackerCredentials::new(to)),
+                creds: Mutex::new(Vec::new()),
+            })
+        }
+
+        /// The credentials each delivery was made with.
+        fn creds(&self) -> Vec<MediaTrackerCredentials> {
+            self.creds
+                .lock()
+                .unwrap()
+                .clone()
+        }
+
         fn seen(&self) -> Vec<(MediaTrackerEventKind, String)> {
             self.seen
                 .lock()
@@ -274,13 +313,28 @@ mod tests {
             MediaTrackerCapabilities::default()
         }
 
+        async fn refresh(
+            &self,
+            creds: &MediaTrackerCredentials,
+            _ctx: &MediaTrackerCtx,
+        ) -> MediaTrackerResult<MediaTrackerCredentials> {
+            Ok(self
+                .renewed
+                .clone()
+                .unwrap_or_else(|| creds.clone()))
+        }
+
         async fn on_event(
             &self,
             event: &MediaTrackerEvent,
             target: &MediaTrackerTarget,
-            _creds: &MediaTrackerCredentials,
+            creds: &MediaTrackerCredentials,
             _ctx: &MediaTrackerCtx,
         ) -> MediaTrackerResult<()> {
+            self.creds
+                .lock()
+                .unwrap()
+                .push(creds.clone());
             self.seen
                 .lock()
                 .unwrap()
@@ -429,6 +483,32 @@ mod tests {
         );
     }
 
+    /// A renewed token is only worth anything if it outlives the pass, and the
+    /// delivery that prompted the renewal has to use it.
+    #[tokio::test]
+    async fn renewed_credentials_are_used_and_saved() {
+        let (_s, guard) = new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+        let addon = ScriptedAddon::renewing(serde_json::json!({ "token": "fresh" }));
+        let (conn, _) = connect(ctx, "ivan", addon.clone()).await;
+        queue(&ctx.db, conn, "Thief").await;
+
+        drain(ctx, &reporter())
+            .await
+            .unwrap();
+
+        let fresh =
+            MediaTrackerCredentials::new(serde_json::json!({ "token": "fresh" }));
+        assert_eq!(addon.creds(), vec![fresh.clone()]);
+        let tracker = UserMediaTracker::get(&ctx.db, conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tracker.credentials, fresh);
+    }
+
     #[tokio::test]
     async fn a_retryable_failure_leaves_the_tracker_health_untouched() {
         let (_s, guard) = new_test_server()
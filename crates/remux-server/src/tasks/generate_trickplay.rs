@@ -0,0 +1,106 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{collections::HashSet, sync::Arc};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{ProgressReporter, Task, TaskCategory, TaskService};
+use crate::{AppContext, db, stream::StreamDescriptor};
+
+pub struct GenerateTrickplayTask;
+
+#[async_trait]
+impl Task for GenerateTrickplayTask {
+    fn key(&self) -> &str {
+        "GenerateTrickplay"
+    }
+
+    fn name(&self) -> &str {
+        "Generate Trickplay Images"
+    }
+
+    fn description(&self) -> &str {
+        "Creates seek preview thumbnails for local and storage-addon videos that don't have them yet, using the interval and width from the encoding settings. Remote streams get theirs the first time they are played."
+    }
+
+    fn short_description(&self) -> &str {
+        "Creates seek preview thumbnails"
+    }
+
+    fn category(&self) -> TaskCategory {
+        TaskCategory::Library
+    }
+
+    async fn run(
+        &self,
+        ctx: AppContext,
+        _tasks: Arc<TaskService>,
+        progress: ProgressReporter,
+    ) -> Result<()> {
+        // Only persisted stream records: walking items would resolve remote
+        // streams over the network for the whole library.
+        let streams: Vec<db::Media> = db::Media::get_by_filter(
+            &ctx.db,
+            &db::MediaFilter {
+                kind: Some(vec![db::MediaKind::Stream]),
+                ..Default::default()
+            },
+        )
+        .await?
+        .records
+        .into_iter()
+        .filter(|s| {
+            matches!(
+                s.stream_info
+                    .as_ref()
+                    .map(|si| &si.descriptor),
+                Some(StreamDescriptor::Local(_) | StreamDescriptor::Opendal { .. })
+            )
+        })
+        .collect();
+        let streams = without_tracks(&ctx, streams).await?;
+
+        let total = streams.len();
+        let mut failed = 0usize;
+        for (i, stream) in streams
+            .iter()
+            .enumerate()
+        {
+            if let Err(e) = crate::trickplay::generate(&ctx, stream).await {
+                warn!(id = %stream.id, "trickplay generation failed: {e:#}");
+                failed += 1;
+            }
+            progress.report(i + 1, total);
+        }
+        info!(total, failed, "trickplay generation finished");
+
+        progress.set(100.0);
+        Ok(())
+    }
+}
+
+/// Drops the sources of music tracks, which have no pictures to preview.
+async fn without_tracks(
+    ctx: &AppContext,
+    streams: Vec<db::Media>,
+) -> Result<Vec<db::Media>> {
+    let parent_ids: Vec<Uuid> = streams
+        .iter()
+        .filter_map(|s| s.parent_id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let tracks: HashSet<Uuid> = db::Media::get_by_ids(&ctx.db, &parent_ids)
+        .await?
+        .into_iter()
+        .filter(|m| m.kind == db::MediaKind::Track)
+        .map(|m| m.id)
+        .collect();
+    Ok(streams
+        .into_iter()
+        .filter(|s| {
+            !s.parent_id
+                .is_some_and(|id| tracks.contains(&id))
+        })
+        .collect())
+}
@@ -22,7 +22,9 @@ mod clean_transcode_folder;
 mod clear_cache;
 mod clear_image_cache;
 mod delivery_queue_sync;
+mod generate_trickplay;
 mod jellyfin_import;
+mod process_downloads;
 mod purge_iptv;
 mod purge_media;
 mod purge_metrics;
@@ -40,7 +42,9 @@ use clean_transcode_folder::CleanTranscodeFolderTask;
 use clear_cache::ClearCacheTask;
 use clear_image_cache::ClearImageCacheTask;
 pub use delivery_queue_sync::{DELIVERY_QUEUE_SYNC_KEY, DeliveryQueueSyncTask};
+use generate_trickplay::GenerateTrickplayTask;
 use jellyfin_import::JellyfinImportTask;
+use process_downloads::ProcessDownloadsTask;
 use purge_iptv::PurgeIptvTask;
 use purge_media::PurgeMediaTask;
 use purge_metrics::PurgeMetricsTask;
@@ -50,6 +54,7 @@ use purge_shows::PurgeShowsTask;
 use refresh_all_meta::RefreshAllMetaTask;
 use refresh_iptv::RefreshIptvTask;
 use refresh_library::RefreshLibraryTask;
+pub(crate) use refresh_library::import_catalogs;
 use refresh_popularity::RefreshPopularityTask;
 use series_sync::SeriesSyncTask;
 
@@ -312,6 +317,9 @@ impl TaskService {
         service
             .register_task(Arc::new(RefreshAllMetaTask))
             .await?;
+        service
+            .register_task(Arc::new(GenerateTrickplayTask))
+            .await?;
         // service.register_task(Arc::new(SeriesSyncTask)).await?;
         service
             .register_task(Arc::new(PurgeMediaTask))
@@ -340,6 +348,9 @@ impl TaskService {
         service
             .register_task(Arc::new(PurgeMetricsTask))
             .await?;
+        service
+            .register_task(Arc::new(ProcessDownloadsTask))
+            .await?;
         let triggers = db::TaskTrigger::get_all(
             &service
                 .ctx
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use super::{ProgressReporter, Task, TaskCategory, TaskService};
+use crate::{AppContext, db::DownloadJob, downloads};
+
+pub struct ProcessDownloadsTask;
+
+#[async_trait]
+impl Task for ProcessDownloadsTask {
+    fn key(&self) -> &str {
+        downloads::TASK_KEY
+    }
+    fn name(&self) -> &str {
+        "Process Downloads"
+    }
+    fn description(&self) -> &str {
+        "Transcodes queued offline downloads and deletes the ones that have expired."
+    }
+    fn short_description(&self) -> &str {
+        "Transcodes queued offline downloads"
+    }
+    fn category(&self) -> TaskCategory {
+        TaskCategory::Users
+    }
+
+    async fn run(
+        &self,
+        ctx: AppContext,
+        _tasks: Arc<TaskService>,
+        progress: ProgressReporter,
+    ) -> Result<()> {
+        purge_expired(&ctx).await?;
+
+        // Only one run works the queue at a time, so anything still marked
+        // running was cut short by a restart or by stopping the task.
+        let requeued = DownloadJob::requeue_interrupted(&ctx.db).await?;
+        if requeued > 0 {
+            info!(requeued, "requeued interrupted downloads");
+        }
+
+        // Jobs queued while this run is going are picked up by it, so the
+        // total grows as they arrive.
+        let mut done = 0usize;
+        while let Some(job) = DownloadJob::next_queued(&ctx.db).await? {
+            let total = done + DownloadJob::count_queued(&ctx.db).await? as usize;
+            let slot = progress.scaled(
+                done as f64 / total as f64 * 100.0,
+                (done + 1) as f64 / total as f64 * 100.0,
+            );
+            DownloadJob::mark_running(&ctx.db, job.id).await?;
+
+            let (tx, mut rx) = tokio::sync::watch::channel(0.0);
+            let transcode = downloads::transcode(&ctx, &job, move |pct| {
+                tx.send_replace(pct);
+            });
+            tokio::pin!(transcode);
+            let mut saved = 0.0;
+            let result = loop {
+                tokio::select! {
+                    result = &mut transcode => break result,
+                    Ok(()) = rx.changed() => {
+                        let pct = *rx.borrow_and_update();
+                        slot.set(pct);
+                        // Whole percents are plenty for the download list.
+                        if pct.floor() > saved {
+                            saved = pct.floor();
+                            DownloadJob::set_progress(&ctx.db, job.id, saved).await?;
+                        }
+                    }
+                }
+            };
+
+            match result {
+                Ok(size) => {
+                    let expires_at = Utc::now().naive_utc()
+                        + chrono::Duration::days(downloads::RETENTION_DAYS);
+                    if !DownloadJob::mark_completed(
+                        &ctx.db,
+                        job.id,
+                        size as i64,
+                        expires_at,
+                    )
+                    .await?
+                    {
+                        // Deleted while it was being transcoded.
+                        let _ = std::fs::remove_file(downloads::job_path(
+                            &ctx.config,
+                            &job,
+                        ));
+                    }
+                }
+                Err(e) => {
+                    warn!(job = %job.id, "download failed: {e:#}");
+                    DownloadJob::mark_failed(&ctx.db, job.id, &format!("{e:#}"))
+                        .await?;
+                }
+            }
+            done += 1;
+        }
+
+        progress.set(100.0);
+        Ok(())
+    }
+}
+
+/// Drop expired jobs with their files, then any file no job owns.
+async fn purge_expired(ctx: &AppContext) -> Result<()> {
+    let expired = DownloadJob::expired(&ctx.db, Utc::now().naive_utc()).await?;
+    for job in &expired {
+        let _ = std::fs::remove_file(downloads::job_path(&ctx.config, job));
+        DownloadJob::delete(&ctx.db, job.id).await?;
+    }
+
+    let ids = DownloadJob::all_ids(&ctx.db).await?;
+    let mut orphans = 0usize;
+    for entry in super::iter_dir(downloads::downloads_dir(&ctx.config)) {
+        let owned = entry
+            .path()
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| {
+                s.parse()
+                    .ok()
+            })
+            .is_some_and(|id| ids.contains(&id));
+        if !owned && std::fs::remove_file(entry.path()).is_ok() {
+            orphans += 1;
+        }
+    }
+    info!(expired = expired.len(), orphans, "cleaned downloads");
+    Ok(())
+}
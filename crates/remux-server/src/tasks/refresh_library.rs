@@ -11,7 +11,7 @@ use super::{
         remove_stale_catalog_memberships,
     },
 };
-use crate::{AppContext, db};
+use crate::{AppContext, addons::ResolvedCatalog, db};
 use remux_sdks::stremio::ResourceType;
 
 pub struct RefreshLibraryTask;
@@ -104,61 +104,12 @@ impl Task for RefreshLibraryTask {
                 "importing enabled catalogs"
             );
 
-            for (cat_idx, cat_info) in enabled
-                .iter()
-                .enumerate()
-            {
-                addon_progress.report(
-                    cat_idx,
-                    enabled
-                        .len()
-                        .max(1),
-                );
-
-                let full_id = &cat_info.catalog_id;
-                let max = cat_info
-                    .max_items
-                    .map(|n| n as usize)
-                    .unwrap_or(global_max);
-
-                valid_collection_ids.insert(cat_info.collection_id);
-
-                let source = match ctx
-                    .addons
-                    .make_catalog_stream(full_id)
-                {
-                    Some(s) => s,
-                    None => {
-                        warn!(catalog = %full_id, "no addon found for catalog, skipping");
-                        continue;
-                    }
-                };
-
-                debug!(catalog = %full_id, max, "importing catalog items");
-
-                let stream = match source
-                    .stream(&ctx)
-                    .await
-                {
-                    Ok(s) => s,
-                    Err(e) => {
-                        error!(catalog = %full_id, error = %e, "failed to open catalog stream");
-                        continue;
-                    }
-                };
-
-                let (counts, new_counts) = import_catalog_items(
-                    &ctx,
-                    cat_info,
-                    full_id,
-                    max,
-                    stream,
-                    &addon_progress,
-                )
-                .await?;
-
-                info!(catalog = %full_id, total = ?counts, new = ?new_counts, "catalog import complete");
-            }
+            valid_collection_ids.extend(
+                enabled
+                    .iter()
+                    .map(|cat_info| cat_info.collection_id),
+            );
+            import_catalogs(&ctx, &enabled, global_max, &addon_progress).await?;
         }
 
         // Must run before remove_stale_catalog_memberships below.
@@ -214,3 +165,60 @@ impl Task for RefreshLibraryTask {
         Ok(())
     }
 }
+
+/// Imports `catalogs` in turn, each a step of `progress`. Also used by the
+/// opendal watcher to re-import one addon's catalogs after it indexed changes.
+pub(crate) async fn import_catalogs(
+    ctx: &AppContext,
+    catalogs: &[&ResolvedCatalog],
+    global_max: usize,
+    progress: &ProgressReporter,
+) -> Result<()> {
+    for (cat_idx, cat_info) in catalogs
+        .iter()
+        .enumerate()
+    {
+        progress.report(
+            cat_idx,
+            catalogs
+                .len()
+                .max(1),
+        );
+
+        let full_id = &cat_info.catalog_id;
+        let max = cat_info
+            .max_items
+            .map(|n| n as usize)
+            .unwrap_or(global_max);
+
+        let source = match ctx
+            .addons
+            .make_catalog_stream(full_id)
+        {
+            Some(s) => s,
+            None => {
+                warn!(catalog = %full_id, "no addon found for catalog, skipping");
+                continue;
+            }
+        };
+
+        debug!(catalog = %full_id, max, "importing catalog items");
+
+        let stream = match source
+            .stream(ctx)
+            .await
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!(catalog = %full_id, error = %e, "failed to open catalog stream");
+                continue;
+            }
+        };
+
+        let (counts, new_counts) =
+            import_catalog_items(ctx, cat_info, full_id, max, stream, progress).await?;
+
+        info!(catalog = %full_id, total = ?counts, new = ?new_counts, "catalog import complete");
+    }
+    Ok(())
+}
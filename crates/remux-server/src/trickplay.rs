@@ -0,0 +1,530 @@
+//! Trickplay (seek preview) tile sheets.
+//!
+//! Sheets are generated with ffmpeg and stored per concrete stream record under
+//! `{data_dir}/trickplay/{source_id}/{width}/` as `0.jpg`, `1.jpg`, … next to an
+//! `info.json` holding the sheet's `TrickplayInfo`. Keying by stream rather than
+//! by item keeps every version of a movie or episode apart.
+
+use anyhow::{Result, anyhow, bail};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    AppContext, api, common::HideConsole, db, keyed_lock::KeyedLock,
+    stream::StreamDescriptor,
+};
+
+/// Thumbnails per row and per column of a tile sheet.
+pub const TILE_COLUMNS: u32 = 10;
+pub const TILE_ROWS: u32 = 10;
+
+const INFO_FILE: &str = "info.json";
+const GENERATE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// One generation per stream at a time; a second caller waits and then finds
+/// the finished sheets.
+static GENERATE_LOCKS: KeyedLock<Uuid> = KeyedLock::new();
+
+fn ffmpeg_bin() -> String {
+    std::env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".into())
+}
+
+fn source_dir(data_dir: &Path, source_id: Uuid) -> PathBuf {
+    data_dir
+        .join("trickplay")
+        .join(
+            source_id
+                .simple()
+                .to_string(),
+        )
+}
+
+pub fn sheet_dir(data_dir: &Path, source_id: Uuid, width: u32) -> PathBuf {
+    source_dir(data_dir, source_id).join(width.to_string())
+}
+
+pub fn sheet_path(data_dir: &Path, source_id: Uuid, width: u32, index: u32) -> PathBuf {
+    sheet_dir(data_dir, source_id, width).join(format!("{index}.jpg"))
+}
+
+/// The finished sheet set for one width, if it has been generated.
+pub async fn load_info(
+    data_dir: &Path,
+    source_id: Uuid,
+    width: u32,
+) -> Option<api::TrickplayInfo> {
+    let json = tokio::fs::read(sheet_dir(data_dir, source_id, width).join(INFO_FILE))
+        .await
+        .ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Every generated width for a stream, keyed by thumbnail width.
+pub async fn load_all(
+    data_dir: &Path,
+    source_id: Uuid,
+) -> HashMap<u32, api::TrickplayInfo> {
+    let mut out = HashMap::new();
+    let Ok(mut entries) = tokio::fs::read_dir(source_dir(data_dir, source_id)).await
+    else {
+        return out;
+    };
+    while let Ok(Some(entry)) = entries
+        .next_entry()
+        .await
+    {
+        let Some(width) = entry
+            .file_name()
+            .to_str()
+            .and_then(|n| {
+                n.parse::<u32>()
+                    .ok()
+            })
+        else {
+            continue;
+        };
+        if let Some(info) = load_info(data_dir, source_id, width).await {
+            out.insert(width, info);
+        }
+    }
+    out
+}
+
+/// Resolves the stream whose sheets answer a request for `item_id`.
+///
+/// Tries the requested media source, then the item itself (a stream record
+/// requested directly), then the item's streams in source order. Clients send
+/// the item id for the first source, so the fallback covers that case too.
+pub async fn find(
+    ctx: &AppContext,
+    item_id: Uuid,
+    media_source_id: Option<Uuid>,
+    width: u32,
+) -> Result<Option<(Uuid, api::TrickplayInfo)>> {
+    let data_dir = &ctx
+        .config
+        .data_dir;
+    for id in media_source_id
+        .into_iter()
+        .chain(std::iter::once(item_id))
+    {
+        if let Some(info) = load_info(data_dir, id, width).await {
+            return Ok(Some((id, info)));
+        }
+    }
+    let mut streams = db::Media::get_by_filter(
+        &ctx.db,
+        &db::MediaFilter {
+            kind: Some(vec![db::MediaKind::Stream]),
+            parent_id: Some(item_id),
+            ..Default::default()
+        },
+    )
+    .await?
+    .records;
+    streams.sort_by_key(|s| s.idx);
+    for stream in streams {
+        if let Some(info) = load_info(data_dir, stream.id, width).await {
+            return Ok(Some((stream.id, info)));
+        }
+    }
+    Ok(None)
+}
+
+/// HLS image playlist (`#EXT-X-IMAGES-ONLY`) listing every sheet. `query` is
+/// appended to each sheet URL so auth and the media source survive.
+pub fn tiles_playlist(info: &api::TrickplayInfo, query: &str) -> String {
+    let per_sheet = info.tile_width * info.tile_height;
+    let interval_secs = info.interval as f64 / 1000.0;
+    let sheets = sheet_count(info.thumbnail_count, per_sheet);
+    let target = (interval_secs * per_sheet as f64).ceil() as u64;
+
+    let mut out = format!(
+        "#EXTM3U\n#EXT-X-TARGETDURATION:{target}\n#EXT-X-VERSION:7\n\
+         #EXT-X-MEDIA-SEQUENCE:1\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-IMAGES-ONLY\n"
+    );
+    for index in 0..sheets {
+        let thumbs = (info.thumbnail_count - index * per_sheet).min(per_sheet);
+        out.push_str(&format!(
+            "\n#EXTINF:{:.3},\n#EXT-X-TILES:RESOLUTION={}x{},LAYOUT={}x{},DURATION={:.3}\n{index}.jpg",
+            thumbs as f64 * interval_secs,
+            info.width,
+            info.height,
+            info.tile_width,
+            info.tile_height,
+            interval_secs,
+        ));
+        if !query.is_empty() {
+            out.push('?');
+            out.push_str(query);
+        }
+        out.push('\n');
+    }
+    out.push_str("\n#EXT-X-ENDLIST\n");
+    out
+}
+
+fn sheet_count(thumbnails: u32, per_sheet: u32) -> u32 {
+    thumbnails.div_ceil(per_sheet)
+}
+
+/// Thumbnail height for `width` that keeps the video's aspect ratio, rounded
+/// to an even number for the JPEG encoder.
+fn thumbnail_height(width: u32, video_width: i64, video_height: i64) -> u32 {
+    if video_width <= 0 || video_height <= 0 {
+        return (width * 9 / 16) & !1;
+    }
+    let height = (width as i64 * video_height / video_width) as u32;
+    (height & !1).max(2)
+}
+
+/// Number of thumbnails ffmpeg's `fps` filter emits for a clip, clamped to
+/// what the written sheets can hold.
+fn thumbnail_count(
+    duration_ms: u64,
+    interval_ms: u32,
+    sheets: u32,
+    per_sheet: u32,
+) -> u32 {
+    if sheets == 0 {
+        return 0;
+    }
+    let expected = duration_ms.div_ceil(interval_ms as u64) as u32;
+    expected.clamp((sheets - 1) * per_sheet + 1, sheets * per_sheet)
+}
+
+/// Generates sheets for one stream at the configured interval and width,
+/// unless they already exist. Returns `None` for streams without video.
+pub async fn generate(
+    ctx: &AppContext,
+    stream: &db::Media,
+) -> Result<Option<api::TrickplayInfo>> {
+    let opts = db::Settings::get_encoding_config(&ctx.db).await?;
+    let interval = opts
+        .trickplay_interval_ms
+        .unwrap_or(10_000)
+        .max(1000);
+    let width = opts
+        .trickplay_width
+        .unwrap_or(320)
+        .max(16);
+    let data_dir = &ctx
+        .config
+        .data_dir;
+
+    let _guard = GENERATE_LOCKS
+        .lock(stream.id)
+        .await;
+    if let Some(info) = load_info(data_dir, stream.id, width).await {
+        return Ok(Some(info));
+    }
+
+    let input = stream
+        .stream_info
+        .as_ref()
+        .map(|si| {
+            si.descriptor
+                .server_input(
+                    stream.id,
+                    ctx.config
+                        .port,
+                )
+        })
+        .ok_or_else(|| anyhow!("stream {} has no source", stream.id))?;
+
+    // A stored probe that lists streams settles the question, audio-only
+    // sources included; one without any is a placeholder worth replacing.
+    let probe = match stream
+        .probe_data
+        .clone()
+        .filter(|p| {
+            !p.media_streams
+                .is_empty()
+        }) {
+        Some(probe) => probe,
+        None => {
+            let url = input.clone();
+            let probe = tokio::task::spawn_blocking(move || {
+                crate::playback::probe::probe_media(&url)
+            })
+            .await??
+            .0;
+            // Kept, so a source without video is not probed again every run.
+            db::Media::save_probe_data(&ctx.db, &stream.id, &probe).await?;
+            probe
+        }
+    };
+    let Some(video) = probe.video_stream() else {
+        debug!(id = %stream.id, "no video stream, skipping trickplay");
+        return Ok(None);
+    };
+    let Some(duration_ms) = probe
+        .run_time_ticks
+        .filter(|t| *t > 0)
+        .map(|t| t as u64 / 10_000)
+    else {
+        bail!("stream {} has no known duration", stream.id);
+    };
+    let height = thumbnail_height(
+        width,
+        video
+            .width
+            .unwrap_or(0),
+        video
+            .height
+            .unwrap_or(0),
+    );
+
+    let final_dir = sheet_dir(data_dir, stream.id, width);
+    let work_dir = final_dir.with_extension("tmp");
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    tokio::fs::create_dir_all(&work_dir).await?;
+
+    info!(id = %stream.id, width, interval, "generating trickplay sheets");
+    let filter = format!(
+        "fps=1000/{interval},scale={width}:{height},tile={TILE_COLUMNS}x{TILE_ROWS}"
+    );
+    let pattern = work_dir.join("%d.jpg");
+    let mut cmd = tokio::process::Command::new(ffmpeg_bin());
+    cmd.hide_console();
+    cmd.kill_on_drop(true);
+    // Keyframes only: decoding every frame of a feature film for one image
+    // every few seconds costs far more than the small timing error.
+    cmd.args([
+        "-nostdin",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-skip_frame",
+        "nokey",
+        "-i",
+    ])
+    .arg(&input)
+    .args([
+        "-an",
+        "-sn",
+        "-dn",
+        "-vf",
+        &filter,
+        "-q:v",
+        "4",
+        "-start_number",
+        "0",
+        "-f",
+        "image2",
+    ])
+    .arg(&pattern);
+    cmd.stdout(std::process::Stdio::null());
+    cmd.stderr(std::process::Stdio::piped());
+    let output = match tokio::time::timeout(GENERATE_TIMEOUT, cmd.output()).await {
+        Ok(output) => output.map_err(|e| anyhow!("failed to run ffmpeg: {e}"))?,
+        Err(_) => {
+            let _ = tokio::fs::remove_dir_all(&work_dir).await;
+            bail!("trickplay generation timed out");
+        }
+    };
+    if !output
+        .status
+        .success()
+    {
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+        bail!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut sheets = 0u32;
+    let mut largest = 0u64;
+    while let Ok(meta) =
+        tokio::fs::metadata(work_dir.join(format!("{sheets}.jpg"))).await
+    {
+        largest = largest.max(meta.len());
+        sheets += 1;
+    }
+    if sheets == 0 {
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+        bail!("ffmpeg wrote no trickplay sheets");
+    }
+
+    let per_sheet = TILE_COLUMNS * TILE_ROWS;
+    let sheet_ms = interval as u64 * per_sheet as u64;
+    let info = api::TrickplayInfo {
+        width,
+        height,
+        tile_width: TILE_COLUMNS,
+        tile_height: TILE_ROWS,
+        thumbnail_count: thumbnail_count(duration_ms, interval, sheets, per_sheet),
+        interval,
+        bandwidth: (largest * 8 * 1000 / sheet_ms) as u32,
+    };
+    tokio::fs::write(work_dir.join(INFO_FILE), serde_json::to_vec(&info)?).await?;
+    let _ = tokio::fs::remove_dir_all(&final_dir).await;
+    tokio::fs::rename(&work_dir, &final_dir).await?;
+    info!(id = %stream.id, sheets, "trickplay sheets ready");
+    Ok(Some(info))
+}
+
+/// Starts background generation for the stream a client just began playing.
+/// Only remote HTTP sources are handled here; local and opendal libraries are
+/// covered by the scheduled task, and torrents would have to be downloaded in
+/// full.
+pub fn spawn_for_playback(
+    ctx: AppContext,
+    item_id: Uuid,
+    media_source_id: Option<Uuid>,
+    user_id: Uuid,
+) {
+    tokio::spawn(async move {
+        let stream = match crate::services::StreamService::lookup(
+            &ctx,
+            item_id,
+            media_source_id,
+            None,
+            Some(user_id),
+        )
+        .await
+        {
+            Ok(stream) => stream,
+            Err(e) => {
+                debug!(%item_id, "trickplay: no stream to generate from: {e:#}");
+                return;
+            }
+        };
+        if !matches!(
+            stream
+                .stream_info
+                .as_ref()
+                .map(|si| &si.descriptor),
+            Some(StreamDescriptor::Http { .. })
+        ) || GENERATE_LOCKS.contains_key(&stream.id)
+        {
+            return;
+        }
+        if let Err(e) = generate(&ctx, &stream).await {
+            warn!(id = %stream.id, "trickplay generation failed: {e:#}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(thumbnail_count: u32) -> api::TrickplayInfo {
+        api::TrickplayInfo {
+            width: 320,
+            height: 180,
+            tile_width: TILE_COLUMNS,
+            tile_height: TILE_ROWS,
+            thumbnail_count,
+            interval: 10_000,
+            bandwidth: 1000,
+        }
+    }
+
+    #[test]
+    fn playlist_lists_one_entry_per_sheet_with_a_short_last_sheet() {
+        let playlist = tiles_playlist(&info(150), "MediaSourceId=abc");
+
+        assert!(playlist.starts_with("#EXTM3U\n#EXT-X-TARGETDURATION:1000\n"));
+        assert!(playlist.contains("#EXT-X-IMAGES-ONLY\n"));
+        assert!(playlist.contains(
+            "#EXTINF:1000.000,\n#EXT-X-TILES:RESOLUTION=320x180,LAYOUT=10x10,DURATION=10.000\n0.jpg?MediaSourceId=abc\n"
+        ));
+        assert!(playlist.contains("#EXTINF:500.000,\n"));
+        assert!(playlist.contains("1.jpg?MediaSourceId=abc\n"));
+        assert!(!playlist.contains("2.jpg"));
+        assert!(playlist.ends_with("#EXT-X-ENDLIST\n"));
+    }
+
+    #[test]
+    fn playlist_without_query_has_bare_sheet_urls() {
+        let playlist = tiles_playlist(&info(3), "");
+        assert!(playlist.contains("\n0.jpg\n"));
+    }
+
+    #[test]
+    fn thumbnail_height_follows_the_aspect_ratio() {
+        assert_eq!(thumbnail_height(320, 1920, 1080), 180);
+        assert_eq!(thumbnail_height(320, 1920, 800), 132);
+        assert_eq!(thumbnail_height(320, 0, 0), 180);
+    }
+
+    #[test]
+    fn thumbnail_count_is_clamped_to_the_written_sheets() {
+        // 95 s at 10 s intervals: 10 thumbnails on one sheet.
+        assert_eq!(thumbnail_count(95_000, 10_000, 1, 100), 10);
+        // Duration says 2 sheets but ffmpeg wrote 3: trust the sheets.
+        assert_eq!(thumbnail_count(1_500_000, 10_000, 3, 100), 201);
+        assert_eq!(thumbnail_count(5_000_000, 10_000, 3, 100), 300);
+        assert_eq!(thumbnail_count(1000, 10_000, 0, 100), 0);
+    }
+
+    /// The probe stored on an audio-only source answers for it, so nothing
+    /// is probed or encoded again.
+    #[tokio::test]
+    async fn an_audio_only_source_is_skipped_on_its_stored_probe() {
+        let (_server, guard) = crate::integration_test::new_test_server()
+            .await
+            .unwrap();
+        let ctx = &guard.0;
+        let stream = db::Media {
+            title: "Track".to_string(),
+            kind: db::MediaKind::Stream,
+            stream_info: Some(crate::stream::StreamInfo {
+                descriptor: StreamDescriptor::Local("missing.flac".into()),
+                ..Default::default()
+            }),
+            probe_data: Some(api::MediaSourceInfo {
+                media_streams: vec![api::MediaStream {
+                    codec: Some("flac".to_string()),
+                    type_: Some(api::MediaStreamType::Audio),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert!(
+            generate(ctx, &stream)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn load_all_reads_every_generated_width() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = Uuid::new_v4();
+        for width in [320, 640] {
+            let sheets = sheet_dir(dir.path(), source, width);
+            std::fs::create_dir_all(&sheets).unwrap();
+            let mut i = info(10);
+            i.width = width;
+            std::fs::write(sheets.join(INFO_FILE), serde_json::to_vec(&i).unwrap())
+                .unwrap();
+        }
+        // An interrupted run leaves only a work dir behind.
+        std::fs::create_dir_all(
+            sheet_dir(dir.path(), source, 160).with_extension("tmp"),
+        )
+        .unwrap();
+
+        let all = load_all(dir.path(), source).await;
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[&640].width, 640);
+        assert!(
+            load_info(dir.path(), Uuid::new_v4(), 320)
+                .await
+                .is_none()
+        );
+    }
+}
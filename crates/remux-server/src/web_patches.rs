@@ -33,7 +33,8 @@ pub static CSS: &str = r##"
   }
 "##;
 
-/// JS injected before `</body>` of every HTML response.
+/// JS injected before `</body>` of every HTML response, after `REMUX_BASE`,
+/// the base URL the page was served under (`""` at the root).
 /// Intercepts React Router (History API) navigation to /wizard and /dashboard
 /// and redirects to our admin UI at /admin.
 pub static JS: &str = r#"
@@ -42,6 +43,7 @@ pub static JS: &str = r#"
   var ADMIN = ['/wizard', '/dashboard'];
 
   function matchesAdmin(p) {
+    if (REMUX_BASE && p.startsWith(REMUX_BASE + '/')) p = p.slice(REMUX_BASE.length);
     for (var i = 0; i < ADMIN.length; i++) {
       var a = ADMIN[i];
       if (p === a || p.startsWith(a + '/') || p.startsWith(a + '?')) return true;
@@ -53,10 +55,10 @@ pub static JS: &str = r#"
   function checkUrl(url) {
     try {
       var u = new URL(String(url), location.href);
-      if (matchesAdmin(u.pathname)) { location.replace('/admin'); return true; }
+      if (matchesAdmin(u.pathname)) { location.replace(REMUX_BASE + '/admin'); return true; }
       if (u.hash) {
         var h = '/' + u.hash.replace(/^#\/?/, '');
-        if (matchesAdmin(h)) { location.replace('/admin'); return true; }
+        if (matchesAdmin(h)) { location.replace(REMUX_BASE + '/admin'); return true; }
       }
     } catch(e) {}
     return false;
@@ -662,6 +664,107 @@ pub static JS: &str = r#"
   processRoot(document.body);
 }());
 
+// OpenID Connect sign-in: finish a redirect from /sso/oidc/callback by trading the
+// one-time code for a session, and offer the SSO button on the login page.
+// The admin dashboard handles its own code, so nothing here runs under /admin.
+(function () {
+  if (/^\/admin(\/|$)/.test(location.pathname.slice(REMUX_BASE.length))) return;
+
+  var PARAM = 'remux_sso';
+  var CREDENTIALS = 'jellyfin_credentials';
+
+  function deviceId() {
+    var id = localStorage.getItem('_deviceId2');
+    if (!id) {
+      id = btoa([navigator.userAgent, Date.now()].join('|')).replace(/=/g, '1');
+      localStorage.setItem('_deviceId2', id);
+    }
+    return id;
+  }
+
+  function storeSession(result) {
+    var creds;
+    try { creds = JSON.parse(localStorage.getItem(CREDENTIALS)) || {}; } catch (e) { creds = {}; }
+    var servers = (creds.Servers || []).filter(function (s) { return s.Id !== result.ServerId; });
+    servers.unshift({
+      Id: result.ServerId,
+      ManualAddress: location.origin + REMUX_BASE,
+      LocalAddress: location.origin + REMUX_BASE,
+      UserId: result.User.Id,
+      AccessToken: result.AccessToken,
+      DateLastAccessed: Date.now(),
+      LastConnectionMode: 2
+    });
+    creds.Servers = servers;
+    localStorage.setItem(CREDENTIALS, JSON.stringify(creds));
+  }
+
+  function withoutCode() {
+    var params = new URLSearchParams(location.search);
+    params.delete(PARAM);
+    var query = params.toString();
+    return location.pathname + (query ? '?' + query : '') + location.hash;
+  }
+
+  var code = new URLSearchParams(location.search).get(PARAM);
+  if (code) {
+    fetch(REMUX_BASE + '/sso/oidc/authenticate', {
+      method: 'POST',
+      headers: {
+        'Content-Type': 'application/json',
+        'Authorization': 'MediaBrowser Client="Jellyfin Web", Device="Web Browser", DeviceId="'
+          + deviceId() + '", Version="10.10.0"'
+      },
+      body: JSON.stringify({ Code: code })
+    }).then(function (res) {
+      if (!res.ok) throw new Error('SSO sign-in failed: ' + res.status);
+      return res.json();
+    }).then(storeSession).catch(function (err) {
+      console.error(err);
+    }).then(function () {
+      location.replace(withoutCode());
+    });
+    return;
+  }
+
+  var info = null;
+
+  function addButton(form) {
+    if (!info || form.querySelector('.remux-sso-button')) return;
+    var button = document.createElement('button');
+    button.type = 'button';
+    button.className = 'raised block emby-button remux-sso-button';
+    button.textContent = info.ButtonLabel;
+    button.addEventListener('click', function () {
+      location.href = REMUX_BASE + '/sso/oidc/start?returnTo=' + encodeURIComponent(location.pathname);
+    });
+    form.appendChild(button);
+  }
+
+  function processRoot(root) {
+    if (!root.querySelectorAll) return;
+    var forms = root.querySelectorAll('.manualLoginForm');
+    for (var i = 0; i < forms.length; i++) addButton(forms[i]);
+    if (root.classList && root.classList.contains('manualLoginForm')) addButton(root);
+  }
+
+  fetch(REMUX_BASE + '/sso/oidc/info').then(function (res) {
+    return res.ok ? res.json() : null;
+  }).then(function (body) {
+    if (!body || !body.Enabled) return;
+    info = body;
+    processRoot(document.body);
+    new MutationObserver(function (mutations) {
+      for (var i = 0; i < mutations.length; i++) {
+        var added = mutations[i].addedNodes;
+        for (var j = 0; j < added.length; j++) {
+          if (added[j].nodeType === 1) processRoot(added[j]);
+        }
+      }
+    }).observe(document.body, { childList: true, subtree: true });
+  }).catch(function () {});
+}());
+
 "#;
 
 #[cfg(test)]
@@ -689,4 +792,14 @@ mod tests {
         assert!(JS.contains("control.click();"));
         assert!(JS.contains("routeHeaderClick"));
     }
+
+    #[test]
+    fn sso_login_leaves_the_admin_dashboard_alone() {
+        assert!(JS.contains(
+            "if (/^\\/admin(\\/|$)/.test(location.pathname.slice(REMUX_BASE.length))) return;"
+        ));
+        assert!(JS.contains("var PARAM = 'remux_sso';"));
+        assert!(JS.contains("fetch(REMUX_BASE + '/sso/oidc/authenticate'"));
+        assert!(JS.contains("REMUX_BASE + '/sso/oidc/start?returnTo='"));
+    }
 }
@@ -13,7 +13,10 @@ use http::Request;
 use http_body_util::BodyExt;
 use tower::{Layer, Service};
 
-use crate::web_patches::{CSS, JS};
+use crate::{
+    base_url,
+    web_patches::{CSS, JS},
+};
 
 const BRANDING_CONFIG_KEY: &str = "branding_configuration";
 
@@ -99,6 +102,7 @@ where
             .uri()
             .path()
             .to_string();
+        let prefix = base_url::prefix(req.headers());
         let cache = self
             .cache
             .clone();
@@ -142,6 +146,16 @@ where
                 .unwrap_or_default();
             let mut html = String::from_utf8_lossy(&bytes).into_owned();
 
+            // The dashboard shell links its assets from the root.
+            if !prefix.is_empty() {
+                for quote in ['"', '\''] {
+                    html = html.replace(
+                        &format!("{quote}/admin/"),
+                        &format!("{quote}{prefix}/admin/"),
+                    );
+                }
+            }
+
             if !CSS.is_empty() {
                 let tag = format!("<style data-remux>{CSS}</style></head>");
                 html = html.replace("</head>", &tag);
@@ -156,7 +170,10 @@ where
                 let extra = user_js
                     .as_deref()
                     .unwrap_or("");
-                let tag = format!("<script data-remux>{JS}{extra}</script></body>");
+                let base = serde_json::Value::from(prefix);
+                let tag = format!(
+                    "<script data-remux>var REMUX_BASE = {base};{JS}{extra}</script></body>"
+                );
                 html = html.replace("</body>", &tag);
             }
 
@@ -13,7 +13,7 @@ use uuid::Uuid;
 
 use crate::{
     AppState, api, api::session::build_session_list, common::get_uuid, db,
-    db::auth::AuthSession,
+    db::auth::AuthSession, syncplay::SyncPlayManager,
 };
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -30,6 +30,8 @@ pub enum SessionMessageType {
     SessionsStart,
     SessionsStop,
     KeepAlive,
+    SyncPlayCommand,
+    SyncPlayGroupUpdate,
     #[serde(other)]
     Unknown,
 }
@@ -91,6 +93,14 @@ pub enum WsEvent {
         device_id: String,
         data: serde_json::Value,
     },
+    SyncPlayCommand {
+        device_ids: Vec<String>,
+        data: api::SendCommand,
+    },
+    SyncPlayGroupUpdate {
+        device_ids: Vec<String>,
+        data: api::GroupUpdate,
+    },
 }
 
 pub async fn ws_handler(
@@ -101,12 +111,32 @@ pub async fn ws_handler(
     ws.on_upgrade(|socket| handle_socket(socket, state, session))
 }
 
+/// Takes the device out of its SyncPlay group however the socket ends, a
+/// failed send included, so the group is not left waiting on it.
+struct LeaveSyncPlay<'a> {
+    syncplay: &'a SyncPlayManager,
+    device_id: &'a str,
+}
+
+impl Drop for LeaveSyncPlay<'_> {
+    fn drop(&mut self) {
+        self.syncplay
+            .disconnect(self.device_id);
+    }
+}
+
 async fn handle_socket(mut socket: WebSocket, state: AppState, session: AuthSession) {
     let my_device_id = session
         .device
         .id
         .clone();
     info!(device_id = %my_device_id, "WS connection opened");
+    let _leave_syncplay = LeaveSyncPlay {
+        syncplay: &state
+            .ctx
+            .syncplay,
+        device_id: &my_device_id,
+    };
     let mut event_rx = state
         .ctx
         .ws_tx
@@ -240,7 +270,22 @@ async fn handle_socket(mut socket: WebSocket, state: AppState, session: AuthSess
                     Ok(WsEvent::RemotePlay { device_id, .. }) => {
                         info!(target = %device_id, me = %my_device_id, "RemotePlay not for this connection");
                     }
-                    Ok(WsEvent::RemotePlaystate { .. } | WsEvent::RemoteCommand { .. }) => {}
+                    Ok(WsEvent::SyncPlayCommand { device_ids, data }) if device_ids.contains(&my_device_id) => {
+                        if !send_msg(&mut socket, SessionMessageType::SyncPlayCommand, Some(data)).await {
+                            return;
+                        }
+                    }
+                    Ok(WsEvent::SyncPlayGroupUpdate { device_ids, data }) if device_ids.contains(&my_device_id) => {
+                        if !send_msg(&mut socket, SessionMessageType::SyncPlayGroupUpdate, Some(data)).await {
+                            return;
+                        }
+                    }
+                    Ok(
+                        WsEvent::RemotePlaystate { .. }
+                        | WsEvent::RemoteCommand { .. }
+                        | WsEvent::SyncPlayCommand { .. }
+                        | WsEvent::SyncPlayGroupUpdate { .. },
+                    ) => {}
                     Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {}
                     Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
                 }
@@ -304,6 +349,30 @@ async fn build_sessions(state: &AppState) -> Vec<api::SessionInfoDto> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn a_closed_socket_leaves_its_syncplay_group() {
+        let (tx, _rx) = tokio::sync::broadcast::channel(16);
+        let syncplay = SyncPlayManager::new(tx);
+        let member = crate::syncplay::Member {
+            device_id: "tv".into(),
+            user_id: Uuid::new_v4(),
+            user_name: "ann".into(),
+        };
+        let group = syncplay
+            .new_group(member, "Movie night".into())
+            .group_id;
+
+        drop(LeaveSyncPlay {
+            syncplay: &syncplay,
+            device_id: "tv",
+        });
+        assert!(
+            syncplay
+                .get(&group)
+                .is_none()
+        );
+    }
+
     #[test]
     fn library_update_info_uses_jellyfin_message_shape() {
         let value = serde_json::to_value(LibraryUpdateInfo {